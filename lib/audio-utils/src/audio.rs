@@ -1,4 +1,7 @@
-use crate::{AudioProcessError, Result};
+use crate::{
+    AudioProcessError, Result,
+    resample::{ResampleQuality, resample},
+};
 
 pub fn mono_to_stereo(audio_data: &[f32]) -> Vec<f32> {
     let mut stereo = Vec::with_capacity(audio_data.len() * 2);
@@ -197,6 +200,7 @@ pub fn normalize_audio(samples: &[f32]) -> Vec<f32> {
 //     Ok(resampled)
 // }
 //
+/// Linear interpolation, use `resample::resample` when quality matters
 pub fn resample_audio(
     input_samples: &[f32],
     input_sample_rate: u32,
@@ -281,11 +285,12 @@ pub fn resample_audio_with_channel(
 
     if current_sample_rate != target_sample_rate {
         log::info!("Resampling audio from {current_sample_rate} Hz to {target_sample_rate} Hz");
-        processed = resample_audio(
+        processed = resample(
             &processed,
             current_sample_rate,
             target_sample_rate,
             target_channels,
+            ResampleQuality::High,
         )?;
     }
 
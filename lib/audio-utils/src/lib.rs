@@ -1,5 +1,8 @@
 pub mod audio;
 pub mod loader;
+pub mod loudness;
+pub mod mixer;
+pub mod resample;
 pub mod vad;
 
 #[cfg(feature = "extraction")]
@@ -25,6 +28,7 @@ impl From<tensor_utils::TensorUtilsError> for AudioProcessError {
     fn from(err: tensor_utils::TensorUtilsError) -> Self {
         match err {
             tensor_utils::TensorUtilsError::InvalidInput(msg) => AudioProcessError::Audio(msg),
+            tensor_utils::TensorUtilsError::Io(e) => AudioProcessError::Io(e),
             tensor_utils::TensorUtilsError::Candle(e) => AudioProcessError::Candle(e),
         }
     }
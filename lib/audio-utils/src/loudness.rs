@@ -0,0 +1,278 @@
+//! ITU-R BS.1770 loudness of interleaved PCM in LUFS: momentary (400ms),
+//! short-term (3s) and gated integrated loudness as in EBU R 128. Unlike
+//! peak or RMS levels it follows the perceived loudness, so meters and
+//! exported files can be compared with targets, e.g. -23 or -14 LUFS.
+
+use std::collections::VecDeque;
+
+// Loudness of a K-weighted mean square of 1.0, so that a 1kHz sine reads
+// its RMS level
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+
+// In LU below the loudness of the blocks above the absolute gate
+const RELATIVE_GATE: f64 = -10.0;
+
+// Blocks are measured every 100ms step
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    // Transposed direct form II
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+// Shelving pre-filter of the head and RLB high pass. The analog prototypes
+// are matched to the 48kHz coefficients of the recommendation, so other
+// sample rates get the same response
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let sample_rate = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+// 5.1 in the order L, R, C, LFE, Ls, Rs leaves out the LFE and weights the
+// surround channels up. Other layouts weight every channel equally
+fn channel_weights(channels: usize) -> Vec<f64> {
+    if channels == 6 {
+        vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+    } else {
+        vec![1.0; channels]
+    }
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    LOUDNESS_OFFSET + 10.0 * mean_square.log10()
+}
+
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    channels: usize,
+    weights: Vec<f64>,
+    filters: Vec<[Biquad; 2]>,
+
+    // Frames of a 100ms step, the weighted sum of squares of the current
+    // step and its frames so far
+    step_frames: usize,
+    step_sum: f64,
+    step_len: usize,
+
+    // Mean squares of the last steps, the newest last
+    steps: VecDeque<f64>,
+
+    // Mean squares of the 400ms blocks above the absolute gate, overlapping
+    // by 75%
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+
+        Self {
+            sample_rate,
+            channels,
+            weights: channel_weights(channels),
+            filters: vec![k_weighting(sample_rate); channels],
+            step_frames: (sample_rate as usize / 10).max(1),
+            step_sum: 0.0,
+            step_len: 0,
+            steps: VecDeque::with_capacity(SHORT_TERM_STEPS),
+            blocks: vec![],
+        }
+    }
+
+    /// Measure interleaved samples, a trailing partial frame is ignored
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for ((sample, filters), weight) in
+                frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let filtered = filters
+                    .iter_mut()
+                    .fold(*sample as f64, |x, filter| filter.process(x));
+                self.step_sum += weight * filtered * filtered;
+            }
+
+            self.step_len += 1;
+            if self.step_len == self.step_frames {
+                self.finish_step();
+            }
+        }
+    }
+
+    fn finish_step(&mut self) {
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps
+            .push_back(self.step_sum / self.step_frames as f64);
+        self.step_sum = 0.0;
+        self.step_len = 0;
+
+        if let Some(block) = self.mean_square(MOMENTARY_STEPS)
+            && to_lufs(block) > ABSOLUTE_GATE
+        {
+            self.blocks.push(block);
+        }
+    }
+
+    // Mean square of the last `steps` steps, `None` before they are measured
+    fn mean_square(&self, steps: usize) -> Option<f64> {
+        if self.steps.len() < steps {
+            return None;
+        }
+
+        Some(self.steps.iter().rev().take(steps).sum::<f64>() / steps as f64)
+    }
+
+    /// Loudness of the last 400ms in LUFS, `None` before 400ms are measured
+    /// and negative infinity for digital silence
+    pub fn momentary(&self) -> Option<f32> {
+        self.mean_square(MOMENTARY_STEPS)
+            .map(|mean_square| to_lufs(mean_square) as f32)
+    }
+
+    /// Loudness of the last 3s in LUFS, `None` before 3s are measured and
+    /// negative infinity for digital silence
+    pub fn short_term(&self) -> Option<f32> {
+        self.mean_square(SHORT_TERM_STEPS)
+            .map(|mean_square| to_lufs(mean_square) as f32)
+    }
+
+    /// Gated loudness of everything measured so far in LUFS. Silence and
+    /// quiet passages are left out, `None` if nothing is loud enough
+    pub fn integrated(&self) -> Option<f32> {
+        if self.blocks.is_empty() {
+            return None;
+        }
+
+        let threshold =
+            to_lufs(self.blocks.iter().sum::<f64>() / self.blocks.len() as f64) + RELATIVE_GATE;
+
+        let (sum, count) = self
+            .blocks
+            .iter()
+            .filter(|block| to_lufs(**block) > threshold)
+            .fold((0.0, 0), |(sum, count), block| (sum + block, count + 1));
+
+        (count > 0).then(|| to_lufs(sum / count as f64) as f32)
+    }
+
+    pub fn reset(&mut self) {
+        for filters in self.filters.iter_mut() {
+            *filters = k_weighting(self.sample_rate);
+        }
+
+        self.step_sum = 0.0;
+        self.step_len = 0;
+        self.steps.clear();
+        self.blocks.clear();
+    }
+}
+
+/// Integrated loudness of a whole interleaved audio in LUFS, e.g. to check
+/// an export against a target
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32, channels: u16) -> Option<f32> {
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.push(samples);
+    meter.integrated()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, channels: u16, secs: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * secs) as usize)
+            .flat_map(|i| {
+                let x = amplitude
+                    * (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin();
+                std::iter::repeat_n(x, channels as usize)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_loudness() {
+        // EBU Tech 3341: a stereo 1kHz sine at -23 dBFS reads -23 LUFS
+        let amplitude = 10f32.powf(-23.0 / 20.0);
+        let samples = sine(1000.0, amplitude, 48_000, 2, 20.0);
+
+        let mut meter = LoudnessMeter::new(48_000, 2);
+        meter.push(&samples);
+        assert!((meter.momentary().unwrap() + 23.0).abs() < 0.1);
+        assert!((meter.short_term().unwrap() + 23.0).abs() < 0.1);
+        assert!((meter.integrated().unwrap() + 23.0).abs() < 0.1);
+
+        // A mono sine reads its RMS level at other sample rates too
+        let samples = sine(1000.0, 0.5, 44_100, 1, 5.0);
+        let loudness = integrated_loudness(&samples, 44_100, 1).unwrap();
+        assert!((loudness + 9.03).abs() < 0.1, "loudness {loudness}");
+    }
+
+    #[test]
+    fn test_gating() {
+        let sample_rate = 16_000;
+        let mut meter = LoudnessMeter::new(sample_rate, 1);
+        assert_eq!(meter.momentary(), None);
+
+        meter.push(&sine(1000.0, 0.1, sample_rate, 1, 10.0));
+        let loud = meter.integrated().unwrap();
+
+        // Silence and a passage 20 LU quieter are gated out
+        meter.push(&vec![0.0; sample_rate as usize * 10]);
+        meter.push(&sine(1000.0, 0.01, sample_rate, 1, 10.0));
+        assert!((meter.integrated().unwrap() - loud).abs() < 0.1);
+        assert!((meter.short_term().unwrap() - (loud - 20.0)).abs() < 0.1);
+
+        meter.reset();
+        meter.push(&vec![0.0; sample_rate as usize]);
+        assert_eq!(meter.momentary(), Some(f32::NEG_INFINITY));
+        assert_eq!(meter.integrated(), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! Mixing of PCM sources of the same sample rate, e.g. microphone, speaker
+//! and music, with per-source gain and panning. Sidechain ducking lowers the
+//! ducked sources, e.g. music, while a sidechain source, e.g. voice, is
+//! active.
+
+use derivative::Derivative;
+use derive_setters::Setters;
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct MixerSource {
+    // Channels of the interleaved input, more than 2 are read as stereo
+    #[derivative(Default(value = "1"))]
+    pub channels: u16,
+
+    #[derivative(Default(value = "1.0"))]
+    pub gain: f32,
+
+    // -1.0 (left) ~ 1.0 (right). The center keeps both sides at full gain
+    pub pan: f32,
+
+    // Its level lowers the ducked sources
+    pub sidechain: bool,
+
+    // Lowered while a sidechain source is active
+    pub ducked: bool,
+
+    // Milliseconds the source is shifted by against the others, negative
+    // values play it earlier. Applied before the samples reach the mixer
+    pub offset_ms: i32,
+}
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct DuckingConfig {
+    // Sidechain level above which the ducked sources are lowered
+    #[derivative(Default(value = "0.02"))]
+    pub threshold: f32,
+
+    // Gain of the ducked sources while the sidechain is active
+    #[derivative(Default(value = "0.3"))]
+    pub duck_gain: f32,
+
+    #[derivative(Default(value = "10"))]
+    pub attack_ms: u32,
+
+    // Kept long so that short pauses between words do not pump the music
+    #[derivative(Default(value = "500"))]
+    pub release_ms: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    sample_rate: u32,
+    channels: u16,
+    sources: Vec<MixerSource>,
+    ducking: Option<DuckingConfig>,
+
+    // Envelope of the sidechain and current gain of the ducked sources
+    sidechain_level: f32,
+    duck_gain: f32,
+}
+
+impl Mixer {
+    /// `channels` of the output, 1 or 2
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.clamp(1, 2),
+            sources: vec![],
+            ducking: None,
+            sidechain_level: 0.0,
+            duck_gain: 1.0,
+        }
+    }
+
+    pub fn with_ducking(mut self, ducking: Option<DuckingConfig>) -> Self {
+        self.ducking = ducking;
+        self
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn set_channels(&mut self, channels: u16) {
+        self.channels = channels.clamp(1, 2);
+    }
+
+    /// Index of the source in the inputs of `mix`
+    pub fn add_source(&mut self, source: MixerSource) -> usize {
+        self.sources.push(source);
+        self.sources.len() - 1
+    }
+
+    /// Change the gain or panning of a source while mixing
+    pub fn source_mut(&mut self, index: usize) -> Option<&mut MixerSource> {
+        self.sources.get_mut(index)
+    }
+
+    /// Current gain of the ducked sources
+    pub fn duck_gain(&self) -> f32 {
+        self.duck_gain
+    }
+
+    /// Mix the interleaved samples of every source in the order they were
+    /// added. Shorter inputs are padded with silence, missing ones are
+    /// silent
+    pub fn mix(&mut self, inputs: &[&[f32]]) -> Vec<f32> {
+        let frames = self
+            .sources
+            .iter()
+            .zip(inputs)
+            .map(|(source, input)| input.len() / source.channels.max(1) as usize)
+            .max()
+            .unwrap_or_default();
+
+        let out_channels = self.channels as usize;
+        let mut output = vec![0.0; frames * out_channels];
+
+        let (attack, release) = match &self.ducking {
+            Some(ducking) => (
+                smoothing_coefficient(ducking.attack_ms, self.sample_rate),
+                smoothing_coefficient(ducking.release_ms, self.sample_rate),
+            ),
+            None => (0.0, 0.0),
+        };
+
+        let mut frames_in = Vec::with_capacity(self.sources.len());
+        for frame in 0..frames {
+            frames_in.clear();
+            frames_in.extend(self.sources.iter().enumerate().map(|(index, source)| {
+                match inputs.get(index) {
+                    Some(input) => source_frame(source, input, frame),
+                    None => (0.0, 0.0),
+                }
+            }));
+
+            if let Some(ducking) = &self.ducking {
+                let level = self
+                    .sources
+                    .iter()
+                    .zip(&frames_in)
+                    .filter(|(source, _)| source.sidechain)
+                    .map(|(source, (left, right))| (left.abs() + right.abs()) / 2.0 * source.gain)
+                    .sum::<f32>();
+
+                let coefficient = if level > self.sidechain_level {
+                    attack
+                } else {
+                    release
+                };
+                self.sidechain_level += (level - self.sidechain_level) * coefficient;
+
+                let target = if self.sidechain_level > ducking.threshold {
+                    ducking.duck_gain
+                } else {
+                    1.0
+                };
+                let coefficient = if target < self.duck_gain {
+                    attack
+                } else {
+                    release
+                };
+                self.duck_gain += (target - self.duck_gain) * coefficient;
+            }
+
+            let out = &mut output[frame * out_channels..(frame + 1) * out_channels];
+            for (source, &(left, right)) in self.sources.iter().zip(&frames_in) {
+                let mut gain = source.gain;
+                if source.ducked {
+                    gain *= self.duck_gain;
+                }
+
+                if out_channels == 1 {
+                    out[0] += (left + right) / 2.0 * gain;
+                } else {
+                    let pan = source.pan.clamp(-1.0, 1.0);
+                    out[0] += left * gain * (1.0 - pan).min(1.0);
+                    out[1] += right * gain * (1.0 + pan).min(1.0);
+                }
+            }
+        }
+
+        for sample in output.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+
+        output
+    }
+}
+
+// Left and right samples of a frame, mono is on both sides
+fn source_frame(source: &MixerSource, input: &[f32], frame: usize) -> (f32, f32) {
+    let channels = source.channels.max(1) as usize;
+    let index = frame * channels;
+
+    match input.get(index..index + channels) {
+        Some([mono]) => (*mono, *mono),
+        Some([left, right, ..]) => (*left, *right),
+        _ => (0.0, 0.0),
+    }
+}
+
+// Per sample step of an exponential smoothing reaching 63% in `ms`
+fn smoothing_coefficient(ms: u32, sample_rate: u32) -> f32 {
+    let samples = ms as f32 * sample_rate as f32 / 1000.0;
+    if samples < 1.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / samples).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mix_gain_and_pan() {
+        let mut mixer = Mixer::new(16_000, 2);
+        mixer.add_source(MixerSource::default().with_gain(0.5));
+        mixer.add_source(MixerSource::default().with_channels(2).with_pan(1.0));
+
+        let mono = [0.4, 0.4];
+        let stereo = [0.2, 0.2, 0.2, 0.2, 0.2, 0.2];
+        let output = mixer.mix(&[&mono[..], &stereo[..]]);
+
+        // The shorter input is padded with silence
+        assert_eq!(output.len(), 6);
+        assert!((output[0] - 0.2).abs() < 1e-6);
+        assert!((output[1] - 0.4).abs() < 1e-6);
+        assert!((output[4] - 0.0).abs() < 1e-6);
+        assert!((output[5] - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ducking() {
+        let sample_rate = 16_000;
+        let mut mixer = Mixer::new(sample_rate, 1).with_ducking(Some(DuckingConfig::default()));
+        let voice = mixer.add_source(MixerSource::default().with_sidechain(true));
+        let music = mixer.add_source(MixerSource::default().with_ducked(true));
+        assert_eq!((voice, music), (0, 1));
+
+        let music_samples = vec![0.1; sample_rate as usize / 2];
+        let silence = vec![0.0; sample_rate as usize / 2];
+        mixer.mix(&[&silence[..], &music_samples[..]]);
+        assert!((mixer.duck_gain() - 1.0).abs() < 1e-6);
+
+        let voice_samples = vec![0.5; sample_rate as usize / 2];
+        let output = mixer.mix(&[&voice_samples[..], &music_samples[..]]);
+        assert!((mixer.duck_gain() - 0.3).abs() < 0.01);
+        assert!((output.last().unwrap() - (0.5 + 0.1 * 0.3)).abs() < 0.01);
+
+        // Released after the voice stops
+        for _ in 0..8 {
+            mixer.mix(&[&silence[..], &music_samples[..]]);
+        }
+        assert!(mixer.duck_gain() > 0.95);
+    }
+}
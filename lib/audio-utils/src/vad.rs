@@ -33,104 +33,266 @@ pub struct VadConfig {
     pub window_size_ms: u32,
 }
 
-pub fn detect_speech_segments(audio_data: &[f32], config: &VadConfig) -> Vec<AudioSegment> {
-    if audio_data.is_empty() {
-        return Vec::new();
+impl VadConfig {
+    /// Set the thresholds of a preset
+    pub fn with_aggressiveness(mut self, aggressiveness: VadAggressiveness) -> Self {
+        let (speech_threshold, min_speech_duration_ms, min_silence_duration_ms) =
+            match aggressiveness {
+                VadAggressiveness::Quality => (0.005, 150, 300),
+                VadAggressiveness::Normal => (0.01, 250, 200),
+                VadAggressiveness::Aggressive => (0.03, 300, 150),
+                VadAggressiveness::VeryAggressive => (0.06, 400, 100),
+            };
+
+        self.speech_threshold = speech_threshold;
+        self.min_speech_duration_ms = min_speech_duration_ms;
+        self.min_silence_duration_ms = min_silence_duration_ms;
+        self
     }
+}
 
-    let window_size = (config.sample_rate as usize * config.window_size_ms as usize) / 1000;
-    let hop_size = window_size / 2; // 50% overlap
-    let min_speech_samples =
-        (config.sample_rate as usize * config.min_speech_duration_ms as usize) / 1000;
-    let min_silence_samples =
-        (config.sample_rate as usize * config.min_silence_duration_ms as usize) / 1000;
+/// How eagerly audio is classified as non-speech. More aggressive presets
+/// drop quiet speech and cut pauses sooner
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VadAggressiveness {
+    /// Keep quiet and short speech
+    Quality,
 
-    let mut energies = Vec::new();
+    /// `VadConfig::default()`
+    #[default]
+    Normal,
 
-    for i in (0..audio_data.len().saturating_sub(window_size)).step_by(hop_size) {
-        let window_energy: f32 = audio_data[i..i + window_size]
-            .iter()
-            .map(|&x| x * x)
-            .sum::<f32>()
-            / window_size as f32;
+    Aggressive,
+    VeryAggressive,
+}
+
+#[derive(Debug, Clone)]
+pub enum VadEvent {
+    /// Speech lasted `min_speech_duration_ms` from this sample, a
+    /// `SpeechEnd` follows
+    SpeechStart(usize),
 
-        energies.push((i, window_energy));
+    SpeechEnd(AudioSegment),
+}
+
+// Per window decay of the energy peak of a live stream, about halves
+// every 10s of 15ms hops so a loud cough does not mute the rest
+const PEAK_ENERGY_DECAY: f32 = 0.999;
+
+// Windows quieter than this are silence whatever the peak is
+const MIN_ENERGY: f32 = 1e-6;
+
+/// Voice activity detection of audio pushed in chunks. The energy of each
+/// window is compared with the peak energy so far
+pub struct StreamingVad {
+    config: VadConfig,
+    window_size: usize,
+    hop_size: usize,
+    min_speech_samples: usize,
+    min_silence_samples: usize,
+
+    // Fixed peak of a whole audio, otherwise the decaying peak of the
+    // stream
+    reference_energy: Option<f32>,
+    peak_energy: f32,
+
+    // Samples from `buffer_start` on, earlier ones are no longer needed
+    buffer: Vec<f32>,
+    buffer_start: usize,
+    received_samples: usize,
+
+    // Start of the next window
+    window_pos: usize,
+
+    in_speech: bool,
+    speech_confirmed: bool,
+    speech_start: usize,
+    silence_start: Option<usize>,
+}
+
+impl StreamingVad {
+    pub fn new(config: VadConfig) -> Self {
+        let window_size =
+            ((config.sample_rate as usize * config.window_size_ms as usize) / 1000).max(2);
+
+        Self {
+            window_size,
+            hop_size: window_size / 2, // 50% overlap
+            min_speech_samples: (config.sample_rate as usize
+                * config.min_speech_duration_ms as usize)
+                / 1000,
+            min_silence_samples: (config.sample_rate as usize
+                * config.min_silence_duration_ms as usize)
+                / 1000,
+            config,
+            reference_energy: None,
+            peak_energy: 0.0,
+            buffer: vec![],
+            buffer_start: 0,
+            received_samples: 0,
+            window_pos: 0,
+            in_speech: false,
+            speech_confirmed: false,
+            speech_start: 0,
+            silence_start: None,
+        }
     }
 
-    if energies.is_empty() {
-        return Vec::new();
+    pub fn config(&self) -> &VadConfig {
+        &self.config
     }
 
-    // Find max energy for normalization
-    let max_energy = energies
-        .iter()
-        .map(|&(_, e)| e)
-        .fold(0.0f32, |a, b| a.max(b));
+    pub fn is_speech(&self) -> bool {
+        self.in_speech && self.speech_confirmed
+    }
 
-    // Audio is too quiet
-    if max_energy < 1e-6 {
-        return Vec::new();
+    /// Push mono samples, returns the events of the windows they complete
+    pub fn push(&mut self, samples: &[f32]) -> Vec<VadEvent> {
+        self.buffer.extend_from_slice(samples);
+        self.received_samples += samples.len();
+
+        let mut events = vec![];
+
+        // The window ending at the last received sample waits for more
+        // audio, as `detect_speech_segments` never reads it
+        while self.window_pos + self.window_size < self.received_samples {
+            let offset = self.window_pos - self.buffer_start;
+            let energy = window_energy(&self.buffer[offset..offset + self.window_size]);
+
+            self.process_window(self.window_pos, energy, &mut events);
+            self.window_pos += self.hop_size;
+        }
+
+        let keep_from = if self.in_speech {
+            self.speech_start.min(self.window_pos)
+        } else {
+            self.window_pos
+        };
+        self.buffer.drain(..keep_from - self.buffer_start);
+        self.buffer_start = keep_from;
+
+        events
     }
 
-    // Detect speech based on threshold
-    let mut in_speech = false;
-    let mut speech_start = 0;
-    let mut silence_start = 0;
-    let mut segments = Vec::new();
-
-    for &(window_pos, energy) in &energies {
-        let normalized_energy = energy / (max_energy + 1e-6);
-        let is_speech = normalized_energy > config.speech_threshold;
-
-        if is_speech && !in_speech {
-            // Start of speech segment
-            in_speech = true;
-            speech_start = window_pos;
-        } else if !is_speech && in_speech {
-            // Potential end of speech segment (silence detected)
-            if silence_start == 0 {
-                silence_start = window_pos;
+    /// End the stream, the ongoing speech becomes the last segment. The VAD
+    /// is ready for a new stream afterwards
+    pub fn finish(&mut self) -> Vec<VadEvent> {
+        let mut events = vec![];
+
+        if self.in_speech && self.received_samples - self.speech_start >= self.min_speech_samples {
+            if !self.speech_confirmed {
+                events.push(VadEvent::SpeechStart(self.speech_start));
             }
 
-            let silence_duration = window_pos - silence_start;
-            if silence_duration >= min_silence_samples {
-                // End of speech segment
-                let speech_duration = window_pos - speech_start;
+            events.push(VadEvent::SpeechEnd(AudioSegment {
+                start_sample: self.speech_start,
+                end_sample: self.received_samples,
+                audio_data: self.buffer[self.speech_start - self.buffer_start..].to_vec(),
+            }));
+        }
 
-                if speech_duration >= min_speech_samples {
-                    let end_sample = window_pos;
-                    let segment_audio = audio_data[speech_start..end_sample].to_vec();
+        self.reset();
+        events
+    }
+
+    pub fn reset(&mut self) {
+        self.peak_energy = 0.0;
+        self.buffer.clear();
+        self.buffer_start = 0;
+        self.received_samples = 0;
+        self.window_pos = 0;
+        self.in_speech = false;
+        self.speech_confirmed = false;
+        self.speech_start = 0;
+        self.silence_start = None;
+    }
 
-                    segments.push(AudioSegment {
-                        start_sample: speech_start,
-                        end_sample: end_sample,
-                        audio_data: segment_audio,
-                    });
+    fn process_window(&mut self, window_pos: usize, energy: f32, events: &mut Vec<VadEvent>) {
+        let reference = match self.reference_energy {
+            Some(reference) => reference,
+            None => {
+                self.peak_energy = energy.max(self.peak_energy * PEAK_ENERGY_DECAY);
+                self.peak_energy
+            }
+        };
+
+        let normalized_energy = energy / (reference + 1e-6);
+        let is_speech = reference >= MIN_ENERGY && normalized_energy > self.config.speech_threshold;
+
+        if is_speech && !self.in_speech {
+            self.in_speech = true;
+            self.speech_confirmed = false;
+            self.speech_start = window_pos;
+        } else if !is_speech && self.in_speech {
+            let silence_start = *self.silence_start.get_or_insert(window_pos);
+
+            if window_pos - silence_start >= self.min_silence_samples {
+                if window_pos - self.speech_start >= self.min_speech_samples {
+                    if !self.speech_confirmed {
+                        events.push(VadEvent::SpeechStart(self.speech_start));
+                    }
+
+                    let start = self.speech_start - self.buffer_start;
+                    let end = window_pos - self.buffer_start;
+                    events.push(VadEvent::SpeechEnd(AudioSegment {
+                        start_sample: self.speech_start,
+                        end_sample: window_pos,
+                        audio_data: self.buffer[start..end].to_vec(),
+                    }));
                 }
 
-                in_speech = false;
-                silence_start = 0;
+                self.in_speech = false;
+                self.silence_start = None;
+                return;
             }
-        } else if is_speech && in_speech {
-            // Still in speech, reset silence timer
-            silence_start = 0;
+        } else if is_speech && self.in_speech {
+            self.silence_start = None;
+        }
+
+        if self.in_speech
+            && !self.speech_confirmed
+            && window_pos - self.speech_start >= self.min_speech_samples
+        {
+            self.speech_confirmed = true;
+            events.push(VadEvent::SpeechStart(self.speech_start));
         }
     }
+}
 
-    // Handle last segment if it ends with speech
-    if in_speech {
-        let speech_duration = audio_data.len() - speech_start;
+fn window_energy(window: &[f32]) -> f32 {
+    window.iter().map(|&x| x * x).sum::<f32>() / window.len() as f32
+}
 
-        if speech_duration >= min_speech_samples {
-            let segment_audio = audio_data[speech_start..].to_vec();
+pub fn detect_speech_segments(audio_data: &[f32], config: &VadConfig) -> Vec<AudioSegment> {
+    if audio_data.is_empty() {
+        return Vec::new();
+    }
 
-            segments.push(AudioSegment {
-                start_sample: speech_start,
-                end_sample: audio_data.len(),
-                audio_data: segment_audio,
-            });
-        }
+    let mut vad = StreamingVad::new(config.clone());
+
+    // The energies are normalized by the peak of the whole audio
+    let window_size = vad.window_size;
+    let max_energy = (0..audio_data.len().saturating_sub(window_size))
+        .step_by(vad.hop_size)
+        .map(|i| window_energy(&audio_data[i..i + window_size]))
+        .fold(0.0f32, |a, b| a.max(b));
+
+    // Audio is too quiet
+    if max_energy < MIN_ENERGY {
+        return Vec::new();
     }
+    vad.reference_energy = Some(max_energy);
+
+    let mut events = vad.push(audio_data);
+    events.extend(vad.finish());
+
+    let segments = events
+        .into_iter()
+        .filter_map(|event| match event {
+            VadEvent::SpeechEnd(segment) => Some(segment),
+            VadEvent::SpeechStart(_) => None,
+        })
+        .collect::<Vec<_>>();
 
     // Merge very close segments (less than min_silence_duration_ms apart)
     if segments.len() > 1 {
@@ -208,4 +370,52 @@ mod tests {
             segments[1].end_sample * 1000 / 16000
         );
     }
+
+    #[test]
+    fn test_streaming_vad() {
+        let sample_rate = 16000;
+        let config = VadConfig::default()
+            .with_sample_rate(sample_rate)
+            .with_min_speech_duration_ms(100)
+            .with_min_silence_duration_ms(100);
+
+        // Speech - silence - speech - silence
+        let mut audio = vec![];
+        audio.extend(std::iter::repeat_n(0.1, sample_rate as usize / 2));
+        audio.extend(std::iter::repeat_n(0.001, sample_rate as usize * 3 / 10));
+        audio.extend(std::iter::repeat_n(0.1, sample_rate as usize / 2));
+        audio.extend(std::iter::repeat_n(0.001, sample_rate as usize * 3 / 10));
+
+        let mut vad = StreamingVad::new(config.clone());
+        let mut events = vec![];
+        for chunk in audio.chunks(320) {
+            events.extend(vad.push(chunk));
+        }
+        events.extend(vad.finish());
+
+        let starts = events
+            .iter()
+            .filter_map(|event| match event {
+                VadEvent::SpeechStart(start) => Some(*start),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let segments = events
+            .into_iter()
+            .filter_map(|event| match event {
+                VadEvent::SpeechEnd(segment) => Some(segment),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let expected = detect_speech_segments(&audio, &config);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments.len(), expected.len());
+        for ((segment, expected), start) in segments.iter().zip(&expected).zip(starts) {
+            assert_eq!(segment.start_sample, start);
+            assert_eq!(segment.start_sample, expected.start_sample);
+            assert_eq!(segment.end_sample, expected.end_sample);
+            assert_eq!(segment.audio_data.len(), expected.audio_data.len());
+        }
+    }
 }
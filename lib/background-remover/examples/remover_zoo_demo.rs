@@ -0,0 +1,43 @@
+use anyhow::Result;
+use background_remover::{ModelQuality, ModelZoo};
+use std::{fs, path::PathBuf, time::Instant};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let input_file = "./examples/test-rgb.png";
+    let output_dir = PathBuf::from("./output");
+    if !output_dir.exists() {
+        fs::create_dir(&output_dir)?;
+    }
+
+    let img = image::open(input_file)?.to_rgb8();
+    let zoo = ModelZoo::new("./models");
+
+    for quality in [
+        ModelQuality::Fast,
+        ModelQuality::Balanced,
+        ModelQuality::Best,
+    ] {
+        let mut remover = zoo
+            .remover(quality, |downloaded, total, progress| {
+                log::debug!("{downloaded}/{total} {:.1}%", progress * 100.0);
+            })
+            .await?;
+        let model_name = remover.model().to_filename().trim_end_matches(".onnx");
+
+        let inference_start = Instant::now();
+        let result = remover.remove(&img)?;
+        log::info!(
+            "{quality:?} {model_name} spent: {:?}",
+            inference_start.elapsed()
+        );
+
+        let output_path = output_dir.join(format!("zoo_{model_name}.png"));
+        result.save(&output_path)?;
+        log::info!("Saving result to: {:?}", output_path);
+    }
+
+    Ok(())
+}
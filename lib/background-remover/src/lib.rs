@@ -1,8 +1,14 @@
 pub mod model;
+pub mod refine;
 pub mod remover;
+pub mod temporal;
+pub mod zoo;
 
-pub use model::Model;
+pub use model::{Model, ModelQuality};
+pub use refine::RefineConfig;
 pub use remover::BackgroundRemover;
+pub use temporal::{TemporalConfig, TemporalMatting};
+pub use zoo::ModelZoo;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -35,6 +41,19 @@ pub enum Error {
     #[error("Image buffer error: {0}")]
     ImageBufferError(#[from] fast_image_resize::ImageBufferError),
 
+    #[error("Download error: {0}")]
+    Download(#[from] downloader::DownloadError),
+
+    #[error("Download failed: {0}")]
+    DownloadFailed(String),
+
+    #[error("Checksum mismatch of {path}: expected {expected}, actual {actual}")]
+    ChecksumMismatch {
+        path: std::path::PathBuf,
+        expected: String,
+        actual: String,
+    },
+
     #[error("{0}")]
     Generic(String),
 }
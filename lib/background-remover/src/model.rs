@@ -1,24 +1,83 @@
 const RMBG14_FILENAME: &str = "rmbg-1.4.onnx";
+const RMBG14_QUANTIZED_FILENAME: &str = "rmbg-1.4-quantized.onnx";
 const MODNET_FILENAME: &str = "modnet_photographic_portrait_matting.onnx";
+const U2NET_FILENAME: &str = "u2net.onnx";
+const U2NETP_FILENAME: &str = "u2netp.onnx";
+const U2NET_HUMAN_SEG_FILENAME: &str = "u2net_human_seg.onnx";
 
 const RMBG14_URL: &str = "https://huggingface.co/briaai/RMBG-1.4/resolve/main/onnx/model.onnx";
+const RMBG14_QUANTIZED_URL: &str =
+    "https://huggingface.co/briaai/RMBG-1.4/resolve/main/onnx/model_quantized.onnx";
 const MODNET_URL: &str = "https://huggingface.co/TheEeeeLin/HivisionIDPhotos_matting/resolve/034769305faf641ad94edfac654aba13be06e816/modnet_photographic_portrait_matting.onnx";
+const U2NET_URL: &str = "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2net.onnx";
+const U2NETP_URL: &str =
+    "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2netp.onnx";
+const U2NET_HUMAN_SEG_URL: &str =
+    "https://github.com/danielgatis/rembg/releases/download/v0.0.0/u2net_human_seg.onnx";
 
-#[derive(Clone, Copy, Debug)]
+// ImageNet statistics the U2-Net models are trained with
+const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelQuality {
+    Fast,
+    Balanced,
+    Best,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Model {
     Modnet,
     Rmbg14,
+    Rmbg14Quantized,
+    U2net,
+    U2netp,
+    U2netHumanSeg,
 }
 
 impl Model {
     pub fn all_models() -> Vec<Self> {
-        vec![Self::Modnet, Self::Rmbg14]
+        vec![
+            Self::Modnet,
+            Self::Rmbg14,
+            Self::Rmbg14Quantized,
+            Self::U2net,
+            Self::U2netp,
+            Self::U2netHumanSeg,
+        ]
+    }
+
+    // The model picked for a quality/speed tradeoff
+    pub fn from_quality(quality: ModelQuality) -> Self {
+        match quality {
+            ModelQuality::Fast => Self::U2netp,
+            ModelQuality::Balanced => Self::Modnet,
+            ModelQuality::Best => Self::Rmbg14,
+        }
+    }
+
+    pub fn quality(&self) -> ModelQuality {
+        match self {
+            Self::U2netp => ModelQuality::Fast,
+            Self::Modnet | Self::Rmbg14Quantized | Self::U2netHumanSeg => ModelQuality::Balanced,
+            Self::Rmbg14 | Self::U2net => ModelQuality::Best,
+        }
     }
 
     pub fn to_input_size(&self) -> (u32, u32) {
         match self {
             Model::Modnet => (512, 512),
-            Model::Rmbg14 => (1024, 1024),
+            Model::Rmbg14 | Model::Rmbg14Quantized => (1024, 1024),
+            Model::U2net | Model::U2netp | Model::U2netHumanSeg => (320, 320),
+        }
+    }
+
+    // Per channel (mean, std) applied to the pixels scaled to [0, 1]
+    pub fn normalization(&self) -> ([f32; 3], [f32; 3]) {
+        match self {
+            Model::Modnet | Model::Rmbg14 | Model::Rmbg14Quantized => ([0.0; 3], [1.0; 3]),
+            Model::U2net | Model::U2netp | Model::U2netHumanSeg => (IMAGENET_MEAN, IMAGENET_STD),
         }
     }
 
@@ -26,29 +85,33 @@ impl Model {
         match self {
             Self::Modnet => MODNET_FILENAME,
             Self::Rmbg14 => RMBG14_FILENAME,
+            Self::Rmbg14Quantized => RMBG14_QUANTIZED_FILENAME,
+            Self::U2net => U2NET_FILENAME,
+            Self::U2netp => U2NETP_FILENAME,
+            Self::U2netHumanSeg => U2NET_HUMAN_SEG_FILENAME,
         }
     }
 
     pub fn try_from_filename(model: &str) -> Option<Self> {
-        match model {
-            MODNET_FILENAME => Some(Model::Modnet),
-            RMBG14_FILENAME => Some(Model::Rmbg14),
-            _ => None,
-        }
+        Self::all_models()
+            .into_iter()
+            .find(|m| m.to_filename() == model)
     }
 
     pub fn try_from_url(url: &str) -> Option<Self> {
-        match url {
-            MODNET_URL => Some(Model::Modnet),
-            RMBG14_URL => Some(Model::Rmbg14),
-            _ => None,
-        }
+        Self::all_models()
+            .into_iter()
+            .find(|m| m.download_url() == url)
     }
 
     pub fn download_url(&self) -> &'static str {
         match self {
             Self::Modnet => MODNET_URL,
             Self::Rmbg14 => RMBG14_URL,
+            Self::Rmbg14Quantized => RMBG14_QUANTIZED_URL,
+            Self::U2net => U2NET_URL,
+            Self::U2netp => U2NETP_URL,
+            Self::U2netHumanSeg => U2NET_HUMAN_SEG_URL,
         }
     }
 }
@@ -0,0 +1,314 @@
+// Post-processing of the model mask for still images. The mask edge is split
+// into a trimap, the alpha of the unknown band between the sure foreground
+// and background is estimated from the local foreground and background colors
+// and smoothed by a guided filter on the image so hair and fur follow the
+// image edges. Then the alpha edge is feathered and the background color
+// bleeding into the semi-transparent pixels is removed
+
+use derive_setters::Setters;
+use image::{GrayImage, RgbImage, Rgba, RgbaImage};
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct RefineConfig {
+    // Half width of the unknown band of the trimap around the mask edge,
+    // 0 disables the alpha matting
+    pub trimap_radius: u32,
+
+    // Window radius and regularization of the guided filter smoothing the
+    // unknown band. A smaller eps follows the image edges more closely
+    pub guided_radius: u32,
+    pub guided_eps: f32,
+
+    // Blur radius of the alpha edge, 0 disables feathering
+    pub feather_radius: u32,
+
+    // Estimate the foreground color of the semi-transparent pixels so the
+    // old background doesn't show as a halo
+    pub despill: bool,
+
+    // Window radius the local background color is estimated in by despill
+    pub despill_radius: u32,
+}
+
+impl Default for RefineConfig {
+    fn default() -> Self {
+        Self {
+            trimap_radius: 0,
+            guided_radius: 8,
+            guided_eps: 1e-3,
+            feather_radius: 0,
+            despill: false,
+            despill_radius: 8,
+        }
+    }
+}
+
+impl RefineConfig {
+    // Settings for product photos and portraits with fine hair edges
+    pub fn high_quality() -> Self {
+        Self {
+            trimap_radius: 10,
+            feather_radius: 1,
+            despill: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.trimap_radius > 0 || self.feather_radius > 0 || self.despill
+    }
+}
+
+// Refined alpha of `mask` (0 = background, 255 = foreground)
+pub fn refine_mask(image: &RgbImage, mask: &GrayImage, config: &RefineConfig) -> GrayImage {
+    if image.dimensions() != mask.dimensions() {
+        log::warn!(
+            "refine mask size {:?} doesn't match image size {:?}",
+            mask.dimensions(),
+            image.dimensions()
+        );
+        return mask.clone();
+    }
+
+    let (width, height) = mask.dimensions();
+    let mut alpha = mask.iter().map(|v| *v as f32 / 255.0).collect::<Vec<_>>();
+
+    if config.trimap_radius > 0 {
+        let trimap = trimap(&alpha, width, height, config.trimap_radius);
+        let estimate = color_alpha(image, &alpha, &trimap, config.trimap_radius * 2);
+        let guide = image
+            .pixels()
+            .map(|p| (p[0] as f32 * 0.299 + p[1] as f32 * 0.587 + p[2] as f32 * 0.114) / 255.0)
+            .collect::<Vec<_>>();
+        let matte = guided_filter(
+            &guide,
+            &estimate,
+            width,
+            height,
+            config.guided_radius,
+            config.guided_eps,
+        );
+
+        for ((a, t), m) in alpha.iter_mut().zip(trimap).zip(matte) {
+            *a = match t {
+                Trimap::Foreground => 1.0,
+                Trimap::Background => 0.0,
+                Trimap::Unknown => m.clamp(0.0, 1.0),
+            };
+        }
+    }
+
+    if config.feather_radius > 0 {
+        // Two box passes are close to a gaussian
+        alpha = box_filter(&alpha, width, height, config.feather_radius);
+        alpha = box_filter(&alpha, width, height, config.feather_radius);
+    }
+
+    let data = alpha
+        .into_iter()
+        .map(|a| (a.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect::<Vec<_>>();
+    GrayImage::from_raw(width, height, data).unwrap_or_else(|| mask.clone())
+}
+
+// The image with the alpha of `mask`, the colors of the semi-transparent
+// pixels are the foreground estimated as I = αF + (1 - α)B with B the local
+// mean of the background
+pub fn despill(image: &RgbImage, mask: &GrayImage, radius: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let alpha = mask.iter().map(|v| *v as f32 / 255.0).collect::<Vec<_>>();
+    let bg_weight = alpha.iter().map(|a| 1.0 - a).collect::<Vec<_>>();
+    let bg_weight_mean = box_filter(&bg_weight, width, height, radius);
+
+    let background = (0..3)
+        .map(|c| {
+            let weighted = image
+                .pixels()
+                .zip(&bg_weight)
+                .map(|(p, w)| p[c] as f32 * w)
+                .collect::<Vec<_>>();
+            box_filter(&weighted, width, height, radius)
+                .into_iter()
+                .zip(&bg_weight_mean)
+                .map(|(sum, w)| if *w > 1e-3 { sum / w } else { 0.0 })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut result = RgbaImage::new(width, height);
+    for (index, ((out, pixel), a)) in result
+        .pixels_mut()
+        .zip(image.pixels())
+        .zip(mask.iter())
+        .enumerate()
+    {
+        let alpha = *a as f32 / 255.0;
+        let mut color = [pixel[0], pixel[1], pixel[2]];
+        if alpha > 0.02 && alpha < 0.98 && bg_weight_mean[index] > 1e-3 {
+            for (c, value) in color.iter_mut().enumerate() {
+                let fg = (pixel[c] as f32 - (1.0 - alpha) * background[c][index]) / alpha;
+                *value = fg.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        *out = Rgba([color[0], color[1], color[2], *a]);
+    }
+
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Trimap {
+    Foreground,
+    Background,
+    Unknown,
+}
+
+// Pixels farther than `radius` from the mask edge are known
+fn trimap(alpha: &[f32], width: u32, height: u32, radius: u32) -> Vec<Trimap> {
+    let binary = alpha
+        .iter()
+        .map(|a| if *a >= 0.5 { 1.0 } else { 0.0 })
+        .collect::<Vec<_>>();
+
+    box_filter(&binary, width, height, radius)
+        .into_iter()
+        .map(|mean| {
+            if mean >= 1.0 - 1e-4 {
+                Trimap::Foreground
+            } else if mean <= 1e-4 {
+                Trimap::Background
+            } else {
+                Trimap::Unknown
+            }
+        })
+        .collect()
+}
+
+// Alpha of the unknown pixels as the projection of their color on the line
+// between the mean colors of the known foreground and background around
+// them. The known pixels keep their trimap value, the unknown ones without
+// known pixels in the window keep the mask value
+fn color_alpha(image: &RgbImage, alpha: &[f32], trimap: &[Trimap], radius: u32) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    let local_mean = |region: Trimap| -> (Vec<f32>, Vec<Vec<f32>>) {
+        let weight = trimap
+            .iter()
+            .map(|t| if *t == region { 1.0 } else { 0.0 })
+            .collect::<Vec<f32>>();
+        let weight_mean = box_filter(&weight, width, height, radius);
+        let colors = (0..3)
+            .map(|c| {
+                let weighted = image
+                    .pixels()
+                    .zip(&weight)
+                    .map(|(p, w)| p[c] as f32 * w)
+                    .collect::<Vec<_>>();
+                box_filter(&weighted, width, height, radius)
+                    .into_iter()
+                    .zip(&weight_mean)
+                    .map(|(sum, w)| if *w > 0.0 { sum / w } else { 0.0 })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        (weight_mean, colors)
+    };
+
+    let (fg_weight, fg) = local_mean(Trimap::Foreground);
+    let (bg_weight, bg) = local_mean(Trimap::Background);
+
+    image
+        .pixels()
+        .enumerate()
+        .map(|(index, pixel)| match trimap[index] {
+            Trimap::Foreground => 1.0,
+            Trimap::Background => 0.0,
+            Trimap::Unknown if fg_weight[index] > 0.0 && bg_weight[index] > 0.0 => {
+                let (mut dot, mut norm) = (0.0, 0.0);
+                for c in 0..3 {
+                    let fb = fg[c][index] - bg[c][index];
+                    dot += (pixel[c] as f32 - bg[c][index]) * fb;
+                    norm += fb * fb;
+                }
+                if norm > 1.0 {
+                    (dot / norm).clamp(0.0, 1.0)
+                } else {
+                    alpha[index]
+                }
+            }
+            Trimap::Unknown => alpha[index],
+        })
+        .collect()
+}
+
+// Guided filter of He et al. with a gray guide: the output is locally a
+// linear function of the guide, so it takes its edges
+fn guided_filter(
+    guide: &[f32],
+    input: &[f32],
+    width: u32,
+    height: u32,
+    radius: u32,
+    eps: f32,
+) -> Vec<f32> {
+    let mean_i = box_filter(guide, width, height, radius);
+    let mean_p = box_filter(input, width, height, radius);
+    let ip = guide
+        .iter()
+        .zip(input)
+        .map(|(i, p)| i * p)
+        .collect::<Vec<_>>();
+    let ii = guide.iter().map(|i| i * i).collect::<Vec<_>>();
+    let corr_ip = box_filter(&ip, width, height, radius);
+    let corr_ii = box_filter(&ii, width, height, radius);
+
+    let mut a = Vec::with_capacity(guide.len());
+    let mut b = Vec::with_capacity(guide.len());
+    for (((mean_i, mean_p), corr_ip), corr_ii) in
+        mean_i.iter().zip(&mean_p).zip(&corr_ip).zip(&corr_ii)
+    {
+        let var_i = corr_ii - mean_i * mean_i;
+        let cov_ip = corr_ip - mean_i * mean_p;
+        let a_k = cov_ip / (var_i + eps);
+        a.push(a_k);
+        b.push(mean_p - a_k * mean_i);
+    }
+
+    let mean_a = box_filter(&a, width, height, radius);
+    let mean_b = box_filter(&b, width, height, radius);
+    guide
+        .iter()
+        .zip(mean_a.iter().zip(&mean_b))
+        .map(|(i, (a, b))| a * i + b)
+        .collect()
+}
+
+// Mean over the (2 * radius + 1)^2 window clipped to the image, by a summed
+// area table so it doesn't depend on the radius
+fn box_filter(data: &[f32], width: u32, height: u32, radius: u32) -> Vec<f32> {
+    let (w, h, r) = (width as usize, height as usize, radius as usize);
+    let mut integral = vec![0f64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row_sum = 0f64;
+        for x in 0..w {
+            row_sum += data[y * w + x] as f64;
+            integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row_sum;
+        }
+    }
+
+    let mut output = vec![0f32; w * h];
+    for y in 0..h {
+        let (y0, y1) = (y.saturating_sub(r), (y + r + 1).min(h));
+        for x in 0..w {
+            let (x0, x1) = (x.saturating_sub(r), (x + r + 1).min(w));
+            let sum = integral[y1 * (w + 1) + x1]
+                - integral[y0 * (w + 1) + x1]
+                - integral[y1 * (w + 1) + x0]
+                + integral[y0 * (w + 1) + x0];
+            output[y * w + x] = (sum / ((y1 - y0) * (x1 - x0)) as f64) as f32;
+        }
+    }
+
+    output
+}
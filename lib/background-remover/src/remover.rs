@@ -1,4 +1,8 @@
-use crate::{Error, Model, Result};
+use crate::{
+    Error, Model, Result,
+    refine::{self, RefineConfig},
+    temporal::{TemporalConfig, TemporalMatting},
+};
 use fast_image_resize::{PixelType, ResizeOptions, Resizer, images::Image as FrImage};
 use image::{GrayImage, ImageBuffer, RgbImage, Rgba, RgbaImage};
 use ndarray::Array;
@@ -8,10 +12,17 @@ use std::path::Path;
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct BackgroundRemover {
+    model: Model,
     input_size: (u32, u32),
     session: Session,
     input_name: String,
     output_names: Vec<String>,
+
+    // Post-processing of the mask by `remove` and `remove_with_mask`
+    refine: RefineConfig,
+
+    // State of the video matting mode between frames
+    temporal: TemporalMatting,
 }
 
 impl BackgroundRemover {
@@ -33,13 +44,20 @@ impl BackgroundRemover {
             .collect();
 
         Ok(Self {
+            model,
             input_size: model.to_input_size(),
             session,
             input_name,
             output_names,
+            refine: RefineConfig::default(),
+            temporal: TemporalMatting::new(TemporalConfig::default()),
         })
     }
 
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
     pub fn input_size(&self) -> (u32, u32) {
         self.input_size
     }
@@ -57,17 +75,66 @@ impl BackgroundRemover {
         self.fast_resize_mask(&mask, image.width(), image.height())
     }
 
+    pub fn set_refine_config(&mut self, config: RefineConfig) {
+        self.refine = config;
+    }
+
+    pub fn refine_config(&self) -> &RefineConfig {
+        &self.refine
+    }
+
     pub fn remove(&mut self, image: &RgbImage) -> Result<RgbaImage> {
-        let mask = self.get_mask(image)?;
-        Self::remove_background(image, &mask)
+        Ok(self.remove_with_mask(image)?.0)
     }
 
+    // The returned mask is the refined one the result is cut out with
     pub fn remove_with_mask(&mut self, image: &RgbImage) -> Result<(RgbaImage, GrayImage)> {
         let mask = self.get_mask(image)?;
-        let result = Self::remove_background(image, &mask)?;
+        if !self.refine.is_enabled() {
+            let result = Self::remove_background(image, &mask)?;
+            return Ok((result, mask));
+        }
+
+        let mask = refine::refine_mask(image, &mask, &self.refine);
+        let result = if self.refine.despill {
+            refine::despill(image, &mask, self.refine.despill_radius)
+        } else {
+            Self::remove_background(image, &mask)?
+        };
         Ok((result, mask))
     }
 
+    // Video matting mode: the mask of a frame is smoothed with the ones of the
+    // previous frames. Frames of one stream must be passed in order
+    pub fn set_temporal_config(&mut self, config: TemporalConfig) {
+        self.temporal = TemporalMatting::new(config);
+    }
+
+    pub fn temporal_config(&self) -> &TemporalConfig {
+        self.temporal.config()
+    }
+
+    // Forget the previous frames, e.g. when the camera is switched
+    pub fn reset_temporal(&mut self) {
+        self.temporal.reset();
+    }
+
+    pub fn get_frame_mask(&mut self, frame: &RgbImage) -> Result<GrayImage> {
+        let mask = self.get_mask(frame)?;
+        self.temporal.update(frame, mask)
+    }
+
+    // The previous mask moved along the motion to `frame` without running the
+    // model, for the frames it's skipped on. None before the first mask
+    pub fn propagate_frame_mask(&mut self, frame: &RgbImage) -> Result<Option<GrayImage>> {
+        self.temporal.propagate(frame)
+    }
+
+    pub fn remove_frame(&mut self, frame: &RgbImage) -> Result<RgbaImage> {
+        let mask = self.get_frame_mask(frame)?;
+        Self::remove_background(frame, &mask)
+    }
+
     pub fn remove_background(image: &RgbImage, mask: &GrayImage) -> Result<RgbaImage> {
         let (width, height) = image.dimensions();
         let mut result = RgbaImage::new(width, height);
@@ -146,14 +213,16 @@ impl BackgroundRemover {
 
         // Create array in NCHW format: (1, 3, H, W)
         let mut array = Array::zeros((1, 3, height as usize, width as usize));
+        let (mean, std) = self.model.normalization();
 
         for y in 0..height {
             for x in 0..width {
                 let pixel = image.get_pixel(x, y);
-                // Normalize to [0, 1] and convert to CHW format
-                array[[0, 0, y as usize, x as usize]] = pixel[0] as f32 / 255.0;
-                array[[0, 1, y as usize, x as usize]] = pixel[1] as f32 / 255.0;
-                array[[0, 2, y as usize, x as usize]] = pixel[2] as f32 / 255.0;
+                // Normalize to [0, 1] with the model statistics and convert to CHW format
+                for (c, value) in pixel.0.iter().enumerate() {
+                    array[[0, c, y as usize, x as usize]] =
+                        (*value as f32 / 255.0 - mean[c]) / std[c];
+                }
             }
         }
 
@@ -0,0 +1,266 @@
+// Temporal state of the video matting mode. Each new mask is blended with
+// the previous one warped along the motion between the frames, so the edges
+// don't flicker from frame to frame while moving people are still followed.
+// The motion is estimated by block matching on a downscaled luma of the frames
+
+use crate::Result;
+use derive_setters::Setters;
+use fast_image_resize::{PixelType, ResizeOptions, Resizer, images::Image as FrImage};
+use image::{GrayImage, RgbImage};
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct TemporalConfig {
+    // Weight of the previous mask (0.0 - 0.95), 0.0 disables smoothing
+    pub smoothing: f32,
+
+    // Warp the previous mask along the motion, it's blended in place when false
+    pub optical_flow: bool,
+
+    // Width of the luma the motion is estimated on
+    pub flow_width: u32,
+
+    // Block size and search radius of the block matching in flow pixels
+    pub flow_block_size: u32,
+    pub flow_search_radius: u32,
+
+    // Where the new and the previous masks differ more than it the new mask
+    // is trusted more, as the person moved faster than the flow followed
+    pub motion_threshold: u8,
+
+    // Mean luma difference of the frames (0 - 255) taken as a scene cut,
+    // the previous mask is dropped then
+    pub scene_cut_threshold: f32,
+}
+
+impl Default for TemporalConfig {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.6,
+            optical_flow: true,
+            flow_width: 160,
+            flow_block_size: 8,
+            flow_search_radius: 4,
+            motion_threshold: 48,
+            scene_cut_threshold: 40.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TemporalMatting {
+    config: TemporalConfig,
+    prev_luma: Option<GrayImage>,
+    prev_mask: Option<GrayImage>,
+}
+
+impl TemporalMatting {
+    pub fn new(config: TemporalConfig) -> Self {
+        Self {
+            config,
+            prev_luma: None,
+            prev_mask: None,
+        }
+    }
+
+    pub fn config(&self) -> &TemporalConfig {
+        &self.config
+    }
+
+    pub fn mask(&self) -> Option<&GrayImage> {
+        self.prev_mask.as_ref()
+    }
+
+    pub fn reset(&mut self) {
+        self.prev_luma = None;
+        self.prev_mask = None;
+    }
+
+    // Blend the mask inferred from `frame` with the previous one
+    pub fn update(&mut self, frame: &RgbImage, mask: GrayImage) -> Result<GrayImage> {
+        let luma = self.flow_luma(frame)?;
+        let warped = match self.warp_prev_mask(&luma, frame.dimensions()) {
+            Some(warped) if warped.dimensions() == mask.dimensions() => warped,
+            _ => {
+                self.prev_luma = Some(luma);
+                self.prev_mask = Some(mask.clone());
+                return Ok(mask);
+            }
+        };
+
+        let smoothing = self.config.smoothing.clamp(0.0, 0.95);
+        let threshold = self.config.motion_threshold as f32;
+        let mut blended = mask;
+        for (new, prev) in blended.iter_mut().zip(warped.iter()) {
+            let diff = (*new as f32 - *prev as f32).abs();
+            let weight = if diff <= threshold {
+                smoothing
+            } else {
+                smoothing * (255.0 - diff) / (255.0 - threshold).max(1.0)
+            };
+            *new = (*prev as f32 * weight + *new as f32 * (1.0 - weight)).round() as u8;
+        }
+
+        self.prev_luma = Some(luma);
+        self.prev_mask = Some(blended.clone());
+        Ok(blended)
+    }
+
+    // Move the previous mask to `frame` without inference, for the frames the
+    // model is skipped on. None when there is no previous mask to follow
+    pub fn propagate(&mut self, frame: &RgbImage) -> Result<Option<GrayImage>> {
+        let luma = self.flow_luma(frame)?;
+        let Some(warped) = self.warp_prev_mask(&luma, frame.dimensions()) else {
+            return Ok(None);
+        };
+
+        self.prev_luma = Some(luma);
+        self.prev_mask = Some(warped.clone());
+        Ok(Some(warped))
+    }
+
+    // The previous mask warped to the current frame. None without a usable
+    // previous mask or on a scene cut
+    fn warp_prev_mask(&self, luma: &GrayImage, frame_size: (u32, u32)) -> Option<GrayImage> {
+        let (prev_luma, prev_mask) = (self.prev_luma.as_ref()?, self.prev_mask.as_ref()?);
+        if prev_luma.dimensions() != luma.dimensions() || prev_mask.dimensions() != frame_size {
+            return None;
+        }
+
+        let mean_diff = prev_luma
+            .iter()
+            .zip(luma.iter())
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum::<u64>() as f32
+            / luma.len().max(1) as f32;
+        if mean_diff > self.config.scene_cut_threshold {
+            log::debug!("scene cut, mean luma difference: {mean_diff:.1}");
+            return None;
+        }
+
+        if !self.config.optical_flow {
+            return Some(prev_mask.clone());
+        }
+
+        let flow = self.block_flow(prev_luma, luma);
+        Some(warp_mask(prev_mask, &flow, luma.dimensions()))
+    }
+
+    // Motion of each block of `luma` from `prev_luma` by the lowest sum of
+    // absolute differences, the zero motion wins ties to keep still
+    // backgrounds steady
+    fn block_flow(&self, prev_luma: &GrayImage, luma: &GrayImage) -> BlockFlow {
+        let (width, height) = luma.dimensions();
+        let block = self.config.flow_block_size.max(1);
+        let radius = self.config.flow_search_radius as i32;
+        let (cols, rows) = (width.div_ceil(block), height.div_ceil(block));
+
+        let sad = |bx: u32, by: u32, dx: i32, dy: i32| -> u32 {
+            let mut sum = 0;
+            for y in by * block..((by + 1) * block).min(height) {
+                for x in bx * block..((bx + 1) * block).min(width) {
+                    let px = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                    let py = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                    let (a, b) = (luma.get_pixel(x, y)[0], prev_luma.get_pixel(px, py)[0]);
+                    sum += a.abs_diff(b) as u32;
+                }
+            }
+            sum
+        };
+
+        let mut vectors = Vec::with_capacity((cols * rows) as usize);
+        for by in 0..rows {
+            for bx in 0..cols {
+                let mut best = (sad(bx, by, 0, 0), 0, 0);
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let cost = sad(bx, by, dx, dy);
+                        if cost < best.0 {
+                            best = (cost, dx, dy);
+                        }
+                    }
+                }
+                vectors.push((best.1 as f32, best.2 as f32));
+            }
+        }
+
+        BlockFlow {
+            block,
+            cols,
+            rows,
+            vectors,
+        }
+    }
+
+    fn flow_luma(&self, frame: &RgbImage) -> Result<GrayImage> {
+        let (width, height) = frame.dimensions();
+        let luma = frame
+            .pixels()
+            .map(|p| ((p[0] as u32 * 77 + p[1] as u32 * 150 + p[2] as u32 * 29) >> 8) as u8)
+            .collect::<Vec<u8>>();
+
+        let flow_width = self.config.flow_width.clamp(1, width.max(1));
+        let flow_height = ((height as u64 * flow_width as u64) / width.max(1) as u64).max(1) as u32;
+        if flow_width == width && flow_height == height {
+            return GrayImage::from_raw(width, height, luma).ok_or_else(|| {
+                crate::Error::ImageProcessing("Failed to create luma image".to_string())
+            });
+        }
+
+        let src_image = FrImage::from_vec_u8(width, height, luma, PixelType::U8)?;
+        let mut dst_image = FrImage::new(flow_width, flow_height, PixelType::U8);
+        Resizer::new().resize(&src_image, &mut dst_image, &ResizeOptions::new())?;
+
+        GrayImage::from_raw(flow_width, flow_height, dst_image.into_vec()).ok_or_else(|| {
+            crate::Error::ImageProcessing("Failed to create resized luma image".to_string())
+        })
+    }
+}
+
+struct BlockFlow {
+    block: u32,
+    cols: u32,
+    rows: u32,
+
+    // (dx, dy) in flow pixels from the current frame to the previous one
+    vectors: Vec<(f32, f32)>,
+}
+
+impl BlockFlow {
+    // Bilinear interpolation of the block vectors at a point of the flow
+    // image, so the warped mask has no block edges
+    fn at(&self, x: f32, y: f32) -> (f32, f32) {
+        let gx = (x / self.block as f32 - 0.5).clamp(0.0, (self.cols - 1) as f32);
+        let gy = (y / self.block as f32 - 0.5).clamp(0.0, (self.rows - 1) as f32);
+        let (x0, y0) = (gx.floor() as u32, gy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.cols - 1), (y0 + 1).min(self.rows - 1));
+        let (tx, ty) = (gx - x0 as f32, gy - y0 as f32);
+
+        let v = |bx: u32, by: u32| self.vectors[(by * self.cols + bx) as usize];
+        let lerp =
+            |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        lerp(
+            lerp(v(x0, y0), v(x1, y0), tx),
+            lerp(v(x0, y1), v(x1, y1), tx),
+            ty,
+        )
+    }
+}
+
+fn warp_mask(prev_mask: &GrayImage, flow: &BlockFlow, flow_size: (u32, u32)) -> GrayImage {
+    let (width, height) = prev_mask.dimensions();
+    let scale_x = width as f32 / flow_size.0 as f32;
+    let scale_y = height as f32 / flow_size.1 as f32;
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = flow.at(x as f32 / scale_x, y as f32 / scale_y);
+        let px = (x as f32 + dx * scale_x)
+            .round()
+            .clamp(0.0, (width - 1) as f32) as u32;
+        let py = (y as f32 + dy * scale_y)
+            .round()
+            .clamp(0.0, (height - 1) as f32) as u32;
+        *prev_mask.get_pixel(px, py)
+    })
+}
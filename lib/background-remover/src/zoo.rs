@@ -0,0 +1,136 @@
+// Models fetched on first use. A downloaded model gets a `.sha256` file with
+// its checksum next to it, the model is checked against it before it's used
+// so a truncated or corrupted file is downloaded again
+
+use crate::{BackgroundRemover, Error, Model, ModelQuality, Result};
+use downloader::{DownloadState, Downloader};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone)]
+pub struct ModelZoo {
+    dir: PathBuf,
+}
+
+impl ModelZoo {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn model_path(&self, model: Model) -> PathBuf {
+        self.dir.join(model.to_filename())
+    }
+
+    // Downloaded and matching its recorded checksum
+    pub fn is_available(&self, model: Model) -> bool {
+        match self.verify(model) {
+            Ok(()) => true,
+            Err(e) => {
+                log::debug!("{} is not available: {e}", model.to_filename());
+                false
+            }
+        }
+    }
+
+    pub fn verify(&self, model: Model) -> Result<()> {
+        let path = self.model_path(model);
+        if !path.exists() {
+            return Err(Error::ModelNotFound(path));
+        }
+
+        let expected = fs::read_to_string(checksum_path(&path))?;
+        let actual = file_sha256(&path)?;
+        if expected.trim() != actual {
+            return Err(Error::ChecksumMismatch {
+                path,
+                expected: expected.trim().to_string(),
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Path of the model, downloaded first when missing or corrupted.
+    // `progress_cb` takes the downloaded bytes, total bytes and progress
+    pub async fn fetch(
+        &self,
+        model: Model,
+        progress_cb: impl FnMut(u64, u64, f32) + 'static,
+    ) -> Result<PathBuf> {
+        let path = self.model_path(model);
+        if self.is_available(model) {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        log::info!(
+            "Downloading {} from {}",
+            model.to_filename(),
+            model.download_url()
+        );
+
+        let downloader = Downloader::new(model.download_url().to_string(), path.clone());
+        match downloader.start(progress_cb).await? {
+            DownloadState::Finsished => {}
+            DownloadState::Cancelled => {
+                return Err(Error::DownloadFailed(format!(
+                    "download {} cancelled",
+                    model.to_filename()
+                )));
+            }
+            DownloadState::Incompleted => {
+                return Err(Error::DownloadFailed(format!(
+                    "download {} incompleted",
+                    model.to_filename()
+                )));
+            }
+        }
+
+        let checksum = file_sha256(&path)?;
+        fs::write(checksum_path(&path), &checksum)?;
+        log::info!("Downloaded {} sha256: {checksum}", path.display());
+
+        Ok(path)
+    }
+
+    // The model of the quality, downloaded on first use
+    pub async fn remover(
+        &self,
+        quality: ModelQuality,
+        progress_cb: impl FnMut(u64, u64, f32) + 'static,
+    ) -> Result<BackgroundRemover> {
+        let model = Model::from_quality(quality);
+        let path = self.fetch(model, progress_cb).await?;
+        BackgroundRemover::new(model, path)
+    }
+}
+
+fn checksum_path(model_path: &Path) -> PathBuf {
+    model_path.with_added_extension("sha256")
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -1,4 +1,4 @@
-use bot::{APIConfig, Chat, ChatConfig, HistoryChat, StreamTextItem};
+use bot::{APIConfig, Chat, ChatConfig, HistoryChat, Provider, StreamTextItem};
 
 #[tokio::main]
 async fn main() {
@@ -10,17 +10,36 @@ async fn main() {
     let question = "hi";
 
     let request_config = APIConfig {
+        provider: Provider::OpenAI,
         api_base_url: "https://api.deepseek.com/v1".to_string(),
         api_model: "deepseek-chat".to_string(),
         api_key,
         temperature: None,
+        max_tokens: None,
+        context_tokens: None,
+        max_attempts: None,
     };
 
     // let config = APIConfig {
+    //     provider: Provider::Ollama,
+    //     api_base_url: "http://localhost:11434".to_string(),
+    //     api_model: "qwen3:8b".to_string(),
+    //     api_key: String::new(),
+    //     temperature: None,
+    //     max_tokens: None,
+    //     context_tokens: None,
+    //     max_attempts: None,
+    // };
+
+    // let config = APIConfig {
+    //     provider: Provider::OpenAI,
     //     api_base_url: "https://api.deepseek.com/v1".to_string(),
     //     api_model: "deepseek-reasoner".to_string(),
     //     api_key,
     //     temperature: None,
+    //     max_tokens: None,
+    //     context_tokens: None,
+    //     max_attempts: None,
     // };
 
     let histories = vec![HistoryChat {
@@ -0,0 +1,174 @@
+// Responses of finished chats kept in a sqldb table, keyed by the sha256 of
+// the request, so sending the same request again costs no tokens. The
+// database is opened by the application with `sqldb::create_db`
+
+use crate::{Error, Result, request::APIConfig, response::StreamTextItem};
+use pmacro::SqlTable;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqldb::Repository;
+
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    repo: Repository<CachedResponse>,
+}
+
+#[derive(Serialize, Deserialize, SqlTable, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct CachedResponse {
+    #[table(key)]
+    pub key: String,
+    pub text: String,
+    pub reasoning_text: String,
+}
+
+impl CachedResponse {
+    pub fn push(&mut self, item: &StreamTextItem) {
+        if let Some(ref text) = item.text {
+            self.text.push_str(text);
+        }
+
+        if let Some(ref text) = item.reasoning_text {
+            self.reasoning_text.push_str(text);
+        }
+    }
+
+    // The items a cache hit is answered with
+    pub fn into_items(self) -> Vec<StreamTextItem> {
+        let mut items = vec![];
+        if !self.reasoning_text.is_empty() {
+            items.push(StreamTextItem {
+                reasoning_text: Some(self.reasoning_text),
+                ..Default::default()
+            });
+        }
+
+        items.push(StreamTextItem {
+            text: Some(self.text),
+            ..Default::default()
+        });
+
+        items.push(StreamTextItem {
+            finished: true,
+            ..Default::default()
+        });
+
+        items
+    }
+}
+
+impl ResponseCache {
+    // A cache in `table` of the database opened as `db`, the table is created
+    // by `init`
+    pub fn new(db: impl ToString, table: impl ToString) -> Self {
+        Self {
+            repo: Repository::new(db, table),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        self.repo.table()
+    }
+
+    pub async fn init(&self) -> Result<()> {
+        self.repo
+            .init()
+            .await
+            .map_err(|e| Error::Cache(e.to_string()))
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        self.repo
+            .delete_all()
+            .await
+            .map_err(|e| Error::Cache(e.to_string()))
+    }
+
+    // The endpoint and the body of the request make the key, the api key
+    // isn't part of it so changing it keeps the cache
+    pub(crate) fn key(config: &APIConfig, body: &serde_json::Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(config.provider.url(config).as_bytes());
+        hasher.update(b"\n");
+        hasher.update(body.to_string().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    pub(crate) async fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.repo.select(key).await.ok()
+    }
+
+    // Parallel chats with the same request may have put it already
+    pub(crate) async fn put(&self, response: &CachedResponse) -> Result<()> {
+        self.repo
+            .upsert(response)
+            .await
+            .map_err(|e| Error::Cache(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(api_base_url: &str, api_key: &str) -> APIConfig {
+        APIConfig {
+            api_base_url: api_base_url.to_string(),
+            api_model: "model".to_string(),
+            api_key: api_key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let body = json!({
+            "model": "model",
+            "stream": true,
+            "messages": [{"role": "user", "content": "Hi"}],
+        });
+        let key = ResponseCache::key(&config("http://localhost/v1", "key"), &body);
+        assert_eq!(key.len(), 64);
+
+        // The order of the fields, the api key and a trailing slash don't change the key
+        let reordered = json!({
+            "messages": [{"content": "Hi", "role": "user"}],
+            "stream": true,
+            "model": "model",
+        });
+        assert_eq!(
+            ResponseCache::key(&config(" http://localhost/v1/ ", "other key"), &reordered),
+            key
+        );
+
+        // The endpoint and the messages do
+        let other = json!({
+            "model": "model",
+            "stream": true,
+            "messages": [{"role": "user", "content": "Hello"}],
+        });
+        assert_ne!(
+            ResponseCache::key(&config("http://localhost/v1", "key"), &other),
+            key
+        );
+        assert_ne!(
+            ResponseCache::key(&config("http://localhost/v2", "key"), &body),
+            key
+        );
+    }
+
+    #[test]
+    fn test_into_items() {
+        let response = CachedResponse {
+            key: "key".to_string(),
+            text: "Hello".to_string(),
+            reasoning_text: String::new(),
+        };
+
+        let items = response.into_items();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].text.as_deref(), Some("Hello"));
+        assert!(items[1].finished);
+    }
+}
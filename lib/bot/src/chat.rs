@@ -1,9 +1,30 @@
-use crate::{Result, request, response};
-use reqwest::header::{ACCEPT, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, HeaderMap};
+#[cfg(feature = "cache")]
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::{
+    Error, Result,
+    history::{ContextWindow, HistoryChat, TruncationStrategy},
+    request, response,
+};
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// The longest `Retry-After` waited for, a longer one is taken as this
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+const SUMMARY_MAX_TOKENS: usize = 512;
+const SUMMARY_HEADER: &str = "Summary of the earlier conversation:";
+const SUMMARY_PROMPT: &str = "Summarize the following conversation in a few sentences. \
+Keep the facts, decisions and terms the user asked for, they are used to continue \
+the conversation. Only output the summary.";
+
 #[derive(Debug)]
 pub struct ChatConfig {
     pub tx: mpsc::Sender<response::StreamTextItem>,
@@ -12,8 +33,14 @@ pub struct ChatConfig {
 #[derive(Debug)]
 pub struct Chat {
     pub config: request::APIConfig,
-    messages: Vec<request::Message>,
+    prompt: String,
+    question: String,
+    chats: Vec<HistoryChat>,
+    context_window: ContextWindow,
     chat_tx: mpsc::Sender<response::StreamTextItem>,
+
+    #[cfg(feature = "cache")]
+    cache: Option<ResponseCache>,
 }
 
 impl Chat {
@@ -22,164 +49,899 @@ impl Chat {
         question: impl ToString,
         config: ChatConfig,
         request_config: request::APIConfig,
-        chats: Vec<request::HistoryChat>,
+        chats: Vec<HistoryChat>,
     ) -> Chat {
-        let mut messages = vec![];
-        messages.push(request::Message {
-            role: "system".to_string(),
-            content: prompt.to_string(),
-        });
+        Chat {
+            prompt: prompt.to_string(),
+            question: question.to_string(),
+            chats,
+            context_window: ContextWindow::from_config(&request_config),
+            config: request_config,
+            chat_tx: config.tx,
 
-        for item in chats.into_iter() {
-            messages.push(request::Message {
-                role: "user".to_string(),
-                content: item.utext,
-            });
+            #[cfg(feature = "cache")]
+            cache: None,
+        }
+    }
 
-            messages.push(request::Message {
-                role: "assistant".to_string(),
-                content: item.btext,
-            })
+    pub fn with_context_window(mut self, context_window: ContextWindow) -> Self {
+        self.context_window = context_window;
+        self
+    }
+
+    // A finished response is kept in the cache and the same request is
+    // answered from it afterwards
+    #[cfg(feature = "cache")]
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // The history is cut to fit the context window first. Rate limited,
+    // server errors and network errors before the first item are tried
+    // again after a jittered exponential backoff, or after the delay the
+    // server asks for in `Retry-After`
+    pub async fn start(self) -> Result<()> {
+        let Chat {
+            config,
+            prompt,
+            question,
+            chats,
+            context_window,
+            chat_tx,
+            #[cfg(feature = "cache")]
+            cache,
+        } = self;
+
+        let client = reqwest::Client::new();
+        let messages =
+            fit_messages(&client, &config, &context_window, prompt, question, chats).await?;
+        let body = config.provider.body(&config, messages);
+
+        #[cfg(feature = "cache")]
+        if let Some(cache) = cache {
+            return cached_request(&client, &config, &body, &chat_tx, &cache).await;
         }
 
+        request(&client, &config, &body, &chat_tx).await
+    }
+}
+
+// The messages of the request with the history cut to the context window
+async fn fit_messages(
+    client: &reqwest::Client,
+    config: &request::APIConfig,
+    context_window: &ContextWindow,
+    prompt: String,
+    question: String,
+    chats: Vec<HistoryChat>,
+) -> Result<Vec<request::Message>> {
+    let counter = &context_window.counter;
+    let limit = context_window.budget();
+    let tokens = counter.count_message(&prompt) + counter.count_message(&question);
+    if tokens > limit {
+        return Err(Error::ContextTooLong { tokens, limit });
+    }
+
+    let mut budget = limit - tokens;
+    let summarize = context_window.strategy == TruncationStrategy::Summarize
+        && chats.iter().map(|chat| chat.tokens(counter)).sum::<usize>() > budget;
+    if summarize {
+        budget = budget.saturating_sub(counter.count_message(SUMMARY_HEADER) + SUMMARY_MAX_TOKENS);
+    }
+
+    let (dropped, chats) = HistoryChat::split_to_fit(chats, budget, counter);
+
+    let mut prompt = prompt;
+    if !dropped.is_empty() {
+        log::info!(
+            "{} of {} chat turns don't fit the {} tokens context",
+            dropped.len(),
+            dropped.len() + chats.len(),
+            context_window.max_tokens
+        );
+
+        if summarize {
+            match summarize_history(client, config, context_window, dropped).await {
+                Ok(summary) if !summary.trim().is_empty() => {
+                    prompt = format!("{prompt}\n\n{SUMMARY_HEADER}\n{}", summary.trim());
+                }
+                Ok(_) => log::warn!("empty chat history summary, drop the history instead"),
+                Err(e) => log::warn!("summarize chat history failed, drop it instead: {e}"),
+            }
+        }
+    }
+
+    let mut messages = vec![request::Message {
+        role: "system".to_string(),
+        content: prompt,
+    }];
+
+    for item in chats.into_iter() {
         messages.push(request::Message {
             role: "user".to_string(),
-            content: question.to_string(),
+            content: item.utext,
         });
 
-        Chat {
-            messages,
-            config: request_config,
-            chat_tx: config.tx,
+        messages.push(request::Message {
+            role: "assistant".to_string(),
+            content: item.btext,
+        })
+    }
+
+    messages.push(request::Message {
+        role: "user".to_string(),
+        content: question,
+    });
+
+    Ok(messages)
+}
+
+async fn summarize_history(
+    client: &reqwest::Client,
+    config: &request::APIConfig,
+    context_window: &ContextWindow,
+    chats: Vec<HistoryChat>,
+) -> Result<String> {
+    let counter = &context_window.counter;
+    let mut config = config.clone();
+    config.max_tokens = Some(SUMMARY_MAX_TOKENS as u32);
+
+    // Only the newest part of a history longer than the window is summarized
+    let budget = context_window
+        .max_tokens
+        .saturating_sub(counter.count_message(SUMMARY_PROMPT) + SUMMARY_MAX_TOKENS);
+    let (_, chats) = HistoryChat::split_to_fit(chats, budget, counter);
+
+    let messages = vec![
+        request::Message {
+            role: "system".to_string(),
+            content: SUMMARY_PROMPT.to_string(),
+        },
+        request::Message {
+            role: "user".to_string(),
+            content: HistoryChat::transcript(&chats),
+        },
+    ];
+    let body = config.provider.body(&config, messages);
+
+    let (tx, mut rx) = mpsc::channel::<response::StreamTextItem>(100);
+    let collect = async {
+        let mut summary = String::new();
+        while let Some(item) = rx.recv().await {
+            if let Some(etext) = item.etext {
+                return Err(Error::Api(etext));
+            }
+
+            if let Some(text) = item.text {
+                summary.push_str(&text);
+            }
+        }
+        Ok(summary)
+    };
+
+    let (result, summary) = tokio::join!(
+        async move {
+            let result = request(client, &config, &body, &tx).await;
+            drop(tx);
+            result
+        },
+        collect
+    );
+
+    result?;
+    summary
+}
+
+// Errors of the cache are only logged, the chat goes on without it
+#[cfg(feature = "cache")]
+async fn cached_request(
+    client: &reqwest::Client,
+    config: &request::APIConfig,
+    body: &serde_json::Value,
+    chat_tx: &mpsc::Sender<response::StreamTextItem>,
+    cache: &ResponseCache,
+) -> Result<()> {
+    let key = ResponseCache::key(config, body);
+    if let Some(response) = cache.get(&key).await {
+        log::info!("chat response cache hit: {key}");
+        for item in response.into_items() {
+            if chat_tx.send(item).await.is_err() {
+                log::info!("receiver dropped");
+                break;
+            }
         }
+        return Ok(());
     }
 
-    fn headers(&self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
-        headers.insert(
-            AUTHORIZATION,
-            format!("Bearer {}", self.config.api_key).parse().unwrap(),
+    // Only a response that finished without an error is kept
+    let (tx, mut rx) = mpsc::channel::<response::StreamTextItem>(100);
+    let forward = async {
+        let mut response = CachedResponse {
+            key: key.clone(),
+            ..Default::default()
+        };
+        let (mut finished, mut failed) = (false, false);
+        while let Some(item) = rx.recv().await {
+            response.push(&item);
+            finished |= item.finished;
+            failed |= item.etext.is_some();
+
+            if chat_tx.send(item).await.is_err() {
+                log::info!("receiver dropped");
+                return None;
+            }
+        }
+        (finished && !failed).then_some(response)
+    };
+
+    let (result, response) = tokio::join!(
+        async move {
+            let result = request(client, config, body, &tx).await;
+            drop(tx);
+            result
+        },
+        forward
+    );
+    result?;
+
+    if let Some(response) = response
+        && let Err(e) = cache.put(&response).await
+    {
+        log::warn!("cache chat response failed: {e}");
+    }
+
+    Ok(())
+}
+
+async fn request(
+    client: &reqwest::Client,
+    config: &request::APIConfig,
+    body: &serde_json::Value,
+    chat_tx: &mpsc::Sender<response::StreamTextItem>,
+) -> Result<()> {
+    let max_attempts = config
+        .max_attempts
+        .unwrap_or(request::DEFAULT_MAX_ATTEMPTS)
+        .max(1);
+
+    let mut attempt = 1;
+    loop {
+        let (failure, retry_after) = match send(client, config, body, chat_tx).await {
+            Ok(Attempt::Done) => return Ok(()),
+            Ok(Attempt::Retry {
+                failure,
+                retry_after,
+            }) => (failure, retry_after),
+            Err(e) => return Err(e),
+        };
+
+        if attempt >= max_attempts {
+            return match failure {
+                Failure::Status(estr) => {
+                    log::info!("{estr}");
+                    let item = response::StreamTextItem {
+                        etext: Some(estr),
+                        ..Default::default()
+                    };
+                    if chat_tx.send(item).await.is_err() {
+                        log::info!("receiver dropped");
+                    }
+                    Ok(())
+                }
+                Failure::Request(e) => Err(e.into()),
+            };
+        }
+
+        let delay = retry_after.unwrap_or_else(|| backoff_delay(attempt));
+        log::warn!(
+            "chat request failed, retry {attempt}/{} in {delay:?}: {failure}",
+            max_attempts - 1
         );
-        headers.insert(ACCEPT, "text/event-stream".parse().unwrap());
-        headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
 
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => (),
+            _ = chat_tx.closed() => {
+                log::info!("receiver dropped");
+                return Ok(());
+            }
+        }
+
+        attempt += 1;
+    }
+}
+
+enum Attempt {
+    Done,
+
+    // Failed before any item was sent, so it can be tried again
+    Retry {
+        failure: Failure,
+        retry_after: Option<Duration>,
+    },
+}
+
+enum Failure {
+    // The error message of a response with a transient status
+    Status(String),
+    Request(reqwest::Error),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Status(estr) => write!(f, "{estr}"),
+            Failure::Request(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    config: &request::APIConfig,
+    body: &serde_json::Value,
+    chat_tx: &mpsc::Sender<response::StreamTextItem>,
+) -> Result<Attempt> {
+    let provider = config.provider;
+    let response = client
+        .post(provider.url(config))
+        .headers(provider.headers(config))
+        .json(body)
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) if is_transient_error(&e) => {
+            return Ok(Attempt::Retry {
+                failure: Failure::Request(e),
+                retry_after: None,
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        let estr = response::error_message(&body).unwrap_or_else(|| format!("{status} {body}"));
+
+        if is_transient_status(status) {
+            return Ok(Attempt::Retry {
+                failure: Failure::Status(estr),
+                retry_after,
+            });
+        }
+
+        log::info!("{estr}");
+        let item = response::StreamTextItem {
+            etext: Some(estr),
+            ..Default::default()
+        };
+        if chat_tx.send(item).await.is_err() {
+            log::info!("receiver dropped");
+        }
+        return Ok(Attempt::Done);
+    }
+
+    // An event may be split across chunks, so only complete lines are
+    // parsed
+    let mut stream = response.bytes_stream();
+    let mut buffer = Vec::new();
+    let mut sent_items = 0;
+
+    loop {
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                buffer.extend_from_slice(&chunk);
+
+                while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    let line = String::from_utf8_lossy(&line);
+                    if !send_line(provider, chat_tx, line.trim(), &mut sent_items).await {
+                        return Ok(Attempt::Done);
+                    }
+                }
+            }
+
+            // The text sent can't be taken back, so only a stream that broke
+            // before it is tried again
+            Some(Err(e)) if sent_items == 0 && is_transient_error(&e) => {
+                return Ok(Attempt::Retry {
+                    failure: Failure::Request(e),
+                    retry_after: None,
+                });
+            }
+            Some(Err(e)) => log::warn!("{e:?}"),
+            None => {
+                let line = String::from_utf8_lossy(&buffer);
+                send_line(provider, chat_tx, line.trim(), &mut sent_items).await;
+                break;
+            }
+        }
+    }
+
+    Ok(Attempt::Done)
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+fn is_transient_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.is_request() || e.is_body()
+}
+
+// `Retry-After` in seconds, or OpenAI's `retry-after-ms`. The http date form
+// falls back to the backoff
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let delay = header("retry-after-ms")
+        .and_then(|ms| ms.trim().parse::<f64>().ok())
+        .map(|ms| ms / 1000.0)
+        .or_else(|| {
+            header(RETRY_AFTER.as_str()).and_then(|secs| secs.trim().parse::<f64>().ok())
+        })?;
+
+    (delay.is_finite() && delay >= 0.0).then(|| Duration::from_secs_f64(delay).min(MAX_RETRY_AFTER))
+}
+
+// Exponential backoff of `attempt` (from 1) with jitter, so the parallel
+// requests that hit the rate limit together don't retry together
+fn backoff_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(rand::random_range(0.5..=1.0))
+}
+
+// Send the items of a line of the stream, false when the stream is over
+async fn send_line(
+    provider: request::Provider,
+    chat_tx: &mpsc::Sender<response::StreamTextItem>,
+    line: &str,
+    sent_items: &mut usize,
+) -> bool {
+    let data = match provider.stream_format() {
+        request::StreamFormat::Sse => match line.strip_prefix("data:") {
+            Some(data) => data.trim(),
+            None => return true,
+        },
+        request::StreamFormat::JsonLines => line,
+    };
+
+    if data.is_empty() {
+        return true;
+    }
+
+    let items = match provider.parse_event(data) {
+        Ok(items) => items,
+        Err(e) => {
+            log::info!("{e:?} {line}");
+            return true;
+        }
+    };
+
+    for item in items {
+        if let Some(ref estr) = item.etext {
+            log::info!("{estr}");
+        }
+
+        let is_over = item.finished || item.etext.is_some();
+        if chat_tx.send(item).await.is_err() {
+            log::info!("receiver dropped");
+            return false;
+        }
+        *sent_items += 1;
+
+        if is_over {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenCounter;
+    use reqwest::header::HeaderValue;
+
+    fn headers(items: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in items {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
         headers
     }
 
-    pub async fn start(self) -> Result<()> {
-        let headers = self.headers();
+    #[test]
+    fn test_is_transient_status() {
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_GATEWAY,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::from_u16(529).unwrap(),
+        ] {
+            assert!(is_transient_status(status), "{status}");
+        }
+
+        for status in [
+            StatusCode::OK,
+            StatusCode::BAD_REQUEST,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::FORBIDDEN,
+            StatusCode::NOT_FOUND,
+        ] {
+            assert!(!is_transient_status(status), "{status}");
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(retry_after(&headers(&[])), None);
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "3")])),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", " 1.5 ")])),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after-ms", "250")])),
+            Some(Duration::from_millis(250))
+        );
+
+        // `retry-after-ms` is more precise
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "1"), ("retry-after-ms", "800")])),
+            Some(Duration::from_millis(800))
+        );
+        assert_eq!(
+            retry_after(&headers(&[
+                ("retry-after", "2"),
+                ("retry-after-ms", "soon")
+            ])),
+            Some(Duration::from_secs(2))
+        );
+
+        // The http date form falls back to the backoff
+        assert_eq!(
+            retry_after(&headers(&[(
+                "retry-after",
+                "Wed, 21 Oct 2015 07:28:00 GMT"
+            )])),
+            None
+        );
+
+        for value in ["-1", "NaN", "inf", "-inf"] {
+            assert_eq!(retry_after(&headers(&[("retry-after", value)])), None);
+            assert_eq!(retry_after(&headers(&[("retry-after-ms", value)])), None);
+        }
+
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "86400")])),
+            Some(MAX_RETRY_AFTER)
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after-ms", "1e12")])),
+            Some(MAX_RETRY_AFTER)
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        for attempt in 1..=5 {
+            let max_delay = RETRY_BASE_DELAY * (1 << (attempt - 1));
+            for _ in 0..100 {
+                let delay = backoff_delay(attempt);
+                assert!(delay >= max_delay / 2 && delay <= max_delay, "{delay:?}");
+            }
+        }
+
+        // The delay is capped, the shift doesn't overflow
+        for attempt in [6, 16, 17, 64, u32::MAX] {
+            for _ in 0..100 {
+                let delay = backoff_delay(attempt);
+                assert!(
+                    delay >= RETRY_MAX_DELAY / 2 && delay <= RETRY_MAX_DELAY,
+                    "{delay:?}"
+                );
+            }
+        }
+
+        // Attempt 0 is treated as the first one
+        assert!(backoff_delay(0) <= RETRY_BASE_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_fit_messages() {
         let client = reqwest::Client::new();
+        let config = request::APIConfig::default();
+        let counter = TokenCounter::default();
+        let history = (0..5)
+            .map(|index| HistoryChat {
+                utext: format!("question {index}"),
+                btext: "answer".to_string(),
+            })
+            .collect::<Vec<_>>();
+        let turn_tokens = history[0].tokens(&counter);
+        let tokens = counter.count_message("Be brief") + counter.count_message("Bye");
 
-        let url = format!("{}{}", self.config.api_base_url, "/chat/completions");
-        let request_body = request::ChatCompletion {
-            messages: self.messages,
-            model: self.config.api_model,
-            temperature: self.config.temperature,
-            stream: true,
+        let fit = |budget: usize, chats: Vec<HistoryChat>| {
+            let context_window = ContextWindow {
+                max_tokens: budget + 100,
+                reserved_tokens: 100,
+                strategy: TruncationStrategy::DropOldest,
+                counter: counter.clone(),
+            };
+            let client = &client;
+            let config = &config;
+            async move {
+                fit_messages(
+                    client,
+                    config,
+                    &context_window,
+                    "Be brief".to_string(),
+                    "Bye".to_string(),
+                    chats,
+                )
+                .await
+            }
+        };
+        let contents = |messages: &[request::Message]| {
+            messages
+                .iter()
+                .map(|message| format!("{}: {}", message.role, message.content))
+                .collect::<Vec<_>>()
         };
 
-        let mut stream = client
-            .post(url)
-            .headers(headers)
-            .json(&request_body)
-            .timeout(Duration::from_secs(15))
-            .send()
-            .await?
-            .bytes_stream();
-
-        loop {
-            match stream.next().await {
-                Some(Ok(chunk)) => {
-                    let body = String::from_utf8_lossy(&chunk);
-
-                    // log::debug!("{body:?}");
-
-                    if let Ok(err) = serde_json::from_str::<response::Error>(&body) {
-                        if let Some(estr) = err.error.get("message") {
-                            let item = response::StreamTextItem {
-                                etext: Some(estr.clone()),
-                                ..Default::default()
-                            };
-                            if self.chat_tx.send(item).await.is_err() {
-                                log::info!("receiver dropped");
-                                break;
-                            }
-                            log::info!("{}", estr);
-                        }
-                        break;
-                    }
+        let messages = fit(tokens + turn_tokens * 5, history.clone())
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 12);
 
-                    if body.starts_with("data: [DONE]") {
-                        break;
-                    }
+        // The oldest turns are dropped, the prompt and the question are kept
+        let messages = fit(tokens + turn_tokens * 2 - 1, history.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            contents(&messages),
+            [
+                "system: Be brief",
+                "user: question 4",
+                "assistant: answer",
+                "user: Bye"
+            ]
+        );
 
-                    let lines: Vec<_> = body.split("\n\n").collect();
+        let messages = fit(tokens, history.clone()).await.unwrap();
+        assert_eq!(contents(&messages), ["system: Be brief", "user: Bye"]);
 
-                    for line in lines.into_iter() {
-                        if !line.starts_with("data:") {
-                            continue;
-                        }
+        match fit(tokens - 1, history).await {
+            Err(Error::ContextTooLong { tokens: t, limit }) => {
+                assert_eq!((t, limit), (tokens, tokens - 1));
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+    }
 
-                        match serde_json::from_str::<response::ChatCompletionChunk>(&line[5..]) {
-                            Ok(chunk) => {
-                                let choice = &chunk.choices[0];
-                                if choice.finish_reason.is_some() {
-                                    let item = response::StreamTextItem {
-                                        finished: true,
-                                        ..Default::default()
-                                    };
-                                    if self.chat_tx.send(item).await.is_err() {
-                                        log::info!("receiver dropped");
-                                        break;
-                                    }
-
-                                    log::info!(
-                                        "finish_reason: {}",
-                                        choice.finish_reason.as_ref().unwrap()
-                                    );
-                                    break;
-                                }
-
-                                let item = if choice.delta.contains_key("content")
-                                    && choice.delta["content"].is_some()
-                                {
-                                    Some(response::StreamTextItem {
-                                        text: choice.delta["content"].clone(),
-                                        ..Default::default()
-                                    })
-                                } else if choice.delta.contains_key("reasoning_content")
-                                    && choice.delta["reasoning_content"].is_some()
-                                {
-                                    Some(response::StreamTextItem {
-                                        reasoning_text: choice.delta["reasoning_content"].clone(),
-                                        ..Default::default()
-                                    })
-                                } else if choice.delta.contains_key("role") {
-                                    log::info!("role: {:?}", choice.delta["role"]);
-                                    None
-                                } else {
-                                    None
-                                };
-
-                                if let Some(item) = item
-                                    && self.chat_tx.send(item).await.is_err()
-                                {
-                                    log::info!("receiver dropped");
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                log::info!("{e:?} {}", &line);
-                                break;
-                            }
+    #[cfg(feature = "cache")]
+    mod cache {
+        use super::*;
+        use serde_json::json;
+        use std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+            sync::{
+                Arc,
+                atomic::{AtomicUsize, Ordering},
+            },
+            thread,
+        };
+
+        const FINISHED: &str = "data: {\"id\":\"1\",\"created\":0,\"model\":\"model\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n\
+             data: {\"id\":\"1\",\"created\":0,\"model\":\"model\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n";
+        const UNFINISHED: &str = "data: {\"id\":\"1\",\"created\":0,\"model\":\"model\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n";
+        const STREAM_ERROR: &str = "data: {\"id\":\"1\",\"created\":0,\"model\":\"model\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n\
+             data: {\"error\":\"overloaded\"}\n\n";
+
+        // A chat endpoint answering every request with `status` and `body`,
+        // and the number of requests it got
+        fn serve(status: &'static str, body: &'static str) -> (String, Arc<AtomicUsize>) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/v1", listener.local_addr().unwrap());
+            let requests = Arc::new(AtomicUsize::new(0));
+
+            let counter = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else {
+                        continue;
+                    };
+
+                    // The whole request is read, so it isn't reset by the close
+                    let mut reader = BufReader::new(&mut stream);
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':')
+                            && name.eq_ignore_ascii_case("content-length")
+                        {
+                            content_length = value.trim().parse().unwrap_or(0);
                         }
                     }
+                    let mut request_body = vec![0; content_length];
+                    _ = reader.read_exact(&mut request_body);
+                    counter.fetch_add(1, Ordering::SeqCst);
+
+                    _ = write!(
+                        stream,
+                        "HTTP/1.1 {status}\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
                 }
-                Some(Err(_)) => (),
-                None => break,
+            });
+
+            (url, requests)
+        }
+
+        async fn response_cache(name: &str) -> ResponseCache {
+            let db_path = format!("/tmp/test-{name}.db");
+            let _ = std::fs::remove_file(&db_path);
+            sqldb::create_db(name, &db_path).await.unwrap();
+
+            let cache = ResponseCache::new(name, "chat_cache");
+            cache.init().await.unwrap();
+            cache
+        }
+
+        fn api_config(url: &str) -> request::APIConfig {
+            request::APIConfig {
+                api_base_url: url.to_string(),
+                api_model: "model".to_string(),
+                max_attempts: Some(1),
+                ..Default::default()
+            }
+        }
+
+        fn body(question: &str) -> serde_json::Value {
+            json!({
+                "model": "model",
+                "stream": true,
+                "messages": [{"role": "user", "content": question}],
+            })
+        }
+
+        // The text, reasoning text, error text and finished flag of the items
+        async fn chat(
+            config: &request::APIConfig,
+            body: &serde_json::Value,
+            cache: &ResponseCache,
+        ) -> Vec<(String, String, String, bool)> {
+            let (tx, mut rx) = mpsc::channel(100);
+            cached_request(&reqwest::Client::new(), config, body, &tx, cache)
+                .await
+                .unwrap();
+            drop(tx);
+
+            let mut items = vec![];
+            while let Some(item) = rx.recv().await {
+                items.push((
+                    item.text.unwrap_or_default(),
+                    item.reasoning_text.unwrap_or_default(),
+                    item.etext.unwrap_or_default(),
+                    item.finished,
+                ));
+            }
+            items
+        }
+
+        fn text(items: &[(String, String, String, bool)]) -> String {
+            items.iter().map(|item| item.0.as_str()).collect()
+        }
+
+        #[tokio::test]
+        async fn test_cache_hit() {
+            let cache = response_cache("bot-cache-hit").await;
+            let (url, requests) = serve("200 OK", FINISHED);
+            let config = api_config(&url);
+
+            let key = ResponseCache::key(&config, &body("Hi"));
+            cache
+                .put(&CachedResponse {
+                    key,
+                    text: "Cached".to_string(),
+                    reasoning_text: "Thinking".to_string(),
+                })
+                .await
+                .unwrap();
+
+            // The fields of the body are in another order
+            let reordered = json!({
+                "messages": [{"content": "Hi", "role": "user"}],
+                "stream": true,
+                "model": "model",
+            });
+            let items = chat(&config, &reordered, &cache).await;
+
+            assert_eq!(
+                items,
+                [
+                    (String::new(), "Thinking".to_string(), String::new(), false),
+                    ("Cached".to_string(), String::new(), String::new(), false),
+                    (String::new(), String::new(), String::new(), true),
+                ]
+            );
+            assert_eq!(requests.load(Ordering::SeqCst), 0);
+        }
+
+        #[tokio::test]
+        async fn test_cache_miss() {
+            let cache = response_cache("bot-cache-miss").await;
+            let (url, requests) = serve("200 OK", FINISHED);
+            let config = api_config(&url);
+
+            let items = chat(&config, &body("Hi"), &cache).await;
+            assert_eq!(text(&items), "Hello");
+            assert!(items.last().unwrap().3);
+            assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+            let cached = cache
+                .get(&ResponseCache::key(&config, &body("Hi")))
+                .await
+                .unwrap();
+            assert_eq!(cached.text, "Hello");
+
+            // Answered from the cache the second time, another question isn't
+            let items = chat(&config, &body("Hi"), &cache).await;
+            assert_eq!(text(&items), "Hello");
+            assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+            chat(&config, &body("Bye"), &cache).await;
+            assert_eq!(requests.load(Ordering::SeqCst), 2);
+        }
+
+        #[tokio::test]
+        async fn test_cache_skips_failed_responses() {
+            let cache = response_cache("bot-cache-failed").await;
+
+            for (status, response) in [
+                (
+                    "400 Bad Request",
+                    "{\"error\":{\"message\":\"bad request\"}}",
+                ),
+                ("200 OK", UNFINISHED),
+                ("200 OK", STREAM_ERROR),
+            ] {
+                let (url, requests) = serve(status, response);
+                let config = api_config(&url);
+
+                for count in 1..=2 {
+                    let items = chat(&config, &body("Hi"), &cache).await;
+                    assert!(items.iter().all(|item| !item.3), "{response}");
+                    assert_eq!(requests.load(Ordering::SeqCst), count, "{response}");
+                }
+
+                assert!(
+                    cache
+                        .get(&ResponseCache::key(&config, &body("Hi")))
+                        .await
+                        .is_none()
+                );
             }
         }
-        Ok(())
     }
 }
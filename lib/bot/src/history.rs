@@ -0,0 +1,191 @@
+// Fitting the chat history into the context window of the model. The system
+// prompt and the question are always sent, the history takes what's left of
+// the window after the tokens reserved for the answer, newest turns first
+
+use crate::{request::APIConfig, token::TokenCounter};
+
+// Tokens kept for the answer when the config doesn't limit it
+const DEFAULT_RESERVED_TOKENS: usize = 4096;
+
+#[derive(Default, Clone, Debug)]
+pub struct HistoryChat {
+    pub utext: String,
+    pub btext: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TruncationStrategy {
+    // Drop the oldest turns until the rest fits
+    #[default]
+    DropOldest,
+
+    // Replace the dropped turns by a summary the model writes of them
+    Summarize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    // Context length of the model in tokens
+    pub max_tokens: usize,
+
+    // Kept free for the answer
+    pub reserved_tokens: usize,
+
+    pub strategy: TruncationStrategy,
+    pub counter: TokenCounter,
+}
+
+impl ContextWindow {
+    // The window of `APIConfig::context_tokens`, or the provider default
+    pub fn from_config(config: &APIConfig) -> Self {
+        let max_tokens = config
+            .context_tokens
+            .unwrap_or_else(|| config.provider.default_context_tokens())
+            as usize;
+
+        // At most a quarter of a small window, so a short history still fits
+        let reserved_tokens = config
+            .max_tokens
+            .map(|tokens| tokens as usize)
+            .unwrap_or(DEFAULT_RESERVED_TOKENS)
+            .min(max_tokens / 4);
+
+        Self {
+            max_tokens,
+            reserved_tokens,
+            strategy: TruncationStrategy::default(),
+            counter: TokenCounter::default(),
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: TruncationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_counter(mut self, counter: TokenCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn with_reserved_tokens(mut self, reserved_tokens: usize) -> Self {
+        self.reserved_tokens = reserved_tokens;
+        self
+    }
+
+    // Tokens the messages of a request may take
+    pub fn budget(&self) -> usize {
+        self.max_tokens.saturating_sub(self.reserved_tokens)
+    }
+}
+
+impl HistoryChat {
+    pub fn tokens(&self, counter: &TokenCounter) -> usize {
+        counter.count_message(&self.utext) + counter.count_message(&self.btext)
+    }
+
+    // Split `chats` into the oldest turns that don't fit in `budget` tokens
+    // and the newest ones that do, both oldest first
+    pub fn split_to_fit(
+        mut chats: Vec<Self>,
+        budget: usize,
+        counter: &TokenCounter,
+    ) -> (Vec<Self>, Vec<Self>) {
+        let mut used = 0;
+        let mut kept = chats.len();
+        for (index, chat) in chats.iter().enumerate().rev() {
+            used += chat.tokens(counter);
+            if used > budget {
+                break;
+            }
+            kept = index;
+        }
+
+        let kept_chats = chats.split_off(kept);
+        (chats, kept_chats)
+    }
+
+    // The turns as a plain transcript, for summarizing them
+    pub fn transcript(chats: &[Self]) -> String {
+        chats
+            .iter()
+            .map(|chat| format!("User: {}\nAssistant: {}", chat.utext, chat.btext))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Provider;
+
+    fn chats(count: usize) -> Vec<HistoryChat> {
+        (0..count)
+            .map(|index| HistoryChat {
+                utext: format!("question {index}"),
+                btext: "answer".to_string(),
+            })
+            .collect()
+    }
+
+    fn utexts(chats: &[HistoryChat]) -> Vec<&str> {
+        chats.iter().map(|chat| chat.utext.as_str()).collect()
+    }
+
+    #[test]
+    fn test_split_to_fit() {
+        let counter = TokenCounter::default();
+        let turn_tokens = chats(1)[0].tokens(&counter);
+        assert_eq!(turn_tokens, 3 + 4 + 2 + 4);
+
+        let (dropped, kept) = HistoryChat::split_to_fit(chats(5), turn_tokens * 2, &counter);
+        assert_eq!(utexts(&dropped), ["question 0", "question 1", "question 2"]);
+        assert_eq!(utexts(&kept), ["question 3", "question 4"]);
+
+        // A turn only partly fitting is dropped
+        let (dropped, kept) = HistoryChat::split_to_fit(chats(5), turn_tokens * 2 - 1, &counter);
+        assert_eq!(dropped.len(), 4);
+        assert_eq!(utexts(&kept), ["question 4"]);
+
+        let (dropped, kept) = HistoryChat::split_to_fit(chats(5), usize::MAX, &counter);
+        assert!(dropped.is_empty());
+        assert_eq!(kept.len(), 5);
+
+        let (dropped, kept) = HistoryChat::split_to_fit(chats(5), 0, &counter);
+        assert_eq!(dropped.len(), 5);
+        assert!(kept.is_empty());
+
+        // The turns older than a long one aren't kept, even if they fit
+        let mut history = chats(3);
+        history[1].btext = "answer ".repeat(100);
+        let (dropped, kept) = HistoryChat::split_to_fit(history, turn_tokens * 2, &counter);
+        assert_eq!(utexts(&dropped), ["question 0", "question 1"]);
+        assert_eq!(utexts(&kept), ["question 2"]);
+
+        let (dropped, kept) = HistoryChat::split_to_fit(vec![], 100, &counter);
+        assert!(dropped.is_empty() && kept.is_empty());
+    }
+
+    #[test]
+    fn test_context_window() {
+        let mut config = APIConfig {
+            provider: Provider::Ollama,
+            ..Default::default()
+        };
+
+        let window = ContextWindow::from_config(&config);
+        assert_eq!(window.max_tokens, 4096);
+        assert_eq!(window.reserved_tokens, 1024);
+        assert_eq!(window.budget(), 3072);
+
+        config.context_tokens = Some(100_000);
+        config.max_tokens = Some(2000);
+        let window = ContextWindow::from_config(&config);
+        assert_eq!(window.reserved_tokens, 2000);
+        assert_eq!(window.budget(), 98_000);
+
+        let window = window.with_reserved_tokens(200_000);
+        assert_eq!(window.budget(), 0);
+    }
+}
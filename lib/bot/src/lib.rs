@@ -1,10 +1,18 @@
+#[cfg(feature = "cache")]
+mod cache;
 mod chat;
+mod history;
 mod request;
 mod response;
+mod token;
 
+#[cfg(feature = "cache")]
+pub use cache::ResponseCache;
 pub use chat::{Chat, ChatConfig};
-pub use request::{APIConfig, HistoryChat};
+pub use history::{ContextWindow, HistoryChat, TruncationStrategy};
+pub use request::{APIConfig, DEFAULT_MAX_ATTEMPTS, Provider};
 pub use response::StreamTextItem;
+pub use token::{TokenCounter, estimate_tokens};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -12,4 +20,20 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     #[error("Request Error {0}")]
     Request(#[from] reqwest::Error),
+
+    #[error("API Error {0}")]
+    Api(String),
+
+    #[error(
+        "Prompt and question take {tokens} tokens, more than the {limit} tokens the context window leaves for them"
+    )]
+    ContextTooLong { tokens: usize, limit: usize },
+
+    #[cfg(feature = "tokenizer")]
+    #[error("Tokenizer Error {0}")]
+    Tokenizer(String),
+
+    #[cfg(feature = "cache")]
+    #[error("Cache Error {0}")]
+    Cache(String),
 }
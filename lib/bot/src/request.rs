@@ -1,17 +1,61 @@
+use crate::response::{self, StreamTextItem};
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug)]
-pub struct HistoryChat {
-    pub utext: String,
-    pub btext: String,
+// Required by the Anthropic API, which has no default for it
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 8192;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    // Any OpenAI compatible API, e.g. OpenAI, DeepSeek and vLLM
+    #[default]
+    OpenAI,
+    Anthropic,
+    Gemini,
+    Ollama,
 }
 
+// How the response stream is split into events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamFormat {
+    // Server sent events, the json is in the `data:` lines
+    Sse,
+
+    // A json object per line
+    JsonLines,
+}
+
+// `api_base_url` is the url the endpoints of the provider are joined to, e.g.
+// https://api.openai.com/v1, https://api.anthropic.com/v1,
+// https://generativelanguage.googleapis.com/v1beta or http://localhost:11434
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
 pub struct APIConfig {
+    pub provider: Provider,
     pub api_base_url: String,
     pub api_model: String,
     pub api_key: String,
     pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+
+    // Context length of the model, None is the provider default. It's also
+    // the context length a Ollama model is loaded with
+    pub context_tokens: Option<u32>,
+
+    // Attempts of a request failing with a transient error, None is
+    // `DEFAULT_MAX_ATTEMPTS`
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Message {
+    pub role: String,
+    pub content: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,10 +66,457 @@ pub(crate) struct ChatCompletion {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub(crate) struct Message {
-    pub role: String,
-    pub content: String,
+pub(crate) struct AnthropicMessages {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub max_tokens: u32,
+    pub stream: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GeminiGenerateContent {
+    pub contents: Vec<GeminiContent>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiContent>,
+
+    pub generation_config: GeminiGenerationConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    pub parts: Vec<GeminiPart>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct GeminiPart {
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct OllamaChat {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    pub options: OllamaOptions,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+}
+
+impl Provider {
+    pub fn all_providers() -> Vec<Self> {
+        vec![Self::OpenAI, Self::Anthropic, Self::Gemini, Self::Ollama]
+    }
+
+    pub fn default_api_base_url(&self) -> &'static str {
+        match self {
+            Self::OpenAI => "https://api.openai.com/v1",
+            Self::Anthropic => "https://api.anthropic.com/v1",
+            Self::Gemini => "https://generativelanguage.googleapis.com/v1beta",
+            Self::Ollama => "http://localhost:11434",
+        }
+    }
+
+    // A lower bound of the context length of the usual models of the
+    // provider. Ollama loads the models with a 4096 tokens context by default
+    pub fn default_context_tokens(&self) -> u32 {
+        match self {
+            Self::OpenAI => 65536,
+            Self::Anthropic => 200000,
+            Self::Gemini => 1048576,
+            Self::Ollama => 4096,
+        }
+    }
+
+    // A local Ollama server doesn't need a key
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, Self::Ollama)
+    }
+
+    pub(crate) fn stream_format(&self) -> StreamFormat {
+        match self {
+            Self::OpenAI | Self::Anthropic | Self::Gemini => StreamFormat::Sse,
+            Self::Ollama => StreamFormat::JsonLines,
+        }
+    }
+
+    pub(crate) fn url(&self, config: &APIConfig) -> String {
+        let base_url = if config.api_base_url.trim().is_empty() {
+            self.default_api_base_url()
+        } else {
+            config.api_base_url.trim()
+        }
+        .trim_end_matches('/');
+
+        match self {
+            Self::OpenAI => format!("{base_url}/chat/completions"),
+            Self::Anthropic => format!("{base_url}/messages"),
+            Self::Gemini => format!(
+                "{base_url}/models/{}:streamGenerateContent?alt=sse",
+                config.api_model
+            ),
+            Self::Ollama => format!("{base_url}/api/chat"),
+        }
+    }
+
+    pub(crate) fn headers(&self, config: &APIConfig) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
+
+        let api_key = HeaderValue::from_str(&config.api_key);
+        match self {
+            Self::OpenAI => {
+                headers.insert(ACCEPT, "text/event-stream".parse().unwrap());
+                if let Ok(value) = format!("Bearer {}", config.api_key).parse() {
+                    headers.insert(AUTHORIZATION, value);
+                }
+            }
+            Self::Anthropic => {
+                headers.insert(ACCEPT, "text/event-stream".parse().unwrap());
+                headers.insert(
+                    HeaderName::from_static("anthropic-version"),
+                    ANTHROPIC_VERSION.parse().unwrap(),
+                );
+                if let Ok(value) = api_key {
+                    headers.insert(HeaderName::from_static("x-api-key"), value);
+                }
+            }
+            Self::Gemini => {
+                headers.insert(ACCEPT, "text/event-stream".parse().unwrap());
+                if let Ok(value) = api_key {
+                    headers.insert(HeaderName::from_static("x-goog-api-key"), value);
+                }
+            }
+            Self::Ollama => {
+                // Set when the server is behind an authenticating proxy
+                if !config.api_key.is_empty()
+                    && let Ok(value) = format!("Bearer {}", config.api_key).parse()
+                {
+                    headers.insert(AUTHORIZATION, value);
+                }
+            }
+        }
+
+        headers
+    }
+
+    // The request body of `messages` in the provider format. The system
+    // messages are moved out of the messages for the providers that take the
+    // system prompt apart
+    pub(crate) fn body(&self, config: &APIConfig, messages: Vec<Message>) -> serde_json::Value {
+        let (system, messages): (Vec<_>, Vec<_>) =
+            messages.into_iter().partition(|m| m.role == "system");
+        let system = system
+            .into_iter()
+            .map(|m| m.content)
+            .filter(|content| !content.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let system = (!system.is_empty()).then_some(system);
+
+        let body = match self {
+            Self::OpenAI => serde_json::to_value(ChatCompletion {
+                messages: with_system_message(system, messages),
+                model: config.api_model.clone(),
+                stream: true,
+                temperature: config.temperature,
+                max_tokens: config.max_tokens,
+            }),
+            Self::Anthropic => serde_json::to_value(AnthropicMessages {
+                model: config.api_model.clone(),
+                messages,
+                max_tokens: config.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+                stream: true,
+                system,
+                temperature: config.temperature,
+            }),
+            Self::Gemini => serde_json::to_value(GeminiGenerateContent {
+                contents: messages
+                    .into_iter()
+                    .map(|m| GeminiContent {
+                        role: Some(
+                            if m.role == "assistant" {
+                                "model"
+                            } else {
+                                "user"
+                            }
+                            .to_string(),
+                        ),
+                        parts: vec![GeminiPart { text: m.content }],
+                    })
+                    .collect(),
+                system_instruction: system.map(|text| GeminiContent {
+                    role: None,
+                    parts: vec![GeminiPart { text }],
+                }),
+                generation_config: GeminiGenerationConfig {
+                    temperature: config.temperature,
+                    max_output_tokens: config.max_tokens,
+                },
+            }),
+            Self::Ollama => serde_json::to_value(OllamaChat {
+                model: config.api_model.clone(),
+                messages: with_system_message(system, messages),
+                stream: true,
+                options: OllamaOptions {
+                    temperature: config.temperature,
+                    num_predict: config.max_tokens,
+                    num_ctx: config.context_tokens,
+                },
+            }),
+        };
+
+        body.unwrap_or_default()
+    }
+
+    // The items of an event of the stream, `data` is the json of a `data:`
+    // line or of a json line
+    pub(crate) fn parse_event(&self, data: &str) -> serde_json::Result<Vec<StreamTextItem>> {
+        match self {
+            Self::OpenAI => response::parse_openai_event(data),
+            Self::Anthropic => response::parse_anthropic_event(data),
+            Self::Gemini => response::parse_gemini_event(data),
+            Self::Ollama => response::parse_ollama_event(data),
+        }
+    }
+}
+
+fn with_system_message(system: Option<String>, messages: Vec<Message>) -> Vec<Message> {
+    system
+        .map(|content| Message {
+            role: "system".to_string(),
+            content,
+        })
+        .into_iter()
+        .chain(messages)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn api_config(provider: Provider) -> APIConfig {
+        APIConfig {
+            provider,
+            api_model: "model".to_string(),
+            api_key: "key".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            message("system", "Be brief"),
+            message("user", "Hi"),
+            message("assistant", "Hello"),
+            message("system", ""),
+            message("user", "Bye"),
+        ]
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            Provider::OpenAI.url(&api_config(Provider::OpenAI)),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(
+            Provider::Anthropic.url(&api_config(Provider::Anthropic)),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(
+            Provider::Gemini.url(&api_config(Provider::Gemini)),
+            "https://generativelanguage.googleapis.com/v1beta/models/model:streamGenerateContent?alt=sse"
+        );
+        assert_eq!(
+            Provider::Ollama.url(&api_config(Provider::Ollama)),
+            "http://localhost:11434/api/chat"
+        );
+
+        let config = APIConfig {
+            api_base_url: " https://api.deepseek.com/v1/ ".to_string(),
+            ..api_config(Provider::OpenAI)
+        };
+        assert_eq!(
+            Provider::OpenAI.url(&config),
+            "https://api.deepseek.com/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        let headers = Provider::OpenAI.headers(&api_config(Provider::OpenAI));
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[ACCEPT], "text/event-stream");
+        assert_eq!(headers[AUTHORIZATION], "Bearer key");
+
+        let headers = Provider::Anthropic.headers(&api_config(Provider::Anthropic));
+        assert_eq!(headers["x-api-key"], "key");
+        assert_eq!(headers["anthropic-version"], ANTHROPIC_VERSION);
+        assert!(!headers.contains_key(AUTHORIZATION));
+
+        let headers = Provider::Gemini.headers(&api_config(Provider::Gemini));
+        assert_eq!(headers["x-goog-api-key"], "key");
+        assert!(!headers.contains_key(AUTHORIZATION));
+
+        let headers = Provider::Ollama.headers(&api_config(Provider::Ollama));
+        assert_eq!(headers[AUTHORIZATION], "Bearer key");
+        assert!(!headers.contains_key(ACCEPT));
+
+        let config = APIConfig {
+            api_key: String::default(),
+            ..api_config(Provider::Ollama)
+        };
+        assert!(
+            !Provider::Ollama
+                .headers(&config)
+                .contains_key(AUTHORIZATION)
+        );
+
+        // A key that isn't a valid header value is left out
+        let config = APIConfig {
+            api_key: "bad\nkey".to_string(),
+            ..api_config(Provider::Anthropic)
+        };
+        assert!(
+            !Provider::Anthropic
+                .headers(&config)
+                .contains_key("x-api-key")
+        );
+    }
+
+    #[test]
+    fn test_body() {
+        let config = APIConfig {
+            temperature: Some(0.5),
+            max_tokens: Some(100),
+            context_tokens: Some(8192),
+            ..api_config(Provider::OpenAI)
+        };
+
+        assert_eq!(
+            Provider::OpenAI.body(&config, messages()),
+            json!({
+                "messages": [
+                    {"role": "system", "content": "Be brief"},
+                    {"role": "user", "content": "Hi"},
+                    {"role": "assistant", "content": "Hello"},
+                    {"role": "user", "content": "Bye"},
+                ],
+                "model": "model",
+                "stream": true,
+                "temperature": 0.5,
+                "max_tokens": 100,
+            })
+        );
+
+        assert_eq!(
+            Provider::Anthropic.body(&config, messages()),
+            json!({
+                "model": "model",
+                "messages": [
+                    {"role": "user", "content": "Hi"},
+                    {"role": "assistant", "content": "Hello"},
+                    {"role": "user", "content": "Bye"},
+                ],
+                "max_tokens": 100,
+                "stream": true,
+                "system": "Be brief",
+                "temperature": 0.5,
+            })
+        );
+
+        assert_eq!(
+            Provider::Gemini.body(&config, messages()),
+            json!({
+                "contents": [
+                    {"role": "user", "parts": [{"text": "Hi"}]},
+                    {"role": "model", "parts": [{"text": "Hello"}]},
+                    {"role": "user", "parts": [{"text": "Bye"}]},
+                ],
+                "systemInstruction": {"parts": [{"text": "Be brief"}]},
+                "generationConfig": {"temperature": 0.5, "maxOutputTokens": 100},
+            })
+        );
+
+        assert_eq!(
+            Provider::Ollama.body(&config, messages()),
+            json!({
+                "model": "model",
+                "messages": [
+                    {"role": "system", "content": "Be brief"},
+                    {"role": "user", "content": "Hi"},
+                    {"role": "assistant", "content": "Hello"},
+                    {"role": "user", "content": "Bye"},
+                ],
+                "stream": true,
+                "options": {"temperature": 0.5, "num_predict": 100, "num_ctx": 8192},
+            })
+        );
+    }
+
+    #[test]
+    fn test_body_defaults() {
+        let messages = vec![message("user", "Hi")];
+
+        let body = Provider::OpenAI.body(&api_config(Provider::OpenAI), messages.clone());
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("max_tokens").is_none());
+
+        let body = Provider::Anthropic.body(&api_config(Provider::Anthropic), messages.clone());
+        assert_eq!(body["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        assert!(body.get("system").is_none());
+
+        let body = Provider::Gemini.body(&api_config(Provider::Gemini), messages.clone());
+        assert!(body.get("systemInstruction").is_none());
+        assert_eq!(body["generationConfig"], json!({}));
+
+        let body = Provider::Ollama.body(&api_config(Provider::Ollama), messages);
+        assert_eq!(body["options"], json!({}));
+    }
 }
@@ -30,6 +30,395 @@ pub(crate) struct ChatCompletionChunk {
 }
 
 #[derive(Serialize, Deserialize)]
-pub(crate) struct Error {
-    pub error: HashMap<String, String>,
+pub(crate) struct AnthropicEvent {
+    #[serde(rename = "type")]
+    pub ty: String,
+
+    #[serde(default)]
+    pub delta: Option<AnthropicDelta>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AnthropicDelta {
+    #[serde(rename = "type", default)]
+    pub ty: Option<String>,
+    pub text: Option<String>,
+    pub thinking: Option<String>,
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GeminiChunk {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GeminiCandidate {
+    pub content: Option<GeminiCandidateContent>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct GeminiCandidateContent {
+    #[serde(default)]
+    pub parts: Vec<GeminiCandidatePart>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct GeminiCandidatePart {
+    pub text: Option<String>,
+
+    // Set on the parts of the thinking summary
+    #[serde(default)]
+    pub thought: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OllamaChunk {
+    pub message: Option<OllamaMessage>,
+
+    #[serde(default)]
+    pub done: bool,
+    pub done_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OllamaMessage {
+    pub content: Option<String>,
+    pub thinking: Option<String>,
+}
+
+// The message of an error body. It's `{"error": "message"}` or
+// `{"error": {"message": "message", ...}}` depending on the provider
+pub(crate) fn error_message(body: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    let error = value.get("error")?;
+
+    match error.as_str() {
+        Some(message) => Some(message.to_string()),
+        None => error
+            .get("message")
+            .and_then(|message| message.as_str())
+            .map(|message| message.to_string())
+            .or_else(|| Some(error.to_string())),
+    }
+}
+
+pub(crate) fn parse_openai_event(data: &str) -> serde_json::Result<Vec<StreamTextItem>> {
+    if data == "[DONE]" {
+        return Ok(vec![finished_item()]);
+    }
+
+    if let Some(etext) = error_message(data) {
+        return Ok(vec![error_item(etext)]);
+    }
+
+    let chunk = serde_json::from_str::<ChatCompletionChunk>(data)?;
+    let mut items = vec![];
+
+    // The last chunk may only have the usage and no choices
+    let Some(choice) = chunk.choices.first() else {
+        return Ok(items);
+    };
+
+    if let Some(Some(text)) = choice.delta.get("content") {
+        items.push(text_item(text.clone()));
+    } else if let Some(Some(text)) = choice.delta.get("reasoning_content") {
+        items.push(reasoning_item(text.clone()));
+    } else if let Some(role) = choice.delta.get("role") {
+        log::info!("role: {role:?}");
+    }
+
+    if let Some(ref reason) = choice.finish_reason {
+        log::info!("finish_reason: {reason}");
+        items.push(finished_item());
+    }
+
+    Ok(items)
+}
+
+pub(crate) fn parse_anthropic_event(data: &str) -> serde_json::Result<Vec<StreamTextItem>> {
+    if let Some(etext) = error_message(data) {
+        return Ok(vec![error_item(etext)]);
+    }
+
+    let event = serde_json::from_str::<AnthropicEvent>(data)?;
+    let item = match (event.ty.as_str(), event.delta) {
+        ("content_block_delta", Some(delta)) => match delta.ty.as_deref() {
+            Some("text_delta") => delta.text.map(text_item),
+            Some("thinking_delta") => delta.thinking.map(reasoning_item),
+            _ => None,
+        },
+        ("message_delta", Some(delta)) => {
+            if let Some(reason) = delta.stop_reason {
+                log::info!("finish_reason: {reason}");
+            }
+            None
+        }
+        ("message_stop", _) => Some(finished_item()),
+        _ => None,
+    };
+
+    Ok(item.into_iter().collect())
+}
+
+pub(crate) fn parse_gemini_event(data: &str) -> serde_json::Result<Vec<StreamTextItem>> {
+    if let Some(etext) = error_message(data) {
+        return Ok(vec![error_item(etext)]);
+    }
+
+    let chunk = serde_json::from_str::<GeminiChunk>(data)?;
+    let mut items = vec![];
+
+    let Some(candidate) = chunk.candidates.into_iter().next() else {
+        return Ok(items);
+    };
+
+    for part in candidate.content.map(|c| c.parts).unwrap_or_default() {
+        match part.text {
+            Some(text) if part.thought => items.push(reasoning_item(text)),
+            Some(text) => items.push(text_item(text)),
+            None => (),
+        }
+    }
+
+    if let Some(reason) = candidate.finish_reason {
+        log::info!("finish_reason: {reason}");
+        items.push(finished_item());
+    }
+
+    Ok(items)
+}
+
+pub(crate) fn parse_ollama_event(data: &str) -> serde_json::Result<Vec<StreamTextItem>> {
+    if let Some(etext) = error_message(data) {
+        return Ok(vec![error_item(etext)]);
+    }
+
+    let chunk = serde_json::from_str::<OllamaChunk>(data)?;
+    let mut items = vec![];
+
+    if let Some(message) = chunk.message {
+        if let Some(text) = message.thinking.filter(|text| !text.is_empty()) {
+            items.push(reasoning_item(text));
+        }
+
+        if let Some(text) = message.content.filter(|text| !text.is_empty()) {
+            items.push(text_item(text));
+        }
+    }
+
+    if chunk.done {
+        log::info!("finish_reason: {}", chunk.done_reason.unwrap_or_default());
+        items.push(finished_item());
+    }
+
+    Ok(items)
+}
+
+fn text_item(text: String) -> StreamTextItem {
+    StreamTextItem {
+        text: Some(text),
+        ..Default::default()
+    }
+}
+
+fn reasoning_item(text: String) -> StreamTextItem {
+    StreamTextItem {
+        reasoning_text: Some(text),
+        ..Default::default()
+    }
+}
+
+fn error_item(etext: String) -> StreamTextItem {
+    StreamTextItem {
+        etext: Some(etext),
+        ..Default::default()
+    }
+}
+
+fn finished_item() -> StreamTextItem {
+    StreamTextItem {
+        finished: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (text, reasoning_text, etext, finished) of an item
+    type Item = (Option<String>, Option<String>, Option<String>, bool);
+
+    fn items(items: serde_json::Result<Vec<StreamTextItem>>) -> Vec<Item> {
+        items
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.text, item.reasoning_text, item.etext, item.finished))
+            .collect()
+    }
+
+    fn text(text: &str) -> Item {
+        (Some(text.to_string()), None, None, false)
+    }
+
+    fn reasoning(text: &str) -> Item {
+        (None, Some(text.to_string()), None, false)
+    }
+
+    fn error(text: &str) -> Item {
+        (None, None, Some(text.to_string()), false)
+    }
+
+    fn finished() -> Item {
+        (None, None, None, true)
+    }
+
+    #[test]
+    fn test_error_message() {
+        assert_eq!(
+            error_message(r#"{"error": "model not found"}"#).as_deref(),
+            Some("model not found")
+        );
+        assert_eq!(
+            error_message(r#"{"error": {"message": "Invalid API key", "type": "auth"}}"#)
+                .as_deref(),
+            Some("Invalid API key")
+        );
+        assert_eq!(
+            error_message(r#"{"error": {"code": 500}}"#).as_deref(),
+            Some(r#"{"code":500}"#)
+        );
+        assert_eq!(error_message(r#"{"choices": []}"#), None);
+        assert_eq!(error_message("[DONE]"), None);
+    }
+
+    #[test]
+    fn test_parse_openai_event() {
+        let role = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4o","choices":[{"index":0,"delta":{"role":"assistant","content":""},"finish_reason":null}]}"#;
+        assert_eq!(items(parse_openai_event(role)), vec![text("")]);
+
+        let content = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":"Hello"},"finish_reason":null}]}"#;
+        assert_eq!(items(parse_openai_event(content)), vec![text("Hello")]);
+
+        let reasoning_content = r#"{"id":"1","created":1700000000,"model":"deepseek-reasoner","choices":[{"index":0,"delta":{"content":null,"reasoning_content":"Think"},"finish_reason":null}]}"#;
+        assert_eq!(
+            items(parse_openai_event(reasoning_content)),
+            vec![reasoning("Think")]
+        );
+
+        // A tool call delta has no text
+        let tool_call = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":null},"finish_reason":"tool_calls"}]}"#;
+        assert_eq!(items(parse_openai_event(tool_call)), vec![finished()]);
+
+        let stop = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4o","choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#;
+        assert_eq!(items(parse_openai_event(stop)), vec![finished()]);
+
+        let usage = r#"{"id":"chatcmpl-1","object":"chat.completion.chunk","created":1700000000,"model":"gpt-4o","choices":[],"usage":{"prompt_tokens":9,"completion_tokens":12}}"#;
+        assert!(items(parse_openai_event(usage)).is_empty());
+
+        assert_eq!(items(parse_openai_event("[DONE]")), vec![finished()]);
+
+        let err = r#"{"error":{"message":"Rate limit reached","type":"requests","code":"rate_limit_exceeded"}}"#;
+        assert_eq!(
+            items(parse_openai_event(err)),
+            vec![error("Rate limit reached")]
+        );
+
+        assert!(parse_openai_event("{").is_err());
+    }
+
+    #[test]
+    fn test_parse_anthropic_event() {
+        let start = r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"model":"claude-sonnet-4-5","stop_reason":null}}"#;
+        assert!(items(parse_anthropic_event(start)).is_empty());
+
+        let block_start =
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#;
+        assert!(items(parse_anthropic_event(block_start)).is_empty());
+
+        let thinking = r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Let me think"}}"#;
+        assert_eq!(
+            items(parse_anthropic_event(thinking)),
+            vec![reasoning("Let me think")]
+        );
+
+        let delta = r#"{"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"Hello"}}"#;
+        assert_eq!(items(parse_anthropic_event(delta)), vec![text("Hello")]);
+
+        // The input of a tool use has no text
+        let tool_use = r#"{"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"{\"city\":"}}"#;
+        assert!(items(parse_anthropic_event(tool_use)).is_empty());
+
+        let ping = r#"{"type":"ping"}"#;
+        assert!(items(parse_anthropic_event(ping)).is_empty());
+
+        let message_delta = r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#;
+        assert!(items(parse_anthropic_event(message_delta)).is_empty());
+
+        let stop = r#"{"type":"message_stop"}"#;
+        assert_eq!(items(parse_anthropic_event(stop)), vec![finished()]);
+
+        let err = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(items(parse_anthropic_event(err)), vec![error("Overloaded")]);
+    }
+
+    #[test]
+    fn test_parse_gemini_event() {
+        let thought = r#"{"candidates":[{"content":{"parts":[{"text":"Planning","thought":true}],"role":"model"},"index":0}],"modelVersion":"gemini-2.5-flash"}"#;
+        assert_eq!(
+            items(parse_gemini_event(thought)),
+            vec![reasoning("Planning")]
+        );
+
+        let parts = r#"{"candidates":[{"content":{"parts":[{"text":"Hello"},{"text":" world"}],"role":"model"},"index":0}],"usageMetadata":{"promptTokenCount":4}}"#;
+        assert_eq!(
+            items(parse_gemini_event(parts)),
+            vec![text("Hello"), text(" world")]
+        );
+
+        // A function call part has no text
+        let function_call = r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"weather","args":{}}}],"role":"model"},"index":0}]}"#;
+        assert!(items(parse_gemini_event(function_call)).is_empty());
+
+        let stop = r#"{"candidates":[{"content":{"parts":[{"text":"!"}],"role":"model"},"finishReason":"STOP","index":0}]}"#;
+        assert_eq!(items(parse_gemini_event(stop)), vec![text("!"), finished()]);
+
+        // A blocked prompt has no candidates
+        let blocked = r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#;
+        assert!(items(parse_gemini_event(blocked)).is_empty());
+
+        let err =
+            r#"{"error":{"code":400,"message":"API key not valid","status":"INVALID_ARGUMENT"}}"#;
+        assert_eq!(
+            items(parse_gemini_event(err)),
+            vec![error("API key not valid")]
+        );
+    }
+
+    #[test]
+    fn test_parse_ollama_event() {
+        let thinking = r#"{"model":"qwen3","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"","thinking":"Hmm"},"done":false}"#;
+        assert_eq!(items(parse_ollama_event(thinking)), vec![reasoning("Hmm")]);
+
+        let content = r#"{"model":"qwen3","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"Hello"},"done":false}"#;
+        assert_eq!(items(parse_ollama_event(content)), vec![text("Hello")]);
+
+        // A tool call message has no content
+        let tool_call = r#"{"model":"qwen3","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"weather","arguments":{}}}]},"done":false}"#;
+        assert!(items(parse_ollama_event(tool_call)).is_empty());
+
+        let done = r#"{"model":"qwen3","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","total_duration":1000}"#;
+        assert_eq!(items(parse_ollama_event(done)), vec![finished()]);
+
+        let err = r#"{"error":"model \"qwen3\" not found, try pulling it first"}"#;
+        assert_eq!(
+            items(parse_ollama_event(err)),
+            vec![error(r#"model "qwen3" not found, try pulling it first"#)]
+        );
+    }
 }
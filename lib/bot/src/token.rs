@@ -0,0 +1,144 @@
+// Token counts of the chat messages, to keep the request within the context
+// window of the model. Without the tokenizer of the model the count is
+// estimated from the text, close to what the BPE tokenizers of the chat
+// models give and rather above it
+
+#[cfg(feature = "tokenizer")]
+use std::{path::Path, sync::Arc};
+
+// Tokens of the role and separators the chat templates add to each message
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+#[derive(Debug, Clone, Default)]
+pub enum TokenCounter {
+    #[default]
+    Estimate,
+
+    #[cfg(feature = "tokenizer")]
+    Tokenizer(Arc<tokenizers::Tokenizer>),
+}
+
+impl TokenCounter {
+    // The `tokenizer.json` of the model
+    #[cfg(feature = "tokenizer")]
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let tokenizer = tokenizers::Tokenizer::from_file(path.as_ref())
+            .map_err(|e| crate::Error::Tokenizer(e.to_string()))?;
+        Ok(Self::Tokenizer(Arc::new(tokenizer)))
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Estimate => estimate_tokens(text),
+
+            #[cfg(feature = "tokenizer")]
+            Self::Tokenizer(tokenizer) => match tokenizer.encode(text, false) {
+                Ok(encoding) => encoding.len(),
+                Err(e) => {
+                    log::warn!("tokenize failed, estimate it instead: {e}");
+                    estimate_tokens(text)
+                }
+            },
+        }
+    }
+
+    pub fn count_message(&self, content: &str) -> usize {
+        self.count(content) + MESSAGE_OVERHEAD_TOKENS
+    }
+}
+
+// Latin words take about a token per 4 letters and numbers one per 3 digits,
+// CJK characters and punctuation a token each and the other scripts about a
+// token per 2 letters. Spaces are merged into the next word
+pub fn estimate_tokens(text: &str) -> usize {
+    #[derive(PartialEq)]
+    enum Run {
+        None,
+        Latin,
+        Digit,
+        Other,
+    }
+
+    let chars_per_token = |run: &Run| match run {
+        Run::None => 1,
+        Run::Latin => 4,
+        Run::Digit => 3,
+        Run::Other => 2,
+    };
+
+    let (mut tokens, mut run, mut run_len) = (0, Run::None, 0usize);
+    for c in text.chars() {
+        let next = if c.is_ascii_alphabetic() {
+            Run::Latin
+        } else if c.is_ascii_digit() {
+            Run::Digit
+        } else if c.is_alphabetic() && !is_cjk(c) {
+            Run::Other
+        } else {
+            Run::None
+        };
+
+        if next != run {
+            tokens += run_len.div_ceil(chars_per_token(&run));
+            run_len = 0;
+        }
+
+        match next {
+            Run::None if c.is_whitespace() => (),
+            Run::None => tokens += 1,
+            _ => run_len += 1,
+        }
+        run = next;
+    }
+
+    tokens + run_len.div_ceil(chars_per_token(&run))
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK unified ideographs
+        | '\u{ac00}'..='\u{d7af}'   // Hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2fa1f}' // CJK extensions B - F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("  \n\t"), 0);
+
+        // A token per 4 letters of a word and per 3 digits of a number
+        assert_eq!(estimate_tokens("hello"), 2);
+        assert_eq!(estimate_tokens("hello world"), 4);
+        assert_eq!(estimate_tokens("12345"), 2);
+        assert_eq!(estimate_tokens("abc123"), 2);
+        assert_eq!(estimate_tokens("Hi, world!"), 5);
+
+        // A token per CJK character and punctuation
+        assert_eq!(estimate_tokens("你好，世界！"), 6);
+        assert_eq!(estimate_tokens("こんにちは"), 5);
+        assert_eq!(estimate_tokens("안녕하세요"), 5);
+        assert_eq!(estimate_tokens("你好 world"), 4);
+
+        // A token per 2 letters of the other scripts
+        assert_eq!(estimate_tokens("Привет мир"), 5);
+    }
+
+    #[test]
+    fn test_count_message() {
+        let counter = TokenCounter::default();
+        assert_eq!(counter.count("hello world"), 4);
+        assert_eq!(
+            counter.count_message("hello world"),
+            4 + MESSAGE_OVERHEAD_TOKENS
+        );
+        assert_eq!(counter.count_message(""), MESSAGE_OVERHEAD_TOKENS);
+    }
+}
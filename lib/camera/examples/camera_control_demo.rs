@@ -0,0 +1,35 @@
+use camera::{CameraClient, CameraConfig, CameraResult, KnownCameraControl, query_first_camera};
+use std::{thread, time::Duration};
+
+fn main() -> CameraResult<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    camera::init();
+
+    let mut client = CameraClient::new(query_first_camera()?, CameraConfig::default())?;
+    client.start()?;
+
+    for control in client.controls()? {
+        println!(
+            "{:<16} value: {:<8} range: {} - {} (step {}, default {}){}{}",
+            control.name,
+            control.value,
+            control.min,
+            control.max,
+            control.step,
+            control.default,
+            if control.automatic { " auto" } else { "" },
+            if control.read_only { " read-only" } else { "" },
+        );
+    }
+
+    if let Ok(zoom) = client.control(KnownCameraControl::Zoom) {
+        client.set_control(KnownCameraControl::Zoom, zoom.max)?;
+        println!("zoom: {}", client.control(KnownCameraControl::Zoom)?.value);
+        thread::sleep(Duration::from_secs(2));
+    }
+
+    client.reset_controls()?;
+    client.stop()?;
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use background_remover::Model;
+use camera::{
+    CameraClient, CameraConfig, CameraEffect, CameraEffectConfig, CameraEffectProcessor,
+    CameraResult, query_first_camera,
+};
+use std::{path::PathBuf, thread, time::Duration};
+
+fn main() -> CameraResult<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    camera::init();
+
+    let model = Model::Modnet;
+    let model_path = PathBuf::from("../background-remover/models").join(model.to_filename());
+
+    let config = CameraEffectConfig::default()
+        .with_effect(CameraEffect::BackgroundBlur(12.0))
+        .with_model(model)
+        .with_model_path(model_path)
+        .with_skip_frames(2);
+    let mut processor = CameraEffectProcessor::new(config)?;
+
+    let mut client = CameraClient::new(query_first_camera()?, CameraConfig::default())?;
+    client.start()?;
+
+    std::fs::create_dir_all("tmp")?;
+    for index in 0..50 {
+        match client.last_frame_rgb() {
+            Ok(frame) => {
+                let frame = processor.process(frame)?;
+                if index % 10 == 0 {
+                    frame.save(format!("tmp/camera_effect_{index}.png"))?;
+                }
+            }
+            Err(e) => log::warn!("get frame failed: {e}"),
+        }
+
+        thread::sleep(Duration::from_millis(40));
+    }
+
+    let background = image::RgbImage::from_fn(640, 360, |x, y| {
+        image::Rgb([(x * 255 / 640) as u8, (y * 255 / 360) as u8, 160])
+    });
+    processor.set_effect(CameraEffect::BackgroundReplace(background));
+
+    let frame = processor.process(client.last_frame_rgb()?)?;
+    frame.save("tmp/camera_effect_replace.png")?;
+    log::info!("saved frames to tmp/");
+
+    client.stop()?;
+    Ok(())
+}
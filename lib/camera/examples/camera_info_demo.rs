@@ -1,4 +1,4 @@
-use camera::camera_info::{query_available_cameras, query_camera_id};
+use camera::camera_info::{query_available_cameras, query_camera_id, query_camera_modes};
 
 fn main() {
     let cameras = query_available_cameras();
@@ -11,5 +11,19 @@ fn main() {
     if !cameras.is_empty() {
         let id = query_camera_id(&cameras[0].name);
         println!("{} -> {:?}", cameras[0].name, id);
+
+        if let Ok(id) = id {
+            match query_camera_modes(id) {
+                Ok(modes) => {
+                    for mode in modes {
+                        println!(
+                            "    {}x{} @ {}fps ({})",
+                            mode.width, mode.height, mode.fps, mode.format
+                        );
+                    }
+                }
+                Err(e) => println!("    query modes failed: {e}"),
+            }
+        }
     }
 }
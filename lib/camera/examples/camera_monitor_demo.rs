@@ -0,0 +1,15 @@
+use camera::{CameraEvent, CameraMonitor};
+use std::time::Duration;
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    camera::init();
+
+    let _monitor = CameraMonitor::start(Duration::from_secs(1), |event| match event {
+        CameraEvent::Connected(info) => println!("connected: {} ({})", info.name, info.index),
+        CameraEvent::Disconnected(info) => println!("disconnected: {} ({})", info.name, info.index),
+    });
+
+    println!("Plug cameras in and out, exiting in 60 seconds...");
+    std::thread::sleep(Duration::from_secs(60));
+}
@@ -0,0 +1,36 @@
+use camera::{
+    CameraResult, RgbaImage, VirtualCamera, VirtualCameraConfig, query_virtual_cameras,
+};
+use std::{thread, time::Duration};
+
+fn main() -> CameraResult<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    log::info!("virtual cameras: {:?}", query_virtual_cameras());
+
+    let (width, height, fps) = (1280, 720, 30);
+    let config = VirtualCameraConfig::default()
+        .with_width(width)
+        .with_height(height)
+        .with_fps(fps);
+
+    let mut camera = VirtualCamera::open(config)?;
+
+    // A moving color gradient, open the virtual camera in any video call application
+    for frame_index in 0..fps * 30 {
+        let shift = (frame_index * 4) % 256;
+        let frame = RgbaImage::from_fn(width, height, |x, y| {
+            camera::Rgba([
+                ((x * 255 / width + shift) % 256) as u8,
+                (y * 255 / height) as u8,
+                (255 - shift) as u8,
+                255,
+            ])
+        });
+
+        camera.send_frame(&frame)?;
+        thread::sleep(Duration::from_millis(1000 / fps as u64));
+    }
+
+    Ok(())
+}
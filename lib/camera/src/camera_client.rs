@@ -1,17 +1,34 @@
-use crate::{CameraError, CameraResult, rgb_to_rgba, rgba_to_rgb};
+use crate::{
+    CameraError, CameraResult,
+    camera_control::{CameraControlInfo, control_setter},
+    camera_info::{CameraMode, query_camera_id, select_mode, supported_modes},
+    rgb_to_rgba, rgba_to_rgb,
+};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::{RgbImage, RgbaImage, imageops};
 use nokhwa::{
     CallbackCamera,
     pixel_format::{RgbAFormat, RgbFormat},
-    utils::{CameraIndex, RequestedFormat, RequestedFormatType, Resolution},
+    utils::{
+        CameraIndex, FrameFormat, KnownCameraControl, RequestedFormat, RequestedFormatType,
+        Resolution,
+    },
 };
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
+/// A running camera without new frames for this long is considered disconnected
+const FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Delay between two attempts to reopen a disconnected camera
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum PixelFormat {
     #[default]
@@ -36,51 +53,65 @@ pub struct CameraConfig {
     #[setters[strip_option]]
     pub height: Option<u32>,
 
+    /// Native camera format, e.g. MJPEG for high resolutions at high frame rates.
+    /// None picks the best format for the requested size and frame rate.
+    #[derivative(Default(value = "None"))]
+    #[setters[strip_option]]
+    pub frame_format: Option<FrameFormat>,
+
     #[derivative(Default(value = "PixelFormat::RGBA"))]
     pub pixel_format: PixelFormat,
 
     #[derivative(Default(value = "false"))]
     pub mirror_horizontal: bool,
+
+    /// Reopen the camera when it is unplugged and plugged in again while running
+    #[derivative(Default(value = "true"))]
+    pub auto_reconnect: bool,
 }
 
 pub struct CameraClient {
     camera: Option<CallbackCamera>,
+    camera_index: CameraIndex,
+    camera_name: String,
+    config: CameraConfig,
     is_running: Arc<AtomicBool>,
     pixel_format: PixelFormat,
     mirror_horizontal: bool,
+    frame_count: Arc<AtomicU64>,
+    watchdog: FrameWatchdog,
+
+    // Controls changed by the user, restored when the camera is reopened
+    control_values: Vec<(KnownCameraControl, f64)>,
+}
+
+/// Tracks the frames of a running camera to detect a lost device
+struct FrameWatchdog {
+    last_count: u64,
+    last_frame_at: Instant,
+    last_reconnect_at: Option<Instant>,
 }
 
 impl CameraClient {
     pub fn new(camera_index: CameraIndex, config: CameraConfig) -> CameraResult<Self> {
-        let pixel_format = config.pixel_format;
-        let mirror_horizontal = config.mirror_horizontal;
-        let format_type = RequestedFormatType::AbsoluteHighestFrameRate;
-        let format = match pixel_format {
-            PixelFormat::RGBA => RequestedFormat::new::<RgbAFormat>(format_type),
-            PixelFormat::RGB => RequestedFormat::new::<RgbFormat>(format_type),
-        };
-
-        let mut camera = CallbackCamera::new(camera_index, format, move |_| {})
-            .map_err(|e| CameraError::InitializationError(e.to_string()))?;
-
-        if let Some(fps) = config.fps
-            && let Err(e) = camera.set_frame_rate(fps)
-        {
-            log::warn!("camera set frame rate ({fps}) failed: {e}");
-        }
-
-        if let Some(w) = config.width
-            && let Some(h) = config.height
-            && let Err(e) = camera.set_resolution(Resolution::new(w, h))
-        {
-            log::warn!("camera set resolution ({w} x {h}) failed: {e}");
-        }
+        let frame_count = Arc::new(AtomicU64::new(0));
+        let camera = open_camera(camera_index.clone(), &config, frame_count.clone())?;
 
         Ok(Self {
+            camera_name: camera.info().human_name(),
             camera: Some(camera),
+            camera_index,
+            pixel_format: config.pixel_format,
+            mirror_horizontal: config.mirror_horizontal,
+            config,
             is_running: Arc::new(AtomicBool::new(false)),
-            pixel_format,
-            mirror_horizontal,
+            frame_count,
+            watchdog: FrameWatchdog {
+                last_count: 0,
+                last_frame_at: Instant::now(),
+                last_reconnect_at: None,
+            },
+            control_values: vec![],
         })
     }
 
@@ -90,6 +121,7 @@ impl CameraClient {
                 .open_stream()
                 .map_err(|e| CameraError::StartError(e.to_string()))?;
             self.is_running.store(true, Ordering::Relaxed);
+            self.watchdog.last_frame_at = Instant::now();
             Ok(())
         } else {
             Err(CameraError::InitializationError(
@@ -105,12 +137,18 @@ impl CameraClient {
                 .map_err(|e| CameraError::StopError(e.to_string()))?;
             self.is_running.store(false, Ordering::Relaxed);
             Ok(())
+        } else if self.is_running() {
+            // The camera was lost while running
+            self.is_running.store(false, Ordering::Relaxed);
+            Ok(())
         } else {
             Err(CameraError::StopError("Camera not initialized".to_string()))
         }
     }
 
-    pub fn last_frame_rgba(&self) -> CameraResult<RgbaImage> {
+    pub fn last_frame_rgba(&mut self) -> CameraResult<RgbaImage> {
+        self.check_connection()?;
+
         match self.camera {
             Some(ref c) => {
                 let buffer = c.last_frame()?;
@@ -136,7 +174,9 @@ impl CameraClient {
         }
     }
 
-    pub fn last_frame_rgb(&self) -> CameraResult<RgbImage> {
+    pub fn last_frame_rgb(&mut self) -> CameraResult<RgbImage> {
+        self.check_connection()?;
+
         match self.camera {
             Some(ref c) => {
                 let buffer = c.last_frame()?;
@@ -162,6 +202,181 @@ impl CameraClient {
         }
     }
 
+    /// All capture modes supported by the camera
+    pub fn supported_modes(&mut self) -> CameraResult<Vec<CameraMode>> {
+        match self.camera {
+            Some(ref mut c) => supported_modes(c),
+            None => Err(CameraError::InitializationError("No camera".to_string())),
+        }
+    }
+
+    /// The current capture mode
+    pub fn mode(&self) -> CameraResult<CameraMode> {
+        match self.camera {
+            Some(ref c) => Ok(c.camera_format()?.into()),
+            None => Err(CameraError::InitializationError("No camera".to_string())),
+        }
+    }
+
+    /// Switch to a capture mode returned by [`CameraClient::supported_modes`].
+    /// The stream is restarted if it is running.
+    pub fn set_mode(&mut self, mode: CameraMode) -> CameraResult<()> {
+        let pixel_format = self.pixel_format;
+
+        match self.camera {
+            Some(ref mut c) => {
+                c.set_camera_requset(requested_format(
+                    pixel_format,
+                    RequestedFormatType::Exact(mode.into()),
+                ))?;
+
+                // Keep the mode when the camera is reopened
+                self.config.width = Some(mode.width);
+                self.config.height = Some(mode.height);
+                self.config.fps = Some(mode.fps);
+                self.config.frame_format = Some(mode.format);
+                Ok(())
+            }
+            None => Err(CameraError::InitializationError("No camera".to_string())),
+        }
+    }
+
+    /// All numeric image controls supported by the camera
+    pub fn controls(&self) -> CameraResult<Vec<CameraControlInfo>> {
+        match self.camera {
+            Some(ref c) => Ok(c
+                .camera_controls()?
+                .iter()
+                .filter_map(CameraControlInfo::from_control)
+                .collect()),
+            None => Err(CameraError::InitializationError("No camera".to_string())),
+        }
+    }
+
+    pub fn control(&self, control: KnownCameraControl) -> CameraResult<CameraControlInfo> {
+        match self.camera {
+            Some(ref c) => {
+                CameraControlInfo::from_control(&c.camera_control(control)?).ok_or_else(|| {
+                    CameraError::ControlError(format!("control {control} is not numeric"))
+                })
+            }
+            None => Err(CameraError::InitializationError("No camera".to_string())),
+        }
+    }
+
+    /// Set a control, the value is clamped to its range and rounded to its step
+    pub fn set_control(&mut self, control: KnownCameraControl, value: f64) -> CameraResult<()> {
+        let Some(ref mut c) = self.camera else {
+            return Err(CameraError::InitializationError("No camera".to_string()));
+        };
+
+        let value = apply_control(c, control, value)?;
+
+        self.control_values.retain(|(item, _)| *item != control);
+        self.control_values.push((control, value));
+        Ok(())
+    }
+
+    /// Restore the default value of a control
+    pub fn reset_control(&mut self, control: KnownCameraControl) -> CameraResult<()> {
+        let default = self.control(control)?.default;
+        self.set_control(control, default)?;
+        self.control_values.retain(|(item, _)| *item != control);
+        Ok(())
+    }
+
+    /// Restore the default values of all writable controls
+    pub fn reset_controls(&mut self) -> CameraResult<()> {
+        for info in self.controls()? {
+            if info.read_only {
+                continue;
+            }
+
+            if let Err(e) = self.reset_control(info.control) {
+                log::warn!("camera reset control {} failed: {e}", info.name);
+            }
+        }
+
+        self.control_values.clear();
+        Ok(())
+    }
+
+    /// Whether the camera is running but was lost and not reopened yet
+    pub fn is_disconnected(&self) -> bool {
+        self.is_running() && (self.camera.is_none() || self.watchdog.last_reconnect_at.is_some())
+    }
+
+    /// Detect a stalled stream and reopen the camera once it is back
+    fn check_connection(&mut self) -> CameraResult<()> {
+        if !self.config.auto_reconnect || !self.is_running() {
+            return Ok(());
+        }
+
+        let count = self.frame_count.load(Ordering::Relaxed);
+        if count != self.watchdog.last_count {
+            self.watchdog.last_count = count;
+            self.watchdog.last_frame_at = Instant::now();
+            self.watchdog.last_reconnect_at = None;
+            return Ok(());
+        }
+
+        if self.camera.is_some() && self.watchdog.last_frame_at.elapsed() < FRAME_TIMEOUT {
+            return Ok(());
+        }
+
+        if self
+            .watchdog
+            .last_reconnect_at
+            .is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL)
+        {
+            return Err(CameraError::Disconnected);
+        }
+
+        if self.watchdog.last_reconnect_at.is_none() {
+            log::warn!(
+                "camera {} stopped delivering frames, reconnecting",
+                self.camera_name
+            );
+        }
+        self.watchdog.last_reconnect_at = Some(Instant::now());
+
+        // Release the lost device before opening it again
+        self.camera = None;
+
+        match self.reopen() {
+            Ok(_) => {
+                log::info!("camera {} reconnected", self.camera_name);
+                self.watchdog.last_frame_at = Instant::now();
+                Ok(())
+            }
+            Err(e) => {
+                log::debug!("reopen camera {} failed: {e}", self.camera_name);
+                Err(CameraError::Disconnected)
+            }
+        }
+    }
+
+    fn reopen(&mut self) -> CameraResult<()> {
+        // The device index may change when it is plugged in again
+        let index =
+            query_camera_id(&self.camera_name).unwrap_or_else(|_| self.camera_index.clone());
+
+        let mut camera = open_camera(index.clone(), &self.config, self.frame_count.clone())?;
+        camera
+            .open_stream()
+            .map_err(|e| CameraError::StartError(e.to_string()))?;
+
+        for (control, value) in &self.control_values {
+            if let Err(e) = apply_control(&mut camera, *control, *value) {
+                log::warn!("camera restore control {control} failed: {e}");
+            }
+        }
+
+        self.camera_index = index;
+        self.camera = Some(camera);
+        Ok(())
+    }
+
     pub fn is_running(&self) -> bool {
         self.is_running.load(Ordering::Relaxed)
     }
@@ -177,6 +392,113 @@ impl CameraClient {
     }
 }
 
+fn open_camera(
+    camera_index: CameraIndex,
+    config: &CameraConfig,
+    frame_count: Arc<AtomicU64>,
+) -> CameraResult<CallbackCamera> {
+    let format = requested_format(
+        config.pixel_format,
+        RequestedFormatType::AbsoluteHighestFrameRate,
+    );
+
+    let mut camera = CallbackCamera::new(camera_index, format, move |_| {
+        frame_count.fetch_add(1, Ordering::Relaxed);
+    })
+    .map_err(|e| CameraError::InitializationError(e.to_string()))?;
+
+    let size = config.width.zip(config.height);
+    if size.is_some() || config.fps.is_some() || config.frame_format.is_some() {
+        negotiate_mode(
+            &mut camera,
+            config.pixel_format,
+            size,
+            config.fps,
+            config.frame_format,
+        );
+    }
+
+    Ok(camera)
+}
+
+/// Set a control and return the value actually requested
+fn apply_control(
+    camera: &mut CallbackCamera,
+    control: KnownCameraControl,
+    value: f64,
+) -> CameraResult<f64> {
+    let current = camera.camera_control(control)?;
+    let info = CameraControlInfo::from_control(&current)
+        .ok_or_else(|| CameraError::ControlError(format!("control {control} is not numeric")))?;
+
+    if info.read_only {
+        return Err(CameraError::ControlError(format!(
+            "control {control} is read only"
+        )));
+    }
+
+    let value = info.normalize(value);
+    camera
+        .set_camera_control(control, control_setter(&current, value)?)
+        .map_err(|e| CameraError::ControlError(format!("set control {control} failed: {e}")))?;
+
+    Ok(value)
+}
+
+fn requested_format(
+    pixel_format: PixelFormat,
+    format_type: RequestedFormatType,
+) -> RequestedFormat<'static> {
+    match pixel_format {
+        PixelFormat::RGBA => RequestedFormat::new::<RgbAFormat>(format_type),
+        PixelFormat::RGB => RequestedFormat::new::<RgbFormat>(format_type),
+    }
+}
+
+/// Request the supported mode closest to the configuration. Falls back to
+/// setting the frame rate and resolution one by one when the camera can't
+/// enumerate its modes.
+fn negotiate_mode(
+    camera: &mut CallbackCamera,
+    pixel_format: PixelFormat,
+    size: Option<(u32, u32)>,
+    fps: Option<u32>,
+    frame_format: Option<FrameFormat>,
+) {
+    let modes = match supported_modes(camera) {
+        Ok(modes) => modes,
+        Err(e) => {
+            log::warn!("query camera modes failed: {e}");
+            vec![]
+        }
+    };
+
+    if let Some(mode) = select_mode(&modes, size, fps, frame_format) {
+        match camera.set_camera_requset(requested_format(
+            pixel_format,
+            RequestedFormatType::Exact(mode.into()),
+        )) {
+            Ok(format) => {
+                log::info!("camera mode: {}", format);
+                return;
+            }
+            Err(e) => log::warn!("camera set mode ({mode:?}) failed: {e}"),
+        }
+    }
+
+    if let Some(fps) = fps
+        && let Err(e) = camera.set_frame_rate(fps)
+    {
+        log::warn!("camera set frame rate ({fps}) failed: {e}");
+    }
+
+    if let Some((w, h)) = size
+        && let Err(e) = camera.set_resolution(Resolution::new(w, h))
+    {
+        log::warn!("camera set resolution ({w} x {h}) failed: {e}");
+    }
+}
+
 impl Drop for CameraClient {
     fn drop(&mut self) {
         if self.is_running() {
@@ -0,0 +1,150 @@
+//! Camera image controls (UVC on Linux and Windows): exposure, gain, white
+//! balance, focus, zoom, pan/tilt, etc.
+//!
+//! The backends describe their controls in different ways (integer ranges,
+//! floats, booleans or enums), they are all mapped to a numeric range here
+//! so a settings panel can show them as sliders.
+
+use crate::{CameraError, CameraResult};
+use nokhwa::utils::{
+    CameraControl, ControlValueDescription, ControlValueSetter, KnownCameraControl,
+    KnownCameraControlFlag,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraControlInfo {
+    pub control: KnownCameraControl,
+    pub name: String,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+
+    /// 0 when any value in the range is accepted
+    pub step: f64,
+    pub default: f64,
+
+    /// Managed by the driver, e.g. auto exposure or auto white balance
+    pub automatic: bool,
+    pub read_only: bool,
+}
+
+impl CameraControlInfo {
+    /// Only numeric controls are supported, `None` for the other kinds
+    pub(crate) fn from_control(control: &CameraControl) -> Option<Self> {
+        let (value, min, max, step, default) = match control.description() {
+            ControlValueDescription::IntegerRange {
+                min,
+                max,
+                value,
+                step,
+                default,
+            } => (
+                *value as f64,
+                *min as f64,
+                *max as f64,
+                *step as f64,
+                *default as f64,
+            ),
+            ControlValueDescription::FloatRange {
+                min,
+                max,
+                value,
+                step,
+                default,
+            } => (*value, *min, *max, *step, *default),
+            ControlValueDescription::Integer {
+                value,
+                default,
+                step,
+            } => (
+                *value as f64,
+                i64::MIN as f64,
+                i64::MAX as f64,
+                *step as f64,
+                *default as f64,
+            ),
+            ControlValueDescription::Float {
+                value,
+                default,
+                step,
+            } => (*value, f64::MIN, f64::MAX, *step, *default),
+            ControlValueDescription::Boolean { value, default } => {
+                (*value as u8 as f64, 0.0, 1.0, 1.0, *default as u8 as f64)
+            }
+            ControlValueDescription::Enum {
+                value,
+                possible,
+                default,
+            } => (
+                *value as f64,
+                possible.iter().min().copied().unwrap_or(*value) as f64,
+                possible.iter().max().copied().unwrap_or(*value) as f64,
+                0.0,
+                *default as f64,
+            ),
+            _ => return None,
+        };
+
+        let flags = control.flag();
+
+        Some(Self {
+            control: control.control(),
+            name: control.name().to_string(),
+            value,
+            min,
+            max,
+            step,
+            default,
+            automatic: flags.contains(&KnownCameraControlFlag::Automatic)
+                || flags.contains(&KnownCameraControlFlag::Continuous),
+            read_only: flags.contains(&KnownCameraControlFlag::ReadOnly)
+                || flags.contains(&KnownCameraControlFlag::Disabled),
+        })
+    }
+
+    /// Round the value to the nearest step and clamp it to the range
+    pub fn normalize(&self, value: f64) -> f64 {
+        // Steps are counted from the default value, the range may be unbounded
+        let value = if self.step > 0.0 {
+            self.default + ((value - self.default) / self.step).round() * self.step
+        } else {
+            value
+        };
+
+        value.clamp(self.min, self.max)
+    }
+}
+
+/// Build the setter of `value` in the kind of value the control expects
+pub(crate) fn control_setter(
+    control: &CameraControl,
+    value: f64,
+) -> CameraResult<ControlValueSetter> {
+    let setter = match control.description() {
+        ControlValueDescription::Integer { .. } | ControlValueDescription::IntegerRange { .. } => {
+            ControlValueSetter::Integer(value.round() as i64)
+        }
+        ControlValueDescription::Float { .. } | ControlValueDescription::FloatRange { .. } => {
+            ControlValueSetter::Float(value)
+        }
+        ControlValueDescription::Boolean { .. } => ControlValueSetter::Boolean(value != 0.0),
+        ControlValueDescription::Enum { possible, .. } => {
+            // Pick the closest possible value
+            let value = value.round() as i64;
+            let value = possible
+                .iter()
+                .copied()
+                .min_by_key(|v| v.abs_diff(value))
+                .unwrap_or(value);
+            ControlValueSetter::EnumValue(value)
+        }
+        _ => {
+            return Err(CameraError::ControlError(format!(
+                "unsupported value of control {}",
+                control.name()
+            )));
+        }
+    };
+
+    Ok(setter)
+}
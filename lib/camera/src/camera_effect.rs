@@ -0,0 +1,185 @@
+//! Live background effects on the camera track.
+//!
+//! The person is segmented with the `background-remover` ONNX models. Running
+//! the model on every frame is too slow for real time, so it only runs every
+//! few frames. The remover runs in its video matting mode, the masks are
+//! smoothed over time to avoid flickering edges and the last mask follows the
+//! motion on the skipped frames.
+
+use crate::{CameraError, CameraResult};
+use background_remover::{BackgroundRemover, Model, TemporalConfig};
+use derivative::Derivative;
+use derive_setters::Setters;
+use image::{DynamicImage, GrayImage, RgbImage, imageops::FilterType};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default)]
+pub enum CameraEffect {
+    #[default]
+    None,
+
+    /// Blur the background with the given gaussian sigma
+    BackgroundBlur(f32),
+
+    /// Replace the background with an image, it's scaled to fill the frame
+    BackgroundReplace(RgbImage),
+}
+
+impl CameraEffect {
+    pub fn is_none(&self) -> bool {
+        matches!(self, CameraEffect::None)
+    }
+}
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct CameraEffectConfig {
+    #[derivative(Default(value = "CameraEffect::None"))]
+    pub effect: CameraEffect,
+
+    #[derivative(Default(value = "Model::Modnet"))]
+    pub model: Model,
+
+    #[derivative(Default(value = "PathBuf::new()"))]
+    pub model_path: PathBuf,
+
+    /// Frames between two segmentations, the last mask is reused for them
+    #[derivative(Default(value = "2"))]
+    pub skip_frames: u32,
+
+    /// Weight of the previous mask when blending it with a new one (0.0 - 1.0)
+    #[derivative(Default(value = "0.5"))]
+    pub mask_smoothing: f32,
+}
+
+pub struct CameraEffectProcessor {
+    config: CameraEffectConfig,
+    remover: BackgroundRemover,
+    mask: Option<GrayImage>,
+    frame_count: u64,
+
+    // The replacement background scaled to the last frame size
+    background: Option<RgbImage>,
+}
+
+impl CameraEffectProcessor {
+    pub fn new(config: CameraEffectConfig) -> CameraResult<Self> {
+        let mut remover = BackgroundRemover::new(config.model, &config.model_path)
+            .map_err(|e| CameraError::EffectError(e.to_string()))?;
+        remover
+            .set_temporal_config(TemporalConfig::default().with_smoothing(config.mask_smoothing));
+
+        Ok(Self {
+            mask: None,
+            config,
+            remover,
+            frame_count: 0,
+            background: None,
+        })
+    }
+
+    pub fn effect(&self) -> &CameraEffect {
+        &self.config.effect
+    }
+
+    pub fn set_effect(&mut self, effect: CameraEffect) {
+        self.config.effect = effect;
+        self.background = None;
+    }
+
+    /// Apply the effect to a camera frame
+    pub fn process(&mut self, frame: RgbImage) -> CameraResult<RgbImage> {
+        if self.config.effect.is_none() {
+            return Ok(frame);
+        }
+
+        let need_mask = self
+            .frame_count
+            .is_multiple_of(self.config.skip_frames as u64 + 1)
+            || self
+                .mask
+                .as_ref()
+                .is_none_or(|mask| mask.dimensions() != frame.dimensions());
+        self.frame_count += 1;
+
+        let mask = if need_mask {
+            Some(self.remover.get_frame_mask(&frame))
+        } else {
+            self.remover.propagate_frame_mask(&frame).transpose()
+        };
+        if let Some(mask) = mask {
+            self.mask = Some(mask.map_err(|e| CameraError::EffectError(e.to_string()))?);
+        }
+
+        let mask = self.mask.as_ref().expect("mask is updated above");
+
+        if let CameraEffect::BackgroundReplace(ref image) = self.config.effect
+            && self
+                .background
+                .as_ref()
+                .is_none_or(|bg| bg.dimensions() != frame.dimensions())
+        {
+            self.background = Some(fill_background(image, frame.width(), frame.height()));
+        }
+
+        Ok(apply_camera_effect(
+            frame,
+            mask,
+            &self.config.effect,
+            self.background.as_ref(),
+        ))
+    }
+}
+
+/// Composite a camera frame over its processed background with a person mask
+/// (0 = background, 255 = foreground) of the same size.
+///
+/// `background` is the replacement image already scaled to the frame size,
+/// it's scaled here when `None`.
+pub fn apply_camera_effect(
+    frame: RgbImage,
+    mask: &GrayImage,
+    effect: &CameraEffect,
+    background: Option<&RgbImage>,
+) -> RgbImage {
+    if mask.dimensions() != frame.dimensions() {
+        log::warn!(
+            "camera effect mask size {:?} doesn't match frame size {:?}",
+            mask.dimensions(),
+            frame.dimensions()
+        );
+        return frame;
+    }
+
+    let background = match effect {
+        CameraEffect::None => return frame,
+        CameraEffect::BackgroundBlur(sigma) => image::imageops::fast_blur(&frame, sigma.max(0.1)),
+        CameraEffect::BackgroundReplace(image) => match background {
+            Some(bg) if bg.dimensions() == frame.dimensions() => bg.clone(),
+            _ => fill_background(image, frame.width(), frame.height()),
+        },
+    };
+
+    let mut output = background;
+    for ((out, fg), alpha) in output.pixels_mut().zip(frame.pixels()).zip(mask.pixels()) {
+        let alpha = alpha[0] as u32;
+        for c in 0..3 {
+            out[c] = ((fg[c] as u32 * alpha + out[c] as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+
+    output
+}
+
+/// Scale a replacement background to fill `width` x `height`, cropping the overflow
+pub fn fill_background(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    if image.dimensions() == (width, height) {
+        return image.clone();
+    }
+
+    DynamicImage::ImageRgb8(image.clone())
+        .resize_to_fill(width, height, FilterType::Triangle)
+        .to_rgb8()
+}
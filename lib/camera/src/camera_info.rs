@@ -1,7 +1,10 @@
 use crate::{CameraError, CameraResult};
 use nokhwa::{
     CallbackCamera, query,
-    utils::{ApiBackend, CameraIndex, RequestedFormat, RequestedFormatType},
+    utils::{
+        ApiBackend, CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType,
+        Resolution,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -11,6 +14,49 @@ pub struct CameraInfo {
     pub description: String,
 }
 
+/// A capture mode supported by a camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CameraMode {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+
+    /// Native format of the camera, MJPEG frames are decoded by the client
+    pub format: FrameFormat,
+}
+
+impl CameraMode {
+    pub fn new(width: u32, height: u32, fps: u32, format: FrameFormat) -> Self {
+        Self {
+            width,
+            height,
+            fps,
+            format,
+        }
+    }
+}
+
+impl From<CameraFormat> for CameraMode {
+    fn from(format: CameraFormat) -> Self {
+        Self::new(
+            format.width(),
+            format.height(),
+            format.frame_rate(),
+            format.format(),
+        )
+    }
+}
+
+impl From<CameraMode> for CameraFormat {
+    fn from(mode: CameraMode) -> Self {
+        CameraFormat::new(
+            Resolution::new(mode.width, mode.height),
+            mode.format,
+            mode.fps,
+        )
+    }
+}
+
 pub fn query_available_cameras() -> Vec<CameraInfo> {
     let cameras = match query(ApiBackend::Auto) {
         Ok(cameras) => cameras,
@@ -51,6 +97,83 @@ pub fn query_first_camera() -> CameraResult<CameraIndex> {
         .ok_or(CameraError::QueryError("No available cameras found".to_string()))
 }
 
+/// Get all capture modes of a camera, sorted by resolution and frame rate (highest first)
+pub fn query_camera_modes(index: CameraIndex) -> CameraResult<Vec<CameraMode>> {
+    let format = RequestedFormat::new::<nokhwa::pixel_format::RgbAFormat>(
+        RequestedFormatType::AbsoluteHighestFrameRate,
+    );
+
+    let mut camera = CallbackCamera::new(index, format, |_| {})
+        .map_err(|e| CameraError::QueryError(e.to_string()))?;
+
+    supported_modes(&mut camera)
+}
+
+pub(crate) fn supported_modes(camera: &mut CallbackCamera) -> CameraResult<Vec<CameraMode>> {
+    let mut modes = vec![];
+
+    for format in camera.compatible_fourcc()? {
+        for (resolution, frame_rates) in camera.compatible_list_by_resolution(format)? {
+            modes.extend(
+                frame_rates.into_iter().map(|fps| {
+                    CameraMode::new(resolution.width(), resolution.height(), fps, format)
+                }),
+            );
+        }
+    }
+
+    modes.sort_by(|a, b| {
+        (b.width * b.height, b.fps)
+            .cmp(&(a.width * a.height, a.fps))
+            .then_with(|| format_rank(a.format).cmp(&format_rank(b.format)))
+    });
+    modes.dedup();
+
+    Ok(modes)
+}
+
+/// Pick the supported mode that best matches the requested size, frame rate
+/// and format. Missing values prefer the highest resolution and frame rate.
+pub(crate) fn select_mode(
+    modes: &[CameraMode],
+    size: Option<(u32, u32)>,
+    fps: Option<u32>,
+    format: Option<FrameFormat>,
+) -> Option<CameraMode> {
+    modes
+        .iter()
+        .filter(|mode| format.is_none_or(|format| mode.format == format))
+        .min_by_key(|mode| {
+            let size_distance = match size {
+                Some((width, height)) => mode.width.abs_diff(width) + mode.height.abs_diff(height),
+                None => u32::MAX - mode.width * mode.height,
+            };
+
+            let fps_distance = match fps {
+                Some(fps) => mode.fps.abs_diff(fps),
+                None => u32::MAX - mode.fps,
+            };
+
+            // Requested values are matched before the preferences for missing ones
+            let (first, second) = match (size, fps) {
+                (None, Some(_)) => (fps_distance, size_distance),
+                _ => (size_distance, fps_distance),
+            };
+
+            (first, second, format_rank(mode.format))
+        })
+        .copied()
+}
+
+/// Raw formats are cheaper to convert than MJPEG, grayscale is the last resort
+fn format_rank(format: FrameFormat) -> u8 {
+    match format {
+        FrameFormat::YUYV | FrameFormat::NV12 | FrameFormat::RAWRGB | FrameFormat::RAWBGR => 0,
+        FrameFormat::MJPEG => 1,
+        FrameFormat::GRAY => 2,
+    }
+}
+
 fn verify_camera(index: CameraIndex) -> bool {
     let format = RequestedFormat::new::<nokhwa::pixel_format::RgbAFormat>(
         RequestedFormatType::AbsoluteHighestFrameRate,
@@ -0,0 +1,336 @@
+use crate::CameraInfo;
+use nokhwa::{query, utils::ApiBackend};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub enum CameraEvent {
+    Connected(CameraInfo),
+    Disconnected(CameraInfo),
+}
+
+/// Watches for cameras being plugged in and out.
+///
+/// The hotplug events come from udev on Linux and from a `DeviceWatcher` on
+/// Windows, the device list is only queried when a camera is plugged in or
+/// out. The other platforms poll the device list of the backend.
+pub struct CameraMonitor {
+    stop_sig: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CameraMonitor {
+    /// Start watching. Cameras present at start are reported as `Connected` first.
+    ///
+    /// `interval` is how often the stop signal is checked, and how often the
+    /// device list is polled without the hotplug events.
+    pub fn start(
+        interval: Duration,
+        mut callback: impl FnMut(CameraEvent) + Send + 'static,
+    ) -> Self {
+        let stop_sig = Arc::new(AtomicBool::new(false));
+        let stop_sig_clone = stop_sig.clone();
+
+        let handle = thread::spawn(move || {
+            // Started before the first query, so no camera plugged in meanwhile is missed
+            let watcher = Watcher::new();
+            let mut cameras = vec![];
+            refresh(&mut cameras, &mut callback);
+
+            while !stop_sig_clone.load(Ordering::Relaxed) {
+                let changes = watcher.wait(interval);
+                if changes.is_empty() {
+                    continue;
+                }
+
+                // A camera plugged out and in again before the query is still reported
+                for change in &changes {
+                    if let DeviceChange::Removed(Some(index)) = change {
+                        cameras.retain(|camera| {
+                            if &camera.index != index {
+                                return true;
+                            }
+
+                            let event = CameraEvent::Disconnected(camera.clone());
+                            log::info!("{event:?}");
+                            callback(event);
+                            false
+                        });
+                    }
+                }
+
+                refresh(&mut cameras, &mut callback);
+            }
+        });
+
+        Self {
+            stop_sig,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_sig.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
+
+impl Drop for CameraMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Debug)]
+enum DeviceChange {
+    /// A device is added, or the device list has to be polled
+    Changed,
+
+    /// A device is removed, with its camera index if the platform tells it
+    Removed(Option<String>),
+}
+
+enum Watcher {
+    #[cfg(target_os = "linux")]
+    Udev(udev_watcher::Watcher),
+
+    #[cfg(target_os = "windows")]
+    DeviceWatcher(device_watcher::Watcher),
+
+    Poll,
+}
+
+impl Watcher {
+    fn new() -> Self {
+        #[cfg(target_os = "linux")]
+        match udev_watcher::Watcher::new() {
+            Ok(watcher) => return Watcher::Udev(watcher),
+            Err(e) => log::warn!("watch cameras with udev failed, poll them instead: {e}"),
+        }
+
+        #[cfg(target_os = "windows")]
+        match device_watcher::Watcher::new() {
+            Ok(watcher) => return Watcher::DeviceWatcher(watcher),
+            Err(e) => log::warn!("watch cameras with DeviceWatcher failed, poll them instead: {e}"),
+        }
+
+        Watcher::Poll
+    }
+
+    /// The changes in `timeout`, empty if nothing changed
+    fn wait(&self, timeout: Duration) -> Vec<DeviceChange> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watcher::Udev(watcher) => watcher.wait(timeout),
+
+            #[cfg(target_os = "windows")]
+            Watcher::DeviceWatcher(watcher) => watcher.wait(timeout),
+
+            Watcher::Poll => {
+                thread::sleep(timeout);
+                vec![DeviceChange::Changed]
+            }
+        }
+    }
+}
+
+fn query_cameras() -> Option<Vec<CameraInfo>> {
+    match query(ApiBackend::Auto) {
+        Ok(list) => Some(
+            list.into_iter()
+                .map(|camera| CameraInfo {
+                    index: camera.index().to_string(),
+                    name: camera.human_name(),
+                    description: camera.description().to_string(),
+                })
+                .collect(),
+        ),
+        Err(e) => {
+            log::warn!("query cameras failed: {e}");
+            None
+        }
+    }
+}
+
+fn refresh(cameras: &mut Vec<CameraInfo>, callback: &mut impl FnMut(CameraEvent)) {
+    let Some(current) = query_cameras() else {
+        return;
+    };
+
+    for event in diff_cameras(cameras, &current) {
+        log::info!("{event:?}");
+        callback(event);
+    }
+
+    *cameras = current;
+}
+
+fn diff_cameras(old: &[CameraInfo], new: &[CameraInfo]) -> Vec<CameraEvent> {
+    let same = |a: &CameraInfo, b: &CameraInfo| a.index == b.index && a.name == b.name;
+
+    let disconnected = old
+        .iter()
+        .filter(|camera| !new.iter().any(|c| same(c, camera)))
+        .map(|camera| CameraEvent::Disconnected(camera.clone()));
+
+    let connected = new
+        .iter()
+        .filter(|camera| !old.iter().any(|c| same(c, camera)))
+        .map(|camera| CameraEvent::Connected(camera.clone()));
+
+    disconnected.chain(connected).collect()
+}
+
+#[cfg(target_os = "linux")]
+mod udev_watcher {
+    use super::DeviceChange;
+    use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+    use std::{io, os::fd::AsFd, thread, time::Duration};
+    use udev::{EventType, MonitorBuilder, MonitorSocket};
+
+    pub(super) struct Watcher {
+        socket: MonitorSocket,
+    }
+
+    impl Watcher {
+        pub(super) fn new() -> io::Result<Self> {
+            let socket = MonitorBuilder::new()?
+                .match_subsystem("video4linux")?
+                .listen()?;
+
+            Ok(Self { socket })
+        }
+
+        pub(super) fn wait(&self, timeout: Duration) -> Vec<DeviceChange> {
+            let poll_timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+            let mut fds = [PollFd::new(self.socket.as_fd(), PollFlags::POLLIN)];
+
+            match poll(&mut fds, poll_timeout) {
+                Ok(0) => return vec![],
+                Ok(_) => (),
+                Err(e) => {
+                    log::warn!("poll udev monitor failed: {e}");
+                    thread::sleep(timeout);
+                    return vec![];
+                }
+            }
+
+            // The socket is nonblocking, the iterator ends with the queued events
+            self.socket
+                .iter()
+                .filter_map(|event| {
+                    log::debug!("udev event: {event:?}");
+
+                    match event.event_type() {
+                        EventType::Add => Some(DeviceChange::Changed),
+                        EventType::Remove => Some(DeviceChange::Removed(camera_index(
+                            &event.sysname().to_string_lossy(),
+                        ))),
+                        _ => None,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    // `video2` -> `2`, the index of the V4L2 backend
+    fn camera_index(sysname: &str) -> Option<String> {
+        sysname
+            .strip_prefix("video")
+            .filter(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+            .map(|index| index.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod device_watcher {
+    use super::DeviceChange;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+            mpsc::{self, Receiver},
+        },
+        time::Duration,
+    };
+    use windows::{
+        Devices::Enumeration::{
+            DeviceClass, DeviceInformation, DeviceInformationUpdate, DeviceWatcher,
+        },
+        Foundation::TypedEventHandler,
+        core::{IInspectable, Result},
+    };
+
+    pub(super) struct Watcher {
+        watcher: DeviceWatcher,
+        receiver: Receiver<DeviceChange>,
+    }
+
+    impl Watcher {
+        pub(super) fn new() -> Result<Self> {
+            let watcher = DeviceInformation::CreateWatcherDeviceClass(DeviceClass::VideoCapture)?;
+            let (sender, receiver) = mpsc::channel();
+
+            // The present cameras are added before the enumeration completes,
+            // they are reported by the first query
+            let enumerated = Arc::new(AtomicBool::new(false));
+
+            let added = TypedEventHandler::<DeviceWatcher, DeviceInformation>::new({
+                let sender = sender.clone();
+                let enumerated = enumerated.clone();
+                move |_, _| {
+                    if enumerated.load(Ordering::Relaxed) {
+                        _ = sender.send(DeviceChange::Changed);
+                    }
+                    Ok(())
+                }
+            });
+
+            // The index of Media Foundation isn't known from the device id
+            let removed =
+                TypedEventHandler::<DeviceWatcher, DeviceInformationUpdate>::new(move |_, _| {
+                    _ = sender.send(DeviceChange::Removed(None));
+                    Ok(())
+                });
+
+            let enumeration_completed =
+                TypedEventHandler::<DeviceWatcher, IInspectable>::new(move |_, _| {
+                    enumerated.store(true, Ordering::Relaxed);
+                    Ok(())
+                });
+
+            watcher.Added(&added)?;
+            watcher.Removed(&removed)?;
+            watcher.EnumerationCompleted(&enumeration_completed)?;
+            watcher.Start()?;
+
+            Ok(Self { watcher, receiver })
+        }
+
+        pub(super) fn wait(&self, timeout: Duration) -> Vec<DeviceChange> {
+            match self.receiver.recv_timeout(timeout) {
+                Ok(change) => std::iter::once(change)
+                    .chain(self.receiver.try_iter())
+                    .collect(),
+                Err(_) => vec![],
+            }
+        }
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            if let Err(e) = self.watcher.Stop() {
+                log::warn!("stop camera DeviceWatcher failed: {e}");
+            }
+        }
+    }
+}
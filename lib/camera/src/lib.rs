@@ -1,14 +1,28 @@
 pub mod camera_client;
+pub mod camera_control;
+pub mod camera_effect;
 pub mod camera_info;
+pub mod camera_monitor;
 pub mod image_composition;
+pub mod virtual_camera;
 
 pub use camera_client::{CameraClient, CameraConfig, PixelFormat};
-pub use camera_info::{CameraInfo, query_available_cameras, query_camera_id, query_first_camera};
+pub use camera_control::CameraControlInfo;
+pub use camera_effect::{
+    CameraEffect, CameraEffectConfig, CameraEffectProcessor, apply_camera_effect, fill_background,
+};
+pub use camera_info::{
+    CameraInfo, CameraMode, query_available_cameras, query_camera_id, query_camera_modes,
+    query_first_camera,
+};
+pub use camera_monitor::{CameraEvent, CameraMonitor};
 pub use image::{ImageBuffer, Rgb, Rgba, RgbaImage};
 pub use image_composition::{
     MixPositionWithPadding, Shape, ShapeBase, ShapeCircle, ShapeRectangle, mix_images,
     mix_images_rgb,
 };
+pub use nokhwa::utils::{FrameFormat, KnownCameraControl};
+pub use virtual_camera::{VirtualCamera, VirtualCameraConfig, query_virtual_cameras};
 
 pub type CameraResult<T> = Result<T, CameraError>;
 
@@ -29,6 +43,18 @@ pub enum CameraError {
     #[error("No frame available")]
     NoFrameAvailable,
 
+    #[error("Camera disconnected")]
+    Disconnected,
+
+    #[error("Virtual camera error: {0}")]
+    VirtualCameraError(String),
+
+    #[error("Camera effect error: {0}")]
+    EffectError(String),
+
+    #[error("Camera control error: {0}")]
+    ControlError(String),
+
     #[error("Invalid camera index: {0}")]
     InvalidCameraIndex(usize),
 
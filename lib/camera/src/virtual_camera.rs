@@ -0,0 +1,383 @@
+//! Virtual camera output, so a composited recording can be used as a webcam
+//! in video call applications.
+//!
+//! - Linux: a v4l2loopback device (`modprobe v4l2loopback exclusive_caps=1`)
+//! - Windows: the softcam DirectShow filter (`softcam.dll` must be installed)
+
+use crate::{CameraError, CameraResult};
+use derivative::Derivative;
+use derive_setters::Setters;
+use image::{RgbImage, RgbaImage};
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct VirtualCameraConfig {
+    /// Device path on Linux (e.g. `/dev/video10`), the first v4l2loopback
+    /// device is used when not set. Ignored on Windows.
+    #[derivative(Default(value = "None"))]
+    #[setters[strip_option]]
+    pub device: Option<String>,
+
+    /// Frame width, must be even
+    #[derivative(Default(value = "1280"))]
+    pub width: u32,
+
+    /// Frame height
+    #[derivative(Default(value = "720"))]
+    pub height: u32,
+
+    #[derivative(Default(value = "30"))]
+    pub fps: u32,
+}
+
+pub struct VirtualCamera {
+    width: u32,
+    height: u32,
+
+    #[cfg(target_os = "linux")]
+    device: v4l2loopback::Device,
+
+    #[cfg(target_os = "windows")]
+    device: softcam::Device,
+}
+
+impl VirtualCamera {
+    pub fn open(config: VirtualCameraConfig) -> CameraResult<Self> {
+        if config.width == 0 || config.height == 0 || !config.width.is_multiple_of(2) {
+            return Err(CameraError::VirtualCameraError(format!(
+                "invalid frame size {} x {}, the width must be even",
+                config.width, config.height
+            )));
+        }
+
+        if config.fps == 0 {
+            return Err(CameraError::VirtualCameraError(
+                "fps must be greater than 0".to_string(),
+            ));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let path = match config.device {
+                Some(ref device) => device.clone(),
+                None => query_virtual_cameras().into_iter().next().ok_or_else(|| {
+                    CameraError::VirtualCameraError(
+                        "no v4l2loopback device found, load the module with `modprobe v4l2loopback exclusive_caps=1`"
+                            .to_string(),
+                    )
+                })?,
+            };
+
+            let device = v4l2loopback::Device::open(&path, config.width, config.height)?;
+            log::info!(
+                "virtual camera opened: {path} ({} x {} @ {}fps)",
+                config.width,
+                config.height,
+                config.fps
+            );
+
+            Ok(Self {
+                width: config.width,
+                height: config.height,
+                device,
+            })
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            let device = softcam::Device::open(config.width, config.height, config.fps)?;
+            log::info!(
+                "virtual camera opened: softcam ({} x {} @ {}fps)",
+                config.width,
+                config.height,
+                config.fps
+            );
+
+            Ok(Self {
+                width: config.width,
+                height: config.height,
+                device,
+            })
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        {
+            Err(CameraError::VirtualCameraError(
+                "virtual camera is not supported on this platform".to_string(),
+            ))
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Send a frame, its size must match the size of the virtual camera
+    pub fn send_frame(&mut self, frame: &RgbaImage) -> CameraResult<()> {
+        self.check_size(frame.width(), frame.height())?;
+        self.send_raw(frame.as_raw(), 4)
+    }
+
+    /// Send a frame, its size must match the size of the virtual camera
+    pub fn send_frame_rgb(&mut self, frame: &RgbImage) -> CameraResult<()> {
+        self.check_size(frame.width(), frame.height())?;
+        self.send_raw(frame.as_raw(), 3)
+    }
+
+    fn check_size(&self, width: u32, height: u32) -> CameraResult<()> {
+        if width != self.width || height != self.height {
+            return Err(CameraError::VirtualCameraError(format!(
+                "frame size {width} x {height} doesn't match the virtual camera size {} x {}",
+                self.width, self.height
+            )));
+        }
+
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn send_raw(&mut self, data: &[u8], channels: usize) -> CameraResult<()> {
+        #[cfg(target_os = "linux")]
+        {
+            let frame = to_yuyv(data, self.width as usize, self.height as usize, channels);
+            self.device.write(&frame)
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            self.device.send(&to_bgr(data, channels))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        {
+            Err(CameraError::VirtualCameraError(
+                "virtual camera is not supported on this platform".to_string(),
+            ))
+        }
+    }
+}
+
+/// Get the v4l2loopback devices. Always empty on other platforms than Linux.
+pub fn query_virtual_cameras() -> Vec<String> {
+    #[cfg(target_os = "linux")]
+    {
+        // v4l2loopback devices are virtual, real cameras are attached to a bus
+        let mut devices = std::fs::read_dir("/sys/devices/virtual/video4linux")
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| format!("/dev/{}", entry.file_name().to_string_lossy()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        devices.sort_by_key(|device| {
+            device
+                .trim_start_matches("/dev/video")
+                .parse::<u32>()
+                .unwrap_or(u32::MAX)
+        });
+        devices
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        vec![]
+    }
+}
+
+/// Convert RGB/RGBA pixels to packed YUYV 4:2:2 (BT.601, limited range)
+#[allow(dead_code)]
+fn to_yuyv(data: &[u8], width: usize, height: usize, channels: usize) -> Vec<u8> {
+    let mut yuyv = Vec::with_capacity(width * height * 2);
+
+    for row in data.chunks_exact(width * channels).take(height) {
+        for pair in row.chunks_exact(channels * 2) {
+            let (r0, g0, b0) = (pair[0] as i32, pair[1] as i32, pair[2] as i32);
+            let (r1, g1, b1) = (
+                pair[channels] as i32,
+                pair[channels + 1] as i32,
+                pair[channels + 2] as i32,
+            );
+
+            let y = |r: i32, g: i32, b: i32| (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+
+            // Chroma of the average of both pixels
+            let (r, g, b) = ((r0 + r1) / 2, (g0 + g1) / 2, (b0 + b1) / 2);
+            let u = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            let v = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+
+            yuyv.extend_from_slice(&[y(r0, g0, b0), u, y(r1, g1, b1), v]);
+        }
+    }
+
+    yuyv
+}
+
+/// Convert RGB/RGBA pixels to BGR
+#[allow(dead_code)]
+fn to_bgr(data: &[u8], channels: usize) -> Vec<u8> {
+    data.chunks_exact(channels)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0]])
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod v4l2loopback {
+    use crate::{CameraError, CameraResult};
+    use std::{fs::File, io::Write, os::fd::AsRawFd};
+
+    const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+    const V4L2_FIELD_NONE: u32 = 1;
+    const V4L2_COLORSPACE_SRGB: u32 = 8;
+    const V4L2_PIX_FMT_YUYV: u32 = u32::from_le_bytes(*b"YUYV");
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct V4l2PixFormat {
+        width: u32,
+        height: u32,
+        pixelformat: u32,
+        field: u32,
+        bytesperline: u32,
+        sizeimage: u32,
+        colorspace: u32,
+        priv_: u32,
+        flags: u32,
+        ycbcr_enc: u32,
+        quantization: u32,
+        xfer_func: u32,
+    }
+
+    /// `fmt` union of `struct v4l2_format`, 200 bytes aligned like a pointer
+    #[repr(C)]
+    union V4l2FormatUnion {
+        pix: V4l2PixFormat,
+        raw_data: [u8; 200],
+        _align: [usize; 200 / std::mem::size_of::<usize>()],
+    }
+
+    #[repr(C)]
+    pub(super) struct V4l2Format {
+        type_: u32,
+        fmt: V4l2FormatUnion,
+    }
+
+    nix::ioctl_readwrite!(vidioc_s_fmt, b'V', 5, V4l2Format);
+
+    pub(super) struct Device {
+        file: File,
+    }
+
+    impl Device {
+        pub(super) fn open(path: &str, width: u32, height: u32) -> CameraResult<Self> {
+            let file = File::options()
+                .write(true)
+                .open(path)
+                .map_err(|e| CameraError::VirtualCameraError(format!("open {path} failed: {e}")))?;
+
+            let mut format = V4l2Format {
+                type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+                fmt: V4l2FormatUnion { raw_data: [0; 200] },
+            };
+
+            format.fmt.pix = V4l2PixFormat {
+                width,
+                height,
+                pixelformat: V4L2_PIX_FMT_YUYV,
+                field: V4L2_FIELD_NONE,
+                bytesperline: width * 2,
+                sizeimage: width * height * 2,
+                colorspace: V4L2_COLORSPACE_SRGB,
+                priv_: 0,
+                flags: 0,
+                ycbcr_enc: 0,
+                quantization: 0,
+                xfer_func: 0,
+            };
+
+            unsafe { vidioc_s_fmt(file.as_raw_fd(), &mut format) }.map_err(|e| {
+                CameraError::VirtualCameraError(format!("set format of {path} failed: {e}"))
+            })?;
+
+            Ok(Self { file })
+        }
+
+        pub(super) fn write(&mut self, frame: &[u8]) -> CameraResult<()> {
+            self.file
+                .write_all(frame)
+                .map_err(|e| CameraError::VirtualCameraError(format!("write frame failed: {e}")))
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod softcam {
+    use crate::{CameraError, CameraResult};
+    use libloading::{Library, Symbol};
+    use std::ffi::c_void;
+
+    type CreateCamera = unsafe extern "C" fn(i32, i32, f32) -> *mut c_void;
+    type DeleteCamera = unsafe extern "C" fn(*mut c_void);
+    type SendFrame = unsafe extern "C" fn(*mut c_void, *const c_void);
+
+    pub(super) struct Device {
+        library: Library,
+        camera: *mut c_void,
+    }
+
+    // The softcam handle is only used through `&mut self`
+    unsafe impl Send for Device {}
+
+    impl Device {
+        pub(super) fn open(width: u32, height: u32, fps: u32) -> CameraResult<Self> {
+            let library = unsafe { Library::new("softcam.dll") }.map_err(|e| {
+                CameraError::VirtualCameraError(format!("load softcam.dll failed: {e}"))
+            })?;
+
+            let camera = unsafe {
+                let create: Symbol<CreateCamera> = library
+                    .get(b"scCreateCamera\0")
+                    .map_err(|e| CameraError::VirtualCameraError(e.to_string()))?;
+                create(width as i32, height as i32, fps as f32)
+            };
+
+            if camera.is_null() {
+                return Err(CameraError::VirtualCameraError(
+                    "create softcam camera failed, is another instance running?".to_string(),
+                ));
+            }
+
+            Ok(Self { library, camera })
+        }
+
+        /// Send a top-down BGR24 frame
+        pub(super) fn send(&mut self, frame: &[u8]) -> CameraResult<()> {
+            unsafe {
+                let send: Symbol<SendFrame> = self
+                    .library
+                    .get(b"scSendFrame\0")
+                    .map_err(|e| CameraError::VirtualCameraError(e.to_string()))?;
+                send(self.camera, frame.as_ptr() as *const c_void);
+            }
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Device {
+        fn drop(&mut self) {
+            unsafe {
+                if let Ok(delete) = self.library.get::<DeleteCamera>(b"scDeleteCamera\0") {
+                    delete(self.camera);
+                }
+            }
+        }
+    }
+}
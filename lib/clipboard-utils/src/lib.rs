@@ -0,0 +1,167 @@
+//! Copy images, files and text to the system clipboard.
+//!
+//! On Wayland the `wlr-data-control` protocol is used, so no window is
+//! needed and the CLI can copy as well. On Windows the Win32 clipboard is
+//! used.
+
+#[cfg(target_os = "linux")]
+mod wayland;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+use std::{path::PathBuf, thread::JoinHandle};
+
+pub type Result<T> = std::result::Result<T, ClipboardError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClipboardError {
+    #[error("Clipboard unsupported: {0}")]
+    Unsupported(String),
+
+    #[error("Clipboard error: {0}")]
+    Clipboard(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Content {
+    /// An encoded PNG image
+    Image(Vec<u8>),
+
+    /// File references, pasted as the files themselves by the file managers
+    /// and as the paths by the text editors
+    Files(Vec<PathBuf>),
+
+    Text(String),
+}
+
+impl Content {
+    /// The MIME types offered on Wayland, the preferred one first
+    pub fn mime_types(&self) -> &'static [&'static str] {
+        match self {
+            Content::Image(_) => &["image/png"],
+            Content::Files(_) => &[
+                "text/uri-list",
+                "x-special/gnome-copied-files",
+                "text/plain;charset=utf-8",
+                "text/plain",
+            ],
+            Content::Text(_) => &[
+                "text/plain;charset=utf-8",
+                "text/plain",
+                "UTF8_STRING",
+                "STRING",
+                "TEXT",
+            ],
+        }
+    }
+
+    /// The data pasted for `mime_type`
+    pub fn data(&self, mime_type: &str) -> Option<Vec<u8>> {
+        if !self.mime_types().contains(&mime_type) {
+            return None;
+        }
+
+        let data = match self {
+            Content::Image(png) => png.clone(),
+            Content::Files(files) => {
+                let uris = files.iter().map(|file| file_uri(file));
+
+                match mime_type {
+                    "text/uri-list" => uris
+                        .map(|uri| format!("{uri}\r\n"))
+                        .collect::<String>()
+                        .into_bytes(),
+                    "x-special/gnome-copied-files" => std::iter::once("copy".to_string())
+                        .chain(uris)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                        .into_bytes(),
+                    _ => files
+                        .iter()
+                        .map(|file| file.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                        .into_bytes(),
+                }
+            }
+            Content::Text(text) => text.clone().into_bytes(),
+        };
+
+        Some(data)
+    }
+}
+
+/// Keeps the copied content available to the other applications
+pub struct ClipboardOwner {
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ClipboardOwner {
+    /// Block until another application takes the clipboard.
+    ///
+    /// The content is served by this process on Wayland, so a process that
+    /// exits right after copying should wait here first.
+    pub fn wait(self) {
+        if let Some(handle) = self.handle {
+            _ = handle.join();
+        }
+    }
+}
+
+/// Put `content` on the clipboard, replacing what was there.
+///
+/// On Wayland the content is served by a background thread until another
+/// application takes the clipboard.
+pub fn copy(content: Content) -> Result<ClipboardOwner> {
+    if let Content::Files(files) = &content
+        && let Some(file) = files.iter().find(|file| !file.is_absolute())
+    {
+        return Err(ClipboardError::Clipboard(format!(
+            "not an absolute path: {}",
+            file.display()
+        )));
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let handle = wayland::copy(content)?;
+        Ok(ClipboardOwner {
+            handle: Some(handle),
+        })
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::copy(content)?;
+        Ok(ClipboardOwner { handle: None })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        _ = content;
+        Err(ClipboardError::Unsupported(
+            "only Wayland and Windows are supported".to_string(),
+        ))
+    }
+}
+
+// `file://` URI with the bytes other than the unreserved ones and `/`
+// percent-encoded
+fn file_uri(file: &std::path::Path) -> String {
+    let path = file.to_string_lossy();
+
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    uri
+}
@@ -0,0 +1,197 @@
+use crate::{ClipboardError, Content, Result};
+use std::{fs::File, io::Write, thread, thread::JoinHandle};
+use wayland_client::{
+    Connection, Dispatch, QueueHandle, event_created_child,
+    protocol::{wl_registry, wl_seat},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1, zwlr_data_control_manager_v1, zwlr_data_control_offer_v1,
+    zwlr_data_control_source_v1,
+};
+
+struct State {
+    content: Content,
+    seat: Option<wl_seat::WlSeat>,
+    manager: Option<zwlr_data_control_manager_v1::ZwlrDataControlManagerV1>,
+
+    // Another client took the clipboard
+    cancelled: bool,
+}
+
+// The selection is set on the first seat, and the returned thread serves the
+// pastes until the source is cancelled
+pub fn copy(content: Content) -> Result<JoinHandle<()>> {
+    let conn = Connection::connect_to_env()
+        .map_err(|e| ClipboardError::Unsupported(format!("connect to Wayland failed: {e}")))?;
+
+    let mut queue = conn.new_event_queue();
+    let qh = queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+
+    let mut state = State {
+        content,
+        seat: None,
+        manager: None,
+        cancelled: false,
+    };
+
+    queue
+        .roundtrip(&mut state)
+        .map_err(|e| ClipboardError::Clipboard(e.to_string()))?;
+
+    let Some(manager) = state.manager.clone() else {
+        return Err(ClipboardError::Unsupported(
+            "the compositor doesn't implement `wlr-data-control` protocol".to_string(),
+        ));
+    };
+
+    let Some(seat) = state.seat.clone() else {
+        return Err(ClipboardError::Clipboard("no seat found".to_string()));
+    };
+
+    let source = manager.create_data_source(&qh, ());
+    for mime_type in state.content.mime_types() {
+        source.offer(mime_type.to_string());
+    }
+
+    let device = manager.get_data_device(&seat, &qh, ());
+    device.set_selection(Some(&source));
+
+    queue
+        .roundtrip(&mut state)
+        .map_err(|e| ClipboardError::Clipboard(e.to_string()))?;
+
+    log::info!("copy to clipboard: {:?}", state.content.mime_types());
+
+    let handle = thread::spawn(move || {
+        while !state.cancelled {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                log::warn!("dispatch clipboard events failed: {e}");
+                break;
+            }
+        }
+
+        source.destroy();
+        device.destroy();
+        _ = conn.flush();
+
+        log::debug!("clipboard taken by another client");
+    });
+
+    Ok(handle)
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, version.min(7), qh, ()))
+                }
+                "zwlr_data_control_manager_v1" => {
+                    state.manager = Some(registry.bind(name, version.min(2), qh, ()))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Dispatch<zwlr_data_control_source_v1::ZwlrDataControlSourceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
+        event: zwlr_data_control_source_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+                let Some(data) = state.content.data(&mime_type) else {
+                    return;
+                };
+
+                // Blocks until the reader takes all of it or closes the pipe
+                if let Err(e) = File::from(fd).write_all(&data) {
+                    log::warn!("send clipboard data as `{mime_type}` failed: {e}");
+                }
+            }
+            zwlr_data_control_source_v1::Event::Cancelled => state.cancelled = true,
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &zwlr_data_control_device_v1::ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            // The offers of the clipboard aren't read, only released
+            zwlr_data_control_device_v1::Event::Selection { id: Some(offer) }
+            | zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => {
+                offer.destroy()
+            }
+            zwlr_data_control_device_v1::Event::Finished => state.cancelled = true,
+            _ => (),
+        }
+    }
+
+    event_created_child!(State, zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<zwlr_data_control_offer_v1::ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+        _: zwlr_data_control_offer_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwlr_data_control_manager_v1::ZwlrDataControlManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        _: zwlr_data_control_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
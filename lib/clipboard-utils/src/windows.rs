@@ -0,0 +1,165 @@
+use crate::{ClipboardError, Content, Result};
+use image::ImageFormat;
+use std::{io, mem, os::windows::ffi::OsStrExt, path::PathBuf, ptr, slice};
+use winapi::{
+    shared::{
+        minwindef::{BOOL, TRUE, UINT},
+        windef::POINT,
+    },
+    um::{
+        winbase::{GMEM_MOVEABLE, GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock},
+        wingdi::{BI_RGB, BITMAPINFOHEADER},
+        winuser::{
+            CF_DIB, CF_HDROP, CF_UNICODETEXT, CloseClipboard, EmptyClipboard, OpenClipboard,
+            RegisterClipboardFormatW, SetClipboardData,
+        },
+    },
+};
+
+// Registered by the browsers and the office applications to keep the alpha
+// channel, which `CF_DIB` loses in most of the readers
+const PNG_FORMAT: &str = "PNG";
+
+// `DROPFILES` of `shlobj.h`, followed by the paths
+#[repr(C)]
+struct DropFiles {
+    p_files: u32,
+    pt: POINT,
+    f_nc: BOOL,
+    f_wide: BOOL,
+}
+
+pub fn copy(content: Content) -> Result<()> {
+    let formats = formats(&content)?;
+
+    unsafe {
+        if OpenClipboard(ptr::null_mut()) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let result = set_formats(&formats);
+        CloseClipboard();
+        result
+    }
+}
+
+fn formats(content: &Content) -> Result<Vec<(UINT, Vec<u8>)>> {
+    let formats = match content {
+        Content::Image(png) => {
+            let png_format = unsafe { RegisterClipboardFormatW(wide(PNG_FORMAT).as_ptr()) };
+            vec![(png_format, png.clone()), (CF_DIB, dib(png)?)]
+        }
+        Content::Files(files) => vec![(CF_HDROP, drop_files(files))],
+        Content::Text(text) => {
+            let text = wide(text);
+            let bytes =
+                unsafe { slice::from_raw_parts(text.as_ptr() as *const u8, text.len() * 2) };
+            vec![(CF_UNICODETEXT, bytes.to_vec())]
+        }
+    };
+
+    Ok(formats
+        .into_iter()
+        .filter(|(format, _)| *format != 0)
+        .collect())
+}
+
+unsafe fn set_formats(formats: &[(UINT, Vec<u8>)]) -> Result<()> {
+    unsafe {
+        EmptyClipboard();
+
+        for (format, data) in formats {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, data.len());
+            if memory.is_null() {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            let dst = GlobalLock(memory) as *mut u8;
+            if dst.is_null() {
+                GlobalFree(memory);
+                return Err(io::Error::last_os_error().into());
+            }
+
+            ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+            GlobalUnlock(memory);
+
+            // The memory is owned by the system once it's set
+            if SetClipboardData(*format, memory).is_null() {
+                GlobalFree(memory);
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A bottom-up 32-bit BGRA bitmap
+fn dib(png: &[u8]) -> Result<Vec<u8>> {
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+        .map_err(|e| ClipboardError::Clipboard(format!("decode image failed: {e}")))?
+        .to_rgba8();
+
+    let header = BITMAPINFOHEADER {
+        biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+        biWidth: image.width() as i32,
+        biHeight: image.height() as i32,
+        biPlanes: 1,
+        biBitCount: 32,
+        biCompression: BI_RGB,
+        biSizeImage: image.width() * image.height() * 4,
+        biXPelsPerMeter: 0,
+        biYPelsPerMeter: 0,
+        biClrUsed: 0,
+        biClrImportant: 0,
+    };
+
+    let mut data = Vec::with_capacity(header.biSize as usize + header.biSizeImage as usize);
+    data.extend_from_slice(unsafe {
+        slice::from_raw_parts(
+            &header as *const BITMAPINFOHEADER as *const u8,
+            header.biSize as usize,
+        )
+    });
+
+    for row in image.rows().rev() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.0;
+            data.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    Ok(data)
+}
+
+// `DROPFILES` with the wide paths, each ended with a NUL and the list ended
+// with another one
+fn drop_files(files: &[PathBuf]) -> Vec<u8> {
+    let header = DropFiles {
+        p_files: mem::size_of::<DropFiles>() as u32,
+        pt: POINT { x: 0, y: 0 },
+        f_nc: 0,
+        f_wide: TRUE,
+    };
+
+    let paths = files
+        .iter()
+        .flat_map(|file| file.as_os_str().encode_wide().chain(Some(0)))
+        .chain(Some(0))
+        .collect::<Vec<u16>>();
+
+    let mut data = Vec::with_capacity(mem::size_of::<DropFiles>() + paths.len() * 2);
+    data.extend_from_slice(unsafe {
+        slice::from_raw_parts(
+            &header as *const DropFiles as *const u8,
+            mem::size_of::<DropFiles>(),
+        )
+    });
+    data.extend(paths.iter().flat_map(|c| c.to_le_bytes()));
+
+    data
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
@@ -0,0 +1,28 @@
+use cursor_client::{Client, Message};
+use std::time::Duration;
+
+fn main() {
+    env_logger::init();
+
+    loop {
+        match Client::connect() {
+            Ok(mut client) => {
+                log::info!("Connected to wayshot-cursor");
+
+                loop {
+                    match client.recv() {
+                        Ok(Message::Heartbeat) => log::debug!("heartbeat"),
+                        Ok(message) => log::info!("{message:?}"),
+                        Err(e) => {
+                            log::warn!("receive message failed: {e}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => log::warn!("connect to wayshot-cursor failed: {e}"),
+        }
+
+        std::thread::sleep(Duration::from_secs(3));
+    }
+}
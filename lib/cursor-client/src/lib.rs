@@ -0,0 +1,63 @@
+//! Client of the `wayshot-cursor` helper, which shares the cursor, the clicks
+//! and the keys on Wayland, where a normal process can't read them.
+
+mod protocol;
+
+pub use protocol::*;
+
+use std::{io, time::Duration};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+pub const SOCKET_PATH: &str = "/run/wayshot-cursor/wayshot-cursor.sock";
+
+/// How often the server sends a heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Unsupported protocol version: {0}")]
+    Version(u8),
+
+    #[error("Malformed message: {0}")]
+    Malformed(String),
+}
+
+#[cfg(unix)]
+pub struct Client {
+    stream: UnixStream,
+}
+
+#[cfg(unix)]
+impl Client {
+    /// Connect to the server on `SOCKET_PATH`
+    pub fn connect() -> Result<Self> {
+        Self::connect_to(SOCKET_PATH)
+    }
+
+    /// The reads time out after missing a few heartbeats, so a dead server
+    /// isn't waited for forever
+    pub fn connect_to(path: impl AsRef<Path>) -> Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(HEARTBEAT_INTERVAL * 3))?;
+        Ok(Self { stream })
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        Ok(self.stream.set_read_timeout(timeout)?)
+    }
+
+    /// Block until the next message of a known kind
+    pub fn recv(&mut self) -> Result<Message> {
+        loop {
+            if let Some(message) = Message::read_from(&mut self.stream)? {
+                return Ok(message);
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Every message is a header followed by the payload:
+//!
+//! | Field   | Size | Description                         |
+//! |---------|------|-------------------------------------|
+//! | version | 1    | `PROTOCOL_VERSION`                  |
+//! | kind    | 1    | `KIND_*`                            |
+//! | length  | 4    | Little-endian length of the payload |
+//!
+//! The integers of the payloads are little-endian. The messages of unknown
+//! kinds are skipped, so new kinds can be added without a new version.
+
+use crate::{Error, Result};
+use std::io::Read;
+
+pub const PROTOCOL_VERSION: u8 = 1;
+
+pub const HEADER_SIZE: usize = 6;
+
+// Larger payloads are treated as a broken stream
+pub const MAX_PAYLOAD_SIZE: usize = 64 * 1024;
+
+/// `x: i32, y: i32`
+pub const KIND_CURSOR: u8 = 1;
+
+/// `button: u8, pressed: u8, x: i32, y: i32`
+pub const KIND_CLICK: u8 = 2;
+
+/// `pressed: u8, modifiers: u8`, then the UTF-8 label of the key
+pub const KIND_KEY: u8 = 3;
+
+/// Empty, sent every `HEARTBEAT_INTERVAL` so the clients can tell a quiet
+/// server from a dead one
+pub const KIND_HEARTBEAT: u8 = 4;
+
+pub const MODIFIER_CTRL: u8 = 1;
+pub const MODIFIER_SHIFT: u8 = 1 << 1;
+pub const MODIFIER_ALT: u8 = 1 << 2;
+pub const MODIFIER_SUPER: u8 = 1 << 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+impl From<u8> for MouseButton {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => MouseButton::Left,
+            2 => MouseButton::Right,
+            3 => MouseButton::Middle,
+            v => MouseButton::Other(v),
+        }
+    }
+}
+
+impl From<MouseButton> for u8 {
+    fn from(value: MouseButton) -> Self {
+        match value {
+            MouseButton::Left => 1,
+            MouseButton::Right => 2,
+            MouseButton::Middle => 3,
+            MouseButton::Other(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub pressed: bool,
+
+    /// The held `MODIFIER_*`
+    pub modifiers: u8,
+
+    /// `Ctrl`, `A`, `F1`, ...
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Cursor {
+        x: i32,
+        y: i32,
+    },
+    Click {
+        button: MouseButton,
+        pressed: bool,
+        x: i32,
+        y: i32,
+    },
+    Key(KeyEvent),
+    Heartbeat,
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, payload) = match self {
+            Message::Cursor { x, y } => (KIND_CURSOR, [x.to_le_bytes(), y.to_le_bytes()].concat()),
+            Message::Click {
+                button,
+                pressed,
+                x,
+                y,
+            } => {
+                let mut payload = vec![(*button).into(), *pressed as u8];
+                payload.extend_from_slice(&x.to_le_bytes());
+                payload.extend_from_slice(&y.to_le_bytes());
+                (KIND_CLICK, payload)
+            }
+            Message::Key(key) => {
+                let mut payload = vec![key.pressed as u8, key.modifiers];
+                payload.extend_from_slice(key.label.as_bytes());
+                (KIND_KEY, payload)
+            }
+            Message::Heartbeat => (KIND_HEARTBEAT, vec![]),
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+        bytes.push(PROTOCOL_VERSION);
+        bytes.push(kind);
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend(payload);
+        bytes
+    }
+
+    /// Read the next message, `None` if its kind is unknown
+    pub fn read_from(reader: &mut impl Read) -> Result<Option<Self>> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+
+        if header[0] != PROTOCOL_VERSION {
+            return Err(Error::Version(header[0]));
+        }
+
+        let length = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as usize;
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(Error::Malformed(format!("payload of {length} bytes")));
+        }
+
+        let mut payload = vec![0u8; length];
+        reader.read_exact(&mut payload)?;
+
+        Self::decode(header[1], &payload)
+    }
+
+    fn decode(kind: u8, payload: &[u8]) -> Result<Option<Self>> {
+        let malformed = || Error::Malformed(format!("payload of kind {kind}: {payload:?}"));
+        let read_i32 = |offset: usize| {
+            payload
+                .get(offset..offset + 4)
+                .map(|v| i32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .ok_or_else(malformed)
+        };
+
+        let message = match kind {
+            KIND_CURSOR => Message::Cursor {
+                x: read_i32(0)?,
+                y: read_i32(4)?,
+            },
+            KIND_CLICK => Message::Click {
+                button: (*payload.first().ok_or_else(malformed)?).into(),
+                pressed: *payload.get(1).ok_or_else(malformed)? != 0,
+                x: read_i32(2)?,
+                y: read_i32(6)?,
+            },
+            KIND_KEY => {
+                if payload.len() < 2 {
+                    return Err(malformed());
+                }
+
+                Message::Key(KeyEvent {
+                    pressed: payload[0] != 0,
+                    modifiers: payload[1],
+                    label: String::from_utf8_lossy(&payload[2..]).into_owned(),
+                })
+            }
+            KIND_HEARTBEAT => Message::Heartbeat,
+            _ => {
+                log::debug!("skip the message of unknown kind {kind}");
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) -> Option<Message> {
+        Message::read_from(&mut message.encode().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let messages = [
+            Message::Cursor { x: -10, y: 2160 },
+            Message::Click {
+                button: MouseButton::Right,
+                pressed: true,
+                x: 3,
+                y: 4,
+            },
+            Message::Key(KeyEvent {
+                pressed: false,
+                modifiers: MODIFIER_CTRL | MODIFIER_SHIFT,
+                label: "F5".to_string(),
+            }),
+            Message::Heartbeat,
+        ];
+
+        for message in messages {
+            assert_eq!(round_trip(message.clone()), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_skip_unknown_kind() {
+        let mut bytes = vec![PROTOCOL_VERSION, 200, 2, 0, 0, 0, 1, 2];
+        bytes.extend(Message::Heartbeat.encode());
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(Message::read_from(&mut reader).unwrap(), None);
+        assert_eq!(
+            Message::read_from(&mut reader).unwrap(),
+            Some(Message::Heartbeat)
+        );
+    }
+
+    #[test]
+    fn test_reject_other_version() {
+        let mut bytes = Message::Heartbeat.encode();
+        bytes[0] = PROTOCOL_VERSION + 1;
+
+        assert!(matches!(
+            Message::read_from(&mut bytes.as_slice()),
+            Err(Error::Version(_))
+        ));
+    }
+
+    #[test]
+    fn test_reject_short_payload() {
+        let bytes = [PROTOCOL_VERSION, KIND_CURSOR, 4, 0, 0, 0, 1, 2, 3, 4];
+        assert!(matches!(
+            Message::read_from(&mut bytes.as_slice()),
+            Err(Error::Malformed(_))
+        ));
+    }
+}
@@ -1,11 +1,22 @@
 //! Cryptographic utilities for encryption, decryption, and hashing.
 //!
 //! This module provides AES-128-CBC encryption/decryption and hash functions.
+//! With the `crypto-stream` feature, it also provides streaming encryption of
+//! large files with chunked XChaCha20-Poly1305.
 
 use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
 use anyhow::{Context, Result, anyhow};
 use crypto_hash::{Algorithm, hex_digest};
 
+#[cfg(feature = "crypto-stream")]
+mod stream;
+
+#[cfg(feature = "crypto-stream")]
+pub use stream::{
+    DEFAULT_CHUNK_SIZE, DecryptReader, EncryptWriter, MAX_CHUNK_SIZE, change_file_passphrase,
+    decrypt_file, encrypt_file,
+};
+
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
@@ -0,0 +1,711 @@
+//! Streaming encryption of large files with chunked XChaCha20-Poly1305.
+//!
+//! The data is split into chunks, each one encrypted and authenticated with the
+//! STREAM construction, so a file can be encrypted while it's written and
+//! decrypted while it's read. Reordered, removed or truncated chunks fail the
+//! decryption.
+//!
+//! Every file is encrypted with its own random key. The key is wrapped with a
+//! key derived from the passphrase by Argon2id and stored in the header, so the
+//! passphrase of a file is changed without encrypting the data again.
+//!
+//! Layout of an encrypted file:
+//!
+//! | Field         | Size            |
+//! |---------------|-----------------|
+//! | Magic         | 8               |
+//! | Chunk size    | 4               |
+//! | Argon2 params | 12              |
+//! | Salt          | 16              |
+//! | Key nonce     | 24              |
+//! | Wrapped key   | 48              |
+//! | Stream nonce  | 19              |
+//! | Chunks        | chunk size + 16 |
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{
+        Aead, OsRng, Payload,
+        rand_core::RngCore,
+        stream::{DecryptorBE32, EncryptorBE32},
+    },
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+};
+
+/// Default size of the plaintext chunks (64 KiB)
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum size of the plaintext chunks (16 MiB)
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+// Ceilings of the Argon2id costs read from a header, so a crafted file can't
+// make the key derivation take gigabytes of memory or minutes of CPU
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 10;
+const MAX_P_COST: u32 = 16;
+
+const MAGIC: &[u8; 8] = b"CUTILAE1";
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const KEY_NONCE_LEN: usize = 24;
+const STREAM_NONCE_LEN: usize = 19;
+const HEADER_LEN: usize =
+    MAGIC.len() + 4 + 12 + SALT_LEN + KEY_NONCE_LEN + KEY_LEN + TAG_LEN + STREAM_NONCE_LEN;
+
+/// Header of an encrypted file
+struct Header {
+    chunk_size: u32,
+    kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    key_nonce: [u8; KEY_NONCE_LEN],
+    wrapped_key: [u8; KEY_LEN + TAG_LEN],
+    stream_nonce: [u8; STREAM_NONCE_LEN],
+}
+
+/// Argon2id cost parameters the key wrapping key is derived with
+#[derive(Debug, Clone, Copy)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    fn check(&self) -> Result<()> {
+        if self.m_cost > MAX_M_COST || self.t_cost > MAX_T_COST || self.p_cost > MAX_P_COST {
+            bail!(
+                "Key derivation costs m={}KiB t={} p={} exceed the limits m={MAX_M_COST}KiB t={MAX_T_COST} p={MAX_P_COST}",
+                self.m_cost,
+                self.t_cost,
+                self.p_cost
+            );
+        }
+        Ok(())
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| anyhow!("Invalid key derivation parameters: {e}"))?;
+
+        let mut key = [0_u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Deriving key failed: {e}"))?;
+
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+impl Header {
+    /// Creates the header of a new file and returns it with the file key.
+    fn new(passphrase: &str, chunk_size: usize, kdf: KdfParams) -> Result<(Self, [u8; KEY_LEN])> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            bail!("chunk size should be in 1..={MAX_CHUNK_SIZE} bytes");
+        }
+        kdf.check()?;
+
+        let mut key = [0_u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+
+        let mut header = Self {
+            chunk_size: chunk_size as u32,
+            kdf,
+            salt: [0; SALT_LEN],
+            key_nonce: [0; KEY_NONCE_LEN],
+            wrapped_key: [0; KEY_LEN + TAG_LEN],
+            stream_nonce: [0; STREAM_NONCE_LEN],
+        };
+        OsRng.fill_bytes(&mut header.stream_nonce);
+        header.wrap_key(passphrase, &key)?;
+
+        Ok((header, key))
+    }
+
+    /// Wraps the file key with a new salt and nonce.
+    fn wrap_key(&mut self, passphrase: &str, key: &[u8; KEY_LEN]) -> Result<()> {
+        OsRng.fill_bytes(&mut self.salt);
+        let key_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let wrapped_key = self
+            .kdf
+            .derive_key(passphrase, &self.salt)?
+            .encrypt(&key_nonce, key.as_slice())
+            .map_err(|_| anyhow!("Wrapping key failed"))?;
+
+        self.key_nonce.copy_from_slice(&key_nonce);
+        self.wrapped_key.copy_from_slice(&wrapped_key);
+        Ok(())
+    }
+
+    fn unwrap_key(&self, passphrase: &str) -> Result<[u8; KEY_LEN]> {
+        let key = self
+            .kdf
+            .derive_key(passphrase, &self.salt)?
+            .decrypt(
+                XNonce::from_slice(&self.key_nonce),
+                self.wrapped_key.as_slice(),
+            )
+            .map_err(|_| anyhow!("Wrong passphrase or damaged header"))?;
+
+        let mut file_key = [0_u8; KEY_LEN];
+        file_key.copy_from_slice(&key);
+        Ok(file_key)
+    }
+
+    // The fields kept by a passphrase change are authenticated with every chunk
+    fn associated_data(&self) -> Vec<u8> {
+        let mut aad = MAGIC.to_vec();
+        aad.extend_from_slice(&self.chunk_size.to_le_bytes());
+        aad.extend_from_slice(&self.stream_nonce);
+        aad
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.chunk_size.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.m_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.t_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.p_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.key_nonce);
+        bytes.extend_from_slice(&self.wrapped_key);
+        bytes.extend_from_slice(&self.stream_nonce);
+        bytes
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self> {
+        let mut bytes = [0_u8; HEADER_LEN];
+        reader
+            .read_exact(&mut bytes)
+            .context("Reading header failed")?;
+
+        let (magic, rest) = bytes.split_at(MAGIC.len());
+        if magic != MAGIC {
+            bail!("Not an encrypted file");
+        }
+
+        let (chunk_size, rest) = rest.split_at(4);
+        let (m_cost, rest) = rest.split_at(4);
+        let (t_cost, rest) = rest.split_at(4);
+        let (p_cost, rest) = rest.split_at(4);
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (key_nonce, rest) = rest.split_at(KEY_NONCE_LEN);
+        let (wrapped_key, stream_nonce) = rest.split_at(KEY_LEN + TAG_LEN);
+
+        let chunk_size = u32::from_le_bytes(chunk_size.try_into()?);
+        if chunk_size == 0 || chunk_size as usize > MAX_CHUNK_SIZE {
+            bail!("Invalid chunk size {chunk_size}");
+        }
+
+        let kdf = KdfParams {
+            m_cost: u32::from_le_bytes(m_cost.try_into()?),
+            t_cost: u32::from_le_bytes(t_cost.try_into()?),
+            p_cost: u32::from_le_bytes(p_cost.try_into()?),
+        };
+        kdf.check()?;
+
+        Ok(Self {
+            chunk_size,
+            kdf,
+            salt: salt.try_into()?,
+            key_nonce: key_nonce.try_into()?,
+            wrapped_key: wrapped_key.try_into()?,
+            stream_nonce: stream_nonce.try_into()?,
+        })
+    }
+}
+
+/// Writer encrypting the data written into it.
+///
+/// The data is encrypted by chunks, [`EncryptWriter::finish`] should be called
+/// to write the last chunk, otherwise the data can't be decrypted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::crypto::EncryptWriter;
+/// use std::{fs::File, io::Write};
+///
+/// let file = File::create("/path/to/recording.mp4.enc").unwrap();
+/// let mut writer = EncryptWriter::new(file, "passphrase").unwrap();
+///
+/// writer.write_all(b"frame data").unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct EncryptWriter<W: Write> {
+    writer: W,
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    aad: Vec<u8>,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Creates a writer with the default chunk size and writes the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key derivation fails or the header can't be written.
+    pub fn new(writer: W, passphrase: &str) -> Result<Self> {
+        Self::with_chunk_size(writer, passphrase, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a writer with the given chunk size and writes the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The chunk size is 0 or larger than [`MAX_CHUNK_SIZE`]
+    /// - The key derivation fails
+    /// - The header can't be written
+    pub fn with_chunk_size(writer: W, passphrase: &str, chunk_size: usize) -> Result<Self> {
+        Self::with_params(writer, passphrase, chunk_size, KdfParams::default())
+    }
+
+    fn with_params(
+        mut writer: W,
+        passphrase: &str,
+        chunk_size: usize,
+        kdf: KdfParams,
+    ) -> Result<Self> {
+        let (header, key) = Header::new(passphrase, chunk_size, kdf)?;
+        writer
+            .write_all(&header.to_bytes())
+            .context("Writing header failed")?;
+
+        Ok(Self {
+            writer,
+            encryptor: Some(EncryptorBE32::new(
+                &key.into(),
+                header.stream_nonce.as_slice().into(),
+            )),
+            aad: header.associated_data(),
+            chunk_size,
+            buffer: Vec::with_capacity(chunk_size),
+        })
+    }
+
+    /// Encrypts the buffered data as the last chunk and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data can't be encrypted or written.
+    pub fn finish(mut self) -> io::Result<W> {
+        let encryptor = self
+            .encryptor
+            .take()
+            .ok_or_else(|| io::Error::other("encryption is finished"))?;
+
+        let chunk = encryptor
+            .encrypt_last(Payload {
+                msg: &self.buffer,
+                aad: &self.aad,
+            })
+            .map_err(|_| io::Error::other("encrypting chunk failed"))?;
+
+        self.writer.write_all(&chunk)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    // A full chunk is encrypted once more data follows it, so the last chunk
+    // is always encrypted by `finish`
+    fn encrypt_chunk(&mut self) -> io::Result<()> {
+        let encryptor = self
+            .encryptor
+            .as_mut()
+            .ok_or_else(|| io::Error::other("encryption is finished"))?;
+
+        let chunk = encryptor
+            .encrypt_next(Payload {
+                msg: &self.buffer[..self.chunk_size],
+                aad: &self.aad,
+            })
+            .map_err(|_| io::Error::other("encrypting chunk failed"))?;
+
+        self.writer.write_all(&chunk)?;
+        self.buffer.drain(..self.chunk_size);
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == self.chunk_size && !buf.is_empty() {
+            self.encrypt_chunk()?;
+        }
+
+        let len = buf.len().min(self.chunk_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reader decrypting the data of an [`EncryptWriter`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::crypto::DecryptReader;
+/// use std::{fs::File, io::Read};
+///
+/// let file = File::open("/path/to/recording.mp4.enc").unwrap();
+/// let mut reader = DecryptReader::new(file, "passphrase").unwrap();
+///
+/// let mut data = vec![];
+/// reader.read_to_end(&mut data).unwrap();
+/// ```
+pub struct DecryptReader<R: Read> {
+    reader: R,
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    aad: Vec<u8>,
+    chunk_len: usize,
+    ciphertext: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Creates a reader by reading the header and unwrapping the file key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The data is not encrypted by [`EncryptWriter`]
+    /// - The passphrase is wrong
+    pub fn new(mut reader: R, passphrase: &str) -> Result<Self> {
+        let header = Header::read_from(&mut reader)?;
+        let key = header.unwrap_key(passphrase)?;
+        let chunk_len = header.chunk_size as usize + TAG_LEN;
+
+        Ok(Self {
+            reader,
+            decryptor: Some(DecryptorBE32::new(
+                &key.into(),
+                header.stream_nonce.as_slice().into(),
+            )),
+            aad: header.associated_data(),
+            chunk_len,
+            ciphertext: Vec::with_capacity(chunk_len + 1),
+            plaintext: vec![],
+            position: 0,
+        })
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // A chunk followed by more data is decrypted as a middle chunk, the one at
+    // the end as the last chunk, so a truncated file fails the decryption
+    fn decrypt_chunk(&mut self) -> io::Result<()> {
+        let Some(decryptor) = self.decryptor.as_mut() else {
+            return Ok(());
+        };
+
+        while self.ciphertext.len() <= self.chunk_len {
+            let len = self.ciphertext.len();
+            self.ciphertext.resize(self.chunk_len + 1, 0);
+
+            match self.reader.read(&mut self.ciphertext[len..]) {
+                Ok(0) => {
+                    self.ciphertext.truncate(len);
+                    break;
+                }
+                Ok(n) => self.ciphertext.truncate(len + n),
+                Err(e) => {
+                    self.ciphertext.truncate(len);
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let invalid_data =
+            |_| io::Error::new(io::ErrorKind::InvalidData, "decrypting chunk failed");
+
+        self.plaintext = if self.ciphertext.len() > self.chunk_len {
+            let plaintext = decryptor
+                .decrypt_next(Payload {
+                    msg: &self.ciphertext[..self.chunk_len],
+                    aad: &self.aad,
+                })
+                .map_err(invalid_data)?;
+
+            self.ciphertext.drain(..self.chunk_len);
+            plaintext
+        } else {
+            let plaintext = self
+                .decryptor
+                .take()
+                .expect("decryptor is checked")
+                .decrypt_last(Payload {
+                    msg: &self.ciphertext,
+                    aad: &self.aad,
+                })
+                .map_err(invalid_data)?;
+
+            self.ciphertext.clear();
+            plaintext
+        };
+
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The last chunk may be empty
+        while self.position == self.plaintext.len() && self.decryptor.is_some() {
+            self.decrypt_chunk()?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Encrypts a file with a passphrase.
+///
+/// # Arguments
+///
+/// * `passphrase` - The passphrase the file key is wrapped with
+/// * `src` - The file to encrypt
+/// * `dst` - The encrypted file, an existing file is replaced
+///
+/// # Errors
+///
+/// Returns an error if the files can't be read or written.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::crypto::{decrypt_file, encrypt_file};
+///
+/// encrypt_file("passphrase", "/path/to/recording.mp4", "/path/to/recording.mp4.enc").unwrap();
+/// decrypt_file("passphrase", "/path/to/recording.mp4.enc", "/path/to/recording.mp4").unwrap();
+/// ```
+pub fn encrypt_file(passphrase: &str, src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut writer = EncryptWriter::new(BufWriter::new(File::create(dst)?), passphrase)?;
+
+    io::copy(&mut reader, &mut writer)?;
+    writer.finish()?.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+/// Decrypts a file encrypted by [`encrypt_file`] or [`EncryptWriter`].
+///
+/// The decrypted file is removed if the decryption fails.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The passphrase is wrong
+/// - The encrypted file is damaged or truncated
+/// - The files can't be read or written
+pub fn decrypt_file(passphrase: &str, src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+    let dst = dst.as_ref();
+    let mut reader = DecryptReader::new(BufReader::new(File::open(src)?), passphrase)?;
+    let mut writer = BufWriter::new(File::create(dst)?);
+
+    let result = io::copy(&mut reader, &mut writer)
+        .and_then(|_| writer.flush())
+        .context("Decrypting file failed");
+
+    if result.is_err() {
+        drop(writer);
+        _ = fs::remove_file(dst);
+    }
+
+    result
+}
+
+/// Changes the passphrase of an encrypted file.
+///
+/// Only the file key in the header is wrapped again, the data is kept.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The file is not encrypted
+/// - `passphrase` is wrong
+/// - The header can't be written
+pub fn change_file_passphrase(
+    path: impl AsRef<Path>,
+    passphrase: &str,
+    new_passphrase: &str,
+) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut header = Header::read_from(&mut file)?;
+    let key = header.unwrap_key(passphrase)?;
+
+    header.wrap_key(new_passphrase, &key)?;
+
+    file.rewind()?;
+    file.write_all(&header.to_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    // Cheap parameters, the default ones are slow without optimizations
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn encrypt(passphrase: &str, chunk_size: usize, data: &[u8]) -> Result<Vec<u8>> {
+        let mut writer = EncryptWriter::with_params(vec![], passphrase, chunk_size, TEST_KDF)?;
+        writer.write_all(data)?;
+        Ok(writer.finish()?)
+    }
+
+    fn decrypt(passphrase: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let mut plaintext = vec![];
+        DecryptReader::new(Cursor::new(data), passphrase)?.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_stream() -> Result<()> {
+        let data = (0..1000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+
+        // Empty data, partial last chunk and data of whole chunks
+        for len in [0, 1, 99, 100, 101, 1000] {
+            let encrypted = encrypt("passphrase", 100, &data[..len])?;
+            assert_eq!(
+                encrypted.len(),
+                HEADER_LEN + len + len.div_ceil(100).max(1) * TAG_LEN
+            );
+            assert_eq!(decrypt("passphrase", &encrypted)?, &data[..len]);
+        }
+
+        let encrypted = encrypt("passphrase", 100, &data)?;
+        assert!(decrypt("wrong", &encrypted).is_err());
+        assert!(decrypt("passphrase", &data).is_err());
+
+        // Truncated at a chunk boundary
+        assert!(decrypt("passphrase", &encrypted[..HEADER_LEN + 2 * (100 + TAG_LEN)]).is_err());
+
+        // Tampered data
+        let mut tampered = encrypted.clone();
+        tampered[HEADER_LEN + 10] ^= 1;
+        assert!(decrypt("passphrase", &tampered).is_err());
+
+        // Tampered chunk size
+        let mut tampered = encrypted;
+        tampered[MAGIC.len()] = 99;
+        assert!(decrypt("passphrase", &tampered).is_err());
+
+        assert!(EncryptWriter::with_params(vec![], "passphrase", 0, TEST_KDF).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kdf_limits() -> Result<()> {
+        let encrypted = encrypt("passphrase", 100, b"recording data")?;
+        let offset = MAGIC.len() + 4;
+
+        // Costs above the ceilings are rejected before deriving the key
+        for (index, cost) in [
+            (0, MAX_M_COST + 1),
+            (1, MAX_T_COST + 1),
+            (2, MAX_P_COST + 1),
+            (0, u32::MAX),
+        ] {
+            let mut tampered = encrypted.clone();
+            let start = offset + index * 4;
+            tampered[start..start + 4].copy_from_slice(&cost.to_le_bytes());
+
+            let err = decrypt("passphrase", &tampered).unwrap_err();
+            assert!(err.to_string().contains("exceed the limits"), "{err}");
+        }
+
+        let kdf = KdfParams {
+            t_cost: MAX_T_COST + 1,
+            ..TEST_KDF
+        };
+        assert!(EncryptWriter::with_params(vec![], "passphrase", 100, kdf).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_file_passphrase() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("data.enc");
+        fs::write(&path, encrypt("passphrase", 64, b"recording data")?)?;
+
+        assert!(change_file_passphrase(&path, "wrong", "new").is_err());
+        change_file_passphrase(&path, "passphrase", "new")?;
+
+        let encrypted = fs::read(&path)?;
+        assert!(decrypt("passphrase", &encrypted).is_err());
+        assert_eq!(decrypt("new", &encrypted)?, b"recording data");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_file() -> Result<()> {
+        let dir = tempdir()?;
+        let (src, enc, dst) = (
+            dir.path().join("data"),
+            dir.path().join("data.enc"),
+            dir.path().join("data.dec"),
+        );
+
+        let data = vec![7_u8; DEFAULT_CHUNK_SIZE * 2 + 10];
+        fs::write(&src, &data)?;
+
+        encrypt_file("passphrase", &src, &enc)?;
+        decrypt_file("passphrase", &enc, &dst)?;
+        assert_eq!(fs::read(&dst)?, data);
+
+        // A damaged file isn't decrypted
+        let mut encrypted = fs::read(&enc)?;
+        let len = encrypted.len();
+        encrypted.truncate(len - 1);
+        fs::write(&enc, encrypted)?;
+
+        assert!(decrypt_file("passphrase", &enc, &dst).is_err());
+        assert!(!dst.exists());
+
+        Ok(())
+    }
+}
@@ -7,6 +7,12 @@ use std::{
     path::{Path, PathBuf},
 };
 
+#[cfg(feature = "fs-watch")]
+mod watch;
+
+#[cfg(feature = "fs-watch")]
+pub use watch::{DEFAULT_WATCH_DEBOUNCE, DirWatcher, WatchOptions, watch_dir, watch_dir_with};
+
 /// Kilobytes constant (1024 bytes)
 pub const KB: u64 = 1024;
 
@@ -0,0 +1,239 @@
+//! Directory watcher with debounced and filtered change notifications.
+
+use anyhow::Result;
+use notify_debouncer_mini::{
+    DebounceEventResult, Debouncer, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Default time the changes are collected for before they are reported
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Options of a directory watcher.
+///
+/// By default, only the direct children of the directory are watched, hidden
+/// files are ignored and files of any extension are reported.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    recursive: bool,
+    hidden: bool,
+    debounce: Duration,
+    extensions: Vec<String>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            hidden: false,
+            debounce: DEFAULT_WATCH_DEBOUNCE,
+            extensions: vec![],
+        }
+    }
+}
+
+impl WatchOptions {
+    /// Watches the subdirectories too.
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Reports the changes of hidden files, whose name starts with a dot.
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Sets the time the changes are collected for before they are reported.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Reports only the files with one of the extensions, compared case-insensitively.
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+        self
+    }
+
+    fn is_matched(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+
+        if !self.hidden && name.to_string_lossy().starts_with('.') {
+            return false;
+        }
+
+        self.extensions.is_empty()
+            || path.extension().is_some_and(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                self.extensions.contains(&ext)
+            })
+    }
+}
+
+/// Watcher of a directory, the directory is watched until it's dropped.
+pub struct DirWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+/// Watches a directory with the default options.
+///
+/// See [`watch_dir_with`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::fs::watch_dir;
+///
+/// let watcher = watch_dir("/path/to/recordings", |paths| {
+///     println!("Changed: {:?}", paths);
+/// })
+/// .unwrap();
+///
+/// // The directory is watched until the watcher is dropped
+/// drop(watcher);
+/// ```
+pub fn watch_dir(
+    path: impl AsRef<Path>,
+    cb: impl FnMut(Vec<PathBuf>) + Send + 'static,
+) -> Result<DirWatcher> {
+    watch_dir_with(path, WatchOptions::default(), cb)
+}
+
+/// Watches a directory and reports the changed files.
+///
+/// The changes are collected for the debounce time, then `cb` is called once
+/// with the created, modified and removed paths matching the options, without
+/// duplicates. `cb` is called on the thread of the watcher.
+///
+/// # Arguments
+///
+/// * `path` - Directory to watch
+/// * `options` - Options of the watcher
+/// * `cb` - Callback receiving the changed paths
+///
+/// # Returns
+///
+/// Returns the watcher, the directory is watched until it's dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::fs::{WatchOptions, watch_dir_with};
+/// use std::time::Duration;
+///
+/// let options = WatchOptions::default()
+///     .with_recursive(true)
+///     .with_debounce(Duration::from_secs(1))
+///     .with_extensions(&["gguf", "onnx"]);
+///
+/// let _watcher = watch_dir_with("/path/to/models", options, |paths| {
+///     println!("Models changed: {:?}", paths);
+/// })
+/// .unwrap();
+/// ```
+pub fn watch_dir_with(
+    path: impl AsRef<Path>,
+    options: WatchOptions,
+    mut cb: impl FnMut(Vec<PathBuf>) + Send + 'static,
+) -> Result<DirWatcher> {
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+
+    let mut debouncer = new_debouncer(options.debounce, {
+        let options = options.clone();
+        move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                let mut paths = events
+                    .into_iter()
+                    .map(|event| event.path)
+                    .filter(|path| options.is_matched(path))
+                    .collect::<Vec<_>>();
+
+                paths.sort();
+                paths.dedup();
+
+                if !paths.is_empty() {
+                    cb(paths);
+                }
+            }
+            Err(e) => eprintln!("Failed to watch directory. {e}"),
+        }
+    })?;
+
+    debouncer.watcher().watch(path.as_ref(), mode)?;
+
+    Ok(DirWatcher {
+        _debouncer: debouncer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, sync::mpsc};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watch_options() {
+        let options = WatchOptions::default().with_extensions(&[".MP4", "mkv"]);
+
+        assert!(options.is_matched(Path::new("/tmp/video.mp4")));
+        assert!(options.is_matched(Path::new("/tmp/video.MKV")));
+        assert!(!options.is_matched(Path::new("/tmp/video.txt")));
+        assert!(!options.is_matched(Path::new("/tmp/video")));
+        assert!(!options.is_matched(Path::new("/tmp/.video.mp4")));
+
+        let options = options.with_hidden(true);
+        assert!(options.is_matched(Path::new("/tmp/.video.mp4")));
+
+        let options = WatchOptions::default();
+        assert!(options.is_matched(Path::new("/tmp/video")));
+    }
+
+    #[test]
+    fn test_watch_dir() -> Result<()> {
+        let dir = tempdir()?;
+        let (tx, rx) = mpsc::channel();
+
+        let options = WatchOptions::default()
+            .with_debounce(Duration::from_millis(100))
+            .with_extensions(&["mp4"]);
+
+        let watcher = watch_dir_with(dir.path(), options, move |paths| {
+            _ = tx.send(paths);
+        })?;
+
+        let video = dir.path().join("video.mp4");
+        fs::write(&video, "video")?;
+        fs::write(&video, "more video")?;
+        fs::write(dir.path().join("notes.txt"), "notes")?;
+
+        let paths = rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(paths, vec![video.clone()]);
+
+        fs::remove_file(&video)?;
+        let paths = rx.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(paths, vec![video]);
+
+        // No change is reported after the watcher is dropped
+        drop(watcher);
+        fs::write(dir.path().join("other.mp4"), "video")?;
+        assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+        Ok(())
+    }
+}
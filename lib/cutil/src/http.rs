@@ -1,13 +1,28 @@
-//! HTTP client utilities for making HTTP requests and handling URLs.
+//! HTTP client utilities for making HTTP requests, uploading files and handling URLs.
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use bytes::Bytes;
+use futures::TryStreamExt;
 use once_cell::sync::Lazy;
 use reqwest::{
-    header::{HeaderMap, ACCEPT, CACHE_CONTROL, USER_AGENT},
-    Client, Url,
+    Body, Client, StatusCode, Url,
+    header::{ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, RANGE, USER_AGENT},
+    multipart::{Form, Part},
 };
-use std::{ffi::OsStr, path::Path, time::Duration};
+use std::{
+    ffi::OsStr,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+pub use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
 
 static HTTP_CLIENT: Lazy<Client> = Lazy::new(Client::new);
 
@@ -118,6 +133,362 @@ pub fn file_extension(url: &str) -> Result<Option<String>> {
         .map(String::from))
 }
 
+/// The end state of an upload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UploadState {
+    /// The upload is finished with the body of the server response.
+    Finished(String),
+
+    /// The upload is cancelled by `Uploader::cancel`.
+    Cancelled,
+}
+
+/// Uploads a file with progress and cancellation.
+///
+/// The file is either sent at once as a `multipart/form-data` field, or in
+/// chunks by resumable `PUT` requests which continue from the bytes the server
+/// already has.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cutil::http::{UploadState, Uploader};
+/// use std::path::PathBuf;
+///
+/// // Note: This function requires an async runtime
+/// // let uploader = Uploader::new("https://example.com/upload", PathBuf::from("/path/to/video.mp4"));
+/// // let state = uploader
+/// //     .multipart("file", &[("title", "demo")], |sent, total| println!("{sent}/{total}"))
+/// //     .await
+/// //     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Uploader {
+    url: String,
+    file_path: PathBuf,
+    headers: HeaderMap,
+    chunk_size: u64,
+    cancel_sig: Arc<AtomicBool>,
+}
+
+impl Uploader {
+    /// Default bytes of a chunk of the resumable upload.
+    pub const DEFAULT_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+    /// Creates an uploader of `file_path` to `url`.
+    pub fn new(url: impl ToString, file_path: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.to_string(),
+            file_path: file_path.into(),
+            headers: HeaderMap::new(),
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            cancel_sig: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Headers sent with every request, likes the authorization.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Bytes of a chunk of the resumable upload, at least 1 byte.
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Stops the upload, it returns `UploadState::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancel_sig.store(true, Ordering::Relaxed);
+    }
+
+    /// The signal `cancel` sets, for cancelling the upload from other tasks.
+    pub fn cancel_sig(&self) -> Arc<AtomicBool> {
+        self.cancel_sig.clone()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_sig.load(Ordering::Relaxed)
+    }
+
+    // The file as a request body reporting the sent bytes. Cancelling fails
+    // the stream, which aborts the request
+    fn body_stream(
+        &self,
+        file: tokio::fs::File,
+        total_size: u64,
+        mut progress_cb: impl FnMut(u64, u64) + Send + 'static,
+    ) -> Body {
+        let cancel_sig = self.cancel_sig.clone();
+        let mut sent = 0;
+        let stream = ReaderStream::new(file)
+            .map_ok(move |chunk| {
+                sent += chunk.len() as u64;
+                progress_cb(sent, total_size);
+                chunk
+            })
+            .and_then(move |chunk| {
+                let cancelled = cancel_sig.load(Ordering::Relaxed);
+                async move {
+                    if cancelled {
+                        Err(io::Error::new(
+                            io::ErrorKind::Interrupted,
+                            "upload cancelled",
+                        ))
+                    } else {
+                        Ok(chunk)
+                    }
+                }
+            });
+
+        Body::wrap_stream(stream)
+    }
+
+    /// Uploads the file as the `field_name` field of a `multipart/form-data`
+    /// POST request, together with the text `fields`.
+    ///
+    /// # Arguments
+    ///
+    /// * `field_name` - The form field of the file
+    /// * `fields` - The text fields of the form
+    /// * `progress_cb` - Called with the sent bytes and the file size
+    ///
+    /// # Returns
+    ///
+    /// Returns the state of the upload on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, the request fails or the
+    /// server responds with an error status.
+    pub async fn multipart(
+        &self,
+        field_name: &str,
+        fields: &[(&str, &str)],
+        progress_cb: impl FnMut(u64, u64) + Send + 'static,
+    ) -> Result<UploadState> {
+        let file = tokio::fs::File::open(&self.file_path)
+            .await
+            .with_context(|| format!("open {} failed", self.file_path.display()))?;
+        let total_size = file.metadata().await?.len();
+        let body = self.body_stream(file, total_size, progress_cb);
+
+        let file_name = self
+            .file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let part =
+            Part::stream_with_length(body, total_size).file_name(file_name);
+        let form = fields
+            .iter()
+            .fold(Form::new(), |form, (name, value)| {
+                form.text(name.to_string(), value.to_string())
+            })
+            .part(field_name.to_string(), part);
+
+        let result = HTTP_CLIENT
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .multipart(form)
+            .send()
+            .await;
+
+        if self.is_cancelled() {
+            return Ok(UploadState::Cancelled);
+        }
+
+        let response = result?.error_for_status()?;
+        Ok(UploadState::Finished(response.text().await?))
+    }
+
+    /// Uploads the file at once by a `PUT` request, for the servers without
+    /// the resumable upload, like WebDAV and S3.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress_cb` - Called with the sent bytes and the file size
+    ///
+    /// # Returns
+    ///
+    /// Returns the state of the upload on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, the request fails or the
+    /// server responds with an error status.
+    pub async fn put(
+        &self,
+        progress_cb: impl FnMut(u64, u64) + Send + 'static,
+    ) -> Result<UploadState> {
+        let file = tokio::fs::File::open(&self.file_path)
+            .await
+            .with_context(|| format!("open {} failed", self.file_path.display()))?;
+        let total_size = file.metadata().await?.len();
+        let body = self.body_stream(file, total_size, progress_cb);
+
+        let result = HTTP_CLIENT
+            .put(&self.url)
+            .headers(self.headers.clone())
+            .header(CONTENT_LENGTH, total_size)
+            .body(body)
+            .send()
+            .await;
+
+        if self.is_cancelled() {
+            return Ok(UploadState::Cancelled);
+        }
+
+        let response = result?.error_for_status()?;
+        Ok(UploadState::Finished(response.text().await?))
+    }
+
+    /// Uploads the file in chunks by `PUT` requests with the `Content-Range`
+    /// header, continuing from the bytes the server already has.
+    ///
+    /// The server responds `308` with the `Range: bytes=0-<last byte>` header
+    /// of the received bytes to a chunk, and `200` or `201` to the last one. A
+    /// request of `Content-Range: bytes */<size>` without a body queries the
+    /// received bytes, so a cancelled or failed upload is resumed by calling
+    /// it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `progress_cb` - Called with the bytes the server has and the file size
+    ///
+    /// # Returns
+    ///
+    /// Returns the state of the upload on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, the request fails or the
+    /// server responds with an unexpected status.
+    pub async fn resumable_put(
+        &self,
+        mut progress_cb: impl FnMut(u64, u64),
+    ) -> Result<UploadState> {
+        let mut file = tokio::fs::File::open(&self.file_path)
+            .await
+            .with_context(|| format!("open {} failed", self.file_path.display()))?;
+        let total_size = file.metadata().await?.len();
+
+        let response = HTTP_CLIENT
+            .put(&self.url)
+            .headers(self.headers.clone())
+            .header(CONTENT_RANGE, format!("bytes */{total_size}"))
+            .send()
+            .await?;
+
+        let mut offset = match self.check_put_response(response, total_size).await? {
+            PutState::Finished(body) => {
+                progress_cb(total_size, total_size);
+                return Ok(UploadState::Finished(body));
+            }
+            PutState::Received(received) => received,
+        };
+
+        loop {
+            progress_cb(offset, total_size);
+
+            if self.is_cancelled() {
+                return Ok(UploadState::Cancelled);
+            }
+
+            let len = self.chunk_size.min(total_size - offset);
+            let mut chunk = vec![0; len as usize];
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.read_exact(&mut chunk).await?;
+
+            // An empty file is sent as an empty chunk
+            let content_range = if len == 0 {
+                format!("bytes */{total_size}")
+            } else {
+                format!("bytes {offset}-{}/{total_size}", offset + len - 1)
+            };
+
+            let response = HTTP_CLIENT
+                .put(&self.url)
+                .headers(self.headers.clone())
+                .header(CONTENT_RANGE, content_range)
+                .body(chunk)
+                .send()
+                .await?;
+
+            match self.check_put_response(response, total_size).await? {
+                PutState::Finished(body) => {
+                    progress_cb(total_size, total_size);
+                    return Ok(UploadState::Finished(body));
+                }
+                PutState::Received(received) if received > offset => offset = received,
+                PutState::Received(received) => {
+                    bail!("the server has {received} bytes after the chunk at {offset}")
+                }
+            }
+
+            if offset >= total_size {
+                bail!("the server doesn't finish the upload after receiving the whole file");
+            }
+        }
+    }
+
+    async fn check_put_response(
+        &self,
+        response: reqwest::Response,
+        total_size: u64,
+    ) -> Result<PutState> {
+        match response.status() {
+            StatusCode::OK | StatusCode::CREATED => Ok(PutState::Finished(response.text().await?)),
+            StatusCode::PERMANENT_REDIRECT => {
+                let received = response
+                    .headers()
+                    .get(RANGE)
+                    .and_then(|range| range.to_str().ok())
+                    .map(received_bytes)
+                    .transpose()?
+                    .unwrap_or_default();
+
+                if received > total_size {
+                    bail!("the server has {received} bytes of the {total_size} bytes file");
+                }
+                Ok(PutState::Received(received))
+            }
+            status => bail!("upload {} failed: {status}", self.url),
+        }
+    }
+}
+
+enum PutState {
+    Finished(String),
+
+    // Bytes the server has from the start of the file
+    Received(u64),
+}
+
+/// Parses the received bytes from the `Range: bytes=0-<last byte>` header of
+/// a resumable upload response.
+fn received_bytes(range: &str) -> Result<u64> {
+    let Some((start, end)) = range
+        .trim()
+        .strip_prefix("bytes=")
+        .and_then(|range| range.split_once('-'))
+    else {
+        bail!("invalid range header: {range}");
+    };
+
+    if start.trim() != "0" {
+        bail!("the received bytes don't start from 0: {range}");
+    }
+
+    Ok(end
+        .trim()
+        .parse::<u64>()
+        .with_context(|| format!("invalid range header: {range}"))?
+        + 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,6 +532,188 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_received_bytes() -> Result<()> {
+        assert_eq!(received_bytes("bytes=0-0")?, 1);
+        assert_eq!(received_bytes(" bytes=0-1023 ")?, 1024);
+
+        assert!(received_bytes("bytes=100-1023").is_err());
+        assert!(received_bytes("bytes=0-").is_err());
+        assert!(received_bytes("0-1023").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_uploader() {
+        let uploader =
+            Uploader::new("https://example.com/upload", "/tmp/video.mp4").with_chunk_size(0);
+        assert_eq!(uploader.chunk_size, 1);
+
+        let cancel_sig = uploader.cancel_sig();
+        assert!(!cancel_sig.load(Ordering::Relaxed));
+        uploader.cancel();
+        assert!(cancel_sig.load(Ordering::Relaxed));
+    }
+
+    // A resumable upload server keeping the received bytes. `reported`
+    // overrides the bytes it reports to the query of the received bytes
+    async fn upload_server(
+        received: Vec<u8>,
+        reported: Option<u64>,
+    ) -> (String, Arc<std::sync::Mutex<(Vec<u8>, Vec<String>)>>) {
+        use tokio::{
+            io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        let state = Arc::new(std::sync::Mutex::new((received, vec![])));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    loop {
+                        let (mut content_range, mut content_length) = (String::new(), 0);
+                        let mut line = String::new();
+                        loop {
+                            line.clear();
+                            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+
+                            let line = line.trim().to_lowercase();
+                            if line.is_empty() {
+                                break;
+                            } else if let Some(value) = line.strip_prefix("content-range:") {
+                                content_range = value.trim().to_string();
+                            } else if let Some(value) = line.strip_prefix("content-length:") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+
+                        let mut body = vec![0; content_length];
+                        stream.read_exact(&mut body).await.unwrap();
+
+                        let (total_size, received) = {
+                            let mut state = state.lock().unwrap();
+                            let total_size = content_range
+                                .rsplit('/')
+                                .next()
+                                .unwrap()
+                                .parse::<usize>()
+                                .unwrap();
+                            let query = content_range.starts_with("bytes */");
+
+                            if !query {
+                                state.1.push(content_range.clone());
+                            }
+                            state.0.extend(body);
+
+                            let received = match reported {
+                                Some(reported) if query => reported,
+                                _ => state.0.len() as u64,
+                            };
+                            (total_size, received)
+                        };
+
+                        let response = if received == total_size as u64 {
+                            "HTTP/1.1 201 Created\r\ncontent-length: 4\r\n\r\ndone".to_string()
+                        } else if received == 0 {
+                            "HTTP/1.1 308 Permanent Redirect\r\ncontent-length: 0\r\n\r\n"
+                                .to_string()
+                        } else {
+                            format!(
+                                "HTTP/1.1 308 Permanent Redirect\r\nrange: bytes=0-{}\r\ncontent-length: 0\r\n\r\n",
+                                received - 1
+                            )
+                        };
+                        stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (url, state)
+    }
+
+    fn upload_file(dir: &tempfile::TempDir) -> (PathBuf, Vec<u8>) {
+        let data = (0..1000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+        let path = dir.path().join("video.mp4");
+        std::fs::write(&path, &data).unwrap();
+        (path, data)
+    }
+
+    #[tokio::test]
+    async fn test_resumable_put() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (path, data) = upload_file(&dir);
+
+        // A fresh upload
+        let (url, state) = upload_server(vec![], None).await;
+        let uploader = Uploader::new(&url, &path).with_chunk_size(400);
+        let progress = Arc::new(std::sync::Mutex::new(vec![]));
+        let progress_cb = progress.clone();
+        let result = uploader
+            .resumable_put(move |sent, total| progress_cb.lock().unwrap().push((sent, total)))
+            .await?;
+
+        assert!(matches!(result, UploadState::Finished(body) if body == "done"));
+        assert_eq!(state.lock().unwrap().0, data);
+        assert_eq!(
+            state.lock().unwrap().1,
+            ["bytes 0-399/1000", "bytes 400-799/1000", "bytes 800-999/1000"]
+        );
+        assert_eq!(
+            *progress.lock().unwrap(),
+            [(0, 1000), (400, 1000), (800, 1000), (1000, 1000)]
+        );
+
+        // A partial upload is resumed from the bytes the server has
+        let (url, state) = upload_server(data[..300].to_vec(), None).await;
+        let uploader = Uploader::new(&url, &path).with_chunk_size(400);
+        let result = uploader.resumable_put(|_, _| {}).await?;
+
+        assert!(matches!(result, UploadState::Finished(_)));
+        assert_eq!(state.lock().unwrap().0, data);
+        assert_eq!(
+            state.lock().unwrap().1,
+            ["bytes 300-699/1000", "bytes 700-999/1000"]
+        );
+
+        // A complete upload sends nothing
+        let (url, state) = upload_server(data.clone(), None).await;
+        let result = Uploader::new(&url, &path).resumable_put(|_, _| {}).await?;
+
+        assert!(matches!(result, UploadState::Finished(_)));
+        assert!(state.lock().unwrap().1.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resumable_put_bad_offset() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (path, _) = upload_file(&dir);
+
+        // The server reports more bytes than the file has
+        let (url, state) = upload_server(vec![], Some(1500)).await;
+        let result = Uploader::new(&url, &path).resumable_put(|_, _| {}).await;
+
+        assert!(result.is_err());
+        assert!(state.lock().unwrap().1.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_file_extension_invalid_url() {
         // Test with invalid URL
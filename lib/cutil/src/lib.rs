@@ -6,10 +6,12 @@
 //! ## Features
 //!
 //! - `fs`: File system utilities (file operations, directory management, size calculations)
+//! - `fs-watch`: Directory watcher with debounced and filtered notifications
 //! - `str`: String manipulation utilities (splitting, formatting, random generation)
 //! - `time`: Time and date utilities (formatting, parsing, calendar operations)
-//! - `http`: HTTP client utilities (requests, headers, URL parsing)
+//! - `http`: HTTP client utilities (requests, uploads, headers, URL parsing)
 //! - `crypto`: Cryptographic utilities (encryption, decryption, hashing)
+//! - `crypto-stream`: Streaming file encryption with a passphrase-wrapped key
 //! - `number`: Number formatting utilities
 //! - `backup-recover`: Backup and restore utilities
 //! - `vec`: Vector manipulation utilities
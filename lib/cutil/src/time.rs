@@ -1,7 +1,7 @@
 //! Time and date utilities for formatting, parsing, and calendar operations.
 
 use anyhow::{Context, Result};
-use chrono::{Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Timelike, Weekday};
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone, Weekday};
 
 /// Represents a simple date with year, month, and day.
 #[derive(Debug, Clone)]
@@ -222,6 +222,217 @@ pub fn diff_dates_to_days(start_date: &str, end_date: &str) -> Result<i64> {
     Ok((end_timestamp - start_timestamp) / (24 * 60 * 60))
 }
 
+/// Style of a formatted duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampStyle {
+    /// `MM:SS`, or `HH:MM:SS` from 1 hour
+    Media,
+
+    /// `MM:SS.mmm`, or `HH:MM:SS.mmm` from 1 hour
+    MediaMs,
+
+    /// `HH:MM:SS,mmm` of SRT subtitles
+    Srt,
+
+    /// `HH:MM:SS.mmm` of WebVTT subtitles
+    Vtt,
+
+    /// `H:MM:SS.cc` with centiseconds of ASS subtitles
+    Ass,
+
+    /// `HH:MM:SS:FF` timecode with the frames of a frame rate
+    Timecode(u32),
+}
+
+/// Formats a duration in milliseconds as a timestamp.
+///
+/// The smaller units are truncated, e.g. the milliseconds of the `Media` style
+/// and the partial frame of the `Timecode` style.
+///
+/// # Arguments
+///
+/// * `ms` - The duration in milliseconds
+/// * `style` - The style of the timestamp
+///
+/// # Returns
+///
+/// Returns the formatted timestamp string.
+///
+/// # Examples
+///
+/// ```
+/// use cutil::time::{TimestampStyle, format_duration};
+///
+/// assert_eq!(format_duration(123_456, TimestampStyle::Media), "02:03");
+/// assert_eq!(format_duration(3_723_456, TimestampStyle::Srt), "01:02:03,456");
+/// assert_eq!(format_duration(3_723_456, TimestampStyle::Ass), "1:02:03.45");
+/// assert_eq!(format_duration(1_500, TimestampStyle::Timecode(30)), "00:00:01:15");
+/// ```
+pub fn format_duration(ms: u64, style: TimestampStyle) -> String {
+    let total_seconds = ms / 1000;
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    let millis = ms % 1000;
+
+    match style {
+        TimestampStyle::Media if hours > 0 => format!("{hours:02}:{minutes:02}:{seconds:02}"),
+        TimestampStyle::Media => format!("{minutes:02}:{seconds:02}"),
+        TimestampStyle::MediaMs if hours > 0 => {
+            format!("{hours:02}:{minutes:02}:{seconds:02}.{millis:03}")
+        }
+        TimestampStyle::MediaMs => format!("{minutes:02}:{seconds:02}.{millis:03}"),
+        TimestampStyle::Srt => format!("{hours:02}:{minutes:02}:{seconds:02},{millis:03}"),
+        TimestampStyle::Vtt => format!("{hours:02}:{minutes:02}:{seconds:02}.{millis:03}"),
+        TimestampStyle::Ass => format!("{hours}:{minutes:02}:{seconds:02}.{:02}", millis / 10),
+        TimestampStyle::Timecode(fps) => {
+            let frames = millis * fps as u64 / 1000;
+            format!("{hours:02}:{minutes:02}:{seconds:02}:{frames:02}")
+        }
+    }
+}
+
+/// Parses a timestamp into milliseconds.
+///
+/// Accepts `MM:SS` and `HH:MM:SS` with an optional fraction of a second after
+/// a `.` or a `,`, so the media, SRT, WebVTT and ASS timestamps are parsed.
+/// The first field isn't limited, e.g. `75:00` is 75 minutes. Frame-based
+/// timecodes are parsed by [`parse_timecode`].
+///
+/// # Arguments
+///
+/// * `timestamp` - The timestamp to parse
+///
+/// # Returns
+///
+/// Returns the duration in milliseconds.
+///
+/// # Errors
+///
+/// Returns an error if the timestamp is malformed or a field is out of range.
+///
+/// # Examples
+///
+/// ```
+/// use cutil::time::parse_timestamp;
+///
+/// assert_eq!(parse_timestamp("02:03").unwrap(), 123_000);
+/// assert_eq!(parse_timestamp("01:02:03,456").unwrap(), 3_723_456);
+/// assert_eq!(parse_timestamp("1:02:03.45").unwrap(), 3_723_450);
+/// assert!(parse_timestamp("01:60").is_err());
+/// ```
+pub fn parse_timestamp(timestamp: &str) -> Result<u64> {
+    let trimmed = timestamp.trim();
+    let (hms, fraction) = match trimmed.rfind(['.', ',']) {
+        Some(index) => (&trimmed[..index], Some(&trimmed[index + 1..])),
+        None => (trimmed, None),
+    };
+
+    let fields = parse_fields(hms).with_context(|| format!("Invalid timestamp `{timestamp}`"))?;
+    let (hours, minutes, seconds) = match fields[..] {
+        [minutes, seconds] => (0, minutes, seconds),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        [_, _, _, _] => anyhow::bail!("Timecode `{timestamp}` needs a frame rate"),
+        _ => anyhow::bail!("Invalid timestamp `{timestamp}`"),
+    };
+
+    let millis = match fraction {
+        Some(fraction) if is_digits(fraction) => {
+            // Only the milliseconds are kept, e.g. `.5` is 500 ms
+            fraction
+                .chars()
+                .chain("00".chars())
+                .take(3)
+                .collect::<String>()
+                .parse::<u64>()?
+        }
+        Some(_) => anyhow::bail!("Invalid fraction of timestamp `{timestamp}`"),
+        None => 0,
+    };
+
+    to_millis(hours, minutes, seconds, millis)
+        .with_context(|| format!("Timestamp `{timestamp}` is out of range"))
+}
+
+/// Parses a `HH:MM:SS:FF` timecode into milliseconds.
+///
+/// # Arguments
+///
+/// * `timecode` - The timecode to parse
+/// * `fps` - The frame rate of the frames field
+///
+/// # Returns
+///
+/// Returns the duration in milliseconds, the partial millisecond is truncated.
+///
+/// # Errors
+///
+/// Returns an error if the frame rate is 0, the timecode is malformed or a
+/// field is out of range.
+///
+/// # Examples
+///
+/// ```
+/// use cutil::time::parse_timecode;
+///
+/// assert_eq!(parse_timecode("00:00:01:15", 30).unwrap(), 1_500);
+/// assert!(parse_timecode("00:00:01:30", 30).is_err());
+/// ```
+pub fn parse_timecode(timecode: &str, fps: u32) -> Result<u64> {
+    if fps == 0 {
+        anyhow::bail!("Frame rate of timecode `{timecode}` is 0");
+    }
+
+    let fields =
+        parse_fields(timecode.trim()).with_context(|| format!("Invalid timecode `{timecode}`"))?;
+    let [hours, minutes, seconds, frames] = fields[..] else {
+        anyhow::bail!("Invalid timecode `{timecode}`");
+    };
+
+    if frames >= fps as u64 {
+        anyhow::bail!("Frames of timecode `{timecode}` exceed the frame rate {fps}");
+    }
+
+    to_millis(hours, minutes, seconds, frames * 1000 / fps as u64)
+        .with_context(|| format!("Timecode `{timecode}` is out of range"))
+}
+
+// The fields after the first one are less than 60, except the frames which are
+// checked with the frame rate
+fn parse_fields(text: &str) -> Result<Vec<u64>> {
+    let fields = text
+        .split(':')
+        .map(|field| {
+            if is_digits(field) {
+                Ok(field.parse::<u64>()?)
+            } else {
+                anyhow::bail!("`{field}` is not a number")
+            }
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    if fields.iter().skip(1).take(2).any(|field| *field >= 60) {
+        anyhow::bail!("Minutes or seconds out of range");
+    }
+
+    Ok(fields)
+}
+
+// None if the milliseconds overflow
+fn to_millis(hours: u64, minutes: u64, seconds: u64, millis: u64) -> Option<u64> {
+    hours
+        .checked_mul(60)?
+        .checked_add(minutes)?
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(millis)
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
 /// Converts seconds to a media timestamp format (HH:MM:SS or MM:SS).
 ///
 /// For durations less than 1 hour, the format is MM:SS.
@@ -244,16 +455,7 @@ pub fn diff_dates_to_days(start_date: &str, end_date: &str) -> Result<i64> {
 /// assert_eq!(seconds_to_media_timestamp(3661.0), "01:01:01");
 /// ```
 pub fn seconds_to_media_timestamp(seconds: f64) -> String {
-    let total_seconds = seconds as u32;
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds % 3600) / 60;
-    let seconds = total_seconds % 60;
-
-    if hours > 0 {
-        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-    } else {
-        format!("{:02}:{:02}", minutes, seconds)
-    }
+    format_duration(seconds_to_ms(seconds), TimestampStyle::Media)
 }
 
 /// Converts seconds to a media timestamp format with milliseconds.
@@ -278,57 +480,22 @@ pub fn seconds_to_media_timestamp(seconds: f64) -> String {
 /// assert_eq!(seconds_to_media_timestamp_with_ms(3661.789), "01:01:01.789");
 /// ```
 pub fn seconds_to_media_timestamp_with_ms(seconds: f64) -> String {
-    let total_seconds = seconds as u32;
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds % 3600) / 60;
-    let secs = total_seconds % 60;
-    let ms = ((seconds - total_seconds as f64) * 1000.0) as u32;
-
-    if hours > 0 {
-        format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, secs, ms)
-    } else {
-        format!("{:02}:{:02}.{:03}", minutes, secs, ms)
-    }
+    format_duration(seconds_to_ms(seconds), TimestampStyle::MediaMs)
 }
 
+/// Parses a media timestamp into milliseconds, see [`parse_timestamp`].
 pub fn media_timestamp_to_ms(time_str: &str) -> Option<u64> {
-    if let Some((minutes, seconds)) = parse_mm_ss(time_str) {
-        return Some(minutes * 60000 + seconds * 1000);
-    }
-
-    let formats = ["%H:%M:%S", "%H:%M:%S%.3f"];
-    for format in &formats {
-        if let Ok(time) = NaiveTime::parse_from_str(time_str, format) {
-            return Some(
-                (time.hour() as u64 * 3600000)
-                    + (time.minute() as u64 * 60000)
-                    + (time.second() as u64 * 1000)
-                    + (time.nanosecond() as u64 / 1_000_000),
-            );
-        }
-    }
-
-    None
+    parse_timestamp(time_str).ok()
 }
 
+/// Parses a media timestamp into seconds, see [`parse_timestamp`].
 pub fn media_timestamp_to_second(time_str: &str) -> Option<u64> {
-    if let Some(ms) = media_timestamp_to_ms(time_str) {
-        return Some(ms / 1000);
-    }
-
-    None
+    media_timestamp_to_ms(time_str).map(|ms| ms / 1000)
 }
 
-fn parse_mm_ss(time_str: &str) -> Option<(u64, u64)> {
-    let parts: Vec<&str> = time_str.split(':').collect();
-    if parts.len() == 2 {
-        if let (Ok(minutes), Ok(seconds)) = (parts[0].parse::<u64>(), parts[1].parse::<u64>()) {
-            if minutes < 60 && seconds < 60 {
-                return Some((minutes, seconds));
-            }
-        }
-    }
-    None
+// Rounded, so `59.999` isn't formatted as `00:59.998`
+fn seconds_to_ms(seconds: f64) -> u64 {
+    (seconds.max(0.0) * 1000.0).round() as u64
 }
 
 #[cfg(test)]
@@ -406,4 +573,92 @@ mod tests {
 
         assert_eq!(ms, expected);
     }
+
+    #[test]
+    fn test_format_duration() {
+        let ms = 3_723_456;
+        assert_eq!(format_duration(ms, TimestampStyle::Media), "01:02:03");
+        assert_eq!(format_duration(ms, TimestampStyle::MediaMs), "01:02:03.456");
+        assert_eq!(format_duration(ms, TimestampStyle::Srt), "01:02:03,456");
+        assert_eq!(format_duration(ms, TimestampStyle::Vtt), "01:02:03.456");
+        assert_eq!(format_duration(ms, TimestampStyle::Ass), "1:02:03.45");
+        assert_eq!(
+            format_duration(ms, TimestampStyle::Timecode(25)),
+            "01:02:03:11"
+        );
+
+        assert_eq!(
+            format_duration(123_456, TimestampStyle::MediaMs),
+            "02:03.456"
+        );
+        assert_eq!(format_duration(0, TimestampStyle::Srt), "00:00:00,000");
+        assert_eq!(
+            format_duration(0, TimestampStyle::Timecode(0)),
+            "00:00:00:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() -> Result<()> {
+        assert_eq!(parse_timestamp("02:03")?, 123_000);
+        assert_eq!(parse_timestamp("75:00")?, 4_500_000);
+        assert_eq!(parse_timestamp("01:02:03")?, 3_723_000);
+        assert_eq!(parse_timestamp(" 01:02:03,456 ")?, 3_723_456);
+        assert_eq!(parse_timestamp("01:02:03.456")?, 3_723_456);
+        assert_eq!(parse_timestamp("02:03.456")?, 123_456);
+        assert_eq!(parse_timestamp("0:00:01.5")?, 1_500);
+        assert_eq!(parse_timestamp("0:00:01.05")?, 1_050);
+        assert_eq!(parse_timestamp("00:00:01.123456")?, 1_123);
+
+        for timestamp in [
+            "",
+            "12",
+            "01:60",
+            "01:60:00",
+            "a:00",
+            "01::00",
+            "01:00,",
+            "01:00.x",
+            "-1:00",
+            "00:00:01:15",
+        ] {
+            assert!(parse_timestamp(timestamp).is_err(), "{timestamp}");
+        }
+
+        // The hours overflow the milliseconds, or don't fit a u64
+        for timestamp in [
+            "5124095576030431:00:00",
+            "99999999999999999:00:00.5",
+            "307445734561825861:00",
+            "99999999999999999999999:00:00",
+        ] {
+            assert!(parse_timestamp(timestamp).is_err(), "{timestamp}");
+        }
+        assert_eq!(
+            parse_timestamp("5124095576030:00:00")?,
+            5_124_095_576_030 * 3_600_000
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_timecode() -> Result<()> {
+        assert_eq!(parse_timecode("00:00:01:15", 30)?, 1_500);
+        assert_eq!(parse_timecode("01:02:03:11", 25)?, 3_723_440);
+        assert_eq!(parse_timecode("00:00:00:01", 60)?, 16);
+
+        assert!(parse_timecode("00:00:01:30", 30).is_err());
+        assert!(parse_timecode("00:00:01:00", 0).is_err());
+        assert!(parse_timecode("00:01:00", 30).is_err());
+        assert!(parse_timecode("00:60:00:00", 30).is_err());
+        assert!(parse_timecode("5124095576030431:00:00:00", 30).is_err());
+
+        for ms in [0, 1_000, 40, 3_723_440] {
+            let timecode = format_duration(ms, TimestampStyle::Timecode(25));
+            assert_eq!(parse_timecode(&timecode, 25)?, ms);
+        }
+
+        Ok(())
+    }
 }
@@ -1,16 +1,31 @@
-use crate::{DownloadError, Result};
-use futures::StreamExt;
-use reqwest::Client;
+use crate::{DownloadError, RateLimiter, Result};
+use futures::{StreamExt, channel::mpsc, future};
+use reqwest::{
+    Client, StatusCode,
+    header::{self, HeaderMap},
+};
 use std::{
     fs,
-    io::Write,
-    path::PathBuf,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
+    time::{Duration, Instant},
 };
 
+// Files smaller than it per connection are downloaded with less connections
+const MIN_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+// The progress file is saved at most this often while downloading
+const PROGRESS_SAVE_INTERVAL: Duration = Duration::from_millis(500);
+
+const PROGRESS_FILE_HEADER: &str = "wayshot-downloader 2";
+
+// How often a paused download checks whether it's resumed or cancelled
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub enum DownloadState {
     Finsished,
     Cancelled,
@@ -23,6 +38,45 @@ pub struct Downloader {
     url: String,
     save_path: PathBuf,
     cancel_sig: Arc<AtomicBool>,
+    pause_sig: Arc<AtomicBool>,
+    limiter: RateLimiter,
+
+    // Ranges downloaded at the same time when the server supports them
+    connections: usize,
+
+    // Attempts of a range after a network error before giving up
+    retries: usize,
+}
+
+// Range [start, end] of the file, `downloaded` bytes from `start` are saved
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    start: u64,
+    end: u64,
+    downloaded: u64,
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        self.end + 1 - self.start
+    }
+
+    fn is_finished(&self) -> bool {
+        self.downloaded >= self.len()
+    }
+}
+
+enum SegmentState {
+    Finished,
+    Cancelled,
+}
+
+// The file on the server when it answers ranged requests. The validator is
+// its strong ETag or Last-Modified, the ranges are only taken from the same file
+#[derive(Debug, Clone, PartialEq)]
+struct RemoteFile {
+    total_size: u64,
+    validator: Option<String>,
 }
 
 impl Downloader {
@@ -31,23 +85,390 @@ impl Downloader {
             url,
             save_path,
             cancel_sig: Arc::new(AtomicBool::new(false)),
+            pause_sig: Arc::new(AtomicBool::new(false)),
+            limiter: RateLimiter::default(),
+            connections: 4,
+            retries: 3,
         }
     }
 
+    pub fn with_connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    // Bytes per second of all the connections together, 0 is unlimited
+    pub fn with_rate_limit(mut self, bytes_per_sec: u64) -> Self {
+        self.limiter = RateLimiter::new(bytes_per_sec);
+        self
+    }
+
+    // Share the limiter with other downloads to cap them together
+    pub fn with_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    // The rate can be changed on it while downloading
+    pub fn limiter(&self) -> RateLimiter {
+        self.limiter.clone()
+    }
+
+    // Downloads into `<save_path>.tmp` and renames it when finished. The
+    // progress of a ranged download is kept in `<save_path>.tmp.progress`,
+    // so a cancelled or failed download resumes from the saved data
     pub async fn start(
         &self,
         mut progress_cb: impl FnMut(u64, u64, f32) + 'static,
     ) -> Result<DownloadState> {
         let tmp_filepath = self.save_path.with_added_extension("tmp");
+        let progress_filepath = tmp_filepath.with_added_extension("progress");
+        let client = Client::new();
+
+        let Some(remote) = self.probe_range_support(&client).await? else {
+            log::info!("{} doesn't support ranges, download it at once", self.url);
+            _ = fs::remove_file(&progress_filepath);
+            return self
+                .download_whole(&client, &tmp_filepath, progress_cb)
+                .await;
+        };
+
+        let total_size = remote.total_size;
+        let mut segments = match load_progress(&progress_filepath, &self.url, &remote) {
+            Some(segments) if file_len(&tmp_filepath) == Some(total_size) => {
+                log::info!(
+                    "Resume {} from {} bytes",
+                    self.url,
+                    segments.iter().map(|s| s.downloaded).sum::<u64>()
+                );
+                segments
+            }
+            _ => {
+                let file = fs::File::create(&tmp_filepath).map_err(|e| {
+                    DownloadError::FileCreateError {
+                        error: e,
+                        path: tmp_filepath.clone(),
+                    }
+                })?;
+                file.set_len(total_size)?;
+                split_segments(total_size, self.connections)
+            }
+        };
+
+        save_progress(&progress_filepath, &self.url, &remote, &segments)?;
+
+        let (progress_sender, mut progress_receiver) = mpsc::unbounded::<(usize, u64)>();
+        let workers = future::join_all(
+            segments
+                .iter()
+                .enumerate()
+                .filter(|(_, segment)| !segment.is_finished())
+                .map(|(index, segment)| {
+                    self.download_segment(
+                        &client,
+                        &tmp_filepath,
+                        remote.validator.as_deref(),
+                        index,
+                        segment.clone(),
+                        progress_sender.clone(),
+                    )
+                }),
+        );
+        drop(progress_sender);
 
+        // Only the reporter touches the callback and the progress file, the
+        // workers send it the bytes they saved
+        let reporter = async {
+            let mut last_save = Instant::now();
+            while let Some((index, bytes)) = progress_receiver.next().await {
+                segments[index].downloaded += bytes;
+
+                let downloaded = segments.iter().map(|s| s.downloaded).sum::<u64>();
+                progress_cb(
+                    downloaded,
+                    total_size,
+                    downloaded as f32 / total_size as f32,
+                );
+
+                if last_save.elapsed() > PROGRESS_SAVE_INTERVAL {
+                    if let Err(e) = save_progress(&progress_filepath, &self.url, &remote, &segments)
+                    {
+                        log::warn!("save download progress failed: {e}");
+                    }
+                    last_save = Instant::now();
+                }
+            }
+        };
+
+        let (results, _) = future::join(workers, reporter).await;
+        save_progress(&progress_filepath, &self.url, &remote, &segments)?;
+
+        let mut cancelled = false;
+        for result in results {
+            match result {
+                Ok(SegmentState::Finished) => {}
+                Ok(SegmentState::Cancelled) => cancelled = true,
+                Err(e @ DownloadError::RemoteChanged { .. }) => {
+                    // The saved ranges are of the old file
+                    _ = fs::remove_file(&progress_filepath);
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if cancelled {
+            return Ok(DownloadState::Cancelled);
+        }
+
+        if segments.iter().all(|s| s.is_finished()) {
+            _ = fs::remove_file(&progress_filepath);
+            _ = fs::rename(&tmp_filepath, &self.save_path);
+            Ok(DownloadState::Finsished)
+        } else {
+            Ok(DownloadState::Incompleted)
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel_sig.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancel_sig(&self) -> Arc<AtomicBool> {
+        self.cancel_sig.clone()
+    }
+
+    // Stop reading from the connections until `resume`. A connection the
+    // server closes meanwhile is opened again from the saved data
+    pub fn pause(&self) {
+        self.pause_sig.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.pause_sig.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_sig.load(Ordering::Relaxed)
+    }
+
+    pub fn pause_sig(&self) -> Arc<AtomicBool> {
+        self.pause_sig.clone()
+    }
+
+    // Wait for the rate limit and a pause before reading more, false when
+    // cancelled meanwhile
+    async fn throttle(&self, bytes: u64) -> bool {
+        self.limiter.acquire(bytes).await;
+
+        while self.pause_sig.load(Ordering::Relaxed) {
+            if self.cancel_sig.load(Ordering::Relaxed) {
+                return false;
+            }
+            tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+
+        !self.cancel_sig.load(Ordering::Relaxed)
+    }
+
+    // The file when the server answers ranged requests, None otherwise
+    async fn probe_range_support(&self, client: &Client) -> Result<Option<RemoteFile>> {
+        let response = client
+            .get(&self.url)
+            .header(header::RANGE, "bytes=0-0")
+            .send()
+            .await
+            .map_err(|e| DownloadError::RequestError {
+                error: e,
+                url: self.url.to_string(),
+            })?;
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(None);
+        }
+
+        // Content-Range: bytes 0-0/<total>
+        let total_size = response
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit('/').next())
+            .and_then(|total| total.trim().parse::<u64>().ok())
+            .filter(|total| *total > 0);
+
+        Ok(total_size.map(|total_size| RemoteFile {
+            total_size,
+            validator: validator(response.headers()),
+        }))
+    }
+
+    async fn download_segment(
+        &self,
+        client: &Client,
+        tmp_filepath: &Path,
+        validator: Option<&str>,
+        index: usize,
+        mut segment: Segment,
+        progress_sender: mpsc::UnboundedSender<(usize, u64)>,
+    ) -> Result<SegmentState> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_filepath)
+            .map_err(|e| DownloadError::FileCreateError {
+                error: e,
+                path: tmp_filepath.to_path_buf(),
+            })?;
+
+        let mut attempt = 0;
+        loop {
+            if !self.throttle(0).await {
+                return Ok(SegmentState::Cancelled);
+            }
+
+            let downloaded = segment.downloaded;
+            let result = self
+                .download_segment_once(
+                    client,
+                    &mut file,
+                    validator,
+                    index,
+                    &mut segment,
+                    &progress_sender,
+                )
+                .await;
+
+            // Only failures in a row count, a long pause or a flaky network
+            // would use up the retries of a large range otherwise
+            if segment.downloaded > downloaded {
+                attempt = 0;
+            }
+
+            match result {
+                Ok(state) => return Ok(state),
+                Err(e @ DownloadError::RemoteChanged { .. }) => return Err(e),
+                Err(e) if attempt < self.retries => {
+                    attempt += 1;
+                    log::warn!(
+                        "download range {}-{} failed, retry {attempt}/{}: {e}",
+                        segment.start,
+                        segment.end,
+                        self.retries
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn download_segment_once(
+        &self,
+        client: &Client,
+        file: &mut fs::File,
+        validator: Option<&str>,
+        index: usize,
+        segment: &mut Segment,
+        progress_sender: &mpsc::UnboundedSender<(usize, u64)>,
+    ) -> Result<SegmentState> {
+        if segment.is_finished() {
+            return Ok(SegmentState::Finished);
+        }
+
+        let offset = segment.start + segment.downloaded;
+        let mut request = client
+            .get(&self.url)
+            .header(header::RANGE, format!("bytes={offset}-{}", segment.end));
+
+        // The server sends the whole file instead of the range if it changed
+        if let Some(validator) = validator {
+            request = request.header(header::IF_RANGE, validator);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| DownloadError::RequestError {
+                error: e,
+                url: self.url.to_string(),
+            })?;
+
+        if validator.is_some() && response.status() == StatusCode::OK {
+            return Err(DownloadError::RemoteChanged {
+                url: self.url.to_string(),
+            });
+        }
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(DownloadError::IncompleteDownload {
+                error: format!(
+                    "unexpected status of a ranged request: {}",
+                    response.status()
+                ),
+                downloaded: segment.downloaded,
+                total: segment.len(),
+            });
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        let mut stream = response.bytes_stream();
+
+        while let Some(chunk) = stream.next().await {
+            if self.cancel_sig.load(Ordering::Relaxed) {
+                return Ok(SegmentState::Cancelled);
+            }
+
+            let chunk = chunk.map_err(|e| DownloadError::IncompleteDownload {
+                error: e.to_string(),
+                downloaded: segment.downloaded,
+                total: segment.len(),
+            })?;
+
+            // A server ignoring the range end mustn't overwrite the next range
+            let remaining = segment.len() - segment.downloaded;
+            let chunk = &chunk[..(chunk.len() as u64).min(remaining) as usize];
+            file.write_all(chunk)?;
+
+            segment.downloaded += chunk.len() as u64;
+            _ = progress_sender.unbounded_send((index, chunk.len() as u64));
+
+            if segment.is_finished() {
+                break;
+            }
+
+            if !self.throttle(chunk.len() as u64).await {
+                return Ok(SegmentState::Cancelled);
+            }
+        }
+
+        if segment.is_finished() {
+            Ok(SegmentState::Finished)
+        } else {
+            Err(DownloadError::IncompleteDownload {
+                error: "connection closed".to_string(),
+                downloaded: segment.downloaded,
+                total: segment.len(),
+            })
+        }
+    }
+
+    async fn download_whole(
+        &self,
+        client: &Client,
+        tmp_filepath: &Path,
+        mut progress_cb: impl FnMut(u64, u64, f32) + 'static,
+    ) -> Result<DownloadState> {
         let mut save_file =
-            fs::File::create(&tmp_filepath).map_err(|e| DownloadError::FileCreateError {
+            fs::File::create(tmp_filepath).map_err(|e| DownloadError::FileCreateError {
                 error: e,
-                path: tmp_filepath.clone(),
+                path: tmp_filepath.to_path_buf(),
             })?;
 
         let response =
-            Client::new()
+            client
                 .get(&self.url)
                 .send()
                 .await
@@ -79,21 +500,259 @@ impl Downloader {
 
             let progress = downloaded as f32 / total_size as f32;
             progress_cb(downloaded, total_size, progress);
+
+            if !self.throttle(chunk.len() as u64).await {
+                return Ok(DownloadState::Cancelled);
+            }
         }
 
         if total_size == downloaded {
-            _ = fs::rename(&tmp_filepath, &self.save_path);
+            _ = fs::rename(tmp_filepath, &self.save_path);
             Ok(DownloadState::Finsished)
         } else {
             Ok(DownloadState::Incompleted)
         }
     }
+}
 
-    pub fn cancel(&self) {
-        self.cancel_sig.store(true, Ordering::Relaxed);
+fn split_segments(total_size: u64, connections: usize) -> Vec<Segment> {
+    let count = (total_size / MIN_SEGMENT_SIZE).clamp(1, connections.max(1) as u64);
+    let segment_size = total_size.div_ceil(count);
+
+    (0..count)
+        .map(|index| index * segment_size)
+        .take_while(|start| *start < total_size)
+        .map(|start| Segment {
+            start,
+            end: (start + segment_size).min(total_size) - 1,
+            downloaded: 0,
+        })
+        .collect()
+}
+
+// If-Range takes a strong ETag or a date, a weak ETag never matches
+fn validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+
+    header(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(header::LAST_MODIFIED))
+        .map(str::to_string)
+}
+
+fn file_len(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+// Progress file lines: header, url, total size, validator (empty when the
+// server sends none), then `start end downloaded` of each range
+fn save_progress(path: &Path, url: &str, remote: &RemoteFile, segments: &[Segment]) -> Result<()> {
+    let mut content = format!(
+        "{PROGRESS_FILE_HEADER}\n{url}\n{}\n{}\n",
+        remote.total_size,
+        remote.validator.as_deref().unwrap_or_default()
+    );
+    for segment in segments {
+        content.push_str(&format!(
+            "{} {} {}\n",
+            segment.start, segment.end, segment.downloaded
+        ));
     }
 
-    pub fn cancel_sig(&self) -> Arc<AtomicBool> {
-        self.cancel_sig.clone()
+    // Written aside and renamed, so a crash never leaves a truncated file
+    let tmp_path = path.with_added_extension("tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// The saved ranges when the progress file is of the same download and the
+// file on the server didn't change
+fn load_progress(path: &Path, url: &str, remote: &RemoteFile) -> Option<Vec<Segment>> {
+    let total_size = remote.total_size;
+    let content = fs::read_to_string(path).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != PROGRESS_FILE_HEADER
+        || lines.next()? != url
+        || lines.next()?.parse::<u64>().ok()? != total_size
+        || lines.next()? != remote.validator.as_deref().unwrap_or_default()
+    {
+        return None;
+    }
+
+    let segments = lines
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split_whitespace().map(|v| v.parse::<u64>().ok());
+            let (start, end, downloaded) = (fields.next()??, fields.next()??, fields.next()??);
+            (start <= end && end < total_size).then(|| Segment {
+                start,
+                end,
+                downloaded: downloaded.min(end + 1 - start),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // The ranges must cover the file without gaps
+    let mut next = 0;
+    for segment in &segments {
+        if segment.start != next {
+            return None;
+        }
+        next = segment.end + 1;
+    }
+
+    (next == total_size).then_some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn segment(start: u64, end: u64, downloaded: u64) -> Segment {
+        Segment {
+            start,
+            end,
+            downloaded,
+        }
+    }
+
+    fn remote(total_size: u64, validator: Option<&str>) -> RemoteFile {
+        RemoteFile {
+            total_size,
+            validator: validator.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_split_segments() {
+        assert_eq!(split_segments(1, 4), vec![segment(0, 0, 0)]);
+        assert_eq!(
+            split_segments(MIN_SEGMENT_SIZE - 1, 4),
+            vec![segment(0, MIN_SEGMENT_SIZE - 2, 0)]
+        );
+        assert_eq!(
+            split_segments(2 * MIN_SEGMENT_SIZE, 4),
+            vec![
+                segment(0, MIN_SEGMENT_SIZE - 1, 0),
+                segment(MIN_SEGMENT_SIZE, 2 * MIN_SEGMENT_SIZE - 1, 0),
+            ]
+        );
+        assert_eq!(split_segments(10 * MIN_SEGMENT_SIZE, 0).len(), 1);
+
+        // The ranges cover the file without gaps
+        for (total_size, connections) in [(10 * MIN_SEGMENT_SIZE + 1, 4), (99_999_999, 7)] {
+            let segments = split_segments(total_size, connections);
+            assert_eq!(segments.len(), connections);
+            assert_eq!(segments[0].start, 0);
+            assert_eq!(segments.last().unwrap().end, total_size - 1);
+            for pair in segments.windows(2) {
+                assert_eq!(pair[0].end + 1, pair[1].start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_validator() {
+        let headers = |items: &[(header::HeaderName, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in items {
+                headers.insert(name, HeaderValue::from_str(value).unwrap());
+            }
+            headers
+        };
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        assert_eq!(validator(&headers(&[])), None);
+        assert_eq!(
+            validator(&headers(&[
+                (header::ETAG, "\"abc\""),
+                (header::LAST_MODIFIED, date)
+            ])),
+            Some("\"abc\"".to_string())
+        );
+        assert_eq!(
+            validator(&headers(&[
+                (header::ETAG, "W/\"abc\""),
+                (header::LAST_MODIFIED, date)
+            ])),
+            Some(date.to_string())
+        );
+        assert_eq!(validator(&headers(&[(header::ETAG, "W/\"abc\"")])), None);
+    }
+
+    #[test]
+    fn test_progress_file() -> anyhow::Result<()> {
+        let path = Path::new("/tmp/test-downloader.progress");
+        let url = "https://example.com/a.mp4";
+        let segments = vec![segment(0, 99, 100), segment(100, 249, 20)];
+
+        let file = remote(250, Some("\"abc\""));
+        save_progress(path, url, &file, &segments)?;
+        assert_eq!(load_progress(path, url, &file), Some(segments.clone()));
+
+        // Another download or a changed file isn't resumed
+        assert_eq!(
+            load_progress(path, "https://example.com/b.mp4", &file),
+            None
+        );
+        assert_eq!(
+            load_progress(path, url, &remote(251, Some("\"abc\""))),
+            None
+        );
+        assert_eq!(
+            load_progress(path, url, &remote(250, Some("\"def\""))),
+            None
+        );
+        assert_eq!(load_progress(path, url, &remote(250, None)), None);
+
+        let file = remote(250, None);
+        save_progress(path, url, &file, &segments)?;
+        assert_eq!(load_progress(path, url, &file), Some(segments));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_progress_file() -> anyhow::Result<()> {
+        let path = Path::new("/tmp/test-downloader-invalid.progress");
+        let url = "https://example.com/a.mp4";
+        let file = remote(250, None);
+        let progress = |lines: &str| format!("{PROGRESS_FILE_HEADER}\n{url}\n250\n\n{lines}");
+
+        // The downloaded bytes are capped by the range
+        fs::write(path, progress("0 99 500\n100 249 0\n"))?;
+        assert_eq!(
+            load_progress(path, url, &file),
+            Some(vec![segment(0, 99, 100), segment(100, 249, 0)])
+        );
+
+        for lines in [
+            "0 99 0\n101 249 0\n",
+            "0 99 0\n",
+            "0 99 0\n100 250 0\n",
+            "0 99\n100 249 0\n",
+            "0 99 x\n100 249 0\n",
+            "99 0 0\n",
+        ] {
+            fs::write(path, progress(lines))?;
+            assert_eq!(load_progress(path, url, &file), None, "{lines}");
+        }
+
+        fs::write(path, format!("wayshot-downloader 1\n{url}\n250\n0 249 0\n"))?;
+        assert_eq!(load_progress(path, url, &file), None);
+
+        assert_eq!(
+            load_progress(Path::new("/tmp/no-such.progress"), url, &file),
+            None
+        );
+        Ok(())
     }
 }
@@ -1,6 +1,8 @@
 pub mod downloader;
+pub mod limiter;
 
 pub use downloader::{DownloadState, Downloader};
+pub use limiter::RateLimiter;
 
 pub type Result<T> = std::result::Result<T, DownloadError>;
 
@@ -25,6 +27,9 @@ pub enum DownloadError {
         total: u64,
     },
 
+    #[error("{url} changed on the server while downloading")]
+    RemoteChanged { url: String },
+
     #[error("Failed to create file: {path}. Error: {error}")]
     FileCreateError {
         error: std::io::Error,
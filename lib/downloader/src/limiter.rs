@@ -0,0 +1,162 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// Token bucket limiting the bytes per second of the downloads it's passed to.
+// Clones share the bucket, so one limiter caps several downloads together and
+// its rate can be changed while they are running
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    // Bytes per second, 0 is unlimited
+    rate: u64,
+
+    // Negative after a chunk larger than the saved tokens, the next
+    // acquirers wait until the debt is paid back
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        // Up to one second of data can be saved for a burst
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl RateLimiter {
+    // `bytes_per_sec` of 0 is unlimited
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: bytes_per_sec,
+                tokens: bytes_per_sec as f64,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().unwrap().rate
+    }
+
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.rate = bytes_per_sec;
+        bucket.tokens = bucket.tokens.min(bytes_per_sec as f64);
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.rate() == 0
+    }
+
+    // Take `bytes` from the bucket, waiting until they fit the rate
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            if bucket.rate == 0 {
+                return;
+            }
+
+            bucket.refill();
+            bucket.tokens -= bytes as f64;
+            (bucket.tokens < 0.0)
+                .then(|| Duration::from_secs_f64(-bucket.tokens / bucket.rate as f64))
+        };
+
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pretend `elapsed` passed since the last refill
+    fn age(limiter: &RateLimiter, elapsed: Duration) {
+        limiter.bucket.lock().unwrap().last_refill -= elapsed;
+    }
+
+    fn tokens(limiter: &RateLimiter) -> f64 {
+        let mut bucket = limiter.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.tokens
+    }
+
+    #[tokio::test]
+    async fn test_burst() {
+        let limiter = RateLimiter::new(10_000);
+
+        // One second of data goes through at once
+        let start = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // The next chunk waits for its tokens
+        let start = Instant::now();
+        limiter.acquire(3_000).await;
+        assert!(start.elapsed() >= Duration::from_millis(290));
+        assert!(start.elapsed() < Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn test_refill() {
+        let limiter = RateLimiter::new(1_000);
+        limiter.bucket.lock().unwrap().tokens = -500.0;
+
+        age(&limiter, Duration::from_millis(200));
+        let refilled = tokens(&limiter);
+        assert!((-300.0..-250.0).contains(&refilled), "{refilled}");
+
+        // The saved tokens are capped at one second
+        age(&limiter, Duration::from_secs(5));
+        assert_eq!(tokens(&limiter), 1_000.0);
+
+        // Lowering the rate drops the tokens over the new burst
+        limiter.set_rate(100);
+        assert_eq!(tokens(&limiter), 100.0);
+
+        // Clones share the bucket
+        let clone = limiter.clone();
+        clone.set_rate(2_000);
+        assert_eq!(limiter.rate(), 2_000);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited() {
+        let limiter = RateLimiter::default();
+        assert!(limiter.is_unlimited());
+
+        let start = Instant::now();
+        for _ in 0..100 {
+            limiter.acquire(u64::MAX / 2).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // The debt of a limited rate is gone once it's unlimited
+        let limiter = RateLimiter::new(1);
+        limiter.bucket.lock().unwrap().tokens = -1_000_000.0;
+        limiter.set_rate(0);
+        let start = Instant::now();
+        limiter.acquire(1_000_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,50 @@
+use fun_ast_nano::{
+    FunASRModelConfig, FunAsrNanoGenerateModel, StreamingConfig, StreamingTranscriber,
+    load_audio_file,
+};
+use std::{sync::mpsc, thread, time::Duration};
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let model_dir = "./Fun-ASR-Nano-2512";
+    let config = FunASRModelConfig::default()
+        .with_model_weights(format!("{}/model.pt", model_dir))
+        .with_tokenizer_path(format!("{}/Qwen3-0.6B/tokenizer.json", model_dir));
+
+    let audio_path = "./data/65s.wav";
+    let audio_config = load_audio_file(audio_path)?;
+
+    log::debug!("Loading model...");
+    let model = FunAsrNanoGenerateModel::new(config, None, None)?;
+
+    let streaming_config = StreamingConfig::default()
+        .with_input_sample_rate(audio_config.sample_rate)
+        .with_input_channels(audio_config.channel);
+    let mut transcriber = StreamingTranscriber::new(model, streaming_config)?;
+
+    // Feed the file in 20ms frames like a recorder
+    let (sender, receiver) = mpsc::channel();
+    let frame_size = audio_config.sample_rate as usize / 50 * audio_config.channel as usize;
+    thread::spawn(move || {
+        for frame in audio_config.samples.chunks(frame_size) {
+            if sender.send(frame.to_vec()).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+
+    transcriber.run(receiver.iter(), |text| {
+        log::debug!(
+            "[{}ms-{}ms] {} {}",
+            text.start_ms,
+            text.end_ms,
+            if text.is_final { "final" } else { "partial" },
+            text.text
+        );
+        Ok(())
+    })?;
+
+    Ok(())
+}
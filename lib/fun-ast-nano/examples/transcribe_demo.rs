@@ -7,7 +7,8 @@ fn main() -> anyhow::Result<()> {
     let model_dir = "./Fun-ASR-Nano-2512";
     let config = FunASRModelConfig::default()
         .with_model_weights(format!("{}/model.pt", model_dir))
-        .with_tokenizer_path(format!("{}/Qwen3-0.6B/tokenizer.json", model_dir));
+        .with_tokenizer_path(format!("{}/Qwen3-0.6B/tokenizer.json", model_dir))
+        .with_batch_size(4);
 
     // let audio_path = "./data/nejia.wav";
     let audio_path = "./data/65s.wav";
@@ -45,9 +46,17 @@ fn main() -> anyhow::Result<()> {
                     total_tokens
                 );
 
+                if let Some(speaker_id) = seg_info.speaker_id {
+                    log::debug!("Speaker {speaker_id}");
+                }
+
                 if !chunk.text.is_empty() {
                     log::debug!("\"{}\"\n", chunk.text);
                 }
+
+                for word in &seg_info.words {
+                    log::debug!("  {}ms-{}ms {}", word.start_ms, word.end_ms, word.text);
+                }
             } else {
                 log::debug!(
                     "\r {:.1}% | {} tokens",
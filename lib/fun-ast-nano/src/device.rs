@@ -1,6 +1,27 @@
 use crate::{FunAsrError, Result};
 use candle_core::{DType, Device};
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ComputeDevice {
+    /// The GPU of the enabled `cuda` or `metal` feature, or the CPU
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(usize),
+    Metal(usize),
+}
+
+impl ComputeDevice {
+    pub fn to_device(self) -> Result<Device> {
+        Ok(match self {
+            ComputeDevice::Auto => get_device(None),
+            ComputeDevice::Cpu => Device::Cpu,
+            ComputeDevice::Cuda(ordinal) => Device::new_cuda(ordinal)?,
+            ComputeDevice::Metal(ordinal) => Device::new_metal(ordinal)?,
+        })
+    }
+}
+
 pub fn get_device(device: Option<&Device>) -> Device {
     device.cloned().unwrap_or_else(|| {
         #[cfg(feature = "cuda")]
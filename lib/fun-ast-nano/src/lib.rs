@@ -9,13 +9,20 @@ pub const ENGLISH_PUNCTUATIONS: &[char] = &[',', '.', '!', '?'];
 pub const CHINESE_PUNCTUATIONS: &[char] = &['，', '。', '！', '？'];
 
 pub use audio_utils::vad::{AudioSegment, VadConfig, detect_speech_segments};
+pub use device::ComputeDevice;
 pub use hound::SampleFormat;
 pub use model::{
     Model,
-    fun_asr_nano::generate::{
-        FunASRModelConfig, FunAsrNanoGenerateModel, SegmentInfo, StreamChunk, TranscriptionRequest,
-        TranscriptionResponse, load_audio_file,
+    fun_asr_nano::{
+        alignment::WordTimestamp,
+        diarization::DiarizationConfig,
+        generate::{
+            AsrBackend, DEFAULT_HOTWORD_BOOST, FunASRModelConfig, FunAsrNanoGenerateModel,
+            SegmentInfo, StreamChunk, TranscriptionRequest, TranscriptionResponse, load_audio_file,
+        },
+        streaming::{StreamingConfig, StreamingText, StreamingTranscriber},
     },
+    language::Language,
 };
 
 pub type Result<T> = std::result::Result<T, FunAsrError>;
@@ -52,6 +59,9 @@ pub enum FunAsrError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("ONNX runtime error: {0}")]
+    Onnx(#[from] ort::Error),
+
     #[error("Transcribe cancelled")]
     TranscribeCancelled,
 }
@@ -70,6 +80,7 @@ impl From<tensor_utils::TensorUtilsError> for FunAsrError {
     fn from(err: tensor_utils::TensorUtilsError) -> Self {
         match err {
             tensor_utils::TensorUtilsError::InvalidInput(msg) => FunAsrError::InvalidInput(msg),
+            tensor_utils::TensorUtilsError::Io(e) => FunAsrError::Io(e),
             tensor_utils::TensorUtilsError::Candle(e) => FunAsrError::Tensor(e),
         }
     }
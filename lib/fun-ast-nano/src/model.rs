@@ -1,6 +1,9 @@
 pub mod common;
 pub mod fun_asr_nano;
+pub(crate) mod hotword;
+pub mod language;
 pub mod qwen3;
+pub mod whisper;
 
 pub use fun_asr_nano::generate::{
     FunAsrNanoGenerateModel, TranscriptionRequest, TranscriptionResponse,
@@ -6,7 +6,7 @@ use candle_nn::{
     conv2d, conv2d_no_bias, layer_norm, linear_b, rms_norm,
 };
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use tensor_utils::repeat_kv;
+use tensor_utils::{kv_cache::KvCache, repeat_kv};
 
 #[derive(Debug, Clone)]
 pub struct GateUpDownMLP {
@@ -90,7 +90,7 @@ pub struct NaiveAttention {
     num_kv_groups: usize,
     head_dim: usize,
     middle_size: usize,
-    kv_cache: Option<(Tensor, Tensor)>,
+    kv_cache: KvCache,
 }
 
 impl NaiveAttention {
@@ -150,7 +150,7 @@ impl NaiveAttention {
             num_kv_groups,
             head_dim,
             middle_size: num_attention_heads * head_dim,
-            kv_cache: None,
+            kv_cache: KvCache::default(),
         })
     }
 
@@ -220,16 +220,7 @@ impl NaiveAttention {
             .transpose(1, 2)?;
         let (query_states, key_states) =
             apply_rotary_pos_emb(&query_states, &key_states, cos, sin, tof32)?;
-        let (key_states, value_states) = match &self.kv_cache {
-            None => (key_states, value_states),
-            Some((prev_k, prev_v)) => {
-                let key_states = Tensor::cat(&[prev_k, &key_states], 2)?;
-                let value_states = Tensor::cat(&[prev_v, &value_states], 2)?;
-                (key_states, value_states)
-            }
-        };
-
-        self.kv_cache = Some((key_states.clone(), value_states.clone()));
+        let (key_states, value_states) = self.kv_cache.append(&key_states, &value_states)?;
         let scale = 1f64 / f64::sqrt(self.head_dim as f64);
         let attn_output = eager_attention_forward(
             &query_states,
@@ -245,7 +236,7 @@ impl NaiveAttention {
     }
 
     pub fn clear_kv_cache(&mut self) {
-        self.kv_cache = None
+        self.kv_cache.reset()
     }
 }
 
@@ -1,5 +1,8 @@
+pub(crate) mod alignment;
 pub(crate) mod config;
+pub(crate) mod diarization;
 pub(crate) mod model;
 pub(crate) mod processor;
 
 pub mod generate;
+pub mod streaming;
@@ -0,0 +1,153 @@
+//! Word timestamps estimated from the audio of a segment. The model does
+//! not output alignments, so the words are spread over the voiced frames of
+//! the segment in proportion to their length in syllables, skipping the
+//! pauses between them.
+
+// A frame is voiced above this fraction of the loud frames energy
+const VOICED_ENERGY_RATIO: f32 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordTimestamp {
+    pub text: String,
+    pub start_ms: u32,
+    pub end_ms: u32,
+}
+
+/// Timestamps of the words of `text` spoken in `samples`, offset by the
+/// start of the segment
+pub(crate) fn estimate_word_timestamps(
+    text: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    offset_ms: u32,
+) -> Vec<WordTimestamp> {
+    let words = split_words(text);
+    if words.is_empty() || samples.is_empty() {
+        return vec![];
+    }
+
+    // 10ms frames
+    let frame_size = (sample_rate as usize / 100).max(1);
+    let frame_ms = frame_size as f32 * 1000.0 / sample_rate as f32;
+    let voiced = voiced_frames(samples, frame_size);
+    let voiced_count = voiced.iter().filter(|&&v| v).count();
+
+    // Silence or noise only, spread the words over the whole segment
+    let voiced = if voiced_count == 0 {
+        vec![true; voiced.len()]
+    } else {
+        voiced
+    };
+    let voiced_count = voiced.iter().filter(|&&v| v).count() as f32;
+
+    let weights = words.iter().map(|w| word_weight(w)).collect::<Vec<_>>();
+    let total_weight = weights.iter().sum::<f32>();
+
+    // Voiced frame index where each word ends
+    let mut word_ends = Vec::with_capacity(words.len());
+    let mut cumulative = 0.0;
+    for weight in &weights {
+        cumulative += weight;
+        word_ends.push(cumulative / total_weight * voiced_count);
+    }
+
+    let mut timestamps = Vec::with_capacity(words.len());
+    let mut voiced_index = 0.0;
+    let mut word_index = 0;
+    let mut word_start = None;
+
+    for (frame, _) in voiced.iter().enumerate().filter(|(_, v)| **v) {
+        word_start.get_or_insert(frame);
+        voiced_index += 1.0;
+
+        while word_index < words.len() && voiced_index >= word_ends[word_index] - 1e-3 {
+            let start = word_start.take().unwrap_or(frame);
+            timestamps.push(WordTimestamp {
+                text: words[word_index].clone(),
+                start_ms: offset_ms + (start as f32 * frame_ms) as u32,
+                end_ms: offset_ms + ((frame + 1) as f32 * frame_ms) as u32,
+            });
+            word_index += 1;
+        }
+    }
+
+    timestamps
+}
+
+/// CJK characters are words on their own, other words are separated by
+/// spaces. Punctuation stays with the word before it.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    let mut current = String::new();
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else if is_cjk(c) {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            words.push(c.to_string());
+        } else if !c.is_alphanumeric() && current.is_empty() {
+            match words.last_mut() {
+                Some(last) => last.push(c),
+                None => current.push(c),
+            }
+        } else {
+            current.push(c);
+        }
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}')
+}
+
+/// Estimated number of syllables
+fn word_weight(word: &str) -> f32 {
+    let mut syllables = 0;
+    let mut in_vowel = false;
+
+    for c in word.chars() {
+        if is_cjk(c) || c.is_ascii_digit() {
+            syllables += 1;
+            in_vowel = false;
+        } else {
+            let vowel = matches!(c.to_ascii_lowercase(), 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+            if vowel && !in_vowel {
+                syllables += 1;
+            }
+            in_vowel = vowel;
+        }
+    }
+
+    syllables.max(1) as f32
+}
+
+fn voiced_frames(samples: &[f32], frame_size: usize) -> Vec<bool> {
+    let energies = samples
+        .chunks(frame_size)
+        .map(|frame| frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32)
+        .collect::<Vec<_>>();
+
+    // The 90th percentile is robust to a few clicks
+    let mut sorted = energies.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let loud = sorted[(sorted.len() - 1) * 9 / 10];
+    let threshold = (loud * VOICED_ENERGY_RATIO).max(1e-8);
+
+    energies.into_iter().map(|e| e > threshold).collect()
+}
@@ -0,0 +1,156 @@
+//! Speaker diarization. Every segment is embedded with a speaker embedding
+//! model (a CAM++ or ECAPA-TDNN ONNX export taking (1, frames, 80) fbank)
+//! and the embeddings are clustered online by cosine similarity, so the
+//! speaker of a segment is known as soon as it is transcribed.
+
+use crate::{FunAsrError, Result};
+use candle_core::Tensor;
+use derivative::Derivative;
+use derive_setters::Setters;
+use ndarray::Array3;
+use ort::{session::Session, value::TensorRef};
+use std::path::Path;
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct DiarizationConfig {
+    #[derivative(Default(value = "String::from(\"speaker_embedding.onnx\")"))]
+    pub embedding_model: String,
+
+    /// A segment closer than this to a known speaker is given its ID,
+    /// otherwise it starts a new speaker
+    #[derivative(Default(value = "0.5"))]
+    pub similarity_threshold: f32,
+
+    #[derivative(Default(value = "8"))]
+    pub max_speakers: usize,
+
+    /// Embeddings of shorter segments are unreliable, they keep the speaker
+    /// of the previous segment
+    #[derivative(Default(value = "600"))]
+    pub min_segment_ms: u32,
+}
+
+struct SpeakerCluster {
+    // Sum of the normalized embeddings of the speaker
+    embedding_sum: Vec<f32>,
+}
+
+pub(crate) struct SpeakerDiarizer {
+    config: DiarizationConfig,
+    session: Session,
+    input_name: String,
+    speakers: Vec<SpeakerCluster>,
+    last_speaker: Option<usize>,
+}
+
+impl SpeakerDiarizer {
+    pub fn new(config: DiarizationConfig) -> Result<Self> {
+        if !Path::new(&config.embedding_model).exists() {
+            return Err(FunAsrError::NotFound(format!(
+                "Speaker embedding model not found: {}",
+                config.embedding_model
+            )));
+        }
+
+        log::info!(
+            "Loading speaker embedding model: {}",
+            config.embedding_model
+        );
+        let session = Session::builder()?.commit_from_file(&config.embedding_model)?;
+        let input_name = session
+            .inputs()
+            .first()
+            .map(|input| input.name().to_string())
+            .ok_or_else(|| FunAsrError::Model("Speaker embedding model has no input".into()))?;
+
+        Ok(Self {
+            config,
+            session,
+            input_name,
+            speakers: vec![],
+            last_speaker: None,
+        })
+    }
+
+    /// Forget the speakers of the previous audio
+    pub fn reset(&mut self) {
+        self.speakers.clear();
+        self.last_speaker = None;
+    }
+
+    /// Speaker ID (1-based) of a segment from its fbank of shape
+    /// (frames, n_mels)
+    pub fn identify(&mut self, fbank: &Tensor, duration_ms: u32) -> Result<usize> {
+        if duration_ms < self.config.min_segment_ms
+            && let Some(speaker) = self.last_speaker
+        {
+            return Ok(speaker + 1);
+        }
+
+        let embedding = self.embed(fbank)?;
+
+        let best = self
+            .speakers
+            .iter()
+            .enumerate()
+            .map(|(index, speaker)| (index, cosine_similarity(&speaker.embedding_sum, &embedding)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let speaker = match best {
+            Some((index, similarity))
+                if similarity >= self.config.similarity_threshold
+                    || self.speakers.len() >= self.config.max_speakers.max(1) =>
+            {
+                let sum = &mut self.speakers[index].embedding_sum;
+                sum.iter_mut().zip(&embedding).for_each(|(s, e)| *s += e);
+                index
+            }
+            _ => {
+                self.speakers.push(SpeakerCluster {
+                    embedding_sum: embedding,
+                });
+                self.speakers.len() - 1
+            }
+        };
+
+        log::debug!(
+            "Segment of {duration_ms}ms is speaker {} of {}",
+            speaker + 1,
+            self.speakers.len()
+        );
+
+        self.last_speaker = Some(speaker);
+        Ok(speaker + 1)
+    }
+
+    fn embed(&mut self, fbank: &Tensor) -> Result<Vec<f32>> {
+        let (frames, n_mels) = fbank.dims2()?;
+        let features = fbank.flatten_all()?.to_vec1::<f32>()?;
+        let input = Array3::from_shape_vec((1, frames, n_mels), features)
+            .map_err(|e| FunAsrError::InvalidInput(e.to_string()))?;
+
+        let outputs = self.session.run(ort::inputs! {
+            &self.input_name => TensorRef::from_array_view(input.view())?
+        })?;
+        let (_, embedding) = outputs[0].try_extract_tensor::<f32>()?;
+
+        let norm = embedding
+            .iter()
+            .map(|v| v * v)
+            .sum::<f32>()
+            .sqrt()
+            .max(1e-12);
+        Ok(embedding.iter().map(|v| v / norm).collect())
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+
+    dot / (norm_a * norm_b).max(1e-12)
+}
@@ -1,10 +1,17 @@
 use crate::{
     ENGLISH_PUNCTUATIONS, FunAsrError, INPUT_AUDIO_CHANNELS, INPUT_AUDIO_SAMPLE_RATE, Result,
-    device::{get_device, get_dtype},
+    device::{ComputeDevice, get_dtype},
     model::fun_asr_nano::{
-        config::FunASRNanoConfig, model::FunAsrNanoModel, processor::FunAsrNanoProcessor,
+        alignment::{WordTimestamp, estimate_word_timestamps},
+        config::FunASRNanoConfig,
+        diarization::{DiarizationConfig, SpeakerDiarizer},
+        model::FunAsrNanoModel,
+        processor::FunAsrNanoProcessor,
     },
+    model::hotword::HotwordBooster,
+    model::language::Language,
     model::qwen3::{Qwen3Config, Qwen3GenerationConfig},
+    model::whisper::WhisperModel,
     tokenizer::TokenizerModel,
 };
 use audio_utils::{
@@ -15,8 +22,19 @@ use candle_core::{DType, Device, Tensor, pickle::read_all_with_key};
 use candle_nn::VarBuilder;
 use derivative::Derivative;
 use derive_setters::Setters;
-use rand::{Rng, SeedableRng};
 use std::{collections::HashMap, path::Path};
+use tensor_utils::sampling::{Sampler, SamplingConfig};
+
+// Attention bias of the padding of batched segments, finite so that rows
+// of only padding do not turn into NaN
+const PADDING_BIAS: f32 = -1e4;
+
+pub const DEFAULT_HOTWORD_BOOST: f32 = 2.0;
+
+// Tokens decoded to tell the language of a Fun-ASR-Nano segment by its
+// script, with the prompt that leaves the language to the model
+const LANGUAGE_PROBE_TOKENS: usize = 8;
+const LANGUAGE_PROBE_PROMPT: &str = "语音转写：";
 
 const ASR_CONFIG_YAML: &str = include_str!("../../../asset/config.yaml");
 const QWEN3_0_6B_LLM_CONFIG_JSON: &str = include_str!("../../../asset/qwen3_0.6b_config.json");
@@ -28,11 +46,38 @@ const QWEN3_0_6B_GENERATION_CONFIG: &str =
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct FunASRModelConfig {
+    pub backend: AsrBackend,
+
     #[derivative(Default(value = "String::from(\"model.pt\")"))]
     pub model_weights: String,
 
     #[derivative(Default(value = "String::from(\"qwen3_0.6B_tokenizer.json\")"))]
     pub tokenizer_path: String,
+
+    /// `config.json` of the Whisper model, unused by Fun-ASR-Nano
+    #[derivative(Default(value = "String::from(\"config.json\")"))]
+    pub whisper_config_path: String,
+
+    /// Label the segments with their speaker
+    pub diarization: Option<DiarizationConfig>,
+
+    /// Used when no device is passed to `FunAsrNanoGenerateModel::new`
+    pub device: ComputeDevice,
+
+    /// Segments decoded together. Larger batches are much faster on a GPU
+    #[derivative(Default(value = "1"))]
+    pub batch_size: usize,
+}
+
+/// Model behind `FunAsrNanoGenerateModel`. `model_weights` and
+/// `tokenizer_path` are the files of the selected model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AsrBackend {
+    #[default]
+    FunAsrNano,
+
+    /// Slower, better multilingual accuracy. Weights in safetensors format
+    Whisper,
 }
 
 #[derive(Debug, Clone, Derivative, Setters)]
@@ -46,12 +91,40 @@ pub struct TranscriptionRequest {
     pub max_tokens: u32,
     pub temperature: Option<f32>,
     pub top_p: Option<f32>,
+
+    /// Terms the decoding is biased to, set with `with_hotwords`
+    #[setters(skip)]
+    pub hotwords: Vec<String>,
+
+    #[setters(skip)]
+    #[derivative(Default(value = "DEFAULT_HOTWORD_BOOST"))]
+    pub hotword_boost: f32,
+
+    /// Detect the language of every segment before decoding it. Costs a
+    /// short extra decoding pass per segment
+    pub detect_language: bool,
+
+    /// Decode each segment with the prompt of its detected language instead
+    /// of `prompt`, which keeps English speech in a Chinese recording from
+    /// being transliterated
+    pub language_prompts: bool,
+}
+
+impl TranscriptionRequest {
+    /// `boost` is added to the logits of the hotword tokens, 1 ~ 5 works
+    /// for most terms
+    pub fn with_hotwords(mut self, hotwords: Vec<String>, boost: f32) -> Self {
+        self.hotwords = hotwords;
+        self.hotword_boost = boost;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TranscriptionResponse {
     pub text: String,
     pub num_tokens: u32,
+    pub words: Vec<WordTimestamp>,
 }
 
 #[derive(Debug, Clone)]
@@ -81,12 +154,30 @@ pub struct SegmentInfo {
     pub total_segments: usize,
     pub segment_start_ms: u32,
     pub segment_end_ms: u32,
+
+    /// Estimated times of the words of the segment, on the timeline of the
+    /// whole audio
+    pub words: Vec<WordTimestamp>,
+
+    /// Speaker of the segment (1-based), `None` without diarization
+    pub speaker_id: Option<usize>,
+
+    /// Spoken language, `None` without `detect_language` or when it is
+    /// not recognized
+    pub language: Option<Language>,
+}
+
+enum SpeechModel {
+    FunAsrNano(FunAsrNanoModel),
+    Whisper(WhisperModel),
 }
 
 pub struct FunAsrNanoGenerateModel {
     tokenizer: TokenizerModel,
     processor: FunAsrNanoProcessor,
-    fun_asr_nano: FunAsrNanoModel,
+    model: SpeechModel,
+    diarizer: Option<SpeakerDiarizer>,
+    batch_size: usize,
     device: Device,
     dtype: DType,
     eos_token_id1: u32,
@@ -109,30 +200,54 @@ impl FunAsrNanoGenerateModel {
 
         let cfg_dtype = cfg.llm_conf.llm_dtype.as_str();
         let dtype = get_dtype(dtype, cfg_dtype)?;
-        let device = get_device(device);
+        let device = match device {
+            Some(device) => device.clone(),
+            None => config.device.to_device()?,
+        };
+        log::info!(
+            "Transcribe on {device:?} in batches of {}",
+            config.batch_size
+        );
+
         let processor = FunAsrNanoProcessor::new(&cfg.frontend_conf, &device)?;
 
-        let tensor_vec: Vec<(String, Tensor)> =
-            match read_all_with_key(&config.model_weights, Some("state_dict")) {
-                Ok(dict) => dict,
-                Err(e) => {
-                    log::warn!(
-                        "model read_all_with_key {} get state_dict err: {}, use None try again",
-                        &config.model_weights,
-                        e
-                    );
-                    read_all_with_key(&config.model_weights, None)?
-                }
-            };
+        let model = match config.backend {
+            AsrBackend::FunAsrNano => {
+                let tensor_vec: Vec<(String, Tensor)> = match read_all_with_key(
+                    &config.model_weights,
+                    Some("state_dict"),
+                ) {
+                    Ok(dict) => dict,
+                    Err(e) => {
+                        log::warn!(
+                            "model read_all_with_key {} get state_dict err: {}, use None try again",
+                            &config.model_weights,
+                            e
+                        );
+                        read_all_with_key(&config.model_weights, None)?
+                    }
+                };
+
+                let dict: HashMap<String, Tensor> = tensor_vec.into_iter().collect();
+                let vb = VarBuilder::from_tensors(dict, dtype, &device);
+                SpeechModel::FunAsrNano(FunAsrNanoModel::new(vb, &cfg, &llm_cfg)?)
+            }
+            AsrBackend::Whisper => SpeechModel::Whisper(WhisperModel::new(
+                &config.model_weights,
+                &config.whisper_config_path,
+                &tokenizer,
+                &device,
+            )?),
+        };
 
-        let dict: HashMap<String, Tensor> = tensor_vec.into_iter().collect();
-        let vb = VarBuilder::from_tensors(dict, dtype, &device);
-        let fun_asr_nano = FunAsrNanoModel::new(vb, &cfg, &llm_cfg)?;
+        let diarizer = config.diarization.map(SpeakerDiarizer::new).transpose()?;
 
         Ok(Self {
             tokenizer,
             processor,
-            fun_asr_nano,
+            model,
+            diarizer,
+            batch_size: config.batch_size.max(1),
             device,
             dtype,
             eos_token_id1: generation_config.eos_token_id[0] as u32,
@@ -160,12 +275,22 @@ impl FunAsrNanoGenerateModel {
             return Ok(TranscriptionResponse {
                 text: String::new(),
                 num_tokens: 0,
+                words: vec![],
             });
         }
 
+        if let Some(diarizer) = self.diarizer.as_mut() {
+            diarizer.reset();
+        }
+
+        let hotwords = self.hotword_booster(&request.hotwords, request.hotword_boost)?;
+
         let total_segments = segments.len();
         let mut all_text = String::new();
+        let mut all_words = vec![];
         let mut total_tokens = 0;
+        let mut batch_results = vec![].into_iter();
+        let mut batch_languages = vec![].into_iter();
 
         for (segment_idx, segment) in segments.iter().enumerate() {
             let segment_num = segment_idx + 1;
@@ -181,21 +306,74 @@ impl FunAsrNanoGenerateModel {
                 segment_end_ms
             );
 
+            if segment_idx % self.batch_size == 0 {
+                let batch = segments[segment_idx..]
+                    .iter()
+                    .take(self.batch_size)
+                    .map(|segment| segment.audio_data.as_slice())
+                    .collect::<Vec<_>>();
+
+                let languages = batch
+                    .iter()
+                    .map(|audio_data| match request.detect_language {
+                        true => self.detect_language(audio_data),
+                        false => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let prompts = languages
+                    .iter()
+                    .map(|language| match language {
+                        Some(language) if request.language_prompts => {
+                            language.prompt().or(request.prompt.as_deref())
+                        }
+                        _ => request.prompt.as_deref(),
+                    })
+                    .collect::<Vec<_>>();
+
+                batch_results = self
+                    .transcribe_segments(
+                        &batch,
+                        &prompts,
+                        request.max_tokens,
+                        request.temperature,
+                        request.top_p,
+                        hotwords.as_ref(),
+                    )?
+                    .into_iter();
+                batch_languages = languages.into_iter();
+            }
+
+            let language = batch_languages.next().flatten();
+            let Some(segment_result) = batch_results.next() else {
+                return Err(FunAsrError::Model(format!(
+                    "No transcription of segment {segment_num}"
+                )));
+            };
+
+            let words = estimate_word_timestamps(
+                &segment_result.text,
+                &segment.audio_data,
+                sample_rate,
+                segment_start_ms,
+            );
+
+            let speaker_id = if segment_result.text.is_empty() {
+                None
+            } else {
+                self.identify_speaker(&segment.audio_data, segment_end_ms - segment_start_ms)?
+            };
+
             let segment_info = SegmentInfo {
                 current_segment: segment_num,
                 total_segments,
                 segment_start_ms,
                 segment_end_ms,
+                words: words.clone(),
+                speaker_id,
+                language,
             };
 
-            let segment_result = self.transcribe_segment(
-                &segment.audio_data,
-                request.prompt.as_deref(),
-                request.max_tokens,
-                request.temperature,
-                request.top_p,
-            )?;
-
             if !segment_result.text.is_empty() {
                 let chunk = StreamChunk {
                     text: segment_result.text.clone(),
@@ -213,35 +391,252 @@ impl FunAsrNanoGenerateModel {
                     all_text.push(' ');
                 }
                 all_text.push_str(&segment_result.text);
+                all_words.extend(words);
             }
 
             total_tokens += segment_result.num_tokens;
         }
 
-        self.fun_asr_nano.clear_kv_cache();
         callback(StreamChunk::finished(all_text.clone(), total_tokens))?;
 
         Ok(TranscriptionResponse {
             text: all_text,
             num_tokens: total_tokens,
+            words: all_words,
         })
     }
 
-    fn transcribe_segment(
+    fn identify_speaker(&mut self, audio_data: &[f32], duration_ms: u32) -> Result<Option<usize>> {
+        let Some(diarizer) = self.diarizer.as_mut() else {
+            return Ok(None);
+        };
+
+        let fbank = self.processor.extract_speaker_fbank(audio_data)?;
+        Ok(Some(diarizer.identify(&fbank, duration_ms)?))
+    }
+
+    fn detect_language(&mut self, audio_data: &[f32]) -> Result<Option<Language>> {
+        let fun_asr_nano = match &mut self.model {
+            SpeechModel::Whisper(whisper) => {
+                let code = whisper.detect_language(audio_data, &self.tokenizer)?;
+                return Ok(code.map(|code| Language::from_code(&code)));
+            }
+            SpeechModel::FunAsrNano(model) => model,
+        };
+
+        let (speech, fbank_mask, mut input_ids) = self.processor.process_audio(
+            audio_data,
+            Some(LANGUAGE_PROBE_PROMPT),
+            &self.tokenizer,
+        )?;
+
+        let mut speech = Some(speech.to_dtype(self.dtype)?);
+        let mut fbank_mask = Some(&fbank_mask);
+        let mut seqlen_offset = 0;
+        let mut generate = Vec::with_capacity(LANGUAGE_PROBE_TOKENS);
+
+        // Greedy, sampling could pick a token of another script
+        for _ in 0..LANGUAGE_PROBE_TOKENS {
+            let logits =
+                fun_asr_nano.forward(&input_ids, speech.as_ref(), fbank_mask, seqlen_offset)?;
+            let next_token = logits.flatten_all()?.argmax(0)?.to_scalar::<u32>()?;
+            if next_token == self.eos_token_id1 || next_token == self.eos_token_id2 {
+                break;
+            }
+            generate.push(next_token);
+
+            seqlen_offset += input_ids.dim(1)?;
+            input_ids = Tensor::from_vec(vec![next_token], (1, 1), &self.device)?;
+            speech = None;
+            fbank_mask = None;
+        }
+
+        fun_asr_nano.clear_kv_cache();
+
+        let text = self.tokenizer.token_decode(generate)?;
+        let language = Language::from_text(&text);
+        log::debug!("Segment language {language:?} from {text:?}");
+
+        Ok(language)
+    }
+
+    pub(crate) fn hotword_booster(
+        &self,
+        hotwords: &[String],
+        boost: f32,
+    ) -> Result<Option<HotwordBooster>> {
+        HotwordBooster::new(&self.tokenizer, hotwords, boost)
+    }
+
+    /// `prompts` has the prompt of each segment
+    fn transcribe_segments(
+        &mut self,
+        segments: &[&[f32]],
+        prompts: &[Option<&str>],
+        max_tokens: u32,
+        temperature: Option<f32>,
+        top_p: Option<f32>,
+        hotwords: Option<&HotwordBooster>,
+    ) -> Result<Vec<TranscriptionResponse>> {
+        if let ([segment], [prompt]) = (segments, prompts) {
+            let result = self.transcribe_segment(
+                segment,
+                *prompt,
+                max_tokens,
+                temperature,
+                top_p,
+                hotwords,
+            )?;
+            return Ok(vec![result]);
+        }
+
+        // Whisper decodes one segment at a time
+        let fun_asr_nano = match &mut self.model {
+            SpeechModel::Whisper(_) => {
+                return segments
+                    .iter()
+                    .zip(prompts)
+                    .map(|(segment, prompt)| {
+                        self.transcribe_segment(
+                            segment,
+                            *prompt,
+                            max_tokens,
+                            temperature,
+                            top_p,
+                            hotwords,
+                        )
+                    })
+                    .collect();
+            }
+            SpeechModel::FunAsrNano(model) => model,
+        };
+
+        let sampling = sampling_config(&self.generation_config, temperature, top_p);
+        let seed = 34562u64;
+        let max_tokens = max_tokens.min(512); // Limit segment tokens
+        let batch = segments.len();
+
+        let mut embeds = Vec::with_capacity(batch);
+        for (audio_data, prompt) in segments.iter().zip(prompts) {
+            let (speech, fbank_mask, input_ids) =
+                self.processor
+                    .process_audio(audio_data, *prompt, &self.tokenizer)?;
+            let speech = speech.to_dtype(self.dtype)?;
+            let embed = fun_asr_nano.embed_inputs(&input_ids, Some(&speech), Some(&fbank_mask))?;
+            embeds.push(embed.squeeze(0)?);
+        }
+
+        // Left pad the prompts so the next tokens of all segments are at the
+        // same position
+        let lens = embeds
+            .iter()
+            .map(|embed| embed.dim(0))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let max_len = lens.iter().copied().max().unwrap_or_default();
+        let mut padded = Vec::with_capacity(batch);
+        let mut padding_bias = Vec::with_capacity(batch * max_len);
+        for (embed, len) in embeds.iter().zip(lens) {
+            padded.push(embed.pad_with_zeros(0, max_len - len, 0)?);
+            padding_bias.extend(std::iter::repeat_n(PADDING_BIAS, max_len - len));
+            padding_bias.extend(std::iter::repeat_n(0.0f32, len));
+        }
+
+        let mut inputs_embeds = Tensor::stack(&padded, 0)?;
+        let mut padding_bias = Tensor::from_vec(padding_bias, (batch, max_len), &self.device)?;
+        let mut samplers = (0..batch)
+            .map(|_| Sampler::new(sampling.clone(), seed))
+            .collect::<Vec<_>>();
+        let mut generates = vec![Vec::new(); batch];
+        let mut texts = vec![String::new(); batch];
+        let mut finished = vec![false; batch];
+        let mut seqlen_offset = 0;
+
+        for _ in 0..max_tokens {
+            let logits =
+                fun_asr_nano.forward_padded(&inputs_embeds, &padding_bias, seqlen_offset)?;
+            let logits = logits.squeeze(1)?.to_dtype(DType::F32)?;
+
+            let mut next_tokens = Vec::with_capacity(batch);
+            for index in 0..batch {
+                if finished[index] {
+                    next_tokens.push(self.eos_token_id1);
+                    continue;
+                }
+
+                let next_token = sample_next_token(
+                    &mut samplers[index],
+                    &logits.get(index)?,
+                    hotwords,
+                    &generates[index],
+                )?;
+                generates[index].push(next_token);
+                next_tokens.push(next_token);
+
+                if next_token == self.eos_token_id1 || next_token == self.eos_token_id2 {
+                    let recent_tokens = generates[index].iter().rev().take(100).rev().cloned();
+                    let decoded_text = self.tokenizer.token_decode(recent_tokens.collect())?;
+                    texts[index] = decoded_text.trim().to_string();
+                    finished[index] = true;
+                }
+            }
+
+            if finished.iter().all(|f| *f) {
+                break;
+            }
+
+            seqlen_offset += inputs_embeds.dim(1)?;
+            let input_ids = Tensor::from_vec(next_tokens, (batch, 1), &self.device)?;
+            inputs_embeds = fun_asr_nano.embed_inputs(&input_ids, None, None)?;
+            let next_bias = Tensor::zeros((batch, 1), DType::F32, &self.device)?;
+            padding_bias = Tensor::cat(&[&padding_bias, &next_bias], 1)?;
+        }
+
+        fun_asr_nano.clear_kv_cache();
+
+        Ok(texts
+            .into_iter()
+            .zip(generates)
+            .map(|(text, generate)| TranscriptionResponse {
+                text,
+                num_tokens: generate.len() as u32,
+                words: vec![],
+            })
+            .collect())
+    }
+
+    pub(crate) fn transcribe_segment(
         &mut self,
         audio_data: &[f32],
         prompt: Option<&str>,
         max_tokens: u32,
         temperature: Option<f32>,
         top_p: Option<f32>,
+        hotwords: Option<&HotwordBooster>,
     ) -> Result<TranscriptionResponse> {
-        let temperature = temperature.unwrap_or(self.generation_config.temperature);
-        let top_p = top_p.unwrap_or(self.generation_config.top_p);
-        let top_k = self.generation_config.top_k;
+        let fun_asr_nano = match &mut self.model {
+            SpeechModel::Whisper(whisper) => {
+                let (text, num_tokens) = whisper.transcribe(
+                    audio_data,
+                    &self.tokenizer,
+                    max_tokens,
+                    temperature,
+                    hotwords,
+                )?;
+
+                return Ok(TranscriptionResponse {
+                    text,
+                    num_tokens,
+                    words: vec![],
+                });
+            }
+            SpeechModel::FunAsrNano(model) => model,
+        };
+
+        let sampling = sampling_config(&self.generation_config, temperature, top_p);
         let seed = 34562u64;
         let max_tokens = max_tokens.min(512); // Limit segment tokens
 
-        let mut logit_processor = SimpleLogitProcessor::new(temperature, top_p, top_k, seed);
+        let mut sampler = Sampler::new(sampling, seed);
 
         let (speech, fbank_mask, mut input_ids) =
             self.processor
@@ -255,14 +650,10 @@ impl FunAsrNanoGenerateModel {
         let mut segment_text = String::new();
 
         for _ in 0..max_tokens {
-            let logits = self.fun_asr_nano.forward(
-                &input_ids,
-                speech.as_ref(),
-                fbank_mask,
-                seqlen_offset,
-            )?;
+            let logits =
+                fun_asr_nano.forward(&input_ids, speech.as_ref(), fbank_mask, seqlen_offset)?;
             let logits = logits.squeeze(0)?.squeeze(0)?.to_dtype(DType::F32)?;
-            let next_token = logit_processor.sample(&logits)?;
+            let next_token = sample_next_token(&mut sampler, &logits, hotwords, &generate)?;
             generate.push(next_token);
 
             let recent_tokens: Vec<u32> = generate.iter().rev().take(100).cloned().collect();
@@ -281,11 +672,12 @@ impl FunAsrNanoGenerateModel {
             fbank_mask = None;
         }
 
-        self.fun_asr_nano.clear_kv_cache();
+        fun_asr_nano.clear_kv_cache();
 
         Ok(TranscriptionResponse {
             text: segment_text,
             num_tokens: generate.len() as u32,
+            words: vec![],
         })
     }
 
@@ -303,45 +695,47 @@ impl FunAsrNanoGenerateModel {
                 config.tokenizer_path
             )));
         }
+
+        if config.backend == AsrBackend::Whisper && !Path::new(&config.whisper_config_path).exists()
+        {
+            return Err(FunAsrError::NotFound(format!(
+                "Whisper config file not found: {}",
+                config.whisper_config_path
+            )));
+        }
         Ok(())
     }
 }
 
-struct SimpleLogitProcessor {
-    temperature: f32,
-    rng: rand::rngs::StdRng,
+// Sampling of the generation config, the request overrides its temperature
+// and top-p
+fn sampling_config(
+    config: &Qwen3GenerationConfig,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+) -> SamplingConfig {
+    SamplingConfig::default()
+        .with_temperature(temperature.unwrap_or(config.temperature) as f64)
+        .with_top_k(Some(config.top_k))
+        .with_top_p(Some(top_p.unwrap_or(config.top_p) as f64))
+        .with_repetition_penalty(config.repetition_penalty)
 }
 
-impl SimpleLogitProcessor {
-    fn new(temperature: f32, _top_p: f32, _top_k: usize, seed: u64) -> Self {
-        Self {
-            temperature,
-            rng: rand::rngs::StdRng::seed_from_u64(seed),
-        }
-    }
-
-    fn sample(&mut self, logits: &Tensor) -> Result<u32> {
-        let logits = logits.to_vec1::<f32>()?;
-        let logits: Vec<f32> = logits.iter().map(|x| x / self.temperature).collect();
-
-        // Compute softmax
-        let max_logit = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-        let exp_logits: Vec<f32> = logits.iter().map(|x| (x - max_logit).exp()).collect();
-        let sum: f32 = exp_logits.iter().sum();
-        let probs: Vec<f32> = exp_logits.iter().map(|x| x / sum).collect();
-
-        // Sample using custom weighted sampling
-        let rand_val: f32 = self.rng.random();
-        let mut cumulative = 0.0f32;
-        for (idx, &prob) in probs.iter().enumerate() {
-            cumulative += prob;
-            if rand_val < cumulative {
-                return Ok(idx as u32);
-            }
-        }
-
-        Ok((probs.len() - 1) as u32)
-    }
+// Apply the hotwords to the logits of the next token before sampling it
+fn sample_next_token(
+    sampler: &mut Sampler,
+    logits: &Tensor,
+    hotwords: Option<&HotwordBooster>,
+    generated: &[u32],
+) -> Result<u32> {
+    let Some(hotwords) = hotwords else {
+        return Ok(sampler.sample(logits, generated)?);
+    };
+
+    let mut values = logits.to_vec1::<f32>()?;
+    hotwords.apply(&mut values, generated);
+    let logits = Tensor::new(values.as_slice(), logits.device())?;
+    Ok(sampler.sample(&logits, generated)?)
 }
 
 pub fn load_audio_file(path: impl AsRef<Path>) -> Result<AudioConfig> {
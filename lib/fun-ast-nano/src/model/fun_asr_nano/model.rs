@@ -455,6 +455,33 @@ impl FunAsrNanoModel {
         speech: Option<&Tensor>,
         fbank_mask: Option<&Tensor>,
         seqlen_offset: usize,
+    ) -> Result<Tensor> {
+        let inputs_embeds = self.embed_inputs(input_ids, speech, fbank_mask)?;
+        let logits = self
+            .llm
+            .forward(None, Some(&inputs_embeds), seqlen_offset)?;
+        Ok(logits)
+    }
+
+    /// Forward of a batch of left padded input embeddings, see
+    /// `Qwen3Model::forward_padded`
+    pub fn forward_padded(
+        &mut self,
+        inputs_embeds: &Tensor,
+        padding_bias: &Tensor,
+        seqlen_offset: usize,
+    ) -> Result<Tensor> {
+        self.llm
+            .forward_padded(inputs_embeds, padding_bias, seqlen_offset)
+    }
+
+    /// Token embeddings with the audio embeddings scattered at the
+    /// positions of `fbank_mask`
+    pub fn embed_inputs(
+        &self,
+        input_ids: &Tensor,
+        speech: Option<&Tensor>,
+        fbank_mask: Option<&Tensor>,
     ) -> Result<Tensor> {
         let mut inputs_embeds = self.llm.embedding_token_id(input_ids)?;
         if let Some(speech) = speech
@@ -468,10 +495,7 @@ impl FunAsrNanoModel {
                 .narrow(0, 0, speech_token_len as usize)?;
             inputs_embeds = masked_scatter_dim0(&inputs_embeds, &audio_embed, fbank_mask)?;
         }
-        let logits = self
-            .llm
-            .forward(None, Some(&inputs_embeds), seqlen_offset)?;
-        Ok(logits)
+        Ok(inputs_embeds)
     }
 
     pub fn clear_kv_cache(&mut self) {
@@ -65,6 +65,24 @@ impl FunAsrNanoProcessor {
         Ok((mat, feat_length))
     }
 
+    /// Fbank of the audio without the low frame rate stacking and with the
+    /// mean removed, as speaker embedding models expect. Shape (frames, n_mels)
+    pub fn extract_speaker_fbank(&self, audio_data: &[f32]) -> Result<Tensor> {
+        let audio = Tensor::from_vec(audio_data.to_vec(), (1, audio_data.len()), &self.device)?;
+        let waveform = audio.affine(32768.0, 0.0)?;
+        let mat = kaldi_fbank(
+            &waveform,
+            &self.mel_energies,
+            self.window_shift,
+            self.window_size,
+            self.padded_window_size,
+            1.0,
+        )?
+        .squeeze(0)?;
+
+        Ok(mat.broadcast_sub(&mat.mean_keepdim(0)?)?)
+    }
+
     pub fn process_audio(
         &self,
         audio_data: &[f32],
@@ -0,0 +1,308 @@
+//! Live transcription of recorder frames. An incremental VAD cuts the audio
+//! into utterances, the pending utterance is transcribed again every
+//! `partial_interval_ms` as partial text and once more as final text when
+//! the speaker pauses or it reaches `max_utterance_ms`.
+
+use crate::{
+    INPUT_AUDIO_SAMPLE_RATE, Result,
+    model::{
+        fun_asr_nano::generate::{DEFAULT_HOTWORD_BOOST, FunAsrNanoGenerateModel},
+        hotword::HotwordBooster,
+    },
+};
+use audio_utils::{
+    audio::{multi_to_mono, rms},
+    resample::{ResampleQuality, Resampler},
+};
+use derivative::Derivative;
+use derive_setters::Setters;
+
+// VAD window
+const WINDOW_MS: usize = 30;
+
+// Audio kept before the start of speech so the first syllable is not cut
+const PREROLL_MS: usize = 300;
+
+// A window is speech when louder than this times the noise floor
+const SPEECH_TO_NOISE_RATIO: f32 = 2.0;
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct StreamingConfig {
+    #[derivative(Default(value = "16_000"))]
+    pub input_sample_rate: u32,
+
+    #[derivative(Default(value = "1"))]
+    pub input_channels: u16,
+
+    pub prompt: Option<String>,
+
+    #[derivative(Default(value = "128"))]
+    pub max_tokens: u32,
+
+    #[derivative(Default(value = "800"))]
+    pub partial_interval_ms: u32,
+
+    #[derivative(Default(value = "500"))]
+    pub min_silence_duration_ms: u32,
+
+    #[derivative(Default(value = "15_000"))]
+    pub max_utterance_ms: u32,
+
+    // Minimum RMS of speech, the noise floor raises it in noisy rooms
+    #[derivative(Default(value = "0.01"))]
+    pub speech_rms_threshold: f32,
+
+    #[setters(skip)]
+    pub hotwords: Vec<String>,
+
+    #[setters(skip)]
+    #[derivative(Default(value = "DEFAULT_HOTWORD_BOOST"))]
+    pub hotword_boost: f32,
+}
+
+impl StreamingConfig {
+    /// See `TranscriptionRequest::with_hotwords`
+    pub fn with_hotwords(mut self, hotwords: Vec<String>, boost: f32) -> Self {
+        self.hotwords = hotwords;
+        self.hotword_boost = boost;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamingText {
+    pub text: String,
+
+    /// The partial text of an utterance is replaced by the next partial or
+    /// final text of the same utterance
+    pub is_final: bool,
+
+    /// Times since the first pushed frame
+    pub start_ms: u32,
+    pub end_ms: u32,
+}
+
+pub struct StreamingTranscriber {
+    model: FunAsrNanoGenerateModel,
+    config: StreamingConfig,
+    hotwords: Option<HotwordBooster>,
+    resampler: Option<Resampler>,
+
+    // Resampled samples not filling a VAD window yet
+    window: Vec<f32>,
+
+    preroll: Vec<f32>,
+    utterance: Vec<f32>,
+    utterance_start: usize,
+    in_speech: bool,
+    silence_samples: usize,
+    samples_since_partial: usize,
+    noise_rms: Option<f32>,
+
+    // Samples passed through the VAD
+    processed_samples: usize,
+}
+
+impl StreamingTranscriber {
+    pub fn new(model: FunAsrNanoGenerateModel, config: StreamingConfig) -> Result<Self> {
+        let hotwords = model.hotword_booster(&config.hotwords, config.hotword_boost)?;
+        let resampler = if config.input_sample_rate != INPUT_AUDIO_SAMPLE_RATE {
+            Some(Resampler::new(
+                config.input_sample_rate,
+                INPUT_AUDIO_SAMPLE_RATE,
+                1,
+                ResampleQuality::Medium,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            model,
+            config,
+            hotwords,
+            resampler,
+            window: vec![],
+            preroll: vec![],
+            utterance: vec![],
+            utterance_start: 0,
+            in_speech: false,
+            silence_samples: 0,
+            samples_since_partial: 0,
+            noise_rms: None,
+            processed_samples: 0,
+        })
+    }
+
+    pub fn into_model(self) -> FunAsrNanoGenerateModel {
+        self.model
+    }
+
+    /// Transcribe the frames of a recorder channel until it is closed, e.g.
+    /// `transcriber.run(receiver.iter(), callback)`
+    pub fn run(
+        &mut self,
+        frames: impl IntoIterator<Item = Vec<f32>>,
+        mut callback: impl FnMut(StreamingText) -> Result<()>,
+    ) -> Result<()> {
+        for frame in frames {
+            self.push(&frame, &mut callback)?;
+        }
+
+        self.flush(&mut callback)
+    }
+
+    /// Push interleaved samples in the input format
+    pub fn push(
+        &mut self,
+        frame: &[f32],
+        mut callback: impl FnMut(StreamingText) -> Result<()>,
+    ) -> Result<()> {
+        let channels = self.config.input_channels.max(1);
+        let samples = multi_to_mono(frame, channels);
+        match self.resampler.as_mut() {
+            Some(resampler) => self.window.extend(resampler.process(&samples)),
+            None => self.window.extend(samples),
+        }
+
+        let window_size = ms_to_samples(WINDOW_MS);
+        while self.window.len() >= window_size {
+            let window = self.window.drain(..window_size).collect::<Vec<_>>();
+            self.process_window(window, &mut callback)?;
+        }
+
+        Ok(())
+    }
+
+    /// Transcribe what is left of the current utterance as final text
+    pub fn flush(&mut self, mut callback: impl FnMut(StreamingText) -> Result<()>) -> Result<()> {
+        let window = std::mem::take(&mut self.window);
+        if self.in_speech {
+            self.utterance.extend(window);
+            self.finish_utterance(&mut callback)?;
+        }
+
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.reset();
+        }
+        self.preroll.clear();
+        Ok(())
+    }
+
+    fn process_window(
+        &mut self,
+        window: Vec<f32>,
+        callback: &mut impl FnMut(StreamingText) -> Result<()>,
+    ) -> Result<()> {
+        let level = rms(&window);
+        let noise = *self.noise_rms.get_or_insert(level);
+        let is_speech = level
+            > self
+                .config
+                .speech_rms_threshold
+                .max(noise * SPEECH_TO_NOISE_RATIO);
+
+        if !is_speech {
+            self.noise_rms = Some(noise * 0.95 + level * 0.05);
+        }
+
+        let window_len = window.len();
+        self.processed_samples += window_len;
+
+        if !self.in_speech {
+            self.preroll.extend(window);
+            let excess = self.preroll.len().saturating_sub(ms_to_samples(PREROLL_MS));
+            self.preroll.drain(..excess);
+
+            if is_speech {
+                self.in_speech = true;
+                self.silence_samples = 0;
+                self.samples_since_partial = 0;
+                self.utterance_start = self.processed_samples - self.preroll.len();
+                self.utterance = std::mem::take(&mut self.preroll);
+            }
+
+            return Ok(());
+        }
+
+        self.utterance.extend(window);
+        self.samples_since_partial += window_len;
+        if is_speech {
+            self.silence_samples = 0;
+        } else {
+            self.silence_samples += window_len;
+        }
+
+        if self.silence_samples >= ms_to_samples(self.config.min_silence_duration_ms as usize)
+            || self.utterance.len() >= ms_to_samples(self.config.max_utterance_ms as usize)
+        {
+            self.finish_utterance(callback)?;
+        } else if self.samples_since_partial
+            >= ms_to_samples(self.config.partial_interval_ms as usize)
+        {
+            self.samples_since_partial = 0;
+            self.emit(false, callback)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish_utterance(
+        &mut self,
+        callback: &mut impl FnMut(StreamingText) -> Result<()>,
+    ) -> Result<()> {
+        // Trailing silence is only noise to the model
+        let speech_len = self.utterance.len() - self.silence_samples.min(self.utterance.len());
+        self.utterance.truncate(speech_len);
+
+        let result = self.emit(true, callback);
+
+        self.in_speech = false;
+        self.silence_samples = 0;
+        self.samples_since_partial = 0;
+        self.utterance.clear();
+
+        result
+    }
+
+    fn emit(
+        &mut self,
+        is_final: bool,
+        callback: &mut impl FnMut(StreamingText) -> Result<()>,
+    ) -> Result<()> {
+        if self.utterance.is_empty() {
+            return Ok(());
+        }
+
+        let response = self.model.transcribe_segment(
+            &self.utterance,
+            self.config.prompt.as_deref(),
+            self.config.max_tokens,
+            None,
+            None,
+            self.hotwords.as_ref(),
+        )?;
+
+        if response.text.is_empty() {
+            return Ok(());
+        }
+
+        callback(StreamingText {
+            text: response.text,
+            is_final,
+            start_ms: samples_to_ms(self.utterance_start),
+            end_ms: samples_to_ms(self.utterance_start + self.utterance.len()),
+        })
+    }
+}
+
+fn ms_to_samples(ms: usize) -> usize {
+    INPUT_AUDIO_SAMPLE_RATE as usize * ms / 1000
+}
+
+fn samples_to_ms(samples: usize) -> u32 {
+    (samples * 1000 / INPUT_AUDIO_SAMPLE_RATE as usize) as u32
+}
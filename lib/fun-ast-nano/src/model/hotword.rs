@@ -0,0 +1,63 @@
+//! Hotword biasing. The logits of the tokens that start a hotword, or
+//! continue one partially decoded, are raised before sampling so that
+//! product names and jargon are spelled as the user wrote them.
+
+use crate::{Result, tokenizer::TokenizerModel};
+use std::collections::HashMap;
+
+pub(crate) struct HotwordBooster {
+    sequences: Vec<Vec<u32>>,
+    boost: f32,
+}
+
+impl HotwordBooster {
+    /// `None` without hotwords
+    pub fn new(
+        tokenizer: &TokenizerModel,
+        hotwords: &[String],
+        boost: f32,
+    ) -> Result<Option<Self>> {
+        let mut sequences: Vec<Vec<u32>> = vec![];
+
+        for word in hotwords.iter().map(|w| w.trim()).filter(|w| !w.is_empty()) {
+            // BPE tokens differ at the start of the text and after a space
+            for text in [word.to_string(), format!(" {word}")] {
+                let tokens = tokenizer.text_encode_vec(text, false)?;
+                if !tokens.is_empty() && !sequences.contains(&tokens) {
+                    sequences.push(tokens);
+                }
+            }
+        }
+
+        if sequences.is_empty() || boost <= 0.0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { sequences, boost }))
+    }
+
+    /// Boost the next token of every hotword after the longest part of it
+    /// that ends `generated`
+    pub fn apply(&self, logits: &mut [f32], generated: &[u32]) {
+        let mut boosts: HashMap<u32, f32> = HashMap::new();
+
+        for sequence in &self.sequences {
+            let matched = (1..sequence.len())
+                .rev()
+                .find(|&len| generated.ends_with(&sequence[..len]))
+                .unwrap_or(0);
+
+            // A partially decoded hotword is completed more eagerly
+            let boost = self.boost * (1.0 + matched as f32 / sequence.len() as f32);
+            let token = sequence[matched];
+            let entry = boosts.entry(token).or_default();
+            *entry = entry.max(boost);
+        }
+
+        for (token, boost) in boosts {
+            if let Some(logit) = logits.get_mut(token as usize) {
+                *logit += boost;
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Spoken language of a segment. Whisper predicts it with its language
+//! token, Fun-ASR-Nano has no language head so it is told by the script of
+//! a few tokens decoded with the language neutral prompt.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    Chinese,
+    English,
+    Japanese,
+    Korean,
+
+    /// Code of a language Whisper detected, e.g. "de"
+    Other(String),
+}
+
+impl Language {
+    /// ISO 639-1 code as in the Whisper language tokens
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "zh" => Self::Chinese,
+            "en" => Self::English,
+            "ja" => Self::Japanese,
+            "ko" => Self::Korean,
+            _ => Self::Other(code.to_string()),
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        match self {
+            Self::Chinese => "zh",
+            Self::English => "en",
+            Self::Japanese => "ja",
+            Self::Korean => "ko",
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Fun-ASR-Nano prompt transcribing in the language, `None` keeps the
+    /// prompt of the request
+    pub fn prompt(&self) -> Option<&'static str> {
+        match self {
+            Self::Chinese => Some("语音转写成中文："),
+            Self::English => Some("语音转写成英文："),
+            Self::Japanese => Some("语音转写成日文："),
+            Self::Korean => Some("语音转写成韩文："),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// Language of the most used script of `text`
+    pub(crate) fn from_text(text: &str) -> Option<Self> {
+        let (mut han, mut kana, mut hangul, mut latin) = (0usize, 0usize, 0usize, 0usize);
+
+        for c in text.chars() {
+            match c {
+                '\u{3040}'..='\u{30ff}' => kana += 1,
+                '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => {
+                    hangul += 1
+                }
+                '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => han += 1,
+                c if c.is_ascii_alphabetic() => latin += 1,
+                _ => (),
+            }
+        }
+
+        // A Latin letter carries about a third of a syllable
+        let latin = latin.div_ceil(3);
+
+        // Japanese mixes kanji into the kana
+        let japanese = if kana > 0 { kana + han } else { 0 };
+
+        [
+            (Self::Japanese, japanese),
+            (Self::Chinese, han),
+            (Self::Korean, hangul),
+            (Self::English, latin),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .map(|(language, _)| language)
+    }
+}
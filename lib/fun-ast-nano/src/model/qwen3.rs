@@ -1,7 +1,7 @@
 use crate::{
     FunAsrError, Result,
     model::common::{GateUpDownMLP, eager_attention_forward},
-    position_embed::rope::{RoPE, apply_rotary_pos_emb},
+    position_embed::rope::RoPE,
 };
 use candle_core::Tensor;
 use candle_nn::{
@@ -9,7 +9,7 @@ use candle_nn::{
     linear_no_bias, rms_norm,
 };
 use serde::Deserialize;
-use tensor_utils::prepare_causal_attention_mask;
+use tensor_utils::{fused::rms_norm_rope, kv_cache::KvCache, prepare_causal_attention_mask};
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Qwen3Config {
@@ -58,14 +58,16 @@ pub struct Qwen3Attention {
     k_proj: Linear,
     v_proj: Linear,
     o_proj: Linear,
-    q_norm: RmsNorm,
-    k_norm: RmsNorm,
+    // Weights of the per head RMSNorm fused with the rotary embedding
+    q_norm: Tensor,
+    k_norm: Tensor,
+    rms_norm_eps: f64,
     num_attention_heads: usize,
     num_key_value_heads: usize,
     num_kv_groups: usize,
     head_dim: usize,
     scaling: f64,
-    kv_cache: Option<(Tensor, Tensor)>,
+    kv_cache: KvCache,
 }
 
 impl Qwen3Attention {
@@ -100,8 +102,8 @@ impl Qwen3Attention {
             config.attention_bias,
             vb.pp("o_proj"),
         )?;
-        let q_norm = rms_norm(head_dim, config.rms_norm_eps, vb.pp("q_norm"))?;
-        let k_norm = rms_norm(head_dim, config.rms_norm_eps, vb.pp("k_norm"))?;
+        let q_norm = vb.pp("q_norm").get(head_dim, "weight")?;
+        let k_norm = vb.pp("k_norm").get(head_dim, "weight")?;
         Ok(Self {
             q_proj,
             k_proj,
@@ -109,12 +111,13 @@ impl Qwen3Attention {
             o_proj,
             q_norm,
             k_norm,
+            rms_norm_eps: config.rms_norm_eps,
             num_attention_heads,
             num_key_value_heads,
             num_kv_groups,
             head_dim,
             scaling,
-            kv_cache: None,
+            kv_cache: KvCache::default(),
         })
     }
 
@@ -132,29 +135,19 @@ impl Qwen3Attention {
             self.num_attention_heads,
             self.head_dim,
         ))?;
-        let query_states = self.q_norm.forward(&query_states)?.transpose(1, 2)?;
+        let query_states = rms_norm_rope(&query_states, &self.q_norm, self.rms_norm_eps, cos, sin)?;
         let key_states = self.k_proj.forward(xs)?.reshape((
             b_sz,
             q_len,
             self.num_key_value_heads,
             self.head_dim,
         ))?;
-        let key_states = self.k_norm.forward(&key_states)?.transpose(1, 2)?;
+        let key_states = rms_norm_rope(&key_states, &self.k_norm, self.rms_norm_eps, cos, sin)?;
         let value_states = self.v_proj.forward(xs)?;
         let value_states = value_states
             .reshape((b_sz, q_len, self.num_key_value_heads, self.head_dim))?
             .transpose(1, 2)?;
-        let (query_states, key_states) =
-            apply_rotary_pos_emb(&query_states, &key_states, cos, sin, false)?;
-        let (key_states, value_states) = match &self.kv_cache {
-            None => (key_states, value_states),
-            Some((prev_k, prev_v)) => {
-                let key_states = Tensor::cat(&[prev_k, &key_states], 2)?;
-                let value_states = Tensor::cat(&[prev_v, &value_states], 2)?;
-                (key_states, value_states)
-            }
-        };
-        self.kv_cache = Some((key_states.clone(), value_states.clone()));
+        let (key_states, value_states) = self.kv_cache.append(&key_states, &value_states)?;
         let attn_output = eager_attention_forward(
             &query_states,
             &key_states,
@@ -170,7 +163,7 @@ impl Qwen3Attention {
     }
 
     pub fn clear_kv_cache(&mut self) {
-        self.kv_cache = None
+        self.kv_cache.reset()
     }
 }
 
@@ -298,14 +291,44 @@ impl Qwen3Model {
             }
         };
 
+        self.forward_layers(inputs_embeds, attention_mask.as_ref(), seqlen_offset)
+    }
+
+    /// Forward of a batch of left padded sequences. `padding_bias` of shape
+    /// (bs, seqlen_offset + seq_len) is 0 at the tokens and a large negative
+    /// value at the padding, so the padding is never attended to.
+    pub fn forward_padded(
+        &mut self,
+        inputs_embeds: &Tensor,
+        padding_bias: &Tensor,
+        seqlen_offset: usize,
+    ) -> Result<Tensor> {
+        let (bs, seq_len, _) = inputs_embeds.dims3()?;
+        let padding_bias = padding_bias.unsqueeze(1)?.unsqueeze(1)?;
+        let attention_mask = if seq_len <= 1 {
+            padding_bias
+        } else {
+            prepare_causal_attention_mask(bs, seq_len, seqlen_offset, inputs_embeds.device())?
+                .broadcast_add(&padding_bias)?
+        };
+
+        self.forward_layers(inputs_embeds.clone(), Some(&attention_mask), seqlen_offset)
+    }
+
+    fn forward_layers(
+        &mut self,
+        inputs_embeds: Tensor,
+        attention_mask: Option<&Tensor>,
+        seqlen_offset: usize,
+    ) -> Result<Tensor> {
+        let seq_len = inputs_embeds.dim(1)?;
         let (cos, sin) = self
             .rotary_emb
             .forward(seqlen_offset, seq_len, inputs_embeds.device())?;
 
         let mut hidden_states = inputs_embeds;
         for decode_layer in &mut self.layers {
-            hidden_states =
-                decode_layer.forward(&hidden_states, &cos, &sin, attention_mask.as_ref())?;
+            hidden_states = decode_layer.forward(&hidden_states, &cos, &sin, attention_mask)?;
         }
         hidden_states = self.norm.forward(&hidden_states)?;
         let hidden_state = hidden_states.narrow(1, seq_len - 1, 1)?;
@@ -0,0 +1,241 @@
+//! Whisper backend of `FunAsrNanoGenerateModel`. It loads the Hugging Face
+//! `model.safetensors`, `config.json` and `tokenizer.json` of a Whisper
+//! model and transcribes each segment in 30s windows.
+
+use crate::{FunAsrError, Result, model::hotword::HotwordBooster, tokenizer::TokenizerModel};
+use audio_utils::extract::{MelScale, hertz_to_mel, mel_to_hertz};
+use candle_core::{D, DType, Device, IndexOp, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::whisper::{
+    self as m, Config, EOT_TOKEN, N_FFT, N_FRAMES, N_SAMPLES, NO_TIMESTAMPS_TOKEN, SAMPLE_RATE,
+    SOT_TOKEN, TRANSCRIBE_TOKEN, audio::pcm_to_mel, model::Whisper,
+};
+use std::path::Path;
+use tensor_utils::sampling::{Sampler, SamplingConfig};
+
+// Multilingual models have the language tokens
+const MULTILINGUAL_VOCAB_SIZE: usize = 51865;
+
+pub struct WhisperModel {
+    model: Whisper,
+    config: Config,
+    device: Device,
+    mel_filters: Vec<f32>,
+    suppress_tokens: Tensor,
+    sot_token: u32,
+    transcribe_token: u32,
+    eot_token: u32,
+    no_timestamps_token: u32,
+}
+
+impl WhisperModel {
+    pub fn new(
+        weights_path: impl AsRef<Path>,
+        config_path: impl AsRef<Path>,
+        tokenizer: &TokenizerModel,
+        device: &Device,
+    ) -> Result<Self> {
+        let config: Config = serde_json::from_slice(&std::fs::read(config_path)?)?;
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights_path], m::DTYPE, device)? };
+        let model = Whisper::load(&vb, config.clone())?;
+
+        let token_id = |token: &str| {
+            tokenizer
+                .tokenizer
+                .token_to_id(token)
+                .ok_or_else(|| FunAsrError::Tokenizer(format!("No found token {token}")))
+        };
+
+        let no_timestamps_token = token_id(NO_TIMESTAMPS_TOKEN)?;
+        let suppress_tokens = (0..config.vocab_size as u32)
+            .map(|i| {
+                if config.suppress_tokens.contains(&i) || i == no_timestamps_token {
+                    f32::NEG_INFINITY
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            mel_filters: mel_filters(config.num_mel_bins),
+            suppress_tokens: Tensor::new(suppress_tokens.as_slice(), device)?,
+            sot_token: token_id(SOT_TOKEN)?,
+            transcribe_token: token_id(TRANSCRIBE_TOKEN)?,
+            eot_token: token_id(EOT_TOKEN)?,
+            no_timestamps_token,
+            device: device.clone(),
+            config,
+            model,
+        })
+    }
+
+    pub(crate) fn transcribe(
+        &mut self,
+        audio_data: &[f32],
+        tokenizer: &TokenizerModel,
+        max_tokens: u32,
+        temperature: Option<f32>,
+        hotwords: Option<&HotwordBooster>,
+    ) -> Result<(String, u32)> {
+        let mut text = String::new();
+        let mut num_tokens = 0;
+
+        for window in audio_data.chunks(N_SAMPLES) {
+            let tokens = self.decode_window(window, max_tokens, temperature, hotwords)?;
+            num_tokens += tokens.len() as u32;
+
+            let window_text = tokenizer.token_decode(tokens)?;
+            let window_text = window_text.trim();
+            if !text.is_empty() && !window_text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(window_text);
+        }
+
+        self.model.reset_kv_cache();
+        Ok((text, num_tokens))
+    }
+
+    fn decode_window(
+        &mut self,
+        samples: &[f32],
+        max_tokens: u32,
+        temperature: Option<f32>,
+        hotwords: Option<&HotwordBooster>,
+    ) -> Result<Vec<u32>> {
+        let audio_features = self.encode(samples)?;
+
+        // Greedy without a temperature
+        let sampling =
+            SamplingConfig::default().with_temperature(temperature.unwrap_or_default() as f64);
+        let mut sampler = Sampler::new(sampling, 34562);
+        let mut tokens = vec![self.sot_token];
+
+        // The cross attention cache of the previous window is flushed by
+        // the first decoder step
+        let mut flush_kv_cache = true;
+
+        // The model predicts the language token after the start token
+        if self.config.vocab_size >= MULTILINGUAL_VOCAB_SIZE {
+            let logits = self.next_logits(&tokens, &audio_features, flush_kv_cache)?;
+            flush_kv_cache = false;
+            tokens.push(logits.argmax(D::Minus1)?.to_scalar::<u32>()?);
+            tokens.push(self.transcribe_token);
+        }
+        tokens.push(self.no_timestamps_token);
+
+        let prompt_len = tokens.len();
+        let max_len = (prompt_len + max_tokens as usize).min(self.config.max_target_positions / 2);
+
+        while tokens.len() < max_len {
+            let mut logits = self
+                .next_logits(&tokens, &audio_features, flush_kv_cache)?
+                .broadcast_add(&self.suppress_tokens)?;
+            flush_kv_cache = false;
+
+            if let Some(hotwords) = hotwords {
+                let mut values = logits.to_vec1::<f32>()?;
+                hotwords.apply(&mut values, &tokens[prompt_len..]);
+                logits = Tensor::new(values.as_slice(), &self.device)?;
+            }
+
+            let next_token = sampler.sample(&logits, &tokens[prompt_len..])?;
+
+            if next_token == self.eot_token {
+                break;
+            }
+            tokens.push(next_token);
+        }
+
+        Ok(tokens.split_off(prompt_len))
+    }
+
+    /// Language code of the language token predicted for the first
+    /// window, `None` for English only models
+    pub(crate) fn detect_language(
+        &mut self,
+        audio_data: &[f32],
+        tokenizer: &TokenizerModel,
+    ) -> Result<Option<String>> {
+        if self.config.vocab_size < MULTILINGUAL_VOCAB_SIZE {
+            return Ok(None);
+        }
+
+        let window = &audio_data[..audio_data.len().min(N_SAMPLES)];
+        let audio_features = self.encode(window)?;
+        let logits = self.next_logits(&[self.sot_token], &audio_features, true)?;
+        let token = logits.argmax(D::Minus1)?.to_scalar::<u32>()?;
+        self.model.reset_kv_cache();
+
+        Ok(tokenizer.tokenizer.id_to_token(token).and_then(|token| {
+            token
+                .strip_prefix("<|")?
+                .strip_suffix("|>")
+                .map(String::from)
+        }))
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Result<Tensor> {
+        let n_mels = self.config.num_mel_bins;
+        let mel = pcm_to_mel(&self.config, samples, &self.mel_filters);
+        let frames = mel.len() / n_mels;
+        let mel = Tensor::from_vec(mel, (1, n_mels, frames), &self.device)?.narrow(
+            2,
+            0,
+            N_FRAMES.min(frames),
+        )?;
+
+        Ok(self.model.encoder.forward(&mel, true)?)
+    }
+
+    fn next_logits(
+        &mut self,
+        tokens: &[u32],
+        audio_features: &Tensor,
+        flush_kv_cache: bool,
+    ) -> Result<Tensor> {
+        let tokens = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        let ys = self
+            .model
+            .decoder
+            .forward(&tokens, audio_features, flush_kv_cache)?;
+        let seq_len = ys.dim(1)?;
+        let logits = self
+            .model
+            .decoder
+            .final_linear(&ys.i((..1, seq_len - 1..))?)?
+            .i(0)?
+            .i(0)?;
+
+        Ok(logits.to_dtype(DType::F32)?)
+    }
+}
+
+/// Slaney mel filters of shape (n_mels, N_FFT / 2 + 1) as in librosa, which
+/// Whisper was trained with
+fn mel_filters(n_mels: usize) -> Vec<f32> {
+    let n_freqs = N_FFT / 2 + 1;
+    let fft_freqs = (0..n_freqs)
+        .map(|i| i as f32 * SAMPLE_RATE as f32 / N_FFT as f32)
+        .collect::<Vec<_>>();
+
+    let max_mel = hertz_to_mel(SAMPLE_RATE as f32 / 2.0, MelScale::Slaney);
+    let mel_freqs = (0..n_mels + 2)
+        .map(|i| mel_to_hertz(max_mel * i as f32 / (n_mels + 1) as f32, MelScale::Slaney))
+        .collect::<Vec<_>>();
+
+    let mut filters = vec![0.0; n_mels * n_freqs];
+    for m in 0..n_mels {
+        let (left, center, right) = (mel_freqs[m], mel_freqs[m + 1], mel_freqs[m + 2]);
+        let enorm = 2.0 / (right - left);
+
+        for (k, freq) in fft_freqs.iter().enumerate() {
+            let lower = (freq - left) / (center - left);
+            let upper = (right - freq) / (right - center);
+            filters[m * n_freqs + k] = lower.min(upper).max(0.0) * enorm;
+        }
+    }
+
+    filters
+}
@@ -1,5 +1,6 @@
 use crate::Result;
 use candle_core::{D, DType, Device, Tensor};
+use tensor_utils::fused;
 
 pub mod rope {
     use super::*;
@@ -60,35 +61,9 @@ pub mod rope {
         sin: &Tensor,
         tof32: bool,
     ) -> Result<(Tensor, Tensor)> {
-        // sin/cos: to (bs, 1, seq_len, head_dim)
+        // sin/cos: (seq_len, head_dim) or (bs, seq_len, head_dim)
         // q/k: (bs, n_head, seq_len, head_dim)
-        let mut cos = cos.clone();
-        let mut sin = sin.clone();
-        if cos.rank() == 2 {
-            // (seq_len, head_dim) -> (1, 1, seq_len, head_dim)
-            cos = cos.unsqueeze(0)?.unsqueeze(0)?;
-            sin = sin.unsqueeze(0)?.unsqueeze(0)?;
-        }
-        if cos.rank() == 3 {
-            // (bs, seq_len, head_dim) -> (bs, 1, seq_len, head_dim)
-            cos = cos.unsqueeze(1)?;
-            sin = sin.unsqueeze(1)?;
-        }
-        let orig_dtype = q.dtype();
-        let q = if tof32 { &q.to_dtype(DType::F32)? } else { q };
-        let k = if tof32 { &k.to_dtype(DType::F32)? } else { k };
-        let cos = cos.to_dtype(q.dtype())?;
-        let sin = sin.to_dtype(q.dtype())?;
-
-        let q_embed = q
-            .broadcast_mul(&cos)?
-            .add(&rotate_half(q)?.broadcast_mul(&sin)?)?
-            .to_dtype(orig_dtype)?;
-        let k_embed = k
-            .broadcast_mul(&cos)?
-            .add(&rotate_half(k)?.broadcast_mul(&sin)?)?
-            .to_dtype(orig_dtype)?;
-        Ok((q_embed, k_embed))
+        Ok(fused::apply_rotary_pos_emb(q, k, cos, sin, tof32)?)
     }
 }
 
@@ -2,17 +2,19 @@
 // https://huggingface.co/cisco-ai/mini-bart-g2p/tree/main/onnx
 
 use gpt_sovits::{
-    GSVError, GptSoVitsModel, GptSoVitsModelConfig, LangId, OUTPUT_AUDIO_CHANNEL,
-    OUTPUT_AUDIO_SAMPLE_RATE, SamplingParams, StreamExt,
+    AudioSink, AudioSinkConfig, ExecutionProvider, GSVError, GptSoVitsModel, GptSoVitsModelConfig,
+    LangId, OUTPUT_AUDIO_CHANNEL, OUTPUT_AUDIO_SAMPLE_RATE, SamplingParams, SpeakerBank, StreamExt,
+    SynthesizeOptions, SynthesizeProgress,
 };
-use hound::{WavSpec, WavWriter};
 use rodio::{OutputStreamBuilder, Sink, buffer::SamplesBuffer};
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 const TEXT: &str = "Liquid 模板语言是一种开源的、安全的模板语言。最新版本为1.12.3。最初由 Shopify 用 Ruby 3.2 编写，并广泛用于其电子商务平台。它的核心设计理念是将业务逻辑与展示层分离，允许非开发者（如设计师、内容管理者）安全地修改界面而不影响后端代码。\nThis is a cross-platform library for interacting with the clipboard. It allows to copy and paste both text and image data in a platform independent way on Linux, Mac, and Windows.";
 
 async fn synth<P>(
     tts: &mut GptSoVitsModel,
+    bank: &mut SpeakerBank,
+    name: &str,
     ref_audio_path: P,
     ref_text: &str,
     text: &str,
@@ -22,9 +24,10 @@ async fn synth<P>(
 where
     P: AsRef<Path>,
 {
-    let ref_data = tts
-        .get_reference_data(ref_audio_path, ref_text, LangId::Auto)
-        .await?;
+    if !bank.contains(name) {
+        tts.add_speaker(bank, name, ref_audio_path, ref_text, LangId::Auto)
+            .await?;
+    }
 
     let sampling_params = SamplingParams::default()
         .with_top_k(Some(4))
@@ -32,24 +35,22 @@ where
         .with_temperature(1.0)
         .with_repetition_penalty(1.35);
 
+    let options = SynthesizeOptions::default().with_progress_callback(Arc::new(
+        |progress: SynthesizeProgress| {
+            log::debug!(
+                "sentence {}/{}, decoder step {}",
+                progress.sentence_index + 1,
+                progress.sentence_count,
+                progress.decoder_steps
+            );
+        },
+    ));
+
     let mut stream = tts
-        .synthesize(text, ref_data, sampling_params, LangId::Auto)
+        .synthesize_speaker(text, bank, name, sampling_params, LangId::Auto, options)
         .await?;
 
-    let mut wav_writer =
-        if let Some(ref wav_path) = output_wav {
-            let spec = WavSpec {
-                channels: 1,
-                sample_rate: 32000,
-                bits_per_sample: 16,
-                sample_format: hound::SampleFormat::Int,
-            };
-            Some(WavWriter::create(&wav_path, spec).map_err(|e| {
-                GSVError::InternalError(format!("Failed to create WAV file: {}", e))
-            })?)
-        } else {
-            None
-        };
+    let mut audio_sink = AudioSink::new(AudioSinkConfig::default());
 
     log::info!("Starting streaming synthesis...");
 
@@ -66,27 +67,15 @@ where
             audio_chunk.clone(),
         ));
 
-        if let Some(ref mut writer) = wav_writer {
-            for &sample in &audio_chunk {
-                let sample_i16 = (sample * i16::MAX as f32) as i16;
-                writer.write_sample(sample_i16).map_err(|e| {
-                    GSVError::InternalError(format!("Failed to write WAV sample: {}", e))
-                })?;
-            }
-        }
-
+        audio_sink.push(&audio_chunk);
         total_samples += chunk_len;
     }
 
     log::info!("Total samples: {}", total_samples);
 
-    if let Some(writer) = wav_writer {
-        writer
-            .finalize()
-            .map_err(|e| GSVError::InternalError(format!("Failed to finalize WAV file: {}", e)))?;
-        if let Some(wav_path) = output_wav {
-            log::info!("Audio saved to: {}", wav_path.as_ref().display());
-        }
+    if let Some(wav_path) = output_wav {
+        audio_sink.save(&wav_path)?;
+        log::info!("Audio saved to: {}", wav_path.as_ref().display());
     }
 
     Ok(())
@@ -112,10 +101,15 @@ async fn main() -> anyhow::Result<()> {
         .with_bert_path(model_dir.join("bert.onnx"))
         .with_g2pw_path(model_dir.join("g2pW.onnx"))
         .with_g2p_en_encoder_path(model_dir.join("g2p_en").join("encoder_model.onnx"))
-        .with_g2p_en_decoder_path(model_dir.join("g2p_en").join("decoder_model.onnx"));
+        .with_g2p_en_decoder_path(model_dir.join("g2p_en").join("decoder_model.onnx"))
+        .with_execution_providers(ExecutionProvider::available_providers());
 
     let mut tts = GptSoVitsModel::new(config)?;
 
+    // The reference features are only computed on the first run
+    let bank_path = Path::new("tmp").join("speakers.bin");
+    let mut bank = SpeakerBank::load(&bank_path).await.unwrap_or_default();
+
     let items = vec![
         ("ai.mp3", "你好啊，我是智能语音助手。"),
         // (
@@ -130,6 +124,8 @@ async fn main() -> anyhow::Result<()> {
 
         synth(
             &mut tts,
+            &mut bank,
+            name,
             Path::new("data").join(item.0),
             item.1,
             TEXT,
@@ -143,6 +139,7 @@ async fn main() -> anyhow::Result<()> {
         .await?;
     }
 
+    bank.save(&bank_path).await?;
     player.sleep_until_end();
 
     Ok(())
@@ -0,0 +1,122 @@
+use crate::{
+    GSVError, GptSoVitsModel, GptSoVitsModelConfig, LangId, ReferenceData, Result, SamplingParams,
+    SynthesizeOptions,
+};
+use futures::{StreamExt, future::join_all};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Models with their own ONNX sessions to synthesize several texts at the
+/// same time. Each model loads its own copy of the weights.
+pub struct GptSoVitsPool {
+    models: Vec<GptSoVitsModel>,
+}
+
+impl GptSoVitsPool {
+    pub fn new(config: GptSoVitsModelConfig, size: usize) -> Result<Self> {
+        let models = (0..size.max(1))
+            .map(|_| GptSoVitsModel::new(config.clone()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { models })
+    }
+
+    pub fn from_models(models: Vec<GptSoVitsModel>) -> Self {
+        Self { models }
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// The models, e.g. to compute reference data with the first one
+    pub fn models_mut(&mut self) -> &mut [GptSoVitsModel] {
+        &mut self.models
+    }
+
+    /// Synthesize every text on the first free model, so at most `len()`
+    /// texts at a time. The results are in the order of `texts` and a failed
+    /// text does not stop the others. The `options` are shared by all the
+    /// texts, so the progress is reported per text.
+    pub async fn synthesize_batch<S: AsRef<str>>(
+        &mut self,
+        texts: &[S],
+        reference_data: &ReferenceData,
+        sampling_param: SamplingParams,
+        lang_id: LangId,
+        options: SynthesizeOptions,
+    ) -> Result<Vec<Result<Vec<f32>>>> {
+        if self.models.is_empty() {
+            return Err(GSVError::InternalError("no model in the pool".to_string()));
+        }
+
+        let next_index = AtomicUsize::new(0);
+        let workers = self.models.iter_mut().map(|model| {
+            let next_index = &next_index;
+            let options = &options;
+            async move {
+                let mut results = vec![];
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    let Some(text) = texts.get(index) else {
+                        break;
+                    };
+
+                    log::debug!("batch item {index} started");
+                    let audio = model
+                        .synthesize_all(
+                            text.as_ref(),
+                            reference_data,
+                            sampling_param,
+                            lang_id,
+                            options.clone(),
+                        )
+                        .await;
+                    results.push((index, audio));
+                }
+                results
+            }
+        });
+
+        let mut results = join_all(workers)
+            .await
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        results.sort_by_key(|(index, _)| *index);
+
+        Ok(results.into_iter().map(|(_, audio)| audio).collect())
+    }
+}
+
+impl GptSoVitsModel {
+    /// Audio of the whole text
+    pub async fn synthesize_all(
+        &mut self,
+        text: &str,
+        reference_data: &ReferenceData,
+        sampling_param: SamplingParams,
+        lang_id: LangId,
+        options: SynthesizeOptions,
+    ) -> Result<Vec<f32>> {
+        let mut stream = self
+            .synthesize(
+                text,
+                reference_data.clone(),
+                sampling_param,
+                lang_id,
+                options,
+            )
+            .await?;
+
+        let mut audio = vec![];
+        while let Some(chunk) = stream.next().await {
+            audio.extend(chunk?);
+        }
+
+        Ok(audio)
+    }
+}
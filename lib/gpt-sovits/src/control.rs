@@ -0,0 +1,411 @@
+//! SSML like control tags in the synthesized text:
+//!
+//! - `<break time="500ms"/>` inserts a pause, `time` also takes seconds
+//!   (`1.5s`) and defaults to 500ms
+//! - `<prosody rate="1.2" pitch="+2st" temperature="0.8">...</prosody>`
+//!   changes the speed (a factor, `120%`, `+20%` or `slow`, `fast`...), the
+//!   pitch (semitones `-3st` or `+10%`) and the sampling temperature of the
+//!   enclosed text. The spans can be nested.
+//!
+//! Any other `<...>` is kept as text.
+
+use crate::{OUTPUT_AUDIO_SAMPLE_RATE, SamplingParams};
+use regex::Regex;
+use std::{f32::consts::PI, sync::LazyLock, time::Duration};
+
+const DEFAULT_BREAK: Duration = Duration::from_millis(500);
+const MAX_BREAK: Duration = Duration::from_secs(10);
+
+static TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)<\s*(/?)\s*(break|prosody)\b([^<>]*?)(/?)\s*>")
+        .expect("Failed to compile TAG_REGEX")
+});
+
+static ATTRIBUTE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(\w+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("Failed to compile ATTRIBUTE_REGEX")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prosody {
+    /// Speed factor, the pitch is kept
+    pub rate: f32,
+
+    /// Pitch shift in semitones, the speed is kept
+    pub pitch: f32,
+
+    /// Overrides the sampling temperature
+    pub temperature: Option<f32>,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            pitch: 0.0,
+            temperature: None,
+        }
+    }
+}
+
+impl Prosody {
+    pub fn is_neutral(&self) -> bool {
+        (self.rate - 1.0).abs() < 1e-3 && self.pitch.abs() < 1e-3
+    }
+
+    pub fn sampling_params(&self, params: SamplingParams) -> SamplingParams {
+        match self.temperature {
+            Some(temperature) => params.with_temperature(temperature),
+            None => params,
+        }
+    }
+
+    /// Shift the pitch by resampling, then bring the audio to the duration
+    /// of the rate with a time stretch
+    pub fn apply(&self, audio: Vec<f32>) -> Vec<f32> {
+        if self.is_neutral() {
+            return audio;
+        }
+
+        let pitch_factor = 2f32.powf(self.pitch / 12.0);
+        let shifted = resample_linear(&audio, (audio.len() as f32 / pitch_factor) as usize);
+        time_stretch(&shifted, (audio.len() as f32 / self.rate) as usize)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextSegment {
+    Text { text: String, prosody: Prosody },
+    Break(Duration),
+}
+
+/// Split the text at the control tags
+pub fn parse_control_tags(text: &str) -> Vec<TextSegment> {
+    let mut segments = vec![];
+    let mut stack = vec![Prosody::default()];
+    let mut last_end = 0;
+
+    for caps in TAG_REGEX.captures_iter(text) {
+        let tag = caps.get(0).unwrap();
+        push_text(
+            &mut segments,
+            &text[last_end..tag.start()],
+            stack[stack.len() - 1],
+        );
+        last_end = tag.end();
+
+        let closing = !caps[1].is_empty();
+        let self_closing = !caps[4].is_empty();
+        let attributes = &caps[3];
+
+        match caps[2].to_lowercase().as_str() {
+            "break" if !closing => segments.push(TextSegment::Break(parse_break(attributes))),
+            "prosody" if closing => {
+                if stack.len() > 1 {
+                    stack.pop();
+                } else {
+                    log::warn!("unmatched </prosody> tag");
+                }
+            }
+            "prosody" if !self_closing => {
+                let prosody = parse_prosody(attributes, stack[stack.len() - 1]);
+                stack.push(prosody);
+            }
+            _ => {}
+        }
+    }
+
+    push_text(&mut segments, &text[last_end..], stack[stack.len() - 1]);
+    segments
+}
+
+fn push_text(segments: &mut Vec<TextSegment>, text: &str, prosody: Prosody) {
+    if text.is_empty() {
+        return;
+    }
+
+    if let Some(TextSegment::Text {
+        text: last,
+        prosody: last_prosody,
+    }) = segments.last_mut()
+        && *last_prosody == prosody
+    {
+        last.push_str(text);
+        return;
+    }
+
+    segments.push(TextSegment::Text {
+        text: text.to_string(),
+        prosody,
+    });
+}
+
+fn attributes(attributes: &str) -> impl Iterator<Item = (String, &str)> {
+    ATTRIBUTE_REGEX.captures_iter(attributes).map(|caps| {
+        let value = caps.get(2).or(caps.get(3)).map_or("", |m| m.as_str());
+        (caps[1].to_lowercase(), value.trim())
+    })
+}
+
+fn parse_break(attrs: &str) -> Duration {
+    let Some((_, time)) = attributes(attrs).find(|(name, _)| name == "time") else {
+        return DEFAULT_BREAK;
+    };
+
+    let seconds = if let Some(ms) = time.strip_suffix("ms") {
+        ms.trim().parse::<f32>().map(|ms| ms / 1000.0)
+    } else {
+        time.trim_end_matches('s').trim().parse::<f32>()
+    };
+
+    match seconds {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+            Duration::from_secs_f32(seconds).min(MAX_BREAK)
+        }
+        _ => {
+            log::warn!("invalid break time: {time}");
+            DEFAULT_BREAK
+        }
+    }
+}
+
+fn parse_prosody(attrs: &str, parent: Prosody) -> Prosody {
+    let mut prosody = parent;
+
+    for (name, value) in attributes(attrs) {
+        let parsed = match name.as_str() {
+            "rate" => parse_rate(value).map(|rate| prosody.rate = parent.rate * rate),
+            "pitch" => parse_pitch(value).map(|pitch| prosody.pitch = parent.pitch + pitch),
+            "temperature" => value
+                .parse::<f32>()
+                .ok()
+                .filter(|t| *t > 0.0)
+                .map(|t| prosody.temperature = Some(t)),
+            _ => Some(()),
+        };
+
+        if parsed.is_none() {
+            log::warn!("invalid prosody {name}: {value}");
+        }
+    }
+
+    prosody.rate = prosody.rate.clamp(0.25, 4.0);
+    prosody.pitch = prosody.pitch.clamp(-12.0, 12.0);
+    prosody
+}
+
+fn parse_rate(value: &str) -> Option<f32> {
+    let rate = match value.to_lowercase().as_str() {
+        "x-slow" => 0.5,
+        "slow" => 0.75,
+        "medium" | "default" => 1.0,
+        "fast" => 1.25,
+        "x-fast" => 1.5,
+        value => match value.strip_suffix('%') {
+            Some(percent) if value.starts_with(['+', '-']) => {
+                1.0 + percent.parse::<f32>().ok()? / 100.0
+            }
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => value.parse::<f32>().ok()?,
+        },
+    };
+
+    (rate.is_finite() && rate > 0.0).then_some(rate)
+}
+
+fn parse_pitch(value: &str) -> Option<f32> {
+    let pitch = if let Some(semitones) = value.strip_suffix("st") {
+        semitones.parse::<f32>().ok()?
+    } else if let Some(percent) = value.strip_suffix('%') {
+        let factor = 1.0 + percent.parse::<f32>().ok()? / 100.0;
+        if factor <= 0.0 {
+            return None;
+        }
+        12.0 * factor.log2()
+    } else {
+        value.parse::<f32>().ok()?
+    };
+
+    pitch.is_finite().then_some(pitch)
+}
+
+/// Audio of `duration` silence
+pub(crate) fn silence(duration: Duration) -> Vec<f32> {
+    vec![0.0; (duration.as_secs_f32() * OUTPUT_AUDIO_SAMPLE_RATE as f32) as usize]
+}
+
+/// Resample the audio to `len` samples with linear interpolation
+fn resample_linear(audio: &[f32], len: usize) -> Vec<f32> {
+    if audio.is_empty() || len == audio.len() {
+        return audio.to_vec();
+    }
+
+    let factor = audio.len() as f32 / len as f32;
+    (0..len)
+        .map(|i| {
+            let pos = i as f32 * factor;
+            let index = pos as usize;
+            let frac = pos - index as f32;
+            let next = audio[(index + 1).min(audio.len() - 1)];
+            audio[index] * (1.0 - frac) + next * frac
+        })
+        .collect()
+}
+
+/// Stretch the audio to `len` samples keeping its pitch. The frames are
+/// overlap-added at the offset most similar to the natural continuation of
+/// the previous frame (WSOLA).
+fn time_stretch(audio: &[f32], len: usize) -> Vec<f32> {
+    const FRAME: usize = 1024;
+    const HOP: usize = FRAME / 2;
+    const SEARCH: usize = 160;
+
+    if len == audio.len() || audio.len() < FRAME * 2 {
+        return resample_linear(audio, len);
+    }
+
+    // Periodic Hann window, its overlaps at half a frame sum to 1
+    let window = (0..FRAME)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME as f32).cos())
+        .collect::<Vec<_>>();
+
+    let factor = len as f32 / audio.len() as f32;
+    let mut output = vec![0.0; len + FRAME];
+    let mut previous: Option<usize> = None;
+    let mut out_pos = 0;
+
+    while out_pos < len {
+        let nominal = ((out_pos as f32 / factor) as usize).min(audio.len() - FRAME);
+
+        let pos = match previous {
+            None => nominal,
+            Some(previous) => {
+                let target = &audio[previous + HOP..previous + HOP + HOP];
+                let lo = nominal.saturating_sub(SEARCH);
+                let hi = (nominal + SEARCH).min(audio.len() - FRAME);
+
+                (lo..=hi)
+                    .map(|pos| {
+                        let similarity = audio[pos..pos + HOP]
+                            .iter()
+                            .zip(target)
+                            .map(|(a, b)| a * b)
+                            .sum::<f32>();
+                        (pos, similarity)
+                    })
+                    .fold((nominal, f32::MIN), |best, item| {
+                        if item.1 > best.1 { item } else { best }
+                    })
+                    .0
+            }
+        };
+
+        for ((out, sample), gain) in output[out_pos..out_pos + FRAME]
+            .iter_mut()
+            .zip(&audio[pos..pos + FRAME])
+            .zip(&window)
+        {
+            *out += sample * gain;
+        }
+
+        previous = Some(pos);
+        out_pos += HOP;
+    }
+
+    output.truncate(len);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, rate: f32, pitch: f32) -> TextSegment {
+        TextSegment::Text {
+            text: text.to_string(),
+            prosody: Prosody {
+                rate,
+                pitch,
+                temperature: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            parse_control_tags("1 < 2, a <b> tag"),
+            [text("1 < 2, a <b> tag", 1.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_break() {
+        assert_eq!(
+            parse_control_tags(r#"你好<break time="250ms"/>世界<break/>end<BREAK time='1.5s' />"#),
+            [
+                text("你好", 1.0, 0.0),
+                TextSegment::Break(Duration::from_millis(250)),
+                text("世界", 1.0, 0.0),
+                TextSegment::Break(DEFAULT_BREAK),
+                text("end", 1.0, 0.0),
+                TextSegment::Break(Duration::from_millis(1500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_prosody() {
+        assert_eq!(
+            parse_control_tags(
+                r#"a<prosody rate="fast">b<prosody rate="200%" pitch="-2st">c</prosody>d</prosody>e"#
+            ),
+            [
+                text("a", 1.0, 0.0),
+                text("b", 1.25, 0.0),
+                text("c", 2.5, -2.0),
+                text("d", 1.25, 0.0),
+                text("e", 1.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_prosody() {
+        let segments = parse_control_tags(
+            r#"<prosody rate="-1" pitch="up" temperature="0.5">a</prosody></prosody>b"#,
+        );
+        assert_eq!(
+            segments[0],
+            TextSegment::Text {
+                text: "a".to_string(),
+                prosody: Prosody {
+                    temperature: Some(0.5),
+                    ..Default::default()
+                },
+            }
+        );
+        assert_eq!(segments[1], text("b", 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_apply_duration() {
+        let audio = (0..32000)
+            .map(|i| (i as f32 * 0.05).sin())
+            .collect::<Vec<_>>();
+
+        let prosody = Prosody {
+            rate: 2.0,
+            pitch: 3.0,
+            temperature: None,
+        };
+        assert_eq!(prosody.apply(audio.clone()).len(), 16000);
+
+        let prosody = Prosody {
+            rate: 0.5,
+            ..Default::default()
+        };
+        let slow = prosody.apply(audio);
+        assert_eq!(slow.len(), 64000);
+        assert!(slow.iter().all(|s| s.abs() <= 1.01));
+    }
+}
@@ -1,12 +1,22 @@
+mod batch;
+mod control;
 mod model;
+mod provider;
 mod sampler;
+mod sink;
 mod sovits;
+mod speaker;
 mod text;
 
+pub use batch::GptSoVitsPool;
+pub use control::{Prosody, TextSegment, parse_control_tags};
 pub use futures::{Stream, StreamExt};
 pub use model::Model;
+pub use provider::ExecutionProvider;
 pub use sampler::*;
+pub use sink::{AudioFormat, AudioSink, AudioSinkConfig, integrated_loudness, normalize_loudness};
 pub use sovits::*;
+pub use speaker::SpeakerBank;
 pub use text::*;
 
 pub const OUTPUT_AUDIO_CHANNEL: u16 = 1;
@@ -17,6 +27,12 @@ pub type Result<T> = std::result::Result<T, GSVError>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum GSVError {
+    #[error("encode audio failed: {0}")]
+    AudioEncode(String),
+
+    #[error(transparent)]
+    AudioProcess(#[from] audio_utils::AudioProcessError),
+
     #[error(transparent)]
     Box(#[from] Box<dyn std::error::Error + Send + Sync>),
 
@@ -35,6 +51,9 @@ pub enum GSVError {
     #[error("internal error: {0}")]
     InternalError(String),
 
+    #[error("invalid speaker bank: {0}")]
+    InvalidSpeakerBank(String),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
@@ -47,9 +66,18 @@ pub enum GSVError {
     #[error(transparent)]
     Shape(#[from] ndarray::ShapeError),
 
+    #[error("speaker not found: {0}")]
+    SpeakerNotFound(String),
+
+    #[error("synthesize cancelled")]
+    SynthesizeCancelled,
+
     #[error(transparent)]
     SystemTime(#[from] std::time::SystemTimeError),
 
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+
     #[error(transparent)]
     RegexError(#[from] regex::Error),
 
@@ -94,7 +122,30 @@ where
 }
 
 pub(crate) fn create_session(path: impl AsRef<std::path::Path>) -> Result<ort::session::Session> {
+    create_session_with_providers(path, &[], 0)
+}
+
+/// Try the available `providers` in order, the CPU is used if none of them
+/// can be registered
+pub(crate) fn create_session_with_providers(
+    path: impl AsRef<std::path::Path>,
+    providers: &[ExecutionProvider],
+    device_id: i32,
+) -> Result<ort::session::Session> {
+    let providers = providers
+        .iter()
+        .filter(|p| {
+            let available = p.is_available();
+            if !available {
+                log::warn!("{p:?} execution provider is not available");
+            }
+            available
+        })
+        .map(|p| p.dispatch(device_id))
+        .collect::<Vec<_>>();
+
     Ok(ort::session::Session::builder()?
+        .with_execution_providers(providers)?
         .with_prepacking(true)?
         .with_config_entry("session.enable_mem_reuse", "1")?
         .with_independent_thread_pool()?
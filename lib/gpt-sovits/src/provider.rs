@@ -0,0 +1,60 @@
+use ort::ep::{self, ExecutionProvider as _, ExecutionProviderDispatch};
+use strum::VariantArray as _;
+use strum_macros::VariantArray;
+
+/// Hardware backend of the ONNX sessions. The GPU providers need the matching
+/// cargo feature (`cuda`, `directml`, `coreml`) and a runtime library built
+/// with their support, the sessions fall back to the CPU otherwise.
+#[derive(VariantArray, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    Cuda,
+    DirectMl,
+    CoreMl,
+}
+
+impl ExecutionProvider {
+    pub fn all_providers() -> Vec<Self> {
+        Self::VARIANTS.to_vec()
+    }
+
+    /// The providers that can be used on this machine, the fastest first
+    pub fn available_providers() -> Vec<Self> {
+        [Self::Cuda, Self::DirectMl, Self::CoreMl, Self::Cpu]
+            .into_iter()
+            .filter(|p| p.is_available())
+            .collect()
+    }
+
+    /// Whether the runtime library supports the provider on this platform,
+    /// a model can still fail to run on it because of unsupported operators
+    pub fn is_available(&self) -> bool {
+        let (supported, available) = match self {
+            Self::Cpu => return true,
+            Self::Cuda => {
+                let ep = ep::CUDA::default();
+                (ep.supported_by_platform(), ep.is_available())
+            }
+            Self::DirectMl => {
+                let ep = ep::DirectML::default();
+                (ep.supported_by_platform(), ep.is_available())
+            }
+            Self::CoreMl => {
+                let ep = ep::CoreML::default();
+                (ep.supported_by_platform(), ep.is_available())
+            }
+        };
+
+        supported && available.unwrap_or(false)
+    }
+
+    pub(crate) fn dispatch(&self, device_id: i32) -> ExecutionProviderDispatch {
+        match self {
+            Self::Cpu => ep::CPU::default().build(),
+            Self::Cuda => ep::CUDA::default().with_device_id(device_id).build(),
+            Self::DirectMl => ep::DirectML::default().with_device_id(device_id).build(),
+            Self::CoreMl => ep::CoreML::default().build(),
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Collect the synthesized audio and save it to a file
+
+use crate::{GSVError, OUTPUT_AUDIO_SAMPLE_RATE, Result, sovits::resample_audio};
+use derivative::Derivative;
+use derive_setters::Setters;
+use futures::{Stream, StreamExt};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{f64::consts::PI, path::Path, time::Duration};
+
+// Highest sample peak after the loudness normalization
+const PEAK_CEILING: f32 = 0.98;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    /// 16 bits integer WAV
+    #[default]
+    Wav,
+
+    /// 32 bits float WAV
+    WavFloat,
+
+    #[cfg(feature = "vorbis")]
+    Ogg,
+
+    #[cfg(feature = "mp3")]
+    Mp3,
+}
+
+impl AudioFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "wav" => Some(Self::Wav),
+            #[cfg(feature = "vorbis")]
+            "ogg" | "oga" => Some(Self::Ogg),
+            #[cfg(feature = "mp3")]
+            "mp3" => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Setters, Derivative)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct AudioSinkConfig {
+    /// Guessed from the extension of the file if `None`
+    #[setters(strip_option)]
+    pub format: Option<AudioFormat>,
+
+    #[derivative(Default(value = "OUTPUT_AUDIO_SAMPLE_RATE"))]
+    pub sample_rate: u32,
+
+    /// Integrated loudness of the file in LUFS, the level is kept if `None`
+    #[derivative(Default(value = "Some(-16.0)"))]
+    pub target_loudness: Option<f32>,
+
+    /// Bitrate of the lossy formats in kbps
+    #[derivative(Default(value = "128"))]
+    pub bitrate: u32,
+}
+
+/// Mono audio at `OUTPUT_AUDIO_SAMPLE_RATE` waiting to be saved
+#[derive(Debug, Clone, Default)]
+pub struct AudioSink {
+    config: AudioSinkConfig,
+    samples: Vec<f32>,
+}
+
+impl AudioSink {
+    pub fn new(config: AudioSinkConfig) -> Self {
+        Self {
+            config,
+            samples: vec![],
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+    }
+
+    /// Collect the whole synthesis stream
+    pub async fn extend_from_stream<S>(&mut self, mut stream: S) -> Result<()>
+    where
+        S: Stream<Item = Result<Vec<f32>>> + Unpin,
+    {
+        while let Some(chunk) = stream.next().await {
+            self.push(&chunk?);
+        }
+        Ok(())
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / OUTPUT_AUDIO_SAMPLE_RATE as f64)
+    }
+
+    /// Normalize, resample and encode the audio
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let format = match self.config.format {
+            Some(format) => format,
+            None => AudioFormat::from_path(path).ok_or_else(|| {
+                GSVError::AudioEncode(format!("unsupported audio file: {}", path.display()))
+            })?,
+        };
+
+        let mut samples = self.samples.clone();
+        if let Some(target) = self.config.target_loudness {
+            normalize_loudness(&mut samples, OUTPUT_AUDIO_SAMPLE_RATE, target);
+        }
+
+        let sample_rate = self.config.sample_rate;
+        let samples = resample_audio(&samples, OUTPUT_AUDIO_SAMPLE_RATE, sample_rate)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        match format {
+            AudioFormat::Wav => write_wav(path, &samples, sample_rate, false),
+            AudioFormat::WavFloat => write_wav(path, &samples, sample_rate, true),
+            #[cfg(feature = "vorbis")]
+            AudioFormat::Ogg => write_ogg(path, &samples, sample_rate, self.config.bitrate),
+            #[cfg(feature = "mp3")]
+            AudioFormat::Mp3 => write_mp3(path, &samples, sample_rate, self.config.bitrate),
+        }
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+fn write_wav(path: &Path, samples: &[f32], sample_rate: u32, float: bool) -> Result<()> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: if float { 32 } else { 16 },
+        sample_format: if float {
+            SampleFormat::Float
+        } else {
+            SampleFormat::Int
+        },
+    };
+
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        if float {
+            writer.write_sample(sample)?;
+        } else {
+            writer.write_sample(to_i16(sample))?;
+        }
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+#[cfg(feature = "vorbis")]
+fn write_ogg(path: &Path, samples: &[f32], sample_rate: u32, bitrate: u32) -> Result<()> {
+    use std::num::{NonZeroU8, NonZeroU32};
+    use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+    let err = |e: vorbis_rs::VorbisError| GSVError::AudioEncode(e.to_string());
+    let invalid = || GSVError::AudioEncode("invalid sample rate or bitrate".to_string());
+
+    let file = std::fs::File::create(path)?;
+    let mut encoder = VorbisEncoderBuilder::new(
+        NonZeroU32::new(sample_rate).ok_or_else(invalid)?,
+        NonZeroU8::new(1).unwrap(),
+        file,
+    )
+    .map_err(err)?
+    .bitrate_management_strategy(VorbisBitrateManagementStrategy::Vbr {
+        target_bitrate: NonZeroU32::new(bitrate * 1000).ok_or_else(invalid)?,
+    })
+    .build()
+    .map_err(err)?;
+
+    for block in samples.chunks(4096) {
+        encoder.encode_audio_block([block]).map_err(err)?;
+    }
+    encoder.finish().map_err(err)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "mp3")]
+fn write_mp3(path: &Path, samples: &[f32], sample_rate: u32, bitrate: u32) -> Result<()> {
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, MonoPcm, Quality};
+
+    let err = |e: &dyn std::fmt::Debug| GSVError::AudioEncode(format!("{e:?}"));
+    let bitrate = match bitrate {
+        0..=96 => Bitrate::Kbps96,
+        97..=128 => Bitrate::Kbps128,
+        129..=160 => Bitrate::Kbps160,
+        161..=192 => Bitrate::Kbps192,
+        193..=256 => Bitrate::Kbps256,
+        _ => Bitrate::Kbps320,
+    };
+
+    let mut builder =
+        Builder::new().ok_or_else(|| GSVError::AudioEncode("create lame failed".to_string()))?;
+    builder.set_num_channels(1).map_err(|e| err(&e))?;
+    builder.set_sample_rate(sample_rate).map_err(|e| err(&e))?;
+    builder.set_brate(bitrate).map_err(|e| err(&e))?;
+    builder.set_quality(Quality::Best).map_err(|e| err(&e))?;
+    let mut encoder = builder.build().map_err(|e| err(&e))?;
+
+    let pcm = samples.iter().copied().map(to_i16).collect::<Vec<_>>();
+    let mut output = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(pcm.len()));
+
+    let size = encoder
+        .encode(MonoPcm(&pcm), output.spare_capacity_mut())
+        .map_err(|e| err(&e))?;
+    // SAFETY: the encoder initialized `size` bytes of the spare capacity
+    unsafe { output.set_len(output.len() + size) };
+
+    let size = encoder
+        .flush::<FlushNoGap>(output.spare_capacity_mut())
+        .map_err(|e| err(&e))?;
+    // SAFETY: same as above
+    unsafe { output.set_len(output.len() + size) };
+
+    std::fs::write(path, output)?;
+    Ok(())
+}
+
+/// Scale the audio to the `target` integrated loudness, the peaks are kept
+/// under `PEAK_CEILING`
+pub fn normalize_loudness(samples: &mut [f32], sample_rate: u32, target: f32) {
+    let Some(loudness) = integrated_loudness(samples, sample_rate) else {
+        return;
+    };
+
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let gain = 10f32
+        .powf((target - loudness) / 20.0)
+        .min(PEAK_CEILING / peak);
+    log::debug!("loudness: {loudness:.1} LUFS, gain: {gain:.2}");
+
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+/// Integrated loudness in LUFS (ITU-R BS.1770), `None` for silence
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let weighted = k_weighting(samples, sample_rate);
+    let block = ((sample_rate as f64 * 0.4) as usize).min(weighted.len());
+    if block == 0 {
+        return None;
+    }
+
+    // 400ms blocks overlapping by 75%
+    let powers = (0..=weighted.len() - block)
+        .step_by((block / 4).max(1))
+        .map(|start| {
+            weighted[start..start + block]
+                .iter()
+                .map(|s| s * s)
+                .sum::<f64>()
+                / block as f64
+        })
+        .collect::<Vec<_>>();
+
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+
+    let gated = powers
+        .into_iter()
+        .filter(|&p| loudness(p) > -70.0)
+        .collect::<Vec<_>>();
+    if gated.is_empty() {
+        return None;
+    }
+
+    let relative_gate = loudness(mean(&gated)) - 10.0;
+    let gated = gated
+        .into_iter()
+        .filter(|&p| loudness(p) > relative_gate)
+        .collect::<Vec<_>>();
+
+    Some(loudness(mean(&gated)) as f32)
+}
+
+/// The high shelf then high pass filters that model the head
+fn k_weighting(samples: &[f32], sample_rate: u32) -> Vec<f64> {
+    let fs = sample_rate as f64;
+
+    let shelf = {
+        let (gain, q, fc) = (4.0, 1.0 / 2f64.sqrt(), 1500.0);
+        let a = 10f64.powf(gain / 40.0);
+        let w0 = 2.0 * PI * fc / fs;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+
+        Biquad::new(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + 2.0 * a.sqrt() * alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - 2.0 * a.sqrt() * alpha),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + 2.0 * a.sqrt() * alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - 2.0 * a.sqrt() * alpha,
+            ],
+        )
+    };
+
+    let high_pass = {
+        let (q, fc) = (0.5, 38.0);
+        let w0 = 2.0 * PI * fc / fs;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+
+        Biquad::new(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    };
+
+    let (mut shelf, mut high_pass) = (shelf, high_pass);
+    samples
+        .iter()
+        .map(|&s| high_pass.process(shelf.process(s as f64)))
+        .collect()
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: b.map(|b| b / a[0]),
+            a: [a[1] / a[0], a[2] / a[0]],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * seconds) as usize)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * 997.0 * i as f32 / sample_rate as f32).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_full_scale_sine_loudness() {
+        // BS.1770: a 0 dBFS 997Hz sine measures -3.01 LUFS
+        let loudness = integrated_loudness(&sine(48000, 1.0, 3.0), 48000).unwrap();
+        assert!((loudness + 3.01).abs() < 0.1, "{loudness}");
+
+        let loudness = integrated_loudness(&sine(32000, 1.0, 3.0), 32000).unwrap();
+        assert!((loudness + 3.01).abs() < 0.1, "{loudness}");
+    }
+
+    #[test]
+    fn test_silence_loudness() {
+        assert_eq!(integrated_loudness(&[0.0; 32000], 32000), None);
+        assert_eq!(integrated_loudness(&[], 32000), None);
+    }
+
+    #[test]
+    fn test_normalize_loudness() {
+        let mut samples = sine(32000, 0.05, 2.0);
+        normalize_loudness(&mut samples, 32000, -16.0);
+
+        let loudness = integrated_loudness(&samples, 32000).unwrap();
+        assert!((loudness + 16.0).abs() < 0.1, "{loudness}");
+
+        // Limited by the peak ceiling
+        normalize_loudness(&mut samples, 32000, 0.0);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - PEAK_CEILING).abs() < 1e-3, "{peak}");
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(AudioFormat::from_path("a/b.WAV"), Some(AudioFormat::Wav));
+        assert_eq!(AudioFormat::from_path("a/b"), None);
+        assert_eq!(AudioFormat::from_path("a/b.txt"), None);
+    }
+}
@@ -1,23 +1,32 @@
 use crate::{
-    BertModel, G2PW, G2pEn, GSVError, LangId, OUTPUT_AUDIO_SAMPLE_RATE,
-    REFERENCE_AUDIO_SAMPLE_RATE, Result, Sampler, SamplingParams, Stream, TextProcessor, argmax,
-    create_session,
+    BertModel, ExecutionProvider, G2PW, G2pEn, GSVError, LangId, OUTPUT_AUDIO_SAMPLE_RATE,
+    REFERENCE_AUDIO_SAMPLE_RATE, Result, Sampler, SamplingParams, SpeakerBank, SpeechItem, Stream,
+    TextProcessor, argmax, control::silence, create_session_with_providers,
 };
 use async_stream::stream;
+use audio_utils::{
+    audio::multi_to_mono,
+    resample::{ResampleQuality, resample},
+};
 use derivative::Derivative;
 use derive_setters::Setters;
 use ndarray::{
-    Array, Array2, ArrayBase, ArrayD, ArrayView2, Axis, IxDyn, OwnedRepr, concatenate, s,
+    Array, Array2, ArrayBase, ArrayView2, ArrayView3, Axis, IxDyn, OwnedRepr, concatenate, s,
 };
 use ort::{
     inputs,
-    session::{RunOptions, Session, SessionOutputs},
+    session::{RunOptions, Session, SessionInputValue, SessionOutputs},
     value::{Tensor, TensorRef},
 };
-use rodio::{Source, buffer::SamplesBuffer, decoder::Decoder, source::UniformSourceIterator};
+use rodio::{Source, decoder::Decoder};
 use std::{
     io::Cursor,
+    mem,
     path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::{Duration, SystemTime},
 };
 use tokio::fs::read;
@@ -29,7 +38,20 @@ const MAX_DECODER_STEPS: usize = 1500;
 const INITIAL_CACHE_SIZE: usize = 2048;
 const CACHE_REALLOC_INCREMENT: usize = 1024;
 
+// The semantic tokens are generated at 25Hz
+const SAMPLES_PER_TOKEN: usize = OUTPUT_AUDIO_SAMPLE_RATE as usize / 25;
+
+// 100ms of fade in and out of a sentence, also the crossfade of two chunks
+const STREAM_FADE_SAMPLES: usize = OUTPUT_AUDIO_SAMPLE_RATE as usize / 10;
+
+#[cfg(not(feature = "kv-f16"))]
 type KvDType = f32;
+
+/// Halves the memory of the caches, the T2S decoders must be exported with
+/// f16 caches
+#[cfg(feature = "kv-f16")]
+type KvDType = half::f16;
+
 type KvCache = ArrayBase<OwnedRepr<KvDType>, IxDyn>;
 type KvCacheTuple = (Vec<KvCache>, Vec<KvCache>, usize);
 
@@ -56,22 +78,116 @@ pub struct GptSoVitsModelConfig {
     pub g2p_en_encoder_path: PathBuf,
     #[derivative(Default(value = "PathBuf::from(\"g2p_en_decoder_model.onnx\")"))]
     pub g2p_en_decoder_path: PathBuf,
+
+    /// Providers of the SSL, T2S and SoVITS sessions tried in order, e.g.
+    /// `ExecutionProvider::available_providers()`. The small text models
+    /// always run on the CPU.
+    #[derivative(Default(value = "vec![ExecutionProvider::Cpu]"))]
+    pub execution_providers: Vec<ExecutionProvider>,
+
+    /// GPU used by the CUDA and DirectML providers
+    pub device_id: i32,
+
+    /// Yield the audio of a sentence in chunks of this many semantic tokens
+    /// (40ms each) while they are generated, or whole sentences if `None`
+    #[derivative(Default(value = "Some(25)"))]
+    pub stream_chunk_tokens: Option<usize>,
+
+    /// Already vocoded tokens decoded again before each chunk, so that it
+    /// joins the previous one smoothly
+    #[derivative(Default(value = "25"))]
+    pub stream_context_tokens: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SynthesizeProgress {
+    pub sentence_index: usize, // (0-based)
+    pub sentence_count: usize,
+    pub decoder_steps: usize,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(SynthesizeProgress) + Send + Sync>;
+
+#[derive(Clone, Default, Setters)]
+#[setters(prefix = "with_", strip_option)]
+#[non_exhaustive]
+pub struct SynthesizeOptions {
+    /// Checked before each sentence and each decoder step. Once set, the
+    /// stream yields `GSVError::SynthesizeCancelled` and ends.
+    pub cancel_sig: Option<Arc<AtomicBool>>,
+
+    /// Called before each decoder step
+    pub progress_callback: Option<ProgressCallback>,
+}
+
+impl SynthesizeOptions {
+    fn check_cancelled(&self) -> Result<()> {
+        match self.cancel_sig {
+            Some(ref cancel_sig) if cancel_sig.load(Ordering::Relaxed) => {
+                Err(GSVError::SynthesizeCancelled)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn on_decoder_step(&self, progress: SynthesizeProgress) -> Result<()> {
+        self.check_cancelled()?;
+        if let Some(ref callback) = self.progress_callback {
+            callback(progress);
+        }
+        Ok(())
+    }
 }
 
 struct DecoderLoopContext {
     y_vec: Vec<i64>,
     k_caches: Vec<KvCache>,
     v_caches: Vec<KvCache>,
+    sampler: Sampler,
     prefix_len: usize,
-    initial_valid_len: usize,
+    valid_len: usize,
+    idx: usize,
+    finished: bool,
+}
+
+impl DecoderLoopContext {
+    /// Semantic tokens that will not change anymore. The last generated
+    /// token is only kept once the decoder finished, replaced by a silent one.
+    fn semantic_tokens(&self) -> Vec<i64> {
+        let end = self.y_vec.len() - 1;
+        let start = (self.prefix_len + 3).min(end);
+
+        let mut tokens = self.y_vec[start..end]
+            .iter()
+            .map(|&i| if i == T2S_DECODER_EOS { 0 } else { i })
+            .collect::<Vec<i64>>();
+
+        if self.finished {
+            tokens.push(0);
+        }
+        tokens
+    }
+}
+
+/// A sentence synthesized in chunks. Each chunk is vocoded with the tokens
+/// before it as context, and its start is crossfaded with the held back end
+/// of the previous chunk.
+struct SentenceStream {
+    decoder: DecoderLoopContext,
+    text_seq: Vec<i64>,
+    vocoded_tokens: usize,
+    vocoded_all: bool,
+
+    // Vocoded samples not yielded yet
+    pending: Vec<f32>,
 }
 
 #[derive(Clone)]
 pub struct ReferenceData {
-    ref_seq: Array2<i64>,
-    ref_bert: Array2<f32>,
-    ref_audio_32k: Array2<f32>,
-    ssl_content: ArrayBase<OwnedRepr<f32>, IxDyn>,
+    pub(crate) ref_seq: Array2<i64>,
+    pub(crate) ref_bert: Array2<f32>,
+    pub(crate) ref_audio_32k: Array2<f32>,
+    pub(crate) ssl_content: ArrayBase<OwnedRepr<f32>, IxDyn>,
 }
 
 pub struct GptSoVitsModel {
@@ -84,6 +200,12 @@ pub struct GptSoVitsModel {
     num_layers: usize,
     run_options: RunOptions,
     last_sentence_end_tokens: Option<Vec<i64>>,
+
+    // Caches of the last sentence reused by the next one
+    kv_cache_pool: Option<(Vec<KvCache>, Vec<KvCache>)>,
+
+    stream_chunk_tokens: Option<usize>,
+    stream_context_tokens: usize,
 }
 
 impl GptSoVitsModel {
@@ -94,6 +216,11 @@ impl GptSoVitsModel {
             BertModel::new(config.bert_path)?,
         )?;
 
+        log::info!("Execution providers: {:?}", config.execution_providers);
+        let create_session = |path: PathBuf| {
+            create_session_with_providers(path, &config.execution_providers, config.device_id)
+        };
+
         Ok(GptSoVitsModel {
             text_processor,
             sovits: create_session(config.sovits_path)?,
@@ -104,6 +231,9 @@ impl GptSoVitsModel {
             num_layers: NUM_LAYERS,
             run_options: RunOptions::new()?,
             last_sentence_end_tokens: None,
+            kv_cache_pool: None,
+            stream_chunk_tokens: config.stream_chunk_tokens.map(|n| n.max(1)),
+            stream_context_tokens: config.stream_context_tokens,
         })
     }
 
@@ -149,21 +279,134 @@ impl GptSoVitsModel {
         reference_data: ReferenceData,
         sampling_param: SamplingParams,
         lang_id: LangId,
+        options: SynthesizeOptions,
     ) -> Result<impl Stream<Item = Result<Vec<f32>>> + Send + Unpin> {
         let start_time = SystemTime::now();
-        let texts_and_seqs = self.text_processor.get_phone_and_bert(text, lang_id)?;
+        let items = self.text_processor.get_speech_items(text, lang_id)?;
         log::debug!("g2pw and preprocess time: {:?}", start_time.elapsed()?);
 
+        let sentence_count = items
+            .iter()
+            .filter(|item| matches!(item, SpeechItem::Sentence { .. }))
+            .count();
+
+        let stream_chunk_tokens = self.stream_chunk_tokens;
         let stream = stream! {
-            for (text, seq, bert) in texts_and_seqs {
-                log::debug!("process: {:?}", text);
-                yield self.in_stream_once_gen(&bert, &seq, &reference_data, sampling_param).await;
+            let mut sentence_index = 0;
+            'items: for item in items {
+                if let Err(e) = options.check_cancelled() {
+                    yield Err(e);
+                    break;
+                }
+
+                let (text, seq, bert, prosody) = match item {
+                    SpeechItem::Sentence { text, phone_ids, bert, prosody } => {
+                        (text, phone_ids, bert, prosody)
+                    }
+                    SpeechItem::Break(duration) => {
+                        yield Ok(silence(duration));
+                        continue;
+                    }
+                };
+                log::debug!("process: {:?}, {:?}", text, prosody);
+
+                let progress = SynthesizeProgress {
+                    sentence_index,
+                    sentence_count,
+                    decoder_steps: 0,
+                };
+                sentence_index += 1;
+
+                let sampling_param = prosody.sampling_params(sampling_param);
+
+                // The rate and the pitch are applied on the whole sentence
+                let chunk_tokens = stream_chunk_tokens.filter(|_| prosody.is_neutral());
+                let Some(chunk_tokens) = chunk_tokens else {
+                    match self
+                        .in_stream_once_gen(&bert, &seq, &reference_data, sampling_param, &options, progress)
+                        .await
+                    {
+                        Ok(audio) => yield Ok(prosody.apply(audio)),
+                        Err(GSVError::SynthesizeCancelled) => {
+                            yield Err(GSVError::SynthesizeCancelled);
+                            break;
+                        }
+                        Err(e) => yield Err(e),
+                    }
+                    continue;
+                };
+
+                let mut sentence = match self
+                    .prepare_decoder(&bert, &seq, &reference_data, sampling_param)
+                    .await
+                {
+                    Ok(decoder) => SentenceStream {
+                        decoder,
+                        text_seq: seq,
+                        vocoded_tokens: 0,
+                        vocoded_all: false,
+                        pending: vec![],
+                    },
+                    Err(e) => {
+                        yield Err(e);
+                        continue;
+                    }
+                };
+
+                loop {
+                    match self
+                        .next_stream_chunk(&mut sentence, &reference_data, sampling_param, chunk_tokens, &options, progress)
+                        .await
+                    {
+                        Ok(Some(chunk)) => yield Ok(chunk),
+                        Ok(None) => break,
+                        Err(GSVError::SynthesizeCancelled) => {
+                            yield Err(GSVError::SynthesizeCancelled);
+                            break 'items;
+                        }
+                        Err(e) => {
+                            yield Err(e);
+                            break;
+                        }
+                    }
+                }
             }
         };
 
         Ok(Box::pin(stream))
     }
 
+    /// Compute the reference data of a speaker and add it to the bank
+    pub async fn add_speaker(
+        &mut self,
+        bank: &mut SpeakerBank,
+        name: impl Into<String>,
+        reference_audio_path: impl AsRef<Path>,
+        ref_text: &str,
+        lang_id: LangId,
+    ) -> Result<()> {
+        let data = self
+            .get_reference_data(reference_audio_path, ref_text, lang_id)
+            .await?;
+        bank.insert(name, data);
+        Ok(())
+    }
+
+    /// Synthesize with the voice of a speaker of the bank
+    pub async fn synthesize_speaker(
+        &mut self,
+        text: &str,
+        bank: &SpeakerBank,
+        speaker: &str,
+        sampling_param: SamplingParams,
+        lang_id: LangId,
+        options: SynthesizeOptions,
+    ) -> Result<impl Stream<Item = Result<Vec<f32>>> + Send + Unpin> {
+        let reference_data = bank.get(speaker)?.clone();
+        self.synthesize(text, reference_data, sampling_param, lang_id, options)
+            .await
+    }
+
     async fn process_ssl(
         &mut self,
         ref_audio_16k: &Array2<f32>,
@@ -182,133 +425,120 @@ impl GptSoVitsModel {
             .into_owned())
     }
 
-    async fn run_t2s_s_decoder_loop(
+    async fn run_t2s_s_decoder_step(
         &mut self,
-        sampler: &mut Sampler,
+        ctx: &mut DecoderLoopContext,
         sampling_param: SamplingParams,
-        ctx: DecoderLoopContext,
-    ) -> Result<ArrayBase<OwnedRepr<i64>, IxDyn>> {
-        let DecoderLoopContext {
-            mut y_vec,
-            mut k_caches,
-            mut v_caches,
-            prefix_len,
-            initial_valid_len,
-        } = ctx;
+    ) -> Result<()> {
+        let idx = ctx.idx;
+        let valid_len = ctx.valid_len;
+
+        let mut inputs = inputs![
+            "iy" => TensorRef::from_array_view(unsafe {ArrayView2::from_shape_ptr((1, ctx.y_vec.len()), ctx.y_vec.as_ptr())})?,
+            "y_len" => Tensor::from_array(Array::from_vec(vec![ctx.prefix_len as i64]))?,
+            "idx" => Tensor::from_array(Array::from_vec(vec![idx as i64]))?,
+        ];
+
+        for i in 0..self.num_layers {
+            inputs.push((
+                format!("ik_cache_{}", i).into(),
+                cache_input(&ctx.k_caches[i], valid_len)?,
+            ));
+            inputs.push((
+                format!("iv_cache_{}", i).into(),
+                cache_input(&ctx.v_caches[i], valid_len)?,
+            ));
+        }
 
-        let mut idx = 0;
-        let mut valid_len = initial_valid_len;
-        y_vec.reserve(INITIAL_CACHE_SIZE);
+        let mut output = self
+            .t2s_s_decoder
+            .run_async(inputs, &self.run_options)?
+            .await?;
 
-        loop {
-            let mut inputs = inputs![
-                "iy" => TensorRef::from_array_view(unsafe {ArrayView2::from_shape_ptr((1, y_vec.len()), y_vec.as_ptr())})?,
-                "y_len" => Tensor::from_array(Array::from_vec(vec![prefix_len as i64]))?,
-                "idx" => Tensor::from_array(Array::from_vec(vec![idx as i64]))?,
-            ];
+        let mut logits = output["logits"].try_extract_array_mut::<f32>()?;
+        let mut logits = logits
+            .as_slice_mut()
+            .map(|s| s.to_owned())
+            .ok_or(GSVError::InternalError("Failed to get logits slice".into()))?;
 
-            for i in 0..self.num_layers {
-                let k = k_caches[i].slice(s![.., 0..valid_len, ..]).to_owned();
-                let v = v_caches[i].slice(s![.., 0..valid_len, ..]).to_owned();
-
-                inputs.push((
-                    format!("ik_cache_{}", i).into(),
-                    Tensor::from_array(k)?.into(),
-                ));
-                inputs.push((
-                    format!("iv_cache_{}", i).into(),
-                    Tensor::from_array(v)?.into(),
-                ));
+        if idx < 11 {
+            // Disable EOS token during first 11 steps to prevent early stopping
+            if let Some(item) = logits.last_mut() {
+                *item = f32::NEG_INFINITY;
             }
 
-            let mut output = self
-                .t2s_s_decoder
-                .run_async(inputs, &self.run_options)?
-                .await?;
+            // Boost neutral token probability during first few steps for smoother start
+            if idx < 5 && !logits.is_empty() {
+                logits[0] *= 1.3; // Token 0 is typically neutral/silent
+            }
+        }
 
-            let mut logits = output["logits"].try_extract_array_mut::<f32>()?;
-            let mut logits = logits
-                .as_slice_mut()
-                .map(|s| s.to_owned())
-                .ok_or(GSVError::InternalError("Failed to get logits slice".into()))?;
+        // The inputs borrow the caches as long as the outputs are alive
+        let new_kv = new_kv_rows(&output, valid_len, self.num_layers)?;
+        drop(output);
 
-            if idx < 11 {
-                // Disable EOS token during first 11 steps to prevent early stopping
-                if let Some(item) = logits.last_mut() {
-                    *item = f32::NEG_INFINITY;
-                }
+        ctx.y_vec
+            .push(ctx.sampler.sample(&mut logits, &ctx.y_vec, &sampling_param));
+        let argmax_value = argmax(&logits);
 
-                // Boost neutral token probability during first few steps for smoother start
-                if idx < 5 && !logits.is_empty() {
-                    logits[0] *= 1.3; // Token 0 is typically neutral/silent
-                }
+        // Check for reallocation and update caches
+        let new_valid_len = valid_len + 1;
+        if new_valid_len > ctx.k_caches[0].shape()[1] {
+            for i in 0..self.num_layers {
+                let old_k = &ctx.k_caches[i];
+                let old_v = &ctx.v_caches[i];
+
+                let mut new_k_dims = old_k.raw_dim().clone();
+                new_k_dims[1] += CACHE_REALLOC_INCREMENT;
+                let mut new_v_dims = old_v.raw_dim().clone();
+                new_v_dims[1] += CACHE_REALLOC_INCREMENT;
+
+                let mut new_k = Array::zeros(new_k_dims);
+                let mut new_v = Array::zeros(new_v_dims);
+
+                new_k
+                    .slice_mut(s![.., 0..valid_len, ..])
+                    .assign(&old_k.slice(s![.., 0..valid_len, ..]));
+                new_v
+                    .slice_mut(s![.., 0..valid_len, ..])
+                    .assign(&old_v.slice(s![.., 0..valid_len, ..]));
+
+                ctx.k_caches[i] = new_k;
+                ctx.v_caches[i] = new_v;
             }
+        }
 
-            y_vec.push(sampler.sample(&mut logits, &y_vec, &sampling_param));
-            let argmax_value = argmax(&logits);
-
-            // Check for reallocation and update caches
-            let new_valid_len = valid_len + 1;
-            if new_valid_len > k_caches[0].shape()[1] {
-                for i in 0..self.num_layers {
-                    let old_k = &k_caches[i];
-                    let old_v = &v_caches[i];
-
-                    let mut new_k_dims = old_k.raw_dim().clone();
-                    new_k_dims[1] += CACHE_REALLOC_INCREMENT;
-                    let mut new_v_dims = old_v.raw_dim().clone();
-                    new_v_dims[1] += CACHE_REALLOC_INCREMENT;
-
-                    let mut new_k = Array::zeros(new_k_dims);
-                    let mut new_v = Array::zeros(new_v_dims);
-
-                    new_k
-                        .slice_mut(s![.., 0..valid_len, ..])
-                        .assign(&old_k.slice(s![.., 0..valid_len, ..]));
-                    new_v
-                        .slice_mut(s![.., 0..valid_len, ..])
-                        .assign(&old_v.slice(s![.., 0..valid_len, ..]));
-
-                    k_caches[i] = new_k;
-                    v_caches[i] = new_v;
-                }
-            }
+        for (i, (k, v)) in new_kv.iter().enumerate() {
+            ctx.k_caches[i].slice_mut(s![.., valid_len, ..]).assign(k);
+            ctx.v_caches[i].slice_mut(s![.., valid_len, ..]).assign(v);
+        }
 
-            update_kv_cache(
-                &mut k_caches,
-                &mut v_caches,
-                &output,
-                valid_len,
-                self.num_layers,
-            )?;
-
-            valid_len = new_valid_len;
-
-            if idx >= MAX_DECODER_STEPS || argmax_value == T2S_DECODER_EOS {
-                let mut sliced = y_vec[(y_vec.len() - idx + 1)..(y_vec.len() - 1)]
-                    .iter()
-                    .map(|&i| if i == T2S_DECODER_EOS { 0 } else { i })
-                    .collect::<Vec<i64>>();
-                sliced.push(0);
-                log::debug!(
-                    "t2s final len: {}, prefix_len: {}",
-                    sliced.len(),
-                    prefix_len
-                );
-                let y = ArrayD::from_shape_vec(IxDyn(&[1, 1, sliced.len()]), sliced)?;
-                return Ok(y);
-            }
-            idx += 1;
+        ctx.valid_len = new_valid_len;
+
+        if idx >= MAX_DECODER_STEPS || argmax_value == T2S_DECODER_EOS {
+            ctx.finished = true;
+        } else {
+            ctx.idx += 1;
         }
+
+        Ok(())
     }
 
-    async fn in_stream_once_gen(
+    /// Keep the caches of a finished sentence for the next one
+    fn recycle_kv_caches(&mut self, ctx: &mut DecoderLoopContext) {
+        if !ctx.k_caches.is_empty() {
+            self.kv_cache_pool = Some((mem::take(&mut ctx.k_caches), mem::take(&mut ctx.v_caches)));
+        }
+    }
+
+    /// Run the encoder and the first decoder pass of a sentence
+    async fn prepare_decoder(
         &mut self,
         text_bert: &Array2<f32>,
         text_seq_vec: &[i64],
         ref_data: &ReferenceData,
         sampling_param: SamplingParams,
-    ) -> Result<Vec<f32>> {
+    ) -> Result<DecoderLoopContext> {
         let text_seq = ArrayView2::from_shape((1, text_seq_vec.len()), text_seq_vec)?;
         let mut sampler = Sampler::new(VOCAB_SIZE);
 
@@ -360,52 +590,54 @@ impl GptSoVitsModel {
 
         let (mut y_vec, _) = prompts.clone().into_raw_vec_and_offset();
         let prefix_len = y_vec.len();
+        y_vec.reserve(INITIAL_CACHE_SIZE);
 
-        let (y_vec, k_caches, v_caches, initial_seq_len) = {
-            let start_time = SystemTime::now();
-            let fs_decoder_output = self
-                .t2s_fs_decoder
-                .run_async(
-                    inputs![
-                        "x" => Tensor::from_array(x)?,
-                        "prompts" => TensorRef::from_array_view(&prompts)?,
-                        "bert" => Tensor::from_array(bert)?,
-                    ],
-                    &self.run_options,
-                )?
-                .await?;
-            log::debug!("T2S FS Decoder time: {:?}", start_time.elapsed()?);
+        let start_time = SystemTime::now();
+        let fs_decoder_output = self
+            .t2s_fs_decoder
+            .run_async(
+                inputs![
+                    "x" => Tensor::from_array(x)?,
+                    "prompts" => TensorRef::from_array_view(&prompts)?,
+                    "bert" => Tensor::from_array(bert)?,
+                ],
+                &self.run_options,
+            )?
+            .await?;
+        log::debug!("T2S FS Decoder time: {:?}", start_time.elapsed()?);
 
-            let logits = fs_decoder_output["logits"]
-                .try_extract_array::<f32>()?
-                .into_owned();
-            let (k_caches, v_caches, initial_seq_len) =
-                initialize_kv_caches(&fs_decoder_output, NUM_LAYERS)?;
+        let logits = fs_decoder_output["logits"]
+            .try_extract_array::<f32>()?
+            .into_owned();
+        let (k_caches, v_caches, initial_seq_len) =
+            initialize_kv_caches(&fs_decoder_output, NUM_LAYERS, self.kv_cache_pool.take())?;
 
-            let (mut logits_vec, _) = logits.into_raw_vec_and_offset();
-            logits_vec.pop();
+        let (mut logits_vec, _) = logits.into_raw_vec_and_offset();
+        logits_vec.pop();
 
-            let sampling_rst = sampler.sample(&mut logits_vec, &y_vec, &sampling_param);
-            y_vec.push(sampling_rst);
+        let sampling_rst = sampler.sample(&mut logits_vec, &y_vec, &sampling_param);
+        y_vec.push(sampling_rst);
 
-            (y_vec, k_caches, v_caches, initial_seq_len)
-        };
+        Ok(DecoderLoopContext {
+            y_vec,
+            k_caches,
+            v_caches,
+            sampler,
+            prefix_len,
+            valid_len: initial_seq_len,
+            idx: 0,
+            finished: false,
+        })
+    }
 
-        let start_time = SystemTime::now();
-        let pred_semantic = self
-            .run_t2s_s_decoder_loop(
-                &mut sampler,
-                sampling_param,
-                DecoderLoopContext {
-                    y_vec,
-                    k_caches,
-                    v_caches,
-                    prefix_len,
-                    initial_valid_len: initial_seq_len,
-                },
-            )
-            .await?;
-        log::debug!("T2S S Decoder all time: {:?}", start_time.elapsed()?);
+    async fn vocode(
+        &mut self,
+        text_seq: &[i64],
+        semantic_tokens: &[i64],
+        ref_data: &ReferenceData,
+    ) -> Result<Vec<f32>> {
+        let text_seq = ArrayView2::from_shape((1, text_seq.len()), text_seq)?;
+        let pred_semantic = ArrayView3::from_shape((1, 1, semantic_tokens.len()), semantic_tokens)?;
 
         let sovits_start = SystemTime::now();
         let outputs = self
@@ -413,7 +645,7 @@ impl GptSoVitsModel {
             .run_async(
                 inputs![
                     "text_seq" => TensorRef::from_array_view(text_seq)?,
-                    "pred_semantic" => TensorRef::from_array_view(&pred_semantic)?,
+                    "pred_semantic" => TensorRef::from_array_view(pred_semantic)?,
                     "ref_audio" => TensorRef::from_array_view(&ref_data.ref_audio_32k)?
                 ],
                 &self.run_options,
@@ -423,24 +655,145 @@ impl GptSoVitsModel {
 
         let output_audio = outputs["audio"].try_extract_array::<f32>()?;
         let (audio, _) = output_audio.into_owned().into_raw_vec_and_offset();
-        let audio: Vec<f32> = audio.into_iter().map(|s| s.clamp(-1.0, 1.0)).collect();
+        Ok(audio.into_iter().map(|s| s.clamp(-1.0, 1.0)).collect())
+    }
 
+    async fn in_stream_once_gen(
+        &mut self,
+        text_bert: &Array2<f32>,
+        text_seq_vec: &[i64],
+        ref_data: &ReferenceData,
+        sampling_param: SamplingParams,
+        options: &SynthesizeOptions,
+        progress: SynthesizeProgress,
+    ) -> Result<Vec<f32>> {
+        let mut ctx = self
+            .prepare_decoder(text_bert, text_seq_vec, ref_data, sampling_param)
+            .await?;
+
+        let start_time = SystemTime::now();
+        while !ctx.finished {
+            options.on_decoder_step(SynthesizeProgress {
+                decoder_steps: ctx.idx,
+                ..progress
+            })?;
+            self.run_t2s_s_decoder_step(&mut ctx, sampling_param)
+                .await?;
+        }
+        self.recycle_kv_caches(&mut ctx);
+        let pred_semantic = ctx.semantic_tokens();
+        log::debug!(
+            "t2s final len: {}, prefix_len: {}",
+            pred_semantic.len(),
+            ctx.prefix_len
+        );
+        log::debug!("T2S S Decoder all time: {:?}", start_time.elapsed()?);
+
+        let audio = self.vocode(text_seq_vec, &pred_semantic, ref_data).await?;
         Ok(apply_fade_in_out(audio, Duration::from_millis(100)))
     }
+
+    /// Decode until the next chunk of `chunk_tokens` tokens of audio is
+    /// ready, the last chunk of the sentence is shorter. `None` once the
+    /// whole sentence was yielded.
+    async fn next_stream_chunk(
+        &mut self,
+        sentence: &mut SentenceStream,
+        ref_data: &ReferenceData,
+        sampling_param: SamplingParams,
+        chunk_tokens: usize,
+        options: &SynthesizeOptions,
+        progress: SynthesizeProgress,
+    ) -> Result<Option<Vec<f32>>> {
+        let chunk_samples = chunk_tokens * SAMPLES_PER_TOKEN;
+
+        loop {
+            // The end is held back to be crossfaded with the next chunk or faded out
+            if sentence.pending.len() >= chunk_samples + STREAM_FADE_SAMPLES {
+                return Ok(Some(sentence.pending.drain(..chunk_samples).collect()));
+            }
+
+            if sentence.vocoded_all {
+                let pending = mem::take(&mut sentence.pending);
+                return Ok((!pending.is_empty()).then_some(pending));
+            }
+
+            let tokens = sentence.decoder.semantic_tokens();
+            if sentence.decoder.finished {
+                self.recycle_kv_caches(&mut sentence.decoder);
+                self.vocode_stream_window(sentence, &tokens, ref_data)
+                    .await?;
+                sentence.vocoded_all = true;
+
+                let len = sentence.pending.len();
+                fade_out(&mut sentence.pending[len - STREAM_FADE_SAMPLES.min(len)..]);
+                continue;
+            }
+
+            let new_samples =
+                tokens.len().saturating_sub(sentence.vocoded_tokens) * SAMPLES_PER_TOKEN;
+            if new_samples > 0
+                && sentence.pending.len() + new_samples >= chunk_samples + STREAM_FADE_SAMPLES
+            {
+                self.vocode_stream_window(sentence, &tokens, ref_data)
+                    .await?;
+            } else {
+                options.on_decoder_step(SynthesizeProgress {
+                    decoder_steps: sentence.decoder.idx,
+                    ..progress
+                })?;
+                self.run_t2s_s_decoder_step(&mut sentence.decoder, sampling_param)
+                    .await?;
+            }
+        }
+    }
+
+    /// Vocode the tokens after `vocoded_tokens` and append their audio to
+    /// the pending samples
+    async fn vocode_stream_window(
+        &mut self,
+        sentence: &mut SentenceStream,
+        tokens: &[i64],
+        ref_data: &ReferenceData,
+    ) -> Result<()> {
+        let context_start = sentence
+            .vocoded_tokens
+            .saturating_sub(self.stream_context_tokens);
+        let audio = self
+            .vocode(&sentence.text_seq, &tokens[context_start..], ref_data)
+            .await?;
+
+        // The audio length is proportional to the number of tokens
+        let start = audio.len() * (sentence.vocoded_tokens - context_start)
+            / (tokens.len() - context_start);
+        let fade = start.min(STREAM_FADE_SAMPLES).min(sentence.pending.len());
+
+        let len = sentence.pending.len();
+        crossfade(
+            &mut sentence.pending[len - fade..],
+            &audio[start - fade..start],
+        );
+        sentence.pending.extend_from_slice(&audio[start..]);
+
+        if sentence.vocoded_tokens == 0 {
+            let len = sentence.pending.len();
+            fade_in(&mut sentence.pending[..STREAM_FADE_SAMPLES.min(len)]);
+        }
+
+        sentence.vocoded_tokens = tokens.len();
+        Ok(())
+    }
 }
 
 async fn read_and_resample_audio(path: impl AsRef<Path>) -> Result<(Array2<f32>, Array2<f32>)> {
     let data = Cursor::new(read(path).await?);
     let decoder = Decoder::new(data)?;
     let sample_rate = decoder.sample_rate();
-    let samples = if decoder.channels() == 1 {
-        decoder.collect::<Vec<_>>()
-    } else {
-        UniformSourceIterator::new(decoder, 1, sample_rate).collect()
-    };
+    let channels = decoder.channels();
+    let samples = multi_to_mono(&decoder.collect::<Vec<_>>(), channels);
 
-    let ref_audio_16k = resample_audio(&samples, sample_rate, REFERENCE_AUDIO_SAMPLE_RATE);
-    let ref_audio_32k = resample_audio(&samples, sample_rate, OUTPUT_AUDIO_SAMPLE_RATE);
+    let ref_audio_16k = resample_audio(&samples, sample_rate, REFERENCE_AUDIO_SAMPLE_RATE)?;
+    let ref_audio_32k = resample_audio(&samples, sample_rate, OUTPUT_AUDIO_SAMPLE_RATE)?;
 
     Ok((
         Array2::from_shape_vec((1, ref_audio_16k.len()), ref_audio_16k)?,
@@ -449,12 +802,14 @@ async fn read_and_resample_audio(path: impl AsRef<Path>) -> Result<(Array2<f32>,
 }
 
 #[inline]
-fn resample_audio(input: &[f32], in_rate: u32, out_rate: u32) -> Vec<f32> {
-    if in_rate == out_rate {
-        return input.to_owned();
-    }
-
-    UniformSourceIterator::new(SamplesBuffer::new(1, in_rate, input), 1, out_rate).collect()
+pub(crate) fn resample_audio(input: &[f32], in_rate: u32, out_rate: u32) -> Result<Vec<f32>> {
+    Ok(resample(
+        input,
+        in_rate,
+        out_rate,
+        1,
+        ResampleQuality::High,
+    )?)
 }
 
 #[inline]
@@ -466,62 +821,85 @@ fn ensure_end_with_punctuation(text: &str) -> String {
     }
 }
 
+/// Copy the caches of the FS decoder at the start of large buffers, the
+/// buffers of `pool` are reused if they have the right shape
 fn initialize_kv_caches(
     fs_decoder_output: &SessionOutputs,
     num_layers: usize,
+    pool: Option<(Vec<KvCache>, Vec<KvCache>)>,
 ) -> Result<KvCacheTuple> {
     let k_init_first = fs_decoder_output["k_cache_0"].try_extract_array::<KvDType>()?;
     let initial_dims_dyn = k_init_first.raw_dim();
     let initial_seq_len = initial_dims_dyn[1];
 
     let mut large_cache_dims = initial_dims_dyn.clone();
-    large_cache_dims[1] = INITIAL_CACHE_SIZE;
+    large_cache_dims[1] = INITIAL_CACHE_SIZE.max(initial_seq_len + CACHE_REALLOC_INCREMENT);
+
+    let initial_shape = k_init_first.shape();
+    let reusable = |caches: &[KvCache]| {
+        caches.len() == num_layers
+            && caches.iter().all(|cache| {
+                let shape = cache.shape();
+                shape.len() == initial_shape.len()
+                    && shape[0] == initial_shape[0]
+                    && shape[1] > initial_seq_len
+                    && shape[2..] == initial_shape[2..]
+            })
+    };
 
-    let mut k_caches = Vec::with_capacity(num_layers);
-    let mut v_caches = Vec::with_capacity(num_layers);
+    let (mut k_caches, mut v_caches) = match pool {
+        Some((k_caches, v_caches)) if reusable(&k_caches) && reusable(&v_caches) => {
+            (k_caches, v_caches)
+        }
+        _ => (
+            vec![Array::zeros(large_cache_dims.clone()); num_layers],
+            vec![Array::zeros(large_cache_dims); num_layers],
+        ),
+    };
 
     for i in 0..num_layers {
         let k_init = fs_decoder_output[format!("k_cache_{}", i)].try_extract_array::<KvDType>()?;
         let v_init = fs_decoder_output[format!("v_cache_{}", i)].try_extract_array::<KvDType>()?;
 
-        let mut k_large = Array::zeros(large_cache_dims.clone());
-        let mut v_large = Array::zeros(large_cache_dims.clone());
-
-        k_large
+        k_caches[i]
             .slice_mut(s![.., 0..initial_seq_len, ..])
             .assign(&k_init);
-        v_large
+        v_caches[i]
             .slice_mut(s![.., 0..initial_seq_len, ..])
             .assign(&v_init);
-
-        k_caches.push(k_large);
-        v_caches.push(v_large);
     }
 
     Ok((k_caches, v_caches, initial_seq_len))
 }
 
-fn update_kv_cache(
-    k_caches: &mut [ArrayBase<OwnedRepr<KvDType>, IxDyn>],
-    v_caches: &mut [ArrayBase<OwnedRepr<KvDType>, IxDyn>],
+/// The valid part of a cache as a decoder input. It is a view of the buffer
+/// when the batch size is 1, so that nothing is copied.
+fn cache_input(cache: &KvCache, valid_len: usize) -> Result<SessionInputValue<'_>> {
+    let view = cache.slice(s![.., 0..valid_len, ..]);
+    if view.is_standard_layout() {
+        Ok(TensorRef::from_array_view(view)?.into())
+    } else {
+        Ok(Tensor::from_array(view.to_owned())?.into())
+    }
+}
+
+/// Key and value of the new token in every layer
+fn new_kv_rows(
     output: &SessionOutputs,
     valid_len: usize,
     num_layers: usize,
-) -> Result<()> {
-    for i in 0..num_layers {
-        let inc_k_cache = output[format!("k_cache_{}", i)].try_extract_array::<KvDType>()?;
-        let inc_v_cache = output[format!("v_cache_{}", i)].try_extract_array::<KvDType>()?;
-        let k_new_slice = inc_k_cache.slice(s![.., valid_len, ..]);
-        let v_new_slice = inc_v_cache.slice(s![.., valid_len, ..]);
-
-        k_caches[i]
-            .slice_mut(s![.., valid_len, ..])
-            .assign(&k_new_slice);
-        v_caches[i]
-            .slice_mut(s![.., valid_len, ..])
-            .assign(&v_new_slice);
-    }
-    Ok(())
+) -> Result<Vec<(Array2<KvDType>, Array2<KvDType>)>> {
+    (0..num_layers)
+        .map(|i| {
+            let inc_k_cache = output[format!("k_cache_{}", i)].try_extract_array::<KvDType>()?;
+            let inc_v_cache = output[format!("v_cache_{}", i)].try_extract_array::<KvDType>()?;
+
+            Ok((
+                inc_k_cache.slice(s![.., valid_len, ..]).to_owned(),
+                inc_v_cache.slice(s![.., valid_len, ..]).to_owned(),
+            ))
+        })
+        .collect()
 }
 
 fn apply_fade_in_out(audio: Vec<f32>, duration: Duration) -> Vec<f32> {
@@ -549,3 +927,26 @@ fn apply_fade_in_out(audio: Vec<f32>, duration: Duration) -> Vec<f32> {
 
     result
 }
+
+fn fade_in(audio: &mut [f32]) {
+    let len = audio.len();
+    for (i, sample) in audio.iter_mut().enumerate() {
+        *sample *= i as f32 / len as f32;
+    }
+}
+
+fn fade_out(audio: &mut [f32]) {
+    let len = audio.len();
+    for (i, sample) in audio.iter_mut().enumerate() {
+        *sample *= (len - i) as f32 / len as f32;
+    }
+}
+
+/// Linear crossfade from `tail` to `head`, written in `tail`
+fn crossfade(tail: &mut [f32], head: &[f32]) {
+    let len = tail.len();
+    for (i, (a, b)) in tail.iter_mut().zip(head).enumerate() {
+        let weight = (i + 1) as f32 / (len + 1) as f32;
+        *a = *a * (1.0 - weight) + b * weight;
+    }
+}
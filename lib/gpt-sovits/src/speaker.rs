@@ -0,0 +1,260 @@
+//! Named reference voices. Computing the SSL and BERT features of a
+//! reference takes seconds, so the bank saves them to disk to be loaded on
+//! the next start.
+
+use crate::{GSVError, ReferenceData, Result};
+use ndarray::{Array2, ArrayD, IxDyn};
+use std::{collections::BTreeMap, path::Path};
+use tokio::fs;
+
+const MAGIC: &[u8; 8] = b"GSVSPKR1";
+
+#[derive(Clone, Default)]
+pub struct SpeakerBank {
+    speakers: BTreeMap<String, ReferenceData>,
+}
+
+impl SpeakerBank {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&fs::read(path).await?)
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        fs::write(path, self.to_bytes()).await?;
+        Ok(())
+    }
+
+    /// Add or replace a speaker, returning the previous reference data
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        data: ReferenceData,
+    ) -> Option<ReferenceData> {
+        self.speakers.insert(name.into(), data)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ReferenceData> {
+        self.speakers.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&ReferenceData> {
+        self.speakers
+            .get(name)
+            .ok_or_else(|| GSVError::SpeakerNotFound(name.to_string()))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.speakers.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.speakers.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.speakers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.speakers.is_empty()
+    }
+
+    // Little endian: magic, speaker count, then the name and the arrays of
+    // each speaker
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = MAGIC.to_vec();
+        write_u64(&mut buf, self.speakers.len() as u64);
+
+        for (name, data) in &self.speakers {
+            write_u64(&mut buf, name.len() as u64);
+            buf.extend_from_slice(name.as_bytes());
+
+            write_array(&mut buf, data.ref_seq.shape(), data.ref_seq.iter());
+            write_array(&mut buf, data.ref_bert.shape(), data.ref_bert.iter());
+            write_array(
+                &mut buf,
+                data.ref_audio_32k.shape(),
+                data.ref_audio_32k.iter(),
+            );
+            write_array(&mut buf, data.ssl_content.shape(), data.ssl_content.iter());
+        }
+
+        buf
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(GSVError::InvalidSpeakerBank("bad magic".to_string()));
+        }
+
+        let mut speakers = BTreeMap::new();
+        for _ in 0..reader.u64()? {
+            let len = reader.u64()? as usize;
+            let name = String::from_utf8(reader.take(len)?.to_vec())
+                .map_err(|e| GSVError::InvalidSpeakerBank(e.to_string()))?;
+
+            let (shape, data) = reader.array::<i64>()?;
+            let ref_seq = Array2::from_shape_vec(shape_2d(&shape)?, data)?;
+            let (shape, data) = reader.array::<f32>()?;
+            let ref_bert = Array2::from_shape_vec(shape_2d(&shape)?, data)?;
+            let (shape, data) = reader.array::<f32>()?;
+            let ref_audio_32k = Array2::from_shape_vec(shape_2d(&shape)?, data)?;
+            let (shape, data) = reader.array::<f32>()?;
+            let ssl_content = ArrayD::from_shape_vec(IxDyn(&shape), data)?;
+
+            speakers.insert(
+                name,
+                ReferenceData {
+                    ref_seq,
+                    ref_bert,
+                    ref_audio_32k,
+                    ssl_content,
+                },
+            );
+        }
+
+        Ok(Self { speakers })
+    }
+}
+
+trait Element: Copy {
+    const SIZE: usize;
+
+    fn write(self, buf: &mut Vec<u8>);
+    fn read(bytes: &[u8]) -> Self;
+}
+
+impl Element for i64 {
+    const SIZE: usize = 8;
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        Self::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+impl Element for f32 {
+    const SIZE: usize = 4;
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        Self::from_le_bytes(bytes.try_into().unwrap())
+    }
+}
+
+fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_array<'a, T: Element + 'a>(
+    buf: &mut Vec<u8>,
+    shape: &[usize],
+    data: impl Iterator<Item = &'a T>,
+) {
+    write_u64(buf, shape.len() as u64);
+    for &dim in shape {
+        write_u64(buf, dim as u64);
+    }
+
+    for &value in data {
+        value.write(buf);
+    }
+}
+
+fn shape_2d(shape: &[usize]) -> Result<(usize, usize)> {
+    match shape {
+        &[rows, cols] => Ok((rows, cols)),
+        _ => Err(GSVError::InvalidSpeakerBank(format!(
+            "expected a 2D array, found shape {shape:?}"
+        ))),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| GSVError::InvalidSpeakerBank("unexpected end of data".to_string()))?;
+
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn array<T: Element>(&mut self) -> Result<(Vec<usize>, Vec<T>)> {
+        let ndim = self.u64()? as usize;
+        let shape = (0..ndim)
+            .map(|_| self.u64().map(|dim| dim as usize))
+            .collect::<Result<Vec<_>>>()?;
+
+        let len = shape
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .and_then(|len| len.checked_mul(T::SIZE))
+            .ok_or_else(|| GSVError::InvalidSpeakerBank(format!("invalid shape {shape:?}")))?;
+
+        let data = self.take(len)?.chunks_exact(T::SIZE).map(T::read).collect();
+        Ok((shape, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array;
+
+    #[test]
+    fn test_round_trip() {
+        let data = ReferenceData {
+            ref_seq: Array2::from_shape_vec((1, 3), vec![1, -2, 3]).unwrap(),
+            ref_bert: Array2::from_shape_fn((3, 4), |(i, j)| (i * 4 + j) as f32 * 0.5),
+            ref_audio_32k: Array2::from_shape_vec((1, 2), vec![0.25, -0.75]).unwrap(),
+            ssl_content: Array::from_elem(IxDyn(&[1, 2, 3]), 1.5),
+        };
+
+        let mut bank = SpeakerBank::new();
+        bank.insert("narrator", data.clone());
+        bank.insert("讲述者", data);
+
+        let loaded = SpeakerBank::from_bytes(&bank.to_bytes()).unwrap();
+        assert_eq!(loaded.names().collect::<Vec<_>>(), ["narrator", "讲述者"]);
+
+        let data = loaded.get("讲述者").unwrap();
+        assert_eq!(data.ref_seq.as_slice().unwrap(), [1, -2, 3]);
+        assert_eq!(data.ref_bert[[2, 3]], 5.5);
+        assert_eq!(data.ref_audio_32k.as_slice().unwrap(), [0.25, -0.75]);
+        assert_eq!(data.ssl_content.shape(), [1, 2, 3]);
+
+        assert!(matches!(
+            loaded.get("unknown"),
+            Err(GSVError::SpeakerNotFound(_))
+        ));
+        assert!(SpeakerBank::from_bytes(&bank.to_bytes()[..20]).is_err());
+    }
+}
@@ -5,11 +5,11 @@ mod phone_symbol;
 mod utils;
 mod zh;
 
-use crate::{GSVError, Result};
+use crate::{GSVError, Prosody, Result, TextSegment, parse_control_tags};
 use jieba_rs::Jieba;
 use ndarray::Array2;
 use regex::Regex;
-use std::sync::LazyLock;
+use std::{sync::LazyLock, time::Duration};
 use unicode_segmentation::UnicodeSegmentation;
 
 pub use bert::BertModel;
@@ -45,6 +45,17 @@ pub enum Lang {
     En,
 }
 
+#[derive(Debug, Clone)]
+pub enum SpeechItem {
+    Sentence {
+        text: String,
+        phone_ids: Vec<i64>,
+        bert: Array2<f32>,
+        prosody: Prosody,
+    },
+    Break(Duration),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum LangId {
     Auto,    // Mandarin
@@ -128,6 +139,38 @@ impl TextProcessor {
         })
     }
 
+    /// Sentences and pauses of a text with control tags, see `parse_control_tags`
+    pub fn get_speech_items(&mut self, text: &str, lang_id: LangId) -> Result<Vec<SpeechItem>> {
+        let mut items = vec![];
+
+        for segment in parse_control_tags(text) {
+            match segment {
+                TextSegment::Break(duration) => items.push(SpeechItem::Break(duration)),
+                TextSegment::Text { text, prosody } => {
+                    // Nothing to pronounce, e.g. the spaces between two tags
+                    if !text.chars().any(char::is_alphanumeric) {
+                        continue;
+                    }
+
+                    for (text, phone_ids, bert) in self.get_phone_and_bert(&text, lang_id)? {
+                        items.push(SpeechItem::Sentence {
+                            text,
+                            phone_ids,
+                            bert,
+                            prosody,
+                        });
+                    }
+                }
+            }
+        }
+
+        if items.is_empty() {
+            return Err(GSVError::InputEmpty);
+        }
+
+        Ok(items)
+    }
+
     pub fn get_phone_and_bert(
         &mut self,
         text: &str,
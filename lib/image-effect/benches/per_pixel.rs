@@ -0,0 +1,100 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use image::RgbaImage;
+use image_effect::{
+    Effect, EffectChain, ImageEffect,
+    colour_space::{GammaCorrectionConfig, SaturationConfig},
+    filter::ColorTintConfig,
+    monochrome::GrayscaleConfig,
+    special::{BrightnessConfig, ContrastConfig},
+};
+use photon_rs::{PhotonImage, channels, colour_spaces, effects, monochrome};
+use std::hint::black_box;
+
+// 4K screenshot
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+
+type PhotonFn = Box<dyn Fn(&mut PhotonImage)>;
+
+fn test_image() -> RgbaImage {
+    RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        image::Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255])
+    })
+}
+
+// The single threaded photon-rs implementations used before
+fn photon(image: RgbaImage, f: impl Fn(&mut PhotonImage)) -> RgbaImage {
+    let mut photon_img = PhotonImage::new(image.into_raw(), WIDTH, HEIGHT);
+    f(&mut photon_img);
+    RgbaImage::from_raw(WIDTH, HEIGHT, photon_img.get_raw_pixels()).unwrap()
+}
+
+fn per_pixel(c: &mut Criterion) {
+    let image = test_image();
+
+    let cases: Vec<(&str, ImageEffect, PhotonFn)> = vec![
+        (
+            "brightness",
+            ImageEffect::Brightness(BrightnessConfig::new().with_brightness(30)),
+            Box::new(|img| effects::adjust_brightness(img, 30)),
+        ),
+        (
+            "contrast",
+            ImageEffect::Contrast(ContrastConfig::new().with_contrast(20.0)),
+            Box::new(|img| effects::adjust_contrast(img, 20.0)),
+        ),
+        ("invert", ImageEffect::Invert, Box::new(channels::invert)),
+        (
+            "grayscale",
+            ImageEffect::Grayscale(GrayscaleConfig::new()),
+            Box::new(monochrome::grayscale_human_corrected),
+        ),
+        (
+            "tint",
+            ImageEffect::ColorTint(ColorTintConfig::from_rgb(90, 60, 30)),
+            Box::new(|img| effects::tint(img, 30, 20, 10)),
+        ),
+        (
+            "gamma",
+            ImageEffect::GammaCorrection(GammaCorrectionConfig::new()),
+            Box::new(|img| colour_spaces::gamma_correction(img, 2.2, 2.2, 2.2)),
+        ),
+        (
+            "saturation",
+            ImageEffect::Saturation(SaturationConfig::new().with_amount(0.3)),
+            Box::new(|img| colour_spaces::saturate_hsl(img, 0.3)),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("per_pixel_4k");
+    group.sample_size(10);
+
+    for (name, effect, reference) in &cases {
+        group.bench_function(BenchmarkId::new("photon", name), |b| {
+            b.iter(|| black_box(photon(image.clone(), reference)))
+        });
+        group.bench_function(BenchmarkId::new("parallel", name), |b| {
+            b.iter(|| black_box(effect.apply(image.clone())))
+        });
+    }
+
+    let chain = EffectChain::new()
+        .with_effect(ImageEffect::Brightness(
+            BrightnessConfig::new().with_brightness(30),
+        ))
+        .with_effect(ImageEffect::Contrast(
+            ContrastConfig::new().with_contrast(20.0),
+        ))
+        .with_effect(ImageEffect::ColorTint(ColorTintConfig::from_rgb(
+            90, 60, 30,
+        )));
+
+    group.bench_function("fused_chain/brightness_contrast_tint", |b| {
+        b.iter(|| black_box(chain.apply(image.clone())))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, per_pixel);
+criterion_main!(benches);
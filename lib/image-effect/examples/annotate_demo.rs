@@ -0,0 +1,62 @@
+use image::{ImageReader, Rgba};
+use image_effect::annotate::{
+    Annotation, Arrow, Ellipse, Font, Highlight, Rectangle, StepBadge, Text,
+};
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = Path::new("tmp");
+    std::fs::create_dir_all(output_dir)?;
+
+    let img_path = Path::new("data/test.png");
+    let mut img = ImageReader::open(img_path)?.decode()?.to_rgba8();
+
+    // Use the given font or the one bundled with the app
+    let font_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "../../wayshot/ui/fonts/SourceHanSansCN.otf".to_string());
+    let font = Font::load(font_path)?;
+
+    let annotations = [
+        Annotation::Highlight(Highlight::new(60.0, 60.0, 300.0, 40.0)),
+        Annotation::Rectangle(
+            Rectangle::new(420.0, 60.0, 300.0, 160.0)
+                .with_stroke_width(4.0)
+                .with_corner_radius(12.0),
+        ),
+        Annotation::Ellipse(
+            Ellipse::new(80.0, 200.0, 260.0, 140.0).with_color(Rgba([30, 120, 230, 255])),
+        ),
+        Annotation::Rectangle(
+            Rectangle::new(440.0, 300.0, 280.0, 120.0)
+                .with_color(Rgba([0, 0, 0, 128]))
+                .with_filled(true),
+        ),
+        Annotation::Arrow(Arrow::new((380.0, 500.0), (560.0, 420.0)).with_width(6.0)),
+        Annotation::StepBadge(StepBadge::new((400.0, 520.0), 1, font.clone())),
+        Annotation::StepBadge(StepBadge::new((60.0, 400.0), 12, font.clone()).with_radius(24.0)),
+        Annotation::Text(
+            Text::new((460.0, 330.0), "Click here\n点击这里", font.clone())
+                .with_size(28.0)
+                .with_color(Rgba([255, 255, 255, 255])),
+        ),
+        Annotation::Text(
+            Text::new((80.0, 480.0), "Annotated", font)
+                .with_size(32.0)
+                .with_color(Rgba([20, 20, 20, 255]))
+                .with_background(Some(Rgba([255, 255, 255, 200]))),
+        ),
+    ];
+
+    for annotation in &annotations {
+        annotation.draw(&mut img);
+    }
+
+    img.save(output_dir.join("annotate.png"))?;
+    println!("✓ Generated annotate.png");
+
+    println!("\n✓ All annotations drawn successfully!");
+    println!("  Images saved to: tmp/");
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use image::ImageReader;
+use image_effect::{
+    Effect, EffectChain, ImageEffect,
+    blur::GaussianBlurConfig,
+    filter::{ColorTintConfig, VignetteConfig},
+    special::{BrightnessConfig, ContrastConfig},
+};
+use std::{path::Path, time::Instant};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = Path::new("tmp");
+    std::fs::create_dir_all(output_dir)?;
+
+    let img_path = Path::new("data/test.png");
+    let img = ImageReader::open(img_path)?.decode()?.to_rgba8();
+
+    let chain = EffectChain::new()
+        .with_effect(ImageEffect::Brightness(
+            BrightnessConfig::new().with_brightness(20),
+        ))
+        .with_effect(ImageEffect::Contrast(
+            ContrastConfig::new().with_contrast(30.0),
+        ))
+        .with_effect(ImageEffect::ColorTint(ColorTintConfig::from_rgb(60, 30, 0)))
+        .with_effect(ImageEffect::GaussianBlur(
+            GaussianBlurConfig::new().with_radius(2),
+        ))
+        .with_effect(ImageEffect::Vignette(VignetteConfig::new()));
+
+    let start = Instant::now();
+    let fused = chain.apply(img.clone()).expect("Effect chain failed");
+    println!("✓ Effect chain: {:.2?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut unfused = img;
+    for effect in chain.effects() {
+        unfused = effect.apply(unfused).expect("Effect failed");
+    }
+    println!("✓ One effect at a time: {:.2?}", start.elapsed());
+
+    fused.save(output_dir.join("effect_chain.png"))?;
+    unfused.save(output_dir.join("effect_chain_unfused.png"))?;
+
+    let preset = output_dir.join("effect_chain.json");
+    chain.save(&preset)?;
+    let loaded = EffectChain::load(&preset)?;
+    println!("✓ Saved and loaded preset with {} effects", loaded.len());
+
+    println!("\n✓ Effect chain applied successfully!");
+    println!("  Images saved to: tmp/");
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use image::{ImageReader, imageops::FilterType};
+use image_effect::blur::GaussianBlurConfig;
+use image_effect::monochrome::GrayscaleConfig;
+use image_effect::stylized::SharpenConfig;
+use image_effect::{Effect, ImageEffect, gpu};
+use std::{path::Path, time::Instant};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let output_dir = Path::new("tmp");
+    std::fs::create_dir_all(output_dir)?;
+
+    // 4K frame
+    let img = ImageReader::open("data/test.png")?.decode()?;
+    let img = img
+        .resize_exact(3840, 2160, FilterType::Triangle)
+        .to_rgba8();
+
+    println!("GPU adapter: {:?}", gpu::adapter_name());
+
+    let effects = [
+        (
+            "gaussian_blur",
+            ImageEffect::GaussianBlur(GaussianBlurConfig::new().with_radius(8)),
+        ),
+        ("sharpen", ImageEffect::Sharpen(SharpenConfig::new())),
+        ("grayscale", ImageEffect::Grayscale(GrayscaleConfig::new())),
+    ];
+
+    for (name, effect) in effects {
+        for enabled in [false, true] {
+            gpu::set_enabled(enabled);
+            let backend = if enabled { "gpu" } else { "cpu" };
+
+            let now = Instant::now();
+            let output = effect.apply(img.clone()).expect("Effect failed");
+            println!("{name} ({backend}): {:.2?}", now.elapsed());
+
+            output.save(output_dir.join(format!("{name}_{backend}.png")))?;
+        }
+    }
+
+    println!("\n✓ Images saved to: tmp/");
+
+    Ok(())
+}
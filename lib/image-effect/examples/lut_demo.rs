@@ -0,0 +1,63 @@
+use image::ImageReader;
+use image_effect::{Effect, Lut3d, LutEffect};
+use std::{fmt::Write, path::Path};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = Path::new("tmp");
+    std::fs::create_dir_all(output_dir)?;
+
+    let img_path = Path::new("data/test.png");
+    let img = ImageReader::open(img_path)?.decode()?.to_rgba8();
+
+    // Use the given .cube file or generate a warm grade
+    let lut = match std::env::args().nth(1) {
+        Some(path) => Lut3d::load(path)?,
+        None => {
+            let size = 17;
+            let max = (size - 1) as f32;
+            let mut cube = String::from("TITLE \"Warm\"\nLUT_3D_SIZE 17\n");
+            for b in 0..size {
+                for g in 0..size {
+                    for r in 0..size {
+                        let (r, g, b) = (r as f32 / max, g as f32 / max, b as f32 / max);
+                        writeln!(
+                            cube,
+                            "{:.6} {:.6} {:.6}",
+                            (r * 1.1).min(1.0),
+                            g.powf(0.95),
+                            b * 0.85
+                        )?;
+                    }
+                }
+            }
+
+            let path = output_dir.join("warm.cube");
+            std::fs::write(&path, cube)?;
+            Lut3d::load(path)?
+        }
+    };
+
+    println!(
+        "✓ Loaded LUT {} ({}x{0}x{0})",
+        lut.title().unwrap_or("untitled"),
+        lut.size()
+    );
+
+    let effect = LutEffect::new(lut);
+    for intensity in [0.5, 1.0] {
+        let output = effect
+            .clone()
+            .with_intensity(intensity)
+            .apply(img.clone())
+            .expect("Effect failed");
+
+        let filename = format!("lut_{intensity}.png");
+        output.save(output_dir.join(&filename))?;
+        println!("✓ Generated {}", filename);
+    }
+
+    println!("\n✓ LUT applied successfully!");
+    println!("  Images saved to: tmp/");
+
+    Ok(())
+}
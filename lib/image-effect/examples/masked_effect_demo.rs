@@ -0,0 +1,78 @@
+use image::{GrayImage, ImageReader, Luma};
+use image_effect::{
+    Effect, EffectRegion, ImageEffect, MaskedEffect, blur::GaussianBlurConfig,
+    monochrome::GrayscaleConfig, stylized::PixelateConfig,
+};
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output_dir = Path::new("tmp");
+    std::fs::create_dir_all(output_dir)?;
+
+    let img_path = Path::new("data/test.png");
+    let img = ImageReader::open(img_path)?.decode()?.to_rgba8();
+    let (width, height) = img.dimensions();
+
+    let blur = ImageEffect::GaussianBlur(GaussianBlurConfig::new().with_radius(12));
+    let effects = [
+        (
+            "masked_rect_blur.png",
+            MaskedEffect::new(
+                blur.clone(),
+                EffectRegion::Rect {
+                    x: width as i32 / 4,
+                    y: height as i32 / 4,
+                    width: width / 2,
+                    height: height / 4,
+                },
+            )
+            .with_feather(16.0),
+        ),
+        (
+            "masked_ellipse_pixelate.png",
+            MaskedEffect::new(
+                ImageEffect::Pixelate(PixelateConfig::new().with_block_size(16)),
+                EffectRegion::Ellipse {
+                    x: width as i32 / 3,
+                    y: height as i32 / 3,
+                    width: width / 3,
+                    height: height / 3,
+                },
+            ),
+        ),
+        (
+            "masked_outside_grayscale.png",
+            MaskedEffect::new(
+                ImageEffect::Grayscale(GrayscaleConfig::new()),
+                EffectRegion::Ellipse {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                },
+            )
+            .with_feather(40.0)
+            .with_inverted(true),
+        ),
+        (
+            "masked_gradient_blur.png",
+            MaskedEffect::new(
+                blur,
+                EffectRegion::Mask(GrayImage::from_fn(width, height, |x, _| {
+                    Luma([(x * 255 / width) as u8])
+                })),
+            ),
+        ),
+    ];
+
+    for (filename, effect) in effects {
+        let output = effect.apply(img.clone()).expect("Effect failed");
+        output.save(output_dir.join(filename))?;
+        println!("✓ Generated {}", filename);
+    }
+
+    println!("\n✓ All masked effects applied successfully!");
+    println!("  Images saved to: tmp/");
+
+    Ok(())
+}
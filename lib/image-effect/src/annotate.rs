@@ -0,0 +1,560 @@
+//! Screenshot annotations: arrows, rectangles, ellipses, highlights,
+//! numbered step badges and text, drawn anti-aliased onto an `RgbaImage`.
+//!
+//! Shapes are rasterized from their signed distance to the outline, so the
+//! strokes have any width and translucent colors are blended only once.
+
+use crate::Effect;
+use ab_glyph::{Font as _, FontArc, GlyphId, PxScale, ScaleFont, point};
+use derive_setters::Setters;
+use image::{Pixel, Rgba, RgbaImage};
+use rayon::prelude::*;
+use std::{fs, path::Path};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AnnotateError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Invalid font: {0}")]
+    InvalidFont(String),
+}
+
+pub type AnnotateResult<T> = Result<T, AnnotateError>;
+
+/// TrueType or OpenType font for the text annotations
+#[derive(Debug, Clone)]
+pub struct Font(FontArc);
+
+impl Font {
+    pub fn load(path: impl AsRef<Path>) -> AnnotateResult<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> AnnotateResult<Self> {
+        FontArc::try_from_vec(data)
+            .map(Self)
+            .map_err(|e| AnnotateError::InvalidFont(e.to_string()))
+    }
+
+    /// Width and height of the text laid out at `size` pixels
+    pub fn text_size(&self, text: &str, size: f32) -> (f32, f32) {
+        let font = self.0.as_scaled(PxScale::from(size));
+        let lines = text.lines().collect::<Vec<_>>();
+
+        let width = lines
+            .iter()
+            .map(|line| layout_line(&font, line).1)
+            .fold(0.0, f32::max);
+        let height = font.height() * lines.len().max(1) as f32
+            + font.line_gap() * lines.len().saturating_sub(1) as f32;
+
+        (width, height)
+    }
+
+    fn draw_text(
+        &self,
+        image: &mut RgbaImage,
+        text: &str,
+        position: (f32, f32),
+        size: f32,
+        color: Rgba<u8>,
+    ) {
+        let scale = PxScale::from(size);
+        let font = self.0.as_scaled(scale);
+        let mut baseline = position.1 + font.ascent();
+
+        for line in text.lines() {
+            for (id, offset) in layout_line(&font, line).0 {
+                let glyph = id.with_scale_and_position(scale, point(position.0 + offset, baseline));
+
+                let Some(outline) = self.0.outline_glyph(glyph) else {
+                    continue;
+                };
+
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x as i64 + gx as i64;
+                    let py = bounds.min.y as i64 + gy as i64;
+                    if px >= 0 && py >= 0 && px < image.width() as i64 && py < image.height() as i64
+                    {
+                        blend(image.get_pixel_mut(px as u32, py as u32), color, coverage);
+                    }
+                });
+            }
+
+            baseline += font.height() + font.line_gap();
+        }
+    }
+}
+
+/// Glyphs of the line with their horizontal offset, and the line width
+fn layout_line<F: ab_glyph::Font>(
+    font: &impl ScaleFont<F>,
+    line: &str,
+) -> (Vec<(GlyphId, f32)>, f32) {
+    let mut caret = 0.0;
+    let mut previous = None;
+
+    let glyphs = line
+        .chars()
+        .map(|c| {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += font.kern(previous, id);
+            }
+            let offset = caret;
+            caret += font.h_advance(id);
+            previous = Some(id);
+            (id, offset)
+        })
+        .collect();
+
+    (glyphs, caret)
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Arrow {
+    #[setters(skip)]
+    start: (f32, f32),
+
+    #[setters(skip)]
+    end: (f32, f32),
+
+    color: Rgba<u8>,
+    width: f32,
+
+    /// Length of the head, its width is a bit smaller
+    head_size: f32,
+}
+
+impl Arrow {
+    pub fn new(start: (f32, f32), end: (f32, f32)) -> Self {
+        Self {
+            start,
+            end,
+            color: Rgba([230, 30, 30, 255]),
+            width: 4.0,
+            head_size: 18.0,
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (dx, dy) = (self.end.0 - self.start.0, self.end.1 - self.start.1);
+        let length = dx.hypot(dy);
+        if length <= 0.0 {
+            return;
+        }
+
+        let (ux, uy) = (dx / length, dy / length);
+        let head = self.head_size.min(length);
+        let base = (self.end.0 - ux * head, self.end.1 - uy * head);
+        let half = head * 0.6;
+        let left = (base.0 - uy * half, base.1 + ux * half);
+        let right = (base.0 + uy * half, base.1 - ux * half);
+
+        // Stop the shaft inside the head so that its end does not show
+        let shaft_end = (self.end.0 - ux * head * 0.5, self.end.1 - uy * head * 0.5);
+        let half_width = self.width / 2.0;
+
+        let margin = half.max(half_width);
+        let bounds = (
+            self.start.0.min(self.end.0) - margin,
+            self.start.1.min(self.end.1) - margin,
+            self.start.0.max(self.end.0) + margin,
+            self.start.1.max(self.end.1) + margin,
+        );
+
+        fill(image, bounds, self.color, |p| {
+            let shaft = if head < length {
+                segment_distance(p, self.start, shaft_end) - half_width
+            } else {
+                f32::MAX
+            };
+            shaft.min(triangle_distance(p, [self.end, left, right]))
+        });
+    }
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Rectangle {
+    #[setters(skip)]
+    rect: (f32, f32, f32, f32),
+
+    color: Rgba<u8>,
+    stroke_width: f32,
+    corner_radius: f32,
+    filled: bool,
+}
+
+impl Rectangle {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            rect: (x, y, width, height),
+            color: Rgba([230, 30, 30, 255]),
+            stroke_width: 3.0,
+            corner_radius: 0.0,
+            filled: false,
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (x, y, width, height) = self.rect;
+        let center = (x + width / 2.0, y + height / 2.0);
+        let half = (width / 2.0, height / 2.0);
+        let radius = self.corner_radius.clamp(0.0, half.0.min(half.1));
+
+        let bounds = (
+            x - self.stroke_width,
+            y - self.stroke_width,
+            x + width + self.stroke_width,
+            y + height + self.stroke_width,
+        );
+
+        fill(image, bounds, self.color, |p| {
+            let d = box_distance(p, center, half, radius);
+            if self.filled {
+                d
+            } else {
+                d.abs() - self.stroke_width / 2.0
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Ellipse {
+    /// Bounding box of the ellipse
+    #[setters(skip)]
+    rect: (f32, f32, f32, f32),
+
+    color: Rgba<u8>,
+    stroke_width: f32,
+    filled: bool,
+}
+
+impl Ellipse {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            rect: (x, y, width, height),
+            color: Rgba([230, 30, 30, 255]),
+            stroke_width: 3.0,
+            filled: false,
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (x, y, width, height) = self.rect;
+        let center = (x + width / 2.0, y + height / 2.0);
+        let radius = (width / 2.0, height / 2.0);
+        if radius.0 <= 0.0 || radius.1 <= 0.0 {
+            return;
+        }
+
+        let bounds = (
+            x - self.stroke_width,
+            y - self.stroke_width,
+            x + width + self.stroke_width,
+            y + height + self.stroke_width,
+        );
+
+        fill(image, bounds, self.color, |p| {
+            let d = ellipse_distance(p, center, radius);
+            if self.filled {
+                d
+            } else {
+                d.abs() - self.stroke_width / 2.0
+            }
+        });
+    }
+}
+
+/// Highlighter pen: multiplies the pixels by the color so that dark text
+/// stays readable
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Highlight {
+    #[setters(skip)]
+    rect: (f32, f32, f32, f32),
+
+    color: Rgba<u8>,
+}
+
+impl Highlight {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            rect: (x, y, width, height),
+            color: Rgba([255, 230, 0, 255]),
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (x, y, width, height) = self.rect;
+        let center = (x + width / 2.0, y + height / 2.0);
+        let half = (width / 2.0, height / 2.0);
+        let alpha = self.color[3] as f32 / 255.0;
+
+        for_each_covered(image, (x, y, x + width, y + height), |p, pixel| {
+            let coverage = (0.5 - box_distance(p, center, half, 0.0)).clamp(0.0, 1.0) * alpha;
+            for i in 0..3 {
+                let factor = 1.0 - coverage + coverage * self.color[i] as f32 / 255.0;
+                pixel[i] = (pixel[i] as f32 * factor).round() as u8;
+            }
+        });
+    }
+}
+
+/// Filled circle with a number, to mark the steps of a tutorial
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct StepBadge {
+    #[setters(skip)]
+    center: (f32, f32),
+
+    #[setters(skip)]
+    number: u32,
+
+    #[setters(skip)]
+    font: Font,
+
+    radius: f32,
+    color: Rgba<u8>,
+    text_color: Rgba<u8>,
+}
+
+impl StepBadge {
+    pub fn new(center: (f32, f32), number: u32, font: Font) -> Self {
+        Self {
+            center,
+            number,
+            font,
+            radius: 16.0,
+            color: Rgba([230, 30, 30, 255]),
+            text_color: Rgba([255, 255, 255, 255]),
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        let (cx, cy) = self.center;
+        let r = self.radius;
+
+        fill(image, (cx - r, cy - r, cx + r, cy + r), self.color, |p| {
+            (p.0 - cx).hypot(p.1 - cy) - r
+        });
+
+        let text = self.number.to_string();
+        let size = r * if text.len() > 2 { 0.9 } else { 1.2 };
+        let (width, height) = self.font.text_size(&text, size);
+        self.font.draw_text(
+            image,
+            &text,
+            (cx - width / 2.0, cy - height / 2.0),
+            size,
+            self.text_color,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Text {
+    /// Top-left corner of the text
+    #[setters(skip)]
+    position: (f32, f32),
+
+    #[setters(skip)]
+    text: String,
+
+    #[setters(skip)]
+    font: Font,
+
+    size: f32,
+    color: Rgba<u8>,
+
+    /// Rounded box drawn behind the text
+    background: Option<Rgba<u8>>,
+    padding: f32,
+}
+
+impl Text {
+    pub fn new(position: (f32, f32), text: impl Into<String>, font: Font) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            font,
+            size: 24.0,
+            color: Rgba([230, 30, 30, 255]),
+            background: None,
+            padding: 6.0,
+        }
+    }
+
+    pub fn draw(&self, image: &mut RgbaImage) {
+        if let Some(background) = self.background {
+            let (width, height) = self.font.text_size(&self.text, self.size);
+            Rectangle::new(
+                self.position.0 - self.padding,
+                self.position.1 - self.padding,
+                width + self.padding * 2.0,
+                height + self.padding * 2.0,
+            )
+            .with_color(background)
+            .with_corner_radius(self.padding)
+            .with_filled(true)
+            .draw(image);
+        }
+
+        self.font
+            .draw_text(image, &self.text, self.position, self.size, self.color);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Annotation {
+    Arrow(Arrow),
+    Rectangle(Rectangle),
+    Ellipse(Ellipse),
+    Highlight(Highlight),
+    StepBadge(StepBadge),
+    Text(Text),
+}
+
+impl Annotation {
+    pub fn draw(&self, image: &mut RgbaImage) {
+        match self {
+            Annotation::Arrow(arrow) => arrow.draw(image),
+            Annotation::Rectangle(rectangle) => rectangle.draw(image),
+            Annotation::Ellipse(ellipse) => ellipse.draw(image),
+            Annotation::Highlight(highlight) => highlight.draw(image),
+            Annotation::StepBadge(badge) => badge.draw(image),
+            Annotation::Text(text) => text.draw(image),
+        }
+    }
+}
+
+impl Effect for Annotation {
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        self.draw(&mut image);
+        Some(image)
+    }
+}
+
+/// Source-over blending of `color` with `coverage` in [0, 1]
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let alpha = color[3] as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+
+    for i in 0..3 {
+        pixel[i] = (color[i] as f32 * alpha + pixel[i] as f32 * (1.0 - alpha)).round() as u8;
+    }
+    pixel[3] = (255.0 * alpha + pixel[3] as f32 * (1.0 - alpha)).round() as u8;
+}
+
+/// Call `f` with the center of each pixel in `bounds` (min x, min y, max x,
+/// max y), one more pixel around it for the anti-aliasing
+fn for_each_covered(
+    image: &mut RgbaImage,
+    bounds: (f32, f32, f32, f32),
+    f: impl Fn((f32, f32), &mut Rgba<u8>) + Sync,
+) {
+    let (width, height) = image.dimensions();
+    let clamp = |v: f32, max: u32| v.clamp(0.0, max as f32) as u32;
+    let (x0, y0) = (
+        clamp(bounds.0.floor() - 1.0, width),
+        clamp(bounds.1.floor() - 1.0, height),
+    );
+    let (x1, y1) = (
+        clamp(bounds.2.ceil() + 1.0, width),
+        clamp(bounds.3.ceil() + 1.0, height),
+    );
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+
+    image
+        .par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .skip(y0 as usize)
+        .take((y1 - y0) as usize)
+        .for_each(|(y, row)| {
+            let (pixels, _) = row.as_chunks_mut::<4>();
+            for x in x0..x1 {
+                let pixel = Rgba::from_slice_mut(&mut pixels[x as usize]);
+                f((x as f32 + 0.5, y as f32 + 0.5), pixel);
+            }
+        });
+}
+
+/// Fill the area where `distance` is negative
+fn fill(
+    image: &mut RgbaImage,
+    bounds: (f32, f32, f32, f32),
+    color: Rgba<u8>,
+    distance: impl Fn((f32, f32)) -> f32 + Sync,
+) {
+    for_each_covered(image, bounds, |p, pixel| {
+        let coverage = 0.5 - distance(p);
+        if coverage > 0.0 {
+            blend(pixel, color, coverage);
+        }
+    });
+}
+
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (pa, ba) = ((p.0 - a.0, p.1 - a.1), (b.0 - a.0, b.1 - a.1));
+    let length = ba.0 * ba.0 + ba.1 * ba.1;
+    let t = if length > 0.0 {
+        ((pa.0 * ba.0 + pa.1 * ba.1) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (pa.0 - ba.0 * t).hypot(pa.1 - ba.1 * t)
+}
+
+/// Signed distance, negative inside
+fn triangle_distance(p: (f32, f32), [a, b, c]: [(f32, f32); 3]) -> f32 {
+    let distance = segment_distance(p, a, b)
+        .min(segment_distance(p, b, c))
+        .min(segment_distance(p, c, a));
+
+    let cross =
+        |o: (f32, f32), u: (f32, f32)| (u.0 - o.0) * (p.1 - o.1) - (u.1 - o.1) * (p.0 - o.0);
+    let (d1, d2, d3) = (cross(a, b), cross(b, c), cross(c, a));
+    let inside = (d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0) || (d1 <= 0.0 && d2 <= 0.0 && d3 <= 0.0);
+
+    if inside { -distance } else { distance }
+}
+
+/// Signed distance to a box with rounded corners
+fn box_distance(p: (f32, f32), center: (f32, f32), half: (f32, f32), radius: f32) -> f32 {
+    let qx = (p.0 - center.0).abs() - half.0 + radius;
+    let qy = (p.1 - center.1).abs() - half.1 + radius;
+
+    qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+}
+
+/// Approximate signed distance to an ellipse, exact for circles
+fn ellipse_distance(p: (f32, f32), center: (f32, f32), radius: (f32, f32)) -> f32 {
+    let (x, y) = (p.0 - center.0, p.1 - center.1);
+    let k0 = (x / radius.0).hypot(y / radius.1);
+    let k1 = (x / (radius.0 * radius.0)).hypot(y / (radius.1 * radius.1));
+
+    if k1 > 0.0 {
+        k0 * (k0 - 1.0) / k1
+    } else {
+        -radius.0.min(radius.1)
+    }
+}
@@ -3,14 +3,16 @@ use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::conv;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct GaussianBlurConfig {
     #[derivative(Default(value = "3"))]
-    radius: i32,
+    pub(crate) radius: i32,
 }
 
 impl GaussianBlurConfig {
@@ -28,8 +30,9 @@ impl Effect for GaussianBlurConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct BoxBlurConfig {
@@ -52,8 +55,9 @@ impl Effect for BoxBlurConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct MedianBlurConfig {
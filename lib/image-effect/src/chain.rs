@@ -0,0 +1,200 @@
+//! Ordered list of effects applied one after another.
+//!
+//! Consecutive per-pixel effects (invert, brightness, contrast, tint,
+//! temperature and grayscale) are fused into a single pass over the frame,
+//! the other effects reuse the buffer handed over by the previous one.
+//! Chains can be saved and loaded as json presets.
+
+use crate::{
+    Effect, ImageEffect,
+    filter::tint_lut,
+    monochrome::{GrayscaleConfig, GrayscaleMode},
+    pixel,
+};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectChain {
+    effects: Vec<ImageEffect>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_effect(mut self, effect: ImageEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn push(&mut self, effect: ImageEffect) {
+        self.effects.push(effect);
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn effects(&self) -> &[ImageEffect] {
+        &self.effects
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json()?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_json(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl From<Vec<ImageEffect>> for EffectChain {
+    fn from(effects: Vec<ImageEffect>) -> Self {
+        Self { effects }
+    }
+}
+
+impl Effect for EffectChain {
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        let mut index = 0;
+
+        while index < self.effects.len() {
+            let (ops, count) = fuse(&self.effects[index..]);
+
+            if count == 0 {
+                image = self.effects[index].apply(image)?;
+                index += 1;
+            } else {
+                apply_pixel_ops(&mut image, &ops);
+                index += count;
+            }
+        }
+
+        Some(image)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PixelOp {
+    /// A lookup table per RGB channel, `opaque` sets alpha to 255
+    Lut {
+        tables: Box<[[u8; 256]; 3]>,
+        opaque: bool,
+    },
+    Grayscale(GrayscaleMode),
+}
+
+impl PixelOp {
+    // Same results as applying the effects one by one
+    fn from_effect(effect: &ImageEffect) -> Option<Self> {
+        let op = match effect {
+            ImageEffect::Invert => Self::lut(pixel::lut(|_, v| 255 - v), false),
+            ImageEffect::Brightness(config) => Self::add(config.brightness.clamp(-255, 255) as i16),
+            ImageEffect::IncBrightness(config) => Self::add(config.brightness as i16),
+            ImageEffect::DecBrightness(config) => Self::add(-(config.brightness as i16)),
+            ImageEffect::Contrast(config) => Self::lut(config.lut(), true),
+            ImageEffect::ColorTint(config) => Self::lut(tint_lut(config.offsets()), true),
+            ImageEffect::WarmFilter(config) | ImageEffect::CoolFilter(config) => {
+                Self::lut(tint_lut(config.offsets()), true)
+            }
+            ImageEffect::Grayscale(config) => Self::Grayscale(config.mode),
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    fn lut(tables: [[u8; 256]; 3], opaque: bool) -> Self {
+        Self::Lut {
+            tables: Box::new(tables),
+            opaque,
+        }
+    }
+
+    fn add(offset: i16) -> Self {
+        Self::lut(
+            pixel::lut(|_, v| (v as i16 + offset).clamp(0, 255) as u8),
+            false,
+        )
+    }
+
+    /// Merge `next` into `self` when both are lookup tables
+    fn merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                Self::Lut { tables, opaque },
+                Self::Lut {
+                    tables: next_tables,
+                    opaque: next_opaque,
+                },
+            ) => {
+                for (table, next_table) in tables.iter_mut().zip(next_tables.iter()) {
+                    for entry in table.iter_mut() {
+                        *entry = next_table[*entry as usize];
+                    }
+                }
+                *opaque |= *next_opaque;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(&self, pixel: &mut [u8; 4]) {
+        match self {
+            Self::Lut { tables, opaque } => {
+                for (value, table) in pixel.iter_mut().zip(tables.iter()) {
+                    *value = table[*value as usize];
+                }
+                if *opaque {
+                    pixel[3] = 255;
+                }
+            }
+            Self::Grayscale(mode) => GrayscaleConfig::apply_to_pixel(*mode, pixel),
+        }
+    }
+}
+
+/// Ops of the leading per-pixel effects with adjacent lookup tables merged,
+/// and the number of effects they replace
+fn fuse(effects: &[ImageEffect]) -> (Vec<PixelOp>, usize) {
+    let mut ops: Vec<PixelOp> = vec![];
+    let mut count = 0;
+
+    for op in effects.iter().map_while(PixelOp::from_effect) {
+        if !ops.last_mut().is_some_and(|last| last.merge(&op)) {
+            ops.push(op);
+        }
+        count += 1;
+    }
+
+    (ops, count)
+}
+
+fn apply_pixel_ops(image: &mut RgbaImage, ops: &[PixelOp]) {
+    pixel::for_each_pixel(image, |pixel| {
+        for op in ops {
+            op.apply(pixel);
+        }
+    });
+}
@@ -1,22 +1,28 @@
-use crate::Effect;
+use crate::{Effect, pixel};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, channels};
+use serde::{Deserialize, Serialize};
 
 pub struct Invert;
 
 impl Effect for Invert {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::invert(&mut photon_img);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        // 255 - v for RGB
+        let mask: [u8; 16] = std::array::from_fn(|i| if i % 4 == 3 { 0 } else { 255 });
+        pixel::for_each_chunk(&mut image, |chunk: &mut [u8; 16]| {
+            for i in 0..16 {
+                chunk[i] ^= mask[i];
+            }
+        });
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct AlterRedChannelConfig {
@@ -31,16 +37,15 @@ impl AlterRedChannelConfig {
 }
 
 impl Effect for AlterRedChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::alter_red_channel(&mut photon_img, self.amount);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [self.amount, 0, 0]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct AlterGreenChannelConfig {
@@ -55,16 +60,15 @@ impl AlterGreenChannelConfig {
 }
 
 impl Effect for AlterGreenChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::alter_green_channel(&mut photon_img, self.amount);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [0, self.amount, 0]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct AlterBlueChannelConfig {
@@ -79,16 +83,15 @@ impl AlterBlueChannelConfig {
 }
 
 impl Effect for AlterBlueChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::alter_blue_channel(&mut photon_img, self.amount);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [0, 0, self.amount]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct AlterTwoChannelsConfig {
@@ -109,22 +112,22 @@ impl AlterTwoChannelsConfig {
 }
 
 impl Effect for AlterTwoChannelsConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::alter_two_channels(
-            &mut photon_img,
-            self.channel1.clamp(0, 2),
-            self.amt1,
-            self.channel2.clamp(0, 2),
-            self.amt2,
-        );
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        let (channel1, channel2) = (self.channel1.clamp(0, 2), self.channel2.clamp(0, 2));
+        pixel::for_each_pixel(&mut image, |pixel| {
+            // Both come from the original values, the second wins for the same channel
+            let value1 = pixel[channel1] as i16 + self.amt1;
+            let value2 = pixel[channel2] as i16 + self.amt2;
+            pixel[channel1] = value1.clamp(0, 255) as u8;
+            pixel[channel2] = value2.clamp(0, 255) as u8;
+        });
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct AlterChannelsConfig {
@@ -143,16 +146,15 @@ impl AlterChannelsConfig {
 }
 
 impl Effect for AlterChannelsConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::alter_channels(&mut photon_img, self.r_amt, self.g_amt, self.b_amt);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [self.r_amt, self.g_amt, self.b_amt]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct RemoveRedChannelConfig {
@@ -167,16 +169,19 @@ impl RemoveRedChannelConfig {
 }
 
 impl Effect for RemoveRedChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::remove_red_channel(&mut photon_img, self.min_filter);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::for_each_pixel(&mut image, |pixel| {
+            if pixel[0] < self.min_filter {
+                pixel[0] = 0;
+            }
+        });
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct RemoveGreenChannelConfig {
@@ -191,16 +196,19 @@ impl RemoveGreenChannelConfig {
 }
 
 impl Effect for RemoveGreenChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::remove_green_channel(&mut photon_img, self.min_filter);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::for_each_pixel(&mut image, |pixel| {
+            if pixel[1] < self.min_filter {
+                pixel[1] = 0;
+            }
+        });
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct RemoveBlueChannelConfig {
@@ -215,16 +223,19 @@ impl RemoveBlueChannelConfig {
 }
 
 impl Effect for RemoveBlueChannelConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        channels::remove_blue_channel(&mut photon_img, self.min_filter);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::for_each_pixel(&mut image, |pixel| {
+            if pixel[2] < self.min_filter {
+                pixel[2] = 0;
+            }
+        });
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SelectiveHueRotateConfig {
@@ -246,16 +257,16 @@ impl SelectiveHueRotateConfig {
 
 impl Effect for SelectiveHueRotateConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
         let ref_color = photon_rs::Rgb::new(self.ref_r, self.ref_g, self.ref_b);
-        channels::selective_hue_rotate(&mut photon_img, ref_color, self.degrees);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            channels::selective_hue_rotate(photon_img, ref_color.clone(), self.degrees)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SelectiveLightenConfig {
@@ -277,16 +288,16 @@ impl SelectiveLightenConfig {
 
 impl Effect for SelectiveLightenConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
         let ref_color = photon_rs::Rgb::new(self.ref_r, self.ref_g, self.ref_b);
-        channels::selective_lighten(&mut photon_img, ref_color, self.amt);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            channels::selective_lighten(photon_img, ref_color.clone(), self.amt)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SelectiveDesaturateConfig {
@@ -308,16 +319,16 @@ impl SelectiveDesaturateConfig {
 
 impl Effect for SelectiveDesaturateConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
         let ref_color = photon_rs::Rgb::new(self.ref_r, self.ref_g, self.ref_b);
-        channels::selective_desaturate(&mut photon_img, ref_color, self.amt);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            channels::selective_desaturate(photon_img, ref_color.clone(), self.amt)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SelectiveSaturateConfig {
@@ -339,16 +350,16 @@ impl SelectiveSaturateConfig {
 
 impl Effect for SelectiveSaturateConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
         let ref_color = photon_rs::Rgb::new(self.ref_r, self.ref_g, self.ref_b);
-        channels::selective_saturate(&mut photon_img, ref_color, self.amt);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            channels::selective_saturate(photon_img, ref_color.clone(), self.amt)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SelectiveGrayscaleConfig {
@@ -376,3 +387,106 @@ impl Effect for SelectiveGrayscaleConfig {
         RgbaImage::from_raw(width, height, raw_pixels)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::tests::assert_photon;
+
+    fn ref_color() -> photon_rs::Rgb {
+        photon_rs::Rgb::new(200, 40, 90)
+    }
+
+    #[test]
+    fn test_invert() {
+        assert_photon(&Invert, channels::invert);
+    }
+
+    #[test]
+    fn test_alter_channels() {
+        for amount in [-300, -40, 0, 75, 255] {
+            assert_photon(&AlterRedChannelConfig::new().with_amount(amount), |img| {
+                channels::alter_red_channel(img, amount)
+            });
+            assert_photon(&AlterGreenChannelConfig::new().with_amount(amount), |img| {
+                channels::alter_green_channel(img, amount)
+            });
+            assert_photon(&AlterBlueChannelConfig::new().with_amount(amount), |img| {
+                channels::alter_blue_channel(img, amount)
+            });
+        }
+
+        assert_photon(
+            &AlterChannelsConfig::new()
+                .with_r_amt(-60)
+                .with_g_amt(20)
+                .with_b_amt(255),
+            |img| channels::alter_channels(img, -60, 20, 255),
+        );
+
+        for (channel1, channel2) in [(0, 2), (1, 1), (5, 0)] {
+            assert_photon(
+                &AlterTwoChannelsConfig::new()
+                    .with_channel1(channel1)
+                    .with_amt1(-30)
+                    .with_channel2(channel2)
+                    .with_amt2(90),
+                |img| channels::alter_two_channels(img, channel1.min(2), -30, channel2.min(2), 90),
+            );
+        }
+    }
+
+    #[test]
+    fn test_remove_channels() {
+        for min_filter in [0, 100, 255] {
+            assert_photon(
+                &RemoveRedChannelConfig::new().with_min_filter(min_filter),
+                |img| channels::remove_red_channel(img, min_filter),
+            );
+            assert_photon(
+                &RemoveGreenChannelConfig::new().with_min_filter(min_filter),
+                |img| channels::remove_green_channel(img, min_filter),
+            );
+            assert_photon(
+                &RemoveBlueChannelConfig::new().with_min_filter(min_filter),
+                |img| channels::remove_blue_channel(img, min_filter),
+            );
+        }
+    }
+
+    #[test]
+    fn test_selective() {
+        assert_photon(
+            &SelectiveHueRotateConfig::new()
+                .with_ref_r(200)
+                .with_ref_g(40)
+                .with_ref_b(90)
+                .with_degrees(0.3),
+            |img| channels::selective_hue_rotate(img, ref_color(), 0.3),
+        );
+        assert_photon(
+            &SelectiveLightenConfig::new()
+                .with_ref_r(200)
+                .with_ref_g(40)
+                .with_ref_b(90)
+                .with_amt(0.2),
+            |img| channels::selective_lighten(img, ref_color(), 0.2),
+        );
+        assert_photon(
+            &SelectiveDesaturateConfig::new()
+                .with_ref_r(200)
+                .with_ref_g(40)
+                .with_ref_b(90)
+                .with_amt(0.4),
+            |img| channels::selective_desaturate(img, ref_color(), 0.4),
+        );
+        assert_photon(
+            &SelectiveSaturateConfig::new()
+                .with_ref_r(200)
+                .with_ref_g(40)
+                .with_ref_b(90)
+                .with_amt(0.4),
+            |img| channels::selective_saturate(img, ref_color(), 0.4),
+        );
+    }
+}
@@ -1,11 +1,13 @@
-use crate::Effect;
+use crate::{Effect, pixel};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
-use photon_rs::{PhotonImage, colour_spaces};
+use photon_rs::colour_spaces;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SaturationConfig {
@@ -21,15 +23,15 @@ impl SaturationConfig {
 
 impl Effect for SaturationConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::saturate_hsl(&mut photon_img, self.amount);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::saturate_hsl(photon_img, self.amount)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HueRotateConfig {
@@ -45,15 +47,15 @@ impl HueRotateConfig {
 
 impl Effect for HueRotateConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::hue_rotate_hsl(&mut photon_img, self.degrees as f32 / 360.0);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::hue_rotate_hsl(photon_img, self.degrees as f32 / 360.0)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct GammaCorrectionConfig {
@@ -72,16 +74,21 @@ impl GammaCorrectionConfig {
 }
 
 impl Effect for GammaCorrectionConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::gamma_correction(&mut photon_img, self.red, self.green, self.blue);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        // Same tables as photon-rs `gamma_correction`
+        let inv_gamma = [1.0 / self.red, 1.0 / self.green, 1.0 / self.blue];
+        let tables = pixel::lut(|channel, v| {
+            (255.0 * (v as f32 / 255.0).powf(inv_gamma[channel]) + 0.5).clamp(0.0, 255.0) as u8
+        });
+
+        pixel::apply_lut(&mut image, &tables, false);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HueRotateHslConfig {
@@ -97,15 +104,15 @@ impl HueRotateHslConfig {
 
 impl Effect for HueRotateHslConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::hue_rotate_hsl(&mut photon_img, self.degrees);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::hue_rotate_hsl(photon_img, self.degrees)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HueRotateHsvConfig {
@@ -121,15 +128,15 @@ impl HueRotateHsvConfig {
 
 impl Effect for HueRotateHsvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::hue_rotate_hsv(&mut photon_img, self.degrees);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::hue_rotate_hsv(photon_img, self.degrees)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HueRotateLchConfig {
@@ -145,15 +152,15 @@ impl HueRotateLchConfig {
 
 impl Effect for HueRotateLchConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::hue_rotate_lch(&mut photon_img, self.degrees);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::hue_rotate_lch(photon_img, self.degrees)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HueRotateHsluvConfig {
@@ -169,15 +176,15 @@ impl HueRotateHsluvConfig {
 
 impl Effect for HueRotateHsluvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::hue_rotate_hsluv(&mut photon_img, self.degrees);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::hue_rotate_hsluv(photon_img, self.degrees)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SaturateLchConfig {
@@ -193,15 +200,15 @@ impl SaturateLchConfig {
 
 impl Effect for SaturateLchConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::saturate_lch(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::saturate_lch(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SaturateHsluvConfig {
@@ -217,15 +224,15 @@ impl SaturateHsluvConfig {
 
 impl Effect for SaturateHsluvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::saturate_hsluv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::saturate_hsluv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SaturateHsvConfig {
@@ -241,15 +248,15 @@ impl SaturateHsvConfig {
 
 impl Effect for SaturateHsvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::saturate_hsv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::saturate_hsv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct LightenLchConfig {
@@ -265,15 +272,15 @@ impl LightenLchConfig {
 
 impl Effect for LightenLchConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::lighten_lch(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::lighten_lch(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct LightenHsluvConfig {
@@ -289,15 +296,15 @@ impl LightenHsluvConfig {
 
 impl Effect for LightenHsluvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::lighten_hsluv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::lighten_hsluv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct LightenHsvConfig {
@@ -313,15 +320,15 @@ impl LightenHsvConfig {
 
 impl Effect for LightenHsvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::lighten_hsv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::lighten_hsv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DarkenLchConfig {
@@ -337,15 +344,15 @@ impl DarkenLchConfig {
 
 impl Effect for DarkenLchConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::darken_lch(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::darken_lch(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DarkenHsluvConfig {
@@ -361,15 +368,15 @@ impl DarkenHsluvConfig {
 
 impl Effect for DarkenHsluvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::darken_hsluv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::darken_hsluv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DarkenHsvConfig {
@@ -385,15 +392,15 @@ impl DarkenHsvConfig {
 
 impl Effect for DarkenHsvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::darken_hsv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::darken_hsv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DesaturateHsvConfig {
@@ -409,15 +416,15 @@ impl DesaturateHsvConfig {
 
 impl Effect for DesaturateHsvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::desaturate_hsv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::desaturate_hsv(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DesaturateLchConfig {
@@ -433,15 +440,15 @@ impl DesaturateLchConfig {
 
 impl Effect for DesaturateLchConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::desaturate_lch(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::desaturate_lch(photon_img, self.level)
+        }))
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DesaturateHsluvConfig {
@@ -457,9 +464,93 @@ impl DesaturateHsluvConfig {
 
 impl Effect for DesaturateHsluvConfig {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        colour_spaces::desaturate_hsluv(&mut photon_img, self.level);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+        Some(pixel::par_photon(image, |photon_img| {
+            colour_spaces::desaturate_hsluv(photon_img, self.level)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::tests::assert_photon;
+
+    #[test]
+    fn test_gamma_correction() {
+        for (red, green, blue) in [(2.2, 2.2, 2.2), (0.5, 1.0, 3.0)] {
+            assert_photon(
+                &GammaCorrectionConfig::new()
+                    .with_red(red)
+                    .with_green(green)
+                    .with_blue(blue),
+                |img| colour_spaces::gamma_correction(img, red, green, blue),
+            );
+        }
+    }
+
+    #[test]
+    fn test_hue_rotate() {
+        assert_photon(&HueRotateConfig::new().with_degrees(90), |img| {
+            colour_spaces::hue_rotate_hsl(img, 0.25)
+        });
+        assert_photon(&HueRotateHslConfig::new().with_degrees(0.4), |img| {
+            colour_spaces::hue_rotate_hsl(img, 0.4)
+        });
+        assert_photon(&HueRotateHsvConfig::new().with_degrees(0.4), |img| {
+            colour_spaces::hue_rotate_hsv(img, 0.4)
+        });
+        assert_photon(&HueRotateLchConfig::new().with_degrees(120.0), |img| {
+            colour_spaces::hue_rotate_lch(img, 120.0)
+        });
+        assert_photon(&HueRotateHsluvConfig::new().with_degrees(120.0), |img| {
+            colour_spaces::hue_rotate_hsluv(img, 120.0)
+        });
+    }
+
+    #[test]
+    fn test_saturation() {
+        assert_photon(&SaturationConfig::new().with_amount(0.3), |img| {
+            colour_spaces::saturate_hsl(img, 0.3)
+        });
+        assert_photon(&SaturateLchConfig::new().with_level(0.3), |img| {
+            colour_spaces::saturate_lch(img, 0.3)
+        });
+        assert_photon(&SaturateHsluvConfig::new().with_level(0.3), |img| {
+            colour_spaces::saturate_hsluv(img, 0.3)
+        });
+        assert_photon(&SaturateHsvConfig::new().with_level(0.3), |img| {
+            colour_spaces::saturate_hsv(img, 0.3)
+        });
+        assert_photon(&DesaturateHsvConfig::new().with_level(0.3), |img| {
+            colour_spaces::desaturate_hsv(img, 0.3)
+        });
+        assert_photon(&DesaturateLchConfig::new().with_level(0.3), |img| {
+            colour_spaces::desaturate_lch(img, 0.3)
+        });
+        assert_photon(&DesaturateHsluvConfig::new().with_level(0.3), |img| {
+            colour_spaces::desaturate_hsluv(img, 0.3)
+        });
+    }
+
+    #[test]
+    fn test_lightness() {
+        assert_photon(&LightenLchConfig::new().with_level(0.2), |img| {
+            colour_spaces::lighten_lch(img, 0.2)
+        });
+        assert_photon(&LightenHsluvConfig::new().with_level(0.2), |img| {
+            colour_spaces::lighten_hsluv(img, 0.2)
+        });
+        assert_photon(&LightenHsvConfig::new().with_level(0.2), |img| {
+            colour_spaces::lighten_hsv(img, 0.2)
+        });
+        assert_photon(&DarkenLchConfig::new().with_level(0.2), |img| {
+            colour_spaces::darken_lch(img, 0.2)
+        });
+        assert_photon(&DarkenHsluvConfig::new().with_level(0.2), |img| {
+            colour_spaces::darken_hsluv(img, 0.2)
+        });
+        assert_photon(&DarkenHsvConfig::new().with_level(0.2), |img| {
+            colour_spaces::darken_hsv(img, 0.2)
+        });
     }
 }
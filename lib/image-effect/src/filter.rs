@@ -1,13 +1,15 @@
-use crate::Effect;
+use crate::{Effect, pixel};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
-use photon_rs::{PhotonImage, effects, monochrome};
+use photon_rs::{PhotonImage, monochrome};
 use rand::Rng;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SepiaConfig {
@@ -56,8 +58,9 @@ impl Effect for SepiaConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct TemperatureConfig {
@@ -69,35 +72,31 @@ impl TemperatureConfig {
     pub fn new() -> Self {
         Self::default()
     }
-}
-
-impl Effect for TemperatureConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
 
+    pub(crate) fn offsets(&self) -> [u32; 3] {
         // Positive amount = warm (more red/yellow)
         // Negative amount = cool (more blue)
         if self.amount > 0.0 {
             // Warm: increase red, decrease blue
-            effects::tint(
-                &mut photon_img,
-                (self.amount * 20.0) as u32,
-                (self.amount * 10.0) as u32,
-                0,
-            );
+            [(self.amount * 20.0) as u32, (self.amount * 10.0) as u32, 0]
         } else {
             // Cool: decrease red, increase blue
             let cool_amount = -self.amount;
-            effects::tint(&mut photon_img, 0, 0, (cool_amount * 20.0) as u32);
+            [0, 0, (cool_amount * 20.0) as u32]
         }
+    }
+}
 
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+impl Effect for TemperatureConfig {
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::apply_lut(&mut image, &tint_lut(self.offsets()), true);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ColorTintConfig {
@@ -119,26 +118,27 @@ impl ColorTintConfig {
     pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
         Self { r, g, b }
     }
+
+    pub(crate) fn offsets(&self) -> [u32; 3] {
+        [self.r as u32 / 3, self.g as u32 / 3, self.b as u32 / 3]
+    }
 }
 
 impl Effect for ColorTintConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-
-        effects::tint(
-            &mut photon_img,
-            self.r as u32 / 3,
-            self.g as u32 / 3,
-            self.b as u32 / 3,
-        );
-
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::apply_lut(&mut image, &tint_lut(self.offsets()), true);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+/// Lookup tables of photon-rs `tint`, alpha is set to 255 too
+pub(crate) fn tint_lut(offsets: [u32; 3]) -> [[u8; 256]; 3] {
+    pixel::lut(|channel, v| (v as u32).saturating_add(offsets[channel]).min(255) as u8)
+}
+
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct VignetteConfig {
@@ -192,8 +192,9 @@ impl Effect for VignetteConfig {
 }
 
 /// Black and white TV snow noise effect (static/white noise)
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SnowNoiseConfig {
@@ -277,3 +278,28 @@ impl Effect for SnowNoiseConfig {
         RgbaImage::from_raw(width as u32, height as u32, pixels)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::tests::assert_photon;
+    use photon_rs::effects;
+
+    #[test]
+    fn test_temperature() {
+        for amount in [-20.0, -1.5, 0.0, 0.8, 20.0] {
+            let config = TemperatureConfig::new().with_amount(amount);
+            let [r, g, b] = config.offsets();
+            assert_photon(&config, |img| effects::tint(img, r, g, b));
+        }
+    }
+
+    #[test]
+    fn test_color_tint() {
+        for (r, g, b) in [(255, 0, 0), (30, 120, 255), (0, 0, 0)] {
+            assert_photon(&ColorTintConfig::from_rgb(r, g, b), |img| {
+                effects::tint(img, r as u32 / 3, g as u32 / 3, b as u32 / 3)
+            });
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! GPU execution backend (wgpu compute shaders) for blur, convolution and
+//! color effects, fast enough to apply a Gaussian blur to 4K frames live.
+//!
+//! [`ImageEffect::apply`](crate::Effect::apply) tries the GPU first when the
+//! `gpu` feature is enabled. Effects without a GPU implementation, images too
+//! large for the device and machines without a usable adapter fall back to
+//! the CPU implementation.
+
+use crate::{ImageEffect, monochrome::GrayscaleMode, stylized::EdgeDetectionMode};
+use image::RgbaImage;
+use std::sync::{
+    Mutex, OnceLock,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 16;
+const PARAMS_SIZE: usize = 80;
+
+const MODE_INVERT: u32 = 0;
+const MODE_GRAYSCALE_LUMINANCE: u32 = 1;
+const MODE_GRAYSCALE_AVERAGE: u32 = 2;
+const MODE_BRIGHTNESS: u32 = 3;
+const MODE_CONTRAST: u32 = 4;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static CONTEXT: OnceLock<Option<GpuContext>> = OnceLock::new();
+
+/// Enable or disable the GPU backend, it's enabled by default
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether a GPU adapter is usable, the device is created on the first call
+pub fn is_available() -> bool {
+    context().is_some()
+}
+
+/// Name of the GPU adapter in use
+pub fn adapter_name() -> Option<String> {
+    context().map(|ctx| ctx.adapter_name.clone())
+}
+
+/// Whether the effect has a GPU implementation
+pub fn is_supported(effect: &ImageEffect) -> bool {
+    GpuOp::from_effect(effect).is_some()
+}
+
+/// Apply the effect on the GPU. `None` when it isn't possible and the CPU
+/// implementation should be used.
+pub(crate) fn apply(effect: &ImageEffect, image: &RgbaImage) -> Option<RgbaImage> {
+    if !is_enabled() || image.width() < 3 || image.height() < 3 {
+        return None;
+    }
+
+    let op = GpuOp::from_effect(effect)?;
+    let ctx = context()?;
+
+    match ctx.run(&op, image) {
+        Ok(output) => Some(output),
+        Err(e) => {
+            log::warn!("gpu effect failed, fall back to cpu: {e}");
+            None
+        }
+    }
+}
+
+fn context() -> Option<&'static GpuContext> {
+    CONTEXT
+        .get_or_init(|| match GpuContext::new() {
+            Ok(ctx) => {
+                log::info!("gpu image effects on {}", ctx.adapter_name);
+                Some(ctx)
+            }
+            Err(e) => {
+                log::info!("gpu image effects unavailable: {e}");
+                None
+            }
+        })
+        .as_ref()
+}
+
+enum GpuOp {
+    Color { mode: u32, values: [f32; 2] },
+    Convolve3x3([f32; 9]),
+    GaussianBlur(f32),
+}
+
+impl GpuOp {
+    fn from_effect(effect: &ImageEffect) -> Option<Self> {
+        let color = |mode, values| Some(GpuOp::Color { mode, values });
+
+        match effect {
+            // A true gaussian with sigma = radius, the CPU version approximates it with box blurs
+            ImageEffect::GaussianBlur(config) if config.radius > 0 => {
+                Some(GpuOp::GaussianBlur(config.radius as f32))
+            }
+            ImageEffect::BoxBlur(_) => Some(GpuOp::Convolve3x3([1.0; 9])),
+            ImageEffect::Sharpen(_) => Some(GpuOp::Convolve3x3([
+                0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0,
+            ])),
+            ImageEffect::Emboss(_) => Some(GpuOp::Convolve3x3([
+                -2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0,
+            ])),
+            ImageEffect::EdgeDetection(config)
+                if matches!(config.mode, EdgeDetectionMode::Standard) =>
+            {
+                Some(GpuOp::Convolve3x3([
+                    -1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0,
+                ]))
+            }
+            ImageEffect::Invert => color(MODE_INVERT, [0.0, 0.0]),
+            ImageEffect::Grayscale(config) => match config.mode {
+                GrayscaleMode::Luminance => color(MODE_GRAYSCALE_LUMINANCE, [0.0, 0.0]),
+                GrayscaleMode::Average => color(MODE_GRAYSCALE_AVERAGE, [0.0, 0.0]),
+                _ => None,
+            },
+            ImageEffect::Brightness(config) => color(
+                MODE_BRIGHTNESS,
+                [config.brightness.clamp(-255, 255) as f32, 0.0],
+            ),
+            ImageEffect::IncBrightness(config) => {
+                color(MODE_BRIGHTNESS, [config.brightness as f32, 0.0])
+            }
+            ImageEffect::DecBrightness(config) => {
+                color(MODE_BRIGHTNESS, [-(config.brightness as f32), 0.0])
+            }
+            ImageEffect::Contrast(config) => {
+                let contrast = config.contrast.clamp(-255.0, 255.0);
+                let factor = (259.0 * (contrast + 255.0)) / (255.0 * (259.0 - contrast));
+                color(MODE_CONTRAST, [factor, -128.0 * factor + 128.0])
+            }
+            _ => None,
+        }
+    }
+}
+
+struct GpuContext {
+    adapter_name: String,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    color_pipeline: wgpu::ComputePipeline,
+    conv3x3_pipeline: wgpu::ComputePipeline,
+    blur_pipeline: wgpu::ComputePipeline,
+
+    // Reused between frames of the same size, also serializes the GPU work
+    buffers: Mutex<Option<FrameBuffers>>,
+}
+
+struct FrameBuffers {
+    size: u64,
+    front: wgpu::Buffer,
+    back: wgpu::Buffer,
+    staging: wgpu::Buffer,
+}
+
+impl GpuContext {
+    fn new() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .map_err(|e| e.to_string())?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("image-effect"),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .map_err(|e| e.to_string())?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("image-effect"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        Ok(Self {
+            adapter_name: adapter.get_info().name,
+            color_pipeline: pipeline("color_main"),
+            conv3x3_pipeline: pipeline("conv3x3_main"),
+            blur_pipeline: pipeline("blur_main"),
+            device,
+            queue,
+            buffers: Mutex::new(None),
+        })
+    }
+
+    fn run(&self, op: &GpuOp, image: &RgbaImage) -> Result<RgbaImage, String> {
+        let (width, height) = image.dimensions();
+        let size = image.as_raw().len() as u64;
+
+        if size > self.device.limits().max_storage_buffer_binding_size as u64 {
+            return Err(format!(
+                "{width} x {height} image is too large for the device"
+            ));
+        }
+
+        let mut buffers = self.buffers.lock().map_err(|e| e.to_string())?;
+        if buffers.as_ref().is_none_or(|b| b.size != size) {
+            *buffers = Some(self.create_buffers(size));
+        }
+        let buffers = buffers.as_ref().unwrap();
+
+        self.queue.write_buffer(&buffers.front, 0, image.as_raw());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let workgroups = (
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+        );
+
+        // The passes ping-pong between the two buffers
+        let passes = match op {
+            GpuOp::Color { mode, values } => {
+                vec![(
+                    &self.color_pipeline,
+                    params(width, height, *mode, 0, &[], *values),
+                )]
+            }
+            GpuOp::Convolve3x3(kernel) => {
+                vec![(
+                    &self.conv3x3_pipeline,
+                    params(width, height, 0, 0, kernel, [0.0; 2]),
+                )]
+            }
+            GpuOp::GaussianBlur(sigma) => {
+                let radius = (sigma * 3.0).ceil() as i32;
+                vec![
+                    (
+                        &self.blur_pipeline,
+                        blur_params(width, height, radius, (1.0, 0.0), *sigma),
+                    ),
+                    (
+                        &self.blur_pipeline,
+                        blur_params(width, height, radius, (0.0, 1.0), *sigma),
+                    ),
+                ]
+            }
+        };
+
+        let mut uniforms = vec![];
+        for (index, (pipeline, params)) in passes.iter().enumerate() {
+            let (src, dst) = if index % 2 == 0 {
+                (&buffers.front, &buffers.back)
+            } else {
+                (&buffers.back, &buffers.front)
+            };
+
+            let uniform = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: params,
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: src.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: dst.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+            drop(pass);
+
+            uniforms.push(uniform);
+        }
+
+        let output = if passes.len() % 2 == 0 {
+            &buffers.front
+        } else {
+            &buffers.back
+        };
+        encoder.copy_buffer_to_buffer(output, 0, &buffers.staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let (tx, rx) = mpsc::channel();
+        let slice = buffers.staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |result| _ = tx.send(result));
+
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| e.to_string())?;
+        rx.recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let data = slice.get_mapped_range().to_vec();
+        buffers.staging.unmap();
+
+        RgbaImage::from_raw(width, height, data).ok_or_else(|| "invalid output size".to_string())
+    }
+
+    fn create_buffers(&self, size: u64) -> FrameBuffers {
+        let storage = |label| {
+            self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        };
+
+        FrameBuffers {
+            size,
+            front: storage("front"),
+            back: storage("back"),
+            staging: self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("staging"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        }
+    }
+}
+
+/// Layout of `Params` in `gpu.wgsl`
+fn params(
+    width: u32,
+    height: u32,
+    mode: u32,
+    radius: i32,
+    kernel: &[f32],
+    values: [f32; 2],
+) -> Vec<u8> {
+    let mut kernel_values = [0.0_f32; 12];
+    kernel_values[..kernel.len()].copy_from_slice(kernel);
+
+    // Normalization factor, like `image::imageops::filter3x3`
+    let sum = kernel.iter().sum::<f32>();
+    kernel_values[9] = if sum == 0.0 { 1.0 } else { 1.0 / sum };
+
+    let mut bytes = Vec::with_capacity(PARAMS_SIZE);
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.extend_from_slice(&mode.to_le_bytes());
+    bytes.extend_from_slice(&radius.to_le_bytes());
+
+    for v in kernel_values
+        .iter()
+        .chain(values.iter())
+        .chain([0.0, 0.0].iter())
+    {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    bytes
+}
+
+fn blur_params(width: u32, height: u32, radius: i32, dir: (f32, f32), sigma: f32) -> Vec<u8> {
+    let mut bytes = params(width, height, 0, radius, &[], [dir.0, dir.1]);
+    bytes[72..76].copy_from_slice(&sigma.to_le_bytes());
+    bytes
+}
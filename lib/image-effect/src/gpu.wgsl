@@ -0,0 +1,113 @@
+// Image effects on packed RGBA8 pixels, one invocation per pixel
+
+struct Params {
+    width: u32,
+    height: u32,
+    mode: u32,
+    radius: i32,
+    // 3x3 kernel in row order, kernel[2].y is its normalization factor
+    kernel: array<vec4<f32>, 3>,
+    values: vec4<f32>,
+}
+
+@group(0) @binding(0) var<storage, read> src: array<u32>;
+@group(0) @binding(1) var<storage, read_write> dst: array<u32>;
+@group(0) @binding(2) var<uniform> params: Params;
+
+const MODE_INVERT: u32 = 0u;
+const MODE_GRAYSCALE_LUMINANCE: u32 = 1u;
+const MODE_GRAYSCALE_AVERAGE: u32 = 2u;
+const MODE_BRIGHTNESS: u32 = 3u;
+const MODE_CONTRAST: u32 = 4u;
+
+fn load(x: i32, y: i32) -> vec4<f32> {
+    let cx = clamp(x, 0, i32(params.width) - 1);
+    let cy = clamp(y, 0, i32(params.height) - 1);
+    return unpack4x8unorm(src[u32(cy) * params.width + u32(cx)]) * 255.0;
+}
+
+fn store(index: u32, color: vec4<f32>) {
+    dst[index] = pack4x8unorm(clamp(color, vec4<f32>(0.0), vec4<f32>(255.0)) / 255.0);
+}
+
+fn kernel_at(i: u32) -> f32 {
+    return params.kernel[i / 4u][i % 4u];
+}
+
+@compute @workgroup_size(16, 16)
+fn color_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let index = id.y * params.width + id.x;
+    let pixel = unpack4x8unorm(src[index]) * 255.0;
+    var rgb = pixel.rgb;
+
+    switch params.mode {
+        case MODE_INVERT: {
+            rgb = vec3<f32>(255.0) - rgb;
+        }
+        case MODE_GRAYSCALE_LUMINANCE: {
+            rgb = vec3<f32>(dot(rgb, vec3<f32>(0.3, 0.59, 0.11)));
+        }
+        case MODE_GRAYSCALE_AVERAGE: {
+            rgb = vec3<f32>((rgb.r + rgb.g + rgb.b) / 3.0);
+        }
+        case MODE_BRIGHTNESS: {
+            rgb = rgb + vec3<f32>(params.values.x);
+        }
+        case MODE_CONTRAST: {
+            rgb = rgb * params.values.x + vec3<f32>(params.values.y);
+        }
+        default: {}
+    }
+
+    // Truncated like the CPU effects
+    store(index, vec4<f32>(floor(rgb), pixel.a));
+}
+
+@compute @workgroup_size(16, 16)
+fn conv3x3_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let x = i32(id.x);
+    let y = i32(id.y);
+    var sum = vec3<f32>(0.0);
+
+    for (var i = 0u; i < 9u; i++) {
+        let dx = i32(i % 3u) - 1;
+        let dy = i32(i / 3u) - 1;
+        sum += load(x + dx, y + dy).rgb * kernel_at(i);
+    }
+
+    let index = id.y * params.width + id.x;
+    let alpha = unpack4x8unorm(src[index]).a * 255.0;
+    store(index, vec4<f32>(sum * params.kernel[2].y, alpha));
+}
+
+// Separable gaussian blur, `values.xy` is the direction and `values.z` the sigma
+@compute @workgroup_size(16, 16)
+fn blur_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    let x = i32(id.x);
+    let y = i32(id.y);
+    let dir = vec2<i32>(params.values.xy);
+    let sigma = params.values.z;
+
+    var sum = vec4<f32>(0.0);
+    var weight_sum = 0.0;
+
+    for (var i = -params.radius; i <= params.radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += load(x + i * dir.x, y + i * dir.y) * weight;
+        weight_sum += weight;
+    }
+
+    store(id.y * params.width + id.x, sum / weight_sum);
+}
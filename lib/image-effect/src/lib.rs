@@ -1,21 +1,33 @@
+pub mod annotate;
 pub mod blur;
+pub mod chain;
 pub mod channel;
 pub mod colour_space;
 pub mod filter;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod lut;
+pub mod masked;
 pub mod monochrome;
 pub mod noise;
+mod pixel;
 pub mod preset_filter;
 pub mod realtime;
 pub mod special;
 pub mod stylized;
 
+pub use chain::EffectChain;
+pub use lut::{Lut3d, LutEffect};
+pub use masked::{EffectRegion, MaskedEffect};
+
 use image::RgbaImage;
+use serde::{Deserialize, Serialize};
 
 pub trait Effect {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage>;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ImageEffect {
     // Blur effects
     GaussianBlur(blur::GaussianBlurConfig),
@@ -114,6 +126,11 @@ pub enum ImageEffect {
 
 impl Effect for ImageEffect {
     fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
+        #[cfg(feature = "gpu")]
+        if let Some(output) = gpu::apply(self, &image) {
+            return Some(output);
+        }
+
         match self {
             // Blur effects
             ImageEffect::GaussianBlur(config) => config.apply(image),
@@ -0,0 +1,327 @@
+//! Color grading with 3D LUTs in the `.cube` format (Adobe/Resolve), the
+//! lookups are interpolated trilinearly.
+
+use crate::Effect;
+use derive_setters::Setters;
+use image::RgbaImage;
+use rayon::prelude::*;
+use std::{fs, path::Path, sync::Arc};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LutError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Parse error at line {line}: {msg}")]
+    ParseError { line: usize, msg: String },
+
+    #[error("Invalid LUT: {0}")]
+    InvalidLut(String),
+}
+
+pub type LutResult<T> = Result<T, LutError>;
+
+// Sizes used in practice are 17, 33 and 65. The table of the largest one
+// takes about 200MB
+pub const MAX_LUT_SIZE: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Lut3d {
+    title: Option<String>,
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+
+    // Red changes fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    pub fn load(path: impl AsRef<Path>) -> LutResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> LutResult<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |msg: &str| LutError::ParseError {
+                line: index + 1,
+                msg: msg.to_string(),
+            };
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword {
+                "TITLE" => title = Some(rest.trim().trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    let value = rest
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| parse_error("invalid LUT_3D_SIZE"))?;
+                    if !(2..=MAX_LUT_SIZE).contains(&value) {
+                        return Err(parse_error(&format!(
+                            "LUT_3D_SIZE {value} is out of 2 ~ {MAX_LUT_SIZE}"
+                        )));
+                    }
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" => {
+                    domain_min =
+                        parse_triplet(rest).ok_or_else(|| parse_error("invalid DOMAIN_MIN"))?
+                }
+                "DOMAIN_MAX" => {
+                    domain_max =
+                        parse_triplet(rest).ok_or_else(|| parse_error("invalid DOMAIN_MAX"))?
+                }
+                "LUT_1D_SIZE" => return Err(parse_error("1D LUTs are not supported")),
+                "LUT_3D_INPUT_RANGE" => {
+                    let range = parse_values(rest)
+                        .filter(|v| v.len() == 2)
+                        .ok_or_else(|| parse_error("invalid LUT_3D_INPUT_RANGE"))?;
+                    domain_min = [range[0]; 3];
+                    domain_max = [range[1]; 3];
+                }
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    log::debug!("skip unknown .cube keyword {keyword}");
+                }
+                _ => table.push(parse_triplet(line).ok_or_else(|| parse_error("invalid entry"))?),
+            }
+        }
+
+        let size = size.ok_or_else(|| LutError::InvalidLut("no LUT_3D_SIZE".to_string()))?;
+        let entries = size
+            .checked_mul(size)
+            .and_then(|n| n.checked_mul(size))
+            .ok_or_else(|| LutError::InvalidLut(format!("size {size} is too large")))?;
+        if table.len() != entries {
+            return Err(LutError::InvalidLut(format!(
+                "expect {entries} entries, found {}",
+                table.len()
+            )));
+        }
+
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err(LutError::InvalidLut("empty domain".to_string()));
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// LUT that leaves the colors unchanged
+    pub fn identity(size: usize) -> Self {
+        let size = size.clamp(2, MAX_LUT_SIZE);
+        let max = (size - 1) as f32;
+        let table = (0..size * size * size)
+            .map(|i| {
+                [
+                    (i % size) as f32 / max,
+                    (i / size % size) as f32 / max,
+                    (i / (size * size)) as f32 / max,
+                ]
+            })
+            .collect();
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Look up a color with components in [0, 1]
+    pub fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+
+        for i in 0..3 {
+            let v = (rgb[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]);
+            let v = v.clamp(0.0, 1.0) * max;
+            base[i] = (v.floor() as usize).min(self.size - 2);
+            frac[i] = v - base[i] as f32;
+        }
+
+        let at = |r: usize, g: usize, b: usize| {
+            self.table
+                [(base[2] + b) * self.size * self.size + (base[1] + g) * self.size + base[0] + r]
+        };
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+
+        let c00 = lerp(at(0, 0, 0), at(1, 0, 0), frac[0]);
+        let c10 = lerp(at(0, 1, 0), at(1, 1, 0), frac[0]);
+        let c01 = lerp(at(0, 0, 1), at(1, 0, 1), frac[0]);
+        let c11 = lerp(at(0, 1, 1), at(1, 1, 1), frac[0]);
+
+        lerp(lerp(c00, c10, frac[1]), lerp(c01, c11, frac[1]), frac[2])
+    }
+}
+
+fn parse_values(text: &str) -> Option<Vec<f32>> {
+    text.split_whitespace().map(|v| v.parse().ok()).collect()
+}
+
+fn parse_triplet(text: &str) -> Option<[f32; 3]> {
+    parse_values(text)?.try_into().ok()
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct LutEffect {
+    #[setters(skip)]
+    lut: Arc<Lut3d>,
+
+    /// Blend between the original (0.0) and the graded (1.0) colors
+    intensity: f32,
+}
+
+impl LutEffect {
+    pub fn new(lut: Lut3d) -> Self {
+        Self {
+            lut: Arc::new(lut),
+            intensity: 1.0,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> LutResult<Self> {
+        Ok(Self::new(Lut3d::load(path)?))
+    }
+
+    pub fn lut(&self) -> &Lut3d {
+        &self.lut
+    }
+}
+
+impl Effect for LutEffect {
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        let intensity = self.intensity.clamp(0.0, 1.0);
+        if intensity == 0.0 || image.width() == 0 {
+            return Some(image);
+        }
+
+        let row_len = image.width() as usize * 4;
+        image.par_chunks_mut(row_len).for_each(|row| {
+            for pixel in row.chunks_exact_mut(4) {
+                let rgb = [
+                    pixel[0] as f32 / 255.0,
+                    pixel[1] as f32 / 255.0,
+                    pixel[2] as f32 / 255.0,
+                ];
+                let graded = self.lut.sample(rgb);
+
+                for i in 0..3 {
+                    let v = rgb[i] + (graded[i].clamp(0.0, 1.0) - rgb[i]) * intensity;
+                    pixel[i] = (v * 255.0).round() as u8;
+                }
+            }
+        });
+
+        Some(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Swaps red and blue
+    const SWAP_CUBE: &str = r#"# Created by hand
+TITLE "swap"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0.0 0.0 0.0
+DOMAIN_MAX 1.0 1.0 1.0
+
+0.0 0.0 0.0
+0.0 0.0 1.0
+0.0 1.0 0.0
+0.0 1.0 1.0
+1.0 0.0 0.0
+1.0 0.0 1.0
+1.0 1.0 0.0
+1.0 1.0 1.0
+"#;
+
+    #[test]
+    fn test_parse() {
+        let lut = Lut3d::parse(SWAP_CUBE).unwrap();
+        assert_eq!(lut.title(), Some("swap"));
+        assert_eq!(lut.size(), 2);
+        assert_eq!(lut.sample([1.0, 0.0, 0.0]), [0.0, 0.0, 1.0]);
+        assert_eq!(lut.sample([0.0, 0.5, 0.25]), [0.25, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_parse_missing_size() {
+        let text = SWAP_CUBE.replace("LUT_3D_SIZE 2\n", "");
+        assert!(matches!(
+            Lut3d::parse(&text),
+            Err(LutError::InvalidLut(msg)) if msg.contains("LUT_3D_SIZE")
+        ));
+    }
+
+    #[test]
+    fn test_parse_wrong_entry_count() {
+        let text = SWAP_CUBE.strip_suffix("1.0 1.0 1.0\n").unwrap();
+        assert!(matches!(
+            Lut3d::parse(text),
+            Err(LutError::InvalidLut(msg)) if msg.contains("expect 8 entries, found 7")
+        ));
+
+        let text = format!("{SWAP_CUBE}0.5 0.5 0.5\n");
+        assert!(matches!(Lut3d::parse(&text), Err(LutError::InvalidLut(_))));
+
+        let text = SWAP_CUBE.replace("0.0 1.0 1.0\n", "0.0 1.0\n");
+        assert!(matches!(
+            Lut3d::parse(&text),
+            Err(LutError::ParseError { line: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_out_of_range_size() {
+        for size in ["0", "1", "257", "18446744073709551615", "-3"] {
+            let text = SWAP_CUBE.replace("LUT_3D_SIZE 2", &format!("LUT_3D_SIZE {size}"));
+            assert!(
+                matches!(
+                    Lut3d::parse(&text),
+                    Err(LutError::ParseError { line: 3, .. })
+                ),
+                "size {size}"
+            );
+        }
+
+        assert_eq!(Lut3d::identity(100_000).size(), MAX_LUT_SIZE);
+    }
+}
@@ -0,0 +1,205 @@
+//! Apply an effect to a part of the image only, e.g. blur a region of a
+//! screenshot or a recorded frame to hide sensitive content.
+
+use crate::{Effect, ImageEffect};
+use derive_setters::Setters;
+use image::{
+    GrayImage, RgbaImage,
+    imageops::{self, FilterType},
+};
+use rayon::prelude::*;
+
+#[derive(Debug, Clone)]
+pub enum EffectRegion {
+    /// Top-left corner and size in pixels
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+
+    /// Ellipse inscribed in the rectangle
+    Ellipse {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+
+    /// Effect strength of each pixel, scaled to the image size when they differ
+    Mask(GrayImage),
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct MaskedEffect {
+    #[setters(skip)]
+    effect: ImageEffect,
+
+    #[setters(skip)]
+    region: EffectRegion,
+
+    /// Width in pixels of the soft edge around the region
+    feather: f32,
+
+    /// Apply the effect outside of the region instead
+    inverted: bool,
+}
+
+impl MaskedEffect {
+    pub fn new(effect: ImageEffect, region: EffectRegion) -> Self {
+        Self {
+            effect,
+            region,
+            feather: 0.0,
+            inverted: false,
+        }
+    }
+
+    pub fn effect(&self) -> &ImageEffect {
+        &self.effect
+    }
+
+    pub fn region(&self) -> &EffectRegion {
+        &self.region
+    }
+
+    /// Area of the image the effect has to be applied to
+    fn bounds(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        match self.region {
+            EffectRegion::Rect {
+                x,
+                y,
+                width: w,
+                height: h,
+            }
+            | EffectRegion::Ellipse {
+                x,
+                y,
+                width: w,
+                height: h,
+            } if !self.inverted => {
+                let margin = self.feather.max(0.0).ceil() as i64;
+                let clamp = |v: i64, max: u32| v.clamp(0, max as i64) as u32;
+
+                (
+                    clamp(x as i64 - margin, width),
+                    clamp(y as i64 - margin, height),
+                    clamp(x as i64 + w as i64 + margin, width),
+                    clamp(y as i64 + h as i64 + margin, height),
+                )
+            }
+            _ => (0, 0, width, height),
+        }
+    }
+
+    /// Effect strength of the pixel at (x, y) for the shape regions
+    fn shape_weight(&self, x: u32, y: u32) -> f32 {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+        let distance = match self.region {
+            EffectRegion::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let dx = (x as f32 - px).max(px - (x as f32 + width as f32)).max(0.0);
+                let dy = (y as f32 - py)
+                    .max(py - (y as f32 + height as f32))
+                    .max(0.0);
+                dx.hypot(dy)
+            }
+            EffectRegion::Ellipse {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if width == 0 || height == 0 {
+                    return 0.0;
+                }
+
+                let (a, b) = (width as f32 / 2.0, height as f32 / 2.0);
+                let r = ((px - x as f32 - a) / a).hypot((py - y as f32 - b) / b);
+
+                // Approximate distance to the edge, exact for circles
+                (r - 1.0).max(0.0) * a.min(b)
+            }
+            EffectRegion::Mask(_) => return 0.0,
+        };
+
+        if distance <= 0.0 {
+            1.0
+        } else if self.feather <= 0.0 {
+            0.0
+        } else {
+            (1.0 - distance / self.feather).max(0.0)
+        }
+    }
+
+    fn feathered_mask(&self, mask: &GrayImage, width: u32, height: u32) -> GrayImage {
+        let mask = if mask.dimensions() != (width, height) {
+            imageops::resize(mask, width, height, FilterType::Triangle)
+        } else {
+            mask.clone()
+        };
+
+        if self.feather > 0.0 {
+            imageops::fast_blur(&mask, self.feather / 2.0)
+        } else {
+            mask
+        }
+    }
+}
+
+impl Effect for MaskedEffect {
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        let (width, height) = image.dimensions();
+        let (x0, y0, x1, y1) = self.bounds(width, height);
+        if x0 >= x1 || y0 >= y1 {
+            return Some(image);
+        }
+
+        let mask = match &self.region {
+            EffectRegion::Mask(mask) => Some(self.feathered_mask(mask, width, height)),
+            _ => None,
+        };
+
+        let area = imageops::crop_imm(&image, x0, y0, x1 - x0, y1 - y0).to_image();
+        let effected = self.effect.apply(area)?;
+        if effected.dimensions() != (x1 - x0, y1 - y0) {
+            log::warn!("masked effect changed the image size");
+            return None;
+        }
+
+        image
+            .par_chunks_mut(width as usize * 4)
+            .enumerate()
+            .skip(y0 as usize)
+            .take((y1 - y0) as usize)
+            .for_each(|(y, row)| {
+                let y = y as u32;
+                for x in x0..x1 {
+                    let weight = match &mask {
+                        Some(mask) => mask.get_pixel(x, y)[0] as f32 / 255.0,
+                        None => self.shape_weight(x, y),
+                    };
+                    let weight = if self.inverted { 1.0 - weight } else { weight };
+                    if weight <= 0.0 {
+                        continue;
+                    }
+
+                    let src = effected.get_pixel(x - x0, y - y0);
+                    let index = x as usize * 4;
+                    for (dst, src) in row[index..index + 4].iter_mut().zip(src.0) {
+                        *dst = (*dst as f32 + (src as f32 - *dst as f32) * weight).round() as u8;
+                    }
+                }
+            });
+
+        Some(image)
+    }
+}
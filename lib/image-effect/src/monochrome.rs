@@ -1,10 +1,11 @@
-use crate::Effect;
+use crate::{Effect, pixel};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, Rgb, effects, monochrome};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum GrayscaleMode {
     Average,
     Luminance,
@@ -13,56 +14,68 @@ pub enum GrayscaleMode {
     BlueChannel,
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct GrayscaleConfig {
     #[derivative(Default(value = "GrayscaleMode::Luminance"))]
-    mode: GrayscaleMode,
+    pub(crate) mode: GrayscaleMode,
 }
 
 impl GrayscaleConfig {
     pub fn new() -> Self {
         Self::default()
     }
+
+    // Same results as the photon-rs grayscale functions, the single channel
+    // modes set alpha to 255
+    #[inline(always)]
+    pub(crate) fn apply_to_pixel(mode: GrayscaleMode, pixel: &mut [u8; 4]) {
+        let [r, g, b, _] = *pixel;
+        let gray = match mode {
+            GrayscaleMode::Average => ((r as u32 + g as u32 + b as u32) / 3) as u8,
+            GrayscaleMode::Luminance => (r as f32 * 0.3 + g as f32 * 0.59 + b as f32 * 0.11) as u8,
+            GrayscaleMode::RedChannel => r,
+            GrayscaleMode::GreenChannel => g,
+            GrayscaleMode::BlueChannel => b,
+        };
+        pixel[..3].fill(gray);
+
+        if !matches!(mode, GrayscaleMode::Average | GrayscaleMode::Luminance) {
+            pixel[3] = 255;
+        }
+    }
 }
 
 impl Effect for GrayscaleConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        // One loop per mode to keep the match out of the inner loop
+        macro_rules! apply {
+            ($mode:expr) => {
+                pixel::for_each_chunk(&mut image, |chunk: &mut [u8; 16]| {
+                    for pixel in chunk.as_chunks_mut::<4>().0 {
+                        Self::apply_to_pixel($mode, pixel);
+                    }
+                })
+            };
+        }
+
         match self.mode {
-            GrayscaleMode::Luminance => {
-                let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-                monochrome::grayscale_human_corrected(&mut photon_img);
-                RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
-            }
-            GrayscaleMode::Average => {
-                let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-                monochrome::grayscale(&mut photon_img);
-                RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
-            }
-            GrayscaleMode::RedChannel => {
-                let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-                monochrome::r_grayscale(&mut photon_img);
-                RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
-            }
-            GrayscaleMode::GreenChannel => {
-                let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-                monochrome::g_grayscale(&mut photon_img);
-                RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
-            }
-            GrayscaleMode::BlueChannel => {
-                let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-                monochrome::b_grayscale(&mut photon_img);
-                RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
-            }
+            GrayscaleMode::Average => apply!(GrayscaleMode::Average),
+            GrayscaleMode::Luminance => apply!(GrayscaleMode::Luminance),
+            GrayscaleMode::RedChannel => apply!(GrayscaleMode::RedChannel),
+            GrayscaleMode::GreenChannel => apply!(GrayscaleMode::GreenChannel),
+            GrayscaleMode::BlueChannel => apply!(GrayscaleMode::BlueChannel),
         }
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DuotoneConfig {
@@ -116,7 +129,7 @@ impl Effect for DuotoneConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SolarizationMode {
     Red,
     Green,
@@ -127,8 +140,9 @@ pub enum SolarizationMode {
     RGB,
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct SolarizationConfig {
@@ -208,8 +222,9 @@ impl Effect for SolarizationConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ThresholdConfig {
@@ -232,8 +247,9 @@ impl Effect for ThresholdConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct LevelConfig {
@@ -272,8 +288,9 @@ impl Effect for LevelConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ColorBalanceConfig {
@@ -303,3 +320,27 @@ impl Effect for ColorBalanceConfig {
         Some(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::tests::assert_photon;
+
+    #[test]
+    fn test_grayscale() {
+        let modes: [(GrayscaleMode, fn(&mut PhotonImage)); 5] = [
+            (GrayscaleMode::Average, monochrome::grayscale),
+            (
+                GrayscaleMode::Luminance,
+                monochrome::grayscale_human_corrected,
+            ),
+            (GrayscaleMode::RedChannel, monochrome::r_grayscale),
+            (GrayscaleMode::GreenChannel, monochrome::g_grayscale),
+            (GrayscaleMode::BlueChannel, monochrome::b_grayscale),
+        ];
+
+        for (mode, f) in modes {
+            assert_photon(&GrayscaleConfig::new().with_mode(mode), f);
+        }
+    }
+}
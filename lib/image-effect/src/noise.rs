@@ -1,8 +1,9 @@
 use crate::Effect;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, noise};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GaussianNoiseConfig;
 
 impl GaussianNoiseConfig {
@@ -20,7 +21,7 @@ impl Effect for GaussianNoiseConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PinkNoiseConfig;
 
 impl PinkNoiseConfig {
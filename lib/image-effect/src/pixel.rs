@@ -0,0 +1,142 @@
+//! Helpers for effects that only look at one pixel at a time. The image is
+//! split into bands of rows processed in parallel, the inner loops work on
+//! fixed size pixels so that they can be vectorized.
+
+use image::RgbaImage;
+use photon_rs::PhotonImage;
+use rayon::prelude::*;
+
+// About 256KB per band, small enough to balance the work between threads
+const BAND_PIXELS: usize = 64 * 1024;
+
+fn band_len(image: &RgbaImage) -> usize {
+    let row_len = image.width() as usize * 4;
+    let rows = (BAND_PIXELS / image.width().max(1) as usize).max(1);
+    (row_len * rows).max(4)
+}
+
+pub(crate) fn for_each_pixel(image: &mut RgbaImage, f: impl Fn(&mut [u8; 4]) + Sync) {
+    let band_len = band_len(image);
+
+    image.par_chunks_mut(band_len).for_each(|band| {
+        let (pixels, _) = band.as_chunks_mut::<4>();
+        pixels.iter_mut().for_each(&f);
+    });
+}
+
+/// Run `f` on chunks of `N` bytes with `N` a multiple of 4, processing a
+/// few pixels at a time lets the compiler vectorize the loops in `f`
+pub(crate) fn for_each_chunk<const N: usize>(
+    image: &mut RgbaImage,
+    f: impl Fn(&mut [u8; N]) + Sync,
+) {
+    let band_len = band_len(image);
+
+    image.par_chunks_mut(band_len).for_each(|band| {
+        let (chunks, rest) = band.as_chunks_mut::<N>();
+        chunks.iter_mut().for_each(&f);
+
+        if !rest.is_empty() {
+            let mut chunk = [0; N];
+            chunk[..rest.len()].copy_from_slice(rest);
+            f(&mut chunk);
+            rest.copy_from_slice(&chunk[..rest.len()]);
+        }
+    });
+}
+
+/// Add a signed offset to each RGB channel, clamped to [0, 255]
+pub(crate) fn add_rgb(image: &mut RgbaImage, offsets: [i16; 3]) {
+    let [r, g, b] = offsets.map(|v| v.clamp(-255, 255));
+    let add: [u8; 16] = std::array::from_fn(|i| [r, g, b, 0][i % 4].max(0) as u8);
+    let sub: [u8; 16] = std::array::from_fn(|i| [r, g, b, 0][i % 4].min(0).unsigned_abs() as u8);
+
+    for_each_chunk(image, |chunk: &mut [u8; 16]| {
+        for i in 0..16 {
+            chunk[i] = chunk[i].saturating_add(add[i]).saturating_sub(sub[i]);
+        }
+    });
+}
+
+/// Map each RGB channel through its lookup table, `opaque` sets alpha to 255
+pub(crate) fn apply_lut(image: &mut RgbaImage, tables: &[[u8; 256]; 3], opaque: bool) {
+    for_each_pixel(image, |pixel| {
+        for (value, table) in pixel.iter_mut().zip(tables) {
+            *value = table[*value as usize];
+        }
+        if opaque {
+            pixel[3] = 255;
+        }
+    });
+}
+
+/// Build the lookup tables from `f(channel, value)`
+pub(crate) fn lut(f: impl Fn(usize, u8) -> u8) -> [[u8; 256]; 3] {
+    let mut tables = [[0; 256]; 3];
+    for (channel, table) in tables.iter_mut().enumerate() {
+        for (value, entry) in table.iter_mut().enumerate() {
+            *entry = f(channel, value as u8);
+        }
+    }
+    tables
+}
+
+/// Run a per-pixel photon-rs function on bands of the image in parallel,
+/// it must not depend on the pixel position or on the other pixels
+pub(crate) fn par_photon(mut image: RgbaImage, f: impl Fn(&mut PhotonImage) + Sync) -> RgbaImage {
+    let width = image.width();
+    if width == 0 || image.height() == 0 {
+        return image;
+    }
+
+    let band_len = band_len(&image);
+
+    image.par_chunks_mut(band_len).for_each(|band| {
+        let rows = (band.len() / (width as usize * 4)) as u32;
+        let mut photon_img = PhotonImage::new(band.to_vec(), width, rows);
+        f(&mut photon_img);
+        band.copy_from_slice(&photon_img.get_raw_pixels());
+    });
+
+    image
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Effect;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    // Random pixels over two bands, with rows that aren't a multiple of the
+    // chunks
+    pub(crate) fn sample_image() -> RgbaImage {
+        let (width, height) = (257, 300);
+        let mut rng = StdRng::seed_from_u64(7);
+        let raw = (0..width * height * 4).map(|_| rng.random()).collect();
+        RgbaImage::from_raw(width, height, raw).unwrap()
+    }
+
+    pub(crate) fn photon(image: &RgbaImage, f: impl FnOnce(&mut PhotonImage)) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        let mut photon_img = PhotonImage::new(image.to_vec(), width, height);
+        f(&mut photon_img);
+        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels()).unwrap()
+    }
+
+    pub(crate) fn assert_same(actual: &RgbaImage, expected: &RgbaImage) {
+        assert_eq!(actual.dimensions(), expected.dimensions());
+        let diff = actual
+            .enumerate_pixels()
+            .zip(expected.pixels())
+            .find(|((_, _, a), b)| a != b);
+        if let Some(((x, y, a), b)) = diff {
+            panic!("pixel ({x}, {y}) is {a:?}, photon-rs gives {b:?}");
+        }
+    }
+
+    // The effect gives the same pixels as the photon-rs function on the sample
+    pub(crate) fn assert_photon(effect: &impl Effect, f: impl FnOnce(&mut PhotonImage)) {
+        let image = sample_image();
+        assert_same(&effect.apply(image.clone()).unwrap(), &photon(&image, f));
+    }
+}
@@ -3,9 +3,11 @@ use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, filters};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct PresetFilterConfig {
@@ -19,7 +21,7 @@ impl PresetFilterConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PresetFilter {
     Oceanic,
     Islands,
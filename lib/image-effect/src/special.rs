@@ -1,16 +1,18 @@
-use crate::Effect;
+use crate::{Effect, pixel};
 use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, effects};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct BrightnessConfig {
     #[derivative(Default(value = "10"))]
-    brightness: i32, // [-255, 255]
+    pub(crate) brightness: i32, // [-255, 255]
 }
 
 impl BrightnessConfig {
@@ -20,21 +22,21 @@ impl BrightnessConfig {
 }
 
 impl Effect for BrightnessConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        effects::adjust_brightness(&mut photon_img, self.brightness as i16);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        let brightness = self.brightness.clamp(-255, 255) as i16;
+        pixel::add_rgb(&mut image, [brightness; 3]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ContrastConfig {
     #[derivative(Default(value = "10.0"))]
-    contrast: f32, // [-255.0, 255.0]
+    pub(crate) contrast: f32, // [-255.0, 255.0]
 }
 
 impl ContrastConfig {
@@ -43,17 +45,26 @@ impl ContrastConfig {
     }
 }
 
+impl ContrastConfig {
+    // Same curve as photon-rs `adjust_contrast`
+    pub(crate) fn lut(&self) -> [[u8; 256]; 3] {
+        let contrast = self.contrast.clamp(-255.0, 255.0);
+        let factor = (259.0 * (contrast + 255.0)) / (255.0 * (259.0 - contrast));
+        let offset = -128.0 * factor + 128.0;
+        pixel::lut(|_, v| (v as f32 * factor + offset).clamp(0.0, 255.0) as u8)
+    }
+}
+
 impl Effect for ContrastConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        effects::adjust_contrast(&mut photon_img, self.contrast);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::apply_lut(&mut image, &self.lut(), true);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct OffsetConfig {
@@ -79,8 +90,9 @@ impl Effect for OffsetConfig {
 }
 
 /// Offset red channel effect configuration
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct OffsetRedConfig {
@@ -103,8 +115,9 @@ impl Effect for OffsetRedConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct OffsetGreenConfig {
@@ -127,8 +140,9 @@ impl Effect for OffsetGreenConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct OffsetBlueConfig {
@@ -151,8 +165,9 @@ impl Effect for OffsetBlueConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct MultipleOffsetsConfig {
@@ -184,7 +199,7 @@ impl Effect for MultipleOffsetsConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct HalftoneConfig;
 
 impl HalftoneConfig {
@@ -202,7 +217,7 @@ impl Effect for HalftoneConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct PrimaryConfig;
 
 impl PrimaryConfig {
@@ -220,7 +235,7 @@ impl Effect for PrimaryConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ColorizeConfig;
 
 impl ColorizeConfig {
@@ -238,13 +253,14 @@ impl Effect for ColorizeConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct IncBrightnessConfig {
     #[derivative(Default(value = "10"))]
-    brightness: u8,
+    pub(crate) brightness: u8,
 }
 
 impl IncBrightnessConfig {
@@ -254,21 +270,20 @@ impl IncBrightnessConfig {
 }
 
 impl Effect for IncBrightnessConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        effects::inc_brightness(&mut photon_img, self.brightness);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [self.brightness as i16; 3]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DecBrightnessConfig {
     #[derivative(Default(value = "10"))]
-    brightness: u8,
+    pub(crate) brightness: u8,
 }
 
 impl DecBrightnessConfig {
@@ -278,16 +293,15 @@ impl DecBrightnessConfig {
 }
 
 impl Effect for DecBrightnessConfig {
-    fn apply(&self, image: RgbaImage) -> Option<RgbaImage> {
-        let (width, height) = (image.width(), image.height());
-        let mut photon_img = PhotonImage::new(image.into_raw(), width, height);
-        effects::dec_brightness(&mut photon_img, self.brightness);
-        RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
+    fn apply(&self, mut image: RgbaImage) -> Option<RgbaImage> {
+        pixel::add_rgb(&mut image, [-(self.brightness as i16); 3]);
+        Some(image)
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct HorizontalStripsConfig {
@@ -310,8 +324,9 @@ impl Effect for HorizontalStripsConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ColorHorizontalStripsConfig {
@@ -341,8 +356,9 @@ impl Effect for ColorHorizontalStripsConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct VerticalStripsConfig {
@@ -365,8 +381,9 @@ impl Effect for VerticalStripsConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct ColorVerticalStripsConfig {
@@ -396,8 +413,9 @@ impl Effect for ColorVerticalStripsConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct OilConfig {
@@ -422,7 +440,7 @@ impl Effect for OilConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct FrostedGlassConfig;
 
 impl FrostedGlassConfig {
@@ -440,7 +458,7 @@ impl Effect for FrostedGlassConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct NormalizeConfig;
 
 impl NormalizeConfig {
@@ -458,8 +476,9 @@ impl Effect for NormalizeConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct DitherConfig {
@@ -481,3 +500,64 @@ impl Effect for DitherConfig {
         RgbaImage::from_raw(width, height, photon_img.get_raw_pixels())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel::tests::{assert_photon, assert_same, photon, sample_image};
+
+    // photon-rs leaves the last pixel out of the brightness changes, the
+    // effects change every pixel
+    fn assert_brightness(effect: &impl Effect, f: impl FnOnce(&mut PhotonImage), offset: i16) {
+        let image = sample_image();
+        let mut actual = effect.apply(image.clone()).unwrap();
+        let mut expected = photon(&image, f);
+
+        let (width, height) = image.dimensions();
+        let last = *image.get_pixel(width - 1, height - 1);
+        let brightened = actual.get_pixel(width - 1, height - 1);
+        for channel in 0..3 {
+            let value = (last[channel] as i16 + offset).clamp(0, 255) as u8;
+            assert_eq!(brightened[channel], value);
+        }
+        assert_eq!(brightened[3], last[3]);
+
+        for image in [&mut actual, &mut expected] {
+            image.put_pixel(width - 1, height - 1, last);
+        }
+        assert_same(&actual, &expected);
+    }
+
+    #[test]
+    fn test_brightness() {
+        for brightness in [-255, -40, 0, 40, 255] {
+            assert_brightness(
+                &BrightnessConfig::new().with_brightness(brightness),
+                |img| effects::adjust_brightness(img, brightness as i16),
+                brightness as i16,
+            );
+        }
+
+        for brightness in [0, 40, 255] {
+            assert_brightness(
+                &IncBrightnessConfig::new().with_brightness(brightness),
+                |img| effects::inc_brightness(img, brightness),
+                brightness as i16,
+            );
+            assert_brightness(
+                &DecBrightnessConfig::new().with_brightness(brightness),
+                |img| effects::dec_brightness(img, brightness),
+                -(brightness as i16),
+            );
+        }
+    }
+
+    #[test]
+    fn test_contrast() {
+        for contrast in [-300.0, -80.0, 0.0, 10.0, 254.0, 300.0] {
+            assert_photon(&ContrastConfig::new().with_contrast(contrast), |img| {
+                effects::adjust_contrast(img, contrast)
+            });
+        }
+    }
+}
@@ -3,14 +3,16 @@ use derivative::Derivative;
 use derive_setters::Setters;
 use image::RgbaImage;
 use photon_rs::{PhotonImage, conv};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct EdgeDetectionConfig {
     #[derivative(Default(value = "EdgeDetectionMode::Standard"))]
-    mode: EdgeDetectionMode,
+    pub(crate) mode: EdgeDetectionMode,
 }
 
 impl EdgeDetectionConfig {
@@ -19,7 +21,7 @@ impl EdgeDetectionConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum EdgeDetectionMode {
     Standard,
     SobelHorizontal,
@@ -51,7 +53,7 @@ impl Effect for EdgeDetectionConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct EmbossConfig;
 
@@ -76,7 +78,7 @@ impl Effect for EmbossConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct SharpenConfig;
 
@@ -101,8 +103,9 @@ impl Effect for SharpenConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct PixelateConfig {
@@ -125,8 +128,9 @@ impl Effect for PixelateConfig {
     }
 }
 
-#[derive(Debug, Clone, Derivative, Setters)]
+#[derive(Debug, Clone, Derivative, Setters, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 #[setters(prefix = "with_")]
 #[non_exhaustive]
 pub struct PosterizeConfig {
@@ -0,0 +1,22 @@
+use mp4m::mp4_processor::{Mp4Processor, Mp4ProcessorConfigBuilder, VideoConfigBuilder};
+use std::io::BufReader;
+
+// Usage: cat data/test.mp4 | cargo run --example mp4_stream_remux_demo -p mp4m
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let output_file = "data/tmp/remux_output.mp4";
+
+    let config = Mp4ProcessorConfigBuilder::default()
+        .save_path(output_file.into())
+        .video_config(VideoConfigBuilder::default().build()?)
+        .build()?;
+
+    // Stdin is not seekable, so this goes through the streaming path
+    let mut processor = Mp4Processor::new(config);
+    processor.remux_from_reader(BufReader::new(std::io::stdin().lock()))?;
+
+    log::info!("Remuxed stdin to `{output_file}`");
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use crate::SampleType;
-use audio_utils::audio::{
-    mono_to_stereo, multi_to_mono, multi_to_stereo, normalize_audio, resample_audio,
+use audio_utils::{
+    audio::{multi_to_mono, multi_to_stereo},
+    mixer::Mixer,
+    resample::{ResampleQuality, Resampler},
 };
 use crossbeam::channel::{Receiver, Sender, bounded};
 use derive_builder::Builder;
@@ -8,6 +10,8 @@ use hound::{SampleFormat, WavSpec, WavWriter};
 use std::{fs::File, io::BufWriter, marker::PhantomData, path::PathBuf};
 use thiserror::Error;
 
+pub use audio_utils::mixer::{DuckingConfig, MixerSource};
+
 const FRAME_DURATION_MS: usize = 20;
 
 /// Common audio sample rates
@@ -72,16 +76,32 @@ pub struct AudioProcessorConfig<T> {
 
     convert_to_mono: bool,
 
+    /// Lower the ducked tracks while a sidechain track is active
+    #[builder(default)]
+    ducking: Option<DuckingConfig>,
+
     output_destination: Option<OutputDestination<T>>,
 }
 
 pub struct AudioProcessor<T: SampleType = f32> {
     config: AudioProcessorConfig<T>,
     max_channels: u16,
+    mixer: Mixer,
     specs: Vec<WavSpec>,
     buffers: Vec<Vec<f32>>,
+
+    // Tracks of the target sample rate have none
+    resamplers: Vec<Option<Resampler>>,
     original_channels: Vec<u16>,
     sample_receiver: Vec<Receiver<Vec<f32>>>,
+
+    // The samples left to drop at the start of the advanced tracks
+    skip_samples: Vec<usize>,
+
+    // Buffered frames of a track before the missing ones are padded with
+    // silence. The shifted tracks lead the others by their offset
+    stall_frames: usize,
+
     writer: Option<WavWriter<BufWriter<File>>>,
     _marker: PhantomData<T>,
 }
@@ -89,27 +109,70 @@ pub struct AudioProcessor<T: SampleType = f32> {
 impl<T: SampleType> AudioProcessor<T> {
     pub fn new(config: AudioProcessorConfig<T>) -> AudioProcessor<T> {
         Self {
-            config,
             max_channels: 1,
+            mixer: Mixer::new(config.target_sample_rate, 1).with_ducking(config.ducking.clone()),
             specs: vec![],
             buffers: vec![],
+            resamplers: vec![],
             original_channels: vec![],
             sample_receiver: vec![],
+            skip_samples: vec![],
+            stall_frames: 3,
             writer: None,
             _marker: PhantomData,
+            config,
         }
     }
 
-    pub fn add_track(&mut self, mut spec: WavSpec) -> Sender<Vec<f32>> {
-        log::info!("add track: {spec:?}");
+    pub fn add_track(&mut self, spec: WavSpec) -> Sender<Vec<f32>> {
+        self.add_source_track(spec, MixerSource::default())
+    }
+
+    /// Add a track with its gain, panning, offset and ducking role in the mix
+    pub fn add_source_track(&mut self, mut spec: WavSpec, source: MixerSource) -> Sender<Vec<f32>> {
+        log::info!("add track: {spec:?}, {source:?}");
+
+        let offset_ms = source.offset_ms;
 
         self.original_channels.push(spec.channels);
         spec.channels = spec.channels.min(2); // max support channel size is 2
         self.max_channels = self.max_channels.max(spec.channels);
+        self.mixer.set_channels(self.max_channels);
+        self.mixer.add_source(source.with_channels(spec.channels));
+
+        // Keep the filter state across frames so that frame edges do not click
+        let resampler = if spec.sample_rate != self.config.target_sample_rate {
+            Resampler::new(
+                spec.sample_rate,
+                self.config.target_sample_rate,
+                spec.channels,
+                ResampleQuality::Medium,
+            )
+            .inspect_err(|e| log::warn!("create resampler failed: {e}"))
+            .ok()
+        } else {
+            None
+        };
+
+        let offset_samples = (offset_ms.unsigned_abs() as u64 * spec.sample_rate as u64 / 1000)
+            as usize
+            * spec.channels as usize;
+        self.stall_frames = self
+            .stall_frames
+            .max(3 + offset_ms.unsigned_abs().div_ceil(FRAME_DURATION_MS as u32) as usize);
+
+        // The delayed track starts with silence, the advanced one loses its start
+        let mut buffer = Vec::with_capacity(spec.sample_rate as usize * 3);
+        if offset_ms > 0 {
+            buffer.resize(offset_samples, 0.0);
+            self.skip_samples.push(0);
+        } else {
+            self.skip_samples.push(offset_samples);
+        }
 
         self.specs.push(spec);
-        self.buffers
-            .push(Vec::with_capacity(spec.sample_rate as usize * 3));
+        self.resamplers.push(resampler);
+        self.buffers.push(buffer);
 
         let (sender, receiver) = bounded(self.config.channel_size);
         self.sample_receiver.push(receiver);
@@ -131,6 +194,12 @@ impl<T: SampleType> AudioProcessor<T> {
                     samples
                 };
 
+                let skip = self.skip_samples[i].min(samples.len());
+                if skip > 0 {
+                    samples.drain(..skip);
+                    self.skip_samples[i] -= skip;
+                }
+
                 self.convert_samples_to_f32(&mut samples, i);
                 self.buffers[i].extend(samples);
             }
@@ -153,18 +222,18 @@ impl<T: SampleType> AudioProcessor<T> {
             }
 
             if !is_all_track_ready {
-                if max_frames < 3 {
+                if max_frames < self.stall_frames {
                     return Ok(());
                 } else {
                     log::debug!(
                         "At least one audio buffer samples counts is great than {}ms samples counts",
-                        3 * FRAME_DURATION_MS
+                        self.stall_frames * FRAME_DURATION_MS
                     );
                 }
             }
 
             // Process all tracks with the same amount of samples
-            let mut all_processed_tracks = Vec::new();
+            let mut tracks = Vec::with_capacity(self.specs.len());
             for i in 0..self.specs.len() {
                 let spec = &self.specs[i];
                 let samples_per_frame =
@@ -176,48 +245,14 @@ impl<T: SampleType> AudioProcessor<T> {
                     self.buffers[i].extend(vec![0.0; silence_samples]);
                 };
 
-                let processed = self.resamples(i, samples_per_frame)?;
-
-                if !processed.is_empty() {
-                    all_processed_tracks.push(processed);
-                }
+                tracks.push(self.resamples(i, samples_per_frame)?);
             }
 
-            if all_processed_tracks.is_empty() {
+            if tracks.iter().all(Vec::is_empty) {
                 return Ok(());
             }
 
-            // Unify channel counts before mixing
-            let mut unified_tracks = Vec::new();
-            for (i, track_samples) in all_processed_tracks.into_iter().enumerate() {
-                let spec = &self.specs[i];
-                // After resampling, all tracks should have the same sample rate but may have different channel counts
-                // Convert to max_channels for consistent mixing
-                if spec.channels == 1 && self.max_channels > 1 {
-                    unified_tracks.push(mono_to_stereo(&track_samples));
-                } else {
-                    unified_tracks.push(track_samples);
-                }
-            }
-
-            let mut final_samples = if unified_tracks.len() > 1 {
-                self.mix_multiple_tracks(&unified_tracks)
-            } else {
-                unified_tracks[0].clone()
-            };
-
-            // Apply mono conversion after mixing if needed
-            if self.config.convert_to_mono && self.max_channels > 1 {
-                final_samples = multi_to_mono(&final_samples, self.max_channels);
-            }
-
-            if self.specs.len() > 1 {
-                // TODO: normalize audio may cause sound unbalanced compare to original sound
-                let normalized = normalize_audio(&final_samples);
-                self.handle_output(&normalized);
-            } else {
-                self.handle_output(&final_samples);
-            }
+            self.mix_tracks(&tracks);
         }
     }
 
@@ -242,14 +277,8 @@ impl<T: SampleType> AudioProcessor<T> {
             return Ok(Vec::new());
         }
 
-        let resampled_samples = if spec.sample_rate != self.config.target_sample_rate {
-            let samples = resample_audio(
-                &buffer[0..samples_to_process],
-                spec.sample_rate,
-                self.config.target_sample_rate,
-                spec.channels,
-            )?;
-
+        let resampled_samples = if let Some(resampler) = self.resamplers[track_index].as_mut() {
+            let samples = resampler.process(&buffer[0..samples_to_process]);
             buffer.drain(0..samples_to_process);
             samples
         } else {
@@ -275,32 +304,16 @@ impl<T: SampleType> AudioProcessor<T> {
         }
     }
 
-    fn mix_multiple_tracks(&self, track_samples: &[Vec<f32>]) -> Vec<f32> {
-        if track_samples.is_empty() {
-            return Vec::new();
-        }
-
-        // All tracks should already have the same channel count (max_channels)
-        // due to the conversion in resamples
-        let max_len = track_samples.iter().map(|s| s.len()).max().unwrap_or(0);
-        let mut mixed = vec![0.0; max_len];
-
-        for samples in track_samples {
-            for (i, &sample) in samples.iter().enumerate() {
-                if i < mixed.len() {
-                    mixed[i] += sample;
-                }
-            }
-        }
+    fn mix_tracks(&mut self, tracks: &[Vec<f32>]) {
+        let inputs = tracks.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let mut final_samples = self.mixer.mix(&inputs);
 
-        // Apply averaging if we have multiple tracks
-        if track_samples.len() > 1 {
-            for sample in &mut mixed {
-                *sample /= track_samples.len() as f32;
-            }
+        // Apply mono conversion after mixing if needed
+        if self.config.convert_to_mono && self.max_channels > 1 {
+            final_samples = multi_to_mono(&final_samples, self.max_channels);
         }
 
-        mixed
+        self.handle_output(&final_samples);
     }
 
     fn handle_output(&mut self, samples: &[f32]) {
@@ -422,55 +435,20 @@ impl<T: SampleType> AudioProcessor<T> {
     pub fn flush(&mut self) -> Result<(), AudioError> {
         // Process any remaining samples in buffers
         loop {
-            let mut all_processed_tracks = vec![];
+            let mut tracks = Vec::with_capacity(self.specs.len());
 
             for i in 0..self.specs.len() {
                 let spec = &self.specs[i];
                 let samples_per_second = spec.sample_rate as usize * spec.channels as usize;
                 let samples_to_process = self.buffers[i].len().min(samples_per_second);
-                let processed = self.resamples(i, samples_to_process)?;
-
-                if !processed.is_empty() {
-                    all_processed_tracks.push(processed);
-                }
+                tracks.push(self.resamples(i, samples_to_process)?);
             }
 
-            if all_processed_tracks.is_empty() {
+            if tracks.iter().all(Vec::is_empty) {
                 break;
             }
 
-            // Unify channel counts before mixing
-            let mut unified_tracks = Vec::new();
-            for (i, track_samples) in all_processed_tracks.iter().enumerate() {
-                let spec = &self.specs[i];
-                // After resampling, all tracks should have the same sample rate but may have different channel counts
-                // Convert to max_channels for consistent mixing
-                if spec.channels == 1 && self.max_channels > 1 {
-                    // Convert mono to stereo for mixing
-                    unified_tracks.push(mono_to_stereo(track_samples));
-                } else {
-                    unified_tracks.push(track_samples.clone());
-                }
-            }
-
-            let mut final_samples = if unified_tracks.len() > 1 {
-                self.mix_multiple_tracks(&unified_tracks)
-            } else {
-                unified_tracks[0].clone()
-            };
-
-            // Apply mono conversion after mixing if needed
-            if self.config.convert_to_mono && self.max_channels > 1 {
-                final_samples = multi_to_mono(&final_samples, self.max_channels);
-            }
-
-            if self.specs.len() > 1 {
-                // TODO: normalize audio may cause sound unbalanced compare to original sound
-                let normalized = normalize_audio(&final_samples);
-                self.handle_output(&normalized);
-            } else {
-                self.handle_output(&final_samples);
-            }
+            self.mix_tracks(&tracks);
         }
 
         if let Some(writer) = self.writer.take() {
@@ -480,3 +458,63 @@ impl<T: SampleType> AudioProcessor<T> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: WavSpec = WavSpec {
+        channels: 1,
+        sample_rate: 1000,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    // 100 ms of a constant microphone and speaker level, the microphone shifted
+    fn mix_shifted_mic(offset_ms: i32) -> Vec<f32> {
+        let (sender, receiver) = bounded(1024);
+        let config = AudioProcessorConfigBuilder::default()
+            .target_sample_rate(SPEC.sample_rate)
+            .convert_to_mono(false)
+            .output_destination(Some(OutputDestination::<f32>::Channel(sender)))
+            .build()
+            .unwrap();
+
+        let mut processor = AudioProcessor::new(config);
+        let mic =
+            processor.add_source_track(SPEC, MixerSource::default().with_offset_ms(offset_ms));
+        let speaker = processor.add_track(SPEC);
+
+        mic.send(vec![0.1; 100]).unwrap();
+        speaker.send(vec![0.2; 100]).unwrap();
+        processor.process_samples().unwrap();
+        processor.flush().unwrap();
+
+        receiver.try_iter().flatten().collect()
+    }
+
+    fn assert_levels(samples: &[f32], levels: &[(usize, f32)]) {
+        assert_eq!(
+            samples.len(),
+            levels.iter().map(|(count, _)| count).sum::<usize>()
+        );
+
+        let mut samples = samples.iter();
+        for (count, level) in levels {
+            for sample in samples.by_ref().take(*count) {
+                assert!((sample - level).abs() < 1e-6, "{sample} != {level}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_mic_offset() {
+        assert_levels(&mix_shifted_mic(0), &[(100, 0.3)]);
+
+        // The speaker keeps its place, the microphone starts 40 ms later
+        assert_levels(&mix_shifted_mic(40), &[(40, 0.2), (60, 0.3), (40, 0.1)]);
+
+        // The first 40 ms of the microphone are dropped
+        assert_levels(&mix_shifted_mic(-40), &[(60, 0.3), (40, 0.2)]);
+    }
+}
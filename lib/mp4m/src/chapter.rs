@@ -0,0 +1,111 @@
+use crate::udta::{make_box, replace_udta_child};
+use std::{fs::OpenOptions, path::Path, time::Duration};
+
+// Nero `chpl` stores at most 255 chapters with titles of at most 255 bytes
+const MAX_CHAPTERS: usize = u8::MAX as usize;
+const MAX_TITLE_LEN: usize = u8::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub timestamp: Duration,
+    pub title: String,
+}
+
+impl Chapter {
+    pub fn new(timestamp: Duration, title: impl Into<String>) -> Self {
+        Self {
+            timestamp,
+            title: title.into(),
+        }
+    }
+}
+
+/// Write `chapters` into a finalized MP4 file, replacing the chapters it has.
+/// Empty `chapters` remove them.
+pub fn write_chapters(path: impl AsRef<Path>, chapters: &[Chapter]) -> std::io::Result<()> {
+    let mut chapters = chapters.to_vec();
+    chapters.sort_by_key(|chapter| chapter.timestamp);
+
+    let chpl = (!chapters.is_empty()).then(|| chpl_box(&chapters));
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    replace_udta_child(&mut file, b"chpl", chpl.as_deref())
+}
+
+/// Build a Nero chapter list (`chpl`) box from chapters sorted by timestamp.
+pub(crate) fn chpl_box(chapters: &[Chapter]) -> Vec<u8> {
+    if chapters.len() > MAX_CHAPTERS {
+        log::warn!(
+            "Only the first {MAX_CHAPTERS} of {} chapters are written",
+            chapters.len()
+        );
+    }
+
+    let chapters = &chapters[..chapters.len().min(MAX_CHAPTERS)];
+
+    // version 1, flags 0, reserved, chapter count
+    let mut payload = vec![1, 0, 0, 0, 0, 0, 0, 0, chapters.len() as u8];
+
+    for chapter in chapters {
+        // Start time is in 100 nanosecond units
+        let start = (chapter.timestamp.as_nanos() / 100) as u64;
+        let title = truncate_utf8(&chapter.title, MAX_TITLE_LEN);
+
+        payload.extend_from_slice(&start.to_be_bytes());
+        payload.push(title.len() as u8);
+        payload.extend_from_slice(title.as_bytes());
+    }
+
+    make_box(b"chpl", &payload)
+}
+
+fn truncate_utf8(text: &str, max_len: usize) -> &str {
+    if text.len() <= max_len {
+        return text;
+    }
+
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chpl_box() {
+        let chapters = [
+            Chapter::new(Duration::from_millis(0), "Intro"),
+            Chapter::new(Duration::from_millis(1500), "章节"),
+        ];
+
+        let data = chpl_box(&chapters);
+        assert_eq!(&data[..4], &(data.len() as u32).to_be_bytes());
+        assert_eq!(&data[4..8], b"chpl");
+        assert_eq!(&data[8..17], &[1, 0, 0, 0, 0, 0, 0, 0, 2]);
+
+        let mut expected = vec![];
+        expected.extend_from_slice(&0u64.to_be_bytes());
+        expected.push(5);
+        expected.extend_from_slice(b"Intro");
+        expected.extend_from_slice(&15_000_000u64.to_be_bytes());
+        expected.push(6);
+        expected.extend_from_slice("章节".as_bytes());
+        assert_eq!(&data[17..], &expected);
+    }
+
+    #[test]
+    fn test_chpl_box_limits() {
+        // The title is cut at a char boundary
+        let title = "章".repeat(100);
+        let chapters = vec![Chapter::new(Duration::ZERO, title); MAX_CHAPTERS + 1];
+
+        let data = chpl_box(&chapters);
+        assert_eq!(data[16] as usize, MAX_CHAPTERS);
+        assert_eq!(data[25], 255);
+        assert_eq!(&data[26..26 + 255], "章".repeat(85).as_bytes());
+        assert_eq!(data.len(), 17 + MAX_CHAPTERS * (8 + 1 + 255));
+    }
+}
@@ -1,13 +1,22 @@
 pub mod audio_processor;
+pub mod chapter;
+pub mod metadata;
 pub mod mp4_processor;
+pub mod mp4_stream_reader;
 pub mod sample_type;
 
+mod udta;
+
 pub use audio_processor::{
-    AudioProcessor, AudioProcessorConfigBuilder, OutputDestination, sample_rate,
+    AudioProcessor, AudioProcessorConfigBuilder, DuckingConfig, MixerSource, OutputDestination,
+    sample_rate,
 };
+pub use chapter::{Chapter, write_chapters};
+pub use metadata::{Mp4Metadata, Mp4MetadataBuilder};
 pub use mp4_processor::{
     AudioConfig, Mp4Processor, Mp4ProcessorConfigBuilder, VideoConfig, VideoFrameType,
 };
+pub use mp4_stream_reader::{Mp4StreamReader, Mp4StreamReaderError, StreamSample, StreamTrack};
 pub use sample_type::{I24, SampleType};
 
 pub use crossbeam::channel::{Receiver, Sender, bounded};
@@ -0,0 +1,156 @@
+use crate::udta::make_box;
+use chrono::{DateTime, Utc};
+use derive_builder::Builder;
+
+// iTunes style well-known data type for UTF-8 text
+const DATA_TYPE_UTF8: u32 = 1;
+
+// ilst atoms of the tag keys
+const TAG_ATOMS: [(&str, &[u8; 4]); 5] = [
+    ("title", b"\xa9nam"),
+    ("artist", b"\xa9ART"),
+    ("comment", b"\xa9cmt"),
+    ("encoder", b"\xa9too"),
+    ("creation_time", b"\xa9day"),
+];
+
+/// Identifying metadata written into the `udta/meta/ilst` boxes.
+#[derive(Builder, Debug, Clone, Default)]
+#[builder(default, setter(into, strip_option))]
+pub struct Mp4Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub comment: Option<String>,
+    pub encoder: Option<String>,
+    pub creation_time: Option<DateTime<Utc>>,
+}
+
+impl Mp4Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.artist.is_none()
+            && self.comment.is_none()
+            && self.encoder.is_none()
+            && self.creation_time.is_none()
+    }
+
+    /// The present fields keyed as in the ffmpeg metadata, e.g. `title`.
+    pub fn tags(&self) -> Vec<(&'static str, String)> {
+        let creation_time = self
+            .creation_time
+            .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+
+        [
+            ("title", self.title.clone()),
+            ("artist", self.artist.clone()),
+            ("comment", self.comment.clone()),
+            ("encoder", self.encoder.clone()),
+            ("creation_time", creation_time),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| (key, value)))
+        .collect()
+    }
+
+    /// Build the `meta` box with an `ilst` item for every present field.
+    pub(crate) fn meta_box(&self) -> Option<Vec<u8>> {
+        let items = self
+            .tags()
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let (_, atom) = TAG_ATOMS.iter().find(|(name, _)| *name == key)?;
+                Some(ilst_item(atom, &value))
+            })
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            return None;
+        }
+
+        // hdlr: version/flags, pre_defined, handler type, reserved, empty name
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(b"mdir");
+        hdlr.extend_from_slice(b"appl");
+        hdlr.extend_from_slice(&[0u8; 9]);
+
+        let mut meta = vec![0u8; 4];
+        meta.extend(make_box(b"hdlr", &hdlr));
+        meta.extend(make_box(b"ilst", &items.concat()));
+
+        Some(make_box(b"meta", &meta))
+    }
+}
+
+fn ilst_item(name: &[u8; 4], value: &str) -> Vec<u8> {
+    // data: type indicator, locale, value
+    let mut data = Vec::with_capacity(value.len() + 8);
+    data.extend_from_slice(&DATA_TYPE_UTF8.to_be_bytes());
+    data.extend_from_slice(&0u32.to_be_bytes());
+    data.extend_from_slice(value.as_bytes());
+
+    make_box(name, &make_box(b"data", &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udta::split_boxes;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_meta_box() {
+        let metadata = Mp4MetadataBuilder::default()
+            .title("Demo")
+            .encoder("wayshot")
+            .creation_time(Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap())
+            .build()
+            .unwrap();
+
+        let meta = metadata.meta_box().unwrap();
+        let boxes = split_boxes(&meta).unwrap();
+        assert_eq!(boxes.len(), 1);
+        let (name, payload, _) = boxes[0];
+        assert_eq!(name, b"meta");
+        assert_eq!(u32::from_be_bytes(meta[..4].try_into().unwrap()), 156);
+
+        // meta is a full box, its children follow the version and flags
+        assert_eq!(&payload[..4], &[0; 4]);
+        let children = split_boxes(&payload[4..]).unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].0, b"hdlr");
+        assert_eq!(children[0].2.len(), 33);
+        assert_eq!(&children[0].1[8..12], b"mdir");
+
+        let (name, ilst, _) = children[1];
+        assert_eq!(name, b"ilst");
+        let items = split_boxes(ilst)
+            .unwrap()
+            .into_iter()
+            .map(|(name, item, whole)| {
+                let data = split_boxes(item).unwrap();
+                assert_eq!(data.len(), 1);
+                assert_eq!(data[0].0, b"data");
+                assert_eq!(&data[0].1[..8], &[0, 0, 0, 1, 0, 0, 0, 0]);
+                assert_eq!(whole.len(), data[0].2.len() + 8);
+                (name, String::from_utf8(data[0].1[8..].to_vec()).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            items,
+            [
+                (&b"\xa9nam"[..], "Demo".to_string()),
+                (&b"\xa9too"[..], "wayshot".to_string()),
+                (&b"\xa9day"[..], "2025-01-02T03:04:05Z".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_meta_box() {
+        let metadata = Mp4Metadata::default();
+        assert!(metadata.is_empty());
+        assert!(metadata.tags().is_empty());
+        assert!(metadata.meta_box().is_none());
+    }
+}
@@ -1,3 +1,9 @@
+use crate::{
+    chapter::{Chapter, chpl_box},
+    metadata::Mp4Metadata,
+    mp4_stream_reader::{Mp4StreamReader, Mp4StreamReaderError, StreamSample},
+    udta::{MP4_EPOCH_OFFSET_SECS, append_to_moov, make_box, set_creation_time},
+};
 use crossbeam::channel::{Receiver, Sender, bounded};
 use derive_builder::Builder;
 use fdk_aac::enc::{BitRate, ChannelMode, Encoder, EncoderParams, Transport};
@@ -6,7 +12,13 @@ use mp4::{
     AacConfig, AvcConfig, ChannelConfig, Mp4Config, Mp4Sample, Mp4Writer, SampleFreqIndex,
     TrackConfig, TrackType,
 };
-use std::{fs::File, io::BufWriter, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Read},
+    path::PathBuf,
+    time::Duration,
+};
 use thiserror::Error;
 use video_encoder::VIDEO_TIMESCALE;
 
@@ -19,6 +31,10 @@ const DEFAULT_SPS: [u8; 25] = [
 #[derive(Clone)]
 pub enum VideoFrameType {
     Frame(Vec<u8>),
+
+    /// The previous frame is shown for one more frame interval
+    Repeat,
+
     End,
 }
 
@@ -32,6 +48,9 @@ pub enum Mp4ProcessorError {
 
     #[error("AAC encoding error: {0}")]
     AacEncoding(String),
+
+    #[error("MP4 stream error: {0}")]
+    Stream(#[from] Mp4StreamReaderError),
 }
 
 #[derive(Builder, Clone)]
@@ -62,6 +81,9 @@ pub struct Mp4ProcessorConfig {
 
     #[builder(default = "1024")]
     pub channel_size: usize,
+
+    #[builder(default)]
+    pub metadata: Mp4Metadata,
 }
 
 pub struct Mp4Processor {
@@ -70,25 +92,37 @@ pub struct Mp4Processor {
     h264_receiver: Receiver<VideoFrameType>,
     total_video_frames: u64,
 
+    // Held until the next frame, so a repeat only extends its duration
+    pending_video_sample: Option<Mp4Sample>,
+
     aac_encoder: Vec<Encoder>,
     audio_config: Vec<AudioConfig>,
     audio_receiver: Vec<Receiver<Vec<f32>>>,
     audio_buffer_cache: Vec<Vec<f32>>,
+
+    chapter_sender: Sender<Chapter>,
+    chapter_receiver: Receiver<Chapter>,
+    chapters: Vec<Chapter>,
 }
 
 impl Mp4Processor {
     pub fn new(config: Mp4ProcessorConfig) -> Self {
         let (h264_sender, h264_receiver) = bounded(config.channel_size);
+        let (chapter_sender, chapter_receiver) = bounded(config.channel_size);
 
         Self {
             config,
             h264_sender,
             h264_receiver,
             total_video_frames: 0,
+            pending_video_sample: None,
             aac_encoder: vec![],
             audio_config: vec![],
             audio_receiver: vec![],
             audio_buffer_cache: vec![],
+            chapter_sender,
+            chapter_receiver,
+            chapters: vec![],
         }
     }
 
@@ -96,6 +130,16 @@ impl Mp4Processor {
         self.h264_sender.clone()
     }
 
+    /// Chapters sent while the processing loop runs are collected by the loop
+    /// and written on finalize.
+    pub fn chapter_sender(&self) -> Sender<Chapter> {
+        self.chapter_sender.clone()
+    }
+
+    pub fn add_chapter(&mut self, timestamp: Duration, title: impl Into<String>) {
+        self.chapters.push(Chapter::new(timestamp, title));
+    }
+
     pub fn add_audio_track(
         &mut self,
         config: AudioConfig,
@@ -411,6 +455,102 @@ impl Mp4Processor {
             .write_end()
             .map_err(|e| Mp4ProcessorError::Mp4(e.to_string()))?;
 
+        self.write_user_data(mp4_writer)?;
+
+        Ok(())
+    }
+
+    fn write_user_data(
+        &mut self,
+        mp4_writer: Mp4Writer<BufWriter<File>>,
+    ) -> Result<(), Mp4ProcessorError> {
+        self.chapters.extend(self.chapter_receiver.try_iter());
+        if self.chapters.is_empty() && self.config.metadata.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = mp4_writer
+            .into_writer()
+            .into_inner()
+            .map_err(|e| Mp4ProcessorError::Io(e.into_error()))?;
+
+        if let Some(creation_time) = self.config.metadata.creation_time {
+            let secs = creation_time.timestamp().max(0) as u64 + MP4_EPOCH_OFFSET_SECS;
+            set_creation_time(&mut file, secs)?;
+        }
+
+        let mut udta = vec![];
+        if !self.chapters.is_empty() {
+            self.chapters.sort_by_key(|chapter| chapter.timestamp);
+            udta.extend(chpl_box(&self.chapters));
+        }
+
+        if let Some(meta) = self.config.metadata.meta_box() {
+            udta.extend(meta);
+        }
+
+        if !udta.is_empty() {
+            append_to_moov(&mut file, &make_box(b"udta", &udta))?;
+        }
+
+        log::info!(
+            "Wrote {} chapters and metadata into `{}`",
+            self.chapters.len(),
+            self.config.save_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Remux MP4 or fragmented MP4 data read from `reader` into `save_path`.
+    ///
+    /// Samples are copied without re-encoding. The reader doesn't need to be
+    /// seekable, so a file can be remuxed while it's still being downloaded.
+    pub fn remux_from_reader<R: Read>(&mut self, reader: R) -> Result<(), Mp4ProcessorError> {
+        let mut stream_reader = Mp4StreamReader::new(reader);
+        let tracks = stream_reader.read_tracks()?.to_vec();
+
+        let mut mp4_writer = self.setup_mp4_writer()?;
+        let mut track_ids = HashMap::new();
+        for (index, track) in tracks.iter().enumerate() {
+            mp4_writer
+                .add_track(&track.config)
+                .map_err(|e| Mp4ProcessorError::Mp4(e.to_string()))?;
+
+            // Output track IDs are assigned in insertion order starting from 1
+            track_ids.insert(track.track_id, (index as u32 + 1, track.config.track_type));
+            log::info!(
+                "Remux track {} ({:?}) to track ID {}",
+                track.track_id,
+                track.config.track_type,
+                index + 1
+            );
+        }
+
+        while let Some(StreamSample { track_id, sample }) = stream_reader.next_sample()? {
+            let Some(&(output_track_id, track_type)) = track_ids.get(&track_id) else {
+                continue;
+            };
+
+            if track_type == TrackType::Video {
+                self.total_video_frames += 1;
+            }
+
+            mp4_writer
+                .write_sample(output_track_id, &sample)
+                .map_err(|e| Mp4ProcessorError::Mp4(e.to_string()))?;
+        }
+
+        mp4_writer
+            .write_end()
+            .map_err(|e| Mp4ProcessorError::Mp4(e.to_string()))?;
+
+        log::info!(
+            "Remuxed {} video frames into `{}`",
+            self.total_video_frames,
+            self.config.save_path.display()
+        );
+
         Ok(())
     }
 
@@ -436,13 +576,33 @@ impl Mp4Processor {
             bytes: data.into(),
         };
 
-        if let Err(e) = mp4_writer.write_sample(1, &sample) {
+        if let Some(sample) = self.pending_video_sample.replace(sample)
+            && let Err(e) = mp4_writer.write_sample(1, &sample)
+        {
             log::warn!("Write video sample failed: {e}");
         }
 
         *video_timestamp += duration as u64;
     }
 
+    fn repeat_video_frame(&mut self, video_timestamp: &mut u64) {
+        let Some(ref mut sample) = self.pending_video_sample else {
+            return;
+        };
+
+        let duration = VIDEO_TIMESCALE / self.config.video_config.fps;
+        sample.duration += duration;
+        *video_timestamp += duration as u64;
+    }
+
+    fn flush_video_sample(&mut self, mp4_writer: &mut Mp4Writer<BufWriter<File>>) {
+        if let Some(sample) = self.pending_video_sample.take()
+            && let Err(e) = mp4_writer.write_sample(1, &sample)
+        {
+            log::warn!("Write video sample failed: {e}");
+        }
+    }
+
     pub fn is_keyframe_length_prefixed(data: &[u8]) -> bool {
         let mut i = 0;
         while i + 4 <= data.len() {
@@ -645,6 +805,9 @@ impl Mp4Processor {
                             VideoFrameType::Frame(data) => {
                                 self.process_video_frame(mp4_writer, video_timestamp, data);
                             },
+                            VideoFrameType::Repeat => {
+                                self.repeat_video_frame(video_timestamp);
+                            },
                             VideoFrameType::End => {
                                 log::info!("h264_receiver receive `End`");
                                 video_ended = true;
@@ -657,6 +820,10 @@ impl Mp4Processor {
                     }
                 }
                 default => {
+                    // The channel is bounded, so the chapters are taken out
+                    // while recording instead of on finalize
+                    self.chapters.extend(self.chapter_receiver.try_iter());
+
                     let all_ended = self.process_audio_receivers(
                         mp4_writer,
                         &audio_track_ids,
@@ -669,6 +836,8 @@ impl Mp4Processor {
                     }
 
                     if video_ended && audio_ended && self.h264_receiver.is_empty() {
+                        self.flush_video_sample(mp4_writer);
+
                         // Flush any remaining cached audio data before breaking
                         self.flush_audio_cache(
                             mp4_writer,
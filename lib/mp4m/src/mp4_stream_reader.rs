@@ -0,0 +1,864 @@
+use crate::udta::split_boxes;
+use mp4::{
+    BoxType, FtypBox, MediaConfig, MoofBox, MoovBox, Mp4Sample, ReadBox, TrackConfig, TrackType,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Cursor, Read},
+};
+use thiserror::Error;
+
+// Matches the default flags written by most fMP4 muxers for non-sync samples
+const SAMPLE_IS_NON_SYNC_FLAG: u32 = 0x0001_0000;
+
+// Boxes parsed in memory and single samples, the sizes come from the stream
+const MAX_PAYLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+// `mdat` payloads kept in memory until the `moov` box arrives
+const MAX_BUFFERED_MDAT_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum Mp4StreamReaderError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("MP4 parsing error: {0}")]
+    Mp4(#[from] mp4::Error),
+
+    #[error("Invalid stream: {0}")]
+    InvalidStream(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamTrack {
+    pub track_id: u32,
+    pub timescale: u32,
+    pub config: TrackConfig,
+}
+
+#[derive(Debug)]
+pub struct StreamSample {
+    pub track_id: u32,
+    pub sample: Mp4Sample,
+}
+
+#[derive(Debug, Clone)]
+struct SampleEntry {
+    track_id: u32,
+    offset: u64,
+    size: u32,
+    start_time: u64,
+    duration: u32,
+    rendering_offset: i32,
+    is_sync: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrackDefaults {
+    sample_duration: u32,
+    sample_size: u32,
+    sample_flags: u32,
+}
+
+/// Reads MP4 or fragmented MP4 data from a non-seekable stream.
+///
+/// Top-level boxes are consumed in order. `moov`/`moof` boxes are buffered and
+/// parsed, `mdat` payloads are streamed sample by sample when the sample table
+/// is already known (fast-start MP4 and fMP4). When `moov` comes after `mdat`
+/// the media data has to be kept in memory until the sample table arrives.
+pub struct Mp4StreamReader<R: Read> {
+    reader: R,
+    position: u64,
+    ftyp: Option<FtypBox>,
+    tracks: Vec<StreamTrack>,
+    track_defaults: HashMap<u32, TrackDefaults>,
+    fragment_decode_times: HashMap<u32, u64>,
+
+    // Samples of a progressive MP4, sorted by file offset
+    pending_entries: VecDeque<SampleEntry>,
+
+    // `mdat` payloads read before the `moov` box, keyed by file offset
+    buffered_mdats: Vec<(u64, Vec<u8>)>,
+
+    ready_samples: VecDeque<StreamSample>,
+    current_mdat_end: Option<u64>,
+    finished: bool,
+}
+
+impl<R: Read> Mp4StreamReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            position: 0,
+            ftyp: None,
+            tracks: vec![],
+            track_defaults: HashMap::new(),
+            fragment_decode_times: HashMap::new(),
+            pending_entries: VecDeque::new(),
+            buffered_mdats: vec![],
+            ready_samples: VecDeque::new(),
+            current_mdat_end: None,
+            finished: false,
+        }
+    }
+
+    pub fn ftyp(&self) -> Option<&FtypBox> {
+        self.ftyp.as_ref()
+    }
+
+    /// Consume boxes until the `moov` box has been parsed and return its tracks.
+    pub fn read_tracks(&mut self) -> Result<&[StreamTrack], Mp4StreamReaderError> {
+        while self.tracks.is_empty() {
+            if !self.read_next_box()? {
+                return Err(Mp4StreamReaderError::InvalidStream(
+                    "No moov box found before end of stream".to_string(),
+                ));
+            }
+        }
+
+        Ok(&self.tracks)
+    }
+
+    pub fn tracks(&self) -> &[StreamTrack] {
+        &self.tracks
+    }
+
+    /// Returns the next sample in stream order, `None` at the end of the stream.
+    pub fn next_sample(&mut self) -> Result<Option<StreamSample>, Mp4StreamReaderError> {
+        loop {
+            if let Some(sample) = self.ready_samples.pop_front() {
+                return Ok(Some(sample));
+            }
+
+            if self.current_mdat_end.is_some() {
+                if let Some(sample) = self.read_streamed_sample()? {
+                    return Ok(Some(sample));
+                }
+                continue;
+            }
+
+            if self.finished || !self.read_next_box()? {
+                self.finished = true;
+                return Ok(None);
+            }
+        }
+    }
+
+    fn read_next_box(&mut self) -> Result<bool, Mp4StreamReaderError> {
+        let box_start = self.position;
+        let Some((name, box_size, header_len)) = self.read_box_header()? else {
+            return Ok(false);
+        };
+
+        if box_size == 0 {
+            return Err(Mp4StreamReaderError::InvalidStream(
+                "Boxes extending to end of stream are not supported".to_string(),
+            ));
+        }
+        let payload_len = box_size.checked_sub(header_len).ok_or_else(|| {
+            Mp4StreamReaderError::InvalidStream(format!("Invalid box size {box_size}"))
+        })?;
+
+        // `read_box` expects the size of a box with a plain 8 bytes header
+        let parse_size = payload_len + 8;
+
+        match name {
+            BoxType::FtypBox => {
+                let mut cursor = self.read_box_into_cursor(name, payload_len)?;
+                self.ftyp = Some(FtypBox::read_box(&mut cursor, parse_size)?);
+            }
+            BoxType::MoovBox => {
+                let mut cursor = self.read_box_into_cursor(name, payload_len)?;
+                let moov = MoovBox::read_box(&mut cursor, parse_size)?;
+
+                // `MvexBox` only keeps one `trex`, each fragmented track has its own
+                self.track_defaults = track_defaults(&cursor.get_ref()[8..])?;
+                self.load_moov(&moov)?;
+            }
+            BoxType::MoofBox => {
+                let mut cursor = self.read_box_into_cursor(name, payload_len)?;
+                let moof = MoofBox::read_box(&mut cursor, parse_size)?;
+                self.load_moof(&moof, box_start)?;
+            }
+            BoxType::MdatBox => {
+                let payload_start = self.position;
+                if self.tracks.is_empty() {
+                    log::debug!("mdat before moov, buffering {payload_len} bytes");
+                    let buffered_len: u64 = self
+                        .buffered_mdats
+                        .iter()
+                        .map(|(_, data)| data.len() as u64)
+                        .sum();
+                    let payload = self.read_payload(
+                        payload_len,
+                        MAX_BUFFERED_MDAT_SIZE.saturating_sub(buffered_len),
+                    )?;
+                    self.buffered_mdats.push((payload_start, payload));
+                } else {
+                    self.current_mdat_end = Some(payload_start.saturating_add(payload_len));
+                }
+            }
+            _ => {
+                self.skip_payload(payload_len)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn read_box_header(&mut self) -> Result<Option<(BoxType, u64, u64)>, Mp4StreamReaderError> {
+        let mut buf = [0u8; 8];
+        match self.reader.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        self.position += 8;
+
+        let size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as u64;
+        let name = BoxType::from(u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]));
+
+        if size == 1 {
+            self.reader.read_exact(&mut buf)?;
+            self.position += 8;
+            return Ok(Some((name, u64::from_be_bytes(buf), 16)));
+        }
+
+        Ok(Some((name, size, 8)))
+    }
+
+    fn read_box_into_cursor(
+        &mut self,
+        name: BoxType,
+        payload_len: u64,
+    ) -> Result<Cursor<Vec<u8>>, Mp4StreamReaderError> {
+        let payload = self.read_payload(payload_len, MAX_PAYLOAD_SIZE)?;
+
+        let mut data = Vec::with_capacity(payload.len() + 8);
+        data.extend_from_slice(&((payload_len + 8) as u32).to_be_bytes());
+        data.extend_from_slice(&u32::from(name).to_be_bytes());
+        data.extend_from_slice(&payload);
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(8);
+        Ok(cursor)
+    }
+
+    fn read_payload(&mut self, len: u64, limit: u64) -> Result<Vec<u8>, Mp4StreamReaderError> {
+        if len > limit {
+            return Err(Mp4StreamReaderError::InvalidStream(format!(
+                "Payload of {len} bytes exceeds the limit of {limit} bytes"
+            )));
+        }
+
+        // Grows with the data read, a truncated stream doesn't allocate the declared size
+        let mut payload = vec![];
+        (&mut self.reader).take(len).read_to_end(&mut payload)?;
+        self.position += payload.len() as u64;
+
+        if (payload.len() as u64) < len {
+            return Err(Mp4StreamReaderError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Stream ended inside a box",
+            )));
+        }
+        Ok(payload)
+    }
+
+    fn skip_payload(&mut self, len: u64) -> Result<(), Mp4StreamReaderError> {
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+        self.position += skipped;
+
+        if skipped < len {
+            return Err(Mp4StreamReaderError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Stream ended inside a box",
+            )));
+        }
+        Ok(())
+    }
+
+    fn load_moov(&mut self, moov: &MoovBox) -> Result<(), Mp4StreamReaderError> {
+        for (index, trak) in moov.traks.iter().enumerate() {
+            let Some(config) = track_config(moov, index) else {
+                log::warn!("Skipping unsupported track {}", trak.tkhd.track_id);
+                continue;
+            };
+
+            self.tracks.push(StreamTrack {
+                track_id: trak.tkhd.track_id,
+                timescale: trak.mdia.mdhd.timescale,
+                config,
+            });
+        }
+
+        if self.tracks.is_empty() {
+            return Err(Mp4StreamReaderError::InvalidStream(
+                "No supported track in moov box".to_string(),
+            ));
+        }
+
+        // The samples of a fragmented MP4 are described by the `moof` boxes
+        if moov.mvex.is_some() {
+            return Ok(());
+        }
+
+        let mut entries = vec![];
+        for (index, trak) in moov.traks.iter().enumerate() {
+            if self.tracks.iter().any(|t| t.track_id == trak.tkhd.track_id) {
+                entries.extend(sample_table_entries(moov, index)?);
+            }
+        }
+        entries.sort_by_key(|entry| entry.offset);
+
+        // Media data that arrived before the moov box can be emitted right away
+        let buffered_mdats = std::mem::take(&mut self.buffered_mdats);
+        let mut streamed_entries = VecDeque::with_capacity(entries.len());
+        for entry in entries {
+            let buffered = buffered_mdats.iter().find_map(|(start, data)| {
+                let end = start + data.len() as u64;
+                let sample_end = entry.offset.checked_add(entry.size as u64)?;
+                (entry.offset >= *start && sample_end <= end).then(|| {
+                    let offset = (entry.offset - start) as usize;
+                    data[offset..offset + entry.size as usize].to_vec()
+                })
+            });
+
+            match buffered {
+                Some(bytes) => self.ready_samples.push_back(entry.into_sample(bytes)),
+                None if entry.offset >= self.position => streamed_entries.push_back(entry),
+                None => log::warn!(
+                    "Sample of track {} at offset {} is no longer available",
+                    entry.track_id,
+                    entry.offset
+                ),
+            }
+        }
+
+        self.pending_entries = streamed_entries;
+        Ok(())
+    }
+
+    fn load_moof(&mut self, moof: &MoofBox, moof_offset: u64) -> Result<(), Mp4StreamReaderError> {
+        let mut entries = vec![];
+
+        for traf in &moof.trafs {
+            let track_id = traf.tfhd.track_id;
+            let Some(trun) = &traf.trun else {
+                continue;
+            };
+
+            let defaults = self
+                .track_defaults
+                .get(&track_id)
+                .cloned()
+                .unwrap_or_default();
+            let default_duration = traf
+                .tfhd
+                .default_sample_duration
+                .unwrap_or(defaults.sample_duration);
+            let default_size = traf
+                .tfhd
+                .default_sample_size
+                .unwrap_or(defaults.sample_size);
+            let default_flags = traf
+                .tfhd
+                .default_sample_flags
+                .unwrap_or(defaults.sample_flags);
+
+            let base_offset = traf.tfhd.base_data_offset.unwrap_or(moof_offset);
+            let mut offset = base_offset
+                .checked_add_signed(trun.data_offset.unwrap_or(0) as i64)
+                .ok_or_else(|| invalid_offset(track_id))?;
+
+            let mut decode_time = match &traf.tfdt {
+                Some(tfdt) => tfdt.base_media_decode_time,
+                None => *self.fragment_decode_times.get(&track_id).unwrap_or(&0),
+            };
+
+            for index in 0..trun.sample_count as usize {
+                let size = trun
+                    .sample_sizes
+                    .get(index)
+                    .copied()
+                    .unwrap_or(default_size);
+                let duration = trun
+                    .sample_durations
+                    .get(index)
+                    .copied()
+                    .unwrap_or(default_duration);
+                let flags = match (index, trun.first_sample_flags) {
+                    (0, Some(first_flags)) => first_flags,
+                    _ => trun
+                        .sample_flags
+                        .get(index)
+                        .copied()
+                        .unwrap_or(default_flags),
+                };
+
+                entries.push(SampleEntry {
+                    track_id,
+                    offset,
+                    size,
+                    start_time: decode_time,
+                    duration,
+                    rendering_offset: trun.sample_cts.get(index).copied().unwrap_or(0) as i32,
+                    is_sync: flags & SAMPLE_IS_NON_SYNC_FLAG == 0,
+                });
+
+                offset = offset
+                    .checked_add(size as u64)
+                    .ok_or_else(|| invalid_offset(track_id))?;
+                decode_time = decode_time.saturating_add(duration as u64);
+            }
+
+            self.fragment_decode_times.insert(track_id, decode_time);
+        }
+
+        entries.sort_by_key(|entry| entry.offset);
+        self.pending_entries.extend(entries);
+        Ok(())
+    }
+
+    fn read_streamed_sample(&mut self) -> Result<Option<StreamSample>, Mp4StreamReaderError> {
+        let Some(mdat_end) = self.current_mdat_end else {
+            return Ok(None);
+        };
+
+        while let Some(entry) = self.pending_entries.front() {
+            let sample_end = entry.offset.saturating_add(entry.size as u64);
+            if sample_end > mdat_end {
+                break;
+            }
+
+            let entry = self.pending_entries.pop_front().unwrap();
+            if entry.offset < self.position {
+                log::warn!(
+                    "Skipping overlapping sample of track {} at offset {}",
+                    entry.track_id,
+                    entry.offset
+                );
+                continue;
+            }
+
+            self.skip_payload(entry.offset - self.position)?;
+            let bytes = self.read_payload(entry.size as u64, MAX_PAYLOAD_SIZE)?;
+            return Ok(Some(entry.into_sample(bytes)));
+        }
+
+        // No more samples in this mdat, move on to the next box
+        self.skip_payload(mdat_end.saturating_sub(self.position))?;
+        self.current_mdat_end = None;
+        Ok(None)
+    }
+}
+
+impl SampleEntry {
+    fn into_sample(self, bytes: Vec<u8>) -> StreamSample {
+        StreamSample {
+            track_id: self.track_id,
+            sample: Mp4Sample {
+                start_time: self.start_time,
+                duration: self.duration,
+                rendering_offset: self.rendering_offset,
+                is_sync: self.is_sync,
+                bytes: bytes.into(),
+            },
+        }
+    }
+}
+
+// `TrakBox` isn't exported by the mp4 crate, tracks are addressed by index
+fn track_config(moov: &MoovBox, index: usize) -> Option<TrackConfig> {
+    let trak = &moov.traks[index];
+    let stsd = &trak.mdia.minf.stbl.stsd;
+    let language = trak.mdia.mdhd.language.clone();
+    let timescale = trak.mdia.mdhd.timescale;
+
+    if let Some(avc1) = &stsd.avc1 {
+        return Some(TrackConfig {
+            track_type: TrackType::Video,
+            timescale,
+            language,
+            media_conf: MediaConfig::AvcConfig(mp4::AvcConfig {
+                width: avc1.width,
+                height: avc1.height,
+                seq_param_set: avc1
+                    .avcc
+                    .sequence_parameter_sets
+                    .first()
+                    .map(|nal| nal.bytes.clone())
+                    .unwrap_or_default(),
+                pic_param_set: avc1
+                    .avcc
+                    .picture_parameter_sets
+                    .first()
+                    .map(|nal| nal.bytes.clone())
+                    .unwrap_or_default(),
+            }),
+        });
+    }
+
+    if let Some(mp4a) = &stsd.mp4a {
+        let esds = mp4a.esds.as_ref()?;
+        let dec_specific = &esds.es_desc.dec_config.dec_specific;
+
+        return Some(TrackConfig {
+            track_type: TrackType::Audio,
+            timescale,
+            language,
+            media_conf: MediaConfig::AacConfig(mp4::AacConfig {
+                bitrate: esds.es_desc.dec_config.avg_bitrate,
+                profile: dec_specific.profile.try_into().ok()?,
+                freq_index: dec_specific.freq_index.try_into().ok()?,
+                chan_conf: dec_specific.chan_conf.try_into().ok()?,
+            }),
+        });
+    }
+
+    None
+}
+
+// The `trex` boxes of `moov/mvex`, keyed by track ID
+fn track_defaults(
+    moov_payload: &[u8],
+) -> Result<HashMap<u32, TrackDefaults>, Mp4StreamReaderError> {
+    let mut defaults = HashMap::new();
+
+    for (name, mvex, _) in split_boxes(moov_payload)? {
+        if name != b"mvex" {
+            continue;
+        }
+
+        for (name, trex, _) in split_boxes(mvex)? {
+            // Version and flags, track ID, sample description index, duration, size and flags
+            if name != b"trex" || trex.len() < 24 {
+                continue;
+            }
+
+            let field =
+                |i: usize| u32::from_be_bytes([trex[i], trex[i + 1], trex[i + 2], trex[i + 3]]);
+            defaults.insert(
+                field(4),
+                TrackDefaults {
+                    sample_duration: field(12),
+                    sample_size: field(16),
+                    sample_flags: field(20),
+                },
+            );
+        }
+    }
+
+    Ok(defaults)
+}
+
+fn invalid_offset(track_id: u32) -> Mp4StreamReaderError {
+    Mp4StreamReaderError::InvalidStream(format!("Track {track_id} has an invalid sample offset"))
+}
+
+fn sample_table_entries(
+    moov: &MoovBox,
+    index: usize,
+) -> Result<Vec<SampleEntry>, Mp4StreamReaderError> {
+    let trak = &moov.traks[index];
+    let track_id = trak.tkhd.track_id;
+    let stbl = &trak.mdia.minf.stbl;
+    let sample_count = stbl.stsz.sample_count as usize;
+
+    let chunk_offsets: Vec<u64> = match (&stbl.stco, &stbl.co64) {
+        (Some(stco), _) => stco.entries.iter().map(|&o| o as u64).collect(),
+        (None, Some(co64)) => co64.entries.clone(),
+        (None, None) => {
+            return Err(Mp4StreamReaderError::InvalidStream(format!(
+                "Track {track_id} has no chunk offset box"
+            )));
+        }
+    };
+
+    let mut durations = stbl
+        .stts
+        .entries
+        .iter()
+        .flat_map(|entry| std::iter::repeat_n(entry.sample_delta, entry.sample_count as usize));
+
+    let mut rendering_offsets = stbl.ctts.iter().flat_map(|ctts| {
+        ctts.entries
+            .iter()
+            .flat_map(|entry| std::iter::repeat_n(entry.sample_offset, entry.sample_count as usize))
+    });
+
+    // Chunk numbers are 1-based
+    if stbl.stsc.entries.iter().any(|entry| entry.first_chunk == 0) {
+        return Err(Mp4StreamReaderError::InvalidStream(format!(
+            "Track {track_id} has a chunk numbered 0"
+        )));
+    }
+
+    let mut entries = vec![];
+    let mut start_time = 0u64;
+    let mut sample_index = 0usize;
+
+    for (i, stsc) in stbl.stsc.entries.iter().enumerate() {
+        let last_chunk = stbl
+            .stsc
+            .entries
+            .get(i + 1)
+            .map(|next| next.first_chunk - 1)
+            .unwrap_or(chunk_offsets.len() as u32);
+
+        for chunk in stsc.first_chunk..=last_chunk {
+            let Some(&chunk_offset) = chunk_offsets.get(chunk as usize - 1) else {
+                break;
+            };
+
+            let mut offset = chunk_offset;
+            for _ in 0..stsc.samples_per_chunk {
+                if sample_index >= sample_count {
+                    break;
+                }
+
+                let size = if stbl.stsz.sample_size != 0 {
+                    stbl.stsz.sample_size
+                } else {
+                    *stbl.stsz.sample_sizes.get(sample_index).ok_or_else(|| {
+                        Mp4StreamReaderError::InvalidStream(format!(
+                            "Track {track_id} has no size for sample {sample_index}"
+                        ))
+                    })?
+                };
+                let duration = durations.next().unwrap_or(0);
+
+                // Sample numbers in stss are 1-based, no stss means every sample is a sync sample
+                let is_sync = stbl
+                    .stss
+                    .as_ref()
+                    .map(|stss| {
+                        stss.entries
+                            .binary_search(&(sample_index as u32 + 1))
+                            .is_ok()
+                    })
+                    .unwrap_or(true);
+
+                entries.push(SampleEntry {
+                    track_id,
+                    offset,
+                    size,
+                    start_time,
+                    duration,
+                    rendering_offset: rendering_offsets.next().unwrap_or(0),
+                    is_sync,
+                });
+
+                offset = offset
+                    .checked_add(size as u64)
+                    .ok_or_else(|| invalid_offset(track_id))?;
+                start_time += duration as u64;
+                sample_index += 1;
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udta::make_box;
+    use mp4::{AvcConfig, Mp4Config, Mp4Writer};
+
+    const SAMPLES: [&[u8]; 3] = [
+        &[0, 0, 0, 2, 0x65, 1],
+        &[0, 0, 0, 1, 0x41],
+        &[0, 0, 0, 1, 0x41],
+    ];
+
+    fn plain_mp4() -> Vec<u8> {
+        let config = Mp4Config {
+            major_brand: str::parse("isom").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![str::parse("isom").unwrap(), str::parse("avc1").unwrap()],
+            timescale: 1000,
+        };
+
+        let mut writer = Mp4Writer::write_start(Cursor::new(vec![]), &config).unwrap();
+        writer
+            .add_track(&TrackConfig {
+                track_type: TrackType::Video,
+                timescale: 90000,
+                language: "und".to_string(),
+                media_conf: MediaConfig::AvcConfig(AvcConfig {
+                    width: 64,
+                    height: 64,
+                    seq_param_set: vec![0x67, 0x64, 0x00, 0x1e],
+                    pic_param_set: vec![0x68, 0xeb],
+                }),
+            })
+            .unwrap();
+
+        for (index, bytes) in SAMPLES.iter().enumerate() {
+            let sample = Mp4Sample {
+                start_time: index as u64 * 3000,
+                duration: 3000,
+                rendering_offset: 0,
+                is_sync: index == 0,
+                bytes: bytes.to_vec().into(),
+            };
+            writer.write_sample(1, &sample).unwrap();
+        }
+
+        writer.write_end().unwrap();
+        writer.into_writer().into_inner()
+    }
+
+    fn trex(track_id: u32, sample_duration: u32) -> Vec<u8> {
+        let mut payload = vec![0; 4];
+        for value in [track_id, 1, sample_duration, 0, SAMPLE_IS_NON_SYNC_FLAG] {
+            payload.extend(value.to_be_bytes());
+        }
+        make_box(b"trex", &payload)
+    }
+
+    // The `ftyp` and `moov` of `plain_mp4` with a `trex` per track, then a
+    // single fragment with the samples
+    fn fragmented_mp4() -> Vec<u8> {
+        let plain = plain_mp4();
+        let boxes = split_boxes(&plain).unwrap();
+        let (_, _, ftyp) = boxes.iter().find(|(name, _, _)| *name == b"ftyp").unwrap();
+        let (_, moov, _) = boxes.iter().find(|(name, _, _)| *name == b"moov").unwrap();
+
+        // The crate only keeps the last `trex`, so the one of track 1 comes first
+        let mut mvex = trex(1, 3000);
+        mvex.extend(trex(2, 1));
+
+        let mut moov = moov.to_vec();
+        moov.extend(make_box(b"mvex", &mvex));
+
+        let mdat: Vec<u8> = SAMPLES.concat();
+        let trun_len = 8 + 16 + 4 * SAMPLES.len();
+        let moof_len = 8 + 16 + 8 + 16 + trun_len;
+
+        // Data offset, first sample flags and sample sizes
+        let mut trun = vec![0, 0, 0x02, 0x05];
+        trun.extend((SAMPLES.len() as u32).to_be_bytes());
+        trun.extend(((moof_len + 8) as i32).to_be_bytes());
+        trun.extend(0u32.to_be_bytes());
+        for sample in SAMPLES {
+            trun.extend((sample.len() as u32).to_be_bytes());
+        }
+
+        let mut tfhd = vec![0; 4];
+        tfhd.extend(1u32.to_be_bytes());
+
+        let mut traf = make_box(b"tfhd", &tfhd);
+        traf.extend(make_box(b"trun", &trun));
+
+        let mut moof = make_box(b"mfhd", &[0, 0, 0, 0, 0, 0, 0, 1]);
+        moof.extend(make_box(b"traf", &traf));
+
+        let mut data = ftyp.to_vec();
+        data.extend(make_box(b"moov", &moov));
+        data.extend(make_box(b"moof", &moof));
+        data.extend(make_box(b"mdat", &mdat));
+        data
+    }
+
+    fn read_all(data: &[u8]) -> Result<Vec<StreamSample>, Mp4StreamReaderError> {
+        let mut reader = Mp4StreamReader::new(data);
+        reader.read_tracks()?;
+
+        let mut samples = vec![];
+        while let Some(sample) = reader.next_sample()? {
+            samples.push(sample);
+        }
+        Ok(samples)
+    }
+
+    fn assert_samples(samples: &[StreamSample]) {
+        assert_eq!(samples.len(), SAMPLES.len());
+        for (index, sample) in samples.iter().enumerate() {
+            assert_eq!(sample.track_id, 1);
+            assert_eq!(&sample.sample.bytes[..], SAMPLES[index]);
+            assert_eq!(sample.sample.start_time, index as u64 * 3000);
+            assert_eq!(sample.sample.duration, 3000);
+            assert_eq!(sample.sample.is_sync, index == 0);
+        }
+    }
+
+    #[test]
+    fn test_plain_mp4() {
+        let data = plain_mp4();
+
+        let mut reader = Mp4StreamReader::new(&data[..]);
+        let tracks = reader.read_tracks().unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].track_id, 1);
+        assert_eq!(tracks[0].config.track_type, TrackType::Video);
+
+        assert_samples(&read_all(&data).unwrap());
+    }
+
+    #[test]
+    fn test_fragmented_mp4() {
+        assert_samples(&read_all(&fragmented_mp4()).unwrap());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = plain_mp4();
+        for len in 0..data.len() {
+            assert!(read_all(&data[..len]).is_err(), "truncated at {len}");
+        }
+
+        // Cut inside the last `mdat`
+        let data = fragmented_mp4();
+        assert!(read_all(&data[..data.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_oversized_box() {
+        let data = plain_mp4();
+        let boxes = split_boxes(&data).unwrap();
+        let (_, _, ftyp) = boxes.iter().find(|(name, _, _)| *name == b"ftyp").unwrap();
+
+        for name in [b"mdat", b"moov"] {
+            let mut data = ftyp.to_vec();
+            data.extend(u32::MAX.to_be_bytes());
+            data.extend(name);
+            data.extend([0; 16]);
+
+            assert!(matches!(
+                read_all(&data),
+                Err(Mp4StreamReaderError::InvalidStream(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_oversized_largesize_box() {
+        let mut mvex = trex(1, 3000);
+        mvex.extend(1u32.to_be_bytes());
+        mvex.extend(b"trex");
+        mvex.extend(u64::MAX.to_be_bytes());
+        mvex.extend([0; 24]);
+
+        let mut moov = make_box(b"mvhd", &[0; 100]);
+        moov.extend(make_box(b"mvex", &mvex));
+        assert!(track_defaults(&moov).is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk() {
+        let mut data = plain_mp4();
+        let stsc = data.windows(4).position(|name| name == b"stsc").unwrap();
+
+        // The first chunk of the first entry, after the version, flags and entry count
+        let first_chunk = stsc + 12;
+        data[first_chunk..first_chunk + 4].copy_from_slice(&0u32.to_be_bytes());
+
+        assert!(matches!(
+            read_all(&data),
+            Err(Mp4StreamReaderError::InvalidStream(_))
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+// Seconds between 1904-01-01 (MP4 epoch) and 1970-01-01 (Unix epoch)
+pub(crate) const MP4_EPOCH_OFFSET_SECS: u64 = 2_082_844_800;
+
+#[derive(Debug, Clone, Copy)]
+struct BoxLocation {
+    start: u64,
+    size: u64,
+    header_len: u64,
+}
+
+/// Serialize a box with a plain 32-bit size header.
+pub(crate) fn make_box(name: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend_from_slice(&((payload.len() + 8) as u32).to_be_bytes());
+    data.extend_from_slice(name);
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Append `children` to the `moov` box of a finalized MP4 file.
+///
+/// `Mp4Writer` writes `moov` as the last top-level box, so the children can be
+/// written at the end of the file and only the `moov` size needs patching.
+pub(crate) fn append_to_moov(file: &mut File, children: &[u8]) -> std::io::Result<()> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let moov = find_box(file, 0, file_len, b"moov")?
+        .ok_or_else(|| std::io::Error::other("No moov box found"))?;
+
+    if moov.start + moov.size != file_len {
+        return Err(std::io::Error::other("moov box is not the last box"));
+    }
+
+    let new_size = moov.size + children.len() as u64;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(children)?;
+
+    if moov.header_len == 16 {
+        file.seek(SeekFrom::Start(moov.start + 8))?;
+        file.write_all(&new_size.to_be_bytes())?;
+    } else if new_size <= u32::MAX as u64 {
+        file.seek(SeekFrom::Start(moov.start))?;
+        file.write_all(&(new_size as u32).to_be_bytes())?;
+    } else {
+        return Err(std::io::Error::other("moov box is too large"));
+    }
+
+    file.flush()
+}
+
+/// Replace the `name` box in `moov/udta` of a finalized MP4 file with `child`,
+/// or remove it when `child` is `None`. The other `udta` boxes are kept.
+///
+/// `moov` has to be the last top-level box, so resizing it doesn't move the
+/// media data the sample tables point to.
+pub(crate) fn replace_udta_child(
+    file: &mut File,
+    name: &[u8; 4],
+    child: Option<&[u8]>,
+) -> std::io::Result<()> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let moov = find_box(file, 0, file_len, b"moov")?
+        .ok_or_else(|| std::io::Error::other("No moov box found"))?;
+
+    if moov.start + moov.size != file_len {
+        return Err(std::io::Error::other("moov box is not the last box"));
+    }
+
+    let mut payload = vec![0u8; (moov.size - moov.header_len) as usize];
+    file.seek(SeekFrom::Start(moov.start + moov.header_len))?;
+    file.read_exact(&mut payload)?;
+
+    let mut children = vec![];
+    let mut udta = None;
+    for (box_name, box_payload, data) in split_boxes(&payload)? {
+        if box_name == b"udta" {
+            udta = Some(box_payload);
+        } else {
+            children.extend_from_slice(data);
+        }
+    }
+
+    let mut udta_payload = vec![];
+    if let Some(udta) = udta {
+        for (box_name, _, data) in split_boxes(udta)? {
+            if box_name != name {
+                udta_payload.extend_from_slice(data);
+            }
+        }
+    }
+
+    if let Some(child) = child {
+        udta_payload.extend_from_slice(child);
+    }
+
+    if !udta_payload.is_empty() {
+        children.extend(make_box(b"udta", &udta_payload));
+    }
+
+    if children.len() + 8 > u32::MAX as usize {
+        return Err(std::io::Error::other("moov box is too large"));
+    }
+
+    let moov_box = make_box(b"moov", &children);
+    file.seek(SeekFrom::Start(moov.start))?;
+    file.write_all(&moov_box)?;
+    file.set_len(moov.start + moov_box.len() as u64)?;
+    file.flush()
+}
+
+/// Set creation and modification time of the `mvhd` box, `Mp4Writer` leaves them at zero.
+pub(crate) fn set_creation_time(file: &mut File, mp4_time_secs: u64) -> std::io::Result<()> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    let moov = find_box(file, 0, file_len, b"moov")?
+        .ok_or_else(|| std::io::Error::other("No moov box found"))?;
+    let mvhd = find_box(
+        file,
+        moov.start + moov.header_len,
+        moov.start + moov.size,
+        b"mvhd",
+    )?
+    .ok_or_else(|| std::io::Error::other("No mvhd box found"))?;
+
+    let mut version = [0u8; 1];
+    file.seek(SeekFrom::Start(mvhd.start + mvhd.header_len))?;
+    file.read_exact(&mut version)?;
+
+    // Skip version and flags
+    file.seek(SeekFrom::Start(mvhd.start + mvhd.header_len + 4))?;
+    if version[0] == 1 {
+        file.write_all(&mp4_time_secs.to_be_bytes())?;
+        file.write_all(&mp4_time_secs.to_be_bytes())?;
+    } else {
+        let time = mp4_time_secs.min(u32::MAX as u64) as u32;
+        file.write_all(&time.to_be_bytes())?;
+        file.write_all(&time.to_be_bytes())?;
+    }
+
+    file.flush()
+}
+
+fn find_box(
+    file: &mut File,
+    start: u64,
+    end: u64,
+    name: &[u8; 4],
+) -> std::io::Result<Option<BoxLocation>> {
+    let mut position = start;
+
+    while position + 8 <= end {
+        file.seek(SeekFrom::Start(position))?;
+
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_len = 8;
+
+        if size == 1 {
+            let mut large_size = [0u8; 8];
+            file.read_exact(&mut large_size)?;
+            size = u64::from_be_bytes(large_size);
+            header_len = 16;
+        } else if size == 0 {
+            size = end - position;
+        }
+
+        if size < header_len {
+            return Err(std::io::Error::other(format!(
+                "Invalid box size {size} at offset {position}"
+            )));
+        }
+
+        if &header[4..8] == name {
+            return Ok(Some(BoxLocation {
+                start: position,
+                size,
+                header_len,
+            }));
+        }
+        position += size;
+    }
+
+    Ok(None)
+}
+
+/// Split the boxes of `data` into their names, payloads and whole boxes.
+#[allow(clippy::type_complexity)]
+pub(crate) fn split_boxes(data: &[u8]) -> std::io::Result<Vec<(&[u8], &[u8], &[u8])>> {
+    let mut boxes = vec![];
+    let mut position = 0;
+
+    while position + 8 <= data.len() {
+        let header = &data[position..position + 8];
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let mut header_len = 8;
+
+        if size == 1 && position + 16 <= data.len() {
+            let mut large_size = [0u8; 8];
+            large_size.copy_from_slice(&data[position + 8..position + 16]);
+            size = usize::try_from(u64::from_be_bytes(large_size)).unwrap_or(usize::MAX);
+            header_len = 16;
+        } else if size == 0 {
+            size = data.len() - position;
+        }
+
+        // A crafted largesize can overflow the end of the box
+        let Some(end) = position
+            .checked_add(size)
+            .filter(|end| size >= header_len && *end <= data.len())
+        else {
+            return Err(std::io::Error::other(format!(
+                "Invalid box size {size} at offset {position}"
+            )));
+        };
+
+        boxes.push((
+            &header[4..8],
+            &data[position + header_len..end],
+            &data[position..end],
+        ));
+        position = end;
+    }
+
+    Ok(boxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    fn mp4_file(path: &str) -> File {
+        let mut data = make_box(b"ftyp", b"isom\0\0\x02\0");
+        data.extend(make_box(b"mdat", &[0; 16]));
+        data.extend(make_box(b"moov", &make_box(b"mvhd", &[0; 100])));
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap();
+        file.write_all(&data).unwrap();
+        file
+    }
+
+    fn read_file(file: &mut File) -> Vec<u8> {
+        let mut data = vec![];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_append_to_moov() {
+        let mut file = mp4_file("/tmp/test-append-to-moov.mp4");
+        let udta = make_box(b"udta", &make_box(b"chpl", &[1, 0, 0, 0, 0, 0, 0, 0, 0]));
+        append_to_moov(&mut file, &udta).unwrap();
+
+        let data = read_file(&mut file);
+        let boxes = split_boxes(&data).unwrap();
+        assert_eq!(boxes.len(), 3);
+
+        let (name, moov, _) = boxes[2];
+        assert_eq!(name, b"moov");
+        assert_eq!(moov.len(), 108 + udta.len());
+
+        let children = split_boxes(moov).unwrap();
+        assert_eq!(children[0].0, b"mvhd");
+        assert_eq!(children[1].2, udta.as_slice());
+    }
+
+    #[test]
+    fn test_append_to_moov_not_last() {
+        let path = "/tmp/test-append-to-moov-not-last.mp4";
+        let mut file = mp4_file(path);
+        file.write_all(&make_box(b"free", &[])).unwrap();
+
+        assert!(append_to_moov(&mut file, &make_box(b"udta", &[])).is_err());
+    }
+
+    #[test]
+    fn test_replace_udta_child() {
+        let mut file = mp4_file("/tmp/test-replace-udta-child.mp4");
+        let meta = make_box(b"meta", &[0; 4]);
+        append_to_moov(&mut file, &make_box(b"udta", &meta)).unwrap();
+
+        let chpl = make_box(b"chpl", &[1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        replace_udta_child(&mut file, b"chpl", Some(&chpl)).unwrap();
+        replace_udta_child(&mut file, b"chpl", Some(&chpl)).unwrap();
+
+        let data = read_file(&mut file);
+        let (_, moov, _) = split_boxes(&data).unwrap()[2];
+        let (_, udta, _) = split_boxes(moov).unwrap()[1];
+        let children = split_boxes(udta).unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].2, meta.as_slice());
+        assert_eq!(children[1].2, chpl.as_slice());
+
+        // The chapters are removed and the other boxes are kept
+        replace_udta_child(&mut file, b"chpl", None).unwrap();
+        let data = read_file(&mut file);
+        let (_, moov, _) = split_boxes(&data).unwrap()[2];
+        let (_, udta, _) = split_boxes(moov).unwrap()[1];
+        assert_eq!(udta, meta.as_slice());
+    }
+
+    #[test]
+    fn test_split_boxes_oversized_largesize() {
+        let mut data = make_box(b"free", &[]);
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(b"mdat");
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+
+        assert!(split_boxes(&data).is_err());
+
+        // Ends past the data without overflowing
+        let len = data.len();
+        data[len - 16..len - 8].copy_from_slice(&(len as u64).to_be_bytes());
+        assert!(split_boxes(&data).is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use ocr::{Model, Ocr, OcrConfig, TextBox};
+use std::{path::PathBuf, time::Instant};
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let input_file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "./examples/test.png".to_string());
+
+    let models_dir = PathBuf::from("./models");
+    for model in Model::all_models() {
+        let path = models_dir.join(model.to_filename());
+        if !path.exists() {
+            log::warn!(
+                "Model file not found: {}. Download it from {}",
+                path.display(),
+                model.download_url()
+            );
+            return Ok(());
+        }
+    }
+
+    let img = image::open(&input_file)
+        .with_context(|| input_file.clone())?
+        .to_rgba8();
+    log::info!("Image size: {}x{}", img.width(), img.height());
+
+    let mut ocr = Ocr::new(OcrConfig::from_dir(&models_dir))?;
+
+    let inference_start = Instant::now();
+    let boxes = ocr.recognize(&img)?;
+    log::info!("OCR spent: {:?}", inference_start.elapsed());
+
+    for b in &boxes {
+        log::info!(
+            "[{}, {}, {}x{}] {:.2} {}",
+            b.x,
+            b.y,
+            b.width,
+            b.height,
+            b.score,
+            b.text
+        );
+    }
+
+    println!("{}", TextBox::to_text(&boxes));
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+//! DBNet text detection: the model predicts for each pixel the probability
+//! to be inside a text line, the connected areas become the text boxes.
+
+use crate::{Error, Result, ocr};
+use image::RgbImage;
+use ndarray::Array;
+use ort::{session::Session, value::TensorRef};
+use std::path::Path;
+
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// Axis aligned box in the coordinates of the input image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub score: f32,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Detector {
+    session: Session,
+    input_name: String,
+    output_name: String,
+}
+
+impl Detector {
+    pub fn new<P: AsRef<Path>>(model_path: P) -> Result<Self> {
+        let (session, input_name, output_name) = ocr::load_session(model_path.as_ref())?;
+
+        Ok(Self {
+            session,
+            input_name,
+            output_name,
+        })
+    }
+
+    /// `limit_side_len` bounds the longest side of the model input,
+    /// `threshold` binarizes the probability map, the boxes with a mean
+    /// probability under `box_threshold` are dropped and the others are
+    /// expanded by `unclip_ratio`
+    pub fn detect(
+        &mut self,
+        image: &RgbImage,
+        limit_side_len: u32,
+        threshold: f32,
+        box_threshold: f32,
+        unclip_ratio: f32,
+    ) -> Result<Vec<DetectedBox>> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(vec![]);
+        }
+
+        let (target_width, target_height) = input_size(width, height, limit_side_len);
+        let resized = ocr::resize(image, target_width, target_height)?;
+        let input = preprocess(&resized);
+
+        let input_tensor = TensorRef::from_array_view(input.view())?;
+        let outputs = self
+            .session
+            .run(ort::inputs! { &self.input_name => input_tensor })?;
+        let output = outputs[self.output_name.as_str()].try_extract_array::<f32>()?;
+
+        let shape = output.shape();
+        if shape.len() < 2 {
+            return Err(Error::InvalidOutput(format!(
+                "Unsupported output shape: {shape:?}"
+            )));
+        }
+
+        let (map_height, map_width) = (shape[shape.len() - 2], shape[shape.len() - 1]);
+        let probs = output
+            .iter()
+            .copied()
+            .take(map_width * map_height)
+            .collect::<Vec<_>>();
+
+        let scale = (
+            width as f32 / map_width as f32,
+            height as f32 / map_height as f32,
+        );
+
+        Ok(
+            find_boxes(&probs, map_width, map_height, threshold, box_threshold)
+                .into_iter()
+                .map(|b| unclip(b, unclip_ratio, scale, (width, height)))
+                .collect(),
+        )
+    }
+}
+
+/// Keep the aspect ratio, DBNet needs sides that are multiples of 32
+fn input_size(width: u32, height: u32, limit_side_len: u32) -> (u32, u32) {
+    let ratio = (limit_side_len as f32 / width.max(height) as f32).min(1.0);
+    let round = |v: u32| ((v as f32 * ratio / 32.0).round() as u32).max(1) * 32;
+
+    (round(width), round(height))
+}
+
+fn preprocess(image: &RgbImage) -> Array<f32, ndarray::Ix4> {
+    let (width, height) = image.dimensions();
+    let mut array = Array::zeros((1, 3, height as usize, width as usize));
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        for c in 0..3 {
+            array[[0, c, y as usize, x as usize]] = (pixel[c] as f32 / 255.0 - MEAN[c]) / STD[c];
+        }
+    }
+
+    array
+}
+
+/// Bounding boxes of the connected areas over `threshold` in the map
+fn find_boxes(
+    probs: &[f32],
+    width: usize,
+    height: usize,
+    threshold: f32,
+    box_threshold: f32,
+) -> Vec<DetectedBox> {
+    let mut visited = vec![false; probs.len()];
+    let mut stack = vec![];
+    let mut boxes = vec![];
+
+    for start in 0..probs.len() {
+        if visited[start] || probs[start] <= threshold {
+            continue;
+        }
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+        let (mut sum, mut count) = (0.0, 0);
+
+        visited[start] = true;
+        stack.push(start);
+
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            (min_x, min_y) = (min_x.min(x), min_y.min(y));
+            (max_x, max_y) = (max_x.max(x), max_y.max(y));
+            sum += probs[index];
+            count += 1;
+
+            let neighbors = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+
+            for neighbor in neighbors.into_iter().flatten() {
+                if !visited[neighbor] && probs[neighbor] > threshold {
+                    visited[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        let score = sum / count as f32;
+        let (box_width, box_height) = (max_x - min_x + 1, max_y - min_y + 1);
+        if score < box_threshold || box_width.min(box_height) < 3 {
+            continue;
+        }
+
+        boxes.push(DetectedBox {
+            x: min_x as u32,
+            y: min_y as u32,
+            width: box_width as u32,
+            height: box_height as u32,
+            score,
+        });
+    }
+
+    boxes
+}
+
+/// The model shrinks the text areas, grow them back by
+/// `area * ratio / perimeter` and scale them to the image size
+fn unclip(
+    b: DetectedBox,
+    ratio: f32,
+    scale: (f32, f32),
+    (width, height): (u32, u32),
+) -> DetectedBox {
+    let (w, h) = (b.width as f32, b.height as f32);
+    let distance = w * h * ratio / (2.0 * (w + h));
+
+    let x0 = ((b.x as f32 - distance) * scale.0).max(0.0);
+    let y0 = ((b.y as f32 - distance) * scale.1).max(0.0);
+    let x1 = ((b.x as f32 + w + distance) * scale.0).min(width as f32);
+    let y1 = ((b.y as f32 + h + distance) * scale.1).min(height as f32);
+
+    DetectedBox {
+        x: x0 as u32,
+        y: y0 as u32,
+        width: (x1 - x0).max(1.0) as u32,
+        height: (y1 - y0).max(1.0) as u32,
+        score: b.score,
+    }
+}
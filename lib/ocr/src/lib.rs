@@ -0,0 +1,36 @@
+pub mod detector;
+pub mod model;
+pub mod ocr;
+pub mod recognizer;
+
+pub use model::Model;
+pub use ocr::{Ocr, OcrConfig, TextBox};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Model file not found: {0}")]
+    ModelNotFound(std::path::PathBuf),
+
+    #[error("Invalid model output: {0}")]
+    InvalidOutput(String),
+
+    #[error("Invalid dictionary: {0}")]
+    InvalidDictionary(String),
+
+    #[error("Image processing error: {0}")]
+    ImageProcessing(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("ONNX Runtime error: {0}")]
+    OnnxRuntime(#[from] ort::Error),
+
+    #[error("Image resize error: {0}")]
+    ImageResize(#[from] fast_image_resize::ResizeError),
+
+    #[error("Image buffer error: {0}")]
+    ImageBufferError(#[from] fast_image_resize::ImageBufferError),
+}
@@ -0,0 +1,58 @@
+const DET_FILENAME: &str = "ch_PP-OCRv4_det_infer.onnx";
+const REC_FILENAME: &str = "ch_PP-OCRv4_rec_infer.onnx";
+const DICTIONARY_FILENAME: &str = "ppocr_keys_v1.txt";
+
+const DET_URL: &str =
+    "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_det_infer.onnx";
+const REC_URL: &str =
+    "https://huggingface.co/SWHL/RapidOCR/resolve/main/PP-OCRv4/ch_PP-OCRv4_rec_infer.onnx";
+const DICTIONARY_URL: &str =
+    "https://raw.githubusercontent.com/PaddlePaddle/PaddleOCR/main/ppocr/utils/ppocr_keys_v1.txt";
+
+/// Files of the PP-OCRv4 pipeline, it recognizes Chinese and English text
+#[derive(Clone, Copy, Debug)]
+pub enum Model {
+    Detection,
+    Recognition,
+    Dictionary,
+}
+
+impl Model {
+    pub fn all_models() -> Vec<Self> {
+        vec![Self::Detection, Self::Recognition, Self::Dictionary]
+    }
+
+    pub fn to_filename(&self) -> &'static str {
+        match self {
+            Self::Detection => DET_FILENAME,
+            Self::Recognition => REC_FILENAME,
+            Self::Dictionary => DICTIONARY_FILENAME,
+        }
+    }
+
+    pub fn try_from_filename(model: &str) -> Option<Self> {
+        match model {
+            DET_FILENAME => Some(Model::Detection),
+            REC_FILENAME => Some(Model::Recognition),
+            DICTIONARY_FILENAME => Some(Model::Dictionary),
+            _ => None,
+        }
+    }
+
+    pub fn try_from_url(url: &str) -> Option<Self> {
+        match url {
+            DET_URL => Some(Model::Detection),
+            REC_URL => Some(Model::Recognition),
+            DICTIONARY_URL => Some(Model::Dictionary),
+            _ => None,
+        }
+    }
+
+    pub fn download_url(&self) -> &'static str {
+        match self {
+            Self::Detection => DET_URL,
+            Self::Recognition => REC_URL,
+            Self::Dictionary => DICTIONARY_URL,
+        }
+    }
+}
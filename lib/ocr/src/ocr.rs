@@ -0,0 +1,201 @@
+use crate::{
+    Error, Model, Result,
+    detector::{DetectedBox, Detector},
+    recognizer::Recognizer,
+};
+use derivative::Derivative;
+use derive_setters::Setters;
+use fast_image_resize::{PixelType, ResizeOptions, Resizer, images::Image as FrImage};
+use image::{RgbImage, RgbaImage, buffer::ConvertBuffer, imageops};
+use ort::session::Session;
+use screen_capture::Capture;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct OcrConfig {
+    pub det_model_path: PathBuf,
+    pub rec_model_path: PathBuf,
+    pub dictionary_path: PathBuf,
+
+    /// Longest side of the detection input, larger finds smaller text
+    #[derivative(Default(value = "960"))]
+    pub det_limit_side_len: u32,
+
+    #[derivative(Default(value = "0.3"))]
+    pub det_threshold: f32,
+
+    #[derivative(Default(value = "0.6"))]
+    pub box_threshold: f32,
+
+    #[derivative(Default(value = "1.5"))]
+    pub unclip_ratio: f32,
+
+    /// Drop the recognized text with a lower confidence
+    #[derivative(Default(value = "0.5"))]
+    pub min_score: f32,
+}
+
+impl OcrConfig {
+    /// Use the files of `Model` in `dir`
+    pub fn from_dir(dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref();
+
+        Self::default()
+            .with_det_model_path(dir.join(Model::Detection.to_filename()))
+            .with_rec_model_path(dir.join(Model::Recognition.to_filename()))
+            .with_dictionary_path(dir.join(Model::Dictionary.to_filename()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBox {
+    pub text: String,
+    pub score: f32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextBox {
+    /// Join the boxes line by line, top to bottom and left to right
+    pub fn to_text(boxes: &[TextBox]) -> String {
+        let mut boxes = boxes.iter().collect::<Vec<_>>();
+        boxes.sort_by_key(|b| (b.y + b.height / 2, b.x));
+
+        let mut lines: Vec<Vec<&TextBox>> = vec![];
+        for b in boxes {
+            let center = b.y + b.height / 2;
+            match lines.last_mut() {
+                Some(line) if center >= line[0].y && center < line[0].y + line[0].height => {
+                    line.push(b)
+                }
+                _ => lines.push(vec![b]),
+            }
+        }
+
+        lines
+            .into_iter()
+            .map(|mut line| {
+                line.sort_by_key(|b| b.x);
+                line.iter()
+                    .map(|b| b.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Ocr {
+    config: OcrConfig,
+    detector: Detector,
+    recognizer: Recognizer,
+}
+
+impl Ocr {
+    pub fn new(config: OcrConfig) -> Result<Self> {
+        let detector = Detector::new(&config.det_model_path)?;
+        let recognizer = Recognizer::new(&config.rec_model_path, &config.dictionary_path)?;
+
+        Ok(Self {
+            config,
+            detector,
+            recognizer,
+        })
+    }
+
+    pub fn config(&self) -> &OcrConfig {
+        &self.config
+    }
+
+    pub fn recognize(&mut self, image: &RgbaImage) -> Result<Vec<TextBox>> {
+        let image: RgbImage = image.convert();
+
+        let boxes = self.detector.detect(
+            &image,
+            self.config.det_limit_side_len,
+            self.config.det_threshold,
+            self.config.box_threshold,
+            self.config.unclip_ratio,
+        )?;
+        log::debug!("detected {} text boxes", boxes.len());
+
+        let mut text_boxes = vec![];
+        for DetectedBox {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } in boxes
+        {
+            let line = imageops::crop_imm(&image, x, y, width, height).to_image();
+            let (text, score) = self.recognizer.recognize(&line)?;
+
+            if text.is_empty() || score < self.config.min_score {
+                continue;
+            }
+
+            text_boxes.push(TextBox {
+                text,
+                score,
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+
+        Ok(text_boxes)
+    }
+
+    pub fn recognize_capture(&mut self, capture: &Capture) -> Result<Vec<TextBox>> {
+        let image = RgbaImage::from_raw(capture.width, capture.height, capture.pixel_data.clone())
+            .ok_or_else(|| Error::ImageProcessing("Invalid capture buffer".to_string()))?;
+
+        self.recognize(&image)
+    }
+}
+
+pub(crate) fn load_session(model_path: &Path) -> Result<(Session, String, String)> {
+    if !model_path.exists() {
+        return Err(Error::ModelNotFound(model_path.to_path_buf()));
+    }
+
+    log::info!("Loading ONNX model from: {}", model_path.display());
+
+    let session = Session::builder()?.commit_from_file(model_path)?;
+    let input_name = session
+        .inputs()
+        .first()
+        .map(|input| input.name().to_string())
+        .ok_or_else(|| Error::InvalidOutput("Model has no input".to_string()))?;
+    let output_name = session
+        .outputs()
+        .first()
+        .map(|output| output.name().to_string())
+        .ok_or_else(|| Error::InvalidOutput("Model has no output".to_string()))?;
+
+    Ok((session, input_name, output_name))
+}
+
+pub(crate) fn resize(image: &RgbImage, target_width: u32, target_height: u32) -> Result<RgbImage> {
+    let (width, height) = image.dimensions();
+    if width == target_width && height == target_height {
+        return Ok(image.clone());
+    }
+
+    let src_image = FrImage::from_vec_u8(width, height, image.as_raw().clone(), PixelType::U8x3)?;
+    let mut dst_image = FrImage::new(target_width, target_height, PixelType::U8x3);
+    Resizer::new().resize(&src_image, &mut dst_image, &ResizeOptions::new())?;
+
+    RgbImage::from_raw(target_width, target_height, dst_image.into_vec())
+        .ok_or_else(|| Error::ImageProcessing("Failed to create resized image".to_string()))
+}
@@ -0,0 +1,129 @@
+//! CRNN text recognition of a single line, the model output is decoded
+//! greedily with CTC.
+
+use crate::{Error, Result, ocr};
+use image::RgbImage;
+use ndarray::Array;
+use ort::{session::Session, value::TensorRef};
+use std::{fs, path::Path};
+
+const INPUT_HEIGHT: u32 = 48;
+const MIN_INPUT_WIDTH: u32 = 320;
+const MAX_INPUT_WIDTH: u32 = 3200;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Recognizer {
+    session: Session,
+    input_name: String,
+    output_name: String,
+
+    // Index 0 is the CTC blank, the last one is the space
+    characters: Vec<String>,
+}
+
+impl Recognizer {
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(model_path: P, dictionary_path: Q) -> Result<Self> {
+        let dictionary_path = dictionary_path.as_ref();
+        if !dictionary_path.exists() {
+            return Err(Error::ModelNotFound(dictionary_path.to_path_buf()));
+        }
+
+        let mut characters = vec![String::new()];
+        characters.extend(
+            fs::read_to_string(dictionary_path)?
+                .lines()
+                .map(|line| line.trim_end_matches('\r').to_string()),
+        );
+        characters.push(" ".to_string());
+
+        if characters.len() <= 2 {
+            return Err(Error::InvalidDictionary(format!(
+                "{} is empty",
+                dictionary_path.display()
+            )));
+        }
+
+        let (session, input_name, output_name) = ocr::load_session(model_path.as_ref())?;
+
+        Ok(Self {
+            session,
+            input_name,
+            output_name,
+            characters,
+        })
+    }
+
+    /// Text of the line image with the mean confidence of its characters
+    pub fn recognize(&mut self, image: &RgbImage) -> Result<(String, f32)> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok((String::new(), 0.0));
+        }
+
+        let target_width = ((INPUT_HEIGHT as f32 * width as f32 / height as f32).ceil() as u32)
+            .clamp(1, MAX_INPUT_WIDTH);
+        let resized = ocr::resize(image, target_width, INPUT_HEIGHT)?;
+        let input = preprocess(&resized, target_width.max(MIN_INPUT_WIDTH));
+
+        let input_tensor = TensorRef::from_array_view(input.view())?;
+        let outputs = self
+            .session
+            .run(ort::inputs! { &self.input_name => input_tensor })?;
+        let output = outputs[self.output_name.as_str()].try_extract_array::<f32>()?;
+
+        // Format: (1, T, C) with the probabilities of each character
+        let shape = output.shape();
+        if shape.len() != 3 || shape[2] != self.characters.len() {
+            return Err(Error::InvalidOutput(format!(
+                "Unexpected output shape {shape:?} for a dictionary of {} characters",
+                self.characters.len()
+            )));
+        }
+
+        let probs = output.iter().copied().collect::<Vec<_>>();
+        Ok(ctc_decode(&probs, shape[2], &self.characters))
+    }
+}
+
+/// Normalize to [-1, 1] and pad on the right with zeros up to `width`
+fn preprocess(image: &RgbImage, width: u32) -> Array<f32, ndarray::Ix4> {
+    let mut array = Array::zeros((1, 3, image.height() as usize, width as usize));
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        for c in 0..3 {
+            array[[0, c, y as usize, x as usize]] = pixel[c] as f32 / 127.5 - 1.0;
+        }
+    }
+
+    array
+}
+
+/// Take the most probable class at each step, merge the repeats and drop
+/// the blanks
+fn ctc_decode(probs: &[f32], classes: usize, characters: &[String]) -> (String, f32) {
+    let mut text = String::new();
+    let (mut score, mut count) = (0.0, 0);
+    let mut previous = 0;
+
+    for step in probs.chunks_exact(classes) {
+        let (index, prob) = step
+            .iter()
+            .copied()
+            .enumerate()
+            .fold(
+                (0, f32::MIN),
+                |max, item| if item.1 > max.1 { item } else { max },
+            );
+
+        if index != 0 && index != previous {
+            text.push_str(&characters[index]);
+            score += prob;
+            count += 1;
+        }
+        previous = index;
+    }
+
+    let score = if count > 0 { score / count as f32 } else { 0.0 };
+    (text.trim().to_string(), score)
+}
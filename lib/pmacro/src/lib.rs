@@ -6,6 +6,8 @@
 //! # Features
 //!
 //! - Automatic `From` trait implementations between Rust structs and Slint UI types
+//! - Nested structs and enums converted with their own `From` implementations
+//! - Mapping between Rust enums and Slint enums by the variant names
 //! - Support for vector field mapping between `Vec<T>` and Slint's `ModelRc<T>`
 //! - Customizable field mappings using attributes
 //! - Default value handling for UI types
@@ -35,12 +37,14 @@
 //! ```
 //!
 //! This will generate `From<MyStruct> for UIType` and `From<UIType> for MyStruct` implementations.
+//!
+//! The `SqlTable` derive macro maps a struct to a typed `sqldb` table, see [`sql_table_derive`].
 
 // cargo expand --bin pmacro
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
+use syn::{Data, DeriveInput, Expr, Fields, Ident, LitStr, Type, parse_macro_input};
 
 /// Derive macro for bidirectional conversion between Rust structs and Slint UI types.
 ///
@@ -52,6 +56,21 @@ use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
 /// - `#[from("UIType")]`: Specifies the target Slint UI type for conversion
 /// - `#[vec_ui("field_name")]`: Creates an empty vector field in the UI type
 /// - `#[vec(from = "ui_field_name")]`: Maps a Rust vector field to a UI field
+/// - `#[enum_map("UIVariant")]`: Maps an enum variant to a differently named UI variant
+/// - `#[from_field(rename = "ui_field_name")]`: Maps a field to a differently named UI field
+/// - `#[from_field(skip)]`: The field isn't in the UI type, it's `Default::default()` when
+///   converted from the UI type
+/// - `#[from_field(default = expr)]`: The field isn't in the UI type, it's `expr` when
+///   converted from the UI type
+///
+/// A malformed attribute is reported as a compile error on it.
+///
+/// The fields are converted with `.into()`, so a field of a struct or an enum deriving
+/// `SlintFromConvert` is converted into the field of its UI type, and so are the items
+/// of a vector field.
+///
+/// On an enum of unit variants, each variant is mapped to the UI variant of the same
+/// name, unless it's renamed by `#[enum_map]`.
 ///
 /// # Example
 ///
@@ -75,127 +94,198 @@ use syn::{Data, DeriveInput, Fields, LitStr, parse_macro_input};
 ///     #[vec(from = "items")]
 ///     user_items: Vec<String>,
 /// }
+///
+/// // Define UI enum and a UI type nesting it
+/// #[derive(Default)]
+/// enum UIRole {
+///     #[default]
+///     Guest,
+///     Admin,
+/// }
+///
+/// #[derive(Default)]
+/// struct UIAccount {
+///     user: UIUser,
+///     role: UIRole,
+/// }
+///
+/// #[derive(SlintFromConvert)]
+/// #[from("UIRole")]
+/// enum Role {
+///     Guest,
+///     #[enum_map("Admin")]
+///     Administrator,
+/// }
+///
+/// #[derive(SlintFromConvert)]
+/// #[from("UIAccount")]
+/// struct Account {
+///     user: User,
+///     role: Role,
+/// }
 /// ```
-#[proc_macro_derive(SlintFromConvert, attributes(from, vec, vec_ui))]
+#[proc_macro_derive(SlintFromConvert, attributes(from, vec, vec_ui, enum_map, from_field))]
 pub fn from_convert_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let name = input.ident;
 
-    let mut target_type = None;
-    let mut vec_names_ui = vec![];
-    let mut vec_field_mappings = std::collections::HashMap::new();
+    from_convert(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    for attr in &input.attrs {
-        // find `#[from("Type")]`
-        if attr.path().is_ident("from") {
-            match attr.parse_args::<LitStr>() {
-                Ok(lit) => {
-                    target_type = Some(syn::parse_str::<syn::Path>(&lit.value()).unwrap());
-                }
-                Err(e) => {
-                    eprintln!("{e:?}");
-                    panic!("parse args failed");
-                }
-            }
-        }
+/// How a struct field is mapped to the UI type
+enum FieldMapping {
+    /// Converted into the UI field of the name
+    Field(Ident),
 
-        // find `#[vec_ui("vec_name")]`
-        if attr.path().is_ident("vec_ui") {
-            match attr.parse_args::<LitStr>() {
-                Ok(lit) => {
-                    vec_names_ui.push(syn::parse_str::<syn::Path>(&lit.value()).unwrap());
-                }
-                Err(e) => {
-                    eprintln!("{e:?}");
-                    panic!("parse args failed");
-                }
-            }
-        }
-    }
+    /// Converted item by item into the `ModelRc` UI field of the name
+    Vec(Ident),
 
-    let target_type = target_type.expect("Must specify target type with #[from(\"Type\")]");
+    /// Not converted, set to the expression from the UI type
+    Skip(proc_macro2::TokenStream),
+}
 
-    let fields = if let Data::Struct(data_struct) = input.data {
-        if let Fields::Named(fields_named) = data_struct.fields {
-            fields_named.named
-        } else {
-            panic!("SlintFromConvert only works on structs with named fields");
-        }
-    } else {
-        panic!("SlintFromConvert only works on structs");
-    };
+impl FieldMapping {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let field_name = field.ident.clone().expect("named field");
 
-    // Process field-level vec attributes
-    for field in &fields {
-        let field_name = field.ident.as_ref().unwrap();
+        let mut vec_name = None;
+        let mut rename = None;
+        let mut skip = false;
+        let mut default = None;
 
         for attr in &field.attrs {
             if attr.path().is_ident("vec") {
-                match attr.parse_args::<syn::Meta>() {
-                    Ok(syn::Meta::NameValue(meta_name_value))
-                        if meta_name_value.path.is_ident("from") =>
-                    {
-                        if let syn::Expr::Lit(syn::ExprLit {
-                            lit: syn::Lit::Str(lit_str),
-                            ..
-                        }) = &meta_name_value.value
-                        {
-                            let ui_field_name =
-                                syn::parse_str::<syn::Path>(&lit_str.value()).unwrap();
-                            vec_field_mappings.insert(field_name.to_string(), ui_field_name);
-                        }
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("from") {
+                        vec_name = Some(meta.value()?.parse::<LitStr>()?.parse::<Ident>()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error(
+                            "invalid #[vec] attribute, expected #[vec(from = \"ui_field_name\")]",
+                        ))
                     }
-                    _ => {
-                        panic!(
-                            "Invalid #[vec] attribute format. Expected #[vec(name = \"field_name\")]"
-                        );
+                })?;
+            } else if attr.path().is_ident("from_field") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        rename = Some(meta.value()?.parse::<LitStr>()?.parse::<Ident>()?);
+                    } else if meta.path.is_ident("skip") {
+                        skip = true;
+                    } else if meta.path.is_ident("default") {
+                        default = Some(meta.value()?.parse::<Expr>()?);
+                    } else {
+                        return Err(meta.error(
+                            "invalid #[from_field] attribute, expected `rename = \"ui_field_name\"`, `skip` or `default = expr`",
+                        ));
                     }
-                }
+                    Ok(())
+                })?;
             }
         }
+
+        // A field with a default value isn't in the UI type
+        if skip || default.is_some() {
+            if rename.is_some() || vec_name.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "a skipped field can't be mapped to a UI field",
+                ));
+            }
+
+            let default = match default {
+                Some(default) => quote! { #default },
+                None => quote! { Default::default() },
+            };
+            return Ok(Self::Skip(default));
+        }
+
+        match (vec_name, rename) {
+            (Some(_), Some(_)) => Err(syn::Error::new_spanned(
+                field,
+                "a vector field is renamed by #[vec(from = \"ui_field_name\")]",
+            )),
+            (Some(vec_name), None) => Ok(Self::Vec(vec_name)),
+            (None, rename) => Ok(Self::Field(rename.unwrap_or(field_name))),
+        }
     }
+}
 
-    let field_conversions = fields.iter().filter_map(|field| {
-        let field_name = &field.ident;
-        let field_name_str = field_name.as_ref().unwrap().to_string();
+fn from_convert(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
 
-        // Check if this field is mapped to a UI field
-        let is_vec_field = vec_field_mappings.contains_key(&field_name_str);
+    let mut target_type = None;
+    let mut vec_names_ui = vec![];
 
-        if is_vec_field {
-            None
-        } else {
-            Some(quote! {
-                #field_name: entry.#field_name.into()
-            })
+    for attr in &input.attrs {
+        // find `#[from("Type")]`
+        if attr.path().is_ident("from") {
+            target_type = Some(attr.parse_args::<LitStr>()?.parse::<syn::Path>()?);
         }
-    });
 
-    let field_conversions_duplicta = field_conversions.clone();
+        // find `#[vec_ui("vec_name")]`
+        if attr.path().is_ident("vec_ui") {
+            vec_names_ui.push(attr.parse_args::<LitStr>()?.parse::<Ident>()?);
+        }
+    }
 
-    // Handle field-level vec mappings
-    let field_vec_conversions = vec_field_mappings.iter().map(|(field_name, ui_field_name)| {
-        let field_ident = syn::parse_str::<syn::Ident>(field_name).unwrap();
-        quote! {
-            #field_ident: entry.#ui_field_name.iter().map(|item| item.clone().into()).collect::<Vec<_>>()
+    let target_type = target_type.ok_or_else(|| {
+        syn::Error::new_spanned(name, "Must specify target type with #[from(\"Type\")]")
+    })?;
+
+    let fields = match &input.data {
+        Data::Enum(data_enum) => return enum_convert(name, &target_type, data_enum),
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields_named) => &fields_named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "SlintFromConvert only works on structs with named fields",
+                ));
+            }
+        },
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "SlintFromConvert only works on structs and enums",
+            ));
         }
-    });
+    };
+
+    let mut ui_conversions = vec![];
+    let mut conversions = vec![];
 
-    let field_vec_conversions_slint =
-        vec_field_mappings
-            .iter()
-            .map(|(field_name, ui_field_name)| {
-                let field_ident = syn::parse_str::<syn::Ident>(field_name).unwrap();
-                quote! {
-                 #ui_field_name: slint::ModelRc::new(
+    for field in fields {
+        let field_name = &field.ident;
+
+        match FieldMapping::parse(field)? {
+            FieldMapping::Field(ui_field_name) => {
+                ui_conversions.push(quote! {
+                    #ui_field_name: entry.#field_name.into()
+                });
+                conversions.push(quote! {
+                    #field_name: entry.#ui_field_name.into()
+                });
+            }
+            FieldMapping::Vec(ui_field_name) => {
+                ui_conversions.push(quote! {
+                    #ui_field_name: slint::ModelRc::new(
                         entry
-                            .#field_ident
+                            .#field_name
                             .into_iter()
                             .map(|item| item.into())
                             .collect::<slint::VecModel<_>>()
                     )
-                }
-            });
+                });
+                conversions.push(quote! {
+                    #field_name: entry.#ui_field_name.iter().map(|item| item.clone().into()).collect::<Vec<_>>()
+                });
+            }
+            FieldMapping::Skip(default) => conversions.push(quote! {
+                #field_name: #default
+            }),
+        }
+    }
 
     let vec_name_ui_conversions_slint = vec_names_ui.iter().map(|name| {
         quote! {
@@ -203,12 +293,13 @@ pub fn from_convert_derive(input: TokenStream) -> TokenStream {
         }
     });
 
-    let expanded = quote! {
+    Ok(quote! {
         impl From<#name> for #target_type {
+            // The UI type may have no other fields
+            #[allow(clippy::needless_update)]
             fn from(entry: #name) -> Self {
                 Self {
-                    #(#field_conversions,)*
-                    #(#field_vec_conversions_slint,)*
+                    #(#ui_conversions,)*
                     #(#vec_name_ui_conversions_slint,)*
                     ..Default::default()
                 }
@@ -218,12 +309,204 @@ pub fn from_convert_derive(input: TokenStream) -> TokenStream {
         impl From<#target_type> for #name {
             fn from(entry: #target_type) -> Self {
                 Self {
-                    #(#field_conversions_duplicta,)*
-                    #(#field_vec_conversions,)*
+                    #(#conversions,)*
                 }
             }
         }
+    })
+}
+
+// Map the unit variants of an enum to the UI variants of the same names, or the
+// names of `#[enum_map("UIVariant")]`
+fn enum_convert(
+    name: &Ident,
+    target_type: &syn::Path,
+    data_enum: &syn::DataEnum,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut variants = vec![];
+    let mut ui_variants = vec![];
+
+    for variant in &data_enum.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "SlintFromConvert only works on enums with unit variants",
+            ));
+        }
+
+        let mut ui_variant = variant.ident.clone();
+        for attr in &variant.attrs {
+            if attr.path().is_ident("enum_map") {
+                ui_variant = attr.parse_args::<LitStr>()?.parse::<Ident>()?;
+            }
+        }
+
+        variants.push(&variant.ident);
+        ui_variants.push(ui_variant);
+    }
+
+    Ok(quote! {
+        impl From<#name> for #target_type {
+            fn from(entry: #name) -> Self {
+                match entry {
+                    #(#name::#variants => #target_type::#ui_variants,)*
+                }
+            }
+        }
+
+        impl From<#target_type> for #name {
+            fn from(entry: #target_type) -> Self {
+                match entry {
+                    #(#target_type::#ui_variants => #name::#variants,)*
+                }
+            }
+        }
+    })
+}
+
+/// Derive macro implementing `sqldb::Table` for a struct stored as a JSON record.
+///
+/// # Attributes
+///
+/// - `#[table(key)]`: The field of the unique key of the record, exactly one is required
+/// - `#[table(index)]`: The field is an index column, which can be filtered and ordered by.
+///   Integers and `bool` are `INTEGER` columns, floats are `REAL` and the others `TEXT`
+/// - `#[table(search)]`: The text of the field is indexed for `Repository::search`. The type
+///   of the field implements `sqldb::SearchText`
+///
+/// The index columns are read from the JSON record by the field names, so they can't be
+/// renamed by `serde`. `id`, `uuid` and `data` are the columns of the table itself.
+///
+/// # Example
+///
+/// ```ignore
+/// use pmacro::SqlTable;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, SqlTable)]
+/// struct Recording {
+///     #[table(key)]
+///     id: String,
+///
+///     #[table(index)]
+///     file: String,
+///
+///     #[table(index)]
+///     size: u64,
+///
+///     #[table(search)]
+///     title: String,
+/// }
+/// ```
+#[proc_macro_derive(SqlTable, attributes(table))]
+pub fn sql_table_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+
+    let fields = if let Data::Struct(data_struct) = input.data {
+        if let Fields::Named(fields_named) = data_struct.fields {
+            fields_named.named
+        } else {
+            panic!("SqlTable only works on structs with named fields");
+        }
+    } else {
+        panic!("SqlTable only works on structs");
+    };
+
+    let mut key_field = None;
+    let mut index_columns = vec![];
+    let mut search_fields = vec![];
+
+    for field in &fields {
+        let field_name = field.ident.as_ref().unwrap();
+
+        for attr in &field.attrs {
+            if !attr.path().is_ident("table") {
+                continue;
+            }
+
+            match attr.parse_args::<syn::Ident>() {
+                Ok(ident) if ident == "key" => {
+                    if key_field.replace(field_name.clone()).is_some() {
+                        panic!("Only one field can be #[table(key)]");
+                    }
+                }
+                Ok(ident) if ident == "index" => {
+                    let column = field_name.to_string();
+                    if ["id", "uuid", "data"].contains(&column.as_str()) {
+                        panic!("`{column}` is a column of the table, rename the field to index it");
+                    }
+
+                    let column_type = match sql_column_type(&field.ty) {
+                        "INTEGER" => quote! { ::sqldb::ColumnType::Integer },
+                        "REAL" => quote! { ::sqldb::ColumnType::Real },
+                        _ => quote! { ::sqldb::ColumnType::Text },
+                    };
+
+                    index_columns.push(quote! {
+                        ::sqldb::Column::new(#column, #column_type)
+                    });
+                }
+                Ok(ident) if ident == "search" => search_fields.push(field_name.clone()),
+                _ => panic!(
+                    "Invalid #[table] attribute format. Expected #[table(key)], #[table(index)] or #[table(search)]"
+                ),
+            }
+        }
+    }
+
+    let key_field = key_field.expect("Must specify the key field with #[table(key)]");
+
+    let search_impl = if search_fields.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            const SEARCHABLE: bool = true;
+
+            fn search_text(&self) -> String {
+                let mut text = String::default();
+                #(::sqldb::SearchText::push_search_text(&self.#search_fields, &mut text);)*
+                text
+            }
+        }
+    };
+
+    let expanded = quote! {
+        impl ::sqldb::Table for #name {
+            const INDEXES: &'static [::sqldb::Column] = &[#(#index_columns,)*];
+
+            fn key(&self) -> String {
+                self.#key_field.to_string()
+            }
+
+            #search_impl
+        }
     };
 
     TokenStream::from(expanded)
 }
+
+// The SQL type of the field, `Option<T>` is the type of `T`
+fn sql_column_type(ty: &Type) -> &'static str {
+    let Type::Path(type_path) = ty else {
+        return "TEXT";
+    };
+
+    let Some(segment) = type_path.path.segments.last() else {
+        return "TEXT";
+    };
+
+    if segment.ident == "Option"
+        && let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+        && let Some(syn::GenericArgument::Type(inner)) = args.args.first()
+    {
+        return sql_column_type(inner);
+    }
+
+    match segment.ident.to_string().as_str() {
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+        | "bool" => "INTEGER",
+        "f32" | "f64" => "REAL",
+        _ => "TEXT",
+    }
+}
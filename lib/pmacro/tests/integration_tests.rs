@@ -117,4 +117,120 @@ fn test_rust_to_ui_conversion() {
     assert_eq!(*ui.items, vec!["a".to_string(), "b".to_string()]);
     assert_eq!(*ui.numbers, vec![10, 20]);
     assert_eq!(*ui.empty_vec, vec![]);
-}
\ No newline at end of file
+}
+/// UI enum with a differently named variant
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum TestUIRole {
+    #[default]
+    Guest,
+    Member,
+    Admin,
+}
+
+/// UI struct nesting other UI types
+#[derive(Debug, Clone, PartialEq, Default)]
+struct TestUINested {
+    basic: TestUIBasic,
+    role: TestUIRole,
+    roles: ModelRc<TestUIRole>,
+    members: ModelRc<TestUIBasic>,
+}
+
+/// Test enum mapped to the UI enum
+#[derive(Debug, Clone, Copy, PartialEq, SlintFromConvert)]
+#[from("TestUIRole")]
+enum TestRole {
+    Guest,
+    Member,
+    #[enum_map("Admin")]
+    Administrator,
+}
+
+/// Test struct with nested struct and enum fields
+#[derive(Debug, Clone, PartialEq, SlintFromConvert)]
+#[from("TestUINested")]
+struct TestNested {
+    basic: TestBasic,
+    role: TestRole,
+    #[vec(from = "roles")]
+    roles: Vec<TestRole>,
+    #[vec(from = "members")]
+    members: Vec<TestBasic>,
+}
+
+#[test]
+fn test_enum_conversion() {
+    assert_eq!(TestUIRole::from(TestRole::Guest), TestUIRole::Guest);
+    assert_eq!(TestUIRole::from(TestRole::Member), TestUIRole::Member);
+    assert_eq!(TestUIRole::from(TestRole::Administrator), TestUIRole::Admin);
+    assert_eq!(TestRole::from(TestUIRole::Admin), TestRole::Administrator);
+}
+
+#[test]
+fn test_nested_conversion() {
+    let original = TestNested {
+        basic: TestBasic {
+            name: "Frank".to_string(),
+            age: 40,
+        },
+        role: TestRole::Administrator,
+        roles: vec![TestRole::Member, TestRole::Guest],
+        members: vec![TestBasic {
+            name: "Grace".to_string(),
+            age: 20,
+        }],
+    };
+
+    let ui: TestUINested = original.clone().into();
+    assert_eq!(ui.basic.name, "Frank");
+    assert_eq!(ui.role, TestUIRole::Admin);
+    assert_eq!(*ui.roles, vec![TestUIRole::Member, TestUIRole::Guest]);
+    assert_eq!(ui.members[0].name, "Grace");
+
+    let converted_back: TestNested = ui.into();
+    assert_eq!(original, converted_back);
+}
+
+/// UI struct with fields named differently from the Rust struct
+#[derive(Debug, Clone, PartialEq, Default)]
+struct TestUIRenamed {
+    title: String,
+    count: u32,
+}
+
+/// Test struct with renamed, skipped and default fields
+#[derive(Debug, Clone, PartialEq, SlintFromConvert)]
+#[from("TestUIRenamed")]
+struct TestRenamed {
+    #[from_field(rename = "title")]
+    name: String,
+    count: u32,
+    #[from_field(skip)]
+    cache: Vec<u8>,
+    #[from_field(default = 3)]
+    retries: u32,
+    #[from_field(default = String::from("en"))]
+    language: String,
+}
+
+#[test]
+fn test_field_attributes_conversion() {
+    let original = TestRenamed {
+        name: "Heidi".to_string(),
+        count: 5,
+        cache: vec![1, 2, 3],
+        retries: 1,
+        language: "fr".to_string(),
+    };
+
+    let ui: TestUIRenamed = original.into();
+    assert_eq!(ui.title, "Heidi");
+    assert_eq!(ui.count, 5);
+
+    let converted_back: TestRenamed = ui.into();
+    assert_eq!(converted_back.name, "Heidi");
+    assert_eq!(converted_back.count, 5);
+    assert!(converted_back.cache.is_empty());
+    assert_eq!(converted_back.retries, 3);
+    assert_eq!(converted_back.language, "en");
+}
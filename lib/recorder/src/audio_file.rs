@@ -0,0 +1,387 @@
+//! Writers of the audio-only recordings, see `ProcessMode::RecordAudio`.
+
+use crate::RecorderError;
+use mp4::{
+    AacConfig, ChannelConfig, MediaConfig, Mp4Config, Mp4Sample, Mp4Writer, SampleFreqIndex,
+    TrackConfig, TrackType,
+};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use srtmp::{AacEncoder, AacEncoderConfig};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use wrtc::{OpusChannels, opus::OpusCoder};
+
+const AAC_BITRATE: u32 = 128_000;
+
+// The samples of the encoder delay at 48 kHz, which the players skip
+const OPUS_PRE_SKIP: u16 = 312;
+const OGG_SERIAL: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    /// AAC in an MP4 container
+    #[default]
+    M4a,
+
+    /// Opus in an Ogg container
+    Ogg,
+}
+
+impl AudioFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Ogg => "ogg",
+        }
+    }
+}
+
+pub(crate) trait AudioFileWriter {
+    /// Interleaved samples of any length
+    fn write(&mut self, samples: &[f32]) -> Result<(), RecorderError>;
+
+    /// Encode the buffered samples and finish the file
+    fn finish(self: Box<Self>) -> Result<(), RecorderError>;
+}
+
+pub(crate) fn new_audio_file_writer(
+    format: AudioFormat,
+    save_path: &Path,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<Box<dyn AudioFileWriter>, RecorderError> {
+    Ok(match format {
+        AudioFormat::M4a => Box::new(M4aWriter::new(save_path, sample_rate, channels)?),
+        AudioFormat::Ogg => Box::new(OggOpusWriter::new(save_path, sample_rate, channels)?),
+    })
+}
+
+// Splits the samples into the frames of the encoder, the rest is kept for
+// the next write
+fn take_frames(buffer: &mut Vec<f32>, samples: &[f32], frame_len: usize) -> Vec<Vec<f32>> {
+    buffer.extend_from_slice(samples);
+
+    let count = buffer.len() / frame_len.max(1);
+    let frames = buffer
+        .chunks_exact(frame_len.max(1))
+        .take(count)
+        .map(<[f32]>::to_vec)
+        .collect();
+
+    buffer.drain(..count * frame_len);
+    frames
+}
+
+fn audio_encoding_error(e: impl std::fmt::Display) -> RecorderError {
+    RecorderError::AudioEncodingFailed(e.to_string())
+}
+
+struct M4aWriter {
+    save_path: PathBuf,
+    writer: Mp4Writer<BufWriter<File>>,
+    encoder: AacEncoder,
+    frame_len: usize,
+    buffer: Vec<f32>,
+    timestamp: u64,
+}
+
+impl M4aWriter {
+    fn new(save_path: &Path, sample_rate: u32, channels: u16) -> Result<Self, RecorderError> {
+        let encoder = AacEncoder::new(
+            AacEncoderConfig::new(sample_rate, channels as u8)
+                .map_err(audio_encoding_error)?
+                .with_bitrate(AAC_BITRATE),
+        )
+        .map_err(audio_encoding_error)?;
+
+        let config = Mp4Config {
+            major_brand: str::parse("M4A ").unwrap(),
+            minor_version: 512,
+            compatible_brands: vec![
+                str::parse("M4A ").unwrap(),
+                str::parse("isom").unwrap(),
+                str::parse("iso2").unwrap(),
+                str::parse("mp41").unwrap(),
+            ],
+            timescale: sample_rate,
+        };
+
+        let file = BufWriter::new(File::create(save_path)?);
+        let mut writer = Mp4Writer::write_start(file, &config).map_err(audio_encoding_error)?;
+
+        writer
+            .add_track(&TrackConfig {
+                track_type: TrackType::Audio,
+                timescale: sample_rate,
+                language: "und".to_string(),
+                media_conf: MediaConfig::AacConfig(AacConfig {
+                    bitrate: AAC_BITRATE,
+                    profile: mp4::AudioObjectType::AacLowComplexity,
+                    freq_index: sample_freq_index(sample_rate)?,
+                    chan_conf: if channels == 1 {
+                        ChannelConfig::Mono
+                    } else {
+                        ChannelConfig::Stereo
+                    },
+                }),
+            })
+            .map_err(audio_encoding_error)?;
+
+        Ok(Self {
+            save_path: save_path.to_path_buf(),
+            frame_len: encoder.input_frame_size() * channels as usize,
+            writer,
+            encoder,
+            buffer: vec![],
+            timestamp: 0,
+        })
+    }
+
+    fn encode_frame(&mut self, frame: &[f32]) -> Result<(), RecorderError> {
+        let data = self.encoder.encode(frame).map_err(audio_encoding_error)?;
+
+        // The first frames only fill the delay of the encoder
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let duration = self.encoder.input_frame_size() as u32;
+        self.writer
+            .write_sample(
+                1,
+                &Mp4Sample {
+                    start_time: self.timestamp,
+                    duration,
+                    rendering_offset: 0,
+                    is_sync: true,
+                    bytes: data.into(),
+                },
+            )
+            .map_err(audio_encoding_error)?;
+
+        self.timestamp += duration as u64;
+        Ok(())
+    }
+}
+
+impl AudioFileWriter for M4aWriter {
+    fn write(&mut self, samples: &[f32]) -> Result<(), RecorderError> {
+        for frame in take_frames(&mut self.buffer, samples, self.frame_len) {
+            self.encode_frame(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), RecorderError> {
+        if !self.buffer.is_empty() {
+            let mut frame = std::mem::take(&mut self.buffer);
+            frame.resize(self.frame_len, 0.0);
+            self.encode_frame(&frame)?;
+        }
+
+        self.writer.write_end().map_err(audio_encoding_error)?;
+        log::info!("Wrote the audio into `{}`", self.save_path.display());
+
+        Ok(())
+    }
+}
+
+fn sample_freq_index(sample_rate: u32) -> Result<SampleFreqIndex, RecorderError> {
+    Ok(match sample_rate {
+        96000 => SampleFreqIndex::Freq96000,
+        88200 => SampleFreqIndex::Freq88200,
+        64000 => SampleFreqIndex::Freq64000,
+        48000 => SampleFreqIndex::Freq48000,
+        44100 => SampleFreqIndex::Freq44100,
+        32000 => SampleFreqIndex::Freq32000,
+        24000 => SampleFreqIndex::Freq24000,
+        22050 => SampleFreqIndex::Freq22050,
+        16000 => SampleFreqIndex::Freq16000,
+        12000 => SampleFreqIndex::Freq12000,
+        11025 => SampleFreqIndex::Freq11025,
+        8000 => SampleFreqIndex::Freq8000,
+        7350 => SampleFreqIndex::Freq7350,
+        _ => {
+            return Err(RecorderError::AudioEncodingFailed(format!(
+                "AAC doesn't support the sample rate {sample_rate}"
+            )));
+        }
+    })
+}
+
+struct OggOpusWriter {
+    save_path: PathBuf,
+    writer: PacketWriter<BufWriter<File>>,
+    coder: OpusCoder,
+    frame_len: usize,
+    buffer: Vec<f32>,
+
+    // Held back until the next one, the last packet has to end the stream
+    pending_packet: Option<Vec<u8>>,
+
+    // The granule position after the written packets, at 48 kHz
+    granule_position: u64,
+}
+
+impl OggOpusWriter {
+    fn new(save_path: &Path, sample_rate: u32, channels: u16) -> Result<Self, RecorderError> {
+        let opus_channels = match channels {
+            1 => OpusChannels::Mono,
+            2 => OpusChannels::Stereo,
+            _ => {
+                return Err(RecorderError::AudioEncodingFailed(format!(
+                    "Opus supports 1 or 2 channels, got {channels}"
+                )));
+            }
+        };
+
+        let coder = OpusCoder::new(sample_rate, opus_channels).map_err(audio_encoding_error)?;
+        let mut writer = PacketWriter::new(BufWriter::new(File::create(save_path)?));
+
+        // Each header is on its own page, see RFC 7845
+        writer.write_packet(
+            opus_head(channels as u8, sample_rate).into_boxed_slice(),
+            OGG_SERIAL,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+        writer.write_packet(
+            opus_tags().into_boxed_slice(),
+            OGG_SERIAL,
+            PacketWriteEndInfo::EndPage,
+            0,
+        )?;
+
+        Ok(Self {
+            save_path: save_path.to_path_buf(),
+            frame_len: coder.input_samples_per_frame(),
+            writer,
+            coder,
+            buffer: vec![],
+            pending_packet: None,
+            granule_position: OPUS_PRE_SKIP as u64,
+        })
+    }
+
+    fn encode_frame(&mut self, frame: &[f32]) -> Result<(), RecorderError> {
+        let packet = self.coder.encode(frame).map_err(audio_encoding_error)?;
+        if let Some(pending_packet) = self.pending_packet.replace(packet) {
+            self.write_packet(pending_packet, PacketWriteEndInfo::NormalPacket)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_packet(
+        &mut self,
+        packet: Vec<u8>,
+        end_info: PacketWriteEndInfo,
+    ) -> Result<(), RecorderError> {
+        self.granule_position += self.coder.frame_size() as u64;
+        self.writer.write_packet(
+            packet.into_boxed_slice(),
+            OGG_SERIAL,
+            end_info,
+            self.granule_position,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl AudioFileWriter for OggOpusWriter {
+    fn write(&mut self, samples: &[f32]) -> Result<(), RecorderError> {
+        for frame in take_frames(&mut self.buffer, samples, self.frame_len) {
+            self.encode_frame(&frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), RecorderError> {
+        if !self.buffer.is_empty() {
+            let mut frame = std::mem::take(&mut self.buffer);
+            frame.resize(self.frame_len, 0.0);
+            self.encode_frame(&frame)?;
+        }
+
+        if let Some(packet) = self.pending_packet.take() {
+            self.write_packet(packet, PacketWriteEndInfo::EndStream)?;
+        }
+
+        let mut file = self.writer.into_inner();
+        std::io::Write::flush(&mut file)?;
+        log::info!("Wrote the audio into `{}`", self.save_path.display());
+
+        Ok(())
+    }
+}
+
+// The identification header, see RFC 7845 section 5.1
+fn opus_head(channels: u8, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels);
+    head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+
+    // Output gain and the mapping family for mono or stereo
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+// The comment header without comments, see RFC 7845 section 5.2
+fn opus_tags() -> Vec<u8> {
+    let vendor = format!("wayshot {}", env!("CARGO_PKG_VERSION"));
+
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_frames() {
+        let mut buffer = vec![];
+
+        assert!(take_frames(&mut buffer, &[0.0; 3], 4).is_empty());
+        assert_eq!(buffer.len(), 3);
+
+        let frames = take_frames(&mut buffer, &[1.0; 6], 4);
+        assert_eq!(frames, vec![vec![0.0, 0.0, 0.0, 1.0], vec![1.0; 4]]);
+        assert_eq!(buffer, vec![1.0]);
+    }
+
+    #[test]
+    fn test_opus_head() {
+        let head = opus_head(2, 44100);
+
+        assert_eq!(head.len(), 19);
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(head[9], 2);
+        assert_eq!(u16::from_le_bytes([head[10], head[11]]), OPUS_PRE_SKIP);
+        assert_eq!(
+            u32::from_le_bytes([head[12], head[13], head[14], head[15]]),
+            44100
+        );
+    }
+
+    #[test]
+    fn test_opus_tags() {
+        let tags = opus_tags();
+        let vendor_len = u32::from_le_bytes([tags[8], tags[9], tags[10], tags[11]]) as usize;
+
+        assert_eq!(&tags[..8], b"OpusTags");
+        assert_eq!(tags.len(), 8 + 4 + vendor_len + 4);
+    }
+}
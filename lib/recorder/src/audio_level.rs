@@ -1,3 +1,5 @@
+use audio_utils::loudness::LoudnessMeter;
+
 pub fn calc_rms_level(samples: &[f32]) -> Option<f32> {
     if samples.is_empty() {
         return None;
@@ -48,6 +50,13 @@ pub fn calc_lufs_style(samples: &[f32]) -> Option<f32> {
     Some(-0.691 + 10.0 * mean_square.log10())
 }
 
+/// Momentary loudness in LUFS after pushing `samples` into `meter`, `None`
+/// until 400ms are measured. Silence is -200 as in `calc_rms_level`
+pub fn calc_loudness_level(meter: &mut LoudnessMeter, samples: &[f32]) -> Option<f32> {
+    meter.push(samples);
+    meter.momentary().map(|lufs| lufs.max(-200.0))
+}
+
 pub fn db_to_normalized(db: f32, min_db: f32, max_db: f32) -> f32 {
     let clamped = db.clamp(min_db, max_db);
     (clamped - min_db) / (max_db - min_db)
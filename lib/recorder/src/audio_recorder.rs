@@ -1,4 +1,5 @@
-use crate::{RealTimeDenoise, apply_gain, calc_rms_level, denoise_model};
+use crate::{RealTimeDenoise, apply_gain, calc_loudness_level, denoise_model};
+use audio_utils::loudness::LoudnessMeter;
 use cpal::{
     Device, Host, InputCallbackInfo, SampleFormat, Stream, StreamConfig,
     traits::{DeviceTrait, HostTrait, StreamTrait},
@@ -197,8 +198,8 @@ impl AudioRecorder {
         // Note:
         //  Without calling `denoise.flush` is not a problem.
         //  Just losing the last frame of real-time samples.
+        let spec = self.spec(device_name)?;
         let mut denoiser = if self.enable_denoise {
-            let spec = self.spec(device_name)?;
             let denoiser = RealTimeDenoise::new(&DENOISE_MODEL, spec)
                 .map_err(|e| AudioRecorderError::DenoiseError(e.to_string()))?;
             Some(denoiser)
@@ -209,6 +210,7 @@ impl AudioRecorder {
         let gain = self.gain.clone();
         let level_sender = self.level_sender.clone();
         let frame_sender = self.frame_sender.clone();
+        let mut loudness_meter = LoudnessMeter::new(spec.sample_rate, spec.channels);
 
         let stream = self.stream_play(device_name, move |f32_samples: &[f32], _info: &_| {
             let mut denoise_samples = None;
@@ -243,7 +245,7 @@ impl AudioRecorder {
             }
 
             if let Some(ref tx) = level_sender
-                && let Some(db) = calc_rms_level(f32_samples)
+                && let Some(db) = calc_loudness_level(&mut loudness_meter, f32_samples)
                 && let Err(e) = tx.try_send(db)
             {
                 log::warn!("try send input audio db level data failed: {e}");
@@ -0,0 +1,278 @@
+//! Measures how late the microphone is against the screen capture. The caller
+//! flashes a white area on the screen and plays a beep at the same time, the
+//! offset is the time between the flash in the frames and the beep in the
+//! microphone samples.
+//!
+//! The latency of the speakers is measured too, so calibrate with the
+//! speakers which are used while recording, not a Bluetooth headset.
+
+use crate::{AudioRecorder, RecorderError};
+use crossbeam::channel::{bounded, unbounded};
+use derive_setters::Setters;
+use screen_capture::{Capture, CaptureStreamConfig, ScreenCapture};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// Only every 8th pixel of every 8th row is checked
+const SAMPLE_STEP: usize = 8;
+
+// A pixel brighter than it in every channel counts as a part of the flash
+const WHITE_THRESHOLD: u8 = 240;
+
+// The flash has to cover this ratio of the screen at least
+const MIN_FLASH_RATIO: f32 = 0.02;
+
+// The audio level is measured in windows of the length
+const LEVEL_WINDOW: Duration = Duration::from_millis(5);
+
+// The beep has to be this much louder than the background noise
+const MIN_BEEP_RATIO: f32 = 4.0;
+const MIN_BEEP_LEVEL: f32 = 0.01;
+
+const CAPTURE_FPS: f64 = 60.0;
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+pub struct AvCalibrationConfig {
+    pub screen_name: String,
+    pub audio_device_name: String,
+
+    /// Captured before the flash, to learn the quiet level
+    pub lead_in: Duration,
+
+    /// Captured after the flash
+    pub duration: Duration,
+}
+
+impl AvCalibrationConfig {
+    pub fn new(screen_name: String, audio_device_name: String) -> Self {
+        Self {
+            screen_name,
+            audio_device_name,
+            lead_in: Duration::from_millis(500),
+            duration: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Returns the value for `RecorderConfig::av_offset_ms`. `flash` is called
+/// once both captures run, it should flash the screen and play the beep.
+pub fn calibrate_av_offset(
+    config: AvCalibrationConfig,
+    screen_capturer: impl ScreenCapture + Send + 'static,
+    flash: impl FnOnce(),
+) -> Result<i32, RecorderError> {
+    let epoch = Instant::now();
+    let stop_sig = Arc::new(AtomicBool::new(false));
+    let sync_sig = Arc::new(AtomicBool::new(false));
+
+    let (frame_sender, frame_receiver) = unbounded();
+    let capture_config = CaptureStreamConfig {
+        name: config.screen_name.clone(),
+        include_cursor: false,
+        fps: Some(CAPTURE_FPS),
+        cancel_sig: stop_sig.clone(),
+        sync_sig: sync_sig.clone(),
+    };
+
+    let capture_worker = thread::spawn(move || {
+        screen_capturer.capture_output_stream(capture_config, move |cb_data| {
+            _ = frame_sender.send((epoch.elapsed(), white_ratio(&cb_data.data)));
+        })
+    });
+
+    let started_at = Instant::now();
+    while !sync_sig.load(Ordering::Relaxed) {
+        if started_at.elapsed() > SYNC_TIMEOUT || capture_worker.is_finished() {
+            stop_sig.store(true, Ordering::Relaxed);
+            return Err(RecorderError::Other(
+                "waiting synchronization signal for a long time".to_string(),
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let (audio_sender, audio_receiver) = bounded(1024);
+    let mut audio_recorder = AudioRecorder::new().with_frame_sender(Some(audio_sender));
+    let spec = audio_recorder.spec(&config.audio_device_name)?;
+    if let Err(e) = audio_recorder.start_recording(&config.audio_device_name) {
+        stop_sig.store(true, Ordering::Relaxed);
+        return Err(e.into());
+    }
+
+    let audio_worker = thread::spawn(move || {
+        let (mut start, mut samples) = (None, vec![]);
+
+        while let Ok(chunk) = audio_receiver.recv() {
+            // The first chunk was recorded before it arrived
+            start.get_or_insert_with(|| {
+                let frames = chunk.len() as u64 / spec.channels.max(1) as u64;
+                epoch.elapsed().saturating_sub(Duration::from_micros(
+                    frames * 1_000_000 / spec.sample_rate.max(1) as u64,
+                ))
+            });
+            samples.extend(chunk);
+        }
+
+        (start, samples)
+    });
+
+    thread::sleep(config.lead_in);
+    flash();
+    thread::sleep(config.duration);
+
+    audio_recorder.stop();
+    stop_sig.store(true, Ordering::Relaxed);
+
+    let (audio_start, samples) = audio_worker.join().unwrap_or_default();
+    if let Ok(Err(e)) = capture_worker.join() {
+        log::warn!("calibration capture exit. error: {e}");
+    }
+    let frames = frame_receiver.try_iter().collect::<Vec<_>>();
+
+    let flash_time = detect_flash(&frames).ok_or_else(|| {
+        RecorderError::Other("no flash is found on the captured screen".to_string())
+    })?;
+
+    let beep_time = audio_start
+        .zip(detect_beep(&samples, spec.sample_rate, spec.channels))
+        .map(|(start, offset)| start + offset)
+        .ok_or_else(|| {
+            RecorderError::Other("no beep is found in the microphone samples".to_string())
+        })?;
+
+    let offset_ms = flash_time.as_millis() as i64 - beep_time.as_millis() as i64;
+    log::info!("flash at {flash_time:.2?}, beep at {beep_time:.2?}, A/V offset: {offset_ms} ms");
+
+    Ok(offset_ms as i32)
+}
+
+// The ratio of the white pixels, which grows when the flash shows up
+fn white_ratio(capture: &Capture) -> f32 {
+    let (width, height) = (capture.width as usize, capture.height as usize);
+    let (mut white, mut total) = (0, 0);
+
+    for y in (0..height).step_by(SAMPLE_STEP) {
+        for x in (0..width).step_by(SAMPLE_STEP) {
+            let index = (y * width + x) * 4;
+            let Some(pixel) = capture.pixel_data.get(index..index + 3) else {
+                continue;
+            };
+
+            total += 1;
+            if pixel.iter().all(|v| *v >= WHITE_THRESHOLD) {
+                white += 1;
+            }
+        }
+    }
+
+    white as f32 / total.max(1) as f32
+}
+
+/// The time of the first frame which is half way to the brightest one
+fn detect_flash(frames: &[(Duration, f32)]) -> Option<Duration> {
+    let baseline = frames.first()?.1;
+    let max = frames
+        .iter()
+        .map(|(_, ratio)| *ratio)
+        .fold(baseline, f32::max);
+
+    if max - baseline < MIN_FLASH_RATIO {
+        return None;
+    }
+
+    let threshold = baseline + (max - baseline) / 2.0;
+    frames
+        .iter()
+        .find(|(_, ratio)| *ratio >= threshold)
+        .map(|(timestamp, _)| *timestamp)
+}
+
+/// The time from the first sample to the start of the beep
+fn detect_beep(samples: &[f32], sample_rate: u32, channels: u16) -> Option<Duration> {
+    let channels = channels.max(1) as usize;
+    let window_len =
+        (sample_rate as u128 * LEVEL_WINDOW.as_millis() / 1000).max(1) as usize * channels;
+
+    let levels = samples
+        .chunks(window_len)
+        .map(|window| (window.iter().map(|v| v * v).sum::<f32>() / window.len() as f32).sqrt())
+        .collect::<Vec<_>>();
+
+    // The beep is short, so the median is the background noise
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise = *sorted.get(sorted.len() / 2)?;
+    let max = *sorted.last()?;
+
+    if max < MIN_BEEP_LEVEL || max < noise * MIN_BEEP_RATIO {
+        return None;
+    }
+
+    let threshold = noise + (max - noise) / 2.0;
+    levels
+        .iter()
+        .position(|level| *level >= threshold)
+        .map(|index| LEVEL_WINDOW * index as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_flash() {
+        let frames = (0..60)
+            .map(|i| {
+                let ratio = if (30..40).contains(&i) { 0.3 } else { 0.05 };
+                (Duration::from_millis(i * 16), ratio)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(detect_flash(&frames), Some(Duration::from_millis(480)));
+    }
+
+    #[test]
+    fn test_detect_flash_without_flash() {
+        let frames = (0..60)
+            .map(|i| (Duration::from_millis(i * 16), 0.05 + (i % 2) as f32 * 0.01))
+            .collect::<Vec<_>>();
+
+        assert_eq!(detect_flash(&frames), None);
+    }
+
+    #[test]
+    fn test_detect_beep() {
+        let (sample_rate, channels) = (48000, 2);
+        let samples = (0..sample_rate as usize * 2)
+            .flat_map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                let noise = if i % 2 == 0 { 0.001 } else { -0.001 };
+                let v = if (1.25..1.35).contains(&t) {
+                    (t * 1000.0 * std::f32::consts::TAU).sin() * 0.5
+                } else {
+                    noise
+                };
+                [v; 2]
+            })
+            .collect::<Vec<_>>();
+
+        let beep = detect_beep(&samples, sample_rate, channels).unwrap();
+        assert!(beep.abs_diff(Duration::from_millis(1250)) <= LEVEL_WINDOW);
+    }
+
+    #[test]
+    fn test_detect_beep_in_silence() {
+        let samples = vec![0.001; 48000];
+        assert_eq!(detect_beep(&samples, 48000, 1), None);
+    }
+}
@@ -1,21 +1,24 @@
 use crate::{
-    AsyncErrorSender, ProcessMode, cursor_tracker::TransitionType, resolution::Resolution,
+    AsyncErrorSender, AudioFormat, ProcessMode, cursor_tracker::TransitionType,
+    recorder::ENCODER_WORKER_CHANNEL_SIZE, resolution::Resolution,
 };
 use background_remover::Model as BackgroundRemoverModel;
 use camera::{Shape, ShapeCircle};
 use chrono::Local;
 use derive_setters::Setters;
-use image_effect::realtime::RealtimeImageEffect;
-use screen_capture::LogicalSize;
+use image_effect::{annotate::Font, realtime::RealtimeImageEffect};
+use mp4m::Mp4Metadata;
+use screen_capture::{LogicalSize, Rectangle};
 use std::{
     collections::VecDeque,
     path::{Path, PathBuf},
     sync::{
         Arc,
-        atomic::{AtomicI32, AtomicU8},
+        atomic::{AtomicBool, AtomicI32, AtomicU8},
     },
     time::{Duration, Instant},
 };
+use video_encoder::RateControl;
 use wrtc::RTCIceServer;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -54,6 +57,22 @@ pub struct RecorderConfig {
     pub resolution: Resolution,
     pub include_cursor: bool,
 
+    /// How the cursor is drawn, it's ignored if the cursor isn't included
+    pub cursor_style: CursorStyleConfig,
+
+    /// Encode the first frame of a new scene as a keyframe, at most once per
+    /// second. The detection compares 1/16 of the luma samples of consecutive
+    /// frames, and each forced IDR frame is as large as a regular keyframe.
+    pub enable_scene_change_detection: bool,
+
+    /// Record only this region of the screen, in the pixels of the captured
+    /// frames. It's ignored while tracking the cursor.
+    #[setters(strip_option)]
+    pub capture_region: Option<Rectangle>,
+
+    /// Seconds to wait before capturing, 0 starts at once
+    pub countdown: u32,
+
     pub audio_device_name: Option<String>,
     pub enable_recording_speaker: bool,
     pub enable_audio_level_channel: bool,
@@ -61,6 +80,13 @@ pub struct RecorderConfig {
     pub enable_denoise: bool,
     pub convert_to_mono: bool,
 
+    /// Lower the speaker audio while the microphone picks up voice
+    pub enable_speaker_ducking: bool,
+
+    /// Milliseconds the microphone is shifted by against the screen and the
+    /// speaker, negative for the microphones with latency. See `calibrate_av_offset`
+    pub av_offset_ms: i32,
+
     #[setters(strip_option)]
     pub audio_gain: Option<Arc<AtomicI32>>,
 
@@ -68,6 +94,12 @@ pub struct RecorderConfig {
     pub speaker_gain: Option<Arc<AtomicI32>>,
 
     pub enable_cursor_tracking: bool,
+
+    /// Move the recorded area to the focused window. It's ignored while
+    /// tracking the cursor, and `region_width` x `region_height` is the
+    /// minimum size of the area.
+    pub enable_window_following: bool,
+
     pub region_width: i32,
     pub region_height: i32,
     pub debounce_radius: u32,
@@ -84,6 +116,21 @@ pub struct RecorderConfig {
     pub push_stream_config: PushStreamConfig,
     pub camera_mix_config: CameraMixConfig,
     pub realtime_image_effect: Arc<AtomicU8>,
+    pub preview_config: PreviewConfig,
+    pub dynamic_fps_config: DynamicFpsConfig,
+    pub live_caption_config: LiveCaptionConfig,
+    pub mp4_metadata: Mp4Metadata,
+
+    /// Seconds of `ProcessMode::RecordScreen` kept in memory for
+    /// `ReplaySaver`, 0 keeps none
+    pub replay_secs: u32,
+
+    /// The container and codec of `ProcessMode::RecordAudio`
+    pub audio_format: AudioFormat,
+
+    /// `None` picks the policy by the process mode, see `drop_policy`
+    #[setters(strip_option)]
+    pub frame_drop_policy: Option<FrameDropPolicy>,
 }
 
 impl RecorderConfig {
@@ -98,6 +145,10 @@ impl RecorderConfig {
             fps: FPS::Fps25,
             resolution: Resolution::P1080,
             include_cursor: true,
+            cursor_style: CursorStyleConfig::default(),
+            enable_scene_change_detection: true,
+            capture_region: None,
+            countdown: 0,
 
             audio_device_name: None,
             enable_recording_speaker: false,
@@ -109,8 +160,11 @@ impl RecorderConfig {
             speaker_gain: None,
             enable_denoise: false,
             convert_to_mono: false,
+            enable_speaker_ducking: false,
+            av_offset_ms: 0,
 
             enable_cursor_tracking: false,
+            enable_window_following: false,
             region_width: 1280,
             region_height: 720,
             debounce_radius: 30,
@@ -127,18 +181,80 @@ impl RecorderConfig {
             push_stream_config: PushStreamConfig::default(),
             camera_mix_config: CameraMixConfig::default(),
             realtime_image_effect: Arc::new(AtomicU8::new(RealtimeImageEffect::None.into())),
+            preview_config: PreviewConfig::default(),
+            dynamic_fps_config: DynamicFpsConfig::default(),
+            live_caption_config: LiveCaptionConfig::default(),
+            mp4_metadata: Mp4Metadata {
+                encoder: Some(format!("wayshot {}", env!("CARGO_PKG_VERSION"))),
+                ..Default::default()
+            },
+            replay_secs: 0,
+            audio_format: AudioFormat::default(),
+            frame_drop_policy: None,
         }
     }
 
+    /// The files should keep every frame, while the live viewers should
+    /// stay close to real time
+    pub fn drop_policy(&self) -> FrameDropPolicy {
+        self.frame_drop_policy.unwrap_or(match self.process_mode {
+            ProcessMode::RecordScreen | ProcessMode::RecordAudio => {
+                FrameDropPolicy::PreferCompleteness
+            }
+            ProcessMode::ShareScreen | ProcessMode::PushStream => FrameDropPolicy::PreferLatency,
+        })
+    }
+
+    /// The cursor is drawn by the recorder instead of the compositor, so it
+    /// can be styled
+    pub fn draw_cursor(&self) -> bool {
+        self.include_cursor && !self.cursor_style.is_default()
+    }
+
     pub fn frame_interval_ms(&self) -> u64 {
         (1000.0 / self.fps.to_u32() as f64) as u64
     }
 
+    /// The region to record clamped to the screen, with the even size the
+    /// video encoder requires
+    pub fn fixed_capture_region(&self) -> Option<Rectangle> {
+        if self.enable_cursor_tracking || self.enable_window_following {
+            return None;
+        }
+
+        let region = self.capture_region?;
+        let x = region.x.clamp(0, self.screen_size.width);
+        let y = region.y.clamp(0, self.screen_size.height);
+        let width = region.width.min(self.screen_size.width - x) & !1;
+        let height = region.height.min(self.screen_size.height - y) & !1;
+
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        Some(Rectangle::new(x, y, width, height))
+    }
+
+    /// Size of the recorded area, which the encoder size is scaled from
+    pub fn capture_size(&self) -> (u32, u32) {
+        match self.fixed_capture_region() {
+            Some(region) => (region.width as u32, region.height as u32),
+            None => (
+                self.screen_size.width as u32,
+                self.screen_size.height as u32,
+            ),
+        }
+    }
+
     pub fn make_filename(dir: impl AsRef<Path>) -> PathBuf {
         let mut filename = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
         filename.push_str(".mp4");
         dir.as_ref().to_path_buf().join(filename)
     }
+
+    pub fn make_audio_filename(dir: impl AsRef<Path>, format: AudioFormat) -> PathBuf {
+        Self::make_filename(dir).with_extension(format.extension())
+    }
 }
 
 #[non_exhaustive]
@@ -180,6 +296,9 @@ pub struct PushStreamConfig {
 
     pub query_params: String,
     pub save_mp4: bool,
+
+    // Most streaming platforms require CBR
+    pub rate_control: RateControl,
 }
 
 impl PushStreamConfig {
@@ -190,6 +309,7 @@ impl PushStreamConfig {
             stream_key,
             query_params: String::new(),
             save_mp4: true,
+            rate_control: RateControl::default(),
         }
     }
 }
@@ -211,6 +331,13 @@ pub struct CameraMixConfig {
 
     pub background_remover_model: Option<BackgroundRemoverModel>,
     pub background_remover_model_path: Option<PathBuf>,
+
+    /// Blur or replace the camera background instead of removing it.
+    /// Requires the background remover model.
+    pub camera_effect: camera::CameraEffect,
+
+    /// Weight of the previous background mask when blending it with a new one
+    pub mask_smoothing: f32,
 }
 
 impl Default for CameraMixConfig {
@@ -226,10 +353,195 @@ impl Default for CameraMixConfig {
             mirror_horizontal: false,
             background_remover_model: None,
             background_remover_model_path: None,
+            camera_effect: camera::CameraEffect::None,
+            mask_smoothing: 0.5,
         }
     }
 }
 
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Setters)]
+#[setters(prefix = "with_")]
+pub struct CursorStyleConfig {
+    /// Multiple of the normal cursor size, the cursor of the compositor is
+    /// often too small in the 4K recordings
+    pub scale: f32,
+
+    /// Draw a translucent circle under the cursor
+    pub highlight: bool,
+
+    /// Radius of the highlight circle in logical pixels
+    pub highlight_radius: u32,
+
+    /// RGBA color of the highlight circle
+    pub highlight_color: [u8; 4],
+
+    /// Hide the cursor after it stays still for the duration
+    #[setters(strip_option)]
+    pub hide_when_idle: Option<Duration>,
+}
+
+impl CursorStyleConfig {
+    pub fn is_default(&self) -> bool {
+        self.scale == 1.0 && !self.highlight && self.hide_when_idle.is_none()
+    }
+}
+
+impl Default for CursorStyleConfig {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            highlight: false,
+            highlight_radius: 24,
+            highlight_color: [255, 214, 0, 96],
+            hide_when_idle: None,
+        }
+    }
+}
+
+/// What the stages of the frame pipeline do when the next one falls behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDropPolicy {
+    /// Small buffers, a frame is dropped as soon as the next stage is busy
+    PreferLatency,
+
+    /// A stage waits for the next one and a frame is only dropped when it's
+    /// stuck
+    PreferCompleteness,
+}
+
+impl FrameDropPolicy {
+    /// Frames buffered between two stages, a 4K frame is about 30MB so it
+    /// never goes above `ENCODER_WORKER_CHANNEL_SIZE`
+    pub fn channel_size(&self) -> usize {
+        match self {
+            FrameDropPolicy::PreferLatency => 16,
+            FrameDropPolicy::PreferCompleteness => ENCODER_WORKER_CHANNEL_SIZE,
+        }
+    }
+
+    /// How long a stage waits for the next one before dropping the frame
+    pub fn send_timeout(&self) -> Duration {
+        match self {
+            FrameDropPolicy::PreferLatency => Duration::ZERO,
+            FrameDropPolicy::PreferCompleteness => Duration::from_secs(1),
+        }
+    }
+}
+
+/// The frames sent to `RecordingSession::with_frame_sender_user`
+#[non_exhaustive]
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+pub struct PreviewConfig {
+    /// The taller frames are downscaled to the height, keeping the aspect ratio
+    pub height: u32,
+
+    /// The frames over the rate are skipped before they are copied
+    pub fps: u32,
+
+    /// Can be toggled while recording. Only the stats are sent when it's off
+    pub enable: Arc<AtomicBool>,
+}
+
+impl PreviewConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(1000 / self.fps.max(1) as u64)
+    }
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            height: 540,
+            fps: 15,
+            enable: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+/// Lowers the processed frames while the encoder can't keep up, and raises
+/// them back once it catches up. The effective FPS is in `StatsUser`.
+#[non_exhaustive]
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+pub struct DynamicFpsConfig {
+    pub enable: bool,
+
+    /// How long the encoder channel stays full or nearly empty before the
+    /// FPS is halved or doubled
+    pub duration: Duration,
+
+    /// The FPS isn't lowered under it
+    pub min_fps: u32,
+}
+
+impl Default for DynamicFpsConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            duration: Duration::from_secs(3),
+            min_fps: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionPosition {
+    Top,
+    Bottom,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Setters)]
+#[setters(prefix = "with_")]
+pub struct CaptionStyleConfig {
+    /// Font size in pixels of a 1080p frame, scaled with the frame height
+    pub font_size: f32,
+
+    /// RGBA color of the text
+    pub color: [u8; 4],
+
+    /// RGBA color of the box behind the text, `None` draws no box
+    pub background: Option<[u8; 4]>,
+
+    pub position: CaptionPosition,
+
+    /// Lines on the screen, the older lines scroll out
+    pub max_lines: usize,
+
+    /// The captions are cleared when no text comes for the duration
+    pub hold: Duration,
+}
+
+impl Default for CaptionStyleConfig {
+    fn default() -> Self {
+        Self {
+            font_size: 40.0,
+            color: [255, 255, 255, 255],
+            background: Some([0, 0, 0, 160]),
+            position: CaptionPosition::Bottom,
+            max_lines: 2,
+            hold: Duration::from_secs(4),
+        }
+    }
+}
+
+/// Captions burned into the video of the ShareScreen and PushStream modes,
+/// see `RecordingSession::get_live_caption`
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Setters)]
+#[setters(prefix = "with_")]
+pub struct LiveCaptionConfig {
+    pub enable: bool,
+
+    /// Required when it's enabled, the recorder bundles no font
+    #[setters(strip_option)]
+    pub font: Option<Font>,
+
+    pub style: CaptionStyleConfig,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct SimpleFpsCounter {
     pub fps: f32,
@@ -268,3 +580,56 @@ impl SimpleFpsCounter {
         0.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(process_mode: ProcessMode) -> RecorderConfig {
+        RecorderConfig::new(
+            "eDP-1".to_string(),
+            LogicalSize::new(1920, 1080),
+            PathBuf::from("/tmp/test.mp4"),
+        )
+        .with_process_mode(process_mode)
+    }
+
+    #[test]
+    fn test_drop_policy_by_process_mode() {
+        assert_eq!(
+            config(ProcessMode::RecordScreen).drop_policy(),
+            FrameDropPolicy::PreferCompleteness
+        );
+        assert_eq!(
+            config(ProcessMode::RecordAudio).drop_policy(),
+            FrameDropPolicy::PreferCompleteness
+        );
+        assert_eq!(
+            config(ProcessMode::ShareScreen).drop_policy(),
+            FrameDropPolicy::PreferLatency
+        );
+        assert_eq!(
+            config(ProcessMode::PushStream).drop_policy(),
+            FrameDropPolicy::PreferLatency
+        );
+    }
+
+    #[test]
+    fn test_drop_policy_override() {
+        let config = config(ProcessMode::PushStream)
+            .with_frame_drop_policy(FrameDropPolicy::PreferCompleteness);
+        assert_eq!(config.drop_policy(), FrameDropPolicy::PreferCompleteness);
+    }
+
+    #[test]
+    fn test_drop_policy_channel() {
+        let latency = FrameDropPolicy::PreferLatency;
+        let completeness = FrameDropPolicy::PreferCompleteness;
+
+        assert!(latency.channel_size() < completeness.channel_size());
+        assert!(completeness.channel_size() <= ENCODER_WORKER_CHANNEL_SIZE);
+
+        assert_eq!(latency.send_timeout(), Duration::ZERO);
+        assert!(completeness.send_timeout() > Duration::ZERO);
+    }
+}
@@ -0,0 +1,44 @@
+use crossbeam::channel::Sender;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+// How often the countdown checks whether it's cancelled
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Wait `seconds` before capturing, so the scene can be set up. The remaining
+/// seconds are sent at the start of every second and `0` when it's finished.
+///
+/// Returns `false` if `stop_sig` is set before the countdown is finished.
+pub fn countdown(seconds: u32, stop_sig: &AtomicBool, sender: Option<&Sender<u32>>) -> bool {
+    let start = Instant::now();
+
+    for remaining in (1..=seconds).rev() {
+        if let Some(sender) = sender
+            && let Err(e) = sender.try_send(remaining)
+        {
+            log::warn!("Try send countdown failed: {e}");
+        }
+
+        log::info!("start capturing in {remaining}s");
+
+        let deadline = start + Duration::from_secs((seconds - remaining + 1) as u64);
+        while Instant::now() < deadline {
+            if stop_sig.load(Ordering::Relaxed) {
+                return false;
+            }
+
+            thread::sleep(
+                CANCEL_CHECK_INTERVAL.min(deadline.saturating_duration_since(Instant::now())),
+            );
+        }
+    }
+
+    if let Some(sender) = sender {
+        _ = sender.try_send(0);
+    }
+
+    !stop_sig.load(Ordering::Relaxed)
+}
@@ -0,0 +1,162 @@
+//! Draws the cursor on the frames instead of the compositor, so it can be
+//! scaled up, highlighted or hidden while it stays still.
+//!
+//! The compositors don't share the cursor image, so an arrow is drawn at the
+//! position from `ScreenCapture::monitor_cursor_position`.
+
+use crate::CursorStyleConfig;
+use screen_capture::{Capture, Position};
+use std::{sync::Mutex, time::Instant};
+
+// The outline of the arrow at the normal size, the tip is at (0, 0)
+const ARROW: [(f32, f32); 7] = [
+    (0.0, 0.0),
+    (0.0, 16.0),
+    (4.0, 12.5),
+    (7.0, 18.5),
+    (9.5, 17.5),
+    (6.5, 11.5),
+    (11.5, 11.5),
+];
+
+const ARROW_WIDTH: f32 = 12.0;
+const ARROW_HEIGHT: f32 = 19.0;
+const ARROW_BORDER_WIDTH: f32 = 1.0;
+
+pub(crate) struct CursorOverlay {
+    style: CursorStyleConfig,
+    scale_factor: f32,
+
+    // The last position and when the cursor moved to it
+    last_move: Mutex<Option<(Position, Instant)>>,
+}
+
+impl CursorOverlay {
+    pub(crate) fn new(style: CursorStyleConfig, scale_factor: f32) -> Self {
+        Self {
+            style,
+            scale_factor: if scale_factor > 0.0 {
+                scale_factor
+            } else {
+                1.0
+            },
+            last_move: Mutex::new(None),
+        }
+    }
+
+    /// The position in the pixels of the output
+    pub(crate) fn update(&self, position: Position) {
+        let mut last_move = self.last_move.lock().unwrap();
+        if last_move.is_none_or(|(last_position, _)| last_position != position) {
+            *last_move = Some((position, Instant::now()));
+        }
+    }
+
+    pub(crate) fn draw(&self, capture: &mut Capture) {
+        let Some((position, moved_at)) = *self.last_move.lock().unwrap() else {
+            return;
+        };
+
+        if let Some(duration) = self.style.hide_when_idle
+            && moved_at.elapsed() >= duration
+        {
+            return;
+        }
+
+        if self.style.highlight {
+            self.draw_highlight(capture, position);
+        }
+
+        self.draw_arrow(capture, position);
+    }
+
+    fn draw_highlight(&self, capture: &mut Capture, center: Position) {
+        let radius = self.style.highlight_radius as f32 * self.scale_factor;
+        let color = self.style.highlight_color;
+        let r = radius.ceil() as i32;
+
+        for y in (center.y - r)..=(center.y + r) {
+            for x in (center.x - r)..=(center.x + r) {
+                let (dx, dy) = ((x - center.x) as f32, (y - center.y) as f32);
+
+                // Soften the edge by a pixel
+                let coverage = (radius - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    let alpha = (color[3] as f32 * coverage) as u8;
+                    blend_pixel(capture, x, y, [color[0], color[1], color[2], alpha]);
+                }
+            }
+        }
+    }
+
+    fn draw_arrow(&self, capture: &mut Capture, tip: Position) {
+        let scale = self.style.scale * self.scale_factor;
+        let (width, height) = (
+            (ARROW_WIDTH * scale).ceil() as i32,
+            (ARROW_HEIGHT * scale).ceil() as i32,
+        );
+
+        for y in 0..height {
+            for x in 0..width {
+                // The center of the pixel in the coordinates of the outline
+                let (u, v) = ((x as f32 + 0.5) / scale, (y as f32 + 0.5) / scale);
+                if !contains(&ARROW, u, v) {
+                    continue;
+                }
+
+                let color = if distance_to_outline(&ARROW, u, v) < ARROW_BORDER_WIDTH {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                };
+
+                blend_pixel(capture, tip.x + x, tip.y + y, color);
+            }
+        }
+    }
+}
+
+fn blend_pixel(capture: &mut Capture, x: i32, y: i32, color: [u8; 4]) {
+    if x < 0 || y < 0 || x >= capture.width as i32 || y >= capture.height as i32 {
+        return;
+    }
+
+    let index = (y as usize * capture.width as usize + x as usize) * 4;
+    let Some(pixel) = capture.pixel_data.get_mut(index..index + 4) else {
+        return;
+    };
+
+    let alpha = color[3] as f32 / 255.0;
+    for (dst, src) in pixel.iter_mut().zip(color).take(3) {
+        *dst = (*dst as f32 * (1.0 - alpha) + src as f32 * alpha) as u8;
+    }
+    pixel[3] = 255;
+}
+
+// Even-odd rule
+fn contains(polygon: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut inside = false;
+
+    for (i, &(x1, y1)) in polygon.iter().enumerate() {
+        let (x2, y2) = polygon[(i + 1) % polygon.len()];
+        if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+fn distance_to_outline(polygon: &[(f32, f32)], x: f32, y: f32) -> f32 {
+    polygon
+        .iter()
+        .enumerate()
+        .map(|(i, &(x1, y1))| {
+            let (x2, y2) = polygon[(i + 1) % polygon.len()];
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let t = (((x - x1) * dx + (y - y1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+            let (px, py) = (x1 + t * dx - x, y1 + t * dy - y);
+            (px * px + py * py).sqrt()
+        })
+        .fold(f32::MAX, f32::min)
+}
@@ -43,6 +43,17 @@ pub enum TransitionType {
     EaseOut,
 }
 
+impl TransitionType {
+    /// Map the linear progress (0.0-1.0) of a transition to the eased one
+    pub(crate) fn ease(self, progress: f64) -> f64 {
+        match self {
+            TransitionType::Linear => progress,
+            TransitionType::EaseIn => progress * progress,
+            TransitionType::EaseOut => 1.0 - (1.0 - progress).powf(2.0),
+        }
+    }
+}
+
 #[derive(Clone, Setters)]
 #[setters(prefix = "with_")]
 pub struct CursorTrackerConfig {
@@ -537,11 +548,7 @@ impl CursorTracker {
             let progress = progress.min(1.0); // Ensure we don't exceed 1.0
 
             // Apply easing function based on transition type
-            let eased_progress = match transition_type {
-                TransitionType::Linear => progress,
-                TransitionType::EaseIn => progress * progress,
-                TransitionType::EaseOut => 1.0 - (1.0 - progress).powf(2.0),
-            };
+            let eased_progress = transition_type.ease(progress);
 
             // Calculate interpolated size with eased progress
             let width = from_size.width as f64
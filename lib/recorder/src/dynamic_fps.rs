@@ -0,0 +1,141 @@
+use crate::DynamicFpsConfig;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Halves the frames sent to the process workers while the encoder channel
+/// stays full, and doubles them back once it stays nearly empty. The skipped
+/// frames repeat the previous one, so the video keeps its timing.
+pub(crate) struct FpsThrottle {
+    config: DynamicFpsConfig,
+    fps: u32,
+    max_divisor: u32,
+
+    // Only every `divisor`th frame is processed, shared with the forward worker
+    divisor: Arc<AtomicU32>,
+
+    saturated_since: Option<Instant>,
+    relaxed_since: Option<Instant>,
+}
+
+impl FpsThrottle {
+    pub(crate) fn new(config: DynamicFpsConfig, fps: u32, divisor: Arc<AtomicU32>) -> Self {
+        let mut max_divisor = 1;
+        while fps / (max_divisor * 2) >= config.min_fps.max(1) {
+            max_divisor *= 2;
+        }
+
+        Self {
+            config,
+            fps,
+            max_divisor,
+            divisor,
+            saturated_since: None,
+            relaxed_since: None,
+        }
+    }
+
+    pub(crate) fn effective_fps(&self) -> u32 {
+        self.fps / self.divisor.load(Ordering::Relaxed).max(1)
+    }
+
+    /// Feed the encoder channel usage, returns the new effective FPS when it
+    /// changes
+    pub(crate) fn update(&mut self, len: usize, capacity: usize, now: Instant) -> Option<u32> {
+        if !self.config.enable || capacity == 0 {
+            return None;
+        }
+
+        let divisor = self.divisor.load(Ordering::Relaxed);
+        let saturated = len * 4 >= capacity * 3;
+        let relaxed = len * 4 <= capacity;
+
+        let saturated_for = Self::lasted_for(&mut self.saturated_since, saturated, now);
+        let relaxed_for = Self::lasted_for(&mut self.relaxed_since, relaxed, now);
+
+        let divisor =
+            if saturated && divisor < self.max_divisor && saturated_for >= self.config.duration {
+                divisor * 2
+            } else if relaxed && divisor > 1 && relaxed_for >= self.config.duration {
+                divisor / 2
+            } else {
+                return None;
+            };
+
+        self.divisor.store(divisor, Ordering::Relaxed);
+        self.saturated_since = None;
+        self.relaxed_since = None;
+
+        Some(self.effective_fps())
+    }
+
+    fn lasted_for(since: &mut Option<Instant>, state: bool, now: Instant) -> Duration {
+        if state {
+            now.duration_since(*since.get_or_insert(now))
+        } else {
+            *since = None;
+            Duration::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(fps: u32) -> FpsThrottle {
+        let config = DynamicFpsConfig::default()
+            .with_duration(Duration::from_secs(2))
+            .with_min_fps(10);
+        FpsThrottle::new(config, fps, Arc::new(AtomicU32::new(1)))
+    }
+
+    #[test]
+    fn test_reduce_and_recover() {
+        let mut throttle = throttle(60);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert_eq!(throttle.update(100, 128, at(0)), None);
+        assert_eq!(throttle.update(100, 128, at(1)), None);
+        assert_eq!(throttle.update(100, 128, at(2)), Some(30));
+
+        // Recovers once the channel stays nearly empty
+        assert_eq!(throttle.update(60, 128, at(3)), None);
+        assert_eq!(throttle.update(10, 128, at(4)), None);
+        assert_eq!(throttle.update(10, 128, at(5)), None);
+        assert_eq!(throttle.update(10, 128, at(6)), Some(60));
+    }
+
+    #[test]
+    fn test_min_fps() {
+        let mut throttle = throttle(25);
+        let start = Instant::now();
+
+        for secs in (0..20).step_by(2) {
+            throttle.update(128, 128, start + Duration::from_secs(secs));
+        }
+
+        assert_eq!(throttle.effective_fps(), 12);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut throttle = FpsThrottle::new(
+            DynamicFpsConfig::default().with_enable(false),
+            60,
+            Arc::new(AtomicU32::new(1)),
+        );
+        let start = Instant::now();
+
+        assert_eq!(throttle.update(128, 128, start), None);
+        assert_eq!(
+            throttle.update(128, 128, start + Duration::from_secs(60)),
+            None
+        );
+    }
+}
@@ -47,9 +47,15 @@ pub enum RecorderError {
     #[error("Camera error failed: {0}")]
     CameraError(#[from] camera::CameraError),
 
+    #[error("Audio encoding failed: {0}")]
+    AudioEncodingFailed(String),
+
     #[error("Denoise failed: {0}")]
     DenoiseError(String),
 
+    #[error("Recording is cancelled before capturing")]
+    Cancelled,
+
     #[error("{0}")]
     Other(String),
 
@@ -0,0 +1,107 @@
+//! Keeps the system awake while recording or streaming, otherwise laptops
+//! may lock, blank the screen or suspend in the middle of a recording.
+//!
+//! The `idle-inhibit-unstable-v1` protocol is used on Wayland and
+//! `SetThreadExecutionState` on Windows.
+
+/// Released when dropped.
+pub struct IdleInhibitor {
+    #[cfg(all(target_os = "linux", feature = "wayland-wlr"))]
+    _inner: screen_capture_wayland_wlr::IdleInhibitor,
+
+    #[cfg(target_os = "windows")]
+    _inner: windows::ExecutionState,
+}
+
+impl IdleInhibitor {
+    /// Returns `None` if the platform doesn't support it, the recording goes
+    /// on without it.
+    pub fn acquire() -> Option<Self> {
+        #[cfg(all(target_os = "linux", feature = "wayland-wlr"))]
+        let inner = screen_capture_wayland_wlr::IdleInhibitor::new();
+
+        #[cfg(target_os = "windows")]
+        let inner = windows::ExecutionState::new();
+
+        #[cfg(any(
+            all(target_os = "linux", feature = "wayland-wlr"),
+            target_os = "windows"
+        ))]
+        {
+            match inner {
+                Ok(inner) => {
+                    log::info!("idle inhibitor acquired");
+                    Some(Self { _inner: inner })
+                }
+                Err(e) => {
+                    log::warn!("acquire idle inhibitor failed: {e}");
+                    None
+                }
+            }
+        }
+
+        #[cfg(not(any(
+            all(target_os = "linux", feature = "wayland-wlr"),
+            target_os = "windows"
+        )))]
+        {
+            log::info!("idle inhibitor is unsupported on the platform");
+            None
+        }
+    }
+}
+
+impl Drop for IdleInhibitor {
+    fn drop(&mut self) {
+        log::info!("idle inhibitor released");
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use crossbeam::channel::{Sender, bounded};
+    use std::thread;
+    use winapi::um::{
+        winbase::SetThreadExecutionState,
+        winnt::{ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED},
+    };
+
+    // The execution state belongs to the thread which sets it, so it's held
+    // by a thread which lives until it's released
+    pub struct ExecutionState {
+        release_sender: Sender<()>,
+    }
+
+    impl ExecutionState {
+        pub fn new() -> Result<Self, String> {
+            let (release_sender, release_receiver) = bounded::<()>(1);
+            let (result_sender, result_receiver) = bounded(1);
+
+            thread::spawn(move || {
+                let previous = unsafe {
+                    SetThreadExecutionState(
+                        ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED,
+                    )
+                };
+                _ = result_sender.send(previous != 0);
+
+                if previous != 0 {
+                    // Returns when the sender is dropped
+                    _ = release_receiver.recv();
+                    unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+                }
+            });
+
+            match result_receiver.recv() {
+                Ok(true) => Ok(Self { release_sender }),
+                _ => Err("SetThreadExecutionState failed".to_string()),
+            }
+        }
+    }
+
+    impl Drop for ExecutionState {
+        fn drop(&mut self) {
+            _ = self.release_sender.try_send(());
+        }
+    }
+}
@@ -1,31 +1,56 @@
+mod audio_file;
 mod audio_level;
 mod audio_recorder;
+mod av_calibration;
 mod config;
+mod countdown;
+mod cursor_overlay;
 mod cursor_tracker;
 mod denoise;
+mod dynamic_fps;
 mod error;
+mod idle_inhibitor;
+mod live_caption;
+mod pause;
 mod process_mode;
 mod recorder;
+mod replay;
 mod resolution;
+mod scene_change;
 mod speaker_recorder;
+mod system_check;
+mod window_follower;
 mod worker;
 
+pub use audio_file::AudioFormat;
 pub use audio_level::*;
 pub use audio_recorder::{AudioDeviceInfo, AudioRecorder, AudioRecorderError};
+pub use av_calibration::{AvCalibrationConfig, calibrate_av_offset};
 pub use config::{
-    CameraMixConfig, FPS, PushStreamConfig, RecorderConfig, ShareScreenConfig, SimpleFpsCounter,
+    CameraMixConfig, CaptionPosition, CaptionStyleConfig, CursorStyleConfig, DynamicFpsConfig, FPS,
+    FrameDropPolicy, LiveCaptionConfig, PreviewConfig, PushStreamConfig, RecorderConfig,
+    ShareScreenConfig, SimpleFpsCounter,
 };
+pub use countdown::countdown;
 pub use crossbeam::channel::{Receiver, Sender, bounded};
 pub use cursor_tracker::{CursorTracker, CursorTrackerConfig, TransitionType};
 pub use denoise::*;
 pub use error::RecorderError;
-pub use recorder::{RecordingSession, ResizedImageBuffer};
+pub use idle_inhibitor::IdleInhibitor;
+pub use live_caption::LiveCaption;
+pub use pause::PauseControl;
+pub use recorder::{ChapterMarker, RecordingSession, ResizedImageBuffer};
+pub use replay::ReplaySaver;
 pub use resolution::Resolution;
 pub use speaker_recorder::{
     SpeakerRecorder, SpeakerRecorderConfig, SpeakerRecorderError, platform_speaker_recoder,
 };
+pub use system_check::{SystemCheckConfig, SystemCheckReport, SystemCheckWarning, system_check};
 pub use tokio::sync::mpsc::channel as AsyncErrorChannel;
-pub use video_encoder::{EncodedFrame, VideoEncoder, VideoEncoderConfig, new as video_encoder_new};
+pub use video_encoder::{
+    EncodedFrame, RateControl, VideoEncoder, VideoEncoderConfig, new as video_encoder_new,
+};
+pub use window_follower::{WindowFollower, WindowFollowerConfig};
 pub use wrtc::RTCIceServer;
 
 pub type AsyncErrorSender = tokio::sync::mpsc::Sender<String>;
@@ -42,6 +67,9 @@ pub enum ProcessMode {
     RecordScreen,
     ShareScreen,
     PushStream,
+
+    /// Only the microphone and the speaker, no video is captured
+    RecordAudio,
 }
 
 #[derive(Debug, Clone)]
@@ -55,10 +83,39 @@ pub struct Frame {
 pub struct StatsUser {
     pub fps: f32,
     pub total_frames: u64,
+
+    /// The sum of `dropped_frames`
     pub loss_frames: u64,
+    pub dropped_frames: FrameDropStats,
+
+    /// Lower than the configured FPS while the encoder can't keep up, see
+    /// `DynamicFpsConfig`
+    pub effective_fps: u32,
     pub share_screen_connections: u32,
 }
 
+/// Frames dropped at each stage of the pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameDropStats {
+    /// Waiting to be cropped, resized and mixed
+    pub process: u64,
+
+    /// Processed too late or waiting to be put in order
+    pub collect: u64,
+
+    /// Waiting for the encoder
+    pub encode: u64,
+
+    /// Encoded, waiting for the MP4 writer or the stream
+    pub output: u64,
+}
+
+impl FrameDropStats {
+    pub fn total(&self) -> u64 {
+        self.process + self.collect + self.encode + self.output
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FrameUser {
     pub stats: StatsUser,
@@ -77,3 +134,13 @@ pub fn platform_screen_capture() -> impl screen_capture::ScreenCapture + Clone +
 
     screen_capturer
 }
+
+/// Let the user select the region to record on the screen, `None` if the
+/// selection is cancelled.
+#[cfg(all(target_os = "linux", feature = "wayland-wlr"))]
+pub fn select_capture_region(
+    screen_info: &screen_capture::ScreenInfo,
+) -> Result<Option<screen_capture::Rectangle>, RecorderError> {
+    screen_capture_wayland_wlr::select_region(screen_info)
+        .map_err(|e| RecorderError::Other(format!("select region failed: {e}")))
+}
@@ -0,0 +1,266 @@
+//! Rolling captions drawn onto the frames of the live modes. The transcriber
+//! runs outside of the recorder, it reads the mixed audio from
+//! `RecordingSession::with_caption_audio_sender` and sends the text back
+//! through `LiveCaption::update`.
+
+use crate::{CaptionPosition, CaptionStyleConfig, ResizedImageBuffer};
+use image::{Rgba, RgbaImage};
+use image_effect::annotate::{Font, Text};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// The font size of the style is for the frames of the height
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+// Ratios of the frame size
+const MAX_WIDTH_RATIO: f32 = 0.8;
+const MARGIN_RATIO: f32 = 0.06;
+
+// Finished utterances kept for the scrolling lines
+const MAX_FINAL_TEXTS: usize = 8;
+
+/// Sends the transcribed text to the captions of a live session
+#[derive(Clone)]
+pub struct LiveCaption {
+    overlay: Arc<CaptionOverlay>,
+}
+
+impl LiveCaption {
+    pub(crate) fn new(overlay: Arc<CaptionOverlay>) -> Self {
+        Self { overlay }
+    }
+
+    /// A partial text is replaced by the next text, a final one scrolls up
+    /// when the next utterance starts
+    pub fn update(&self, text: impl Into<String>, is_final: bool) {
+        self.overlay.update(text.into(), is_final);
+    }
+}
+
+pub(crate) struct CaptionOverlay {
+    font: Font,
+    style: CaptionStyleConfig,
+    state: Mutex<CaptionState>,
+}
+
+#[derive(Default)]
+struct CaptionState {
+    finals: VecDeque<String>,
+    partial: String,
+    updated_at: Option<Instant>,
+
+    // Laid out again only when the text or the frame size changes
+    rendered: Option<((u32, u32), Option<Arc<RgbaImage>>)>,
+}
+
+impl CaptionState {
+    fn text(&self) -> String {
+        self.finals
+            .iter()
+            .map(String::as_str)
+            .chain((!self.partial.is_empty()).then_some(self.partial.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl CaptionOverlay {
+    pub(crate) fn new(font: Font, style: CaptionStyleConfig) -> Self {
+        Self {
+            font,
+            style,
+            state: Mutex::new(CaptionState::default()),
+        }
+    }
+
+    fn update(&self, text: String, is_final: bool) {
+        let text = text.trim().to_string();
+        let mut state = self.state.lock().unwrap();
+
+        if is_final {
+            state.partial.clear();
+            if !text.is_empty() {
+                state.finals.push_back(text);
+            }
+            while state.finals.len() > MAX_FINAL_TEXTS {
+                state.finals.pop_front();
+            }
+        } else {
+            state.partial = text;
+        }
+
+        state.updated_at = Some(Instant::now());
+        state.rendered = None;
+    }
+
+    pub(crate) fn draw(&self, image: &mut ResizedImageBuffer) {
+        let size = image.dimensions();
+        let caption = {
+            let mut state = self.state.lock().unwrap();
+            let Some(updated_at) = state.updated_at else {
+                return;
+            };
+
+            // Cleared once nobody speaks for a while
+            if updated_at.elapsed() >= self.style.hold {
+                *state = CaptionState::default();
+                return;
+            }
+
+            match state.rendered {
+                Some((rendered_size, ref caption)) if rendered_size == size => caption.clone(),
+                _ => {
+                    let caption = self.render(&state.text(), size).map(Arc::new);
+                    state.rendered = Some((size, caption.clone()));
+                    caption
+                }
+            }
+        };
+
+        let Some(caption) = caption else {
+            return;
+        };
+
+        let (width, height) = size;
+        let margin = (height as f32 * MARGIN_RATIO) as u32;
+        let x = width.saturating_sub(caption.width()) / 2;
+        let y = match self.style.position {
+            CaptionPosition::Top => margin,
+            CaptionPosition::Bottom => height.saturating_sub(caption.height() + margin),
+        };
+
+        blend_caption(image, &caption, (x, y));
+    }
+
+    // The last lines of the text on a transparent image, `None` if there is
+    // nothing to show
+    fn render(&self, text: &str, (width, height): (u32, u32)) -> Option<RgbaImage> {
+        let font_size = self.style.font_size * height as f32 / REFERENCE_HEIGHT;
+        let padding = font_size * 0.3;
+        let max_width = width as f32 * MAX_WIDTH_RATIO;
+
+        let lines = wrap_text(text, max_width, |line| {
+            self.font.text_size(line, font_size).0
+        });
+        let lines = &lines[lines.len().saturating_sub(self.style.max_lines.max(1))..];
+        if lines.is_empty() {
+            return None;
+        }
+
+        let text = lines.join("\n");
+        let (text_width, text_height) = self.font.text_size(&text, font_size);
+        let mut caption = RgbaImage::new(
+            (text_width + padding * 2.0).ceil() as u32,
+            (text_height + padding * 2.0).ceil() as u32,
+        );
+
+        Text::new((padding, padding), text, self.font.clone())
+            .with_size(font_size)
+            .with_color(Rgba(self.style.color))
+            .with_background(self.style.background.map(Rgba))
+            .with_padding(padding)
+            .draw(&mut caption);
+
+        Some(caption)
+    }
+}
+
+/// Greedy line breaking, at the last space when the line has one, otherwise
+/// between any characters, so the texts without spaces are wrapped too
+fn wrap_text(text: &str, max_width: f32, text_width: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for c in text.chars() {
+        if line.is_empty() && c.is_whitespace() {
+            continue;
+        }
+
+        line.push(c);
+        if text_width(&line) <= max_width || line.chars().count() == 1 {
+            continue;
+        }
+
+        line.pop();
+        match line.rfind(char::is_whitespace) {
+            Some(index) if !c.is_whitespace() => {
+                let rest = line.split_off(index);
+                lines.push(line.trim_end().to_string());
+                line = rest.trim_start().to_string();
+            }
+            _ => lines.push(std::mem::take(&mut line)),
+        }
+
+        if !c.is_whitespace() {
+            line.push(c);
+        }
+    }
+
+    if !line.trim().is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines
+}
+
+// The caption is drawn onto a transparent image, so its colors are already
+// multiplied by the alpha
+fn blend_caption(image: &mut ResizedImageBuffer, caption: &RgbaImage, (x, y): (u32, u32)) {
+    for (cx, cy, pixel) in caption.enumerate_pixels() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || x + cx >= image.width() || y + cy >= image.height() {
+            continue;
+        }
+
+        let target = image.get_pixel_mut(x + cx, y + cy);
+        for i in 0..3 {
+            target[i] = (pixel[i] as u32 + target[i] as u32 * (255 - alpha) / 255).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character is 10 pixels wide
+    fn wrap(text: &str, max_width: f32) -> Vec<String> {
+        wrap_text(text, max_width, |line| line.chars().count() as f32 * 10.0)
+    }
+
+    #[test]
+    fn test_wrap_at_spaces() {
+        assert_eq!(
+            wrap("hello live captions", 110.0),
+            vec!["hello live", "captions"]
+        );
+        assert_eq!(wrap("  hello  ", 110.0), vec!["hello"]);
+    }
+
+    #[test]
+    fn test_wrap_without_spaces() {
+        assert_eq!(wrap("实时字幕测试", 40.0), vec!["实时字幕", "测试"]);
+    }
+
+    #[test]
+    fn test_wrap_long_word() {
+        assert_eq!(
+            wrap("a verylongword", 50.0),
+            vec!["a", "veryl", "ongwo", "rd"]
+        );
+    }
+
+    #[test]
+    fn test_caption_state_text() {
+        let mut state = CaptionState::default();
+        state.finals.push_back("first".to_string());
+        state.partial = "second".to_string();
+        assert_eq!(state.text(), "first second");
+
+        state.partial.clear();
+        assert_eq!(state.text(), "first");
+    }
+}
@@ -0,0 +1,115 @@
+//! Pausing a recording drops the captured frames and the mixed audio until
+//! it's resumed, so the saved file goes on from where it was paused.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct PauseState {
+    // Checked for every frame and audio chunk, so it's kept out of the lock
+    paused: AtomicBool,
+
+    // When the running pause started and the length of the finished ones
+    timer: Mutex<(Option<Instant>, Duration)>,
+}
+
+impl PauseState {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Returns `false` if it's already in the state
+    fn set_paused(&self, paused: bool, now: Instant) -> bool {
+        let mut timer = self.timer.lock().unwrap();
+        match (paused, timer.0) {
+            (true, None) => timer.0 = Some(now),
+            (false, Some(paused_at)) => {
+                timer.0 = None;
+                timer.1 += now.saturating_duration_since(paused_at);
+            }
+            _ => return false,
+        }
+
+        self.paused.store(paused, Ordering::Relaxed);
+        true
+    }
+
+    fn paused_duration(&self, now: Instant) -> Duration {
+        let timer = self.timer.lock().unwrap();
+        let running = timer
+            .0
+            .map(|paused_at| now.saturating_duration_since(paused_at))
+            .unwrap_or_default();
+
+        timer.1 + running
+    }
+
+    /// The time since `start_time` without the pauses
+    pub(crate) fn recording_time(&self, start_time: Instant) -> Duration {
+        let now = Instant::now();
+        now.saturating_duration_since(start_time)
+            .saturating_sub(self.paused_duration(now))
+    }
+}
+
+#[derive(Clone)]
+pub struct PauseControl {
+    pub(crate) state: Arc<PauseState>,
+}
+
+impl PauseControl {
+    /// Returns `false` if it's already paused.
+    pub fn pause(&self) -> bool {
+        let changed = self.state.set_paused(true, Instant::now());
+        if changed {
+            log::info!("recording is paused");
+        }
+        changed
+    }
+
+    /// Returns `false` if it isn't paused.
+    pub fn resume(&self) -> bool {
+        let changed = self.state.set_paused(false, Instant::now());
+        if changed {
+            log::info!("recording is resumed");
+        }
+        changed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.is_paused()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_state() {
+        let state = PauseState::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(!state.is_paused());
+        assert!(!state.set_paused(false, at(1)));
+
+        assert!(state.set_paused(true, at(2)));
+        assert!(state.is_paused());
+        assert!(!state.set_paused(true, at(3)));
+        assert_eq!(state.paused_duration(at(5)), Duration::from_secs(3));
+
+        assert!(state.set_paused(false, at(6)));
+        assert!(!state.is_paused());
+        assert_eq!(state.paused_duration(at(10)), Duration::from_secs(4));
+
+        assert!(state.set_paused(true, at(12)));
+        assert!(state.set_paused(false, at(13)));
+        assert_eq!(state.paused_duration(at(20)), Duration::from_secs(5));
+    }
+}
@@ -1,12 +1,14 @@
 use crate::{
-    AudioRecorder, RecorderError, RecordingSession, SpeakerRecorder, platform_speaker_recoder,
-    recorder::ENCODER_WORKER_CHANNEL_SIZE, speaker_recorder::SpeakerRecorderConfig,
+    AudioRecorder, RecorderError, RecordingSession, SpeakerRecorder,
+    audio_file::new_audio_file_writer, platform_speaker_recoder,
+    recorder::ENCODER_WORKER_CHANNEL_SIZE, replay::ReplayBuffer,
+    speaker_recorder::SpeakerRecorderConfig,
 };
 use crossbeam::channel::{Receiver, Sender, bounded};
 use hound::WavSpec;
 use mp4m::{
-    AudioConfig, AudioProcessor, AudioProcessorConfigBuilder, Mp4Processor,
-    Mp4ProcessorConfigBuilder, OutputDestination, VideoConfig, VideoFrameType,
+    AudioConfig, AudioProcessor, AudioProcessorConfigBuilder, DuckingConfig, MixerSource,
+    Mp4Processor, Mp4ProcessorConfigBuilder, OutputDestination, VideoConfig, VideoFrameType,
 };
 use once_cell::sync::Lazy;
 use std::{
@@ -79,21 +81,35 @@ impl RecordingSession {
                     .channels
             };
             mix_audio_channels = Some(target_channels);
+            self.mix_audio_format = Some((target_sample_rate, target_channels));
 
             let config = AudioProcessorConfigBuilder::default()
                 .target_sample_rate(target_sample_rate)
                 .channel_size(AUDIO_MIXER_CHANNEL_SIZE)
                 .convert_to_mono(self.config.convert_to_mono)
+                .ducking(
+                    self.config
+                        .enable_speaker_ducking
+                        .then(DuckingConfig::default),
+                )
                 .output_destination(Some(OutputDestination::<f32>::Channel(mix_audios_tx)))
                 .build()?;
 
             let mut audio_processor = AudioProcessor::new(config);
 
+            // Only the microphone has the latency, the speaker is in sync with the screen
+            let mic_source = MixerSource::default().with_offset_ms(self.config.av_offset_ms);
+
             if self.config.audio_device_name.is_some() && self.config.enable_recording_speaker {
-                audio_sender = Some(audio_processor.add_track(specs[0]));
-                speak_sender = Some(audio_processor.add_track(specs[1]));
+                audio_sender = Some(
+                    audio_processor.add_source_track(specs[0], mic_source.with_sidechain(true)),
+                );
+                speak_sender = Some(
+                    audio_processor
+                        .add_source_track(specs[1], MixerSource::default().with_ducked(true)),
+                );
             } else if self.config.audio_device_name.is_some() {
-                audio_sender = Some(audio_processor.add_track(specs[0]));
+                audio_sender = Some(audio_processor.add_source_track(specs[0], mic_source));
             } else if self.config.enable_recording_speaker {
                 speak_sender = Some(audio_processor.add_track(specs[0]));
             }
@@ -134,6 +150,35 @@ impl RecordingSession {
         ))
     }
 
+    // Copies the mixed audio to the transcriber of the live captions. The
+    // copies are dropped when the transcriber falls behind, the stream isn't
+    // held up by it.
+    pub(crate) fn caption_audio_worker(
+        &mut self,
+        mix_audio_receiver: Option<Receiver<Vec<f32>>>,
+    ) -> Option<Receiver<Vec<f32>>> {
+        let Some(caption_sender) = self.caption_audio_sender.take() else {
+            return mix_audio_receiver;
+        };
+        let mix_audio_rx = mix_audio_receiver?;
+
+        let (sender, receiver) = bounded(AUDIO_MIXER_CHANNEL_SIZE);
+        thread::spawn(move || {
+            while let Ok(data) = mix_audio_rx.recv() {
+                if let Err(e) = caption_sender.try_send(data.clone()) {
+                    log::debug!("send audio samples to the caption transcriber failed: {e}");
+                }
+
+                if sender.send(data).is_err() {
+                    break;
+                }
+            }
+            log::info!("exit caption audio worker");
+        });
+
+        Some(receiver)
+    }
+
     pub(crate) fn mp4_worker(
         &mut self,
         video_encoder_header_data: Option<Vec<u8>>,
@@ -141,20 +186,38 @@ impl RecordingSession {
         mix_audio_channels: Option<u16>,
         mix_audio_sample_rate: Option<u32>,
     ) -> Result<Option<Sender<VideoFrameType>>, RecorderError> {
-        let (encoder_width, encoder_height) = self.config.resolution.dimensions(
-            self.config.screen_size.width as u32,
-            self.config.screen_size.height as u32,
-        );
+        let (capture_width, capture_height) = self.config.capture_size();
+        let (encoder_width, encoder_height) = self
+            .config
+            .resolution
+            .dimensions(capture_width, capture_height);
+
+        let mut metadata = self.config.mp4_metadata.clone();
+        metadata.creation_time.get_or_insert_with(chrono::Utc::now);
+
+        let video_config = VideoConfig {
+            width: encoder_width,
+            height: encoder_height,
+            fps: self.config.fps.to_u32(),
+        };
+
+        if self.config.replay_secs > 0 {
+            self.replay_buffer = Some(Arc::new(ReplayBuffer::new(
+                self.config.replay_secs,
+                self.config.save_path.clone(),
+                video_encoder_header_data.clone().unwrap_or_default(),
+                video_config.clone(),
+                self.config.mp4_metadata.clone(),
+                mix_audio_sample_rate.zip(mix_audio_channels),
+            )));
+        }
 
         let mut mp4_processor = Mp4Processor::new(
             Mp4ProcessorConfigBuilder::default()
                 .save_path(self.config.save_path.clone())
+                .metadata(metadata)
                 .channel_size(AUDIO_MIXER_CHANNEL_SIZE)
-                .video_config(VideoConfig {
-                    width: encoder_width,
-                    height: encoder_height,
-                    fps: self.config.fps.to_u32(),
-                })
+                .video_config(video_config)
                 .build()?,
         );
 
@@ -179,6 +242,8 @@ impl RecordingSession {
             && let Some(mix_audio_rx) = mix_audio_receiver.take()
         {
             let stop_sig = self.stop_sig.clone();
+            let pause = self.pause.clone();
+            let replay_buffer = self.replay_buffer.clone();
             thread::spawn(move || {
                 loop {
                     if stop_sig.load(Ordering::Relaxed) {
@@ -186,6 +251,14 @@ impl RecordingSession {
                     }
 
                     while let Ok(data) = mix_audio_rx.try_recv() {
+                        if pause.is_paused() {
+                            continue;
+                        }
+
+                        if let Some(ref replay_buffer) = replay_buffer {
+                            replay_buffer.push_audio(data.clone());
+                        }
+
                         if let Err(e) = mp4_audio_tx.try_send(data) {
                             log::warn!("forward mix audio samples to mp4 processor faild: {e}");
                         }
@@ -197,6 +270,7 @@ impl RecordingSession {
         }
 
         let h264_frame_sender = Some(mp4_processor.h264_sender());
+        self.chapter_sender = Some(mp4_processor.chapter_sender());
         let handle = thread::spawn(move || {
             if let Err(e) = mp4_processor.run_processing_loop(video_encoder_header_data) {
                 log::warn!("MP4 processing error: {}", e);
@@ -207,6 +281,60 @@ impl RecordingSession {
         Ok(h264_frame_sender)
     }
 
+    pub(crate) fn audio_file_worker(
+        &mut self,
+        mix_audio_receiver: Receiver<Vec<f32>>,
+        mix_audio_channels: u16,
+        mix_audio_sample_rate: u32,
+    ) -> Result<(), RecorderError> {
+        let format = self.config.audio_format;
+        let save_path = self.config.save_path.clone();
+        let pause = self.pause.clone();
+        let (init_sender, init_receiver) = bounded(1);
+
+        // The encoders can't be moved across threads, so the writer is created
+        // in the worker and only the result is sent back
+        let handle = thread::spawn(move || {
+            let mut writer = match new_audio_file_writer(
+                format,
+                &save_path,
+                mix_audio_sample_rate,
+                mix_audio_channels,
+            ) {
+                Ok(writer) => {
+                    _ = init_sender.send(Ok(()));
+                    writer
+                }
+                Err(e) => {
+                    _ = init_sender.send(Err(e));
+                    return;
+                }
+            };
+
+            // Disconnected once the audio mixer is flushed
+            while let Ok(data) = mix_audio_receiver.recv() {
+                if pause.is_paused() {
+                    continue;
+                }
+
+                if let Err(e) = writer.write(&data) {
+                    log::warn!("write audio samples failed: {e}");
+                }
+            }
+
+            if let Err(e) = writer.finish() {
+                log::warn!("finish the audio file failed: {e}");
+            }
+        });
+
+        init_receiver
+            .recv()
+            .map_err(|e| RecorderError::Other(format!("audio file worker exit: {e}")))??;
+        self.audio_file_worker = Some(handle);
+
+        Ok(())
+    }
+
     pub(crate) fn share_screen_worker(
         &mut self,
         rt_handle: tokio::runtime::Handle,
@@ -424,6 +552,7 @@ impl RecordingSession {
                             VideoFrameType::Frame(ref content) => {
                                 VideoFrameType::Frame(convert_annexb_to_length_prefixes(&content))
                             }
+                            VideoFrameType::Repeat => VideoFrameType::Repeat,
                             VideoFrameType::End => VideoFrameType::End,
                         };
 
@@ -490,10 +619,11 @@ impl RecordingSession {
             None
         };
 
-        let (encoder_width, encoder_height) = self.config.resolution.dimensions(
-            self.config.screen_size.width as u32,
-            self.config.screen_size.height as u32,
-        );
+        let (capture_width, capture_height) = self.config.capture_size();
+        let (encoder_width, encoder_height) = self
+            .config
+            .resolution
+            .dimensions(capture_width, capture_height);
 
         let video_info = VideoInfo::default()
             .with_width(encoder_width as i32)
@@ -540,6 +670,7 @@ impl RecordingSession {
 
         let stop_sig = self.stop_sig.clone();
         let error_sender = self.config.async_error_sender.clone();
+        let keyframe_request_sig = self.keyframe_request_sig.clone();
 
         let rt_handle_clone = rt_handle.clone();
         std::thread::spawn(move || {
@@ -572,6 +703,9 @@ impl RecordingSession {
                                     connections.insert(addr);
                                     SHARE_SCREEN_CONNECTIONS_COUNT.store(connections.len() as u32, Ordering::Relaxed);
                                     log::info!("connections count: {}", connections.len());
+
+                                    // The new viewer can't decode anything before the next keyframe
+                                    keyframe_request_sig.store(true, Ordering::Relaxed);
                                 }
                                 Ok(Event::LocalClosed(addr)) => {
                                     log::info!("LocalClosed({addr})");
@@ -623,10 +757,11 @@ impl RecordingSession {
         let (video_tx, video_rx) = bounded(ENCODER_WORKER_CHANNEL_SIZE / 2);
         let (audio_tx, audio_rx) = bounded(ENCODER_WORKER_CHANNEL_SIZE);
 
-        let (encoder_width, encoder_height) = self.config.resolution.dimensions(
-            self.config.screen_size.width as u32,
-            self.config.screen_size.height as u32,
-        );
+        let (capture_width, capture_height) = self.config.capture_size();
+        let (encoder_width, encoder_height) = self
+            .config
+            .resolution
+            .dimensions(capture_width, capture_height);
 
         let config = RtmpClientConfig::new(
             self.config.push_stream_config.server_addr.clone(),
@@ -651,7 +786,8 @@ impl RecordingSession {
         };
 
         let error_sender = self.config.async_error_sender.clone();
-        let mut client = RtmpClient::new(config, aac_config, video_rx, audio_rx, stop_sig.clone())?;
+        let mut client = RtmpClient::new(config, aac_config, video_rx, audio_rx, stop_sig.clone())?
+            .with_keyframe_request_sig(self.keyframe_request_sig.clone());
         let audio_input_frame_size = client.aac_encoder_input_frame_size();
 
         thread::spawn(move || match client.start() {
@@ -736,6 +872,7 @@ impl RecordingSession {
                             VideoFrameType::Frame(ref content) => {
                                 VideoFrameType::Frame(convert_annexb_to_length_prefixes(&content))
                             }
+                            VideoFrameType::Repeat => VideoFrameType::Repeat,
                             VideoFrameType::End => VideoFrameType::End,
                         };
 
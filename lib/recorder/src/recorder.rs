@@ -1,20 +1,25 @@
 use crate::{
-    AudioRecorder, EncodedFrame, FPS, Frame, FrameUser, ProcessMode, ProgressState, RecorderConfig,
-    RecorderError, Resolution, SpeakerRecorder, platform_speaker_recoder,
+    AudioRecorder, EncodedFrame, FPS, Frame, FrameDropStats, FrameUser, IdleInhibitor, ProcessMode,
+    ProgressState, RecorderConfig, RecorderError, Resolution, SpeakerRecorder, countdown,
+    cursor_overlay::CursorOverlay,
+    live_caption::{CaptionOverlay, LiveCaption},
+    pause::{PauseControl, PauseState},
+    platform_speaker_recoder,
+    replay::{ReplayBuffer, ReplaySaver},
     speaker_recorder::SpeakerRecorderConfig,
 };
 use camera::{CameraClient, CameraConfig, query_camera_id, query_first_camera};
 use crossbeam::channel::{Receiver, Sender, bounded};
 use derive_setters::Setters;
 use image::{GrayImage, ImageBuffer, Rgb};
-use mp4m::VideoFrameType;
+use mp4m::{Chapter, VideoFrameType};
 use screen_capture::{CaptureStreamConfig, LogicalSize, Rectangle, ScreenCapture};
 use spin_sleep::SpinSleeper;
 use std::{
     path::PathBuf,
     sync::{
         Arc, Mutex,
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
     },
     thread::{self, JoinHandle},
     time::{Duration, Instant},
@@ -23,7 +28,29 @@ use video_encoder::{VideoEncoder, VideoEncoderConfig};
 
 pub type ResizedImageBuffer = ImageBuffer<Rgb<u8>, Vec<u8>>;
 pub(crate) type CameraImage = image::RgbImage;
-pub(crate) type EncoderChannelData = (u64, ResizedImageBuffer, Option<CameraImage>);
+
+pub(crate) enum ProcessedFrame {
+    Image(ResizedImageBuffer),
+
+    /// Skipped by the `FpsThrottle`, the previous frame is shown once more
+    Repeat,
+}
+
+pub(crate) struct CollectChannelData {
+    /// The process worker, `None` for the repeats from the forward worker
+    pub(crate) thread_index: Option<usize>,
+    pub(crate) timestamp: Instant,
+    pub(crate) total_frame_index: u64,
+    pub(crate) frame: ProcessedFrame,
+}
+
+pub(crate) struct EncoderChannelData {
+    pub(crate) total_frame_index: u64,
+    pub(crate) frame: ProcessedFrame,
+
+    /// The first frame of a new scene is encoded as a keyframe
+    pub(crate) force_keyframe: bool,
+}
 
 pub(crate) const USER_CHANNEL_SIZE: usize = 64;
 pub(crate) const CURSOR_CHANNEL_SIZE: usize = 4094;
@@ -44,6 +71,10 @@ pub struct RecordingSession {
     #[setters(generate)]
     pub(crate) frame_sender_user: Option<Sender<FrameUser>>,
 
+    /// Receives the remaining seconds of the countdown before capturing
+    #[setters(generate)]
+    pub(crate) countdown_sender: Option<Sender<u32>>,
+
     pub(crate) audio_recorder: Option<AudioRecorder>,
     pub(crate) audio_level_receiver: Option<Receiver<f32>>,
 
@@ -53,28 +84,89 @@ pub struct RecordingSession {
     pub(crate) audio_mixer_stop_sig: Option<Arc<AtomicBool>>,
     pub(crate) audio_mixer_finished_sig: Option<Arc<AtomicBool>>,
     pub(crate) audio_mixer_worker: Option<JoinHandle<()>>,
+
+    /// The sample rate and the channels of the mixed audio
+    pub(crate) mix_audio_format: Option<(u32, u16)>,
+
+    /// Receives a copy of the mixed audio to transcribe for the live captions
+    #[setters(generate)]
+    pub(crate) caption_audio_sender: Option<Sender<Vec<f32>>>,
+
     pub(crate) mp4_writer_worker: Option<JoinHandle<()>>,
+    pub(crate) audio_file_worker: Option<JoinHandle<()>>,
     pub(crate) share_screen_worker: Option<JoinHandle<()>>,
     pub(crate) push_stream_worker: Option<JoinHandle<()>>,
     pub(crate) h264_frame_sender: Option<Sender<VideoFrameType>>,
+    pub(crate) chapter_sender: Option<Sender<Chapter>>,
+    pub(crate) pause: Arc<PauseState>,
+
+    // The last `replay_secs` of the video and the audio
+    pub(crate) replay_buffer: Option<Arc<ReplayBuffer>>,
 
     pub(crate) crop_region_receiver: Option<Receiver<Rectangle>>,
+    pub(crate) cursor_overlay: Option<Arc<CursorOverlay>>,
+    pub(crate) caption_overlay: Option<Arc<CaptionOverlay>>,
     pub(crate) video_encoder: Option<Box<dyn VideoEncoder>>,
+    pub(crate) keyframe_request_sig: Arc<AtomicBool>,
+
+    // Only every `fps_divisor`th frame is processed, see `FpsThrottle`
+    pub(crate) fps_divisor: Arc<AtomicU32>,
 
     pub(crate) camera_image_receiver: Option<Receiver<CameraImage>>,
     pub(crate) camera_background_remover_receiver: Option<Receiver<CameraImage>>,
     pub(crate) camera_background_remover_waiting_frame: Arc<AtomicBool>,
     pub(crate) camera_background_mask: Arc<Mutex<Option<GrayImage>>>,
 
+    // Held from the end of the countdown until the session is stopped
+    pub(crate) idle_inhibitor: Option<IdleInhibitor>,
+
     // statistic
     pub(crate) start_time: Instant,
     pub(crate) total_frame_count: Arc<AtomicU64>,
-    pub(crate) loss_frame_count: Arc<AtomicU64>,
+    pub(crate) frame_drops: Arc<FrameDropCounter>,
+}
+
+// The dropped frames of each stage, see `FrameDropStats`
+#[derive(Debug, Default)]
+pub(crate) struct FrameDropCounter {
+    pub(crate) process: AtomicU64,
+    pub(crate) collect: AtomicU64,
+    pub(crate) encode: AtomicU64,
+    pub(crate) output: AtomicU64,
+}
+
+impl FrameDropCounter {
+    pub(crate) fn stats(&self) -> FrameDropStats {
+        FrameDropStats {
+            process: self.process.load(Ordering::Relaxed),
+            collect: self.collect.load(Ordering::Relaxed),
+            encode: self.encode.load(Ordering::Relaxed),
+            output: self.output.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ChapterMarker {
+    sender: Sender<Chapter>,
+    start_time: Instant,
+    pause: Arc<PauseState>,
+}
+
+impl ChapterMarker {
+    /// Mark a chapter at the current recording position, the pauses aren't
+    /// counted in.
+    pub fn add(&self, title: impl Into<String>) {
+        let chapter = Chapter::new(self.pause.recording_time(self.start_time), title);
+        if let Err(e) = self.sender.try_send(chapter) {
+            log::warn!("Try send chapter failed: {e}");
+        }
+    }
 }
 
 impl RecordingSession {
     pub fn new(config: RecorderConfig) -> Self {
-        let (frame_sender, frame_receiver) = bounded(ENCODER_WORKER_CHANNEL_SIZE);
+        let (frame_sender, frame_receiver) = bounded(config.drop_policy().channel_size());
 
         Self {
             config,
@@ -86,6 +178,7 @@ impl RecordingSession {
             capture_workers: vec![],
 
             frame_sender_user: None,
+            countdown_sender: None,
 
             audio_recorder: None,
             audio_level_receiver: None,
@@ -96,23 +189,35 @@ impl RecordingSession {
             audio_mixer_stop_sig: None,
             audio_mixer_finished_sig: None,
             audio_mixer_worker: None,
+            mix_audio_format: None,
+            caption_audio_sender: None,
 
             mp4_writer_worker: None,
+            audio_file_worker: None,
             share_screen_worker: None,
             push_stream_worker: None,
             h264_frame_sender: None,
+            chapter_sender: None,
+            pause: Arc::new(PauseState::default()),
+            replay_buffer: None,
 
             crop_region_receiver: None,
+            cursor_overlay: None,
+            caption_overlay: None,
             video_encoder: None,
+            keyframe_request_sig: Arc::new(AtomicBool::new(false)),
+            fps_divisor: Arc::new(AtomicU32::new(1)),
 
             camera_image_receiver: None,
             camera_background_remover_receiver: None,
             camera_background_remover_waiting_frame: Arc::new(AtomicBool::new(true)),
             camera_background_mask: Arc::new(Mutex::new(None)),
 
+            idle_inhibitor: None,
+
             start_time: std::time::Instant::now(),
             total_frame_count: Arc::new(AtomicU64::new(0)),
-            loss_frame_count: Arc::new(AtomicU64::new(0)),
+            frame_drops: Arc::new(FrameDropCounter::default()),
         }
     }
 
@@ -137,6 +242,31 @@ impl RecordingSession {
             )));
         }
 
+        if self.config.process_mode == ProcessMode::RecordAudio
+            && self.config.audio_device_name.is_none()
+            && !self.config.enable_recording_speaker
+        {
+            return Err(RecorderError::InvalidConfig(
+                "No audio source to record, enable the microphone or the speaker".to_string(),
+            ));
+        }
+
+        if self.config.countdown > 0
+            && !countdown(
+                self.config.countdown,
+                &self.stop_sig,
+                self.countdown_sender.as_ref(),
+            )
+        {
+            return Err(RecorderError::Cancelled);
+        }
+
+        self.idle_inhibitor = IdleInhibitor::acquire();
+
+        if self.config.process_mode == ProcessMode::RecordAudio {
+            return self.start_record_audio();
+        }
+
         let thread_counts = self.evaluate_need_threads(&mut screen_capturer)?;
         if thread_counts == 0 {
             return Err(RecorderError::Other(format!("capture thread counts is 0")));
@@ -146,18 +276,23 @@ impl RecordingSession {
 
         self.start_time = std::time::Instant::now();
 
-        let (encoder_width, encoder_height) = self.config.resolution.dimensions(
-            self.config.screen_size.width as u32,
-            self.config.screen_size.height as u32,
-        );
+        let (capture_width, capture_height) = self.config.capture_size();
+        let (encoder_width, encoder_height) = self
+            .config
+            .resolution
+            .dimensions(capture_width, capture_height);
 
-        let video_encoder_config = VideoEncoderConfig::new(encoder_width, encoder_height)
+        let mut video_encoder_config = VideoEncoderConfig::new(encoder_width, encoder_height)
             .with_fps(self.config.fps.to_u32())
             .with_annexb(match self.config.process_mode {
-                ProcessMode::RecordScreen => false,
+                ProcessMode::RecordScreen | ProcessMode::RecordAudio => false,
                 ProcessMode::ShareScreen | ProcessMode::PushStream => true,
             });
 
+        if self.config.process_mode == ProcessMode::PushStream {
+            video_encoder_config.rate_control = self.config.push_stream_config.rate_control.clone();
+        }
+
         let mut video_encoder = video_encoder::new(video_encoder_config)?;
         let headers_data = video_encoder.headers()?;
 
@@ -169,6 +304,13 @@ impl RecordingSession {
             mix_audio_sample_rate,
         ) = self.mix_audio_tracks()?;
 
+        let mix_audio_receiver = if self.live_caption_enabled() {
+            self.enable_live_caption()?;
+            self.caption_audio_worker(mix_audio_receiver)
+        } else {
+            mix_audio_receiver
+        };
+
         let h264_frame_sender = match self.config.process_mode {
             ProcessMode::RecordScreen => self.mp4_worker(
                 Some(headers_data.clone()),
@@ -190,6 +332,7 @@ impl RecordingSession {
                 mix_audio_channels,
                 mix_audio_sample_rate,
             )?,
+            ProcessMode::RecordAudio => unreachable!("audio is recorded without the video"),
         };
 
         self.h264_frame_sender = h264_frame_sender;
@@ -205,7 +348,7 @@ impl RecordingSession {
         let fps_per_thread = self.config.fps.to_u32() as f64 / thread_counts as f64;
         let config = CaptureStreamConfig {
             name: self.config.screen_name.clone(),
-            include_cursor: self.config.include_cursor,
+            include_cursor: self.config.include_cursor && !self.config.draw_cursor(),
             fps: Some(fps_per_thread),
             cancel_sig: self.stop_sig.clone(),
             sync_sig: self.sync_sig.clone(),
@@ -263,10 +406,19 @@ impl RecordingSession {
                     "`sync_sig` is true. start to run audio, speaker and cursor tracker threads"
                 );
 
+                // Created before the cursor tracker, which shares the cursor positions
+                if self.config.draw_cursor() {
+                    self.cursor_overlay_worker(screen_capturer.clone())?;
+                }
+
                 if self.config.enable_cursor_tracking {
                     let (crop_region_sender, crop_region_receiver) = bounded(CURSOR_CHANNEL_SIZE);
                     self.cursor_tracker_worker(screen_capturer.clone(), crop_region_sender)?;
                     self.crop_region_receiver = Some(crop_region_receiver);
+                } else if self.config.enable_window_following {
+                    let (crop_region_sender, crop_region_receiver) = bounded(CURSOR_CHANNEL_SIZE);
+                    self.window_follower_worker(screen_capturer.clone(), crop_region_sender)?;
+                    self.crop_region_receiver = Some(crop_region_receiver);
                 }
 
                 if let Some(device_name) = self.config.audio_device_name.clone() {
@@ -291,32 +443,115 @@ impl RecordingSession {
         Ok(())
     }
 
+    // Skips the capture and the video encoder, the mixed audio is written
+    // into the file directly
+    fn start_record_audio(&mut self) -> Result<(), RecorderError> {
+        self.start_time = std::time::Instant::now();
+        self.frame_sender.take();
+
+        let (
+            audio_sender,
+            speak_sender,
+            mix_audio_receiver,
+            mix_audio_channels,
+            mix_audio_sample_rate,
+        ) = self.mix_audio_tracks()?;
+
+        let (Some(mix_audio_receiver), Some(mix_audio_channels), Some(mix_audio_sample_rate)) = (
+            mix_audio_receiver,
+            mix_audio_channels,
+            mix_audio_sample_rate,
+        ) else {
+            return Err(RecorderError::InvalidConfig(
+                "No audio source to record".to_string(),
+            ));
+        };
+
+        self.audio_file_worker(
+            mix_audio_receiver,
+            mix_audio_channels,
+            mix_audio_sample_rate,
+        )?;
+
+        if let Some(device_name) = self.config.audio_device_name.clone() {
+            self.enable_audio(device_name.as_str(), audio_sender)?;
+            log::info!("Enable audio recording successfully");
+        }
+
+        if self.config.enable_recording_speaker {
+            self.enable_speaker_audio(speak_sender)?;
+            log::info!("Enable speaker recording successfully");
+        }
+
+        Ok(())
+    }
+
     pub fn wait(mut self) -> Result<ProgressState, RecorderError> {
+        if self.config.process_mode == ProcessMode::RecordAudio {
+            while !self.stop_sig.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(50));
+            }
+
+            log::info!("audio recording exit...");
+            self.wait_stop(vec![])?;
+            return Ok(ProgressState::Stopped);
+        }
+
+        let drop_policy = self.config.drop_policy();
         let (encoder_sender, encoder_receiver) =
-            bounded::<EncoderChannelData>(ENCODER_WORKER_CHANNEL_SIZE);
+            bounded::<EncoderChannelData>(drop_policy.channel_size());
         let process_frame_handles = Self::process_frame_workers(&self, encoder_sender);
 
         loop {
             match encoder_receiver.recv() {
-                Ok((total_frame_index, img, _)) => {
-                    let now = std::time::Instant::now();
-                    match self
-                        .video_encoder
-                        .as_mut()
-                        .unwrap()
-                        .encode_frame(img.into())
+                Ok(EncoderChannelData {
+                    frame: ProcessedFrame::Repeat,
+                    ..
+                }) => {
+                    if let Some(ref replay_buffer) = self.replay_buffer {
+                        replay_buffer.push_frame(VideoFrameType::Repeat);
+                    }
+
+                    // Extends the previous sample instead of encoding a copy
+                    if let Some(ref sender) = self.h264_frame_sender
+                        && let Err(e) = sender.try_send(VideoFrameType::Repeat)
                     {
+                        self.frame_drops.output.fetch_add(1, Ordering::Relaxed);
+                        log::warn!("Try send h264 repeat frame faield: {e}");
+                    }
+                }
+                Ok(EncoderChannelData {
+                    total_frame_index,
+                    frame: ProcessedFrame::Image(img),
+                    force_keyframe,
+                }) => {
+                    let now = std::time::Instant::now();
+                    let video_encoder = self.video_encoder.as_mut().unwrap();
+
+                    // A new viewer asks for it at any frame, a scene change on its own frame
+                    if self.keyframe_request_sig.swap(false, Ordering::Relaxed) || force_keyframe {
+                        log::info!("force keyframe at frame[{total_frame_index}]");
+                        video_encoder.request_keyframe();
+                    }
+
+                    match video_encoder.encode_frame(img.into()) {
                         Ok(EncodedFrame::Frame((_, encoded_frame))) => {
                             log::debug!(
                                 "total encoded frame[{total_frame_index}] {} bytes",
                                 encoded_frame.len()
                             );
 
+                            if let Some(ref replay_buffer) = self.replay_buffer {
+                                replay_buffer
+                                    .push_frame(VideoFrameType::Frame(encoded_frame.clone()));
+                            }
+
                             if let Some(ref sender) = self.h264_frame_sender {
+                                // Never wait here, the encoder would fall behind the capture
                                 if let Err(e) =
                                     sender.try_send(VideoFrameType::Frame(encoded_frame))
                                 {
-                                    self.loss_frame_count.fetch_add(1, Ordering::Relaxed);
+                                    self.frame_drops.output.fetch_add(1, Ordering::Relaxed);
                                     log::warn!("Try send h264 body frame faield: {e}");
                                 }
                             }
@@ -408,6 +643,26 @@ impl RecordingSession {
         Ok(())
     }
 
+    // Only the live viewers get the captions, the recorded files can be
+    // transcribed afterwards
+    fn live_caption_enabled(&self) -> bool {
+        self.config.live_caption_config.enable
+            && matches!(
+                self.config.process_mode,
+                ProcessMode::ShareScreen | ProcessMode::PushStream
+            )
+    }
+
+    fn enable_live_caption(&mut self) -> Result<(), RecorderError> {
+        let config = &self.config.live_caption_config;
+        let font = config.font.clone().ok_or(RecorderError::InvalidConfig(
+            "No font for the live captions".to_string(),
+        ))?;
+
+        self.caption_overlay = Some(Arc::new(CaptionOverlay::new(font, config.style.clone())));
+        Ok(())
+    }
+
     fn enable_camera(&mut self) -> Result<(), RecorderError> {
         camera::init();
 
@@ -560,6 +815,14 @@ impl RecordingSession {
             }
         }
 
+        if let Some(handle) = self.audio_file_worker.take() {
+            if let Err(e) = handle.join() {
+                log::warn!("join audio file worker failed: {:?}", e);
+            } else {
+                log::info!("join audio file worker successfully");
+            }
+        }
+
         if let Some(handle) = self.share_screen_worker.take() {
             if let Err(e) = handle.join() {
                 log::warn!("join share screen worker failed: {:?}", e);
@@ -576,17 +839,22 @@ impl RecordingSession {
             }
         }
 
+        let frame_drops = self.frame_drops.stats();
         log::info!(
-            "Total frame: {}. loss frame: {} ({:.2}%)",
+            "Total frame: {}. loss frame: {} ({:.2}%). {frame_drops:?}",
             self.total_frame_count.load(Ordering::Relaxed),
-            self.loss_frame_count.load(Ordering::Relaxed),
-            self.loss_frame_count.load(Ordering::Relaxed) as f64 * 100.0
+            frame_drops.total(),
+            frame_drops.total() as f64 * 100.0
                 / self.total_frame_count.load(Ordering::Relaxed).max(1) as f64,
         );
 
-        if matches!(self.config.process_mode, ProcessMode::RecordScreen)
-            || (matches!(self.config.process_mode, ProcessMode::ShareScreen)
-                && self.config.share_screen_config.save_mp4)
+        self.idle_inhibitor.take();
+
+        if matches!(
+            self.config.process_mode,
+            ProcessMode::RecordScreen | ProcessMode::RecordAudio
+        ) || (matches!(self.config.process_mode, ProcessMode::ShareScreen)
+            && self.config.share_screen_config.save_mp4)
             || (matches!(self.config.process_mode, ProcessMode::PushStream)
                 && self.config.push_stream_config.save_mp4)
         {
@@ -620,6 +888,45 @@ impl RecordingSession {
         self.speaker_level_receiver.clone()
     }
 
+    /// `None` if the captions aren't enabled or the mode isn't live
+    pub fn get_live_caption(&self) -> Option<LiveCaption> {
+        self.caption_overlay.clone().map(LiveCaption::new)
+    }
+
+    /// The sample rate and the channels of the audio sent to
+    /// `with_caption_audio_sender`, known once it's started
+    pub fn mix_audio_format(&self) -> Option<(u32, u16)> {
+        self.mix_audio_format
+    }
+
+    /// Available after `start` when the session saves an MP4 file.
+    pub fn get_chapter_marker(&self) -> Option<ChapterMarker> {
+        self.chapter_sender.clone().map(|sender| ChapterMarker {
+            sender,
+            start_time: self.start_time,
+            pause: self.pause.clone(),
+        })
+    }
+
+    /// `None` for the live modes, the viewers expect the stream to go on.
+    pub fn get_pause_control(&self) -> Option<PauseControl> {
+        matches!(
+            self.config.process_mode,
+            ProcessMode::RecordScreen | ProcessMode::RecordAudio
+        )
+        .then(|| PauseControl {
+            state: self.pause.clone(),
+        })
+    }
+
+    /// Available after `start` when `replay_secs` is set and the screen is
+    /// recorded into a file.
+    pub fn get_replay_saver(&self) -> Option<ReplaySaver> {
+        self.replay_buffer
+            .clone()
+            .map(|buffer| ReplaySaver { buffer })
+    }
+
     pub fn warmup_video_encoder(screen_size: LogicalSize, resolution: Resolution, fps: FPS) {
         let (encoder_width, encoder_height) =
             resolution.dimensions(screen_size.width as u32, screen_size.height as u32);
@@ -647,3 +954,32 @@ impl RecordingSession {
         Ok(((mean_ms / iterval_ms).ceil() * 2.0).ceil() as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_drop_counter() {
+        let counter = FrameDropCounter::default();
+        assert_eq!(counter.stats(), FrameDropStats::default());
+        assert_eq!(counter.stats().total(), 0);
+
+        counter.process.fetch_add(1, Ordering::Relaxed);
+        counter.collect.fetch_add(2, Ordering::Relaxed);
+        counter.encode.fetch_add(3, Ordering::Relaxed);
+        counter.output.fetch_add(4, Ordering::Relaxed);
+
+        let stats = counter.stats();
+        assert_eq!(
+            stats,
+            FrameDropStats {
+                process: 1,
+                collect: 2,
+                encode: 3,
+                output: 4,
+            }
+        );
+        assert_eq!(stats.total(), 10);
+    }
+}
@@ -0,0 +1,327 @@
+//! Keeps the last seconds of the encoded video and the mixed audio in
+//! memory, so they can be saved into a file of their own while the
+//! recording goes on.
+
+use crate::RecorderError;
+use hound::WavSpec;
+use mp4m::{
+    AudioConfig, Mp4Metadata, Mp4Processor, Mp4ProcessorConfigBuilder, VideoConfig, VideoFrameType,
+};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+struct ReplayFrame {
+    keyframe: bool,
+    frame: VideoFrameType,
+}
+
+#[derive(Default)]
+struct ReplayData {
+    frames: VecDeque<ReplayFrame>,
+    audio: VecDeque<Vec<f32>>,
+    audio_samples: usize,
+}
+
+pub(crate) struct ReplayBuffer {
+    save_path: PathBuf,
+    saved_count: AtomicU32,
+    headers: Vec<u8>,
+    video_config: VideoConfig,
+    metadata: Mp4Metadata,
+
+    /// The sample rate and the channels of the mixed audio
+    audio_format: Option<(u32, u16)>,
+
+    max_frames: usize,
+    max_audio_samples: usize,
+    data: Mutex<ReplayData>,
+}
+
+impl ReplayBuffer {
+    pub(crate) fn new(
+        seconds: u32,
+        save_path: PathBuf,
+        headers: Vec<u8>,
+        video_config: VideoConfig,
+        metadata: Mp4Metadata,
+        audio_format: Option<(u32, u16)>,
+    ) -> Self {
+        let max_frames = (seconds * video_config.fps) as usize;
+
+        // A second more than the video, the tail is cut to its length on saving
+        let max_audio_samples = audio_format
+            .map(|(sample_rate, channels)| {
+                (seconds as usize + 1) * sample_rate as usize * channels as usize
+            })
+            .unwrap_or_default();
+
+        Self {
+            save_path,
+            saved_count: AtomicU32::new(0),
+            headers,
+            video_config,
+            metadata,
+            audio_format,
+            max_frames,
+            max_audio_samples,
+            data: Mutex::new(ReplayData::default()),
+        }
+    }
+
+    pub(crate) fn push_frame(&self, frame: VideoFrameType) {
+        let keyframe = match frame {
+            VideoFrameType::Frame(ref data) => Mp4Processor::is_keyframe_length_prefixed(data),
+            VideoFrameType::Repeat => false,
+            VideoFrameType::End => return,
+        };
+
+        let mut data = self.data.lock().unwrap();
+        data.frames.push_back(ReplayFrame { keyframe, frame });
+
+        // The replay has to start with a keyframe, so whole GOPs are dropped
+        // as long as the rest is still long enough
+        while data.frames.len() > self.max_frames {
+            let Some(next_keyframe) = data
+                .frames
+                .iter()
+                .skip(1)
+                .position(|frame| frame.keyframe)
+                .map(|index| index + 1)
+            else {
+                break;
+            };
+
+            if data.frames.len() - next_keyframe < self.max_frames {
+                break;
+            }
+
+            data.frames.drain(..next_keyframe);
+        }
+    }
+
+    pub(crate) fn push_audio(&self, samples: Vec<f32>) {
+        let mut data = self.data.lock().unwrap();
+        data.audio_samples += samples.len();
+        data.audio.push_back(samples);
+
+        while let Some(front) = data.audio.front()
+            && data.audio_samples - front.len() >= self.max_audio_samples
+        {
+            data.audio_samples -= front.len();
+            data.audio.pop_front();
+        }
+    }
+
+    // The frames from the first kept keyframe, and the audio as long as them
+    fn snapshot(&self) -> (Vec<VideoFrameType>, Vec<f32>) {
+        let data = self.data.lock().unwrap();
+        let frames = data
+            .frames
+            .iter()
+            .skip_while(|frame| !frame.keyframe)
+            .map(|frame| frame.frame.clone())
+            .collect::<Vec<_>>();
+
+        let Some((sample_rate, channels)) = self.audio_format else {
+            return (frames, vec![]);
+        };
+
+        let audio_samples = frames.len() * sample_rate as usize / self.video_config.fps as usize
+            * channels as usize;
+        let skip_samples = data.audio_samples.saturating_sub(audio_samples);
+        let audio = data
+            .audio
+            .iter()
+            .flatten()
+            .skip(skip_samples)
+            .copied()
+            .collect::<Vec<_>>();
+
+        (frames, audio)
+    }
+
+    fn save(&self) -> Result<PathBuf, RecorderError> {
+        let (frames, audio) = self.snapshot();
+        if frames.is_empty() {
+            return Err(RecorderError::Other(
+                "No keyframe is kept for the replay yet".to_string(),
+            ));
+        }
+
+        let index = self.saved_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let save_path = replay_save_path(&self.save_path, index);
+
+        let mut metadata = self.metadata.clone();
+        metadata.creation_time = Some(chrono::Utc::now());
+
+        // Everything is queued before the processing loop runs
+        let mut mp4_processor = Mp4Processor::new(
+            Mp4ProcessorConfigBuilder::default()
+                .save_path(save_path.clone())
+                .metadata(metadata)
+                .channel_size(frames.len() + 1)
+                .video_config(self.video_config.clone())
+                .build()?,
+        );
+
+        if let Some((sample_rate, channels)) = self.audio_format {
+            let sender = mp4_processor.add_audio_track(AudioConfig {
+                convert_to_mono: false,
+                spec: WavSpec {
+                    channels,
+                    sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                },
+            })?;
+
+            if !audio.is_empty() && sender.try_send(audio).is_err() {
+                return Err(RecorderError::QueueError(
+                    "queue the replay audio failed".to_string(),
+                ));
+            }
+        }
+
+        let h264_sender = mp4_processor.h264_sender();
+        for frame in frames.into_iter().chain([VideoFrameType::End]) {
+            if h264_sender.try_send(frame).is_err() {
+                return Err(RecorderError::QueueError(
+                    "queue the replay frames failed".to_string(),
+                ));
+            }
+        }
+
+        mp4_processor.run_processing_loop(Some(self.headers.clone()))?;
+        log::info!("Successfully save replay: {}", save_path.display());
+
+        Ok(save_path)
+    }
+}
+
+// Next to the recording, e.g. `a.mp4` -> `a-replay-1.mp4`
+fn replay_save_path(save_path: &Path, index: u32) -> PathBuf {
+    let stem = save_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file_name = match save_path.extension() {
+        Some(ext) => format!("{stem}-replay-{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-replay-{index}"),
+    };
+
+    save_path.with_file_name(file_name)
+}
+
+#[derive(Clone)]
+pub struct ReplaySaver {
+    pub(crate) buffer: Arc<ReplayBuffer>,
+}
+
+impl ReplaySaver {
+    /// Save the kept seconds next to the recording and return the path of
+    /// the file. It blocks until the file is written.
+    pub fn save(&self) -> Result<PathBuf, RecorderError> {
+        self.buffer.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYFRAME: [u8; 5] = [0x00, 0x00, 0x00, 0x01, 0x65];
+    const FRAME: [u8; 5] = [0x00, 0x00, 0x00, 0x01, 0x41];
+
+    fn replay_buffer(seconds: u32, fps: u32, audio_format: Option<(u32, u16)>) -> ReplayBuffer {
+        ReplayBuffer::new(
+            seconds,
+            PathBuf::from("/tmp/test-replay.mp4"),
+            vec![],
+            VideoConfig {
+                width: 1920,
+                height: 1080,
+                fps,
+            },
+            Mp4Metadata::default(),
+            audio_format,
+        )
+    }
+
+    fn keyframes(buffer: &ReplayBuffer) -> Vec<bool> {
+        let data = buffer.data.lock().unwrap();
+        data.frames.iter().map(|frame| frame.keyframe).collect()
+    }
+
+    #[test]
+    fn test_push_frame() {
+        let buffer = replay_buffer(1, 4, None);
+        for index in 0..10 {
+            let data = if index % 3 == 0 { KEYFRAME } else { FRAME };
+            buffer.push_frame(VideoFrameType::Frame(data.to_vec()));
+        }
+
+        // Frames 6 to 9 are left, the older GOPs are dropped whole
+        assert_eq!(keyframes(&buffer), [true, false, false, true]);
+
+        buffer.push_frame(VideoFrameType::Repeat);
+        buffer.push_frame(VideoFrameType::End);
+        assert_eq!(keyframes(&buffer), [true, false, false, true, false]);
+
+        let (frames, audio) = buffer.snapshot();
+        assert_eq!(frames.len(), 5);
+        assert!(matches!(frames[4], VideoFrameType::Repeat));
+        assert!(audio.is_empty());
+    }
+
+    #[test]
+    fn test_push_frame_without_keyframe() {
+        let buffer = replay_buffer(1, 2, None);
+        for _ in 0..4 {
+            buffer.push_frame(VideoFrameType::Frame(FRAME.to_vec()));
+        }
+
+        assert_eq!(keyframes(&buffer).len(), 4);
+        assert!(buffer.snapshot().0.is_empty());
+    }
+
+    #[test]
+    fn test_push_audio() {
+        let buffer = replay_buffer(1, 2, Some((4, 2)));
+        for index in 0..10 {
+            buffer.push_audio(vec![index as f32; 4]);
+        }
+
+        // 2 seconds of 4Hz stereo audio are kept
+        let data = buffer.data.lock().unwrap();
+        assert_eq!(data.audio_samples, 16);
+        assert_eq!(data.audio.front(), Some(&vec![6.0; 4]));
+        drop(data);
+
+        buffer.push_frame(VideoFrameType::Frame(KEYFRAME.to_vec()));
+        buffer.push_frame(VideoFrameType::Frame(FRAME.to_vec()));
+
+        // The audio is as long as the 1 second of the video
+        let (frames, audio) = buffer.snapshot();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(audio, [8.0, 8.0, 8.0, 8.0, 9.0, 9.0, 9.0, 9.0]);
+    }
+
+    #[test]
+    fn test_replay_save_path() {
+        assert_eq!(
+            replay_save_path(Path::new("/tmp/a.mp4"), 1),
+            PathBuf::from("/tmp/a-replay-1.mp4")
+        );
+        assert_eq!(
+            replay_save_path(Path::new("/tmp/a"), 2),
+            PathBuf::from("/tmp/a-replay-2")
+        );
+    }
+}
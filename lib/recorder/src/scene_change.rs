@@ -0,0 +1,146 @@
+use crate::ResizedImageBuffer;
+
+// Only every 4th pixel of every 4th row is compared
+const SAMPLE_STEP: u32 = 4;
+
+// 16x16 pixel blocks, in sampled pixels
+const BLOCK_SAMPLES: usize = (16 / SAMPLE_STEP) as usize;
+
+// Mean absolute luma difference of a block to count it as changed
+const BLOCK_DIFF_THRESHOLD: u32 = 24;
+
+// Ratio of changed blocks to count the frame as a new scene
+const CHANGED_BLOCKS_RATIO: f32 = 0.4;
+
+/// Detect large content changes between consecutive frames, e.g. a slide switch,
+/// by comparing the block-level SAD of the subsampled luma planes.
+pub(crate) struct SceneChangeDetector {
+    min_interval_frames: u64,
+    frames_since_change: u64,
+    size: (u32, u32),
+    prev_luma: Vec<u8>,
+}
+
+impl SceneChangeDetector {
+    pub(crate) fn new(min_interval_frames: u64) -> Self {
+        Self {
+            min_interval_frames,
+            frames_since_change: 0,
+            size: (0, 0),
+            prev_luma: vec![],
+        }
+    }
+
+    /// Feed the next frame in display order. Scene changes closer than
+    /// `min_interval_frames` to the previous one are ignored.
+    pub(crate) fn is_scene_change(&mut self, img: &ResizedImageBuffer) -> bool {
+        let size = img.dimensions();
+        let luma = subsampled_luma(img);
+
+        let changed = size == self.size
+            && changed_blocks_ratio(&self.prev_luma, &luma, (size.0 / SAMPLE_STEP) as usize)
+                > CHANGED_BLOCKS_RATIO;
+
+        self.size = size;
+        self.prev_luma = luma;
+        self.frames_since_change += 1;
+
+        if changed && self.frames_since_change >= self.min_interval_frames {
+            self.frames_since_change = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn subsampled_luma(img: &ResizedImageBuffer) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let (cols, rows) = (width / SAMPLE_STEP, height / SAMPLE_STEP);
+    let mut luma = Vec::with_capacity((cols * rows) as usize);
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let [r, g, b] = img.get_pixel(col * SAMPLE_STEP, row * SAMPLE_STEP).0;
+            luma.push(((r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8) as u8);
+        }
+    }
+
+    luma
+}
+
+fn changed_blocks_ratio(prev: &[u8], current: &[u8], cols: usize) -> f32 {
+    if cols == 0 || prev.len() != current.len() {
+        return 0.0;
+    }
+
+    let rows = current.len() / cols;
+    let (block_cols, block_rows) = (cols / BLOCK_SAMPLES, rows / BLOCK_SAMPLES);
+    if block_cols == 0 || block_rows == 0 {
+        return 0.0;
+    }
+
+    let mut changed_blocks = 0;
+    for block_row in 0..block_rows {
+        for block_col in 0..block_cols {
+            let mut sad = 0;
+            for row in block_row * BLOCK_SAMPLES..(block_row + 1) * BLOCK_SAMPLES {
+                let start = row * cols + block_col * BLOCK_SAMPLES;
+                let end = start + BLOCK_SAMPLES;
+
+                sad += prev[start..end]
+                    .iter()
+                    .zip(&current[start..end])
+                    .map(|(a, b)| a.abs_diff(*b) as u32)
+                    .sum::<u32>();
+            }
+
+            if sad > BLOCK_DIFF_THRESHOLD * (BLOCK_SAMPLES * BLOCK_SAMPLES) as u32 {
+                changed_blocks += 1;
+            }
+        }
+    }
+
+    changed_blocks as f32 / (block_cols * block_rows) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn solid_frame(value: u8) -> ResizedImageBuffer {
+        ResizedImageBuffer::from_pixel(64, 64, Rgb([value, value, value]))
+    }
+
+    #[test]
+    fn test_scene_change() {
+        let mut detector = SceneChangeDetector::new(1);
+
+        assert!(!detector.is_scene_change(&solid_frame(0)));
+        assert!(!detector.is_scene_change(&solid_frame(5)));
+        assert!(detector.is_scene_change(&solid_frame(200)));
+        assert!(!detector.is_scene_change(&solid_frame(200)));
+    }
+
+    #[test]
+    fn test_scene_change_min_interval() {
+        let mut detector = SceneChangeDetector::new(3);
+
+        assert!(!detector.is_scene_change(&solid_frame(0)));
+        assert!(!detector.is_scene_change(&solid_frame(200)));
+        assert!(detector.is_scene_change(&solid_frame(0)));
+        assert!(!detector.is_scene_change(&solid_frame(200)));
+    }
+
+    #[test]
+    fn test_changed_blocks_ratio() {
+        let prev = vec![0; 16 * 8];
+        let mut current = prev.clone();
+        for row in 0..4 {
+            current[row * 16..row * 16 + 4].fill(255);
+        }
+
+        assert_eq!(changed_blocks_ratio(&prev, &current, 16), 1.0 / 8.0);
+    }
+}
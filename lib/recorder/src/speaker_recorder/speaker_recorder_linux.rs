@@ -1,7 +1,8 @@
 use crate::{
-    audio_level::{apply_gain, calc_rms_level},
+    audio_level::{apply_gain, calc_loudness_level},
     speaker_recorder::{SpeakerRecorder, SpeakerRecorderConfig, SpeakerRecorderError},
 };
+use audio_utils::loudness::LoudnessMeter;
 use crossbeam::channel::Sender;
 use hound::WavSpec;
 use pipewire::{
@@ -79,6 +80,8 @@ impl SpeakerRecorderLinux {
         level_sender: Option<Sender<f32>>,
         gain: Option<Arc<AtomicI32>>,
     ) -> Result<StreamListener<()>, SpeakerRecorderError> {
+        let mut loudness_meter = LoudnessMeter::new(48000, 2);
+
         let stream_listener = stream
             .add_local_listener::<()>()
             .process(move |stream, _| {
@@ -120,7 +123,7 @@ impl SpeakerRecorderLinux {
                     }
 
                     if let Some(ref tx) = level_sender
-                        && let Some(db) = calc_rms_level(f32_samples)
+                        && let Some(db) = calc_loudness_level(&mut loudness_meter, f32_samples)
                         && let Err(e) = tx.try_send(db)
                     {
                         log::warn!("try send speaker audio db level data failed: {e}");
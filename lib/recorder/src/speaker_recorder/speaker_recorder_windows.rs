@@ -1,7 +1,8 @@
 use crate::{
-    audio_level::{apply_gain, calc_rms_level},
+    audio_level::{apply_gain, calc_loudness_level},
     speaker_recorder::{SpeakerRecorder, SpeakerRecorderConfig, SpeakerRecorderError},
 };
+use audio_utils::loudness::LoudnessMeter;
 use crossbeam::channel::Sender;
 use hound::WavSpec;
 use spin_sleep::SpinSleeper;
@@ -223,6 +224,7 @@ impl SpeakerRecorderWindows {
         buffer: &[u8],
         frame_sender: Option<&Sender<Vec<f32>>>,
         level_sender: Option<&Sender<f32>>,
+        loudness_meter: &mut LoudnessMeter,
         gain: Option<&Arc<AtomicI32>>,
     ) -> std::result::Result<(), SpeakerRecorderError> {
         // For Windows speaker recording, we're always working with 32-bit float format
@@ -250,7 +252,7 @@ impl SpeakerRecorderWindows {
         }
 
         if let Some(ref tx) = level_sender
-            && let Some(db) = calc_rms_level(processed_samples)
+            && let Some(db) = calc_loudness_level(loudness_meter, processed_samples)
             && let Err(e) = tx.try_send(db)
         {
             log::warn!("try send speaker audio db level data failed: {e}");
@@ -322,6 +324,9 @@ impl SpeakerRecorder for SpeakerRecorderWindows {
 
         log::info!("Using sample rate: {}Hz", sample_rate);
 
+        let spec = self.spec();
+        let mut loudness_meter = LoudnessMeter::new(spec.sample_rate, spec.channels);
+
         let start_time = std::time::Instant::now();
         let mut total_frames_written: u64 = 0;
         let latency_threshold_frames = (sample_rate as f64 * 0.020) as u64; // 20ms
@@ -346,14 +351,15 @@ impl SpeakerRecorder for SpeakerRecorderWindows {
                 let samples_to_fill = (missing_frames as usize * channels) as usize; // Use actual channel count
                 let silent_buffer = vec![0.0f32; samples_to_fill];
 
-                if let Some(ref tx) = self.config.frame_sender {
-                    _ = tx.try_send(silent_buffer);
-                }
-
                 if let Some(ref tx) = self.config.level_sender {
+                    loudness_meter.push(&silent_buffer);
                     let _ = tx.try_send(-200.0);
                 }
 
+                if let Some(ref tx) = self.config.frame_sender {
+                    _ = tx.try_send(silent_buffer);
+                }
+
                 log::trace!("Filled silence gap: {} frames", missing_frames);
                 total_frames_written += missing_frames;
             }
@@ -389,13 +395,14 @@ impl SpeakerRecorder for SpeakerRecorderWindows {
                             let silent_len = (num_frames_available as usize * channels) as usize;
                             let silent_buffer = vec![0.0f32; silent_len];
 
-                            if let Some(ref tx) = self.config.frame_sender {
-                                _ = tx.try_send(silent_buffer);
-                            }
-
                             if let Some(ref tx) = self.config.level_sender {
+                                loudness_meter.push(&silent_buffer);
                                 _ = tx.try_send(-200.0);
                             }
+
+                            if let Some(ref tx) = self.config.frame_sender {
+                                _ = tx.try_send(silent_buffer);
+                            }
                         } else {
                             // Calculate buffer length based on actual format
                             let bytes_per_sample = self
@@ -415,6 +422,7 @@ impl SpeakerRecorder for SpeakerRecorderWindows {
                                 buffer,
                                 self.config.frame_sender.as_ref(),
                                 self.config.level_sender.as_ref(),
+                                &mut loudness_meter,
                                 self.config.gain.as_ref(),
                             )?;
                         }
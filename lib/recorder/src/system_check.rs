@@ -0,0 +1,347 @@
+//! Measures the capture, the encoder and the disk before recording, so the
+//! user can be warned when the settings will drop frames.
+
+use crate::{FPS, RecorderError, ResizedImageBuffer, resolution::Resolution};
+use derive_setters::Setters;
+use screen_capture::{LogicalSize, ScreenCapture};
+use std::{
+    cell::Cell,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+use video_encoder::{EncodedFrame, VideoEncoderConfig};
+
+// The encoder and the disk should keep up with some room left for the rest
+// of the pipeline
+const HEADROOM: f64 = 1.2;
+
+const DISK_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+pub struct SystemCheckConfig {
+    pub screen_name: String,
+    pub screen_size: LogicalSize,
+    pub fps: FPS,
+    pub resolution: Resolution,
+
+    /// The directory of the recorded files, a temporary file is written in it
+    pub save_dir: PathBuf,
+
+    /// Frames captured to measure the capture time
+    pub capture_frames: u32,
+
+    /// Frames encoded in the benchmark
+    pub encode_frames: u32,
+
+    /// Bytes written to measure the disk speed
+    pub disk_write_size: usize,
+}
+
+impl SystemCheckConfig {
+    pub fn new(screen_name: String, screen_size: LogicalSize, save_dir: PathBuf) -> Self {
+        Self {
+            screen_name,
+            screen_size,
+            save_dir,
+            fps: FPS::Fps25,
+            resolution: Resolution::P1080,
+            capture_frames: 5,
+            encode_frames: 60,
+            disk_write_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SystemCheckWarning {
+    /// The screen can't be captured at the wanted FPS
+    SlowCapture { attainable_fps: f64 },
+
+    /// The encoder can't keep up at the chosen resolution
+    SlowEncoder { encode_fps: f64 },
+
+    /// The disk is slower than the encoded video
+    SlowDisk {
+        write_speed: f64,
+        required_speed: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemCheckReport {
+    pub fps: u32,
+
+    /// The encoded width and height
+    pub dimensions: (u32, u32),
+
+    /// `None` if the backend captures a stream, its time can't be measured
+    pub capture_mean_time: Option<Duration>,
+
+    /// The capture threads which the recording would start
+    pub capture_threads: u32,
+
+    /// Frames per second with all the capture threads, `None` if unknown
+    pub capture_fps: Option<f64>,
+
+    pub encode_fps: f64,
+
+    /// Bytes per second of the encoded benchmark video
+    pub encoded_bitrate: f64,
+
+    /// Bytes per second
+    pub disk_write_speed: f64,
+}
+
+impl SystemCheckReport {
+    pub fn warnings(&self) -> Vec<SystemCheckWarning> {
+        let fps = self.fps as f64;
+        let mut warnings = vec![];
+
+        if let Some(capture_fps) = self.capture_fps
+            && capture_fps < fps
+        {
+            warnings.push(SystemCheckWarning::SlowCapture {
+                attainable_fps: capture_fps,
+            });
+        }
+
+        if self.encode_fps < fps * HEADROOM {
+            warnings.push(SystemCheckWarning::SlowEncoder {
+                encode_fps: self.encode_fps,
+            });
+        }
+
+        let required_speed = self.encoded_bitrate * HEADROOM;
+        if self.disk_write_speed < required_speed {
+            warnings.push(SystemCheckWarning::SlowDisk {
+                write_speed: self.disk_write_speed,
+                required_speed,
+            });
+        }
+
+        warnings
+    }
+
+    pub fn will_drop_frames(&self) -> bool {
+        !self.warnings().is_empty()
+    }
+}
+
+/// Takes a few seconds, run it out of the UI thread
+pub fn system_check(
+    config: SystemCheckConfig,
+    mut screen_capturer: impl ScreenCapture,
+) -> Result<SystemCheckReport, RecorderError> {
+    let fps = config.fps.to_u32();
+
+    let capture_mean_time =
+        screen_capturer.capture_mean_time(&config.screen_name, config.capture_frames.max(1))?;
+    let (capture_threads, capture_fps) = match capture_mean_time {
+        None => (1, None),
+        Some(mean_time) => {
+            // The same count as `RecordingSession::start`, but the threads
+            // can't run faster than the cores
+            let interval_ms = 1000.0 / fps as f64;
+            let threads = ((mean_time.as_millis() as f64 / interval_ms).ceil() * 2.0).max(1.0);
+            let cores = thread::available_parallelism().map_or(1, |v| v.get()) as f64;
+            let fps = threads.min(cores) / mean_time.as_secs_f64().max(f64::EPSILON);
+
+            (threads as u32, Some(fps))
+        }
+    };
+
+    log::info!("capture mean time: {capture_mean_time:.2?}, threads: {capture_threads}");
+
+    let dimensions = config.resolution.dimensions(
+        config.screen_size.width as u32,
+        config.screen_size.height as u32,
+    );
+    let (encode_fps, encoded_bitrate) = encode_benchmark(dimensions, fps, config.encode_frames)?;
+    log::info!(
+        "encode {}x{}: {encode_fps:.2} fps, {:.2} KB/s",
+        dimensions.0,
+        dimensions.1,
+        encoded_bitrate / 1024.0
+    );
+
+    let disk_write_speed = disk_write_speed(&config.save_dir, config.disk_write_size)?;
+    log::info!(
+        "disk write speed: {:.2} MB/s",
+        disk_write_speed / 1024.0 / 1024.0
+    );
+
+    Ok(SystemCheckReport {
+        fps,
+        dimensions,
+        capture_mean_time,
+        capture_threads,
+        capture_fps,
+        encode_fps,
+        encoded_bitrate,
+        disk_write_speed,
+    })
+}
+
+// Returns the encoded frames per second and the bytes per second at `fps`
+fn encode_benchmark(
+    (width, height): (u32, u32),
+    fps: u32,
+    frames: u32,
+) -> Result<(f64, f64), RecorderError> {
+    let frames = frames.max(1);
+    let mut encoder = video_encoder::new(VideoEncoderConfig::new(width, height).with_fps(fps))?;
+
+    // Generated first, only the encoding is timed
+    let images = (0..frames)
+        .map(|i| benchmark_frame(width, height, i))
+        .collect::<Vec<_>>();
+
+    let total_bytes = Rc::new(Cell::new(0));
+    let start = Instant::now();
+
+    for image in images {
+        if let EncodedFrame::Frame((_, data)) = encoder.encode_frame(image)? {
+            total_bytes.set(total_bytes.get() + data.len());
+        }
+    }
+
+    // The delayed frames are encoded while flushing
+    let flushed_bytes = total_bytes.clone();
+    encoder.flush(Box::new(move |data| {
+        flushed_bytes.set(flushed_bytes.get() + data.len())
+    }))?;
+    let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    Ok((
+        frames as f64 / elapsed,
+        total_bytes.get() as f64 / frames as f64 * fps as f64,
+    ))
+}
+
+// A moving pattern with noisy bands, a still image is encoded much faster
+// than a real screen, while pure noise is much slower
+fn benchmark_frame(width: u32, height: u32, index: u32) -> ResizedImageBuffer {
+    let mut seed = index.wrapping_mul(2654435761) | 1;
+
+    ResizedImageBuffer::from_fn(width, height, |x, y| {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+
+        let noise = if (y / 64 + index).is_multiple_of(4) {
+            (seed & 0x1f) as u8
+        } else {
+            0
+        };
+        let shift = index * 8;
+        image::Rgb([
+            ((x + shift) % 256) as u8 ^ noise,
+            ((y + shift) % 256) as u8,
+            ((x + y) % 256) as u8 ^ noise,
+        ])
+    })
+}
+
+// Bytes per second, including the time to flush the data to the disk
+fn disk_write_speed(dir: &Path, size: usize) -> Result<f64, RecorderError> {
+    fs::create_dir_all(dir)?;
+
+    let path = dir.join(format!(".wayshot-system-check-{}", std::process::id()));
+    let chunk = vec![0xa5u8; DISK_CHUNK_SIZE];
+
+    let result = (|| {
+        let mut file = File::create(&path)?;
+        let start = Instant::now();
+
+        let mut written = 0;
+        while written < size.max(1) {
+            let len = DISK_CHUNK_SIZE.min(size.max(1) - written);
+            file.write_all(&chunk[..len])?;
+            written += len;
+        }
+
+        file.sync_all()?;
+        Ok::<_, std::io::Error>(written as f64 / start.elapsed().as_secs_f64().max(f64::EPSILON))
+    })();
+
+    if let Err(e) = fs::remove_file(&path) {
+        log::warn!("remove {} failed: {e}", path.display());
+    }
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> SystemCheckReport {
+        SystemCheckReport {
+            fps: 30,
+            dimensions: (1920, 1080),
+            capture_mean_time: Some(Duration::from_millis(20)),
+            capture_threads: 4,
+            capture_fps: Some(200.0),
+            encode_fps: 60.0,
+            encoded_bitrate: 1024.0 * 1024.0,
+            disk_write_speed: 100.0 * 1024.0 * 1024.0,
+        }
+    }
+
+    #[test]
+    fn test_report_without_warnings() {
+        assert!(report().warnings().is_empty());
+        assert!(!report().will_drop_frames());
+    }
+
+    #[test]
+    fn test_report_warnings() {
+        let report = SystemCheckReport {
+            capture_fps: Some(20.0),
+            encode_fps: 31.0,
+            disk_write_speed: 1024.0 * 1024.0,
+            ..report()
+        };
+
+        assert_eq!(
+            report.warnings(),
+            vec![
+                SystemCheckWarning::SlowCapture {
+                    attainable_fps: 20.0
+                },
+                SystemCheckWarning::SlowEncoder { encode_fps: 31.0 },
+                SystemCheckWarning::SlowDisk {
+                    write_speed: 1024.0 * 1024.0,
+                    required_speed: 1024.0 * 1024.0 * HEADROOM,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_capture_fps() {
+        let report = SystemCheckReport {
+            capture_mean_time: None,
+            capture_fps: None,
+            ..report()
+        };
+
+        assert!(report.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_disk_write_speed() {
+        let dir = std::env::temp_dir();
+        assert!(disk_write_speed(&dir, 3 * DISK_CHUNK_SIZE / 2).unwrap() > 0.0);
+        assert!(
+            !dir.join(format!(".wayshot-system-check-{}", std::process::id()))
+                .exists()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{cursor_tracker::TransitionType, error::RecorderError};
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender};
+use derive_setters::Setters;
+use screen_capture::{LogicalSize, Rectangle};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+#[derive(Clone, Setters)]
+#[setters(prefix = "with_")]
+pub struct WindowFollowerConfig {
+    /// The frame rate for transition animations
+    fps: u32,
+
+    /// The total screen dimensions (width and height) in pixels
+    screen_size: LogicalSize,
+
+    /// The minimum crop region size, so a small dialog isn't zoomed in too much
+    min_size: LogicalSize,
+
+    /// Channel for sending calculated crop regions to the recording system
+    crop_region_sender: Sender<Rectangle>,
+
+    /// Channel for receiving the region of the focused window, `None` if no
+    /// window is focused on the screen
+    window_region_receiver: Receiver<Option<Rectangle>>,
+
+    /// The time it takes to smoothly transition from the last window to the
+    /// next one
+    transition_duration: Duration,
+
+    /// Used when the next region is smaller than the current one
+    zoom_in_transition_type: TransitionType,
+
+    /// Used when the next region is larger than the current one
+    zoom_out_transition_type: TransitionType,
+
+    stop_sig: Arc<AtomicBool>,
+}
+
+impl WindowFollowerConfig {
+    pub fn new(
+        screen_size: LogicalSize,
+        min_size: LogicalSize,
+        crop_region_sender: Sender<Rectangle>,
+        window_region_receiver: Receiver<Option<Rectangle>>,
+        stop_sig: Arc<AtomicBool>,
+    ) -> Result<Self, RecorderError> {
+        assert!(screen_size.width > 0 && screen_size.height > 0);
+
+        Ok(Self {
+            fps: 25,
+            screen_size,
+            min_size,
+            crop_region_sender,
+            window_region_receiver,
+            transition_duration: Duration::from_millis(1000),
+            zoom_in_transition_type: TransitionType::EaseIn,
+            zoom_out_transition_type: TransitionType::EaseOut,
+            stop_sig,
+        })
+    }
+}
+
+/// Moves the crop region to the focused window. The region keeps the aspect
+/// ratio of the screen, so the frames aren't stretched when they're scaled
+/// to the video size.
+pub struct WindowFollower {
+    config: WindowFollowerConfig,
+    current_region: Rectangle,
+}
+
+impl WindowFollower {
+    pub fn new(config: WindowFollowerConfig) -> Self {
+        let current_region =
+            Rectangle::new(0, 0, config.screen_size.width, config.screen_size.height);
+
+        Self {
+            config,
+            current_region,
+        }
+    }
+
+    pub fn run(mut self) -> Result<(), RecorderError> {
+        if let Err(e) = self.config.crop_region_sender.try_send(self.current_region) {
+            return Err(RecorderError::CursorTrackerChannelError(format!(
+                "Failed to send initial crop region: {}",
+                e
+            )));
+        }
+
+        let frame_interval = Duration::from_secs_f64(1.0 / self.config.fps.max(1) as f64);
+        let mut target_region = self.current_region;
+
+        loop {
+            if self.config.stop_sig.load(Ordering::Relaxed) {
+                log::info!("Receive a stop signal, exit window follower...");
+                break;
+            }
+
+            match self
+                .config
+                .window_region_receiver
+                .recv_timeout(Duration::from_millis(100))
+            {
+                Ok(window_region) => target_region = self.fit_screen(window_region),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if target_region == self.current_region {
+                continue;
+            }
+
+            // A new window may be focused during the transition, which
+            // starts the next transition from where this one stops
+            for region in self.handle_transition(&target_region) {
+                if self.config.stop_sig.load(Ordering::Relaxed)
+                    || !self.config.window_region_receiver.is_empty()
+                {
+                    break;
+                }
+
+                if let Err(e) = self.config.crop_region_sender.try_send(region) {
+                    log::warn!("Failed to send window region: {e}");
+                }
+
+                self.current_region = region;
+                thread::sleep(frame_interval);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Expand the window region to the aspect ratio of the screen around its
+    // center, the whole screen if no window is focused
+    fn fit_screen(&self, window_region: Option<Rectangle>) -> Rectangle {
+        let screen_size = &self.config.screen_size;
+        let Some(region) = window_region else {
+            return Rectangle::new(0, 0, screen_size.width, screen_size.height);
+        };
+
+        let aspect_ratio = screen_size.width as f64 / screen_size.height as f64;
+        let mut width = (region.width.max(self.config.min_size.width) as f64)
+            .max(region.height.max(self.config.min_size.height) as f64 * aspect_ratio);
+        width = width.min(screen_size.width as f64);
+        let height = (width / aspect_ratio).min(screen_size.height as f64);
+
+        let center_x = region.x as f64 + region.width as f64 / 2.0;
+        let center_y = region.y as f64 + region.height as f64 / 2.0;
+        let x = (center_x - width / 2.0).clamp(0.0, screen_size.width as f64 - width);
+        let y = (center_y - height / 2.0).clamp(0.0, screen_size.height as f64 - height);
+
+        Rectangle::new(x as i32, y as i32, width as i32, height as i32)
+    }
+
+    fn handle_transition(&self, to_region: &Rectangle) -> Vec<Rectangle> {
+        let total_frames = (self.config.transition_duration.as_secs_f64() * self.config.fps as f64)
+            .ceil() as usize;
+        let mut regions = Vec::with_capacity(total_frames + 1);
+        let from_region = self.current_region;
+
+        let transition_type = if to_region.width < from_region.width {
+            self.config.zoom_in_transition_type
+        } else {
+            self.config.zoom_out_transition_type
+        };
+
+        let lerp = |from: i32, to: i32, progress: f64| {
+            (from as f64 + (to as f64 - from as f64) * progress) as i32
+        };
+
+        for frame in 1..=total_frames {
+            let progress = ((frame as f64) / (total_frames as f64)).min(1.0);
+            let eased_progress = transition_type.ease(progress);
+
+            regions.push(Rectangle::new(
+                lerp(from_region.x, to_region.x, eased_progress),
+                lerp(from_region.y, to_region.y, eased_progress),
+                lerp(from_region.width, to_region.width, eased_progress),
+                lerp(from_region.height, to_region.height, eased_progress),
+            ));
+        }
+
+        // Ensure final state is exactly the target region
+        if regions.last() != Some(to_region) {
+            regions.push(*to_region);
+        }
+
+        regions
+    }
+}
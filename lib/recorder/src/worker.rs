@@ -1,31 +1,70 @@
 use crate::{
-    CursorTracker, CursorTrackerConfig, Frame, FrameUser, RecorderError, RecordingSession,
-    ResizedImageBuffer, Resolution, SimpleFpsCounter, StatsUser,
+    CursorTracker, CursorTrackerConfig, Frame, FrameDropPolicy, FrameUser, RecorderError,
+    RecordingSession, ResizedImageBuffer, Resolution, SimpleFpsCounter, StatsUser, WindowFollower,
+    WindowFollowerConfig,
+    cursor_overlay::CursorOverlay,
+    dynamic_fps::FpsThrottle,
     process_mode::SHARE_SCREEN_CONNECTIONS_COUNT,
-    recorder::{CURSOR_CHANNEL_SIZE, CameraImage, ENCODER_WORKER_CHANNEL_SIZE, EncoderChannelData},
+    recorder::{
+        CURSOR_CHANNEL_SIZE, CameraImage, CollectChannelData, EncoderChannelData, FrameDropCounter,
+        ProcessedFrame,
+    },
+    scene_change::SceneChangeDetector,
 };
-use background_remover::BackgroundRemover;
-use camera::mix_images_rgb;
+use background_remover::{BackgroundRemover, TemporalConfig};
+use camera::{CameraEffect, apply_camera_effect, fill_background, mix_images_rgb};
 use crossbeam::channel::{Receiver, Sender, bounded};
 use fast_image_resize::images::Image;
 use image::{GrayImage, ImageBuffer, Rgb, Rgba, buffer::ConvertBuffer};
 use image_effect::realtime::RealtimeImageEffect;
 use once_cell::sync::Lazy;
 use screen_capture::{
-    Capture, LogicalSize, MonitorCursorPositionConfig, Position, Rectangle, ScreenCapture,
-    ScreenInfoError,
+    Capture, LogicalSize, MonitorActiveWindowConfig, MonitorCursorPositionConfig, Position,
+    Rectangle, ScreenCapture, ScreenInfo, ScreenInfoError,
 };
 use std::{
     collections::HashMap,
     path::PathBuf,
     sync::{
         Arc, Mutex,
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
     thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
+// Throttles the frames of the preview, so the full-size frames are only
+// copied at the preview rate
+struct PreviewSender {
+    sender: Sender<(StatsUser, Option<ResizedImageBuffer>)>,
+    interval: Duration,
+    enable: Arc<AtomicBool>,
+    last_sent: Option<Instant>,
+}
+
+impl PreviewSender {
+    fn send(&mut self, img: &ResizedImageBuffer, stats: impl FnOnce() -> StatsUser) {
+        if self
+            .last_sent
+            .is_some_and(|last_sent| last_sent.elapsed() < self.interval)
+        {
+            return;
+        }
+
+        // The preview worker is still busy with the last frame
+        if self.sender.is_full() {
+            return;
+        }
+
+        self.last_sent = Some(Instant::now());
+
+        let img = self.enable.load(Ordering::Relaxed).then(|| img.clone());
+        if let Err(e) = self.sender.try_send((stats(), img)) {
+            log::warn!("try send frame to preview channel failed: {e}");
+        }
+    }
+}
+
 static CURSOR_POSITION: AtomicU64 = AtomicU64::new(u64::MAX);
 static LAST_CROP_REGION: Lazy<Mutex<Option<Rectangle>>> = Lazy::new(|| Mutex::new(None));
 
@@ -36,10 +75,15 @@ impl RecordingSession {
     ) -> Vec<JoinHandle<()>> {
         let mut handles = vec![];
 
-        let (frame_sender, frame_receiver) = bounded(ENCODER_WORKER_CHANNEL_SIZE);
-        let (collect_sender, collect_receiver) = bounded(ENCODER_WORKER_CHANNEL_SIZE);
+        let channel_size = session.config.drop_policy().channel_size();
+        let (frame_sender, frame_receiver) = bounded(channel_size);
+        let (collect_sender, collect_receiver) = bounded(channel_size);
 
-        handles.push(Self::process_forward_worker(session, frame_sender));
+        handles.push(Self::process_forward_worker(
+            session,
+            frame_sender,
+            collect_sender.clone(),
+        ));
 
         // Base worker count + camera mix workers + image effect workers
         let mut worker_count = 3;
@@ -72,28 +116,57 @@ impl RecordingSession {
             ));
         }
 
+        // The preview is downscaled apart from the encoder, it may lag behind
+        let preview_sender = session.frame_sender_user.clone().map(|user_sender| {
+            let (preview_sender, preview_receiver) = bounded(1);
+            handles.push(Self::preview_worker(
+                preview_receiver,
+                user_sender,
+                session.config.preview_config.height,
+            ));
+
+            PreviewSender {
+                sender: preview_sender,
+                interval: session.config.preview_config.interval(),
+                enable: session.config.preview_config.enable.clone(),
+                last_sent: None,
+            }
+        });
+
         handles.push(Self::process_collect_worker(
             session,
             encoder_sender,
             collect_receiver,
+            preview_sender,
         ));
         handles
     }
 
+    // The skipped frames go straight to the collect worker as repeats of the
+    // previous frame
     fn process_forward_worker(
         session: &RecordingSession,
         sender: Sender<(u64, Frame, Option<CameraImage>)>,
+        collect_sender: Sender<CollectChannelData>,
     ) -> JoinHandle<()> {
         let start_time = session.start_time;
         let receiver = session.frame_receiver.clone();
-        let loss_frame_count = session.loss_frame_count.clone();
+        let drop_policy = session.config.drop_policy();
+        let frame_drops = session.frame_drops.clone();
         let total_frame_count = session.total_frame_count.clone();
+        let fps_divisor = session.fps_divisor.clone();
+        let pause = session.pause.clone();
         let enable_camera_mix = session.config.camera_mix_config.enable;
         let camera_image_receiver = session.camera_image_receiver.clone();
         let mut last_camera_image: Option<CameraImage> = None;
 
         thread::spawn(move || {
             while let Ok(frame) = receiver.recv() {
+                // Dropped before it's counted, so the indexes have no gap
+                if pause.is_paused() {
+                    continue;
+                }
+
                 let total_frame_count = total_frame_count.fetch_add(1, Ordering::Relaxed) + 1;
 
                 log::debug!(
@@ -107,6 +180,23 @@ impl RecordingSession {
                     receiver.capacity().unwrap_or_default() - receiver.len()
                 );
 
+                if !total_frame_count
+                    .is_multiple_of(fps_divisor.load(Ordering::Relaxed).max(1) as u64)
+                {
+                    let data = CollectChannelData {
+                        thread_index: None,
+                        timestamp: frame.timestamp,
+                        total_frame_index: total_frame_count,
+                        frame: ProcessedFrame::Repeat,
+                    };
+
+                    if let Err(e) = collect_sender.send_timeout(data, drop_policy.send_timeout()) {
+                        frame_drops.collect.fetch_add(1, Ordering::Relaxed);
+                        log::warn!("forward worker try send skipped frame failed: {e}");
+                    }
+                    continue;
+                }
+
                 let camera_img = if enable_camera_mix {
                     if let Some(ref receiver) = camera_image_receiver
                         && let Ok(img) = receiver.try_recv()
@@ -118,8 +208,11 @@ impl RecordingSession {
                     None
                 };
 
-                if let Err(e) = sender.try_send((total_frame_count, frame, camera_img)) {
-                    loss_frame_count.fetch_add(1, Ordering::Relaxed);
+                if let Err(e) = sender.send_timeout(
+                    (total_frame_count, frame, camera_img),
+                    drop_policy.send_timeout(),
+                ) {
+                    frame_drops.process.fetch_add(1, Ordering::Relaxed);
                     log::warn!("process worker try send failed: {e}");
                 }
             }
@@ -131,62 +224,96 @@ impl RecordingSession {
     fn process_collect_worker(
         session: &RecordingSession,
         sender: Sender<EncoderChannelData>,
-        receiver: Receiver<(usize, Instant, EncoderChannelData)>,
+        receiver: Receiver<CollectChannelData>,
+        mut preview_sender: Option<PreviewSender>,
     ) -> JoinHandle<()> {
         let total_frame_count = session.total_frame_count.clone();
-        let loss_frame_count = session.loss_frame_count.clone();
-        let frame_sender_user = session.frame_sender_user.clone();
+        let drop_policy = session.config.drop_policy();
+        let frame_drops = session.frame_drops.clone();
+        let mut fps_throttle = FpsThrottle::new(
+            session.config.dynamic_fps_config.clone(),
+            session.config.fps.to_u32(),
+            session.fps_divisor.clone(),
+        );
+
+        // Force a keyframe at most once per second
+        let mut scene_change_detector = session
+            .config
+            .enable_scene_change_detection
+            .then(|| SceneChangeDetector::new(session.config.fps.to_u32() as u64));
 
         thread::spawn(move || {
             let mut expect_total_frame_index = 1;
             let mut disorder_frame_counts = 0;
-            let mut frame_cache: HashMap<u64, (u64, ResizedImageBuffer, Option<CameraImage>)> =
-                HashMap::new();
+            let mut frame_cache: HashMap<u64, ProcessedFrame> = HashMap::new();
             let mut fps_counter = SimpleFpsCounter::new();
 
-            while let Ok((thread_index, frame_timestamp, (total_frame_index, img, _camera_img))) =
-                receiver.recv()
+            // Frames are in order here, so it's the place to compare consecutive frames
+            let mut send_frame = |frame: ProcessedFrame, total_frame_index: u64, fps: f32| {
+                let force_keyframe = match (&frame, scene_change_detector.as_mut()) {
+                    (ProcessedFrame::Image(img), Some(detector)) => detector.is_scene_change(img),
+                    _ => false,
+                };
+
+                if force_keyframe {
+                    log::debug!("scene change at frame[{total_frame_index}]");
+                }
+
+                Self::send_frame_to_encoder(
+                    frame,
+                    force_keyframe,
+                    &sender,
+                    &mut preview_sender,
+                    total_frame_index,
+                    drop_policy,
+                    &total_frame_count,
+                    &frame_drops,
+                    fps,
+                    fps_throttle.effective_fps(),
+                );
+
+                if let Some(effective_fps) = fps_throttle.update(
+                    sender.len(),
+                    sender.capacity().unwrap_or_default(),
+                    Instant::now(),
+                ) {
+                    log::warn!("encoder channel is busy for long, effective fps: {effective_fps}");
+                }
+            };
+
+            while let Ok(CollectChannelData {
+                thread_index,
+                timestamp: frame_timestamp,
+                total_frame_index,
+                frame,
+            }) = receiver.recv()
             {
                 // FIXME: no accuracy. because frame_timestamp may be disorder
-                let fps = fps_counter.add_frame(frame_timestamp);
+                // The repeated frames aren't counted
+                let fps = match frame {
+                    ProcessedFrame::Image(_) => fps_counter.add_frame(frame_timestamp),
+                    ProcessedFrame::Repeat => fps_counter.fps,
+                };
 
                 if expect_total_frame_index == total_frame_index {
                     disorder_frame_counts = 0;
 
-                    Self::send_frame_to_encoder(
-                        img,
-                        &sender,
-                        &frame_sender_user,
-                        expect_total_frame_index,
-                        total_frame_count.clone(),
-                        loss_frame_count.clone(),
-                        fps,
-                    );
+                    send_frame(frame, expect_total_frame_index, fps);
 
                     loop {
                         expect_total_frame_index += 1;
                         match frame_cache.remove(&expect_total_frame_index) {
-                            Some(frame) => {
-                                Self::send_frame_to_encoder(
-                                    frame.1,
-                                    &sender,
-                                    &frame_sender_user,
-                                    expect_total_frame_index,
-                                    total_frame_count.clone(),
-                                    loss_frame_count.clone(),
-                                    fps,
-                                );
-                            }
+                            Some(frame) => send_frame(frame, expect_total_frame_index, fps),
                             _ => break,
                         }
                     }
                 } else if expect_total_frame_index > total_frame_index {
-                    loss_frame_count.fetch_add(1, Ordering::Relaxed);
+                    frame_drops.collect.fetch_add(1, Ordering::Relaxed);
                     log::warn!(
-                        "too late thread[{thread_index}] frame, frame index: {total_frame_index}, expected index: {expect_total_frame_index}"
+                        "too late thread[{thread_index:?}] frame, frame index: {total_frame_index}, expected index: {expect_total_frame_index}"
                     );
                 } else {
-                    frame_cache.insert(total_frame_index, (total_frame_index, img, _camera_img));
+                    frame_cache.insert(total_frame_index, frame);
                     disorder_frame_counts += 1;
 
                     if disorder_frame_counts > 5 {
@@ -200,17 +327,7 @@ impl RecordingSession {
                         loop {
                             expect_total_frame_index += 1;
                             match frame_cache.remove(&expect_total_frame_index) {
-                                Some(frame) => {
-                                    Self::send_frame_to_encoder(
-                                        frame.1,
-                                        &sender,
-                                        &frame_sender_user,
-                                        expect_total_frame_index,
-                                        total_frame_count.clone(),
-                                        loss_frame_count.clone(),
-                                        fps,
-                                    );
-                                }
+                                Some(frame) => send_frame(frame, expect_total_frame_index, fps),
                                 _ => break,
                             }
                         }
@@ -224,36 +341,61 @@ impl RecordingSession {
 
     fn process_frame_worker(
         session: &RecordingSession,
-        sender: Sender<(usize, Instant, EncoderChannelData)>,
+        sender: Sender<CollectChannelData>,
         receiver: Receiver<(u64, Frame, Option<CameraImage>)>,
         thread_index: usize,
     ) -> JoinHandle<()> {
         let resolution = session.config.resolution.clone();
-        let loss_frame_count = session.loss_frame_count.clone();
+        let drop_policy = session.config.drop_policy();
+        let frame_drops = session.frame_drops.clone();
         let enable_cursor_tracking = session.config.enable_cursor_tracking;
+        let enable_window_following = session.config.enable_window_following;
+        let capture_region = session.config.fixed_capture_region();
         let crop_region_receiver = session.crop_region_receiver.clone();
+        let cursor_overlay = session.cursor_overlay.clone();
+        let caption_overlay = session.caption_overlay.clone();
         let enable_camera_mix = session.config.camera_mix_config.enable;
         let camera_shape = session.config.camera_mix_config.shape.clone();
         let realtime_image_effect = session.config.realtime_image_effect.clone();
         let camera_background_mask = session.camera_background_mask.clone();
+        let camera_effect = session.config.camera_mix_config.camera_effect.clone();
 
         thread::spawn(move || {
-            while let Ok((total_frame_count, frame, camera_img)) = receiver.recv() {
+            // The replacement background scaled to the camera frame size
+            let mut camera_effect_background = None;
+
+            while let Ok((total_frame_count, mut frame, camera_img)) = receiver.recv() {
                 let now = Instant::now();
                 let frame_timestamp = frame.timestamp;
 
-                let img = if enable_cursor_tracking {
-                    match Self::crop_and_resize_frame(
-                        frame,
-                        resolution,
-                        crop_region_receiver.clone().unwrap(),
-                    ) {
+                // Drawn before cropping, so it's scaled with the screen
+                if let Some(ref cursor_overlay) = cursor_overlay {
+                    cursor_overlay.draw(&mut frame.cb_data.data);
+                }
+
+                let img = if enable_cursor_tracking || enable_window_following {
+                    let crop_region_receiver = crop_region_receiver.clone().unwrap();
+                    let region = if enable_cursor_tracking {
+                        Self::get_matched_crop_region(crop_region_receiver)
+                    } else {
+                        Self::get_latest_crop_region(crop_region_receiver)
+                    };
+
+                    match Self::crop_and_resize_frame(frame, resolution, region) {
                         Ok(img) => img,
                         Err(e) => {
                             log::warn!("crop and resize frame failed: {e}");
                             continue;
                         }
                     }
+                } else if let Some(region) = capture_region {
+                    match Self::crop_frame(frame, resolution, region) {
+                        Ok(img) => img,
+                        Err(e) => {
+                            log::warn!("crop frame failed: {e}");
+                            continue;
+                        }
+                    }
                 } else {
                     match Self::resize_frame(frame, resolution) {
                         Ok(img) => img,
@@ -273,21 +415,35 @@ impl RecordingSession {
                     img
                 };
 
-                let img = if enable_camera_mix {
+                let mut img = if enable_camera_mix {
                     let mask = camera_background_mask.lock().unwrap().clone();
+                    let (camera_img, mask) = Self::apply_camera_effect(
+                        camera_img,
+                        mask,
+                        &camera_effect,
+                        &mut camera_effect_background,
+                    );
                     Self::mix_screen_and_camera(img, camera_img, &camera_shape, mask)
                 } else {
                     img
                 };
 
+                // Drawn last, so the camera doesn't cover the captions
+                if let Some(ref caption_overlay) = caption_overlay {
+                    caption_overlay.draw(&mut img);
+                }
+
                 log::debug!("process frame spent: {:.2?}", now.elapsed());
 
-                if let Err(e) = sender.try_send((
-                    thread_index,
-                    frame_timestamp,
-                    (total_frame_count, img, None),
-                )) {
-                    loss_frame_count.fetch_add(1, Ordering::Relaxed);
+                let data = CollectChannelData {
+                    thread_index: Some(thread_index),
+                    timestamp: frame_timestamp,
+                    total_frame_index: total_frame_count,
+                    frame: ProcessedFrame::Image(img),
+                };
+
+                if let Err(e) = sender.send_timeout(data, drop_policy.send_timeout()) {
+                    frame_drops.collect.fetch_add(1, Ordering::Relaxed);
                     log::warn!("process worker try send failed: {e}");
                 }
             }
@@ -301,16 +457,7 @@ impl RecordingSession {
         crop_region_sender: Sender<Rectangle>,
     ) -> Result<(), RecorderError> {
         let stop_sig = self.stop_sig.clone();
-        let screen_name = self.config.screen_name.clone();
-
-        let screen_info = screen_capturer
-            .available_screens()?
-            .iter()
-            .find(|item| item.name == screen_name)
-            .ok_or(RecorderError::ScreenInfoFailed(ScreenInfoError::Other(
-                format!("No found screen in cursor monitor thread {screen_name}"),
-            )))?
-            .clone();
+        let screen_info = self.recording_screen_info(&mut screen_capturer)?;
 
         let (cursor_sender, cursor_receiver) = bounded(CURSOR_CHANNEL_SIZE);
         let target_size = LogicalSize::new(
@@ -358,17 +505,10 @@ impl RecordingSession {
 
         let stable_radius = self.config.stable_radius;
         let cursor_monitor_stop_sig = stop_sig.clone();
+        let cursor_overlay = self.cursor_overlay.clone();
         thread::spawn(move || {
             CURSOR_POSITION.store(u64::MAX, Ordering::SeqCst);
-
-            {
-                *LAST_CROP_REGION.lock().unwrap() = Some(Rectangle::new(
-                    0,
-                    0,
-                    screen_info.logical_size.width,
-                    screen_info.logical_size.height,
-                ));
-            }
+            Self::reset_last_crop_region(&screen_info);
 
             let config = MonitorCursorPositionConfig::new(screen_info, cursor_monitor_stop_sig)
                 .with_use_transparent_layer_surface(true)
@@ -379,6 +519,10 @@ impl RecordingSession {
                     (((position.x as u64) << 32) & 0xffff_ffff_0000_0000) | (position.y as u64);
                 CURSOR_POSITION.store(current_position, Ordering::Relaxed);
 
+                if let Some(ref cursor_overlay) = cursor_overlay {
+                    cursor_overlay.update(Position::new(position.x, position.y));
+                }
+
                 log::debug!(
                     "dimensions: {}x{} at ({}, {}). (x, y) = ({}, {})",
                     position.output_width,
@@ -402,6 +546,117 @@ impl RecordingSession {
         Ok(())
     }
 
+    pub(crate) fn window_follower_worker(
+        &mut self,
+        mut screen_capturer: impl ScreenCapture + Clone + Send + 'static,
+        crop_region_sender: Sender<Rectangle>,
+    ) -> Result<(), RecorderError> {
+        let stop_sig = self.stop_sig.clone();
+        let screen_info = self.recording_screen_info(&mut screen_capturer)?;
+        Self::reset_last_crop_region(&screen_info);
+
+        let (window_region_sender, window_region_receiver) = bounded(CURSOR_CHANNEL_SIZE);
+        let min_size = LogicalSize::new(
+            self.config.region_width.max(1),
+            self.config.region_height.max(1),
+        );
+
+        let window_follower_config = WindowFollowerConfig::new(
+            screen_info.logical_size,
+            min_size,
+            crop_region_sender,
+            window_region_receiver,
+            stop_sig.clone(),
+        )?
+        .with_fps(self.config.fps.to_u32())
+        .with_zoom_in_transition_type(self.config.zoom_in_transition_type)
+        .with_zoom_out_transition_type(self.config.zoom_out_transition_type)
+        .with_transition_duration(Duration::from_millis(self.config.zoom_transition_duration));
+
+        thread::spawn(move || {
+            if let Err(e) = WindowFollower::new(window_follower_config).run() {
+                log::error!("Run window follower failed: {e}");
+            }
+
+            log::info!("Exit window follower thread");
+        });
+
+        thread::spawn(move || {
+            let config = MonitorActiveWindowConfig::new(screen_info, stop_sig);
+
+            if let Err(e) = screen_capturer.monitor_active_window(config, move |region| {
+                if let Err(e) = window_region_sender.try_send(region) {
+                    log::warn!("window region sender failed: {e}");
+                }
+            }) {
+                log::error!("monitor active window failed: {e}");
+            }
+
+            log::info!("Exit monitor active window thread");
+        });
+
+        Ok(())
+    }
+
+    // The cursor tracker shares its cursor positions, so they're only
+    // monitored here without it
+    pub(crate) fn cursor_overlay_worker(
+        &mut self,
+        mut screen_capturer: impl ScreenCapture + Clone + Send + 'static,
+    ) -> Result<(), RecorderError> {
+        let screen_info = self.recording_screen_info(&mut screen_capturer)?;
+        let cursor_overlay = Arc::new(CursorOverlay::new(
+            self.config.cursor_style.clone(),
+            screen_info.scale_factor,
+        ));
+        self.cursor_overlay = Some(cursor_overlay.clone());
+
+        if self.config.enable_cursor_tracking {
+            return Ok(());
+        }
+
+        let stop_sig = self.stop_sig.clone();
+        thread::spawn(move || {
+            let config = MonitorCursorPositionConfig::new(screen_info, stop_sig)
+                .with_use_transparent_layer_surface(true);
+
+            if let Err(e) = screen_capturer.monitor_cursor_position(config, move |position| {
+                cursor_overlay.update(Position::new(position.x, position.y));
+            }) {
+                log::error!("monitor cursor position for the cursor overlay failed: {e}");
+            }
+
+            log::info!("Exit cursor overlay thread");
+        });
+
+        Ok(())
+    }
+
+    fn recording_screen_info(
+        &self,
+        screen_capturer: &mut impl ScreenCapture,
+    ) -> Result<ScreenInfo, RecorderError> {
+        let screen_name = &self.config.screen_name;
+
+        Ok(screen_capturer
+            .available_screens()?
+            .iter()
+            .find(|item| &item.name == screen_name)
+            .ok_or(RecorderError::ScreenInfoFailed(ScreenInfoError::Other(
+                format!("No found screen {screen_name}"),
+            )))?
+            .clone())
+    }
+
+    fn reset_last_crop_region(screen_info: &ScreenInfo) {
+        *LAST_CROP_REGION.lock().unwrap() = Some(Rectangle::new(
+            0,
+            0,
+            screen_info.logical_size.width,
+            screen_info.logical_size.height,
+        ));
+    }
+
     pub(crate) fn background_remover_worker(
         &mut self,
         model_path: PathBuf,
@@ -430,13 +685,19 @@ impl RecordingSession {
         let stop_sig = self.stop_sig.clone();
         let mask_cache = self.camera_background_mask.clone();
         let waiting_frame = self.camera_background_remover_waiting_frame.clone();
+        remover.set_temporal_config(
+            TemporalConfig::default().with_smoothing(self.config.camera_mix_config.mask_smoothing),
+        );
 
+        // The model is too slow for every camera frame, it takes the next frame
+        // when it's idle and the latest smoothed mask is reused in between.
+        // The frames are still in order, so the video matting mode applies
         thread::spawn(move || {
             while !stop_sig.load(Ordering::Relaxed) {
                 if let Ok(camera_img) =
                     camera_image_receiver.recv_timeout(Duration::from_millis(100))
                 {
-                    match remover.get_mask(&camera_img) {
+                    match remover.get_frame_mask(&camera_img) {
                         Ok(mask) => *mask_cache.lock().unwrap() = Some(mask),
                         Err(e) => log::warn!("Failed to generate background mask: {e}"),
                     }
@@ -453,10 +714,8 @@ impl RecordingSession {
     fn crop_and_resize_frame(
         frame: Frame,
         resolution: Resolution,
-        crop_region_receiver: Receiver<Rectangle>,
+        region: Rectangle,
     ) -> Result<ResizedImageBuffer, RecorderError> {
-        let region = Self::get_matched_crop_region(crop_region_receiver);
-
         log::debug!("crop region: {:?}", region);
 
         if matches!(resolution, Resolution::Original(_))
@@ -482,6 +741,16 @@ impl RecordingSession {
         Self::resize_image(frame.cb_data.data, target_size, Some(region))
     }
 
+    // Unlike the cursor tracking, the output is scaled from the region size
+    fn crop_frame(
+        frame: Frame,
+        resolution: Resolution,
+        region: Rectangle,
+    ) -> Result<ResizedImageBuffer, RecorderError> {
+        let target_size = resolution.dimensions(region.width as u32, region.height as u32);
+        Self::resize_image(frame.cb_data.data, target_size, Some(region))
+    }
+
     fn resize_frame(
         frame: Frame,
         resolution: Resolution,
@@ -578,38 +847,110 @@ impl RecordingSession {
     }
 
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     fn send_frame_to_encoder(
-        img: ResizedImageBuffer,
+        frame: ProcessedFrame,
+        force_keyframe: bool,
         encoder_sender: &Sender<EncoderChannelData>,
-        frame_sender_user: &Option<Sender<FrameUser>>,
+        preview_sender: &mut Option<PreviewSender>,
         expect_total_frame_index: u64,
-        total_frame_count: Arc<AtomicU64>,
-        loss_frame_count: Arc<AtomicU64>,
+        drop_policy: FrameDropPolicy,
+        total_frame_count: &AtomicU64,
+        frame_drops: &FrameDropCounter,
         fps: f32,
+        effective_fps: u32,
     ) {
-        if let Some(sender) = frame_sender_user {
-            let frame_user = FrameUser {
-                stats: StatsUser {
+        if let Some(preview_sender) = preview_sender
+            && let ProcessedFrame::Image(ref img) = frame
+        {
+            preview_sender.send(img, || {
+                let dropped_frames = frame_drops.stats();
+                StatsUser {
                     fps,
                     total_frames: total_frame_count.load(Ordering::Relaxed),
-                    loss_frames: loss_frame_count.load(Ordering::Relaxed),
+                    loss_frames: dropped_frames.total(),
+                    dropped_frames,
+                    effective_fps,
                     share_screen_connections: SHARE_SCREEN_CONNECTIONS_COUNT
                         .load(Ordering::Relaxed),
-                },
-                buffer: img.clone(),
-            };
-
-            if let Err(e) = sender.try_send(frame_user) {
-                log::warn!("try send frame to user frame channel failed: {e}");
-            }
+                }
+            });
         }
 
-        if let Err(e) = encoder_sender.try_send((expect_total_frame_index, img, None)) {
-            loss_frame_count.fetch_add(1, Ordering::Relaxed);
+        let data = EncoderChannelData {
+            total_frame_index: expect_total_frame_index,
+            frame,
+            force_keyframe,
+        };
+
+        if let Err(e) = encoder_sender.send_timeout(data, drop_policy.send_timeout()) {
+            frame_drops.encode.fetch_add(1, Ordering::Relaxed);
             log::warn!("collected thread try send to encoder reciever failed: {e}");
         }
     }
 
+    fn preview_worker(
+        receiver: Receiver<(StatsUser, Option<ResizedImageBuffer>)>,
+        user_sender: Sender<FrameUser>,
+        height: u32,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while let Ok((stats, img)) = receiver.recv() {
+                let buffer = match img {
+                    Some(img) => Self::downscale_preview(img, height).unwrap_or_else(|e| {
+                        log::warn!("downscale preview failed: {e}");
+                        ImageBuffer::new(0, 0)
+                    }),
+                    None => ImageBuffer::new(0, 0),
+                };
+
+                if let Err(e) = user_sender.try_send(FrameUser { stats, buffer }) {
+                    log::warn!("try send frame to user frame channel failed: {e}");
+                }
+            }
+
+            log::info!("preview thread exit");
+        })
+    }
+
+    fn downscale_preview(
+        img: ResizedImageBuffer,
+        height: u32,
+    ) -> Result<ResizedImageBuffer, RecorderError> {
+        let (src_width, src_height) = img.dimensions();
+        if height == 0 || src_height <= height {
+            return Ok(img);
+        }
+
+        let dst_width = ((src_width as u64 * height as u64 / src_height as u64) as u32).max(1);
+        let src_image = Image::from_vec_u8(
+            src_width,
+            src_height,
+            img.into_raw(),
+            fast_image_resize::PixelType::U8x3,
+        )
+        .map_err(|e| {
+            RecorderError::ImageProcessingFailed(format!("Failed to create source image: {}", e))
+        })?;
+
+        let mut dst_image = Image::new(dst_width, height, fast_image_resize::PixelType::U8x3);
+
+        // Bilinear is good enough for a small preview and much cheaper than Lanczos
+        let resize_options = fast_image_resize::ResizeOptions::new().resize_alg(
+            fast_image_resize::ResizeAlg::Convolution(fast_image_resize::FilterType::Bilinear),
+        );
+
+        fast_image_resize::Resizer::new()
+            .resize(&src_image, &mut dst_image, &resize_options)
+            .map_err(|e| RecorderError::ImageProcessingFailed(format!("Resize failed: {}", e)))?;
+
+        ImageBuffer::from_raw(dst_width, height, dst_image.into_vec()).ok_or_else(|| {
+            RecorderError::ImageProcessingFailed(
+                "Failed to create preview image buffer".to_string(),
+            )
+        })
+    }
+
     fn apply_realtime_image_effect(
         rgb_image: ResizedImageBuffer,
         effect: RealtimeImageEffect,
@@ -633,6 +974,36 @@ impl RecordingSession {
         }
     }
 
+    // Composite the camera image over its blurred or replaced background,
+    // the mask is consumed so the whole camera image is mixed
+    fn apply_camera_effect(
+        camera_img: Option<CameraImage>,
+        mask: Option<GrayImage>,
+        effect: &CameraEffect,
+        background: &mut Option<CameraImage>,
+    ) -> (Option<CameraImage>, Option<GrayImage>) {
+        match (camera_img, mask) {
+            (Some(camera_img), Some(mask)) if !effect.is_none() => {
+                if let CameraEffect::BackgroundReplace(image) = effect
+                    && background
+                        .as_ref()
+                        .is_none_or(|bg| bg.dimensions() != camera_img.dimensions())
+                {
+                    *background = Some(fill_background(
+                        image,
+                        camera_img.width(),
+                        camera_img.height(),
+                    ));
+                }
+
+                let camera_img =
+                    apply_camera_effect(camera_img, &mask, effect, background.as_ref());
+                (Some(camera_img), None)
+            }
+            (camera_img, mask) => (camera_img, mask),
+        }
+    }
+
     fn mix_screen_and_camera(
         screen_image: ResizedImageBuffer,
         camera_img: Option<CameraImage>,
@@ -680,4 +1051,15 @@ impl RecordingSession {
             };
         }
     }
+
+    // The window regions don't depend on the cursor, so they're taken in order
+    fn get_latest_crop_region(crop_region_receiver: Receiver<Rectangle>) -> Rectangle {
+        match crop_region_receiver.try_recv() {
+            Ok(v) => {
+                *LAST_CROP_REGION.lock().unwrap() = Some(v);
+                v
+            }
+            _ => LAST_CROP_REGION.lock().unwrap().clone().unwrap(),
+        }
+    }
 }
@@ -1,31 +1,27 @@
-use crate::Result;
+use crate::{Error, Result};
+use cursor_client::{Client, Message};
 use screen_capture::{CursorPosition, MonitorCursorPositionConfig};
-use std::{
-    sync::atomic::Ordering,
-    {io::Read, os::unix::net::UnixStream, time::Duration},
-};
+use std::{sync::atomic::Ordering, time::Duration};
 
 pub fn monitor_cursor_position(
     config: MonitorCursorPositionConfig,
     mut callback: impl FnMut(CursorPosition) + Send + 'static,
 ) -> Result<()> {
-    let socket_path = "/tmp/wayshot-cursor.sock";
-
     loop {
         if config.stop_sig.load(Ordering::Relaxed) {
             log::info!("exit monitor cursor thread...");
             break;
         }
 
-        match UnixStream::connect(socket_path) {
-            Ok(mut stream) => {
+        match Client::connect() {
+            Ok(mut client) => {
                 log::info!("Connected to server process");
 
-                if let Err(e) = process_mouse_positions(&mut stream, &config, &mut callback) {
+                if let Err(e) = process_mouse_positions(&mut client, &config, &mut callback) {
                     log::warn!("process mouse positions failed: {e}");
                 }
             }
-            Err(e) => log::warn!("UnixStream connect `{socket_path}` failed: {e}"),
+            Err(e) => log::warn!("connect `{}` failed: {e}", cursor_client::SOCKET_PATH),
         }
 
         std::thread::sleep(Duration::from_secs(3));
@@ -34,8 +30,9 @@ pub fn monitor_cursor_position(
     Ok(())
 }
 
+// The heartbeats wake the loop up to check `stop_sig` while the cursor stays still
 fn process_mouse_positions(
-    stream: &mut UnixStream,
+    client: &mut Client,
     config: &MonitorCursorPositionConfig,
     callback: &mut (impl FnMut(CursorPosition) + Send + 'static),
 ) -> Result<()> {
@@ -44,9 +41,13 @@ fn process_mouse_positions(
             break;
         }
 
-        let pos = receive_position(stream)?;
-        let x = ((pos >> 32) & 0x0000_0000_ffff_ffff) as i32;
-        let y = (pos & 0x0000_0000_ffff_ffff) as i32;
+        let message = client
+            .recv()
+            .map_err(|e| Error::CursorError(e.to_string()))?;
+
+        let Message::Cursor { x, y } = message else {
+            continue;
+        };
 
         log::debug!("Received mouse position: ({}, {})", x, y);
 
@@ -60,16 +61,7 @@ fn process_mouse_positions(
         };
 
         callback(position);
-
-        std::thread::sleep(Duration::from_millis(5));
     }
 
     Ok(())
 }
-
-fn receive_position(stream: &mut UnixStream) -> Result<u64> {
-    let mut buffer = [0u8; 8];
-    stream.read_exact(&mut buffer)?;
-    let value = u64::from_ne_bytes(buffer);
-    Ok(value)
-}
@@ -47,4 +47,14 @@ impl screen_capture::ScreenCapture for ScreenCaptureWaylandPortal {
         cursor::monitor_cursor_position(config, callback)
             .map_err(|e| screen_capture::CursorError::ConnectionFailed(e.to_string()))
     }
+
+    fn monitor_active_window(
+        &mut self,
+        _config: screen_capture::MonitorActiveWindowConfig,
+        _callback: impl FnMut(Option<screen_capture::Rectangle>) + Send + 'static,
+    ) -> std::result::Result<(), screen_capture::ActiveWindowError> {
+        Err(screen_capture::ActiveWindowError::Unimplemented(
+            "The desktop portal doesn't expose the focused window".to_string(),
+        ))
+    }
 }
@@ -0,0 +1,310 @@
+//! Focused window of the `wlr-foreign-toplevel-management` protocol.
+//!
+//! The protocol tells which window is focused but not where it's, so the
+//! geometry is queried from the IPC of sway or Hyprland. The whole output is
+//! followed on the other compositors.
+
+use screen_capture::{ActiveWindowError, MonitorActiveWindowConfig, Rectangle, ScreenInfo};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, backend::ObjectId, event_created_child,
+    protocol::wl_registry,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1, zwlr_foreign_toplevel_manager_v1,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The windows are moved and resized without a toplevel event
+const GEOMETRY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const IPC_TIMEOUT: Duration = Duration::from_millis(200);
+
+const SWAY_IPC_MAGIC: &[u8] = b"i3-ipc";
+const SWAY_IPC_GET_TREE: u32 = 4;
+
+#[derive(Default)]
+struct Toplevel {
+    activated: bool,
+    pending_activated: bool,
+}
+
+#[derive(Default)]
+struct ActiveWindowState {
+    manager: Option<zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1>,
+    toplevels: HashMap<ObjectId, Toplevel>,
+
+    // Another window is focused since the last geometry query
+    focus_changed: bool,
+}
+
+pub fn monitor_active_window(
+    config: MonitorActiveWindowConfig,
+    mut callback: impl FnMut(Option<Rectangle>) + Send + 'static,
+) -> Result<(), ActiveWindowError> {
+    let conn = Connection::connect_to_env()
+        .map_err(|e| ActiveWindowError::ConnectionFailed(e.to_string()))?;
+    let mut queue = conn.new_event_queue();
+    let qh = queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+
+    let mut state = ActiveWindowState::default();
+    queue
+        .roundtrip(&mut state)
+        .map_err(|e| ActiveWindowError::ConnectionFailed(e.to_string()))?;
+
+    if state.manager.is_none() {
+        return Err(ActiveWindowError::ProtocolNotAvailable(
+            "zwlr_foreign_toplevel_manager_v1".to_string(),
+        ));
+    }
+
+    if env::var_os("SWAYSOCK").is_none() && env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_none() {
+        log::warn!("No sway or Hyprland IPC for the window geometry, the whole output is followed");
+    }
+
+    let mut last_region = None;
+    let mut last_query_time = None::<Instant>;
+
+    loop {
+        if config.stop_sig.load(Ordering::Relaxed) {
+            break;
+        }
+
+        if let Err(e) = queue.roundtrip(&mut state) {
+            log::warn!("Roundtrip error: {e}");
+        }
+
+        if state.focus_changed
+            || last_query_time.is_none_or(|t| t.elapsed() >= GEOMETRY_POLL_INTERVAL)
+        {
+            state.focus_changed = false;
+            last_query_time = Some(Instant::now());
+
+            let region = if state.toplevels.values().any(|t| t.activated) {
+                focused_window_region(&config.screen_info)
+            } else {
+                None
+            };
+
+            if last_region != Some(region) {
+                log::debug!("active window region: {region:?}");
+                last_region = Some(region);
+                callback(region);
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    log::info!("exit monitor_active_window thread...");
+    Ok(())
+}
+
+fn focused_window_region(screen_info: &ScreenInfo) -> Option<Rectangle> {
+    let whole_output = Rectangle::new(
+        0,
+        0,
+        screen_info.logical_size.width,
+        screen_info.logical_size.height,
+    );
+
+    match sway_focused_window().or_else(hyprland_active_window) {
+        Some(geometry) => geometry.and_then(|geometry| to_output_region(geometry, screen_info)),
+        None => Some(whole_output),
+    }
+}
+
+// From the layout coordinates of the compositor to the pixels of the output,
+// `None` if the window isn't on the output
+fn to_output_region(geometry: Rectangle, screen_info: &ScreenInfo) -> Option<Rectangle> {
+    let scale = screen_info.scale_factor;
+    let to_pixels = |v: i32, origin: i32| ((v - origin) as f32 * scale).round() as i32;
+
+    let left = to_pixels(geometry.x, screen_info.position.x).max(0);
+    let top = to_pixels(geometry.y, screen_info.position.y).max(0);
+    let right = to_pixels(geometry.x + geometry.width, screen_info.position.x)
+        .min(screen_info.logical_size.width);
+    let bottom = to_pixels(geometry.y + geometry.height, screen_info.position.y)
+        .min(screen_info.logical_size.height);
+
+    (right > left && bottom > top).then(|| Rectangle::new(left, top, right - left, bottom - top))
+}
+
+// `None` if the IPC isn't available, `Some(None)` if no window is focused
+fn sway_focused_window() -> Option<Option<Rectangle>> {
+    let socket = env::var_os("SWAYSOCK")?;
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT)).ok()?;
+
+    let mut request = SWAY_IPC_MAGIC.to_vec();
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(&SWAY_IPC_GET_TREE.to_ne_bytes());
+    stream.write_all(&request).ok()?;
+
+    // The magic, the payload length and the payload type
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header).ok()?;
+    let length = u32::from_ne_bytes(header[6..10].try_into().ok()?) as usize;
+
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload).ok()?;
+
+    let tree: Value = serde_json::from_slice(&payload).ok()?;
+    Some(sway_find_focused(&tree))
+}
+
+fn sway_find_focused(node: &Value) -> Option<Rectangle> {
+    if node["focused"].as_bool() == Some(true) {
+        return match node["type"].as_str() {
+            Some("con" | "floating_con") => json_rectangle(&node["rect"]),
+            _ => None,
+        };
+    }
+
+    node["nodes"]
+        .as_array()
+        .into_iter()
+        .chain(node["floating_nodes"].as_array())
+        .flatten()
+        .find_map(sway_find_focused)
+}
+
+fn json_rectangle(rect: &Value) -> Option<Rectangle> {
+    Some(Rectangle::new(
+        rect["x"].as_i64()? as i32,
+        rect["y"].as_i64()? as i32,
+        rect["width"].as_i64()? as i32,
+        rect["height"].as_i64()? as i32,
+    ))
+}
+
+// `None` if the IPC isn't available, `Some(None)` if no window is focused
+fn hyprland_active_window() -> Option<Option<Rectangle>> {
+    let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")?;
+    let socket = PathBuf::from(runtime_dir)
+        .join("hypr")
+        .join(signature)
+        .join(".socket.sock");
+
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT)).ok()?;
+    stream.write_all(b"j/activewindow").ok()?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).ok()?;
+
+    // It's `{}` if no window is focused
+    let window: Value = serde_json::from_slice(&response).ok()?;
+    let (Some(at), Some(size)) = (window["at"].as_array(), window["size"].as_array()) else {
+        return Some(None);
+    };
+
+    let value = |values: &Vec<Value>, index: usize| {
+        values.get(index).and_then(Value::as_i64).map(|v| v as i32)
+    };
+
+    let region = match (value(at, 0), value(at, 1), value(size, 0), value(size, 1)) {
+        (Some(x), Some(y), Some(width), Some(height)) => Some(Rectangle::new(x, y, width, height)),
+        _ => None,
+    };
+
+    Some(region)
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for ActiveWindowState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+            && interface == "zwlr_foreign_toplevel_manager_v1"
+        {
+            state.manager = Some(registry.bind(name, version.min(3), qh, ()));
+        }
+    }
+}
+
+impl Dispatch<zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, ()>
+    for ActiveWindowState
+{
+    fn event(
+        state: &mut Self,
+        _: &zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.toplevels.insert(toplevel.id(), Toplevel::default());
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => state.toplevels.clear(),
+            _ => (),
+        }
+    }
+
+    event_created_child!(ActiveWindowState, zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, ()>
+    for ActiveWindowState
+{
+    fn event(
+        state: &mut Self,
+        handle: &zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            // An array of the native-endian `u32` states
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+                if let Some(toplevel) = state.toplevels.get_mut(&handle.id()) {
+                    toplevel.pending_activated = states.chunks_exact(4).any(|s| {
+                        u32::from_ne_bytes([s[0], s[1], s[2], s[3]])
+                            == zwlr_foreign_toplevel_handle_v1::State::Activated as u32
+                    });
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                if let Some(toplevel) = state.toplevels.get_mut(&handle.id())
+                    && toplevel.activated != toplevel.pending_activated
+                {
+                    toplevel.activated = toplevel.pending_activated;
+                    state.focus_changed = true;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                if let Some(toplevel) = state.toplevels.remove(&handle.id()) {
+                    state.focus_changed |= toplevel.activated;
+                }
+                handle.destroy();
+            }
+            _ => (),
+        }
+    }
+}
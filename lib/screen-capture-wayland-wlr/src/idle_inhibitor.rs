@@ -0,0 +1,284 @@
+//! Idle inhibitor of the `idle-inhibit-unstable-v1` protocol.
+//!
+//! The compositors only honour an inhibitor of a visible surface, so it's
+//! bound to a transparent 1x1 layer-shell surface which ignores the input.
+
+use crate::Error;
+use nix::sys::memfd;
+use std::{fs::File, os::fd::AsFd};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle,
+    protocol::{wl_buffer, wl_compositor, wl_region, wl_registry, wl_shm, wl_shm_pool, wl_surface},
+};
+use wayland_protocols::wp::idle_inhibit::zv1::client::{
+    zwp_idle_inhibit_manager_v1, zwp_idle_inhibitor_v1,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+#[derive(Default)]
+struct InhibitorState {
+    compositor: Option<wl_compositor::WlCompositor>,
+    shm: Option<wl_shm::WlShm>,
+    layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    idle_inhibit_manager: Option<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1>,
+    configured: bool,
+}
+
+/// Keeps the outputs from idling, so the screen isn't locked or blanked and
+/// the system isn't suspended. It's released when dropped.
+pub struct IdleInhibitor {
+    conn: Connection,
+    inhibitor: zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+    layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    surface: wl_surface::WlSurface,
+    buffer: wl_buffer::WlBuffer,
+}
+
+impl IdleInhibitor {
+    pub fn new() -> Result<Self, Error> {
+        let conn = Connection::connect_to_env()?;
+        let mut queue: EventQueue<InhibitorState> = conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = conn.display().get_registry(&qh, ());
+
+        let mut state = InhibitorState::default();
+        queue.roundtrip(&mut state)?;
+
+        let (Some(compositor), Some(shm), Some(layer_shell), Some(idle_inhibit_manager)) = (
+            state.compositor.clone(),
+            state.shm.clone(),
+            state.layer_shell.clone(),
+            state.idle_inhibit_manager.clone(),
+        ) else {
+            return Err(Error::Unimplemented(
+                "Unsupported Window Manager which doesn't implement `wlr-layer-shell` and `idle-inhibit-unstable-v1` protocols."
+                    .to_string(),
+            ));
+        };
+
+        let surface = compositor.create_surface(&qh, ());
+
+        // Clicks go through to the windows under it
+        let input_region = compositor.create_region(&qh, ());
+        surface.set_input_region(Some(&input_region));
+        input_region.destroy();
+
+        let layer_surface = layer_shell.get_layer_surface(
+            &surface,
+            None,
+            zwlr_layer_shell_v1::Layer::Overlay,
+            "wayshot_idle_inhibitor".to_string(),
+            &qh,
+            (),
+        );
+        layer_surface
+            .set_anchor(zwlr_layer_surface_v1::Anchor::Top | zwlr_layer_surface_v1::Anchor::Left);
+        layer_surface.set_size(1, 1);
+        surface.commit();
+
+        while !state.configured {
+            queue.blocking_dispatch(&mut state)?;
+        }
+
+        let buffer = transparent_buffer(&shm, &qh)?;
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(0, 0, 1, 1);
+        surface.commit();
+
+        let inhibitor = idle_inhibit_manager.create_inhibitor(&surface, &qh, ());
+        queue.roundtrip(&mut state)?;
+
+        Ok(Self {
+            conn,
+            inhibitor,
+            layer_surface,
+            surface,
+            buffer,
+        })
+    }
+}
+
+impl Drop for IdleInhibitor {
+    fn drop(&mut self) {
+        self.inhibitor.destroy();
+        self.layer_surface.destroy();
+        self.surface.destroy();
+        self.buffer.destroy();
+
+        if let Err(e) = self.conn.flush() {
+            log::warn!("release idle inhibitor failed: {e}");
+        }
+    }
+}
+
+// A 1x1 ARGB8888 buffer of a zeroed memfd
+fn transparent_buffer(
+    shm: &wl_shm::WlShm,
+    qh: &QueueHandle<InhibitorState>,
+) -> Result<wl_buffer::WlBuffer, Error> {
+    let fd = memfd::memfd_create(c"wayshot_idle_inhibitor", memfd::MFdFlags::MFD_CLOEXEC)
+        .map_err(|e| Error::Other(format!("create memfd failed: {e}")))?;
+    let file = File::from(fd);
+    file.set_len(4)
+        .map_err(|e| Error::Other(format!("set memfd size failed: {e}")))?;
+
+    let pool = shm.create_pool(file.as_fd(), 4, qh, ());
+    let buffer = pool.create_buffer(0, 1, 1, 4, wl_shm::Format::Argb8888, qh, ());
+    pool.destroy();
+
+    Ok(buffer)
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for InhibitorState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_compositor" => state.compositor = Some(registry.bind(name, version, qh, ())),
+                "wl_shm" => state.shm = Some(registry.bind(name, version, qh, ())),
+                "zwlr_layer_shell_v1" => {
+                    state.layer_shell = Some(registry.bind(name, version, qh, ()))
+                }
+                "zwp_idle_inhibit_manager_v1" => {
+                    state.idle_inhibit_manager = Some(registry.bind(name, version, qh, ()))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for InhibitorState {
+    fn event(
+        state: &mut Self,
+        layer_surface: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_layer_surface_v1::Event::Configure { serial, .. } = event {
+            layer_surface.ack_configure(serial);
+            state.configured = true;
+        }
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_compositor::WlCompositor,
+        _: wl_compositor::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_region::WlRegion, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_region::WlRegion,
+        _: wl_region::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_surface::WlSurface,
+        _: wl_surface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_shm::WlShm,
+        _: wl_shm::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_shm_pool::WlShmPool,
+        _: wl_shm_pool::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        _: zwlr_layer_shell_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+        _: zwp_idle_inhibit_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1, ()> for InhibitorState {
+    fn event(
+        _: &mut Self,
+        _: &zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+        _: zwp_idle_inhibitor_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
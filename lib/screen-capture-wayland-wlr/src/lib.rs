@@ -1,12 +1,17 @@
+mod active_window;
 mod backend;
 mod capture;
 mod cursor;
 mod error;
+mod idle_inhibitor;
+mod region_selector;
 mod screen_info;
 
 pub use capture::*;
 pub use cursor::*;
 pub use error::*;
+pub use idle_inhibitor::*;
+pub use region_selector::*;
 pub use screen_info::*;
 
 #[derive(Clone, Default)]
@@ -44,4 +49,12 @@ impl screen_capture::ScreenCapture for ScreenCaptureWaylandWlr {
     ) -> Result<(), screen_capture::CursorError> {
         cursor::monitor_cursor_position(config, callback)
     }
+
+    fn monitor_active_window(
+        &mut self,
+        config: screen_capture::MonitorActiveWindowConfig,
+        callback: impl FnMut(Option<screen_capture::Rectangle>) + Send + 'static,
+    ) -> Result<(), screen_capture::ActiveWindowError> {
+        active_window::monitor_active_window(config, callback)
+    }
 }
@@ -0,0 +1,738 @@
+//! Interactive region selector on a layer-shell overlay of the output.
+//!
+//! The output is frozen by a screenshot and dimmed outside of the rubber-band
+//! rectangle. Guide lines, a magnifier around the pointer and the coordinates
+//! of the pointer and the size of the selection help to pick the corners at
+//! pixel precision, also on the scaled outputs.
+//!
+//! Dragging with the left button selects the region and releasing the button
+//! confirms it, a click without dragging selects the whole output. `Escape` or
+//! the right button cancels the selection.
+
+use crate::{Error, capture};
+use memmap2::MmapMut;
+use nix::sys::memfd;
+use screen_capture::{Capture, Rectangle, ScreenInfo};
+use std::{fs::File, os::fd::AsFd};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+    protocol::{
+        wl_buffer, wl_callback, wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_registry,
+        wl_seat, wl_shm, wl_shm_pool, wl_surface,
+    },
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+// Linux input event codes
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const KEY_ESC: u32 = 1;
+
+// Source pixels on each side of the pointer pixel in the magnifier
+const MAGNIFIER_RADIUS: i32 = 7;
+const MAGNIFIER_ZOOM: i32 = 8;
+const MAGNIFIER_OFFSET: i32 = 24;
+
+const LABEL_SCALE: i32 = 2;
+const LABEL_PADDING: i32 = 4;
+
+const DIM_FACTOR: u8 = 2;
+const BORDER_COLOR: u32 = 0xff_ff_ff_ff;
+const GUIDE_COLOR: u32 = 0xc0_ff_ff_ff;
+const LABEL_BACKGROUND: u32 = 0xd0_00_00_00;
+const LABEL_COLOR: u32 = 0xff_ff_ff_ff;
+const MAGNIFIER_CENTER_COLOR: u32 = 0xff_ff_30_30;
+
+// 3x5 bitmaps of the characters in the labels, a row in the low 3 bits
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+const GLYPHS: [(char, [u8; 5]); 12] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('x', [0b000, 0b101, 0b010, 0b101, 0b000]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+];
+
+#[derive(Default)]
+struct SelectorState {
+    compositor: Option<wl_compositor::WlCompositor>,
+    shm: Option<wl_shm::WlShm>,
+    seat: Option<wl_seat::WlSeat>,
+    layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    outputs: Vec<(wl_output::WlOutput, String)>,
+
+    pointer: Option<wl_pointer::WlPointer>,
+    keyboard: Option<wl_keyboard::WlKeyboard>,
+
+    configured: bool,
+    closed: bool,
+    frame_pending: bool,
+    dirty: bool,
+
+    // In the pixels of the output
+    scale_factor: f32,
+    output_width: i32,
+    output_height: i32,
+    pointer_position: Option<(i32, i32)>,
+    anchor: Option<(i32, i32)>,
+
+    // `Some(None)` if it's cancelled
+    result: Option<Option<Rectangle>>,
+}
+
+impl SelectorState {
+    fn selection(&self) -> Option<Rectangle> {
+        let ((ax, ay), (px, py)) = (self.anchor?, self.pointer_position?);
+        let (x, y) = (ax.min(px), ay.min(py));
+        Some(Rectangle::new(
+            x,
+            y,
+            (ax.max(px) - x + 1).min(self.output_width - x),
+            (ay.max(py) - y + 1).min(self.output_height - y),
+        ))
+    }
+
+    fn update_pointer(&mut self, surface_x: f64, surface_y: f64) {
+        let x = (surface_x * self.scale_factor as f64).floor() as i32;
+        let y = (surface_y * self.scale_factor as f64).floor() as i32;
+        self.pointer_position = Some((
+            x.clamp(0, self.output_width - 1),
+            y.clamp(0, self.output_height - 1),
+        ));
+        self.dirty = true;
+    }
+}
+
+// The software rendered overlay in ARGB8888 at the surface size
+struct Canvas {
+    width: i32,
+    height: i32,
+    scale_factor: f32,
+    screenshot: Capture,
+    dimmed: Vec<u32>,
+    bright: Vec<u32>,
+    mmap: MmapMut,
+    buffer: wl_buffer::WlBuffer,
+}
+
+impl Canvas {
+    fn new(
+        shm: &wl_shm::WlShm,
+        qh: &QueueHandle<SelectorState>,
+        width: i32,
+        height: i32,
+        scale_factor: f32,
+        screenshot: Capture,
+    ) -> Result<Self, Error> {
+        let stride = width * 4;
+        let size = stride * height;
+
+        let fd = memfd::memfd_create(c"wayshot_region_selector", memfd::MFdFlags::MFD_CLOEXEC)
+            .map_err(|e| Error::Other(format!("create memfd failed: {e}")))?;
+        let file = File::from(fd);
+        file.set_len(size as u64)
+            .map_err(|e| Error::Other(format!("set memfd size failed: {e}")))?;
+
+        let mmap = unsafe { MmapMut::map_mut(&file) }
+            .map_err(|e| Error::Other(format!("map memfd failed: {e}")))?;
+
+        let pool = shm.create_pool(file.as_fd(), size, qh, ());
+        let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, qh, ());
+        pool.destroy();
+
+        let mut canvas = Self {
+            width,
+            height,
+            scale_factor,
+            screenshot,
+            dimmed: vec![],
+            bright: vec![],
+            mmap,
+            buffer,
+        };
+
+        canvas.bright = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (px, py) = canvas.to_output(x, y);
+                canvas.screenshot_pixel(px, py)
+            })
+            .collect();
+
+        canvas.dimmed = canvas
+            .bright
+            .iter()
+            .map(|color| {
+                let [b, g, r, _] = color.to_le_bytes();
+                u32::from_le_bytes([b / DIM_FACTOR, g / DIM_FACTOR, r / DIM_FACTOR, 0xff])
+            })
+            .collect();
+
+        Ok(canvas)
+    }
+
+    fn to_output(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            (x as f32 * self.scale_factor) as i32,
+            (y as f32 * self.scale_factor) as i32,
+        )
+    }
+
+    fn to_surface(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            (x as f32 / self.scale_factor) as i32,
+            (y as f32 / self.scale_factor) as i32,
+        )
+    }
+
+    // The screenshot is in RGBA, black outside of it
+    fn screenshot_pixel(&self, x: i32, y: i32) -> u32 {
+        if x < 0 || y < 0 || x >= self.screenshot.width as i32 || y >= self.screenshot.height as i32
+        {
+            return 0xff_00_00_00;
+        }
+
+        let index = ((y as u32 * self.screenshot.width + x as u32) * 4) as usize;
+        let [r, g, b] = [
+            self.screenshot.pixel_data[index],
+            self.screenshot.pixel_data[index + 1],
+            self.screenshot.pixel_data[index + 2],
+        ];
+        u32::from_le_bytes([b, g, r, 0xff])
+    }
+
+    fn draw(&mut self, state: &SelectorState) {
+        let mut pixels = self.dimmed.clone();
+
+        if let Some(selection) = state.selection() {
+            let (x0, y0) = self.to_surface(selection.x, selection.y);
+            let (x1, y1) = self.to_surface(
+                selection.x + selection.width,
+                selection.y + selection.height,
+            );
+            let (x1, y1) = (x1.max(x0 + 1), y1.max(y0 + 1));
+
+            for y in y0..y1.min(self.height) {
+                let row = (y * self.width) as usize;
+                let (start, end) = (row + x0 as usize, row + x1.min(self.width) as usize);
+                pixels[start..end].copy_from_slice(&self.bright[start..end]);
+            }
+
+            self.stroke_rect(&mut pixels, x0 - 1, y0 - 1, x1 - x0 + 2, y1 - y0 + 2);
+
+            let label = format!("{}x{}", selection.width, selection.height);
+            self.draw_label(&mut pixels, x0, y0 - self.label_height() - 2, &label);
+        }
+
+        if let Some((px, py)) = state.pointer_position {
+            let (sx, sy) = self.to_surface(px, py);
+            for x in 0..self.width {
+                self.blend(&mut pixels, x, sy, GUIDE_COLOR);
+            }
+            for y in 0..self.height {
+                self.blend(&mut pixels, sx, y, GUIDE_COLOR);
+            }
+
+            self.draw_magnifier(&mut pixels, px, py);
+        }
+
+        for (chunk, color) in self.mmap.chunks_exact_mut(4).zip(pixels) {
+            chunk.copy_from_slice(&color.to_le_bytes());
+        }
+    }
+
+    // Placed at the bottom right of the pointer, flipped near the edges
+    fn draw_magnifier(&self, pixels: &mut [u32], px: i32, py: i32) {
+        let size = (MAGNIFIER_RADIUS * 2 + 1) * MAGNIFIER_ZOOM;
+        let (sx, sy) = self.to_surface(px, py);
+
+        let mut left = sx + MAGNIFIER_OFFSET;
+        if left + size + 2 > self.width {
+            left = sx - MAGNIFIER_OFFSET - size;
+        }
+
+        let label_height = self.label_height();
+        let mut top = sy + MAGNIFIER_OFFSET;
+        if top + size + label_height + 4 > self.height {
+            top = sy - MAGNIFIER_OFFSET - size - label_height - 2;
+        }
+
+        for dy in -MAGNIFIER_RADIUS..=MAGNIFIER_RADIUS {
+            for dx in -MAGNIFIER_RADIUS..=MAGNIFIER_RADIUS {
+                let color = self.screenshot_pixel(px + dx, py + dy);
+                let x = left + (dx + MAGNIFIER_RADIUS) * MAGNIFIER_ZOOM;
+                let y = top + (dy + MAGNIFIER_RADIUS) * MAGNIFIER_ZOOM;
+                self.fill_rect(pixels, x, y, MAGNIFIER_ZOOM, MAGNIFIER_ZOOM, color);
+            }
+        }
+
+        let center = MAGNIFIER_RADIUS * MAGNIFIER_ZOOM;
+        self.stroke_rect(
+            pixels,
+            left + center,
+            top + center,
+            MAGNIFIER_ZOOM,
+            MAGNIFIER_ZOOM,
+        );
+        self.stroke_rect_with(
+            pixels,
+            left + center - 1,
+            top + center - 1,
+            MAGNIFIER_ZOOM + 2,
+            MAGNIFIER_ZOOM + 2,
+            MAGNIFIER_CENTER_COLOR,
+        );
+        self.stroke_rect(pixels, left - 1, top - 1, size + 2, size + 2);
+
+        self.draw_label(pixels, left, top + size + 2, &format!("{px},{py}"));
+    }
+
+    fn label_height(&self) -> i32 {
+        GLYPH_HEIGHT * LABEL_SCALE + LABEL_PADDING * 2
+    }
+
+    fn draw_label(&self, pixels: &mut [u32], x: i32, y: i32, text: &str) {
+        let advance = (GLYPH_WIDTH + 1) * LABEL_SCALE;
+        let width = advance * text.chars().count() as i32 - LABEL_SCALE + LABEL_PADDING * 2;
+        let height = self.label_height();
+
+        let x = x.clamp(0, (self.width - width).max(0));
+        let y = y.clamp(0, (self.height - height).max(0));
+        self.fill_rect(pixels, x, y, width, height, LABEL_BACKGROUND);
+
+        for (index, ch) in text.chars().enumerate() {
+            let Some((_, rows)) = GLYPHS.iter().find(|(c, _)| *c == ch) else {
+                continue;
+            };
+
+            let left = x + LABEL_PADDING + index as i32 * advance;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill_rect(
+                            pixels,
+                            left + column * LABEL_SCALE,
+                            y + LABEL_PADDING + row as i32 * LABEL_SCALE,
+                            LABEL_SCALE,
+                            LABEL_SCALE,
+                            LABEL_COLOR,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn stroke_rect(&self, pixels: &mut [u32], x: i32, y: i32, width: i32, height: i32) {
+        self.stroke_rect_with(pixels, x, y, width, height, BORDER_COLOR);
+    }
+
+    fn stroke_rect_with(
+        &self,
+        pixels: &mut [u32],
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        color: u32,
+    ) {
+        self.fill_rect(pixels, x, y, width, 1, color);
+        self.fill_rect(pixels, x, y + height - 1, width, 1, color);
+        self.fill_rect(pixels, x, y, 1, height, color);
+        self.fill_rect(pixels, x + width - 1, y, 1, height, color);
+    }
+
+    fn fill_rect(&self, pixels: &mut [u32], x: i32, y: i32, width: i32, height: i32, color: u32) {
+        for row in y.max(0)..(y + height).min(self.height) {
+            for column in x.max(0)..(x + width).min(self.width) {
+                self.blend(pixels, column, row, color);
+            }
+        }
+    }
+
+    fn blend(&self, pixels: &mut [u32], x: i32, y: i32, color: u32) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+
+        let index = (y * self.width + x) as usize;
+        let [sb, sg, sr, sa] = color.to_le_bytes();
+        if sa == 0xff {
+            pixels[index] = color;
+            return;
+        }
+
+        let [db, dg, dr, _] = pixels[index].to_le_bytes();
+        let mix =
+            |s: u8, d: u8| ((s as u32 * sa as u32 + d as u32 * (255 - sa as u32)) / 255) as u8;
+        pixels[index] = u32::from_le_bytes([mix(sb, db), mix(sg, dg), mix(sr, dr), 0xff]);
+    }
+}
+
+/// Let the user select a region of the output interactively.
+///
+/// Returns the region in the pixels of the output, which is the coordinate
+/// space of the captured frames, or `None` if the selection is cancelled.
+pub fn select_region(screen_info: &ScreenInfo) -> Result<Option<Rectangle>, Error> {
+    if screen_info.scale_factor <= 0.0 {
+        return Err(Error::Other("scale factor is 0".to_string()));
+    }
+
+    // Taken before the overlay is shown, so it isn't in the screenshot
+    let screenshot = capture::capture_output(&screen_info.name, false)?;
+
+    let conn = Connection::connect_to_env()?;
+    let mut queue: EventQueue<SelectorState> = conn.new_event_queue();
+    let qh = queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+
+    let mut state = SelectorState {
+        scale_factor: screen_info.scale_factor,
+        output_width: screenshot.width as i32,
+        output_height: screenshot.height as i32,
+        ..Default::default()
+    };
+    queue.roundtrip(&mut state)?;
+    queue.roundtrip(&mut state)?;
+
+    let (Some(compositor), Some(shm), Some(layer_shell)) = (
+        state.compositor.clone(),
+        state.shm.clone(),
+        state.layer_shell.clone(),
+    ) else {
+        return Err(Error::Unimplemented(
+            "Unsupported Window Manager which doesn't implement `wlr-layer-shell` protocol."
+                .to_string(),
+        ));
+    };
+
+    let Some(output) = state
+        .outputs
+        .iter()
+        .find(|(_, name)| *name == screen_info.name)
+        .map(|(output, _)| output.clone())
+    else {
+        return Err(Error::NoOutput(screen_info.name.clone()));
+    };
+
+    let width = (screen_info.logical_size.width as f32 / screen_info.scale_factor) as i32;
+    let height = (screen_info.logical_size.height as f32 / screen_info.scale_factor) as i32;
+
+    let surface = compositor.create_surface(&qh, ());
+    let layer_surface = layer_shell.get_layer_surface(
+        &surface,
+        Some(&output),
+        zwlr_layer_shell_v1::Layer::Overlay,
+        "wayshot_region_selector".to_string(),
+        &qh,
+        (),
+    );
+    layer_surface.set_anchor(
+        zwlr_layer_surface_v1::Anchor::Top
+            | zwlr_layer_surface_v1::Anchor::Bottom
+            | zwlr_layer_surface_v1::Anchor::Left
+            | zwlr_layer_surface_v1::Anchor::Right,
+    );
+    layer_surface.set_size(width as u32, height as u32);
+    layer_surface.set_exclusive_zone(-1);
+    layer_surface
+        .set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive);
+    surface.commit();
+
+    let mut canvas = Canvas::new(
+        &shm,
+        &qh,
+        width,
+        height,
+        screen_info.scale_factor,
+        screenshot,
+    )?;
+    state.dirty = true;
+
+    while state.result.is_none() && !state.closed {
+        queue.blocking_dispatch(&mut state)?;
+
+        if state.configured && state.dirty && !state.frame_pending {
+            canvas.draw(&state);
+            surface.attach(Some(&canvas.buffer), 0, 0);
+            surface.damage_buffer(0, 0, width, height);
+            surface.frame(&qh, ());
+            surface.commit();
+
+            state.dirty = false;
+            state.frame_pending = true;
+        }
+    }
+
+    layer_surface.destroy();
+    surface.destroy();
+    canvas.buffer.destroy();
+    queue.roundtrip(&mut state)?;
+
+    Ok(state.result.flatten())
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_compositor" => state.compositor = Some(registry.bind(name, version, qh, ())),
+                "wl_shm" => state.shm = Some(registry.bind(name, version, qh, ())),
+                "wl_seat" => state.seat = Some(registry.bind(name, version, qh, ())),
+                "zwlr_layer_shell_v1" => {
+                    state.layer_shell = Some(registry.bind(name, version, qh, ()))
+                }
+                "wl_output" => {
+                    let output = registry.bind(name, version.min(4), qh, ());
+                    state.outputs.push((output, String::new()));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        output: &wl_output::WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event
+            && let Some((_, output_name)) = state
+                .outputs
+                .iter_mut()
+                .find(|(o, _)| o.id() == output.id())
+        {
+            *output_name = name;
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        layer_surface: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, .. } => {
+                layer_surface.ack_configure(serial);
+                state.configured = true;
+                state.dirty = true;
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.closed = true,
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            state.frame_pending = false;
+        }
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            if capabilities.contains(wl_seat::Capability::Pointer) && state.pointer.is_none() {
+                state.pointer = Some(seat.get_pointer(qh, ()));
+            }
+
+            if capabilities.contains(wl_seat::Capability::Keyboard) && state.keyboard.is_none() {
+                state.keyboard = Some(seat.get_keyboard(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        pointer: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            // The guide lines and the magnifier take the place of the cursor
+            wl_pointer::Event::Enter {
+                serial,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                pointer.set_cursor(serial, None, 0, 0);
+                state.update_pointer(surface_x, surface_y);
+            }
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => state.update_pointer(surface_x, surface_y),
+            wl_pointer::Event::Button {
+                button,
+                state: WEnum::Value(button_state),
+                ..
+            } => match (button, button_state) {
+                (BTN_LEFT, wl_pointer::ButtonState::Pressed) => {
+                    state.anchor = state.pointer_position;
+                    state.dirty = true;
+                }
+                (BTN_LEFT, wl_pointer::ButtonState::Released) => {
+                    let whole_output =
+                        Rectangle::new(0, 0, state.output_width, state.output_height);
+
+                    state.result = match state.selection() {
+                        Some(selection) if selection.width > 1 || selection.height > 1 => {
+                            Some(Some(selection))
+                        }
+                        _ => Some(Some(whole_output)),
+                    };
+                }
+                (BTN_RIGHT, wl_pointer::ButtonState::Pressed) => state.result = Some(None),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for SelectorState {
+    fn event(
+        state: &mut Self,
+        _: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_keyboard::Event::Key {
+            key: KEY_ESC,
+            state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+            ..
+        } = event
+        {
+            state.result = Some(None);
+        }
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_compositor::WlCompositor,
+        _: wl_compositor::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_surface::WlSurface,
+        _: wl_surface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_shm::WlShm,
+        _: wl_shm::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_shm_pool::WlShmPool,
+        _: wl_shm_pool::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for SelectorState {
+    fn event(
+        _: &mut Self,
+        _: &zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        _: zwlr_layer_shell_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
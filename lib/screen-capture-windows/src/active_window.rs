@@ -0,0 +1,135 @@
+use screen_capture::{ActiveWindowError, MonitorActiveWindowConfig, Rectangle, ScreenInfo};
+use std::{
+    mem, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use winapi::{
+    shared::{
+        minwindef::DWORD,
+        ntdef::LONG,
+        windef::{HWINEVENTHOOK, HWND, RECT},
+        winerror::S_OK,
+    },
+    um::{
+        dwmapi::{DWMWA_EXTENDED_FRAME_BOUNDS, DwmGetWindowAttribute},
+        winuser::{
+            DispatchMessageW, EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_MOVESIZEEND,
+            GetForegroundWindow, IsIconic, MSG, PM_REMOVE, PeekMessageW, SetWinEventHook,
+            TranslateMessage, UnhookWinEvent, WINEVENT_OUTOFCONTEXT,
+        },
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// The windows may be resized by the applications without a move-size event
+const GEOMETRY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Set by the event hook, which has no context
+static FOREGROUND_CHANGED: AtomicBool = AtomicBool::new(true);
+
+pub fn monitor_active_window(
+    config: MonitorActiveWindowConfig,
+    mut callback: impl FnMut(Option<Rectangle>) + Send + 'static,
+) -> Result<(), ActiveWindowError> {
+    // The out-of-context events are posted to the message queue of this thread
+    let hook = unsafe {
+        SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_MOVESIZEEND,
+            ptr::null_mut(),
+            Some(win_event_proc),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT,
+        )
+    };
+
+    if hook.is_null() {
+        return Err(ActiveWindowError::ConnectionFailed(
+            "SetWinEventHook failed".to_string(),
+        ));
+    }
+
+    let mut last_region = None;
+    let mut last_query_time = None::<Instant>;
+    let mut msg: MSG = unsafe { mem::zeroed() };
+
+    while !config.stop_sig.load(Ordering::Relaxed) {
+        while unsafe { PeekMessageW(&mut msg, ptr::null_mut(), 0, 0, PM_REMOVE) } != 0 {
+            unsafe {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+
+        if FOREGROUND_CHANGED.swap(false, Ordering::Relaxed)
+            || last_query_time.is_none_or(|t| t.elapsed() >= GEOMETRY_POLL_INTERVAL)
+        {
+            last_query_time = Some(Instant::now());
+
+            let region = unsafe { foreground_window_region(&config.screen_info) };
+            if last_region != Some(region) {
+                log::debug!("active window region: {region:?}");
+                last_region = Some(region);
+                callback(region);
+            }
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    unsafe { UnhookWinEvent(hook) };
+    log::info!("exit monitor_active_window thread...");
+
+    Ok(())
+}
+
+// Moving or resizing ends with `EVENT_SYSTEM_MOVESIZEEND`, which is in the
+// range of the hook with the events between them ignored
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: DWORD,
+    _hwnd: HWND,
+    _id_object: LONG,
+    _id_child: LONG,
+    _event_thread: DWORD,
+    _event_time: DWORD,
+) {
+    if event == EVENT_SYSTEM_FOREGROUND || event == EVENT_SYSTEM_MOVESIZEEND {
+        FOREGROUND_CHANGED.store(true, Ordering::Relaxed);
+    }
+}
+
+// The visible bounds of the window without the shadow, `None` if it isn't on
+// the screen
+unsafe fn foreground_window_region(screen_info: &ScreenInfo) -> Option<Rectangle> {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() || IsIconic(hwnd) != 0 {
+            return None;
+        }
+
+        let mut rect: RECT = mem::zeroed();
+        let result = DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RECT as *mut _,
+            mem::size_of::<RECT>() as DWORD,
+        );
+
+        if result != S_OK {
+            return None;
+        }
+
+        let left = (rect.left - screen_info.position.x).max(0);
+        let top = (rect.top - screen_info.position.y).max(0);
+        let right = (rect.right - screen_info.position.x).min(screen_info.logical_size.width);
+        let bottom = (rect.bottom - screen_info.position.y).min(screen_info.logical_size.height);
+
+        (right > left && bottom > top)
+            .then(|| Rectangle::new(left, top, right - left, bottom - top))
+    }
+}
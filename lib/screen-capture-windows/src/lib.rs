@@ -1,3 +1,4 @@
+mod active_window;
 mod backend;
 mod capture;
 mod cursor;
@@ -45,4 +46,12 @@ impl screen_capture::ScreenCapture for ScreenCaptureWindows {
     ) -> Result<(), screen_capture::CursorError> {
         cursor::monitor_cursor_position(config, callback)
     }
+
+    fn monitor_active_window(
+        &mut self,
+        config: screen_capture::MonitorActiveWindowConfig,
+        callback: impl FnMut(Option<screen_capture::Rectangle>) + Send + 'static,
+    ) -> Result<(), screen_capture::ActiveWindowError> {
+        active_window::monitor_active_window(config, callback)
+    }
 }
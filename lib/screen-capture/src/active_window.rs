@@ -0,0 +1,30 @@
+use crate::ScreenInfo;
+use std::sync::{Arc, atomic::AtomicBool};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ActiveWindowError {
+    #[error("Failed to connect to the display server: {0}")]
+    ConnectionFailed(String),
+
+    #[error("Required protocol not available: {0}")]
+    ProtocolNotAvailable(String),
+
+    #[error("{0}")]
+    Unimplemented(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorActiveWindowConfig {
+    pub screen_info: ScreenInfo,
+    pub stop_sig: Arc<AtomicBool>,
+}
+
+impl MonitorActiveWindowConfig {
+    pub fn new(screen_info: ScreenInfo, stop_sig: Arc<AtomicBool>) -> Self {
+        Self {
+            screen_info,
+            stop_sig,
+        }
+    }
+}
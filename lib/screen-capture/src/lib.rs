@@ -1,10 +1,14 @@
+mod active_window;
 mod capture;
 mod cursor;
 mod screen_info;
+mod scrolling_capture;
 
+pub use active_window::*;
 pub use capture::*;
 pub use cursor::*;
 pub use screen_info::*;
+pub use scrolling_capture::*;
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum ScreenCaptureError {
@@ -34,6 +38,14 @@ pub trait ScreenCapture {
         config: MonitorCursorPositionConfig,
         callback: impl FnMut(CursorPosition) + Send + 'static,
     ) -> Result<(), CursorError>;
+
+    // call back with the region of the focused window in the pixels of the
+    // output, or `None` if the focused window isn't on the output
+    fn monitor_active_window(
+        &mut self,
+        config: MonitorActiveWindowConfig,
+        callback: impl FnMut(Option<Rectangle>) + Send + 'static,
+    ) -> Result<(), ActiveWindowError>;
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
@@ -0,0 +1,302 @@
+use crate::{Capture, CaptureStreamConfig, Rectangle, ScreenCapture, ScreenCaptureError};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+#[derive(Debug, Clone, derive_setters::Setters)]
+#[setters(prefix = "with_")]
+pub struct ScrollingCaptureConfig {
+    /// Name of the output to capture
+    #[setters(skip)]
+    pub name: String,
+
+    /// Region of the output in physical pixels, the whole output if empty
+    pub region: Rectangle,
+
+    /// Frames per second to capture while the user scrolls
+    pub fps: f64,
+
+    /// Stop when the stitched image reaches this height
+    pub max_height: u32,
+
+    /// Cancellation signal - set it to true when the user stops scrolling
+    pub cancel_sig: Arc<AtomicBool>,
+}
+
+impl ScrollingCaptureConfig {
+    pub fn new(name: String, cancel_sig: Arc<AtomicBool>) -> Self {
+        Self {
+            name,
+            region: Rectangle::default(),
+            fps: 10.0,
+            max_height: 30000,
+            cancel_sig,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StitchStatus {
+    /// The frame is the first one or added this many new rows
+    Appended(u32),
+
+    /// The content did not move
+    Unchanged,
+
+    /// No overlap with the previous frame, scrolling too fast. The frame is
+    /// dropped and the next one is matched against the same previous frame
+    NoOverlap,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScrollingCaptureProgress {
+    pub frame_index: u64,
+    pub status: StitchStatus,
+
+    /// Height of the stitched image so far
+    pub height: u32,
+}
+
+/// Stitch the frames of a scrolling area into one tall image.
+///
+/// The overlap between two frames is found by matching the hashes of their
+/// rows. The rows that do not move between frames, like sticky headers and
+/// footers, are left out of the matching and are only kept once.
+#[derive(Debug, Clone)]
+pub struct Stitcher {
+    width: u32,
+
+    // Stitched RGBA rows, they always end with the whole last frame
+    pixel_data: Vec<u8>,
+    last_frame_hashes: Vec<u64>,
+
+    /// Minimum ratio of matched rows to accept an overlap
+    min_match_ratio: f32,
+
+    /// Minimum number of non uniform rows in the overlap
+    min_overlap_rows: usize,
+}
+
+impl Stitcher {
+    pub fn new(width: u32) -> Self {
+        Self {
+            width,
+            pixel_data: vec![],
+            last_frame_hashes: vec![],
+            min_match_ratio: 0.95,
+            min_overlap_rows: 8,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.pixel_data.len() / self.row_len().max(1)) as u32
+    }
+
+    /// `frame` must have the width of the stitcher and the same height as
+    /// the previous frames
+    pub fn push(&mut self, frame: &Capture) -> Result<StitchStatus, ScreenCaptureError> {
+        if frame.width != self.width
+            || frame.pixel_data.len() != self.row_len() * frame.height as usize
+        {
+            return Err(ScreenCaptureError::Capture(format!(
+                "invalid frame {}x{} for a stitched width of {}",
+                frame.width, frame.height, self.width
+            )));
+        }
+
+        let hashes = frame
+            .pixel_data
+            .chunks_exact(self.row_len().max(1))
+            .map(row_hash)
+            .collect::<Vec<_>>();
+
+        if self.last_frame_hashes.is_empty() {
+            self.pixel_data = frame.pixel_data.clone();
+            self.last_frame_hashes = hashes;
+            return Ok(StitchStatus::Appended(frame.height));
+        }
+
+        if hashes.len() != self.last_frame_hashes.len() {
+            return Err(ScreenCaptureError::Capture(format!(
+                "frame height changed from {} to {}",
+                self.last_frame_hashes.len(),
+                hashes.len()
+            )));
+        }
+
+        let previous = &self.last_frame_hashes;
+        let height = hashes.len();
+        let top = (0..height)
+            .take_while(|&i| previous[i] == hashes[i])
+            .count();
+        if top == height {
+            return Ok(StitchStatus::Unchanged);
+        }
+        let bottom = (0..height)
+            .rev()
+            .take_while(|&i| previous[i] == hashes[i])
+            .count();
+
+        let Some(offset) = self.find_offset(
+            &previous[top..height - bottom],
+            &hashes[top..height - bottom],
+        ) else {
+            return Ok(StitchStatus::NoOverlap);
+        };
+
+        // Drop the footer of the previous frame, then add the new rows and
+        // the footer of this frame
+        let row_len = self.row_len();
+        self.pixel_data
+            .truncate(self.pixel_data.len() - bottom * row_len);
+        self.pixel_data
+            .extend_from_slice(&frame.pixel_data[(height - bottom - offset) * row_len..]);
+        self.last_frame_hashes = hashes;
+
+        Ok(StitchStatus::Appended(offset as u32))
+    }
+
+    pub fn into_capture(self) -> Capture {
+        Capture {
+            width: self.width,
+            height: self.height(),
+            pixel_data: self.pixel_data,
+        }
+    }
+
+    fn row_len(&self) -> usize {
+        self.width as usize * 4
+    }
+
+    /// Scroll distance in rows between the moving areas of the previous and
+    /// the current frame
+    fn find_offset(&self, previous: &[u64], current: &[u64]) -> Option<usize> {
+        let mut best: Option<(usize, f32)> = None;
+        for offset in 1..current.len() {
+            let (mut compared, mut matched) = (0, 0);
+            for (a, b) in previous[offset..].iter().zip(current) {
+                // Blank rows match everywhere, they do not tell the offset
+                if *b == UNIFORM_ROW {
+                    continue;
+                }
+
+                compared += 1;
+                if a == b {
+                    matched += 1;
+                }
+            }
+
+            if compared < self.min_overlap_rows {
+                break;
+            }
+
+            let ratio = matched as f32 / compared as f32;
+            if ratio >= self.min_match_ratio && best.is_none_or(|(_, r)| ratio > r) {
+                best = Some((offset, ratio));
+            }
+        }
+
+        best.map(|(offset, _)| offset)
+    }
+}
+
+// Hash of the rows with a single color
+const UNIFORM_ROW: u64 = 0;
+
+/// FNV-1a hash of the row
+fn row_hash(row: &[u8]) -> u64 {
+    if row.chunks_exact(4).all(|pixel| pixel == &row[..4]) {
+        return UNIFORM_ROW;
+    }
+
+    row.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Crop `region` out of the frame, the whole frame if the region is empty
+fn crop(frame: &Capture, region: Rectangle) -> Capture {
+    if region.width <= 0 || region.height <= 0 || frame.width == 0 {
+        return frame.clone();
+    }
+
+    let x0 = region.x.clamp(0, frame.width as i32) as usize;
+    let y0 = region.y.clamp(0, frame.height as i32) as usize;
+    let x1 = (region.x + region.width).clamp(0, frame.width as i32) as usize;
+    let y1 = (region.y + region.height).clamp(0, frame.height as i32) as usize;
+
+    let row_len = frame.width as usize * 4;
+    let pixel_data = frame
+        .pixel_data
+        .chunks_exact(row_len)
+        .skip(y0)
+        .take(y1 - y0)
+        .flat_map(|row| &row[x0 * 4..x1 * 4])
+        .copied()
+        .collect();
+
+    Capture {
+        width: (x1 - x0) as u32,
+        height: (y1 - y0) as u32,
+        pixel_data,
+    }
+}
+
+/// Capture the region repeatedly while the user scrolls and stitch the
+/// frames, until `cancel_sig` is set or the image reaches `max_height`
+pub fn scrolling_capture(
+    capturer: impl ScreenCapture,
+    config: ScrollingCaptureConfig,
+    mut cb: impl FnMut(ScrollingCaptureProgress),
+) -> Result<Capture, ScreenCaptureError> {
+    let stream_config = CaptureStreamConfig {
+        name: config.name.clone(),
+        include_cursor: false,
+        fps: Some(config.fps),
+        cancel_sig: config.cancel_sig.clone(),
+        sync_sig: Arc::new(AtomicBool::new(false)),
+    };
+
+    let mut stitcher: Option<Stitcher> = None;
+    let mut error = None;
+
+    capturer.capture_output_stream(stream_config, |data| {
+        if config.cancel_sig.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let frame = crop(&data.data, config.region);
+        let stitcher = stitcher.get_or_insert_with(|| Stitcher::new(frame.width));
+
+        match stitcher.push(&frame) {
+            Ok(status) => {
+                cb(ScrollingCaptureProgress {
+                    frame_index: data.frame_index,
+                    status,
+                    height: stitcher.height(),
+                });
+
+                if stitcher.height() >= config.max_height {
+                    config.cancel_sig.store(true, Ordering::Relaxed);
+                }
+            }
+            Err(e) => {
+                error = Some(e);
+                config.cancel_sig.store(true, Ordering::Relaxed);
+            }
+        }
+    })?;
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    stitcher
+        .map(Stitcher::into_capture)
+        .ok_or_else(|| ScreenCaptureError::Capture("no frame captured".to_string()))
+}
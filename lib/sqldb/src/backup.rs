@@ -0,0 +1,336 @@
+//! Backup and restore of the databases
+//!
+//! A backup is a consistent snapshot written by `VACUUM INTO` while the
+//! database is in use. An encrypted database is backed up with its key, so the
+//! backup is restored with the same passphrase.
+
+use super::{MAX_CONNECTIONS, POOLS, pool};
+use anyhow::{Context, Result, bail};
+use sqlx::{ConnectOptions, Connection, SqliteExecutor, sqlite::SqlitePoolOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extension of the daily backups
+const BACKUP_EXTENSION: &str = "db";
+
+/// Write a backup of the database `name` to `path`, an existing file is replaced
+///
+/// # Errors
+/// Returns an error if:
+/// - The database is not opened
+/// - The backup can't be written
+///
+/// # Example
+/// ```no_run
+/// use sqldb::{backup, create_db, restore};
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     create_db("app", "/path/to/app.db").await?;
+///     backup("app", "/path/to/app-backup.db").await?;
+///     restore("app", "/path/to/app-backup.db").await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn backup(name: &str, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let tmp_path = tmp_path(path, "backup");
+    let _ = fs::remove_file(&tmp_path);
+
+    // `VACUUM INTO` doesn't overwrite a file
+    let result = sqlx::query("VACUUM INTO ?")
+        .bind(tmp_path.to_string_lossy().as_ref())
+        .execute(&pool(name).await?)
+        .await;
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("write backup {} failed", path.display()))?;
+
+    Ok(())
+}
+
+/// Replace the database `name` with the backup at `path`
+///
+/// The backup is checked before the database is replaced. The connection pool
+/// is closed while the file is replaced, then it's reopened with the same
+/// options.
+///
+/// # Errors
+/// Returns an error if:
+/// - The database is not opened
+/// - The backup is damaged or can't be opened with the options of the database
+/// - The database file can't be replaced
+pub async fn restore(name: &str, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let options = (*pool(name).await?.connect_options()).clone();
+    let db_path = options.get_filename().to_path_buf();
+
+    let mut conn = options
+        .clone()
+        .filename(path)
+        .create_if_missing(false)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("open backup {} failed", path.display()))?;
+    check(&mut conn)
+        .await
+        .with_context(|| format!("invalid backup {}", path.display()))?;
+    conn.close().await?;
+
+    // Operations on the database wait for the new pool
+    let mut pools = POOLS.lock().await;
+    if let Some(pool) = pools.remove(name) {
+        pool.close().await;
+    }
+
+    let result = replace_db(path, &db_path);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .connect_with(options)
+        .await?;
+    pools.insert(name.to_string(), pool);
+
+    result
+}
+
+/// Check the integrity of the database `name`
+///
+/// # Errors
+/// Returns an error if:
+/// - The database is not opened
+/// - The database is damaged
+pub async fn check_integrity(name: &str) -> Result<()> {
+    check(&pool(name).await?).await
+}
+
+/// Write the backup of today into `dir` and remove the oldest ones, only the
+/// latest `keep` backups are kept
+///
+/// The backups are named `<name>-<YYYY-MM-DD>.db`, the backup of today is
+/// replaced if it exists.
+///
+/// # Returns
+/// Returns the path of the backup of today
+///
+/// # Errors
+/// Returns an error if:
+/// - `keep` is 0
+/// - The database is not opened
+/// - The backup can't be written
+pub async fn backup_daily(name: &str, dir: impl AsRef<Path>, keep: usize) -> Result<PathBuf> {
+    if keep == 0 {
+        bail!("at least a backup should be kept");
+    }
+
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let today: String = sqlx::query_scalar("SELECT date('now', 'localtime')")
+        .fetch_one(&pool(name).await?)
+        .await?;
+    let path = dir.join(format!("{name}-{today}.{BACKUP_EXTENSION}"));
+    backup(name, &path).await?;
+
+    for old_backup in daily_backups(name, dir)?.into_iter().skip(keep) {
+        if let Err(e) = fs::remove_file(&old_backup) {
+            log::warn!("remove backup {} failed: {e}", old_backup.display());
+        }
+    }
+
+    Ok(path)
+}
+
+/// The daily backups of the database `name` in `dir`, the latest first
+pub fn daily_backups(name: &str, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let prefix = format!("{name}-");
+    let mut backups = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == BACKUP_EXTENSION)
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.strip_prefix(&prefix))
+                    .is_some_and(is_date)
+        })
+        .collect::<Vec<_>>();
+
+    // The dates in `YYYY-MM-DD` are ordered as the text
+    backups.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+
+    Ok(backups)
+}
+
+async fn check<'c>(executor: impl SqliteExecutor<'c>) -> Result<()> {
+    let result: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(executor)
+        .await?;
+
+    if result != "ok" {
+        bail!("database is damaged: {result}");
+    }
+
+    Ok(())
+}
+
+// Copy the backup next to the database and move it over the database. The
+// journal of the old database would be rolled back into the new one, so it's
+// removed
+fn replace_db(backup_path: &Path, db_path: &Path) -> Result<()> {
+    let tmp_path = tmp_path(db_path, "restore");
+    fs::copy(backup_path, &tmp_path)
+        .with_context(|| format!("copy backup {} failed", backup_path.display()))?;
+
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut journal = db_path.as_os_str().to_os_string();
+        journal.push(suffix);
+        let _ = fs::remove_file(journal);
+    }
+
+    fs::rename(&tmp_path, db_path)
+        .with_context(|| format!("replace {} failed", db_path.display()))?;
+
+    Ok(())
+}
+
+fn tmp_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(format!(".{suffix}"));
+    PathBuf::from(tmp_path)
+}
+
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.char_indices().all(|(index, c)| match index {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Query, Repository, create_db};
+    use pmacro::SqlTable;
+    use serde::{Deserialize, Serialize};
+
+    const TABLE_NAME: &str = "test_table";
+
+    #[derive(Serialize, Deserialize, SqlTable, Debug, Clone, PartialEq, Default)]
+    #[serde(default)]
+    struct Transcript {
+        #[table(key)]
+        id: String,
+
+        #[table(search)]
+        text: String,
+    }
+
+    fn transcript(id: &str, text: &str) -> Transcript {
+        Transcript {
+            id: id.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Test restoring a backup
+    #[tokio::test]
+    async fn test_backup_restore() -> Result<()> {
+        let (db, db_path) = ("backup", "/tmp/test-backup.db");
+        let backup_path = "/tmp/test-backup.db.bak";
+        let _ = fs::remove_file(db_path);
+
+        create_db(db, db_path).await?;
+        let repo = Repository::<Transcript>::new(db, TABLE_NAME);
+        repo.init().await?;
+        repo.insert(&transcript("uuid-1", "first transcript"))
+            .await?;
+
+        // An existing backup is replaced
+        backup(db, backup_path).await?;
+        repo.insert(&transcript("uuid-2", "second transcript"))
+            .await?;
+        backup(db, backup_path).await?;
+
+        repo.delete_all().await?;
+        assert!(repo.select_all().await?.is_empty());
+
+        // The search index is restored too
+        restore(db, backup_path).await?;
+        check_integrity(db).await?;
+        assert_eq!(repo.select_all().await?.len(), 2);
+        assert_eq!(
+            repo.search("second", &Query::new()).await?,
+            vec![transcript("uuid-2", "second transcript")]
+        );
+
+        // A damaged backup doesn't replace the database
+        fs::write(backup_path, "not a database")?;
+        assert!(restore(db, backup_path).await.is_err());
+        assert!(restore(db, "/tmp/test-backup-none.db").await.is_err());
+        assert_eq!(repo.select_all().await?.len(), 2);
+
+        assert!(backup("backup-none", backup_path).await.is_err());
+
+        Ok(())
+    }
+
+    /// Test the rotation of the daily backups
+    #[tokio::test]
+    async fn test_backup_daily() -> Result<()> {
+        let (db, db_path) = ("backup-daily", "/tmp/test-backup-daily.db");
+        let dir = Path::new("/tmp/test-backup-daily");
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_dir_all(dir);
+
+        create_db(db, db_path).await?;
+        Repository::<Transcript>::new(db, TABLE_NAME).init().await?;
+
+        fs::create_dir_all(dir)?;
+        for file in [
+            "backup-daily-2020-01-01.db",
+            "backup-daily-2020-01-03.db",
+            "backup-daily-2020-01-02.db",
+            "backup-daily-latest.db",
+            "other-2020-01-01.db",
+        ] {
+            fs::write(dir.join(file), "")?;
+        }
+
+        assert!(backup_daily(db, dir, 0).await.is_err());
+
+        let today = backup_daily(db, dir, 3).await?;
+        assert_eq!(
+            daily_backups(db, dir)?,
+            vec![
+                today.clone(),
+                dir.join("backup-daily-2020-01-03.db"),
+                dir.join("backup-daily-2020-01-02.db"),
+            ]
+        );
+
+        // The backup of today is replaced, the other files are kept
+        assert_eq!(backup_daily(db, dir, 1).await?, today);
+        assert_eq!(daily_backups(db, dir)?, vec![today]);
+        assert!(dir.join("backup-daily-latest.db").exists());
+        assert!(dir.join("other-2020-01-01.db").exists());
+
+        assert!(daily_backups(db, "/tmp/test-backup-daily-none")?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+//! At-rest encryption of the database with SQLCipher
+//!
+//! The whole database file is encrypted, including the index columns and the
+//! full-text search tables. SQLCipher derives the key from the passphrase with
+//! PBKDF2-HMAC-SHA512, so the passphrase itself is never stored.
+
+use super::{MAX_CONNECTIONS, POOLS, close_db, pool, set_pool};
+use anyhow::{Context, Result, bail};
+use sqlx::{
+    ConnectOptions, Connection, Pool,
+    sqlite::{Sqlite, SqliteConnectOptions, SqliteConnection, SqliteExecutor, SqlitePoolOptions},
+};
+use std::{fs, io::Read, str::FromStr};
+
+/// Header of a plaintext database, an encrypted one starts with a random salt
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Create or open an encrypted database and register its connection pool as
+/// `name`
+///
+/// A plaintext database at `db_path` is encrypted with `passphrase` first, so
+/// the data written before the encryption was enabled is kept.
+///
+/// # Errors
+/// Returns an error if:
+/// - The passphrase is empty
+/// - The passphrase is wrong
+/// - The database cannot be created or encrypted
+///
+/// # Example
+/// ```no_run
+/// use sqldb::create_encrypted_db;
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     create_encrypted_db("app", "/path/to/app.db", "correct horse battery staple").await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn create_encrypted_db(name: &str, db_path: &str, passphrase: &str) -> Result<()> {
+    if passphrase.is_empty() {
+        bail!("the passphrase of the database is empty");
+    }
+
+    if is_plaintext(db_path)? {
+        // The file is replaced by the encrypted one
+        close_db(name).await;
+
+        encrypt_plaintext_db(db_path, passphrase).await?;
+    }
+
+    let pool = connect(db_path, passphrase).await?;
+    set_pool(name, pool).await;
+
+    Ok(())
+}
+
+/// Change the passphrase of the database opened as `name` by
+/// `create_encrypted_db`
+///
+/// The connection pool is closed while the database is re-encrypted, then it's
+/// reopened with the passphrase in use.
+///
+/// # Errors
+/// Returns an error if:
+/// - The database is not opened
+/// - The new passphrase is empty
+/// - `passphrase` is not the current passphrase
+/// - The database query fails
+pub async fn change_passphrase(name: &str, passphrase: &str, new_passphrase: &str) -> Result<()> {
+    if new_passphrase.is_empty() {
+        bail!("the new passphrase of the database is empty");
+    }
+
+    let db_path = pool(name)
+        .await?
+        .connect_options()
+        .get_filename()
+        .to_string_lossy()
+        .to_string();
+
+    // The pool is kept if the passphrase is wrong
+    open(&db_path, passphrase).await?.close().await?;
+
+    // Operations on the database wait for the new pool
+    let mut pools = POOLS.lock().await;
+    if let Some(pool) = pools.remove(name) {
+        pool.close().await;
+    }
+
+    let result = rekey(&db_path, passphrase, new_passphrase).await;
+    let passphrase = if result.is_ok() {
+        new_passphrase
+    } else {
+        passphrase
+    };
+
+    pools.insert(name.to_string(), connect(&db_path, passphrase).await?);
+
+    result
+}
+
+async fn connect(db_path: &str, passphrase: &str) -> Result<Pool<Sqlite>> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .connect_with(options(db_path, passphrase)?)
+        .await?;
+
+    check_passphrase(&pool).await?;
+    Ok(pool)
+}
+
+async fn open(db_path: &str, passphrase: &str) -> Result<SqliteConnection> {
+    let mut conn = options(db_path, passphrase)?.connect().await?;
+    check_passphrase(&mut conn).await?;
+    Ok(conn)
+}
+
+// A wrong passphrase fails on the first read of the database only
+async fn check_passphrase<'c>(executor: impl SqliteExecutor<'c>) -> Result<()> {
+    sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+        .execute(executor)
+        .await
+        .context("wrong passphrase or not a database")?;
+
+    Ok(())
+}
+
+async fn rekey(db_path: &str, passphrase: &str, new_passphrase: &str) -> Result<()> {
+    let mut conn = open(db_path, passphrase).await?;
+
+    sqlx::query(&format!("PRAGMA rekey = {}", quote(new_passphrase)))
+        .execute(&mut conn)
+        .await?;
+
+    conn.close().await?;
+    Ok(())
+}
+
+// Export the plaintext database into an encrypted copy, which replaces it
+async fn encrypt_plaintext_db(db_path: &str, passphrase: &str) -> Result<()> {
+    let encrypted_path = format!("{db_path}.encrypted");
+    let _ = fs::remove_file(&encrypted_path);
+
+    // The attached database is created with the flags of the main one
+    let mut conn = SqliteConnectOptions::from_str(&format!("sqlite:{db_path}"))?
+        .create_if_missing(true)
+        .connect()
+        .await?;
+
+    sqlx::query("ATTACH DATABASE ? AS encrypted KEY ?")
+        .bind(&encrypted_path)
+        .bind(passphrase)
+        .execute(&mut conn)
+        .await?;
+
+    sqlx::query("SELECT sqlcipher_export('encrypted')")
+        .execute(&mut conn)
+        .await?;
+
+    sqlx::query("DETACH DATABASE encrypted")
+        .execute(&mut conn)
+        .await?;
+
+    conn.close().await?;
+
+    fs::rename(&encrypted_path, db_path)
+        .with_context(|| format!("replace {db_path} with the encrypted database failed"))?;
+
+    Ok(())
+}
+
+fn options(db_path: &str, passphrase: &str) -> Result<SqliteConnectOptions> {
+    Ok(
+        SqliteConnectOptions::from_str(&format!("sqlite:{db_path}"))?
+            .create_if_missing(true)
+            .pragma("key", quote(passphrase)),
+    )
+}
+
+// The pragma value is written into the SQL as it is
+fn quote(passphrase: &str) -> String {
+    format!("'{}'", passphrase.replace('\'', "''"))
+}
+
+fn is_plaintext(db_path: &str) -> Result<bool> {
+    let mut file = match fs::File::open(db_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    // An empty file is a new database
+    let mut header = [0; 16];
+    Ok(file.read_exact(&mut header).is_ok() && &header == PLAINTEXT_HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Query, Repository, create_db};
+    use pmacro::SqlTable;
+    use serde::{Deserialize, Serialize};
+
+    const TABLE_NAME: &str = "test_table";
+
+    #[derive(Serialize, Deserialize, SqlTable, Debug, Clone, PartialEq, Default)]
+    #[serde(default)]
+    struct Transcript {
+        #[table(key)]
+        id: String,
+
+        #[table(search)]
+        text: String,
+    }
+
+    fn transcript(id: &str, text: &str) -> Transcript {
+        Transcript {
+            id: id.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Test encrypting a plaintext database and changing its passphrase
+    #[tokio::test]
+    async fn test_encrypted_db() -> Result<()> {
+        let (db, db_path) = ("encrypted", "/tmp/test-encrypted.db");
+        let _ = fs::remove_file(db_path);
+
+        create_db(db, db_path).await?;
+        let repo = Repository::<Transcript>::new(db, TABLE_NAME);
+        repo.init().await?;
+        repo.insert(&transcript("uuid-1", "secret transcript"))
+            .await?;
+        assert!(is_plaintext(db_path)?);
+
+        // The records are kept by the encryption
+        create_encrypted_db(db, db_path, "pass'word").await?;
+        assert!(!is_plaintext(db_path)?);
+        assert!(!String::from_utf8_lossy(&fs::read(db_path)?).contains("secret"));
+        assert_eq!(
+            repo.search("secret", &Query::new()).await?,
+            vec![transcript("uuid-1", "secret transcript")]
+        );
+
+        repo.insert(&transcript("uuid-2", "another transcript"))
+            .await?;
+
+        assert!(create_encrypted_db(db, db_path, "").await.is_err());
+        assert!(create_encrypted_db(db, db_path, "wrong").await.is_err());
+        assert!(change_passphrase(db, "wrong", "new").await.is_err());
+
+        // The pool is still usable after a failed change
+        assert_eq!(repo.select_all().await?.len(), 2);
+
+        change_passphrase(db, "pass'word", "new").await?;
+        assert_eq!(repo.select_all().await?.len(), 2);
+
+        assert!(create_encrypted_db(db, db_path, "pass'word").await.is_err());
+        create_encrypted_db(db, db_path, "new").await?;
+        assert_eq!(repo.select("uuid-2").await?.text, "another transcript");
+
+        // The backup is encrypted with the same passphrase
+        let backup_path = "/tmp/test-encrypted.db.bak";
+        crate::backup(db, backup_path).await?;
+        assert!(!is_plaintext(backup_path)?);
+
+        repo.delete_all().await?;
+        crate::restore(db, backup_path).await?;
+        assert_eq!(repo.select_all().await?.len(), 2);
+
+        Ok(())
+    }
+
+    /// Test reopening a closed encrypted database the way the app starts
+    #[tokio::test]
+    async fn test_reopen_encrypted_db() -> Result<()> {
+        let (db, db_path) = ("reopen-encrypted", "/tmp/test-reopen-encrypted.db");
+        let _ = fs::remove_file(db_path);
+
+        create_encrypted_db(db, db_path, "right").await?;
+        let repo = Repository::<Transcript>::new(db, TABLE_NAME);
+        repo.init().await?;
+        repo.insert(&transcript("uuid-1", "secret transcript"))
+            .await?;
+        close_db(db).await;
+
+        // A wrong passphrase doesn't open the database
+        assert!(create_encrypted_db(db, db_path, "wrong").await.is_err());
+        assert!(repo.select_all().await.is_err());
+
+        create_encrypted_db(db, db_path, "right").await?;
+        assert_eq!(
+            repo.select_all().await?,
+            vec![transcript("uuid-1", "secret transcript")]
+        );
+
+        Ok(())
+    }
+}
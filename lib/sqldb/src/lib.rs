@@ -1,87 +1,111 @@
 //! SQL Database abstraction layer for Slint applications
 //!
 //! This library provides a simple SQLite database abstraction with async operations,
-//! connection pooling, and common CRUD operations. It's designed to work seamlessly
+//! connection pooling, and typed tables of records. It's designed to work seamlessly
 //! with the Slint GUI framework across multiple platforms.
 //!
 //! # Features
 //! - Async SQLite operations using `sqlx`
 //! - Connection pooling with configurable limits
+//! - Several named databases opened at the same time
 //! - Automatic database creation and table management
-//! - Common data operations (insert, update, delete, select)
+//! - Typed records mapped to tables with `pmacro::SqlTable`
+//! - Indexed columns with filtering, ordering and pagination
+//! - Full-text search of the records with FTS5
+//! - Online backup, restore and daily backups rotation
+//! - At-rest encryption with SQLCipher, enabled by the `sqlcipher` feature
 //! - Thread-safe operations with `tokio::sync::Mutex`
-//! - Serde serialization support for data structures
 //!
 //! # Examples
-//! ```no_run
-//! use sqldb::{create_db, entry};
+//! ```ignore
+//! use sqldb::{Op, Query, Repository, create_db};
 //! use anyhow::Result;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
 //!     // Create database
-//!     create_db("/path/to/database.db").await?;
-//!     
+//!     create_db("app", "/path/to/database.db").await?;
+//!
 //!     // Create table
-//!     entry::new("users").await?;
-//!     
+//!     let users = Repository::<User>::new("app", "users");
+//!     users.init().await?;
+//!
 //!     // Insert data
-//!     entry::insert("users", "user-123", "user data").await?;
-//!     
+//!     users.insert(&user).await?;
+//!
+//!     // Query data
+//!     let adults = users.select_where(&Query::new().filter("age", Op::Ge, 18)).await?;
+//!
 //!     Ok(())
 //! }
 //! ```
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
 use sqlx::{
+    Pool,
     migrate::MigrateDatabase,
     sqlite::{Sqlite, SqlitePoolOptions},
-    Pool,
 };
+use std::collections::HashMap;
 use tokio::sync::Mutex;
 
-pub mod entry;
+mod backup;
+#[cfg(feature = "sqlcipher")]
+mod cipher;
+mod search;
+mod table;
 
-/// Maximum number of concurrent database connections in the pool
+// The tests derive `Table` with `pmacro`, which refers to this crate by name
+#[cfg(test)]
+extern crate self as sqldb;
+
+pub use backup::{backup, backup_daily, check_integrity, daily_backups, restore};
+#[cfg(feature = "sqlcipher")]
+pub use cipher::{change_passphrase, create_encrypted_db};
+pub use search::SearchText;
+pub use table::{
+    Column, ColumnType, KEY_COLUMN, Op, Order, Query, ROWID_COLUMN, Repository, Table, Value,
+};
+
+/// Maximum number of concurrent database connections in a pool
 const MAX_CONNECTIONS: u32 = 3;
 
-/// Common database entry structure with UUID and data fields
+/// Connection pools of the opened databases by name
 ///
-/// This struct represents a generic database record that can be used
-/// across different tables and applications. It provides serialization
-/// support for both JSON and database operations.
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
-pub struct ComEntry {
-    /// Unique identifier for the entry
-    pub uuid: String,
-    /// Data payload stored as JSON string
-    pub data: String,
-}
+/// A database is registered by `create_db` and every operation on it looks
+/// up its pool by name, so several databases can be opened at the same time.
+static POOLS: Lazy<Mutex<HashMap<String, Pool<Sqlite>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
-/// Global database connection pool
+/// Get the connection pool of the database `name`
 ///
-/// This is a thread-safe connection pool that is lazily initialized
-/// when the database is first created. It uses a mutex to ensure
-/// safe concurrent access across async tasks.
-static POOL: Lazy<Mutex<Option<Pool<Sqlite>>>> = Lazy::new(|| Mutex::new(None));
+/// # Errors
+/// Returns an error if the database has not been opened by `create_db`
+pub async fn pool(name: &str) -> Result<Pool<Sqlite>> {
+    POOLS
+        .lock()
+        .await
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("database {name} is not opened"))
+}
 
-/// Get the global database connection pool
-///
-/// # Panics
-/// Panics if the database pool has not been initialized.
-/// Use `create_db()` to initialize the pool first.
-async fn pool() -> Pool<Sqlite> {
-    POOL.lock().await.clone().unwrap()
+// Register the pool of `name`, the pool it replaces is closed
+async fn set_pool(name: &str, pool: Pool<Sqlite>) {
+    let old_pool = POOLS.lock().await.insert(name.to_string(), pool);
+    if let Some(old_pool) = old_pool {
+        old_pool.close().await;
+    }
 }
 
-/// Create a new SQLite database and initialize the connection pool
+/// Create a new SQLite database and register its connection pool as `name`
 ///
 /// This function creates the database file if it doesn't exist and
 /// sets up a connection pool with the configured maximum connections.
+/// A database already opened as `name` is closed.
 ///
 /// # Arguments
+/// * `name` - Name the database is referred to by
 /// * `db_path` - Path to the SQLite database file
 ///
 /// # Errors
@@ -95,11 +119,12 @@ async fn pool() -> Pool<Sqlite> {
 ///
 /// #[tokio::main]
 /// async fn main() -> anyhow::Result<()> {
-///     create_db("/path/to/app.db").await?;
+///     create_db("config", "/path/to/config.db").await?;
+///     create_db("history", "/path/to/history.db").await?;
 ///     Ok(())
 /// }
 /// ```
-pub async fn create_db(db_path: &str) -> Result<()> {
+pub async fn create_db(name: &str, db_path: &str) -> Result<()> {
     Sqlite::create_database(db_path).await?;
 
     let pool = SqlitePoolOptions::new()
@@ -107,14 +132,23 @@ pub async fn create_db(db_path: &str) -> Result<()> {
         .connect(&format!("sqlite:{}", db_path))
         .await?;
 
-    *POOL.lock().await = Some(pool);
+    set_pool(name, pool).await;
 
     Ok(())
 }
 
+/// Close the database `name`, nothing is done if it's not opened
+pub async fn close_db(name: &str) {
+    let pool = POOLS.lock().await.remove(name);
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
+}
+
 /// Check if a table exists in the database
 ///
 /// # Arguments
+/// * `name` - Name of the database
 /// * `table_name` - Name of the table to check
 ///
 /// # Returns
@@ -122,33 +156,42 @@ pub async fn create_db(db_path: &str) -> Result<()> {
 ///
 /// # Errors
 /// Returns an error if:
+/// - The database is not opened
 /// - The database query fails
 /// - The table does not exist
-pub async fn is_table_exist(table_name: &str) -> Result<()> {
+pub async fn is_table_exist(name: &str, table_name: &str) -> Result<()> {
     sqlx::query("SELECT name FROM sqlite_master WHERE type='table' AND name=?")
         .bind(table_name)
-        .fetch_one(&pool().await)
+        .fetch_one(&pool(name).await?)
         .await?;
 
     Ok(())
 }
 
-/// Drop a table from the database
+/// Drop a table and its full-text search table from the database
 ///
 /// # Arguments
+/// * `name` - Name of the database
 /// * `table_name` - Name of the table to drop
 ///
 /// # Errors
 /// Returns an error if:
+/// - The database is not opened
 /// - The table does not exist
 /// - The database query fails
 ///
 /// # Warning
 /// This operation is destructive and cannot be undone.
 /// Make sure to backup important data before calling this function.
-pub async fn drop_table(table_name: &str) -> Result<()> {
+pub async fn drop_table(name: &str, table_name: &str) -> Result<()> {
+    let pool = pool(name).await?;
+
     sqlx::query(&format!("DROP TABLE {}", table_name))
-        .execute(&pool().await)
+        .execute(&pool)
+        .await?;
+
+    sqlx::query(&format!("DROP TABLE IF EXISTS {}_fts", table_name))
+        .execute(&pool)
         .await?;
 
     Ok(())
@@ -158,114 +201,81 @@ pub async fn drop_table(table_name: &str) -> Result<()> {
 mod tests {
     use super::*;
 
-    static MTX: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct TestRecord {
+        id: String,
+    }
+
+    impl Table for TestRecord {
+        const INDEXES: &'static [Column] = &[];
+
+        fn key(&self) -> String {
+            self.id.clone()
+        }
+    }
 
     /// Initialize test database with a test table
-    pub async fn init(db_path: &str) {
-        create_db(db_path).await.expect("create db");
-        entry::new("test").await.expect("account table failed");
+    pub async fn init(name: &str, db_path: &str) {
+        create_db(name, db_path).await.expect("create db");
+        Repository::<TestRecord>::new(name, "test")
+            .init()
+            .await
+            .expect("account table failed");
     }
 
     /// Test database creation
     #[tokio::test]
     async fn test_create_db() -> Result<()> {
-        let _mtx = MTX.lock().await;
-        
         let test_db_path = "/tmp/test-create-db.db";
-        
+
         // Clean up any existing test database
         let _ = std::fs::remove_file(test_db_path);
-        
-        create_db(test_db_path).await?;
-        
+
+        create_db("create-db", test_db_path).await?;
+
         // Verify database file was created
         assert!(std::path::Path::new(test_db_path).exists());
-        
+        assert!(pool("create-db").await.is_ok());
+
+        close_db("create-db").await;
+        assert!(pool("create-db").await.is_err());
+
         Ok(())
     }
 
     /// Test table existence checking
     #[tokio::test]
     async fn test_db_is_table_exist() -> Result<()> {
-        let _mtx = MTX.lock().await;
-
         let test_db_path = "/tmp/test-is-table-exist.db";
-        init(test_db_path).await;
-        
+        init("is-table-exist", test_db_path).await;
+
         // Test non-existent table
-        assert!(is_table_exist("hello").await.is_err());
-        
+        assert!(is_table_exist("is-table-exist", "hello").await.is_err());
+
         // Test existing table
-        assert!(is_table_exist("test").await.is_ok());
-        
+        assert!(is_table_exist("is-table-exist", "test").await.is_ok());
+
+        // Test database that is not opened
+        assert!(is_table_exist("hello", "test").await.is_err());
+
         Ok(())
     }
 
     /// Test table dropping
     #[tokio::test]
     async fn test_db_drop_table() -> Result<()> {
-        let _mtx = MTX.lock().await;
-
         let test_db_path = "/tmp/test-drop-table.db";
-        init(test_db_path).await;
-        
+        init("drop-table", test_db_path).await;
+
         // Test dropping non-existent table
-        assert!(drop_table("hello").await.is_err());
-        
-        // Test dropping existing table
-        assert!(drop_table("test").await.is_ok());
-        
-        // Verify table no longer exists
-        assert!(is_table_exist("test").await.is_err());
-        
-        Ok(())
-    }
+        assert!(drop_table("drop-table", "hello").await.is_err());
 
-    /// Test ComEntry struct serialization
-    #[test]
-    fn test_com_entry_serialization() {
-        let entry = ComEntry {
-            uuid: "test-uuid".to_string(),
-            data: "test-data".to_string(),
-        };
-
-        // Test serialization to JSON
-        let json = serde_json::to_string(&entry).unwrap();
-        assert!(json.contains("test-uuid"));
-        assert!(json.contains("test-data"));
-
-        // Test deserialization from JSON
-        let deserialized: ComEntry = serde_json::from_str(&json).unwrap();
-        assert_eq!(deserialized.uuid, "test-uuid");
-        assert_eq!(deserialized.data, "test-data");
-    }
+        // Test dropping existing table
+        assert!(drop_table("drop-table", "test").await.is_ok());
 
-    /// Test ComEntry struct cloning
-    #[test]
-    fn test_com_entry_clone() {
-        let original = ComEntry {
-            uuid: "original-uuid".to_string(),
-            data: "original-data".to_string(),
-        };
-
-        let cloned = original.clone();
-        assert_eq!(original.uuid, cloned.uuid);
-        assert_eq!(original.data, cloned.data);
-        
-        // Verify they are separate instances
-        assert!(!std::ptr::eq(&original, &cloned));
-    }
+        // Verify table no longer exists
+        assert!(is_table_exist("drop-table", "test").await.is_err());
 
-    /// Test ComEntry struct debug formatting
-    #[test]
-    fn test_com_entry_debug() {
-        let entry = ComEntry {
-            uuid: "debug-uuid".to_string(),
-            data: "debug-data".to_string(),
-        };
-
-        let debug_output = format!("{:?}", entry);
-        assert!(debug_output.contains("debug-uuid"));
-        assert!(debug_output.contains("debug-data"));
+        Ok(())
     }
 }
@@ -0,0 +1,172 @@
+//! Full-text search of the records
+//!
+//! The text of the searchable fields is kept in an FTS5 table named
+//! `<table>_fts`, whose rowid is the `id` of the record. The trigram
+//! tokenizer is used, so text without spaces between the words like
+//! Chinese is searched as well as the other languages.
+
+use super::table::Value;
+
+/// Text of a field indexed for full-text search
+///
+/// # Example
+/// ```
+/// use sqldb::SearchText;
+///
+/// struct Subtitle {
+///     text: String,
+///     translation: String,
+/// }
+///
+/// impl SearchText for Subtitle {
+///     fn push_search_text(&self, text: &mut String) {
+///         self.text.push_search_text(text);
+///         self.translation.push_search_text(text);
+///     }
+/// }
+/// ```
+pub trait SearchText {
+    /// Append the text, a line each
+    fn push_search_text(&self, text: &mut String);
+}
+
+impl SearchText for str {
+    fn push_search_text(&self, text: &mut String) {
+        if self.trim().is_empty() {
+            return;
+        }
+
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(self);
+    }
+}
+
+impl SearchText for String {
+    fn push_search_text(&self, text: &mut String) {
+        self.as_str().push_search_text(text);
+    }
+}
+
+impl<T: SearchText> SearchText for Option<T> {
+    fn push_search_text(&self, text: &mut String) {
+        if let Some(value) = self {
+            value.push_search_text(text);
+        }
+    }
+}
+
+impl<T: SearchText> SearchText for Vec<T> {
+    fn push_search_text(&self, text: &mut String) {
+        for item in self {
+            item.push_search_text(text);
+        }
+    }
+}
+
+// The trigram tokenizer only matches terms of 3 characters at least
+const MIN_MATCH_CHARS: usize = 3;
+
+/// Condition of the searched terms on the `text` column of the FTS table
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SearchPattern {
+    /// A FTS5 query with all the terms, ranked by relevance
+    Match(String),
+
+    /// `LIKE` patterns of the terms, for terms too short to match
+    Like(Vec<String>),
+}
+
+impl SearchPattern {
+    /// `None` if there is no term in `text`
+    pub fn new(text: &str) -> Option<Self> {
+        let terms = text.split_whitespace().collect::<Vec<_>>();
+        if terms.is_empty() {
+            return None;
+        }
+
+        if terms
+            .iter()
+            .all(|term| term.chars().count() >= MIN_MATCH_CHARS)
+        {
+            // Quoted terms are matched as they are, `"` is escaped by doubling it
+            let query = terms
+                .iter()
+                .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            return Some(SearchPattern::Match(query));
+        }
+
+        let patterns = terms
+            .iter()
+            .map(|term| {
+                let term = term
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                format!("%{term}%")
+            })
+            .collect();
+        Some(SearchPattern::Like(patterns))
+    }
+
+    /// The SQL conditions on the `text` column of `fts_table`
+    pub fn conditions(&self, fts_table: &str) -> Vec<String> {
+        match self {
+            SearchPattern::Match(_) => vec![format!("{fts_table}.text MATCH ?")],
+            SearchPattern::Like(patterns) => patterns
+                .iter()
+                .map(|_| format!("{fts_table}.text LIKE ? ESCAPE '\\'"))
+                .collect(),
+        }
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        match self {
+            SearchPattern::Match(query) => vec![Value::Text(query.clone())],
+            SearchPattern::Like(patterns) => patterns
+                .iter()
+                .map(|pattern| Value::Text(pattern.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the text of the searchable fields
+    #[test]
+    fn test_search_text() {
+        let mut text = String::default();
+        "hello".push_search_text(&mut text);
+        String::from(" ").push_search_text(&mut text);
+        None::<String>.push_search_text(&mut text);
+        vec![Some("你好".to_string()), None].push_search_text(&mut text);
+        assert_eq!(text, "hello\n你好");
+    }
+
+    /// Test the patterns of the searched terms
+    #[test]
+    fn test_search_pattern() {
+        assert_eq!(SearchPattern::new("  "), None);
+
+        assert_eq!(
+            SearchPattern::new("hello \"world\""),
+            Some(SearchPattern::Match(
+                "\"hello\" \"\"\"world\"\"\"".to_string()
+            ))
+        );
+
+        assert_eq!(
+            SearchPattern::new("你好 100%_"),
+            Some(SearchPattern::Like(vec![
+                "%你好%".to_string(),
+                "%100\\%\\_%".to_string()
+            ]))
+        );
+    }
+}
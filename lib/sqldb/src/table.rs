@@ -0,0 +1,1129 @@
+//! Typed tables of records
+//!
+//! Each record is stored as JSON in the `data` column and keyed by the
+//! `uuid` column. The fields declared in `Table::INDEXES` are generated
+//! columns extracted from the JSON and indexed, so records can be filtered,
+//! ordered and paginated in SQL instead of loading the whole table. Fields
+//! that aren't indexed keep their `serde` defaults, so adding one to a
+//! record needs no migration.
+//!
+//! Use `pmacro::SqlTable` to derive `Table` for a struct.
+
+use super::{pool, search::SearchPattern};
+use anyhow::{Result, bail};
+use serde::{Serialize, de::DeserializeOwned};
+use sqlx::{
+    Arguments, Pool,
+    sqlite::{Sqlite, SqliteArguments, SqliteConnection},
+};
+use std::marker::PhantomData;
+
+/// Column of the record key
+pub const KEY_COLUMN: &str = "uuid";
+
+/// Column of the insertion order
+pub const ROWID_COLUMN: &str = "id";
+
+/// SQL type of an index column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+}
+
+/// Index column generated from the field of the same name in the JSON record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+}
+
+impl Column {
+    pub const fn new(name: &'static str, ty: ColumnType) -> Self {
+        Self { name, ty }
+    }
+}
+
+/// Record stored in a table
+///
+/// # Example
+/// ```ignore
+/// use pmacro::SqlTable;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, SqlTable)]
+/// struct Recording {
+///     #[table(key)]
+///     id: String,
+///
+///     #[table(index)]
+///     file: String,
+///
+///     #[table(index)]
+///     size: u64,
+/// }
+/// ```
+pub trait Table: Serialize + DeserializeOwned + Send + Unpin + 'static {
+    /// Fields that can be filtered and ordered by
+    const INDEXES: &'static [Column];
+
+    /// Whether the records are indexed for `Repository::search`
+    const SEARCHABLE: bool = false;
+
+    /// Unique key of the record
+    fn key(&self) -> String;
+
+    /// Text of the record indexed for `Repository::search`
+    fn search_text(&self) -> String {
+        String::default()
+    }
+}
+
+/// Value compared with a column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+macro_rules! value_from_integer {
+    ($($ty:ty),*) => {
+        $(impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Value::Integer(value as i64)
+            }
+        })*
+    };
+}
+
+value_from_integer!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, bool);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Real(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Real(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Comparison of a filter. `Eq` and `Ne` with `Value::Null` test for `NULL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+
+    /// SQL `LIKE` pattern, `%` matches any text and `_` one character
+    Like,
+}
+
+impl Op {
+    fn as_sql(&self, value: &Value) -> &'static str {
+        match (self, value) {
+            (Op::Eq, Value::Null) => "IS",
+            (Op::Ne, Value::Null) => "IS NOT",
+            (Op::Eq, _) => "=",
+            (Op::Ne, _) => "!=",
+            (Op::Lt, _) => "<",
+            (Op::Le, _) => "<=",
+            (Op::Gt, _) => ">",
+            (Op::Ge, _) => ">=",
+            (Op::Like, _) => "LIKE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    column: String,
+    op: Op,
+    value: Value,
+}
+
+/// Filters, ordering and pagination of a select
+///
+/// The filters are joined with `AND`. Records are ordered by the insertion
+/// order after the given orders, so pages don't overlap.
+///
+/// # Example
+/// ```no_run
+/// use sqldb::{Op, Order, Query};
+///
+/// // The third page of the large files, newest first
+/// let query = Query::new()
+///     .filter("size", Op::Gt, 1024 * 1024)
+///     .order_by(sqldb::ROWID_COLUMN, Order::Desc)
+///     .page(2, 20);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    filters: Vec<Filter>,
+    orders: Vec<(String, Order)>,
+    limit: Option<u32>,
+    offset: u32,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, column: impl ToString, op: Op, value: impl Into<Value>) -> Self {
+        self.filters.push(Filter {
+            column: column.to_string(),
+            op,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn order_by(mut self, column: impl ToString, order: Order) -> Self {
+        self.orders.push((column.to_string(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Page `page` counted from 0, of `page_size` records
+    pub fn page(self, page: u32, page_size: u32) -> Self {
+        self.limit(page_size).offset(page.saturating_mul(page_size))
+    }
+
+    // The `conditions` and the filters as a `WHERE` clause, the columns are
+    // checked as they are written into the SQL
+    fn where_clause<T: Table>(&self, table: &str, mut conditions: Vec<String>) -> Result<String> {
+        for filter in &self.filters {
+            check_column::<T>(&filter.column)?;
+            conditions.push(format!(
+                "{table}.{} {} ?",
+                filter.column,
+                filter.op.as_sql(&filter.value)
+            ));
+        }
+
+        if conditions.is_empty() {
+            return Ok(String::default());
+        }
+
+        Ok(format!(" WHERE {}", conditions.join(" AND ")))
+    }
+
+    // `default_order` is used when no order is given
+    fn order_clause<T: Table>(&self, table: &str, default_order: Option<String>) -> Result<String> {
+        let mut orders = vec![];
+        for (column, order) in &self.orders {
+            check_column::<T>(column)?;
+            orders.push(format!(
+                "{table}.{column} {}",
+                if *order == Order::Asc { "ASC" } else { "DESC" }
+            ));
+        }
+
+        if orders.is_empty()
+            && let Some(order) = default_order
+        {
+            orders.push(order);
+        }
+
+        if !self.orders.iter().any(|(column, _)| column == ROWID_COLUMN) {
+            orders.push(format!("{table}.{ROWID_COLUMN} ASC"));
+        }
+
+        Ok(format!(" ORDER BY {}", orders.join(", ")))
+    }
+
+    // The values of the `conditions` and the filters of `where_clause`
+    fn arguments(&self, values: Vec<Value>) -> Result<SqliteArguments<'static>> {
+        let mut arguments = SqliteArguments::default();
+        let filter_values = self.filters.iter().map(|filter| filter.value.clone());
+
+        for value in values.into_iter().chain(filter_values) {
+            match value {
+                Value::Null => arguments.add(None::<String>),
+                Value::Integer(value) => arguments.add(value),
+                Value::Real(value) => arguments.add(value),
+                Value::Text(value) => arguments.add(value),
+            }
+            .map_err(|e| anyhow::anyhow!(e))?;
+        }
+
+        Ok(arguments)
+    }
+
+    fn limit_clause(&self) -> String {
+        // SQLite only takes an `OFFSET` after a `LIMIT`, -1 is no limit
+        match (self.limit, self.offset) {
+            (None, 0) => String::default(),
+            (limit, offset) => format!(
+                " LIMIT {} OFFSET {offset}",
+                limit.map_or(-1, |limit| limit as i64)
+            ),
+        }
+    }
+}
+
+fn check_column<T: Table>(column: &str) -> Result<()> {
+    if column == KEY_COLUMN
+        || column == ROWID_COLUMN
+        || T::INDEXES.iter().any(|index| index.name == column)
+    {
+        Ok(())
+    } else {
+        bail!("`{column}` isn't an index column")
+    }
+}
+
+/// Operations on the records of type `T` in a table
+///
+/// # Example
+/// ```ignore
+/// use sqldb::{Repository, Op, Query};
+///
+/// let recordings = Repository::<Recording>::new("app", "recordings");
+/// recordings.init().await?;
+/// recordings.insert(&recording).await?;
+///
+/// let found = recordings
+///     .select_where(&Query::new().filter("file", Op::Eq, "demo.mp4"))
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Repository<T> {
+    db: String,
+    table: String,
+    _record: PhantomData<fn() -> T>,
+}
+
+impl<T: Table> Repository<T> {
+    /// Records in `table` of the database opened as `db`
+    pub fn new(db: impl ToString, table: impl ToString) -> Self {
+        Self {
+            db: db.to_string(),
+            table: table.to_string(),
+            _record: PhantomData,
+        }
+    }
+
+    pub fn db(&self) -> &str {
+        &self.db
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    async fn pool(&self) -> Result<Pool<Sqlite>> {
+        pool(&self.db).await
+    }
+
+    fn fts_table(&self) -> String {
+        format!("{}_fts", self.table)
+    }
+
+    /// Create the table, the index columns and the full-text search table
+    /// that don't exist yet
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - The database is not opened
+    /// - An index column exists with another definition
+    pub async fn init(&self) -> Result<()> {
+        let table = &self.table;
+        let pool = self.pool().await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                 {ROWID_COLUMN} INTEGER PRIMARY KEY,
+                 {KEY_COLUMN} TEXT NOT NULL UNIQUE,
+                 data TEXT NOT NULL
+                 )"
+        ))
+        .execute(&pool)
+        .await?;
+
+        // Generated columns are only listed by `table_xinfo`
+        let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_xinfo(?)")
+            .bind(table)
+            .fetch_all(&pool)
+            .await?;
+
+        for index in T::INDEXES {
+            if !columns.iter().any(|(name,)| name == index.name) {
+                sqlx::query(&format!(
+                    "ALTER TABLE {table} ADD COLUMN {name} {ty} \
+                     GENERATED ALWAYS AS (json_extract(data, '$.{name}')) VIRTUAL",
+                    name = index.name,
+                    ty = index.ty.as_sql(),
+                ))
+                .execute(&pool)
+                .await?;
+            }
+
+            sqlx::query(&format!(
+                "CREATE INDEX IF NOT EXISTS {table}_{name} ON {table} ({name})",
+                name = index.name
+            ))
+            .execute(&pool)
+            .await?;
+        }
+
+        if T::SEARCHABLE {
+            self.init_search().await?;
+        }
+
+        Ok(())
+    }
+
+    // The records written before the table was searchable are indexed
+    async fn init_search(&self) -> Result<()> {
+        let fts_table = self.fts_table();
+        let mut tx = self.pool().await?.begin().await?;
+
+        sqlx::query(&format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {fts_table} USING fts5(text, tokenize = 'trigram')"
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        let unindexed: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT {ROWID_COLUMN}, data FROM {} \
+             WHERE {ROWID_COLUMN} NOT IN (SELECT rowid FROM {fts_table})",
+            self.table
+        ))
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (id, data) in unindexed {
+            match serde_json::from_str::<T>(&data) {
+                Ok(record) => self.index_record(&mut tx, id, &record).await?,
+                Err(e) => log::warn!("parse record of {} failed: {e}", self.table),
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Replace the indexed text of the record `id`
+    async fn index_record(&self, conn: &mut SqliteConnection, id: i64, record: &T) -> Result<()> {
+        if !T::SEARCHABLE {
+            return Ok(());
+        }
+
+        let fts_table = self.fts_table();
+        sqlx::query(&format!("DELETE FROM {fts_table} WHERE rowid=?"))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {fts_table} (rowid, text) VALUES (?, ?)"
+        ))
+        .bind(id)
+        .bind(record.search_text())
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn unindex_records(&self, conn: &mut SqliteConnection, ids: &[i64]) -> Result<()> {
+        if !T::SEARCHABLE {
+            return Ok(());
+        }
+
+        let fts_table = self.fts_table();
+        for id in ids {
+            sqlx::query(&format!("DELETE FROM {fts_table} WHERE rowid=?"))
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert a record
+    ///
+    /// # Errors
+    /// Returns an error if a record with the same key exists
+    pub async fn insert(&self, record: &T) -> Result<()> {
+        let mut tx = self.pool().await?.begin().await?;
+
+        let id: i64 = sqlx::query_scalar(&format!(
+            "INSERT INTO {} ({KEY_COLUMN}, data) VALUES (?, ?) RETURNING {ROWID_COLUMN}",
+            self.table
+        ))
+        .bind(record.key())
+        .bind(serde_json::to_string(record)?)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        self.index_record(&mut tx, id, record).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Update the record of the same key, nothing is done if it doesn't exist
+    pub async fn update(&self, record: &T) -> Result<()> {
+        let mut tx = self.pool().await?.begin().await?;
+
+        let id: Option<i64> = sqlx::query_scalar(&format!(
+            "UPDATE {} SET data=? WHERE {KEY_COLUMN}=? RETURNING {ROWID_COLUMN}",
+            self.table
+        ))
+        .bind(serde_json::to_string(record)?)
+        .bind(record.key())
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(id) = id {
+            self.index_record(&mut tx, id, record).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Insert the record or update the record of the same key
+    pub async fn upsert(&self, record: &T) -> Result<()> {
+        let mut tx = self.pool().await?.begin().await?;
+
+        let id: i64 = sqlx::query_scalar(&format!(
+            "INSERT INTO {} ({KEY_COLUMN}, data) VALUES (?, ?) \
+             ON CONFLICT({KEY_COLUMN}) DO UPDATE SET data=excluded.data \
+             RETURNING {ROWID_COLUMN}",
+            self.table
+        ))
+        .bind(record.key())
+        .bind(serde_json::to_string(record)?)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        self.index_record(&mut tx, id, record).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.delete_where(&Query::new().filter(KEY_COLUMN, Op::Eq, key))
+            .await
+    }
+
+    /// Delete the records matched by the filters of `query`
+    pub async fn delete_where(&self, query: &Query) -> Result<()> {
+        let sql = format!(
+            "DELETE FROM {table}{} RETURNING {ROWID_COLUMN}",
+            query.where_clause::<T>(&self.table, vec![])?,
+            table = self.table,
+        );
+
+        let mut tx = self.pool().await?.begin().await?;
+        let ids: Vec<i64> = sqlx::query_scalar_with(&sql, query.arguments(vec![])?)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        self.unindex_records(&mut tx, &ids).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_all(&self) -> Result<()> {
+        let mut tx = self.pool().await?.begin().await?;
+
+        sqlx::query(&format!("DELETE FROM {}", self.table))
+            .execute(&mut *tx)
+            .await?;
+
+        if T::SEARCHABLE {
+            sqlx::query(&format!("DELETE FROM {}", self.fts_table()))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Select the record of `key`
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - The record does not exist
+    /// - The record can't be parsed as `T`
+    pub async fn select(&self, key: &str) -> Result<T> {
+        let (data,): (String,) = sqlx::query_as(&format!(
+            "SELECT data FROM {} WHERE {KEY_COLUMN}=?",
+            self.table
+        ))
+        .bind(key)
+        .fetch_one(&self.pool().await?)
+        .await?;
+
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Select all the records in the insertion order
+    pub async fn select_all(&self) -> Result<Vec<T>> {
+        self.select_where(&Query::new()).await
+    }
+
+    /// Select the records matched by `query`. Records that can't be parsed as
+    /// `T` are skipped
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - A column of `query` isn't `KEY_COLUMN`, `ROWID_COLUMN` or an index
+    /// - The database query fails
+    pub async fn select_where(&self, query: &Query) -> Result<Vec<T>> {
+        let table = &self.table;
+        let sql = format!(
+            "SELECT data FROM {table}{}{}{}",
+            query.where_clause::<T>(table, vec![])?,
+            query.order_clause::<T>(table, None)?,
+            query.limit_clause()
+        );
+
+        self.fetch_records(&sql, query.arguments(vec![])?).await
+    }
+
+    /// Search the records containing all the whitespace separated terms of
+    /// `text` in their searchable fields, and matched by `query`. The records
+    /// are ordered by relevance if `query` has no order. An empty `text`
+    /// matches all the records
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - `T` has no searchable field
+    /// - A column of `query` isn't `KEY_COLUMN`, `ROWID_COLUMN` or an index
+    /// - The database query fails
+    ///
+    /// # Example
+    /// ```ignore
+    /// use sqldb::{Query, Repository};
+    ///
+    /// let transcripts = Repository::<Transcript>::new("app", "transcripts");
+    /// let found = transcripts.search("rust async", &Query::new().limit(20)).await?;
+    /// ```
+    pub async fn search(&self, text: &str, query: &Query) -> Result<Vec<T>> {
+        if !T::SEARCHABLE {
+            bail!("{} has no searchable field", self.table);
+        }
+
+        let Some(pattern) = SearchPattern::new(text) else {
+            return self.select_where(query).await;
+        };
+
+        let table = &self.table;
+        let fts_table = self.fts_table();
+
+        // Only a match has a rank
+        let rank = matches!(pattern, SearchPattern::Match(_)).then(|| format!("{fts_table}.rank"));
+
+        let sql = format!(
+            "SELECT {table}.data FROM {table} \
+             JOIN {fts_table} ON {fts_table}.rowid = {table}.{ROWID_COLUMN}{}{}{}",
+            query.where_clause::<T>(table, pattern.conditions(&fts_table))?,
+            query.order_clause::<T>(table, rank)?,
+            query.limit_clause()
+        );
+
+        self.fetch_records(&sql, query.arguments(pattern.values())?)
+            .await
+    }
+
+    async fn fetch_records(
+        &self,
+        sql: &str,
+        arguments: SqliteArguments<'static>,
+    ) -> Result<Vec<T>> {
+        let rows: Vec<(String,)> = sqlx::query_as_with(sql, arguments)
+            .fetch_all(&self.pool().await?)
+            .await?;
+
+        let records = rows
+            .into_iter()
+            .filter_map(|(data,)| match serde_json::from_str::<T>(&data) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    log::warn!("parse record of {} failed: {e}", self.table);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(records)
+    }
+
+    /// Count the records matched by the filters of `query`
+    pub async fn count(&self, query: &Query) -> Result<i64> {
+        let sql = format!(
+            "SELECT COUNT(*) FROM {}{}",
+            self.table,
+            query.where_clause::<T>(&self.table, vec![])?
+        );
+
+        Ok(sqlx::query_scalar_with(&sql, query.arguments(vec![])?)
+            .fetch_one(&self.pool().await?)
+            .await?)
+    }
+
+    /// Returns `Ok(())` if the record of `key` exists, otherwise an error
+    pub async fn is_exist(&self, key: &str) -> Result<()> {
+        let count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {} WHERE {KEY_COLUMN}=?",
+            self.table
+        ))
+        .bind(key)
+        .fetch_one(&self.pool().await?)
+        .await?;
+
+        if count == 0 {
+            bail!("no found record {key} in {}", self.table);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_db;
+    use pmacro::SqlTable;
+    use serde::Deserialize;
+
+    const TABLE_NAME: &str = "test_table";
+
+    #[derive(Serialize, Deserialize, SqlTable, Debug, Clone, PartialEq, Default)]
+    #[serde(default)]
+    struct Recording {
+        #[table(key)]
+        id: String,
+
+        #[table(index)]
+        file: String,
+
+        #[table(index)]
+        size: u64,
+
+        #[table(index)]
+        favorite: bool,
+
+        note: String,
+    }
+
+    #[derive(Serialize, Deserialize, SqlTable, Debug, Clone, PartialEq, Default)]
+    #[serde(default)]
+    struct Transcript {
+        #[table(key)]
+        id: String,
+
+        #[table(index)]
+        size: u64,
+
+        #[table(search)]
+        title: String,
+
+        #[table(search)]
+        lines: Vec<String>,
+    }
+
+    fn transcript(id: &str, size: u64, title: &str, lines: &[&str]) -> Transcript {
+        Transcript {
+            id: id.to_string(),
+            size,
+            title: title.to_string(),
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    fn recording(id: &str, file: &str, size: u64) -> Recording {
+        Recording {
+            id: id.to_string(),
+            file: file.to_string(),
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// Create a fresh database with the test table
+    async fn init(name: &str, db_path: &str) -> Result<Repository<Recording>> {
+        let _ = std::fs::remove_file(db_path);
+        create_db(name, db_path).await?;
+
+        let repo = Repository::<Recording>::new(name, TABLE_NAME);
+        repo.init().await?;
+        Ok(repo)
+    }
+
+    /// Test the derived table mapping
+    #[test]
+    fn test_derive_table() {
+        assert_eq!(
+            Recording::INDEXES,
+            &[
+                Column::new("file", ColumnType::Text),
+                Column::new("size", ColumnType::Integer),
+                Column::new("favorite", ColumnType::Integer),
+            ]
+        );
+        assert_eq!(recording("uuid-1", "a.mp4", 1).key(), "uuid-1");
+
+        assert_eq!(
+            transcript("uuid-1", 1, "Rust", &["hello", "", "world"]).search_text(),
+            "Rust\nhello\nworld"
+        );
+    }
+
+    /// Test comprehensive CRUD operations
+    #[tokio::test]
+    async fn test_crud() -> Result<()> {
+        let repo = init("table-crud", "/tmp/test-table-crud.db").await?;
+
+        // Create
+        repo.insert(&recording("uuid-1", "a.mp4", 1)).await?;
+        repo.insert(&recording("uuid-2", "b.mp4", 2)).await?;
+        assert!(repo.insert(&recording("uuid-1", "c.mp4", 3)).await.is_err());
+
+        // Read
+        assert_eq!(
+            repo.select("uuid-1").await?,
+            recording("uuid-1", "a.mp4", 1)
+        );
+        assert!(repo.select("uuid-0").await.is_err());
+        assert!(repo.is_exist("uuid-1").await.is_ok());
+        assert!(repo.is_exist("uuid-0").await.is_err());
+
+        // Update
+        repo.update(&recording("uuid-1", "a.mkv", 10)).await?;
+        assert_eq!(repo.select("uuid-1").await?.file, "a.mkv");
+
+        // Note: SQLite UPDATE on non-existent rows doesn't error, it just affects 0 rows
+        repo.update(&recording("uuid-0", "x.mp4", 0)).await?;
+        assert_eq!(repo.count(&Query::new()).await?, 2);
+
+        repo.upsert(&recording("uuid-2", "b.mkv", 20)).await?;
+        repo.upsert(&recording("uuid-3", "c.mp4", 3)).await?;
+        assert_eq!(repo.select("uuid-2").await?.size, 20);
+        assert_eq!(repo.count(&Query::new()).await?, 3);
+
+        // Delete
+        repo.delete("uuid-1").await?;
+        assert!(repo.select("uuid-1").await.is_err());
+        assert_eq!(repo.select_all().await?.len(), 2);
+
+        repo.delete_all().await?;
+        assert_eq!(repo.count(&Query::new()).await?, 0);
+
+        Ok(())
+    }
+
+    /// Test filtering, ordering and pagination
+    #[tokio::test]
+    async fn test_select_where() -> Result<()> {
+        let repo = init("table-select-where", "/tmp/test-table-select-where.db").await?;
+
+        for (index, size) in [30, 10, 50, 20, 40].into_iter().enumerate() {
+            let mut item = recording(&format!("uuid-{index}"), &format!("{index}.mp4"), size);
+            item.favorite = size >= 30;
+            repo.insert(&item).await?;
+        }
+
+        let sizes =
+            |items: Vec<Recording>| items.into_iter().map(|item| item.size).collect::<Vec<_>>();
+
+        // Insertion order by default
+        assert_eq!(sizes(repo.select_all().await?), vec![30, 10, 50, 20, 40]);
+
+        let query = Query::new()
+            .filter("size", Op::Gt, 15)
+            .order_by("size", Order::Desc);
+        assert_eq!(
+            sizes(repo.select_where(&query).await?),
+            vec![50, 40, 30, 20]
+        );
+        assert_eq!(repo.count(&query).await?, 4);
+
+        // Pages don't overlap and the count ignores the pagination
+        assert_eq!(
+            sizes(repo.select_where(&query.clone().page(0, 3)).await?),
+            vec![50, 40, 30]
+        );
+        assert_eq!(
+            sizes(repo.select_where(&query.clone().page(1, 3)).await?),
+            vec![20]
+        );
+        assert_eq!(repo.count(&query.clone().page(1, 3)).await?, 4);
+        assert_eq!(
+            sizes(repo.select_where(&query.clone().offset(3)).await?),
+            vec![20]
+        );
+
+        let query = Query::new()
+            .filter("favorite", Op::Eq, true)
+            .filter("file", Op::Like, "%4.mp4")
+            .order_by(ROWID_COLUMN, Order::Desc);
+        assert_eq!(sizes(repo.select_where(&query).await?), vec![40]);
+
+        let query = Query::new().filter(KEY_COLUMN, Op::Eq, "uuid-1");
+        assert_eq!(sizes(repo.select_where(&query).await?), vec![10]);
+
+        let query = Query::new().filter("file", Op::Eq, None::<String>);
+        assert!(repo.select_where(&query).await?.is_empty());
+
+        repo.delete_where(&Query::new().filter("size", Op::Le, 20))
+            .await?;
+        assert_eq!(sizes(repo.select_all().await?), vec![30, 50, 40]);
+
+        // Only the index columns can be written into the SQL
+        let query = Query::new().filter("note", Op::Eq, "");
+        assert!(repo.select_where(&query).await.is_err());
+        let query = Query::new().order_by("size; DROP TABLE test_table", Order::Asc);
+        assert!(repo.select_where(&query).await.is_err());
+
+        Ok(())
+    }
+
+    /// Test upgrading a table created with the uuid and data columns only
+    #[tokio::test]
+    async fn test_upgrade_table() -> Result<()> {
+        let (db, db_path) = ("table-upgrade", "/tmp/test-table-upgrade.db");
+        let _ = std::fs::remove_file(db_path);
+        create_db(db, db_path).await?;
+
+        sqlx::query(&format!(
+            "CREATE TABLE {TABLE_NAME} (
+                 id INTEGER PRIMARY KEY,
+                 uuid TEXT NOT NULL UNIQUE,
+                 data TEXT NOT NULL
+                 )"
+        ))
+        .execute(&pool(db).await?)
+        .await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {TABLE_NAME} (uuid, data) VALUES (?, ?)"
+        ))
+        .bind("uuid-1")
+        .bind(r#"{"id":"uuid-1","file":"a.mp4"}"#)
+        .execute(&pool(db).await?)
+        .await?;
+
+        let repo = Repository::<Recording>::new(db, TABLE_NAME);
+        repo.init().await?;
+
+        // Init twice keeps the columns
+        repo.init().await?;
+
+        let query = Query::new().filter("file", Op::Eq, "a.mp4");
+        assert_eq!(
+            repo.select_where(&query).await?,
+            vec![recording("uuid-1", "a.mp4", 0)]
+        );
+
+        Ok(())
+    }
+
+    /// Test the full-text search and the maintenance of its index
+    #[tokio::test]
+    async fn test_search() -> Result<()> {
+        let (db, db_path) = ("table-search", "/tmp/test-table-search.db");
+        let _ = std::fs::remove_file(db_path);
+        create_db(db, db_path).await?;
+
+        let repo = Repository::<Transcript>::new(db, TABLE_NAME);
+        repo.init().await?;
+
+        repo.insert(&transcript(
+            "uuid-1",
+            10,
+            "Async Rust",
+            &["Futures are lazy", "今天我们聊聊异步编程"],
+        ))
+        .await?;
+        repo.insert(&transcript(
+            "uuid-2",
+            20,
+            "Rust macros",
+            &["Procedural macros", "宏展开"],
+        ))
+        .await?;
+        repo.insert(&transcript("uuid-3", 30, "Cooking", &["100% butter"]))
+            .await?;
+
+        let ids =
+            |items: Vec<Transcript>| items.into_iter().map(|item| item.id).collect::<Vec<_>>();
+
+        // All the terms are matched, ignoring the case
+        let found = ids(repo
+            .search("rust", &Query::new().order_by("size", Order::Asc))
+            .await?);
+        assert_eq!(found, vec!["uuid-1", "uuid-2"]);
+        assert_eq!(
+            ids(repo.search("RUST lazy", &Query::new()).await?),
+            vec!["uuid-1"]
+        );
+        assert_eq!(
+            ids(repo.search("异步编程", &Query::new()).await?),
+            vec!["uuid-1"]
+        );
+
+        // Terms shorter than a trigram
+        assert_eq!(ids(repo.search("宏", &Query::new()).await?), vec!["uuid-2"]);
+        assert_eq!(ids(repo.search("0%", &Query::new()).await?), vec!["uuid-3"]);
+        assert!(repo.search("_", &Query::new()).await?.is_empty());
+
+        // Filters and pagination apply to the found records
+        let query = Query::new().filter("size", Op::Gt, 10);
+        assert_eq!(ids(repo.search("rust", &query).await?), vec!["uuid-2"]);
+        let query = Query::new().order_by("size", Order::Desc).limit(1);
+        assert_eq!(ids(repo.search("rust", &query).await?), vec!["uuid-2"]);
+
+        // An empty text matches all the records
+        assert_eq!(repo.search(" ", &Query::new()).await?.len(), 3);
+
+        // The index follows the updates
+        repo.update(&transcript("uuid-1", 10, "Async Python", &[]))
+            .await?;
+        repo.upsert(&transcript("uuid-3", 30, "Rust cooking", &[]))
+            .await?;
+        let found = ids(repo
+            .search("rust", &Query::new().order_by("size", Order::Asc))
+            .await?);
+        assert_eq!(found, vec!["uuid-2", "uuid-3"]);
+        assert!(repo.search("lazy", &Query::new()).await?.is_empty());
+
+        repo.delete("uuid-2").await?;
+        repo.delete_where(&Query::new().filter("size", Op::Eq, 30))
+            .await?;
+        assert!(repo.search("rust", &Query::new()).await?.is_empty());
+
+        repo.delete_all().await?;
+        let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {TABLE_NAME}_fts"))
+            .fetch_one(&pool(db).await?)
+            .await?;
+        assert_eq!(count, 0);
+
+        // A table without searchable fields can't be searched
+        let recordings = Repository::<Recording>::new(db, "test_recordings");
+        recordings.init().await?;
+        assert!(recordings.search("rust", &Query::new()).await.is_err());
+
+        Ok(())
+    }
+
+    /// Test indexing the records written before the table was searchable
+    #[tokio::test]
+    async fn test_search_index_existing_records() -> Result<()> {
+        let (db, db_path) = (
+            "table-search-existing",
+            "/tmp/test-table-search-existing.db",
+        );
+        let _ = std::fs::remove_file(db_path);
+        create_db(db, db_path).await?;
+
+        let recordings = Repository::<Recording>::new(db, TABLE_NAME);
+        recordings.init().await?;
+        recordings.insert(&recording("uuid-1", "a.mp4", 1)).await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {TABLE_NAME} (uuid, data) VALUES (?, ?)"
+        ))
+        .bind("uuid-2")
+        .bind(r#"{"id":"uuid-2","title":"Async Rust"}"#)
+        .execute(&pool(db).await?)
+        .await?;
+
+        let repo = Repository::<Transcript>::new(db, TABLE_NAME);
+        repo.init().await?;
+
+        // Init twice doesn't index the records again
+        repo.init().await?;
+
+        let found = repo.search("rust", &Query::new()).await?;
+        assert_eq!(found, vec![transcript("uuid-2", 0, "Async Rust", &[])]);
+
+        Ok(())
+    }
+
+    /// Test the tables of the same name in separate databases
+    #[tokio::test]
+    async fn test_separate_dbs() -> Result<()> {
+        let config = init("table-config", "/tmp/test-table-config.db").await?;
+        let history = init("table-history", "/tmp/test-table-history.db").await?;
+
+        config.insert(&recording("uuid-1", "a.mp4", 1)).await?;
+        history.insert(&recording("uuid-2", "b.mp4", 2)).await?;
+
+        assert_eq!(
+            config.select_all().await?,
+            vec![recording("uuid-1", "a.mp4", 1)]
+        );
+        assert_eq!(
+            history.select_all().await?,
+            vec![recording("uuid-2", "b.mp4", 2)]
+        );
+
+        let closed = Repository::<Recording>::new("table-closed", TABLE_NAME);
+        assert!(closed.select_all().await.is_err());
+
+        Ok(())
+    }
+}
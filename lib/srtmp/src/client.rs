@@ -345,6 +345,7 @@ pub struct RtmpClient {
     exit_sig: Arc<AtomicBool>,
     aac_encoder: Option<AacEncoder>,
     write_buffer: Vec<u8>,
+    keyframe_request_sig: Option<Arc<AtomicBool>>,
 }
 
 impl RtmpClient {
@@ -371,9 +372,23 @@ impl RtmpClient {
             exit_sig,
             aac_encoder,
             write_buffer: Vec::new(),
+            keyframe_request_sig: None,
         })
     }
 
+    /// Set `sig` to true whenever the server needs a keyframe to start decoding,
+    /// i.e. once publishing starts and after dropping frames to catch up.
+    pub fn with_keyframe_request_sig(mut self, sig: Arc<AtomicBool>) -> Self {
+        self.keyframe_request_sig = Some(sig);
+        self
+    }
+
+    fn request_keyframe(&self) {
+        if let Some(ref sig) = self.keyframe_request_sig {
+            sig.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
     pub fn start(&mut self) -> Result<(), RtmpClientError> {
         log::info!("Starting RTMP client");
 
@@ -414,6 +429,7 @@ impl RtmpClient {
 
         self.establish_rtmp_session(&mut stream, &mut client_session)?;
         log::info!("RTMP session established and publishing started");
+        self.request_keyframe();
 
         self.forward_data(&mut stream, &mut client_session)?;
 
@@ -808,6 +824,11 @@ impl RtmpClient {
 
                                 log::info!("Dropped {} frames (backlog: {}, now at keyframe)",
                                     dropped_before_keyframe, (backlog as u64).max(dropped_before_keyframe));
+
+                                // No keyframe was queued, the following frames reference dropped ones
+                                if !video_data.is_keyframe {
+                                    self.request_keyframe();
+                                }
                             }
 
                             let tagged_video = video_data.tagged_video();
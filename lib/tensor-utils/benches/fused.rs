@@ -0,0 +1,97 @@
+use candle_core::{D, DType, Device, Tensor};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+use tensor_utils::fused::{apply_rotary_pos_emb, rms_norm_rope};
+
+// Qwen3-0.6B attention of a 10s segment prompt
+const HEADS: usize = 16;
+const HEAD_DIM: usize = 128;
+const SEQ_LEN: usize = 256;
+
+fn cos_sin(device: &Device) -> (Tensor, Tensor) {
+    let inv_freq = (0..HEAD_DIM)
+        .step_by(2)
+        .map(|i| 1.0 / 1_000_000f32.powf(i as f32 / HEAD_DIM as f32))
+        .collect::<Vec<_>>();
+    let inv_freq = Tensor::from_slice(&inv_freq, (1, HEAD_DIM / 2), device).unwrap();
+    let positions = Tensor::arange(0.0, SEQ_LEN as f32, device)
+        .unwrap()
+        .reshape((SEQ_LEN, 1))
+        .unwrap();
+    let freqs = positions.matmul(&inv_freq).unwrap();
+    let emb = Tensor::cat(&[&freqs, &freqs], D::Minus1).unwrap();
+    (emb.cos().unwrap(), emb.sin().unwrap())
+}
+
+// The rotate-half formula the models used before
+fn rope_naive(x: &Tensor, cos: &Tensor, sin: &Tensor) -> Tensor {
+    let half = x.dim(D::Minus1).unwrap() / 2;
+    let x1 = x.narrow(D::Minus1, 0, half).unwrap();
+    let x2 = x
+        .narrow(D::Minus1, half, half)
+        .unwrap()
+        .affine(-1.0, 0.0)
+        .unwrap();
+    let rotated = Tensor::cat(&[&x2, &x1], D::Minus1)
+        .unwrap()
+        .contiguous()
+        .unwrap();
+    let cos = cos.unsqueeze(0).unwrap().unsqueeze(0).unwrap();
+    let sin = sin.unsqueeze(0).unwrap().unsqueeze(0).unwrap();
+    x.broadcast_mul(&cos)
+        .unwrap()
+        .add(&rotated.broadcast_mul(&sin).unwrap())
+        .unwrap()
+}
+
+fn rope(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let (cos, sin) = cos_sin(&device);
+    let mut group = c.benchmark_group("rope");
+
+    for dtype in [DType::F32, DType::F16] {
+        // Projections are transposed to (bs, n_head, seq_len, head_dim)
+        let q = Tensor::randn(0f32, 1.0, (1, SEQ_LEN, HEADS, HEAD_DIM), &device)
+            .unwrap()
+            .to_dtype(dtype)
+            .unwrap()
+            .transpose(1, 2)
+            .unwrap();
+        let k = q.clone();
+        let (cos, sin) = (cos.to_dtype(dtype).unwrap(), sin.to_dtype(dtype).unwrap());
+
+        group.bench_function(BenchmarkId::new("naive", format!("{dtype:?}")), |b| {
+            b.iter(|| black_box((rope_naive(&q, &cos, &sin), rope_naive(&k, &cos, &sin))))
+        });
+        group.bench_function(BenchmarkId::new("fused", format!("{dtype:?}")), |b| {
+            b.iter(|| black_box(apply_rotary_pos_emb(&q, &k, &cos, &sin, false).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn norm_rope(c: &mut Criterion) {
+    let device = Device::Cpu;
+    let (cos, sin) = cos_sin(&device);
+    let x = Tensor::randn(0f32, 1.0, (1, SEQ_LEN, HEADS, HEAD_DIM), &device).unwrap();
+    let weight = Tensor::ones(HEAD_DIM, DType::F32, &device).unwrap();
+    let norm = candle_nn::RmsNorm::new(weight.clone(), 1e-6);
+    let mut group = c.benchmark_group("rms_norm_rope");
+
+    group.bench_function("naive", |b| {
+        b.iter(|| {
+            let x = candle_core::Module::forward(&norm, &x)
+                .unwrap()
+                .transpose(1, 2)
+                .unwrap();
+            black_box(rope_naive(&x, &cos, &sin))
+        })
+    });
+    group.bench_function("fused", |b| {
+        b.iter(|| black_box(rms_norm_rope(&x, &weight, 1e-6, &cos, &sin).unwrap()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, rope, norm_rope);
+criterion_main!(benches);
@@ -0,0 +1,199 @@
+// Fused kernels of the rotary embedding and RMSNorm of the attention
+// modules. Every op of the rotate-half formula allocates a tensor of the
+// size of q/k, the kernels of candle-nn make a single pass instead and take
+// the layout of the projections before the heads are transposed
+
+use crate::{Result, TensorUtilsError};
+use candle_core::{D, DType, Tensor};
+use candle_nn::{ops, rotary_emb};
+
+// Rotary embedding of the rotate-half layout
+// x: (bs, n_head, seq_len, head_dim) contiguous or transposed from
+// (bs, seq_len, n_head, head_dim)
+// cos/sin: (seq_len, head_dim) or (bs, seq_len, head_dim) repeating the
+// frequencies, or only their first half
+pub fn rope(x: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<Tensor> {
+    let (cos, sin) = half_cos_sin(x, cos, sin)?;
+
+    if x.is_contiguous() {
+        return Ok(rotary_emb::rope(x, &cos, &sin)?);
+    }
+
+    // Rotated in the layout of the projection without a copy
+    let x_thd = x.transpose(1, 2)?;
+    if x_thd.is_contiguous() {
+        return Ok(rotary_emb::rope_thd(&x_thd, &cos, &sin)?.transpose(1, 2)?);
+    }
+
+    Ok(rotary_emb::rope(&x.contiguous()?, &cos, &sin)?)
+}
+
+// Drop-in for the rotate-half `apply_rotary_pos_emb` of the models.
+// f16/bf16 run in their own dtype unless `tof32` is set
+pub fn apply_rotary_pos_emb(
+    q: &Tensor,
+    k: &Tensor,
+    cos: &Tensor,
+    sin: &Tensor,
+    tof32: bool,
+) -> Result<(Tensor, Tensor)> {
+    let orig_dtype = q.dtype();
+    if tof32 && orig_dtype != DType::F32 {
+        let q = rope(&q.to_dtype(DType::F32)?, cos, sin)?.to_dtype(orig_dtype)?;
+        let k = rope(&k.to_dtype(DType::F32)?, cos, sin)?.to_dtype(orig_dtype)?;
+        return Ok((q, k));
+    }
+
+    Ok((rope(q, cos, sin)?, rope(k, cos, sin)?))
+}
+
+// RMSNorm of the last dimension, non-contiguous inputs are copied for the
+// fused kernel rather than normalized op by op
+pub fn rms_norm(x: &Tensor, weight: &Tensor, eps: f64) -> Result<Tensor> {
+    let weight = weight.to_dtype(x.dtype())?;
+    if x.is_contiguous() {
+        Ok(ops::rms_norm(x, &weight, eps as f32)?)
+    } else {
+        Ok(ops::rms_norm(&x.contiguous()?, &weight, eps as f32)?)
+    }
+}
+
+// Per head RMSNorm and rotary embedding of the q/k projection, e.g. Qwen3
+// x: (bs, seq_len, n_head, head_dim)
+// Returns a (bs, n_head, seq_len, head_dim) view
+pub fn rms_norm_rope(
+    x: &Tensor,
+    weight: &Tensor,
+    eps: f64,
+    cos: &Tensor,
+    sin: &Tensor,
+) -> Result<Tensor> {
+    let x = rms_norm(x, weight, eps)?;
+    let (cos, sin) = half_cos_sin(&x.transpose(1, 2)?, cos, sin)?;
+    Ok(rotary_emb::rope_thd(&x, &cos, &sin)?.transpose(1, 2)?)
+}
+
+// cos/sin of the first half of the head dimension in the dtype of x, as the
+// kernels take them
+fn half_cos_sin(x: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<(Tensor, Tensor)> {
+    let head_dim = x.dim(D::Minus1)?;
+    if head_dim % 2 != 0 {
+        return Err(TensorUtilsError::InvalidInput(format!(
+            "head_dim must be even, the head_dim: {head_dim}"
+        )));
+    }
+
+    let half = |t: &Tensor| -> Result<Tensor> {
+        let t = if t.dim(D::Minus1)? == head_dim {
+            t.narrow(D::Minus1, 0, head_dim / 2)?
+        } else {
+            t.clone()
+        };
+        Ok(t.to_dtype(x.dtype())?.contiguous()?)
+    };
+
+    Ok((half(cos)?, half(sin)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    const EPS: f64 = 1e-6;
+
+    fn rotate_half(x: &Tensor) -> Result<Tensor> {
+        let half = x.dim(D::Minus1)? / 2;
+        let x1 = x.narrow(D::Minus1, 0, half)?;
+        let x2 = x.narrow(D::Minus1, half, half)?;
+        Ok(Tensor::cat(&[&x2.neg()?, &x1], D::Minus1)?)
+    }
+
+    // x: (bs, seq_len, n_head, head_dim), cos/sin broadcast to
+    // (bs, n_head, seq_len, head_dim)
+    fn naive_rms_norm_rope(
+        x: &Tensor,
+        weight: &Tensor,
+        cos: &Tensor,
+        sin: &Tensor,
+    ) -> Result<Tensor> {
+        let variance = x.sqr()?.mean_keepdim(D::Minus1)?;
+        let x = x
+            .broadcast_div(&(variance + EPS)?.sqrt()?)?
+            .broadcast_mul(weight)?;
+
+        let x = x.transpose(1, 2)?;
+        let (cos, sin) = if cos.rank() == 3 {
+            (cos.unsqueeze(1)?, sin.unsqueeze(1)?)
+        } else {
+            (cos.clone(), sin.clone())
+        };
+        Ok((x.broadcast_mul(&cos)? + rotate_half(&x)?.broadcast_mul(&sin)?)?)
+    }
+
+    // cos/sin of (positions, head_dim) repeating the frequencies
+    fn cos_sin(positions: &[u32], head_dim: usize) -> Result<(Tensor, Tensor)> {
+        let inv_freq = (0..head_dim / 2)
+            .map(|i| 1.0 / 10000f32.powf(2.0 * i as f32 / head_dim as f32))
+            .collect::<Vec<_>>();
+        let inv_freq = Tensor::new(inv_freq.as_slice(), &Device::Cpu)?;
+        let positions = Tensor::new(positions, &Device::Cpu)?.to_dtype(DType::F32)?;
+        let freqs = positions
+            .unsqueeze(1)?
+            .broadcast_mul(&inv_freq.unsqueeze(0)?)?;
+        let freqs = Tensor::cat(&[&freqs, &freqs], D::Minus1)?;
+        Ok((freqs.cos()?, freqs.sin()?))
+    }
+
+    fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+        assert_eq!(a.dims(), b.dims());
+        Ok((a - b)?.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()?)
+    }
+
+    fn check(bs: usize, seq_len: usize, cos: &Tensor, sin: &Tensor) -> Result<()> {
+        let (n_head, head_dim) = (4, cos.dim(D::Minus1)?);
+        let x = Tensor::randn(0f32, 1.0, (bs, seq_len, n_head, head_dim), &Device::Cpu)?;
+        let weight = Tensor::randn(0f32, 1.0, head_dim, &Device::Cpu)?;
+
+        let fused = rms_norm_rope(&x, &weight, EPS, cos, sin)?;
+        let naive = naive_rms_norm_rope(&x, &weight, cos, sin)?;
+        assert!(max_diff(&fused, &naive)? < 1e-4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rms_norm_rope() -> Result<()> {
+        let (cos, sin) = cos_sin(&[0, 1, 2, 3, 4], 16)?;
+        check(1, 5, &cos, &sin)?;
+        check(3, 5, &cos, &sin)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rms_norm_rope_batch_positions() -> Result<()> {
+        // Every sequence of the batch has its own positions
+        let (cos_a, sin_a) = cos_sin(&[0, 1, 2, 3], 8)?;
+        let (cos_b, sin_b) = cos_sin(&[7, 8, 9, 10], 8)?;
+        let cos = Tensor::stack(&[&cos_a, &cos_b], 0)?;
+        let sin = Tensor::stack(&[&sin_a, &sin_b], 0)?;
+        check(2, 4, &cos, &sin)
+    }
+
+    #[test]
+    fn test_rms_norm_rope_half_cos_sin() -> Result<()> {
+        let (cos, sin) = cos_sin(&[3, 4, 5], 8)?;
+        let x = Tensor::randn(0f32, 1.0, (2, 3, 2, 8), &Device::Cpu)?;
+        let weight = Tensor::randn(0f32, 1.0, 8, &Device::Cpu)?;
+
+        let full = rms_norm_rope(&x, &weight, EPS, &cos, &sin)?;
+        let half = rms_norm_rope(
+            &x,
+            &weight,
+            EPS,
+            &cos.narrow(D::Minus1, 0, 4)?,
+            &sin.narrow(D::Minus1, 0, 4)?,
+        )?;
+        assert!(max_diff(&full, &half)? < 1e-6);
+        Ok(())
+    }
+}
@@ -0,0 +1,597 @@
+// Key/value cache of the attention modules. The keys and values are written
+// in place into buffers that grow a page of positions at a time, instead of
+// concatenating the whole history at every decoding step. The oldest
+// positions can be dropped by a sliding window, and the pages over a memory
+// cap are spilled to disk or dropped
+
+use crate::{Result, TensorUtilsError};
+use candle_core::{Device, Tensor};
+use derive_setters::Setters;
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static SPILL_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct KvCacheConfig {
+    // Sequence dimension of the keys and values, 2 for
+    // (bs, n_head, seq_len, head_dim)
+    pub dim: usize,
+
+    // Positions the buffers grow by and the unit pages are spilled in
+    pub page_size: usize,
+
+    // Keep only the last positions. The attention mask must then be built
+    // from `KvCache::len` rather than the number of decoded positions
+    pub window: Option<usize>,
+
+    // Bytes of keys and values kept in memory. The oldest pages over it are
+    // spilled to `spill_dir`, or dropped as by the window without it
+    pub max_memory: Option<usize>,
+
+    pub spill_dir: Option<PathBuf>,
+}
+
+impl Default for KvCacheConfig {
+    fn default() -> Self {
+        Self {
+            dim: 2,
+            page_size: 256,
+            window: None,
+            max_memory: None,
+            spill_dir: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SpilledPage {
+    path: PathBuf,
+    len: usize,
+
+    // Leading positions of the page dropped since it was spilled
+    skip: usize,
+}
+
+#[derive(Debug)]
+pub struct KvCache {
+    config: KvCacheConfig,
+    k: Option<Tensor>,
+    v: Option<Tensor>,
+
+    // Valid positions of the buffers are [start, start + len)
+    start: usize,
+    len: usize,
+
+    spilled: Vec<SpilledPage>,
+}
+
+impl Default for KvCache {
+    fn default() -> Self {
+        Self::new(KvCacheConfig::default())
+    }
+}
+
+impl KvCache {
+    pub fn new(config: KvCacheConfig) -> Self {
+        Self {
+            config,
+            k: None,
+            v: None,
+            start: 0,
+            len: 0,
+            spilled: vec![],
+        }
+    }
+
+    pub fn config(&self) -> &KvCacheConfig {
+        &self.config
+    }
+
+    // Positions held, in memory and spilled
+    pub fn len(&self) -> usize {
+        self.spilled_len() + self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Positions held in memory
+    pub fn resident_len(&self) -> usize {
+        self.len
+    }
+
+    // Append the keys and values of the new positions and return all the
+    // keys and values held
+    pub fn append(&mut self, k: &Tensor, v: &Tensor) -> Result<(Tensor, Tensor)> {
+        let dim = self.config.dim;
+        let n = k.dim(dim)?;
+        if v.dim(dim)? != n {
+            return Err(TensorUtilsError::InvalidInput(format!(
+                "keys and values must have the same length, the keys: {n}, the values: {}",
+                v.dim(dim)?
+            )));
+        }
+
+        self.reserve(k, v, n)?;
+        if let (Some(k_buf), Some(v_buf)) = (&self.k, &self.v) {
+            let offset = self.start + self.len;
+            k_buf.slice_set(&k.contiguous()?, dim, offset)?;
+            v_buf.slice_set(&v.contiguous()?, dim, offset)?;
+        }
+        self.len += n;
+
+        if let Some(window) = self.config.window
+            && self.len() > window
+        {
+            self.drop_front(self.len() - window)?;
+        }
+        self.enforce_memory_cap()?;
+
+        self.current()?.ok_or_else(|| {
+            TensorUtilsError::InvalidInput("kv cache is empty after append".to_string())
+        })
+    }
+
+    // All the keys and values held, spilled pages are loaded back
+    pub fn current(&self) -> Result<Option<(Tensor, Tensor)>> {
+        let (Some(k_buf), Some(v_buf)) = (&self.k, &self.v) else {
+            return Ok(None);
+        };
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let dim = self.config.dim;
+        let k = k_buf.narrow(dim, self.start, self.len)?;
+        let v = v_buf.narrow(dim, self.start, self.len)?;
+        if self.spilled.is_empty() {
+            return Ok(Some((k, v)));
+        }
+
+        let mut ks = vec![];
+        let mut vs = vec![];
+        for page in &self.spilled {
+            let (page_k, page_v) = self.load_page(page, k_buf.device())?;
+            ks.push(page_k);
+            vs.push(page_v);
+        }
+        ks.push(k);
+        vs.push(v);
+        Ok(Some((Tensor::cat(&ks, dim)?, Tensor::cat(&vs, dim)?)))
+    }
+
+    // Keep the first `len` positions, e.g. to roll back rejected tokens
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        let spilled_len = self.spilled_len();
+        if len >= self.len() {
+            return Ok(());
+        }
+        if len >= spilled_len {
+            self.len = len - spilled_len;
+            return Ok(());
+        }
+
+        self.start = 0;
+        self.len = 0;
+
+        let mut kept = 0;
+        let mut pages = std::mem::take(&mut self.spilled).into_iter();
+        for page in pages.by_ref() {
+            let page_len = page.len - page.skip;
+            if kept + page_len <= len {
+                kept += page_len;
+                self.spilled.push(page);
+                if kept == len {
+                    break;
+                }
+                continue;
+            }
+
+            // The page cut by `len` is loaded back into memory
+            let device = self
+                .k
+                .as_ref()
+                .map(|k| k.device().clone())
+                .unwrap_or(Device::Cpu);
+            let (k, v) = self.load_page(&page, &device)?;
+            let dim = self.config.dim;
+            let (k, v) = (k.narrow(dim, 0, len - kept)?, v.narrow(dim, 0, len - kept)?);
+            remove_page(&page);
+
+            self.reserve(&k, &v, len - kept)?;
+            if let (Some(k_buf), Some(v_buf)) = (&self.k, &self.v) {
+                k_buf.slice_set(&k.contiguous()?, dim, 0)?;
+                v_buf.slice_set(&v.contiguous()?, dim, 0)?;
+            }
+            self.len = len - kept;
+            break;
+        }
+
+        for page in pages {
+            remove_page(&page);
+        }
+        Ok(())
+    }
+
+    // Drop all the positions, the buffers are kept for the next sequence
+    pub fn reset(&mut self) {
+        for page in self.spilled.drain(..) {
+            remove_page(&page);
+        }
+        self.start = 0;
+        self.len = 0;
+    }
+
+    fn spilled_len(&self) -> usize {
+        self.spilled.iter().map(|page| page.len - page.skip).sum()
+    }
+
+    // Make room for `n` more positions after the valid ones. The valid
+    // positions are moved to the front of new buffers when they don't fit
+    fn reserve(&mut self, k: &Tensor, v: &Tensor, n: usize) -> Result<()> {
+        let dim = self.config.dim;
+        let page_size = self.config.page_size.max(1);
+
+        if let (Some(k_buf), Some(v_buf)) = (&self.k, &self.v) {
+            let same_layout = |buf: &Tensor, t: &Tensor| {
+                buf.dtype() == t.dtype()
+                    && buf.rank() == t.rank()
+                    && buf
+                        .dims()
+                        .iter()
+                        .zip(t.dims())
+                        .enumerate()
+                        .all(|(index, (buf_size, size))| index == dim || buf_size == size)
+            };
+            if !same_layout(k_buf, k) || !same_layout(v_buf, v) {
+                if self.len > 0 || !self.spilled.is_empty() {
+                    return Err(TensorUtilsError::InvalidInput(format!(
+                        "appended shape must match the cached shape {:?}, the shape: {:?}",
+                        k_buf.dims(),
+                        k.dims()
+                    )));
+                }
+                self.k = None;
+                self.v = None;
+            } else if self.start + self.len + n <= k_buf.dim(dim)? {
+                return Ok(());
+            }
+        }
+
+        let capacity = (self.len + n).div_ceil(page_size) * page_size;
+        let new_buf = |t: &Tensor, old: Option<&Tensor>| -> Result<Tensor> {
+            let mut shape = t.dims().to_vec();
+            shape[dim] = capacity;
+            let buf = Tensor::zeros(shape, t.dtype(), t.device())?;
+            if let Some(old) = old
+                && self.len > 0
+            {
+                buf.slice_set(
+                    &old.narrow(dim, self.start, self.len)?.contiguous()?,
+                    dim,
+                    0,
+                )?;
+            }
+            Ok(buf)
+        };
+
+        let k_buf = new_buf(k, self.k.as_ref())?;
+        let v_buf = new_buf(v, self.v.as_ref())?;
+        self.k = Some(k_buf);
+        self.v = Some(v_buf);
+        self.start = 0;
+        Ok(())
+    }
+
+    // Drop the oldest `n` positions, spilled ones first
+    fn drop_front(&mut self, mut n: usize) -> Result<()> {
+        while n > 0
+            && let Some(page) = self.spilled.first_mut()
+        {
+            let page_len = page.len - page.skip;
+            if n < page_len {
+                page.skip += n;
+                return Ok(());
+            }
+            n -= page_len;
+            remove_page(&self.spilled.remove(0));
+        }
+
+        let n = n.min(self.len);
+        self.start += n;
+        self.len -= n;
+        Ok(())
+    }
+
+    fn enforce_memory_cap(&mut self) -> Result<()> {
+        let Some(max_memory) = self.config.max_memory else {
+            return Ok(());
+        };
+        let (Some(k_buf), Some(v_buf)) = (self.k.clone(), self.v.clone()) else {
+            return Ok(());
+        };
+
+        let dim = self.config.dim;
+        let bytes_per_position = |buf: &Tensor| -> Result<usize> {
+            Ok(buf.elem_count() / buf.dim(dim)? * buf.dtype().size_in_bytes())
+        };
+        let bytes_per_position = bytes_per_position(&k_buf)? + bytes_per_position(&v_buf)?;
+        let page_size = self.config.page_size.max(1);
+
+        // One page is always kept in memory for the next appends
+        while self.len * bytes_per_position > max_memory && self.len > page_size {
+            if let Some(spill_dir) = &self.config.spill_dir {
+                fs::create_dir_all(spill_dir)?;
+                let path = spill_dir.join(format!(
+                    "kv-cache-{}-{}.safetensors",
+                    std::process::id(),
+                    SPILL_ID.fetch_add(1, Ordering::Relaxed)
+                ));
+                let tensors = HashMap::from([
+                    (
+                        "k".to_string(),
+                        k_buf.narrow(dim, self.start, page_size)?.contiguous()?,
+                    ),
+                    (
+                        "v".to_string(),
+                        v_buf.narrow(dim, self.start, page_size)?.contiguous()?,
+                    ),
+                ]);
+                candle_core::safetensors::save(&tensors, &path)?;
+                self.spilled.push(SpilledPage {
+                    path,
+                    len: page_size,
+                    skip: 0,
+                });
+            }
+
+            self.start += page_size;
+            self.len -= page_size;
+        }
+        Ok(())
+    }
+
+    fn load_page(&self, page: &SpilledPage, device: &Device) -> Result<(Tensor, Tensor)> {
+        let mut tensors = candle_core::safetensors::load(&page.path, device)?;
+        let dim = self.config.dim;
+        let mut take = |name: &str| -> Result<Tensor> {
+            let t = tensors.remove(name).ok_or_else(|| {
+                TensorUtilsError::InvalidInput(format!(
+                    "no {name} in the spilled page: {}",
+                    page.path.display()
+                ))
+            })?;
+            Ok(t.narrow(dim, page.skip, page.len - page.skip)?)
+        };
+        Ok((take("k")?, take("v")?))
+    }
+}
+
+// The copy has its own buffers and spilled pages, as the buffers are written
+// in place
+impl Clone for KvCache {
+    fn clone(&self) -> Self {
+        let copy = |t: &Option<Tensor>| t.as_ref().and_then(|t| t.copy().ok());
+        let spilled = self
+            .spilled
+            .iter()
+            .filter_map(|page| {
+                let path = page.path.with_file_name(format!(
+                    "kv-cache-{}-{}.safetensors",
+                    std::process::id(),
+                    SPILL_ID.fetch_add(1, Ordering::Relaxed)
+                ));
+                fs::copy(&page.path, &path).ok().map(|_| SpilledPage {
+                    path,
+                    len: page.len,
+                    skip: page.skip,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let (k, v) = (copy(&self.k), copy(&self.v));
+        let complete = k.is_some() == self.k.is_some()
+            && v.is_some() == self.v.is_some()
+            && spilled.len() == self.spilled.len();
+        if !complete {
+            for page in &spilled {
+                remove_page(page);
+            }
+            return Self::new(self.config.clone());
+        }
+
+        Self {
+            config: self.config.clone(),
+            k,
+            v,
+            start: self.start,
+            len: self.len,
+            spilled,
+        }
+    }
+}
+
+impl Drop for KvCache {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+
+fn remove_page(page: &SpilledPage) {
+    _ = fs::remove_file(&page.path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::DType;
+
+    // Keys of (1, 2, n, 4) holding the position index, the values its negation
+    fn kv(start: usize, end: usize) -> Result<(Tensor, Tensor)> {
+        let k = Tensor::arange(start as f32, end as f32, &Device::Cpu)?
+            .reshape((1, 1, end - start, 1))?
+            .broadcast_as((1, 2, end - start, 4))?
+            .contiguous()?;
+        let v = k.neg()?;
+        Ok((k, v))
+    }
+
+    fn positions(t: &Tensor) -> Result<Vec<f32>> {
+        Ok(t.narrow(1, 1, 1)?
+            .narrow(3, 3, 1)?
+            .flatten_all()?
+            .to_vec1::<f32>()?)
+    }
+
+    fn assert_holds(cache: &KvCache, start: usize, end: usize) -> Result<()> {
+        let (k, v) = cache.current()?.expect("cache is empty");
+        let (expect_k, expect_v) = kv(start, end)?;
+        assert_eq!(k.dims(), expect_k.dims());
+        assert_eq!(positions(&k)?, positions(&expect_k)?);
+        assert_eq!(positions(&v)?, positions(&expect_v)?);
+        Ok(())
+    }
+
+    fn spilled_files(dir: &std::path::Path) -> Result<usize> {
+        Ok(fs::read_dir(dir)?.count())
+    }
+
+    #[test]
+    fn test_append_grows() -> Result<()> {
+        let mut cache = KvCache::new(KvCacheConfig::default().with_page_size(4));
+        assert!(cache.current()?.is_none());
+
+        let (k, v) = kv(0, 3)?;
+        let (all_k, _) = cache.append(&k, &v)?;
+        assert_eq!(all_k.dims(), &[1, 2, 3, 4]);
+
+        // Grows past the first page
+        let (k, v) = kv(3, 6)?;
+        let (all_k, all_v) = cache.append(&k, &v)?;
+        assert_eq!(all_k.dims(), &[1, 2, 6, 4]);
+        assert_eq!(all_v.dims(), &[1, 2, 6, 4]);
+
+        let (k, v) = kv(6, 7)?;
+        cache.append(&k, &v)?;
+        assert_eq!(cache.len(), 7);
+        assert_eq!(cache.resident_len(), 7);
+        assert_holds(&cache, 0, 7)?;
+
+        // Another head count doesn't fit the cached positions
+        let k = Tensor::zeros((1, 3, 1, 4), DType::F32, &Device::Cpu)?;
+        assert!(cache.append(&k, &k).is_err());
+
+        // Nor do keys and values of different lengths
+        let (k, _) = kv(7, 8)?;
+        let (_, v) = kv(7, 9)?;
+        assert!(cache.append(&k, &v).is_err());
+        assert_eq!(cache.len(), 7);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_window() -> Result<()> {
+        let mut cache = KvCache::new(
+            KvCacheConfig::default()
+                .with_page_size(4)
+                .with_window(Some(5)),
+        );
+
+        for start in (0..9).step_by(3) {
+            let (k, v) = kv(start, start + 3)?;
+            let (all_k, _) = cache.append(&k, &v)?;
+            assert_eq!(all_k.dim(2)?, (start + 3).min(5));
+        }
+
+        assert_eq!(cache.len(), 5);
+        assert_holds(&cache, 4, 9)?;
+
+        // The evicted positions are reused by the next appends
+        for start in 9..20 {
+            let (k, v) = kv(start, start + 1)?;
+            cache.append(&k, &v)?;
+        }
+        assert_holds(&cache, 15, 20)?;
+        assert!(cache.k.as_ref().unwrap().dim(2)? <= 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate() -> Result<()> {
+        let mut cache = KvCache::new(KvCacheConfig::default().with_page_size(4));
+        let (k, v) = kv(0, 6)?;
+        cache.append(&k, &v)?;
+
+        cache.truncate(10)?;
+        assert_eq!(cache.len(), 6);
+
+        cache.truncate(4)?;
+        assert_eq!(cache.len(), 4);
+        assert_holds(&cache, 0, 4)?;
+
+        // The rolled back positions are overwritten
+        let (k, v) = kv(4, 7)?;
+        cache.append(&k, &v)?;
+        assert_holds(&cache, 0, 7)?;
+
+        cache.reset();
+        assert!(cache.is_empty());
+        assert!(cache.current()?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spill_reload() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // A position of the keys and values is 64 bytes, so 2 fit in memory
+        let mut cache = KvCache::new(
+            KvCacheConfig::default()
+                .with_page_size(2)
+                .with_max_memory(Some(128))
+                .with_spill_dir(Some(dir.path().to_path_buf())),
+        );
+
+        let (k, v) = kv(0, 7)?;
+        let (all_k, all_v) = cache.append(&k, &v)?;
+        assert_eq!(positions(&all_k)?, positions(&k)?);
+        assert_eq!(positions(&all_v)?, positions(&v)?);
+        assert_eq!(cache.len(), 7);
+        assert_eq!(cache.resident_len(), 1);
+        assert_eq!(spilled_files(dir.path())?, 3);
+
+        // The copy has its own spilled pages
+        let copy = cache.clone();
+        assert_eq!(spilled_files(dir.path())?, 6);
+        assert_holds(&copy, 0, 7)?;
+        drop(copy);
+        assert_eq!(spilled_files(dir.path())?, 3);
+
+        // Cuts the second page, which is loaded back, and drops the third
+        cache.truncate(3)?;
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.resident_len(), 1);
+        assert_eq!(spilled_files(dir.path())?, 1);
+        assert_holds(&cache, 0, 3)?;
+
+        let (k, v) = kv(3, 5)?;
+        cache.append(&k, &v)?;
+        assert_holds(&cache, 0, 5)?;
+
+        drop(cache);
+        assert_eq!(spilled_files(dir.path())?, 0);
+
+        Ok(())
+    }
+}
@@ -1,3 +1,7 @@
+pub mod fused;
+pub mod kv_cache;
+pub mod sampling;
+
 use candle_core::{D, DType, Device, IndexOp, Tensor, shape::Dim};
 use thiserror::Error;
 
@@ -8,6 +12,9 @@ pub enum TensorUtilsError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("Candle error: {0}")]
     Candle(#[from] candle_core::Error),
 }
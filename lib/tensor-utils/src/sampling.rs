@@ -0,0 +1,296 @@
+// Sampling of the next token from the logits of a language model:
+// temperature, top-k, nucleus (top-p) and repetition penalty over the
+// recently generated tokens
+
+use crate::{Result, TensorUtilsError};
+use candle_core::{D, DType, Tensor};
+use derive_setters::Setters;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+// Softmax of the last dimension after dividing the logits by `temperature`
+pub fn softmax_with_temperature(logits: &Tensor, temperature: f64) -> Result<Tensor> {
+    if temperature <= 0.0 {
+        return Err(TensorUtilsError::InvalidInput(format!(
+            "temperature must > 0, the temperature: {temperature}"
+        )));
+    }
+
+    let logits = (logits.to_dtype(DType::F32)? / temperature)?;
+    let max = logits.max_keepdim(D::Minus1)?;
+    let exp = logits.broadcast_sub(&max)?.exp()?;
+    let sum = exp.sum_keepdim(D::Minus1)?;
+    Ok(exp.broadcast_div(&sum)?)
+}
+
+// Keep the `k` largest logits of a 1D tensor, the others are set to -inf.
+// Ties with the k-th logit are kept
+pub fn top_k_filter(logits: &Tensor, k: usize) -> Result<Tensor> {
+    let mut values = logits_vec(logits)?;
+    if k == 0 || k >= values.len() {
+        return Ok(logits.clone());
+    }
+
+    let mut sorted = values.clone();
+    sorted.select_nth_unstable_by(k - 1, |a, b| b.total_cmp(a));
+    let threshold = sorted[k - 1];
+
+    for value in values.iter_mut().filter(|value| **value < threshold) {
+        *value = f32::NEG_INFINITY;
+    }
+    Ok(Tensor::new(values, logits.device())?.to_dtype(logits.dtype())?)
+}
+
+// Keep the fewest largest logits of a 1D tensor whose probabilities add up
+// to `p`, the others are set to -inf
+pub fn top_p_filter(logits: &Tensor, p: f64) -> Result<Tensor> {
+    if p <= 0.0 || p >= 1.0 {
+        return Ok(logits.clone());
+    }
+
+    let mut values = logits_vec(logits)?;
+    let probs = softmax_with_temperature(&Tensor::new(values.as_slice(), logits.device())?, 1.0)?
+        .to_vec1::<f32>()?;
+
+    let mut indices = (0..probs.len()).collect::<Vec<_>>();
+    indices.sort_unstable_by(|a, b| probs[*b].total_cmp(&probs[*a]));
+
+    let mut cumulative = 0.0;
+    let mut kept = 0;
+    for index in &indices {
+        cumulative += probs[*index] as f64;
+        kept += 1;
+        if cumulative >= p {
+            break;
+        }
+    }
+
+    for index in &indices[kept..] {
+        values[*index] = f32::NEG_INFINITY;
+    }
+    Ok(Tensor::new(values, logits.device())?.to_dtype(logits.dtype())?)
+}
+
+// Make the tokens of `context` less likely as in CTRL: positive logits are
+// divided by `penalty` and negative ones multiplied by it. Each token is
+// penalized once however often it occurs
+pub fn apply_repetition_penalty(logits: &Tensor, penalty: f32, context: &[u32]) -> Result<Tensor> {
+    if penalty == 1.0 || context.is_empty() {
+        return Ok(logits.clone());
+    }
+
+    let mut values = logits_vec(logits)?;
+    let mut seen = std::collections::HashSet::new();
+    for token in context.iter().filter(|token| seen.insert(**token)) {
+        if let Some(value) = values.get_mut(*token as usize) {
+            if *value >= 0.0 {
+                *value /= penalty;
+            } else {
+                *value *= penalty;
+            }
+        }
+    }
+    Ok(Tensor::new(values, logits.device())?.to_dtype(logits.dtype())?)
+}
+
+fn logits_vec(logits: &Tensor) -> Result<Vec<f32>> {
+    if logits.rank() != 1 {
+        return Err(TensorUtilsError::InvalidInput(format!(
+            "logits rank must be equal to 1, the logits rank: {}",
+            logits.rank()
+        )));
+    }
+    Ok(logits.to_dtype(DType::F32)?.to_vec1::<f32>()?)
+}
+
+#[derive(Debug, Clone, Setters)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct SamplingConfig {
+    // Greedy decoding when not greater than 0
+    pub temperature: f64,
+
+    pub top_k: Option<usize>,
+    pub top_p: Option<f64>,
+
+    // 1.0 disables the penalty
+    pub repetition_penalty: f32,
+
+    // Tokens at the end of the history the penalty applies to
+    pub repeat_last_n: usize,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_k: None,
+            top_p: None,
+            repetition_penalty: 1.0,
+            repeat_last_n: 64,
+        }
+    }
+}
+
+pub struct Sampler {
+    config: SamplingConfig,
+    rng: StdRng,
+}
+
+impl Sampler {
+    pub fn new(config: SamplingConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn config(&self) -> &SamplingConfig {
+        &self.config
+    }
+
+    // Sample the next token from 1D logits, `history` are the tokens
+    // generated so far
+    pub fn sample(&mut self, logits: &Tensor, history: &[u32]) -> Result<u32> {
+        let window = history.len().saturating_sub(self.config.repeat_last_n);
+        let logits =
+            apply_repetition_penalty(logits, self.config.repetition_penalty, &history[window..])?
+                .to_dtype(DType::F32)?;
+
+        if self.config.temperature <= 0.0 {
+            return Ok(logits.argmax(D::Minus1)?.to_scalar::<u32>()?);
+        }
+
+        let mut logits = (logits / self.config.temperature)?;
+        if let Some(k) = self.config.top_k {
+            logits = top_k_filter(&logits, k)?;
+        }
+        if let Some(p) = self.config.top_p {
+            logits = top_p_filter(&logits, p)?;
+        }
+
+        let probs = softmax_with_temperature(&logits, 1.0)?.to_vec1::<f32>()?;
+        let rand_val: f32 = self.rng.random();
+        let mut cumulative = 0.0f32;
+        for (index, prob) in probs.iter().enumerate() {
+            cumulative += prob;
+            if rand_val < cumulative {
+                return Ok(index as u32);
+            }
+        }
+
+        // Rounding left the random value above the sum, take the last
+        // token still allowed
+        let last = probs
+            .iter()
+            .rposition(|prob| *prob > 0.0)
+            .unwrap_or_default();
+        Ok(last as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    fn tensor(values: &[f32]) -> Result<Tensor> {
+        Ok(Tensor::new(values, &Device::Cpu)?)
+    }
+
+    fn kept(logits: &Tensor) -> Result<Vec<bool>> {
+        Ok(logits
+            .to_vec1::<f32>()?
+            .iter()
+            .map(|value| value.is_finite())
+            .collect())
+    }
+
+    #[test]
+    fn test_top_k_one_is_argmax() -> Result<()> {
+        let logits = Tensor::randn(0f32, 3.0, 100, &Device::Cpu)?;
+        let argmax = logits.argmax(D::Minus1)?.to_scalar::<u32>()?;
+
+        let mut sampler = Sampler::new(
+            SamplingConfig::default()
+                .with_temperature(1.5)
+                .with_top_k(Some(1)),
+            7,
+        );
+        for _ in 0..50 {
+            assert_eq!(sampler.sample(&logits, &[])?, argmax);
+        }
+
+        // Greedy decoding without a temperature
+        let mut sampler = Sampler::new(SamplingConfig::default().with_temperature(0.0), 7);
+        assert_eq!(sampler.sample(&logits, &[])?, argmax);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_k_ties() -> Result<()> {
+        let filtered = top_k_filter(&tensor(&[1.0, 3.0, 2.0, 3.0, 0.5])?, 2)?;
+        assert_eq!(kept(&filtered)?, [false, true, false, true, false]);
+
+        // The k-th logit ties with another one
+        let filtered = top_k_filter(&tensor(&[1.0, 3.0, 2.0, 2.0, 0.5])?, 2)?;
+        assert_eq!(kept(&filtered)?, [false, true, true, true, false]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_p_cutoff() -> Result<()> {
+        // Probabilities of 0.5, 0.05, 0.3 and 0.15
+        let logits = tensor(&[0.5f32.ln(), 0.05f32.ln(), 0.3f32.ln(), 0.15f32.ln()])?;
+
+        let filtered = top_p_filter(&logits, 0.45)?;
+        assert_eq!(kept(&filtered)?, [true, false, false, false]);
+
+        let filtered = top_p_filter(&logits, 0.75)?;
+        assert_eq!(kept(&filtered)?, [true, false, true, false]);
+
+        let filtered = top_p_filter(&logits, 0.9)?;
+        assert_eq!(kept(&filtered)?, [true, false, true, true]);
+
+        // Out of (0, 1) keeps every token
+        assert_eq!(kept(&top_p_filter(&logits, 1.0)?)?, [true; 4]);
+        assert_eq!(kept(&top_p_filter(&logits, 0.0)?)?, [true; 4]);
+
+        // Only the kept tokens are sampled
+        let mut sampler = Sampler::new(SamplingConfig::default().with_top_p(Some(0.75)), 7);
+        for _ in 0..50 {
+            assert!(matches!(sampler.sample(&logits, &[])?, 0 | 2));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repetition_penalty() -> Result<()> {
+        let logits = tensor(&[2.0, -2.0, 0.5, -1.0])?;
+
+        // Both signs get less likely, each token once
+        let penalized = apply_repetition_penalty(&logits, 2.0, &[0, 1, 1, 0])?;
+        assert_eq!(penalized.to_vec1::<f32>()?, [1.0, -4.0, 0.5, -1.0]);
+
+        assert_eq!(
+            apply_repetition_penalty(&logits, 1.0, &[0, 1])?.to_vec1::<f32>()?,
+            logits.to_vec1::<f32>()?
+        );
+
+        // Only the last `repeat_last_n` tokens are penalized
+        let logits = tensor(&[2.0, 1.9])?;
+        let mut sampler = Sampler::new(
+            SamplingConfig::default()
+                .with_temperature(0.0)
+                .with_repetition_penalty(2.0)
+                .with_repeat_last_n(1),
+            7,
+        );
+        assert_eq!(sampler.sample(&logits, &[0])?, 1);
+        assert_eq!(sampler.sample(&logits, &[0, 1])?, 0);
+
+        Ok(())
+    }
+}
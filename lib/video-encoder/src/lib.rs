@@ -9,6 +9,7 @@ mod ve_ffmpeg;
 
 use derive_setters::Setters;
 use image::{ImageBuffer, Rgb};
+use std::path::PathBuf;
 
 // Standard video timescale (90kHz) for better compatibility
 pub const VIDEO_TIMESCALE: u32 = 90000;
@@ -41,9 +42,49 @@ impl Default for EncodedFrame {
 pub trait VideoEncoder {
     fn encode_frame(&mut self, img: ResizedImageBuffer) -> Result<EncodedFrame>;
     fn headers(&mut self) -> Result<Vec<u8>>;
+
+    /// Force the next encoded frame to be an IDR frame, e.g. when a new viewer joins the stream.
+    fn request_keyframe(&mut self);
     fn flush(self: Box<Self>, cb: Box<dyn FnMut(Vec<u8>) + 'static>) -> Result<()>;
 }
 
+/// Bitrates are in bits per second.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RateControl {
+    /// Constant quality, 0 (lossless) to 51 (worst)
+    Crf(u8),
+
+    /// Constant bitrate with a strict HRD buffer, as required by most push-streaming platforms
+    Cbr { bitrate: u32, buffer_size: u32 },
+
+    /// Constrained variable bitrate
+    Vbr {
+        bitrate: u32,
+        max_bitrate: u32,
+        buffer_size: u32,
+    },
+
+    /// One pass of a 2-pass encode. The first pass writes the statistics into `stats_path`,
+    /// the second pass reads them back to distribute `bitrate` across the whole video.
+    TwoPass {
+        bitrate: u32,
+        pass: TwoPassStage,
+        stats_path: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TwoPassStage {
+    First,
+    Second,
+}
+
+impl Default for RateControl {
+    fn default() -> Self {
+        RateControl::Crf(23)
+    }
+}
+
 #[derive(Clone, Debug, Setters)]
 #[setters(prefix = "with_")]
 pub struct VideoEncoderConfig {
@@ -51,6 +92,9 @@ pub struct VideoEncoderConfig {
     pub height: u32,
     pub fps: u32,
     pub annexb: bool,
+
+    // The backends which can't apply the mode fail to create the encoder
+    pub rate_control: RateControl,
 }
 
 impl VideoEncoderConfig {
@@ -60,6 +104,7 @@ impl VideoEncoderConfig {
             height,
             fps: 25,
             annexb: false,
+            rate_control: RateControl::default(),
         }
     }
 }
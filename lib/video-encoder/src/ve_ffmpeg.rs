@@ -1,13 +1,15 @@
 use super::{
-    EncodedFrame, EncoderError, ResizedImageBuffer, Result, VideoEncoder, VideoEncoderConfig,
+    EncodedFrame, EncoderError, RateControl, ResizedImageBuffer, Result, TwoPassStage,
+    VideoEncoder, VideoEncoderConfig,
 };
-use ffmpeg_next::{Dictionary, Rational, codec, encoder, format, frame, packet};
+use ffmpeg_next::{Dictionary, Rational, codec, encoder, format, frame, packet, picture};
 use std::time::Duration;
 
 pub struct FfmpegVideoEncoder {
     width: u32,
     height: u32,
     frame_index: u64,
+    keyframe_requested: bool,
     encoder: encoder::Video,
 }
 
@@ -47,15 +49,69 @@ impl FfmpegVideoEncoder {
         let mut opts = Dictionary::new();
         opts.set("preset", if config.annexb { "faster" } else { "superfast" });
         opts.set("profile", "baseline");
-        opts.set("crf", "23");
         opts.set("g", &fps.to_string()); // max_keyframe_interval
         opts.set("tune", "zerolatency");
         opts.set("forced-idr", "1"); // Force keyframes more regularly
 
-        let x264_params = format!(
+        let mut x264_params = format!(
             "annexb={}:bframes=0:cabac=0:scenecut=0:keyint={fps}:keyint_min={fps}:rc_lookahead=0",
             if config.annexb { 1 } else { 0 },
         );
+
+        match &config.rate_control {
+            RateControl::Crf(crf) => {
+                opts.set("crf", &crf.to_string());
+            }
+            RateControl::Cbr {
+                bitrate,
+                buffer_size,
+            } => {
+                let bitrate = bitrate.to_string();
+                opts.set("b", &bitrate);
+                opts.set("minrate", &bitrate);
+                opts.set("maxrate", &bitrate);
+                opts.set("bufsize", &buffer_size.to_string());
+
+                // Pad the stream with filler data so the HRD buffer never underflows
+                x264_params.push_str(":nal-hrd=cbr:force-cfr=1");
+            }
+            RateControl::Vbr {
+                bitrate,
+                max_bitrate,
+                buffer_size,
+            } => {
+                if max_bitrate < bitrate {
+                    return Err(EncoderError::VideoEncodingFailed(format!(
+                        "max bitrate {max_bitrate} is lower than bitrate {bitrate}"
+                    )));
+                }
+
+                opts.set("b", &bitrate.to_string());
+                opts.set("maxrate", &max_bitrate.to_string());
+                opts.set("bufsize", &buffer_size.to_string());
+                x264_params.push_str(":nal-hrd=vbr");
+            }
+            RateControl::TwoPass {
+                bitrate,
+                pass,
+                stats_path,
+            } => {
+                let stats_path = stats_path.to_str().ok_or_else(|| {
+                    EncoderError::VideoEncodingFailed(format!(
+                        "Invalid 2-pass stats path: {}",
+                        stats_path.display()
+                    ))
+                })?;
+
+                encoder.set_flags(match pass {
+                    TwoPassStage::First => codec::Flags::PASS1,
+                    TwoPassStage::Second => codec::Flags::PASS2,
+                });
+                opts.set("b", &bitrate.to_string());
+                opts.set("stats", stats_path);
+            }
+        }
+
         opts.set("x264-params", x264_params.as_str());
 
         let encoder = encoder.open_with(opts).map_err(|e| {
@@ -67,6 +123,7 @@ impl FfmpegVideoEncoder {
             height: config.height,
             encoder,
             frame_index: 0,
+            keyframe_requested: false,
         })
     }
 
@@ -114,6 +171,12 @@ impl VideoEncoder for FfmpegVideoEncoder {
         let mut output_frame = self.create_yuv_frame_from_i420(&i420_data)?;
         output_frame.set_pts(Some(self.frame_index as i64));
 
+        // `forced-idr` makes libx264 emit an IDR instead of a plain I frame
+        if self.keyframe_requested {
+            output_frame.set_kind(picture::Type::I);
+            self.keyframe_requested = false;
+        }
+
         self.encoder.send_frame(&output_frame).map_err(|e| {
             EncoderError::VideoEncodingFailed(format!("FFmpeg encoding failed: {e}"))
         })?;
@@ -195,6 +258,10 @@ impl VideoEncoder for FfmpegVideoEncoder {
         Ok(vec![])
     }
 
+    fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
     fn flush(mut self: Box<Self>, mut cb: Box<dyn FnMut(Vec<u8>) + 'static>) -> Result<()> {
         let mut empty_count = 0;
         let max_empty_attempts = 3;
@@ -1,13 +1,13 @@
 use crate::{
-    EncodedFrame, EncoderError, ResizedImageBuffer, Result, VideoEncoder, VideoEncoderConfig,
-    rgb_to_i420_yuv,
+    EncodedFrame, EncoderError, RateControl, ResizedImageBuffer, Result, VideoEncoder,
+    VideoEncoderConfig, rgb_to_i420_yuv,
 };
 use image::{ImageBuffer, Rgb};
 use openh264::{
     OpenH264API,
     encoder::{
-        Complexity, Encoder, EncoderConfig, FrameRate, IntraFramePeriod, Profile, RateControlMode,
-        UsageType,
+        BitRate, Complexity, Encoder, EncoderConfig, FrameRate, IntraFramePeriod, Profile, QpRange,
+        RateControlMode, UsageType,
     },
     formats::{RgbSliceU8, YUVBuffer},
 };
@@ -33,10 +33,10 @@ impl OpenH264VideoEncoder {
             .complexity(Complexity::Low)
             .background_detection(false)
             .adaptive_quantization(false)
-            .rate_control_mode(RateControlMode::Timestamp)
             .usage_type(UsageType::ScreenContentRealTime)
             .max_frame_rate(FrameRate::from_hz(config.fps as f32))
             .intra_frame_period(IntraFramePeriod::from_num_frames(config.fps));
+        let encoder_config = Self::apply_rate_control(encoder_config, &config.rate_control)?;
 
         let encoder = Encoder::with_api_config(OpenH264API::from_source(), encoder_config)
             .map_err(|e| {
@@ -56,6 +56,30 @@ impl OpenH264VideoEncoder {
         })
     }
 
+    // OpenH264 has no CRF, the quality is held by pinning the QP to it. It has
+    // no HRD buffer either, so CBR only sets the target bitrate
+    fn apply_rate_control(
+        encoder_config: EncoderConfig,
+        rate_control: &RateControl,
+    ) -> Result<EncoderConfig> {
+        match rate_control {
+            RateControl::Crf(crf) if *crf <= 51 => Ok(encoder_config
+                .rate_control_mode(RateControlMode::Quality)
+                .qp(QpRange::new(*crf, *crf))),
+            RateControl::Crf(crf) => Err(EncoderError::VideoEncodingFailed(format!(
+                "crf {crf} is out of 0 ~ 51"
+            ))),
+            RateControl::Cbr { bitrate, .. } => Ok(encoder_config
+                .rate_control_mode(RateControlMode::Bitrate)
+                .bitrate(BitRate::from_bps(*bitrate))),
+            RateControl::Vbr { .. } | RateControl::TwoPass { .. } => {
+                Err(EncoderError::VideoEncodingFailed(format!(
+                    "OpenH264 doesn't support the rate control {rate_control:?}"
+                )))
+            }
+        }
+    }
+
     fn convert_annex_b_to_length_prefixed(&self, annex_b_data: &[u8]) -> Vec<u8> {
         let mut result = Vec::with_capacity(annex_b_data.len());
         let mut i = 0;
@@ -291,6 +315,10 @@ impl VideoEncoder for OpenH264VideoEncoder {
         Ok(vec![])
     }
 
+    fn request_keyframe(&mut self) {
+        self.encoder.force_intra_frame();
+    }
+
     fn flush(self: Box<Self>, _cb: Box<dyn FnMut(Vec<u8>) + 'static>) -> Result<()> {
         Ok(())
     }
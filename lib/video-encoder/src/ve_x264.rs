@@ -1,112 +1,257 @@
 use crate::{
-    EncodedFrame, EncoderError, ResizedImageBuffer, Result, VIDEO_TIMESCALE, VideoEncoder,
-    VideoEncoderConfig, rgb_to_i420_yuv,
+    EncodedFrame, EncoderError, RateControl, ResizedImageBuffer, Result, TwoPassStage,
+    VIDEO_TIMESCALE, VideoEncoder, VideoEncoderConfig, rgb_to_i420_yuv,
+};
+use std::{ffi::CString, mem::MaybeUninit};
+use x264::{Data, Encoder, Image};
+use x264_sys::{
+    X264_CSP_I420, X264_NAL_HRD_CBR, X264_NAL_HRD_VBR, X264_RC_ABR, X264_RC_CRF, X264_TYPE_AUTO,
+    X264_TYPE_IDR, x264_param_t, x264_picture_t, x264_t,
 };
-use x264::{Colorspace, Encoder, Image, Preset, Setup, Tune};
 
 pub struct X264VideoEncoder {
-    width: u32,
-    height: u32,
+    config: VideoEncoderConfig,
     frame_index: u64,
-    fps: u32,
+    keyframe_requested: bool,
     encoder: Encoder,
+
+    // Owned by `encoder`, used to set the picture type which the crate doesn't expose
+    raw: *mut x264_t,
+
+    // x264 keeps the pointer to the 2-pass statistics path
+    _stats_path: Option<CString>,
 }
 
 impl X264VideoEncoder {
     pub fn new(config: VideoEncoderConfig) -> Result<Self> {
-        let VideoEncoderConfig {
-            width,
-            height,
-            fps,
-            annexb,
-            ..
-        } = config;
-
-        assert!(width > 0 && height > 0);
-        let is_real_time = annexb;
-
-        let encoder = Setup::preset(
-            if is_real_time {
-                Preset::Faster
-            } else {
-                Preset::Superfast
-            },
-            Tune::None,
-            true,
-            true,
-        )
-        .max_keyframe_interval(if is_real_time {
-            fps as i32 * 3
-        } else {
-            fps as i32
-        })
-        .fps(fps, 1)
-        .scenecut_threshold(0)
-        .annexb(annexb)
-        .baseline()
-        .build(Colorspace::I420, width as i32, height as i32)
-        .map_err(|e| {
-            EncoderError::VideoEncodingFailed(format!("Failed to create x264 encoder: {e:?}"))
-        })?;
+        assert!(config.width > 0 && config.height > 0);
 
+        let (raw, stats_path) = Self::build_encoder(&config)?;
         Ok(Self {
-            encoder,
-            width,
-            height,
+            config,
             frame_index: 0,
-            fps,
+            keyframe_requested: false,
+            encoder: unsafe { Encoder::from_raw(raw) },
+            raw,
+            _stats_path: stats_path,
         })
     }
+
+    // The x264 crate only sets the bitrate, so the parameters are set through
+    // x264-sys and the opened encoder is handed to the crate
+    fn build_encoder(config: &VideoEncoderConfig) -> Result<(*mut x264_t, Option<CString>)> {
+        let is_real_time = config.annexb;
+        let preset = if is_real_time {
+            c"faster"
+        } else {
+            c"superfast"
+        };
+
+        let mut param = MaybeUninit::<x264_param_t>::uninit();
+        let err = unsafe {
+            x264_sys::x264_param_default_preset(
+                param.as_mut_ptr(),
+                preset.as_ptr(),
+                c"fastdecode,zerolatency".as_ptr(),
+            )
+        };
+        if err < 0 {
+            return Err(EncoderError::VideoEncodingFailed(
+                "Failed to set the x264 preset".to_string(),
+            ));
+        }
+
+        let mut param = unsafe { param.assume_init() };
+        param.i_keyint_max = if is_real_time {
+            config.fps as i32 * 3
+        } else {
+            config.fps as i32
+        };
+        param.i_fps_num = config.fps;
+        param.i_fps_den = 1;
+        param.i_scenecut_threshold = 0;
+        param.b_annexb = config.annexb as i32;
+
+        let stats_path = Self::apply_rate_control(&mut param, &config.rate_control)?;
+
+        if unsafe { x264_sys::x264_param_apply_profile(&mut param, c"baseline".as_ptr()) } < 0 {
+            return Err(EncoderError::VideoEncodingFailed(
+                "Failed to apply the x264 baseline profile".to_string(),
+            ));
+        }
+
+        param.i_csp = X264_CSP_I420 as i32;
+        param.i_width = config.width as i32;
+        param.i_height = config.height as i32;
+
+        let raw = unsafe { x264_sys::x264_encoder_open(&mut param) };
+        if raw.is_null() {
+            return Err(EncoderError::VideoEncodingFailed(
+                "Failed to create x264 encoder".to_string(),
+            ));
+        }
+
+        Ok((raw, stats_path))
+    }
+
+    // Bitrates of x264 are in kbit/s. Returns the 2-pass statistics path,
+    // which has to outlive the encoder
+    fn apply_rate_control(
+        param: &mut x264_param_t,
+        rate_control: &RateControl,
+    ) -> Result<Option<CString>> {
+        match rate_control {
+            RateControl::Crf(crf) => {
+                if *crf > 51 {
+                    return Err(EncoderError::VideoEncodingFailed(format!(
+                        "crf {crf} is out of 0 ~ 51"
+                    )));
+                }
+
+                param.rc.i_rc_method = X264_RC_CRF as i32;
+                param.rc.f_rf_constant = *crf as f32;
+            }
+            RateControl::Cbr {
+                bitrate,
+                buffer_size,
+            } => {
+                param.rc.i_rc_method = X264_RC_ABR as i32;
+                param.rc.i_bitrate = (bitrate / 1000) as i32;
+                param.rc.i_vbv_max_bitrate = (bitrate / 1000) as i32;
+                param.rc.i_vbv_buffer_size = (buffer_size / 1000) as i32;
+
+                // Pad the stream with filler data so the HRD buffer never underflows
+                param.i_nal_hrd = X264_NAL_HRD_CBR as i32;
+            }
+            RateControl::Vbr {
+                bitrate,
+                max_bitrate,
+                buffer_size,
+            } => {
+                if max_bitrate < bitrate {
+                    return Err(EncoderError::VideoEncodingFailed(format!(
+                        "max bitrate {max_bitrate} is lower than bitrate {bitrate}"
+                    )));
+                }
+
+                param.rc.i_rc_method = X264_RC_ABR as i32;
+                param.rc.i_bitrate = (bitrate / 1000) as i32;
+                param.rc.i_vbv_max_bitrate = (max_bitrate / 1000) as i32;
+                param.rc.i_vbv_buffer_size = (buffer_size / 1000) as i32;
+                param.i_nal_hrd = X264_NAL_HRD_VBR as i32;
+            }
+            RateControl::TwoPass {
+                bitrate,
+                pass,
+                stats_path,
+            } => {
+                let stats_path = stats_path
+                    .to_str()
+                    .and_then(|path| CString::new(path).ok())
+                    .ok_or_else(|| {
+                        EncoderError::VideoEncodingFailed(format!(
+                            "Invalid 2-pass stats path: {}",
+                            stats_path.display()
+                        ))
+                    })?;
+
+                param.rc.i_rc_method = X264_RC_ABR as i32;
+                param.rc.i_bitrate = (bitrate / 1000) as i32;
+                match pass {
+                    TwoPassStage::First => {
+                        param.rc.b_stat_write = 1;
+                        param.rc.psz_stat_out = stats_path.as_ptr() as *mut _;
+                    }
+                    TwoPassStage::Second => {
+                        param.rc.b_stat_read = 1;
+                        param.rc.psz_stat_in = stats_path.as_ptr() as *mut _;
+                    }
+                }
+
+                return Ok(Some(stats_path));
+            }
+        }
+
+        Ok(None)
+    }
 }
 
 impl VideoEncoder for X264VideoEncoder {
     fn encode_frame(&mut self, img: ResizedImageBuffer) -> Result<EncodedFrame> {
+        let (width, height) = (self.config.width, self.config.height);
         let (img_width, img_height) = img.dimensions();
-        if img_width != self.width || img_height != self.height {
+        if img_width != width || img_height != height {
             return Err(EncoderError::ImageProcessingFailed(format!(
                 "frame is already resize. current size: {}x{}. expect size: {}x{}",
-                img_width, img_height, self.width, self.height
+                img_width, img_height, width, height
             )));
         }
 
         // Convert RGB to I420 for x264 encoding using yuv library
-        let i420_data = rgb_to_i420_yuv(img.as_raw(), self.width, self.height)?;
+        let i420_data = rgb_to_i420_yuv(img.as_raw(), width, height)?;
 
         // Create x264 image from I420 buffer using manual plane setup
-        let frame_size = (self.width * self.height) as usize;
+        let frame_size = (width * height) as usize;
         let y_plane = &i420_data[0..frame_size];
         let u_plane = &i420_data[frame_size..frame_size + frame_size / 4];
         let v_plane = &i420_data[frame_size + frame_size / 4..];
 
         let planes = [
             x264::Plane {
-                stride: self.width as i32,
+                stride: width as i32,
                 data: y_plane,
             },
             x264::Plane {
-                stride: self.width as i32 / 2,
+                stride: width as i32 / 2,
                 data: u_plane,
             },
             x264::Plane {
-                stride: self.width as i32 / 2,
+                stride: width as i32 / 2,
                 data: v_plane,
             },
         ];
 
-        let image = Image::new(
-            x264::Colorspace::I420,
-            self.width as i32,
-            self.height as i32,
-            &planes,
-        );
+        let image = Image::new(x264::Colorspace::I420, width as i32, height as i32, &planes);
 
         // Calculate timestamp in x264 timebase units (frame_index * timebase / fps)
         // x264 uses a timebase of 1/90000 by default, so we need to convert frame number to this timescale
-        let timestamp = (self.frame_index * VIDEO_TIMESCALE as u64) / self.fps as u64;
-        let (data, _) = self.encoder.encode(timestamp as i64, image).map_err(|e| {
-            EncoderError::VideoEncodingFailed(format!("x264 encoding failed: {:?}", e))
-        })?;
+        let timestamp = (self.frame_index * VIDEO_TIMESCALE as u64) / self.config.fps as u64;
+
+        let mut picture = MaybeUninit::<x264_picture_t>::uninit();
+        let mut picture = unsafe {
+            x264_sys::x264_picture_init(picture.as_mut_ptr());
+            picture.assume_init()
+        };
+        picture.i_pts = timestamp as i64;
+        picture.img = image.raw();
+
+        // Force an IDR frame instead of waiting for the next keyint
+        picture.i_type = if self.keyframe_requested {
+            X264_TYPE_IDR as i32
+        } else {
+            X264_TYPE_AUTO as i32
+        };
+
+        let mut nals = MaybeUninit::uninit();
+        let mut nals_count = 0;
+        let mut picture_out = MaybeUninit::<x264_picture_t>::uninit();
+        let err = unsafe {
+            x264_sys::x264_encoder_encode(
+                self.raw,
+                nals.as_mut_ptr(),
+                &mut nals_count,
+                &mut picture,
+                picture_out.as_mut_ptr(),
+            )
+        };
+        if err < 0 {
+            return Err(EncoderError::VideoEncodingFailed(format!(
+                "x264 encoding failed: {err}"
+            )));
+        }
+        self.keyframe_requested = false;
 
+        let data = unsafe { Data::from_raw_parts(nals.assume_init(), nals_count as usize) };
         let encoded_data = data.entirety().to_vec();
         let encoded_frame = EncodedFrame::Frame((self.frame_index, encoded_data));
         self.frame_index += 1;
@@ -125,6 +270,10 @@ impl VideoEncoder for X264VideoEncoder {
             .to_vec())
     }
 
+    fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
     fn flush(self: Box<Self>, mut cb: Box<dyn FnMut(Vec<u8>) + 'static>) -> Result<()> {
         let mut items = self.encoder.flush();
         while let Some(result) = items.next() {
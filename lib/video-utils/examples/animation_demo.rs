@@ -0,0 +1,74 @@
+//! Animated GIF/WebP export example
+//!
+//! This example demonstrates exporting a clip of a video as an animation.
+
+use std::path::Path;
+use std::time::Duration;
+use video_utils::animation::{export_gif, export_webp, AnimationExportConfig, DitherMode};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    println!("╔════════════════════════════════════════════════════════════════╗");
+    println!("║              动画导出功能测试                                        ║");
+    println!("╚════════════════════════════════════════════════════════════════╝");
+    println!();
+
+    let input_file = "data/test.mp4";
+    if !Path::new(input_file).exists() {
+        println!("❌ 测试文件不存在: {}", input_file);
+        println!("请先确保有测试视频文件");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all("tmp")?;
+
+    // Test 1: GIF with the default palette dithering
+    println!("【测试1】导出 GIF (前 3 秒, 480px, 10 fps)");
+    println!("=========================================");
+    let config = AnimationExportConfig::new(input_file, "tmp/animation.gif")
+        .with_duration(Some(Duration::from_secs(3)));
+
+    match export_gif(&config) {
+        Ok(_) => print_size("tmp/animation.gif")?,
+        Err(e) => println!("❌ 导出失败: {}", e),
+    }
+    println!();
+
+    // Test 2: GIF limited to 1MB
+    println!("【测试2】导出 GIF (Bayer 抖动, 最大 1MB)");
+    println!("=========================================");
+    let config = AnimationExportConfig::new(input_file, "tmp/animation_1mb.gif")
+        .with_start(Duration::from_secs(1))
+        .with_duration(Some(Duration::from_secs(3)))
+        .with_width(640)
+        .with_dither(DitherMode::Bayer(3))
+        .with_max_size(Some(1024 * 1024));
+
+    match export_gif(&config) {
+        Ok(_) => print_size("tmp/animation_1mb.gif")?,
+        Err(e) => println!("❌ 导出失败: {}", e),
+    }
+    println!();
+
+    // Test 3: WebP
+    println!("【测试3】导出 WebP (前 3 秒, 质量 60)");
+    println!("=========================================");
+    let config = AnimationExportConfig::new(input_file, "tmp/animation.webp")
+        .with_duration(Some(Duration::from_secs(3)))
+        .with_fps(15)
+        .with_webp_quality(60.0);
+
+    match export_webp(&config) {
+        Ok(_) => print_size("tmp/animation.webp")?,
+        Err(e) => println!("❌ 导出失败: {}", e),
+    }
+
+    Ok(())
+}
+
+fn print_size(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let size = std::fs::metadata(path)?.len();
+    println!("✓ 导出完成: {} ({:.2} KB)", path, size as f64 / 1024.0);
+    Ok(())
+}
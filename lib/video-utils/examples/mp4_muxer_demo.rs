@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 use std::time::Duration;
-use video_utils::mp4_muxer::{MP4Muxer, MP4MuxerConfig, AACConfig, FrameData, AudioData};
+use video_utils::mp4_muxer::{MP4Muxer, MP4MuxerConfig, AACConfig, FrameData, AudioData, Mp4Metadata};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
@@ -18,6 +18,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             sample_rate: 44_100,
             channels: 2,
         },
+        metadata: Mp4Metadata {
+            title: Some("MP4 muxer demo".to_string()),
+            encoder: Some("video-utils".to_string()),
+            ..Default::default()
+        },
     };
 
     println!("配置:");
@@ -0,0 +1,51 @@
+//! Picture-in-picture composition example
+//!
+//! This example demonstrates overlaying one video onto another.
+
+use std::path::Path;
+use std::time::Duration;
+use video_utils::editor::pip::{compose_pip, PipConfig, PipPosition};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    println!("╔════════════════════════════════════════════════════════════════╗");
+    println!("║              画中画合成功能测试                                      ║");
+    println!("╚════════════════════════════════════════════════════════════════╝");
+    println!();
+
+    let input_file = "data/test.mp4";
+    if !Path::new(input_file).exists() {
+        println!("❌ 测试文件不存在: {}", input_file);
+        println!("请先确保有测试视频文件");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all("tmp")?;
+
+    // For demo purposes, use the same video twice (in practice: screen + webcam takes)
+    println!("【测试1】右下角画中画 (25% 宽度)");
+    println!("=========================================");
+    let config = PipConfig::new("tmp/pip_bottom_right.mp4");
+
+    match compose_pip(input_file, input_file, config) {
+        Ok(_) => println!("✓ 合成完成: tmp/pip_bottom_right.mp4"),
+        Err(e) => println!("❌ 合成失败: {}", e),
+    }
+    println!();
+
+    println!("【测试2】左上角圆角画中画 (延迟 2 秒出现)");
+    println!("=========================================");
+    let config = PipConfig::new("tmp/pip_rounded.mp4")
+        .with_position(PipPosition::TopLeft)
+        .with_scale(0.35)
+        .with_border_radius(32)
+        .with_offset(Duration::from_secs(2));
+
+    match compose_pip(input_file, input_file, config) {
+        Ok(_) => println!("✓ 合成完成: tmp/pip_rounded.mp4"),
+        Err(e) => println!("❌ 合成失败: {}", e),
+    }
+
+    Ok(())
+}
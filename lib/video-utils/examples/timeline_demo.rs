@@ -0,0 +1,55 @@
+//! Timeline rendering example
+//!
+//! This example demonstrates rendering a multi-clip project in one pass.
+
+use std::path::Path;
+use std::time::Duration;
+use video_utils::timeline::{render, Clip, Overlay, Project, Transition, TransitionKind};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    println!("╔════════════════════════════════════════════════════════════════╗");
+    println!("║              时间线多片段渲染测试                                    ║");
+    println!("╚════════════════════════════════════════════════════════════════╝");
+    println!();
+
+    let input_file = "data/test.mp4";
+    if !Path::new(input_file).exists() {
+        println!("❌ 测试文件不存在: {}", input_file);
+        println!("请先确保有测试视频文件");
+        return Ok(());
+    }
+
+    std::fs::create_dir_all("tmp")?;
+
+    println!("【测试1】三个片段 + 转场 + 画中画");
+    println!("=========================================");
+    let project = Project::new(1280, 720)
+        .add_clip(Clip::new(input_file).with_out_point(Duration::from_secs(4)))
+        .add_clip(
+            Clip::new(input_file)
+                .with_in_point(Duration::from_secs(2))
+                .with_out_point(Duration::from_secs(6))
+                .with_transition(Transition::new(TransitionKind::Dissolve, Duration::from_secs(1))),
+        )
+        .add_clip(
+            Clip::new(input_file)
+                .with_out_point(Duration::from_secs(3))
+                .with_transition(Transition::new(TransitionKind::SlideLeft, Duration::from_millis(500))),
+        )
+        .add_overlay(
+            Overlay::new(input_file, Duration::from_secs(2))
+                .with_duration(Duration::from_secs(4))
+                .with_width(320),
+        );
+
+    match render(&project, "tmp/timeline.mp4", |progress| {
+        print!("\r  进度: {:.0}%", progress * 100.0);
+    }) {
+        Ok(_) => println!("\n✓ 渲染完成: tmp/timeline.mp4"),
+        Err(e) => println!("\n❌ 渲染失败: {}", e),
+    }
+
+    Ok(())
+}
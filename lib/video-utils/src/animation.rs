@@ -0,0 +1,553 @@
+//! Animated GIF and WebP export
+
+use crate::encode::{self, StreamTarget};
+use crate::{Error, Result};
+use derivative::Derivative;
+use derive_setters::Setters;
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+use std::time::Duration;
+
+/// Re-encode attempts with a smaller width when the output exceeds `max_size`
+const MAX_SIZE_ATTEMPTS: usize = 5;
+
+/// Smallest width tried when shrinking the output to fit `max_size`
+const MIN_WIDTH: u32 = 64;
+
+/// Dithering applied when mapping frames to the GIF palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMode {
+    /// No dithering, smallest files but visible banding
+    None,
+    /// Ordered 8x8 Bayer dithering, scale 0-5 (higher is less visible pattern)
+    Bayer(u8),
+    /// Floyd-Steinberg error diffusion
+    FloydSteinberg,
+    /// Sierra-2-4A error diffusion
+    Sierra2_4a,
+}
+
+impl DitherMode {
+    fn to_filter_option(self) -> String {
+        match self {
+            DitherMode::None => "dither=none".to_string(),
+            DitherMode::Bayer(scale) => format!("dither=bayer:bayer_scale={}", scale.min(5)),
+            DitherMode::FloydSteinberg => "dither=floyd_steinberg".to_string(),
+            DitherMode::Sierra2_4a => "dither=sierra2_4a".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnimationFormat {
+    Gif,
+    WebP,
+}
+
+impl AnimationFormat {
+    fn muxer_name(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "webp",
+        }
+    }
+
+    fn encoder_name(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "libwebp_anim",
+        }
+    }
+}
+
+/// Configuration for exporting a time range of a video as an animation
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct AnimationExportConfig {
+    /// Input video path
+    #[derivative(Default(value = "String::new()"))]
+    pub input: String,
+    /// Output animation path
+    #[derivative(Default(value = "String::new()"))]
+    pub output: String,
+    /// Start of the exported range
+    pub start: Duration,
+    /// Length of the exported range (None = until the end of the video)
+    pub duration: Option<Duration>,
+    /// Output frame rate
+    #[derivative(Default(value = "10"))]
+    pub fps: u32,
+    /// Output width, height keeps the aspect ratio (0 = source width)
+    #[derivative(Default(value = "480"))]
+    pub width: u32,
+    /// Number of loops (0 = loop forever)
+    pub loop_count: u16,
+    /// GIF palette dithering
+    #[derivative(Default(value = "DitherMode::Sierra2_4a"))]
+    pub dither: DitherMode,
+    /// GIF palette size (2-256)
+    #[derivative(Default(value = "256"))]
+    pub max_colors: u32,
+    /// WebP quality (0-100)
+    #[derivative(Default(value = "75.0"))]
+    pub webp_quality: f32,
+    /// Lossless WebP encoding
+    pub webp_lossless: bool,
+    /// Maximum output file size in bytes, the width is reduced until it fits
+    pub max_size: Option<u64>,
+}
+
+impl AnimationExportConfig {
+    /// Create a new export config (convenience method)
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self::default()
+            .with_input(input.into())
+            .with_output(output.into())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.input.is_empty() {
+            return Err(Error::InvalidConfig("Input path is empty".to_string()));
+        }
+        if self.output.is_empty() {
+            return Err(Error::InvalidConfig("Output path is empty".to_string()));
+        }
+        if !Path::new(&self.input).exists() {
+            return Err(Error::InvalidConfig(format!(
+                "Input file does not exist: {}",
+                self.input
+            )));
+        }
+        if self.fps == 0 {
+            return Err(Error::InvalidConfig("FPS must be greater than 0".to_string()));
+        }
+        if self.duration.is_some_and(|d| d.is_zero()) {
+            return Err(Error::InvalidConfig("Duration must be greater than 0".to_string()));
+        }
+        if !(2..=256).contains(&self.max_colors) {
+            return Err(Error::InvalidConfig(format!(
+                "Max colors must be in 2-256, got {}",
+                self.max_colors
+            )));
+        }
+        if !(0.0..=100.0).contains(&self.webp_quality) {
+            return Err(Error::InvalidConfig(format!(
+                "WebP quality must be in 0-100, got {}",
+                self.webp_quality
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Export a time range of a video as an animated GIF
+///
+/// A palette is generated from the exported frames and applied with the configured
+/// dithering, which gives much better colors than a fixed palette.
+///
+/// # Arguments
+/// * `config` - Export configuration
+///
+/// # Example
+/// ```no_run
+/// use video_utils::animation::{export_gif, AnimationExportConfig, DitherMode};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = AnimationExportConfig::new("input.mp4", "output.gif")
+///     .with_start(Duration::from_secs(5))
+///     .with_duration(Some(Duration::from_secs(3)))
+///     .with_width(320)
+///     .with_dither(DitherMode::Bayer(3))
+///     .with_max_size(Some(5 * 1024 * 1024));
+///
+/// export_gif(&config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn export_gif(config: &AnimationExportConfig) -> Result<()> {
+    export_animation(config, AnimationFormat::Gif)
+}
+
+/// Export a time range of a video as an animated WebP
+///
+/// Requires FFmpeg built with `libwebp`.
+///
+/// # Arguments
+/// * `config` - Export configuration
+///
+/// # Example
+/// ```no_run
+/// use video_utils::animation::{export_webp, AnimationExportConfig};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = AnimationExportConfig::new("input.mp4", "output.webp")
+///     .with_duration(Some(Duration::from_secs(5)))
+///     .with_fps(15)
+///     .with_webp_quality(60.0);
+///
+/// export_webp(&config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn export_webp(config: &AnimationExportConfig) -> Result<()> {
+    export_animation(config, AnimationFormat::WebP)
+}
+
+fn export_animation(config: &AnimationExportConfig, format: AnimationFormat) -> Result<()> {
+    config.validate()?;
+
+    ffmpeg::init().map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    log::info!(
+        "Exporting {:?}: {} -> {} (start: {:?}, duration: {:?})",
+        format, config.input, config.output, config.start, config.duration
+    );
+
+    let mut width = config.width;
+    for attempt in 1..=MAX_SIZE_ATTEMPTS {
+        let output_width = encode_animation(config, format, width)?;
+        let size = std::fs::metadata(&config.output)?.len();
+
+        let Some(max_size) = config.max_size else {
+            break;
+        };
+
+        if size <= max_size {
+            break;
+        }
+
+        let next_width = shrink_width(output_width, size, max_size);
+        if attempt == MAX_SIZE_ATTEMPTS || next_width < MIN_WIDTH {
+            return Err(Error::InvalidConfig(format!(
+                "Can't fit the animation into {} bytes, smallest output is {} bytes at width {}",
+                max_size, size, output_width
+            )));
+        }
+
+        log::info!(
+            "Output is {} bytes, larger than {} bytes. Retry with width {} -> {}",
+            size, max_size, output_width, next_width
+        );
+        width = next_width;
+    }
+
+    log::info!("Animation export complete: {}", config.output);
+
+    Ok(())
+}
+
+/// Decode, filter and encode the configured range once, returns the output width
+fn encode_animation(config: &AnimationExportConfig, format: AnimationFormat, width: u32) -> Result<u32> {
+    let mut input_ctx = ffmpeg::format::input(&config.input)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let (stream_index, time_base, parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg("No video stream found".to_string()))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+        .decoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+    let (out_width, out_height) = output_size(decoder.width(), decoder.height(), width);
+    log::debug!(
+        "Source: {}x{}, output: {}x{} @ {} fps",
+        decoder.width(), decoder.height(), out_width, out_height, config.fps
+    );
+
+    // Filter graph: fps + scale, plus palette generation for GIF
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    let buffer_args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+        decoder.width(),
+        decoder.height(),
+        decoder
+            .format()
+            .descriptor()
+            .ok_or_else(|| Error::FFmpeg("Unknown pixel format".to_string()))?
+            .name(),
+        time_base,
+        decoder.aspect_ratio()
+    );
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffer").unwrap(), "in", &buffer_args)
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffer filter: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffersink").unwrap(), "out", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffersink: {}", e)))?;
+
+    let scale_spec = format!(
+        "fps={},scale={}:{}:flags=lanczos",
+        config.fps, out_width, out_height
+    );
+
+    let filter_spec = match format {
+        AnimationFormat::Gif => format!(
+            "{},split[a][b];[a]palettegen=max_colors={}:stats_mode=diff[p];[b][p]paletteuse={}",
+            scale_spec,
+            config.max_colors,
+            config.dither.to_filter_option()
+        ),
+        AnimationFormat::WebP => format!("{},format=yuv420p", scale_spec),
+    };
+
+    log::debug!("Filter spec: {}", filter_spec);
+
+    filter_graph
+        .output("in", 0)
+        .and_then(|p| p.input("out", 0))
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?
+        .parse(&filter_spec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    // Encoder, the fps filter outputs frames in a 1/fps time base
+    let encoder_time_base = ffmpeg::Rational::new(1, config.fps as i32);
+
+    let codec = ffmpeg::encoder::find_by_name(format.encoder_name()).ok_or_else(|| {
+        Error::FFmpeg(format!("Encoder {} not found", format.encoder_name()))
+    })?;
+
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create encoder: {}", e)))?;
+
+    encoder.set_width(out_width);
+    encoder.set_height(out_height);
+    encoder.set_time_base(encoder_time_base);
+    encoder.set_frame_rate(Some(ffmpeg::Rational::new(config.fps as i32, 1)));
+
+    let mut encoder_opts = ffmpeg::Dictionary::new();
+    match format {
+        AnimationFormat::Gif => encoder.set_format(ffmpeg::format::Pixel::PAL8),
+        AnimationFormat::WebP => {
+            encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+            encoder_opts.set("quality", &config.webp_quality.to_string());
+            encoder_opts.set("lossless", if config.webp_lossless { "1" } else { "0" });
+        }
+    }
+
+    let mut encoder = encoder
+        .open_with(encoder_opts)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open encoder: {}", e)))?;
+
+    // Output
+    let mut output_ctx = ffmpeg::format::output_as(&config.output, format.muxer_name())
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    {
+        let mut output_stream = output_ctx
+            .add_stream(codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add output stream: {}", e)))?;
+        output_stream.set_parameters(&encoder);
+        output_stream.set_time_base(encoder_time_base);
+    }
+
+    let mut muxer_opts = ffmpeg::Dictionary::new();
+    muxer_opts.set("loop", &config.loop_count.to_string());
+
+    output_ctx
+        .write_header_with(muxer_opts)
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let target = StreamTarget {
+        stream_index: 0,
+        encoder_time_base,
+        output_time_base: output_ctx
+            .stream(0)
+            .ok_or_else(|| Error::FFmpeg("Failed to get output stream".to_string()))?
+            .time_base(),
+    };
+
+    // Seek to the keyframe before the start of the range
+    if !config.start.is_zero() {
+        let seek_timestamp = config.start.as_micros() as i64;
+        input_ctx
+            .seek(seek_timestamp, ..seek_timestamp)
+            .map_err(|e| Error::FFmpeg(format!("Failed to seek: {}", e)))?;
+    }
+
+    let start_ts = duration_to_ts(config.start, time_base);
+    let end_ts = config
+        .duration
+        .map(|duration| duration_to_ts(config.start + duration, time_base));
+
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut frame_count = 0;
+    let mut finished = false;
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let Some(ts) = decoded.timestamp().or(decoded.pts()) else {
+                continue;
+            };
+
+            if ts < start_ts {
+                continue;
+            }
+
+            if end_ts.is_some_and(|end_ts| ts >= end_ts) {
+                finished = true;
+                break;
+            }
+
+            // Shift the range to start at zero
+            decoded.set_pts(Some(ts - start_ts));
+            push_frame(&mut filter_graph, Some(&decoded))?;
+            encode::drain_filter(&mut filter_graph, "out", &mut encoder, &mut output_ctx, &target, |_| {})?;
+            frame_count += 1;
+        }
+
+        if finished {
+            break;
+        }
+    }
+
+    if !finished {
+        decoder
+            .send_eof()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            let Some(ts) = decoded.timestamp().or(decoded.pts()) else {
+                continue;
+            };
+
+            if ts < start_ts || end_ts.is_some_and(|end_ts| ts >= end_ts) {
+                continue;
+            }
+
+            decoded.set_pts(Some(ts - start_ts));
+            push_frame(&mut filter_graph, Some(&decoded))?;
+            encode::drain_filter(&mut filter_graph, "out", &mut encoder, &mut output_ctx, &target, |_| {})?;
+            frame_count += 1;
+        }
+    }
+
+    if frame_count == 0 {
+        return Err(Error::InvalidConfig(format!(
+            "No frames in the exported range (start: {:?}, duration: {:?})",
+            config.start, config.duration
+        )));
+    }
+
+    // The palette is only generated once all frames are seen, so most GIF frames come out here
+    push_frame(&mut filter_graph, None)?;
+    encode::drain_filter(&mut filter_graph, "out", &mut encoder, &mut output_ctx, &target, |_| {})?;
+
+    encode::finish_encoder(&mut encoder, &mut output_ctx, &target)?;
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    log::debug!("Encoded {} source frames into {}", frame_count, config.output);
+
+    Ok(out_width)
+}
+
+/// Add a frame to the filter graph, `None` flushes it
+fn push_frame(filter_graph: &mut ffmpeg::filter::Graph, frame: Option<&ffmpeg::frame::Video>) -> Result<()> {
+    let mut in_filter = filter_graph
+        .get("in")
+        .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?;
+
+    match frame {
+        Some(frame) => in_filter
+            .source()
+            .add(frame)
+            .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e))),
+        None => in_filter
+            .source()
+            .flush()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e))),
+    }
+}
+
+fn duration_to_ts(duration: Duration, time_base: ffmpeg::Rational) -> i64 {
+    (duration.as_secs_f64() * time_base.denominator() as f64 / time_base.numerator() as f64) as i64
+}
+
+/// Output size for the requested width, keeping the aspect ratio with even dimensions
+fn output_size(src_width: u32, src_height: u32, width: u32) -> (u32, u32) {
+    let width = if width == 0 { src_width } else { width.min(src_width) };
+    let height = (src_height as u64 * width as u64 / src_width.max(1) as u64) as u32;
+
+    ((width & !1).max(2), (height & !1).max(2))
+}
+
+/// Next width to try so that the output roughly fits into `max_size`.
+/// File size scales with the frame area, so the width scales with its square root.
+fn shrink_width(width: u32, size: u64, max_size: u64) -> u32 {
+    let factor = (max_size as f64 / size as f64).sqrt() * 0.95;
+    (width as f64 * factor.clamp(0.5, 0.9)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_config_default() {
+        let config = AnimationExportConfig::default();
+        assert_eq!(config.fps, 10);
+        assert_eq!(config.width, 480);
+        assert_eq!(config.loop_count, 0);
+        assert_eq!(config.dither, DitherMode::Sierra2_4a);
+        assert_eq!(config.max_colors, 256);
+        assert_eq!(config.max_size, None);
+    }
+
+    #[test]
+    fn test_export_config_validation() {
+        assert!(AnimationExportConfig::default().validate().is_err());
+        assert!(AnimationExportConfig::new("missing.mp4", "out.gif").validate().is_err());
+    }
+
+    #[test]
+    fn test_dither_filter_option() {
+        assert_eq!(DitherMode::None.to_filter_option(), "dither=none");
+        assert_eq!(DitherMode::Bayer(9).to_filter_option(), "dither=bayer:bayer_scale=5");
+        assert_eq!(DitherMode::FloydSteinberg.to_filter_option(), "dither=floyd_steinberg");
+    }
+
+    #[test]
+    fn test_output_size() {
+        assert_eq!(output_size(1920, 1080, 480), (480, 270));
+        assert_eq!(output_size(1920, 1080, 0), (1920, 1080));
+        assert_eq!(output_size(640, 480, 1280), (640, 480));
+        assert_eq!(output_size(1366, 768, 321), (320, 180));
+    }
+
+    #[test]
+    fn test_shrink_width() {
+        assert_eq!(shrink_width(480, 4_000_000, 1_000_000), 240);
+        assert!(shrink_width(480, 1_100_000, 1_000_000) < 480);
+    }
+}
@@ -146,6 +146,138 @@ pub fn extract_all_audio<P: AsRef<Path>>(video_path: P) -> Result<AudioSamples>
     })
 }
 
+/// Decode the best audio stream of a media file and downmix it to mono
+///
+/// The samples are passed to `on_samples` chunk by chunk together with the sample
+/// rate, so whole files can be processed without keeping them in memory.
+///
+/// # Arguments
+///
+/// * `media_path` - Path to the media file
+/// * `on_samples` - Called with the sample rate and the mono samples in [-1.0, 1.0]
+///
+/// # Returns
+///
+/// Returns the sample rate of the audio stream
+///
+/// # Example
+///
+/// ```no_run
+/// use video_utils::audio_extraction::decode_audio_mono;
+///
+/// let mut peak = 0.0f32;
+/// decode_audio_mono("video.mp4", |_, samples| {
+///     peak = samples.iter().fold(peak, |peak, s| peak.max(s.abs()));
+/// }).unwrap();
+/// println!("Peak: {:.3}", peak);
+/// ```
+pub fn decode_audio_mono<P, F>(media_path: P, mut on_samples: F) -> Result<u32>
+where
+    P: AsRef<Path>,
+    F: FnMut(u32, &[f32]),
+{
+    let media_path = media_path.as_ref();
+    if !media_path.exists() {
+        return Err(Error::IO(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("File not found: {}", media_path.display()),
+        )));
+    }
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(media_path)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let (stream_index, parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Audio)
+            .ok_or_else(|| Error::FFmpeg("No audio stream found in input file".to_string()))?;
+        (stream.index(), stream.parameters())
+    };
+
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+        .decoder()
+        .audio()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create audio decoder: {}", e)))?;
+
+    let sample_rate = decoder.rate();
+    let mut frame = ffmpeg::frame::Audio::empty();
+    let mut mono = Vec::new();
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        if let Err(e) = decoder.send_packet(&packet) {
+            log::warn!("Skip undecodable audio packet: {}", e);
+            continue;
+        }
+
+        while decoder.receive_frame(&mut frame).is_ok() {
+            frame_to_mono(&frame, &mut mono);
+            on_samples(sample_rate, &mono);
+        }
+    }
+
+    decoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+
+    while decoder.receive_frame(&mut frame).is_ok() {
+        frame_to_mono(&frame, &mut mono);
+        on_samples(sample_rate, &mono);
+    }
+
+    Ok(sample_rate)
+}
+
+/// Average all channels of a decoded frame into `mono`
+fn frame_to_mono(frame: &ffmpeg::frame::Audio, mono: &mut Vec<f32>) {
+    use ffmpeg::format::Sample;
+
+    let samples = frame.samples();
+    let channels = (frame.channels() as usize).max(1);
+    let format = frame.format();
+    let bytes = format.bytes();
+
+    mono.clear();
+    mono.resize(samples, 0.0);
+
+    for channel in 0..channels {
+        for (i, value) in mono.iter_mut().enumerate() {
+            let (plane, offset) = if format.is_planar() {
+                (channel, i * bytes)
+            } else {
+                (0, (i * channels + channel) * bytes)
+            };
+
+            let data = &frame.data(plane)[offset..offset + bytes];
+            *value += match format {
+                Sample::U8(_) => (data[0] as f32 - 128.0) / 128.0,
+                Sample::I16(_) => i16::from_ne_bytes([data[0], data[1]]) as f32 / 32768.0,
+                Sample::I32(_) => {
+                    i32::from_ne_bytes(data.try_into().unwrap()) as f32 / 2_147_483_648.0
+                }
+                Sample::I64(_) => {
+                    (i64::from_ne_bytes(data.try_into().unwrap()) as f64 / 9.223_372_036_854_776e18) as f32
+                }
+                Sample::F32(_) => f32::from_ne_bytes(data.try_into().unwrap()),
+                Sample::F64(_) => f64::from_ne_bytes(data.try_into().unwrap()) as f32,
+                Sample::None => 0.0,
+            };
+        }
+    }
+
+    if channels > 1 {
+        mono.iter_mut().for_each(|value| *value /= channels as f32);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
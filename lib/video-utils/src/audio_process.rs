@@ -20,6 +20,10 @@ pub struct LoudnormConfig {
     /// True peak in dBFS (default: -1.5)
     #[derivative(Default(value = "-1.5"))]
     pub tp: f32,
+
+    /// Loudness measured by a first pass (None = single-pass dynamic normalization)
+    #[setters(strip_option)]
+    pub measured: Option<LoudnessMeasurement>,
 }
 
 impl LoudnormConfig {
@@ -30,10 +34,73 @@ impl LoudnormConfig {
 
     /// Build the loudnorm filter specification string
     fn build_filter_spec(&self) -> String {
-        format!("I={}:LRA={}:TP={}", self.target_i, self.lra, self.tp)
+        let mut spec = format!("I={}:LRA={}:TP={}", self.target_i, self.lra, self.tp);
+
+        // With the measured values loudnorm can apply a single linear gain
+        // instead of dynamic compression, it falls back to dynamic mode by itself
+        // when the linear gain would exceed the true peak limit.
+        if let Some(measured) = self.measured {
+            spec.push_str(&format!(
+                ":measured_I={:.2}:measured_LRA={:.2}:measured_TP={:.2}:measured_thresh={:.2}:linear=true",
+                measured.integrated.clamp(-99.0, 0.0),
+                measured.lra.clamp(0.0, 99.0),
+                measured.true_peak.clamp(-99.0, 99.0),
+                measured.threshold.clamp(-99.0, 0.0),
+            ));
+        }
+
+        spec
+    }
+}
+
+/// Loudness normalization presets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoudnessPreset {
+    /// Podcast and streaming delivery: -16 LUFS, 11 LU, -1.5 dBTP
+    Podcast,
+
+    /// EBU R128 broadcast delivery: -23 LUFS, 7 LU, -1 dBTP
+    Broadcast,
+}
+
+impl LoudnessPreset {
+    /// Loudness normalization configuration of the preset
+    pub fn loudnorm_config(self) -> LoudnormConfig {
+        match self {
+            LoudnessPreset::Podcast => LoudnormConfig::new()
+                .with_target_i(-16.0)
+                .with_lra(11.0)
+                .with_tp(-1.5),
+            LoudnessPreset::Broadcast => LoudnormConfig::new()
+                .with_target_i(-23.0)
+                .with_lra(7.0)
+                .with_tp(-1.0),
+        }
+    }
+}
+
+impl From<LoudnessPreset> for LoudnormConfig {
+    fn from(preset: LoudnessPreset) -> Self {
+        preset.loudnorm_config()
     }
 }
 
+/// EBU R128 loudness of an audio stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness in LUFS
+    pub integrated: f32,
+
+    /// Loudness range in LU
+    pub lra: f32,
+
+    /// Maximum true peak over all channels in dBTP
+    pub true_peak: f32,
+
+    /// Relative gating threshold in LUFS
+    pub threshold: f32,
+}
+
 /// Audio processing configuration
 #[derive(Debug, Clone, Derivative, Setters)]
 #[derivative(Default)]
@@ -281,22 +348,23 @@ pub fn process_audio(config: &AudioProcessConfig) -> Result<()> {
 
     // Build filter specification
     // Apply volume if configured
-    // aresample will handle sample rate conversion if needed
-    let _loudnorm_spec = config.loudnorm.build_filter_spec();
+    // loudnorm always outputs 192kHz, aresample converts back to the input rate
+    let loudnorm_spec = config.loudnorm.build_filter_spec();
 
     // Calculate input duration in samples for trimming
     let input_duration = input_ctx.duration() as f64 / 1_000_000.0; // microseconds to seconds
     let input_duration_ts = (input_duration * sample_rate as f64) as i64;
     log::debug!("Input duration: {:.2}s ({} samples)", input_duration, input_duration_ts);
 
-    let filter_spec = if let Some(vol) = config.volume {
-        format!(
-            "volume={},aformat=sample_fmts=fltp,asetnsamples=1024",
-            vol
-        )
-    } else {
-        "aformat=sample_fmts=fltp,asetnsamples=1024".to_string()
-    };
+    let volume_spec = config
+        .volume
+        .map(|vol| format!("volume={},", vol))
+        .unwrap_or_default();
+
+    let filter_spec = format!(
+        "{}loudnorm={},aresample={},aformat=sample_fmts=fltp,asetnsamples=1024",
+        volume_spec, loudnorm_spec, sample_rate
+    );
 
     log::debug!("Filter spec: {}", filter_spec);
 
@@ -436,6 +504,29 @@ pub fn process_audio(config: &AudioProcessConfig) -> Result<()> {
         }
     }
 
+    // Flush filter graph, loudnorm keeps a lookahead buffer
+    in_filter
+        .source()
+        .flush()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+
+    while out_filter.sink().frame(&mut out_frame).is_ok() {
+        encoder
+            .send_frame(&out_frame)
+            .map_err(|e| Error::FFmpeg(format!("Encoder send failed: {}", e)))?;
+
+        while encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(output_audio_stream_index);
+            packet.rescale_ts(input_time_base, output_time_base);
+
+            packet
+                .write(&mut output_ctx)
+                .map_err(|e| Error::FFmpeg(format!("Failed to write packet: {}", e)))?;
+        }
+
+        frame_count += 1;
+    }
+
     // Flush encoder
     encoder
         .send_eof()
@@ -463,6 +554,237 @@ pub fn process_audio(config: &AudioProcessConfig) -> Result<()> {
     Ok(())
 }
 
+/// Measure the EBU R128 loudness of the audio in a media file
+///
+/// # Arguments
+///
+/// * `input` - Path to the media file
+///
+/// # Returns
+///
+/// Returns the `LoudnessMeasurement` of the best audio stream
+///
+/// # Example
+///
+/// ```no_run
+/// use video_utils::audio_process::measure_loudness;
+///
+/// let loudness = measure_loudness("input.mp4").unwrap();
+/// println!("{} LUFS, {} dBTP", loudness.integrated, loudness.true_peak);
+/// ```
+pub fn measure_loudness<P: AsRef<Path>>(input: P) -> Result<LoudnessMeasurement> {
+    let input = input.as_ref();
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(input)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let input_audio_stream = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .ok_or_else(|| Error::FFmpeg("No audio stream found in input file".to_string()))?;
+    let audio_stream_index = input_audio_stream.index();
+
+    let decoder_context =
+        ffmpeg::codec::context::Context::from_parameters(input_audio_stream.parameters())
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?;
+
+    let mut decoder = decoder_context
+        .decoder()
+        .audio()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create audio decoder: {}", e)))?;
+
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    let buffer_args = format!(
+        "time_base=1/{}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+        decoder.rate(),
+        decoder.rate(),
+        format_sample_fmt(decoder.format()),
+        decoder.channel_layout().bits()
+    );
+
+    filter_graph
+        .add(
+            &ffmpeg::filter::find("abuffer").unwrap(),
+            "in",
+            &buffer_args,
+        )
+        .map_err(|e| Error::FFmpeg(format!("Failed to add abuffer filter: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("abuffersink").unwrap(), "out", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add abuffersink: {}", e)))?;
+
+    // ebur128 attaches the running measurements to the frame metadata
+    filter_graph
+        .output("in", 0)
+        .and_then(|p| p.input("out", 0))
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?
+        .parse("ebur128=metadata=1:peak=true")
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    let mut in_filter = filter_graph
+        .get("in")
+        .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?;
+
+    let mut out_filter = filter_graph
+        .get("out")
+        .ok_or_else(|| Error::FFmpeg("Failed to get out filter".to_string()))?;
+
+    let mut in_frame = ffmpeg::frame::Audio::empty();
+    let mut out_frame = ffmpeg::frame::Audio::empty();
+    let mut meter = LoudnessMeter::default();
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != audio_stream_index {
+            continue;
+        }
+
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+
+        while decoder.receive_frame(&mut in_frame).is_ok() {
+            in_filter
+                .source()
+                .add(&in_frame)
+                .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+
+            while out_filter.sink().frame(&mut out_frame).is_ok() {
+                meter.update(&out_frame);
+            }
+        }
+    }
+
+    decoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+
+    while decoder.receive_frame(&mut in_frame).is_ok() {
+        in_filter
+            .source()
+            .add(&in_frame)
+            .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+
+        while out_filter.sink().frame(&mut out_frame).is_ok() {
+            meter.update(&out_frame);
+        }
+    }
+
+    in_filter
+        .source()
+        .flush()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+
+    while out_filter.sink().frame(&mut out_frame).is_ok() {
+        meter.update(&out_frame);
+    }
+
+    let measurement = meter.finish()?;
+    log::info!(
+        "Measured loudness of {}: I={:.2} LUFS, LRA={:.2} LU, TP={:.2} dBTP",
+        input.display(),
+        measurement.integrated,
+        measurement.lra,
+        measurement.true_peak
+    );
+
+    Ok(measurement)
+}
+
+/// Normalize the loudness of the audio in a video file to a preset target
+///
+/// Runs two passes: the loudness of the input is measured first, then the audio
+/// is corrected with the measured values so that a single linear gain can be used
+/// whenever the true peak limit allows it.
+///
+/// # Arguments
+///
+/// * `input` - Input video file path
+/// * `output` - Output video file path
+/// * `preset` - Target loudness preset
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the operation fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use video_utils::audio_process::{normalize_loudness, LoudnessPreset};
+///
+/// normalize_loudness("input.mp4", "output.mp4", LoudnessPreset::Podcast).unwrap();
+/// ```
+pub fn normalize_loudness(input: &str, output: &str, preset: LoudnessPreset) -> Result<()> {
+    let measured = measure_loudness(input)?;
+
+    let mut loudnorm = preset.loudnorm_config();
+    if measured.integrated.is_finite() {
+        loudnorm = loudnorm.with_measured(measured);
+    } else {
+        log::warn!("Input is silent, skip loudness measurement: {}", input);
+    }
+
+    let config = AudioProcessConfig::new()
+        .with_input(input.to_string())
+        .with_output(output.to_string())
+        .with_loudnorm(loudnorm);
+
+    process_audio(&config)
+}
+
+/// Collects the ebur128 frame metadata, the last values cover the whole stream
+#[derive(Debug, Default)]
+struct LoudnessMeter {
+    integrated: Option<f32>,
+    lra: Option<f32>,
+    true_peak: Option<f32>,
+}
+
+impl LoudnessMeter {
+    fn update(&mut self, frame: &ffmpeg::frame::Audio) {
+        let metadata = frame.metadata();
+        let value = |key: &str| metadata.get(key).and_then(|v| v.trim().parse::<f32>().ok());
+
+        if let Some(integrated) = value("lavfi.r128.I") {
+            self.integrated = Some(integrated);
+        }
+
+        if let Some(lra) = value("lavfi.r128.LRA") {
+            self.lra = Some(lra);
+        }
+
+        for (key, v) in metadata.iter() {
+            if key.starts_with("lavfi.r128.true_peaks_ch")
+                && let Ok(peak) = v.trim().parse::<f32>()
+            {
+                self.true_peak = Some(self.true_peak.map_or(peak, |p| p.max(peak)));
+            }
+        }
+    }
+
+    fn finish(self) -> Result<LoudnessMeasurement> {
+        let integrated = self
+            .integrated
+            .ok_or_else(|| Error::FFmpeg("No loudness measurement produced".to_string()))?;
+
+        Ok(LoudnessMeasurement {
+            integrated,
+            lra: self.lra.unwrap_or(0.0),
+            true_peak: self.true_peak.unwrap_or(-99.0),
+            // The relative gate of EBU R128 sits 10 LU below the integrated loudness
+            threshold: integrated - 10.0,
+        })
+    }
+}
+
 /// Format sample format for filter arguments
 fn format_sample_fmt(fmt: ffmpeg::format::Sample) -> String {
     use ffmpeg::format::sample::Type;
@@ -512,6 +834,30 @@ mod tests {
         assert_eq!(spec, "I=-16:LRA=11:TP=-1.5");
     }
 
+    #[test]
+    fn test_loudnorm_filter_spec_measured() {
+        let config = LoudnormConfig::new().with_measured(LoudnessMeasurement {
+            integrated: -27.5,
+            lra: 6.25,
+            true_peak: -4.0,
+            threshold: -37.5,
+        });
+        assert_eq!(
+            config.build_filter_spec(),
+            "I=-16:LRA=11:TP=-1.5:measured_I=-27.50:measured_LRA=6.25:measured_TP=-4.00:measured_thresh=-37.50:linear=true"
+        );
+    }
+
+    #[test]
+    fn test_loudness_presets() {
+        let podcast = LoudnessPreset::Podcast.loudnorm_config();
+        assert_eq!(podcast.build_filter_spec(), "I=-16:LRA=11:TP=-1.5");
+
+        let broadcast: LoudnormConfig = LoudnessPreset::Broadcast.into();
+        assert_eq!(broadcast.build_filter_spec(), "I=-23:LRA=7:TP=-1");
+        assert!(broadcast.measured.is_none());
+    }
+
     #[test]
     fn test_audio_process_config_default() {
         let config = AudioProcessConfig::default();
@@ -0,0 +1,562 @@
+//! Audio track replacement and mixing
+//!
+//! Dub a recording with a separately recorded voiceover or add background
+//! music. The video stream is copied without re-encoding, only the audio is
+//! rendered through an FFmpeg filter graph and encoded to AAC.
+
+use crate::encode::{self, StreamTarget};
+use crate::{Result, Error};
+use ffmpeg_next as ffmpeg;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Sample rate of the rendered audio track
+const OUTPUT_SAMPLE_RATE: u32 = 48000;
+
+/// Bitrate of the rendered audio track
+const OUTPUT_AUDIO_BITRATE: usize = 192_000;
+
+/// One audio source of the rendered track
+#[derive(Debug, Clone)]
+struct TrackSource {
+    path: PathBuf,
+    gain: f32,
+    offset: Duration,
+}
+
+/// Replace the audio of a video with the audio of another file
+///
+/// The new audio starts `offset` after the start of the video and is cut at
+/// the end of the video.
+///
+/// # Arguments
+/// * `video` - Video file whose video stream is kept
+/// * `audio` - Audio (or media) file providing the new audio track
+/// * `output` - Output video file
+/// * `offset` - Start time of the new audio on the video timeline
+///
+/// # Example
+/// ```no_run
+/// use video_utils::editor::audio_track::replace_audio;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// replace_audio("recording.mp4", "voiceover.wav", "dubbed.mp4", Duration::from_millis(500))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn replace_audio<P, Q, R>(video: P, audio: Q, output: R, offset: Duration) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    log::info!(
+        "Replacing audio: {} + {} (offset: {:?}) -> {}",
+        video.as_ref().display(),
+        audio.as_ref().display(),
+        offset,
+        output.as_ref().display()
+    );
+
+    let sources = [TrackSource {
+        path: audio.as_ref().to_path_buf(),
+        gain: 1.0,
+        offset,
+    }];
+
+    render_audio_track(video.as_ref(), &sources, output.as_ref())
+}
+
+/// Mix additional audio files into the audio of a video
+///
+/// `gains` holds one linear gain per track: the first one applies to the
+/// original audio of the video, the following ones to `extra_audio` in order.
+/// Use a gain of `0.0` to mute the original audio. The mix is cut at the end
+/// of the video.
+///
+/// # Arguments
+/// * `video` - Video file whose video stream is kept
+/// * `extra_audio` - Audio files mixed into the original audio
+/// * `gains` - Linear gain of the original audio followed by one per extra file
+/// * `output` - Output video file
+///
+/// # Example
+/// ```no_run
+/// use video_utils::editor::audio_track::mix_audio_tracks;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Keep the voice, add background music at 20% volume
+/// mix_audio_tracks("recording.mp4", &["music.mp3"], &[1.0, 0.2], "output.mp4")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn mix_audio_tracks<P, Q, R>(video: P, extra_audio: &[Q], gains: &[f32], output: R) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    if gains.len() != extra_audio.len() + 1 {
+        return Err(Error::InvalidConfig(format!(
+            "Expected {} gains (original audio + {} extra tracks), got {}",
+            extra_audio.len() + 1,
+            extra_audio.len(),
+            gains.len()
+        )));
+    }
+
+    if let Some(gain) = gains.iter().find(|gain| !gain.is_finite() || **gain < 0.0) {
+        return Err(Error::InvalidConfig(format!("Gain must be non-negative, got: {}", gain)));
+    }
+
+    log::info!(
+        "Mixing {} extra audio tracks into {} -> {}",
+        extra_audio.len(),
+        video.as_ref().display(),
+        output.as_ref().display()
+    );
+
+    let mut sources = vec![];
+
+    if has_audio_stream(video.as_ref())? {
+        sources.push(TrackSource {
+            path: video.as_ref().to_path_buf(),
+            gain: gains[0],
+            offset: Duration::ZERO,
+        });
+    } else {
+        log::info!("Video has no audio stream, mixing the extra tracks only");
+    }
+
+    for (path, gain) in extra_audio.iter().zip(&gains[1..]) {
+        sources.push(TrackSource {
+            path: path.as_ref().to_path_buf(),
+            gain: *gain,
+            offset: Duration::ZERO,
+        });
+    }
+
+    if sources.is_empty() {
+        return Err(Error::InvalidConfig("No audio tracks to mix".to_string()));
+    }
+
+    render_audio_track(video.as_ref(), &sources, output.as_ref())
+}
+
+fn has_audio_stream(path: &Path) -> Result<bool> {
+    let input_ctx = ffmpeg::format::input(&path)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", path.display(), e)))?;
+
+    Ok(input_ctx.streams().best(ffmpeg::media::Type::Audio).is_some())
+}
+
+/// Build the filter graph description, inputs are `[a0]`, `[a1]`, ...
+fn build_filter_spec(sources: &[TrackSource], video_duration: f64) -> String {
+    let mut spec = String::new();
+
+    for (index, source) in sources.iter().enumerate() {
+        spec.push_str(&format!(
+            "[a{}]asetpts=PTS-STARTPTS,aresample={},aformat=sample_fmts=fltp:channel_layouts=stereo,volume={}",
+            index, OUTPUT_SAMPLE_RATE, source.gain
+        ));
+
+        if !source.offset.is_zero() {
+            spec.push_str(&format!(",adelay=delays={}:all=1", source.offset.as_millis()));
+        }
+
+        spec.push_str(&format!("[t{}];", index));
+    }
+
+    for index in 0..sources.len() {
+        spec.push_str(&format!("[t{}]", index));
+    }
+
+    if sources.len() > 1 {
+        spec.push_str(&format!(
+            "amix=inputs={}:duration=longest:normalize=0,",
+            sources.len()
+        ));
+    } else {
+        spec.push_str("anull,");
+    }
+
+    spec.push_str(&format!(
+        "atrim=end={:.6},aformat=sample_fmts=fltp,asetnsamples=n=1024",
+        video_duration
+    ));
+
+    spec
+}
+
+/// Decoding state of one audio source
+struct AudioInput {
+    ctx: ffmpeg::format::context::Input,
+    decoder: ffmpeg::decoder::Audio,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    offset_secs: f64,
+    /// Timestamp of the last decoded frame on the output timeline in seconds
+    last_secs: f64,
+    eof_sent: bool,
+    finished: bool,
+}
+
+impl AudioInput {
+    fn open(source: &TrackSource) -> Result<Self> {
+        let path = source.path.as_path();
+        let ctx = ffmpeg::format::input(&path)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", path.display(), e)))?;
+
+        let (stream_index, time_base, parameters) = {
+            let stream = ctx
+                .streams()
+                .best(ffmpeg::media::Type::Audio)
+                .ok_or_else(|| {
+                    Error::FFmpeg(format!("No audio stream found in {}", path.display()))
+                })?;
+            (stream.index(), stream.time_base(), stream.parameters())
+        };
+
+        let decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+            .decoder()
+            .audio()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create audio decoder: {}", e)))?;
+
+        Ok(Self {
+            ctx,
+            decoder,
+            stream_index,
+            time_base,
+            offset_secs: source.offset.as_secs_f64(),
+            last_secs: source.offset.as_secs_f64(),
+            eof_sent: false,
+            finished: false,
+        })
+    }
+
+    fn buffer_args(&self) -> String {
+        let channel_layout = if self.decoder.channel_layout().is_empty() {
+            ffmpeg::ChannelLayout::default(self.decoder.channels() as i32)
+        } else {
+            self.decoder.channel_layout()
+        };
+
+        format!(
+            "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+            self.time_base,
+            self.decoder.rate(),
+            self.decoder.format().name(),
+            channel_layout.bits()
+        )
+    }
+
+    /// Decode the next frame, returns false and marks the input as finished
+    /// once all frames are decoded
+    fn next_frame(&mut self, frame: &mut ffmpeg::frame::Audio) -> Result<bool> {
+        loop {
+            if self.decoder.receive_frame(frame).is_ok() {
+                if let Some(ts) = frame.timestamp().or(frame.pts()) {
+                    frame.set_pts(Some(ts));
+                    self.last_secs = ts as f64 * f64::from(self.time_base) + self.offset_secs;
+                }
+                return Ok(true);
+            }
+
+            if self.eof_sent {
+                self.finished = true;
+                return Ok(false);
+            }
+
+            let next_packet = self
+                .ctx
+                .packets()
+                .next()
+                .map(|(stream, packet)| (stream.index(), packet));
+
+            match next_packet {
+                Some((stream_index, packet)) if stream_index == self.stream_index => {
+                    self.decoder
+                        .send_packet(&packet)
+                        .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+                }
+                Some(_) => {}
+                None => {
+                    self.decoder
+                        .send_eof()
+                        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+                    self.eof_sent = true;
+                }
+            }
+        }
+    }
+}
+
+/// Copy the video stream of `video` and render `sources` into a new AAC track
+fn render_audio_track(video: &Path, sources: &[TrackSource], output: &Path) -> Result<()> {
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut video_ctx = ffmpeg::format::input(&video)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", video.display(), e)))?;
+
+    let video_duration = video_ctx.duration() as f64 / 1_000_000.0; // microseconds to seconds
+
+    let (video_stream_index, video_time_base, video_parameters) = {
+        let stream = video_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg(format!("No video stream found in {}", video.display())))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+
+    let mut inputs = sources.iter().map(AudioInput::open).collect::<Result<Vec<_>>>()?;
+
+    // Filter graph
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    for (index, input) in inputs.iter().enumerate() {
+        filter_graph
+            .add(
+                &ffmpeg::filter::find("abuffer").unwrap(),
+                &format!("a{}", index),
+                &input.buffer_args(),
+            )
+            .map_err(|e| Error::FFmpeg(format!("Failed to add abuffer filter: {}", e)))?;
+    }
+
+    filter_graph
+        .add(&ffmpeg::filter::find("abuffersink").unwrap(), "out", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add abuffersink: {}", e)))?;
+
+    let filter_spec = build_filter_spec(sources, video_duration);
+    log::debug!("Filter spec: {}", filter_spec);
+
+    let mut parser = filter_graph
+        .input("out", 0)
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?;
+
+    for index in 0..inputs.len() {
+        parser = parser
+            .output(&format!("a{}", index), 0)
+            .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?;
+    }
+
+    parser
+        .parse(&filter_spec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    // Output
+    let mut output_ctx = ffmpeg::format::output(&output)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    let output_video_index = {
+        let mut output_stream = output_ctx
+            .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
+            .map_err(|e| Error::FFmpeg(format!("Failed to add video stream: {}", e)))?;
+        output_stream.set_parameters(video_parameters);
+        output_stream.index()
+    };
+
+    let aac_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+        .ok_or_else(|| Error::FFmpeg("AAC encoder not found".to_string()))?;
+
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(aac_codec)
+        .encoder()
+        .audio()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create audio encoder: {}", e)))?;
+
+    let encoder_time_base = ffmpeg::Rational::new(1, OUTPUT_SAMPLE_RATE as i32);
+    encoder.set_rate(OUTPUT_SAMPLE_RATE as i32);
+    encoder.set_format(ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar));
+    encoder.set_channel_layout(ffmpeg::ChannelLayout::STEREO);
+    encoder.set_bit_rate(OUTPUT_AUDIO_BITRATE);
+    encoder.set_time_base(encoder_time_base);
+
+    if output_ctx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER)
+    {
+        encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder = encoder
+        .open_as(aac_codec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open encoder: {}", e)))?;
+
+    let output_audio_index = {
+        let mut output_stream = output_ctx
+            .add_stream(aac_codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add audio stream: {}", e)))?;
+        output_stream.set_parameters(&encoder);
+        output_stream.index()
+    };
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let output_video_time_base = output_ctx.stream(output_video_index).unwrap().time_base();
+    let output_audio_time_base = output_ctx.stream(output_audio_index).unwrap().time_base();
+
+    let audio_target = StreamTarget {
+        stream_index: output_audio_index,
+        encoder_time_base,
+        output_time_base: output_audio_time_base,
+    };
+
+    // Decode the sources in timestamp order so that amix never queues much audio,
+    // and copy the video packets up to the rendered audio time
+    let mut decoded = ffmpeg::frame::Audio::empty();
+    let mut video_finished = false;
+
+    while let Some(index) = inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| !input.finished)
+        .min_by(|(_, a), (_, b)| a.last_secs.total_cmp(&b.last_secs))
+        .map(|(index, _)| index)
+    {
+        let has_frame = inputs[index].next_frame(&mut decoded)?;
+
+        let mut source = filter_graph
+            .get(&format!("a{}", index))
+            .ok_or_else(|| Error::FFmpeg(format!("Failed to get a{} filter", index)))?;
+
+        if has_frame {
+            source
+                .source()
+                .add(&decoded)
+                .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+        } else {
+            source
+                .source()
+                .flush()
+                .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+        }
+
+        encode::drain_filter(&mut filter_graph, "out", &mut encoder, &mut output_ctx, &audio_target, |_| {})?;
+
+        if !video_finished {
+            let audio_secs = inputs
+                .iter()
+                .filter(|input| !input.finished)
+                .map(|input| input.last_secs)
+                .fold(f64::INFINITY, f64::min);
+
+            video_finished = copy_video_packets(
+                &mut video_ctx,
+                video_stream_index,
+                video_time_base,
+                &mut output_ctx,
+                output_video_index,
+                output_video_time_base,
+                audio_secs,
+            )?;
+        }
+    }
+
+    if !video_finished {
+        copy_video_packets(
+            &mut video_ctx,
+            video_stream_index,
+            video_time_base,
+            &mut output_ctx,
+            output_video_index,
+            output_video_time_base,
+            f64::INFINITY,
+        )?;
+    }
+
+    encode::finish_encoder(&mut encoder, &mut output_ctx, &audio_target)?;
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    log::info!("Audio track rendered: {}", output.display());
+
+    Ok(())
+}
+
+/// Copy video packets until one past `until_secs`, returns true at the end of the input
+fn copy_video_packets(
+    video_ctx: &mut ffmpeg::format::context::Input,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    output_index: usize,
+    output_time_base: ffmpeg::Rational,
+    until_secs: f64,
+) -> Result<bool> {
+    for (stream, mut packet) in video_ctx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        let packet_secs = packet
+            .dts()
+            .or(packet.pts())
+            .map(|ts| ts as f64 * f64::from(time_base))
+            .unwrap_or(0.0);
+
+        packet.set_stream(output_index);
+        packet.rescale_ts(time_base, output_time_base);
+        packet
+            .write_interleaved(output_ctx)
+            .map_err(|e| Error::FFmpeg(format!("Failed to write video packet: {}", e)))?;
+
+        if packet_secs > until_secs {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(gain: f32, offset: Duration) -> TrackSource {
+        TrackSource {
+            path: PathBuf::from("audio.wav"),
+            gain,
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_filter_spec_single_track() {
+        let spec = build_filter_spec(&[source(1.0, Duration::from_millis(1500))], 10.0);
+        assert_eq!(
+            spec,
+            "[a0]asetpts=PTS-STARTPTS,aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo,volume=1,adelay=delays=1500:all=1[t0];\
+             [t0]anull,atrim=end=10.000000,aformat=sample_fmts=fltp,asetnsamples=n=1024"
+        );
+    }
+
+    #[test]
+    fn test_filter_spec_mix() {
+        let spec = build_filter_spec(
+            &[source(1.0, Duration::ZERO), source(0.25, Duration::ZERO)],
+            5.5,
+        );
+        assert!(spec.contains("[a1]asetpts=PTS-STARTPTS,aresample=48000"));
+        assert!(spec.contains("volume=0.25[t1];"));
+        assert!(!spec.contains("adelay"));
+        assert!(spec.contains("[t0][t1]amix=inputs=2:duration=longest:normalize=0,atrim=end=5.500000"));
+    }
+
+    #[test]
+    fn test_mix_gain_validation() {
+        assert!(mix_audio_tracks("video.mp4", &["music.mp3"], &[1.0], "out.mp4").is_err());
+        assert!(mix_audio_tracks("video.mp4", &["music.mp3"], &[1.0, -0.5], "out.mp4").is_err());
+    }
+}
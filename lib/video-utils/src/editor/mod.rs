@@ -6,13 +6,22 @@
 //! - Splitting videos
 //! - Speed control
 //! - Crossfading
+//! - Picture-in-picture composition
+//! - Audio track replacement and mixing
+//! - Silence detection and jump cuts
 
 pub mod trim;
 pub mod concat;
 pub mod split;
 pub mod speed;
+pub mod pip;
+pub mod audio_track;
+pub mod silence;
 
-pub use trim::{trim_video, TrimConfig, extract_segment};
+pub use trim::{trim_video, TrimConfig, extract_segment, lossless_trim, CutMode};
 pub use concat::{concat_videos, ConcatConfig, concat_videos_simple};
 pub use split::{split_video, SplitConfig, split_equal, split_by_duration, split_at_points};
 pub use speed::{change_speed, SpeedConfig, speed_up, slow_down, reverse_video, SpeedFactor};
+pub use pip::{compose_pip, PipConfig, PipPosition};
+pub use audio_track::{replace_audio, mix_audio_tracks};
+pub use silence::{detect_silence, auto_cut_silence, SilenceConfig, SilenceAction, AutoCutConfig};
@@ -0,0 +1,508 @@
+//! Picture-in-picture composition
+//!
+//! This module overlays one video onto another, e.g. a webcam take onto a
+//! screen recording, using the FFmpeg filter graph.
+
+use crate::encode::{self, StreamTarget};
+use crate::{Result, Error};
+use derivative::Derivative;
+use derive_setters::Setters;
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+use std::time::Duration;
+
+/// Placement of the overlay video on the main video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// Top-left corner of the overlay in main video pixels (margin is ignored)
+    Custom { x: u32, y: u32 },
+}
+
+impl PipPosition {
+    /// Convert to the x/y expressions of the FFmpeg overlay filter
+    pub(crate) fn to_overlay_option(self, margin: u32) -> String {
+        match self {
+            PipPosition::TopLeft => format!("x={}:y={}", margin, margin),
+            PipPosition::TopRight => format!("x=W-w-{}:y={}", margin, margin),
+            PipPosition::BottomLeft => format!("x={}:y=H-h-{}", margin, margin),
+            PipPosition::BottomRight => format!("x=W-w-{}:y=H-h-{}", margin, margin),
+            PipPosition::Custom { x, y } => format!("x={}:y={}", x, y),
+        }
+    }
+}
+
+/// Picture-in-picture configuration
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct PipConfig {
+    /// Output video file
+    #[derivative(Default(value = "String::new()"))]
+    pub output: String,
+    /// Placement of the overlay
+    #[derivative(Default(value = "PipPosition::BottomRight"))]
+    pub position: PipPosition,
+    /// Distance to the edges of the main video in pixels
+    #[derivative(Default(value = "20"))]
+    pub margin: u32,
+    /// Overlay width relative to the main video width, in (0.0, 1.0]
+    #[derivative(Default(value = "0.25"))]
+    pub scale: f32,
+    /// Radius of the rounded overlay corners in pixels (0 = square corners)
+    #[derivative(Default(value = "0"))]
+    pub border_radius: u32,
+    /// Time on the main video at which the overlay starts
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub offset: Duration,
+    /// Quality of the composed video as a H.264 CRF, lower is better
+    #[derivative(Default(value = "23"))]
+    pub crf: u8,
+}
+
+impl PipConfig {
+    /// Create a new picture-in-picture config (convenience method)
+    pub fn new(output: impl Into<String>) -> Self {
+        Self::default().with_output(output.into())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.output.is_empty() {
+            return Err(Error::InvalidConfig("Output path is empty".to_string()));
+        }
+
+        if !(self.scale > 0.0 && self.scale <= 1.0) {
+            return Err(Error::InvalidConfig(format!(
+                "Overlay scale must be in (0.0, 1.0], got: {}",
+                self.scale
+            )));
+        }
+
+        encode::validate_crf(self.crf)?;
+
+        Ok(())
+    }
+
+    /// Overlay width in pixels for the given main video width
+    fn overlay_width(&self, main_width: u32) -> u32 {
+        (((main_width as f32 * self.scale).round() as u32) & !1).max(2)
+    }
+
+    /// Build the filter graph description, inputs are `[main]` and `[overlay]`
+    fn build_filter_spec(&self, main_width: u32) -> String {
+        let overlay_width = self.overlay_width(main_width);
+        let radius = self.border_radius.min(overlay_width / 2);
+
+        let mut pip_spec = format!(
+            "[overlay]setpts=PTS-STARTPTS+{:.6}/TB,scale={}:-2",
+            self.offset.as_secs_f64(),
+            overlay_width
+        );
+
+        // Make the pixels outside of the rounded corners transparent
+        if radius > 0 {
+            pip_spec.push_str(&format!(
+                ",format=yuva420p,geq=lum='lum(X,Y)':cb='cb(X,Y)':cr='cr(X,Y)':a='if(gt(abs(W/2-X),W/2-{r})*gt(abs(H/2-Y),H/2-{r}),if(lte(hypot({r}-(W/2-abs(W/2-X)),{r}-(H/2-abs(H/2-Y))),{r}),255,0),255)'",
+                r = radius
+            ));
+        }
+
+        format!(
+            "{}[pip];[main][pip]overlay={}:eof_action=pass,format=yuv420p",
+            pip_spec,
+            self.position.to_overlay_option(self.margin)
+        )
+    }
+}
+
+/// Overlay a video onto another one (picture-in-picture)
+///
+/// The overlay is scaled relative to the main video, placed at the configured
+/// position and starts at `offset` on the main video timeline. Once the overlay
+/// ends, the main video continues alone. The audio of the main video is copied.
+///
+/// # Arguments
+/// * `main` - Background video file
+/// * `overlay` - Video file shown on top of the main video
+/// * `config` - Picture-in-picture configuration
+///
+/// # Example
+/// ```no_run
+/// use video_utils::editor::pip::{compose_pip, PipConfig, PipPosition};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = PipConfig::new("output.mp4")
+///     .with_position(PipPosition::BottomRight)
+///     .with_scale(0.3)
+///     .with_border_radius(24)
+///     .with_offset(Duration::from_millis(1500));
+///
+/// compose_pip("screen.mp4", "webcam.mp4", config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn compose_pip<P: AsRef<Path>, Q: AsRef<Path>>(main: P, overlay: Q, config: PipConfig) -> Result<()> {
+    config.validate()?;
+
+    log::info!(
+        "Composing picture-in-picture: {} + {} -> {}",
+        main.as_ref().display(),
+        overlay.as_ref().display(),
+        config.output
+    );
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut main_input = PipInput::open(main.as_ref())?;
+    let mut overlay_input = PipInput::open(overlay.as_ref())?;
+
+    let width = main_input.decoder.width();
+    let height = main_input.decoder.height();
+    let frame_rate = main_input.frame_rate;
+
+    // Filter graph
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffer").unwrap(), "main", &main_input.buffer_args()?)
+        .map_err(|e| Error::FFmpeg(format!("Failed to add main buffer filter: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffer").unwrap(), "overlay", &overlay_input.buffer_args()?)
+        .map_err(|e| Error::FFmpeg(format!("Failed to add overlay buffer filter: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffersink").unwrap(), "out", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffersink: {}", e)))?;
+
+    let filter_spec = config.build_filter_spec(width);
+    log::debug!("Filter spec: {}", filter_spec);
+
+    filter_graph
+        .output("main", 0)
+        .and_then(|p| p.output("overlay", 0))
+        .and_then(|p| p.input("out", 0))
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?
+        .parse(&filter_spec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    let encoder_time_base = filter_graph
+        .get("out")
+        .ok_or_else(|| Error::FFmpeg("Failed to get out filter".to_string()))?
+        .sink()
+        .time_base();
+
+    // Encoder
+    let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
+        .ok_or_else(|| Error::FFmpeg("H.264 encoder not found".to_string()))?;
+
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create encoder: {}", e)))?;
+
+    encoder.set_width(width);
+    encoder.set_height(height);
+    encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+    encoder.set_time_base(encoder_time_base);
+    encoder.set_frame_rate(Some(frame_rate));
+
+    let mut output_ctx = ffmpeg::format::output(&config.output)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    if output_ctx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER)
+    {
+        encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder_opts = ffmpeg::Dictionary::new();
+    encoder_opts.set("crf", &config.crf.to_string());
+    encoder_opts.set("preset", "medium");
+
+    let mut encoder = encoder
+        .open_with(encoder_opts)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open encoder: {}", e)))?;
+
+    {
+        let mut output_stream = output_ctx
+            .add_stream(codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add video stream: {}", e)))?;
+        output_stream.set_parameters(&encoder);
+        output_stream.set_time_base(encoder_time_base);
+    }
+
+    // Copy the audio of the main video without re-encoding
+    let audio_stream = main_input
+        .ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .map(|stream| (stream.index(), stream.time_base(), stream.parameters()));
+
+    let audio_stream = match audio_stream {
+        Some((index, time_base, parameters)) => {
+            let mut output_stream = output_ctx
+                .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
+                .map_err(|e| Error::FFmpeg(format!("Failed to add audio stream: {}", e)))?;
+            output_stream.set_parameters(parameters);
+            Some((index, time_base, output_stream.index()))
+        }
+        None => {
+            log::info!("No audio stream in main video, output has no audio");
+            None
+        }
+    };
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let video_target = StreamTarget {
+        stream_index: 0,
+        encoder_time_base,
+        output_time_base: output_ctx
+            .stream(0)
+            .ok_or_else(|| Error::FFmpeg("Failed to get output video stream".to_string()))?
+            .time_base(),
+    };
+
+    let output_audio_time_base = audio_stream
+        .and_then(|(_, _, out_index)| output_ctx.stream(out_index))
+        .map(|stream| stream.time_base());
+
+    // Feed both inputs in presentation order so the overlay filter never has to
+    // queue more than a few frames of either input
+    let offset_secs = config.offset.as_secs_f64();
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut frame_count = 0u64;
+
+    loop {
+        let use_main = match (main_input.finished, overlay_input.finished) {
+            (true, true) => break,
+            (false, true) => true,
+            (true, false) => false,
+            (false, false) => main_input.last_secs <= overlay_input.last_secs + offset_secs,
+        };
+
+        let (input, name) = if use_main {
+            (&mut main_input, "main")
+        } else {
+            (&mut overlay_input, "overlay")
+        };
+
+        let has_frame = input.next_frame(&mut decoded, |stream_index, packet| {
+            if let (Some((audio_index, in_time_base, out_index)), Some(out_time_base)) =
+                (audio_stream, output_audio_time_base)
+                && use_main
+                && stream_index == audio_index
+            {
+                packet.set_stream(out_index);
+                packet.rescale_ts(in_time_base, out_time_base);
+                packet
+                    .write_interleaved(&mut output_ctx)
+                    .map_err(|e| Error::FFmpeg(format!("Failed to write audio packet: {}", e)))?;
+            }
+
+            Ok(())
+        })?;
+
+        let mut source = filter_graph
+            .get(name)
+            .ok_or_else(|| Error::FFmpeg(format!("Failed to get {} filter", name)))?;
+
+        if has_frame {
+            source
+                .source()
+                .add(&decoded)
+                .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+        } else {
+            source
+                .source()
+                .flush()
+                .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+        }
+
+        frame_count +=
+            encode::drain_filter(&mut filter_graph, "out", &mut encoder, &mut output_ctx, &video_target, |_| {})?;
+    }
+
+    encode::finish_encoder(&mut encoder, &mut output_ctx, &video_target)?;
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    log::info!("Picture-in-picture complete: {} frames -> {}", frame_count, config.output);
+
+    Ok(())
+}
+
+/// Decoding state of one input video
+struct PipInput {
+    ctx: ffmpeg::format::context::Input,
+    decoder: ffmpeg::decoder::Video,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    frame_rate: ffmpeg::Rational,
+    /// Timestamp of the last decoded frame in seconds
+    last_secs: f64,
+    eof_sent: bool,
+    finished: bool,
+}
+
+impl PipInput {
+    fn open(path: &Path) -> Result<Self> {
+        let ctx = ffmpeg::format::input(&path)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", path.display(), e)))?;
+
+        let (stream_index, time_base, frame_rate, parameters) = {
+            let stream = ctx
+                .streams()
+                .best(ffmpeg::media::Type::Video)
+                .ok_or_else(|| {
+                    Error::FFmpeg(format!("No video stream found in {}", path.display()))
+                })?;
+            (stream.index(), stream.time_base(), stream.avg_frame_rate(), stream.parameters())
+        };
+
+        let decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+            .decoder()
+            .video()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+        Ok(Self {
+            ctx,
+            decoder,
+            stream_index,
+            time_base,
+            frame_rate,
+            last_secs: 0.0,
+            eof_sent: false,
+            finished: false,
+        })
+    }
+
+    fn buffer_args(&self) -> Result<String> {
+        Ok(format!(
+            "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+            self.decoder.width(),
+            self.decoder.height(),
+            self.decoder
+                .format()
+                .descriptor()
+                .ok_or_else(|| Error::FFmpeg("Unknown pixel format".to_string()))?
+                .name(),
+            self.time_base,
+            self.decoder.aspect_ratio()
+        ))
+    }
+
+    /// Decode the next frame, packets of the other streams are passed to `on_packet`.
+    /// Returns false and marks the input as finished once all frames are decoded.
+    fn next_frame<F>(&mut self, frame: &mut ffmpeg::frame::Video, mut on_packet: F) -> Result<bool>
+    where
+        F: FnMut(usize, &mut ffmpeg::Packet) -> Result<()>,
+    {
+        loop {
+            if self.decoder.receive_frame(frame).is_ok() {
+                if let Some(ts) = frame.timestamp().or(frame.pts()) {
+                    frame.set_pts(Some(ts));
+                    self.last_secs = ts as f64 * f64::from(self.time_base);
+                }
+                return Ok(true);
+            }
+
+            if self.eof_sent {
+                self.finished = true;
+                return Ok(false);
+            }
+
+            let next_packet = self
+                .ctx
+                .packets()
+                .next()
+                .map(|(stream, packet)| (stream.index(), packet));
+
+            match next_packet {
+                Some((stream_index, packet)) if stream_index == self.stream_index => {
+                    self.decoder
+                        .send_packet(&packet)
+                        .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+                }
+                Some((stream_index, mut packet)) => on_packet(stream_index, &mut packet)?,
+                None => {
+                    self.decoder
+                        .send_eof()
+                        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+                    self.eof_sent = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pip_config_default() {
+        let config = PipConfig::new("out.mp4");
+        assert_eq!(config.output, "out.mp4");
+        assert_eq!(config.position, PipPosition::BottomRight);
+        assert_eq!(config.scale, 0.25);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_pip_config_validation() {
+        assert!(PipConfig::default().validate().is_err());
+        assert!(PipConfig::new("out.mp4").with_scale(0.0).validate().is_err());
+        assert!(PipConfig::new("out.mp4").with_scale(1.5).validate().is_err());
+        assert!(PipConfig::new("out.mp4").with_crf(60).validate().is_err());
+    }
+
+    #[test]
+    fn test_overlay_width() {
+        let config = PipConfig::new("out.mp4").with_scale(0.3);
+        assert_eq!(config.overlay_width(1920), 576);
+        assert_eq!(config.overlay_width(1001), 300);
+        assert_eq!(config.with_scale(0.001).overlay_width(100), 2);
+    }
+
+    #[test]
+    fn test_overlay_position() {
+        assert_eq!(PipPosition::TopLeft.to_overlay_option(10), "x=10:y=10");
+        assert_eq!(PipPosition::BottomRight.to_overlay_option(10), "x=W-w-10:y=H-h-10");
+        assert_eq!(PipPosition::Custom { x: 5, y: 7 }.to_overlay_option(10), "x=5:y=7");
+    }
+
+    #[test]
+    fn test_filter_spec() {
+        let config = PipConfig::new("out.mp4")
+            .with_position(PipPosition::TopRight)
+            .with_offset(Duration::from_millis(1500));
+
+        assert_eq!(
+            config.build_filter_spec(1280),
+            "[overlay]setpts=PTS-STARTPTS+1.500000/TB,scale=320:-2[pip];[main][pip]overlay=x=W-w-20:y=20:eof_action=pass,format=yuv420p"
+        );
+
+        let rounded = config.with_border_radius(16).build_filter_spec(1280);
+        assert!(rounded.contains("format=yuva420p,geq="));
+        assert!(rounded.contains("hypot(16-"));
+    }
+}
@@ -0,0 +1,1040 @@
+//! Silence detection and jump-cut editing
+//!
+//! Finds silent (and optionally idle) sections of a recording from the audio
+//! RMS and the frame difference, then removes or speeds them up.
+
+use crate::audio_extraction::decode_audio_mono;
+use crate::encode::{self, StreamTarget};
+use crate::{Result, Error};
+use derivative::Derivative;
+use derive_setters::Setters;
+use ffmpeg_next as ffmpeg;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+/// Length of the audio windows the RMS is computed over
+const RMS_WINDOW: Duration = Duration::from_millis(20);
+
+/// Size of the grayscale thumbnails compared for the frame difference
+const DIFF_WIDTH: u32 = 64;
+const DIFF_HEIGHT: u32 = 36;
+
+/// Sample rate of the output audio
+const OUTPUT_SAMPLE_RATE: u32 = 48000;
+
+/// AAC frame size of the output audio
+const AAC_FRAME_SIZE: usize = 1024;
+
+/// Configuration for silence detection
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct SilenceConfig {
+    /// Audio windows quieter than this RMS level in dBFS count as silent
+    #[derivative(Default(value = "-40.0"))]
+    pub threshold_db: f32,
+    /// Minimum length of a silent section
+    #[derivative(Default(value = "Duration::from_millis(500)"))]
+    pub min_duration: Duration,
+    /// Time kept at both ends of a silent section so that cuts don't clip speech
+    #[derivative(Default(value = "Duration::from_millis(150)"))]
+    pub padding: Duration,
+    /// Mean absolute luma difference (0-255) between consecutive frames below which
+    /// the video counts as idle. Sections must then be both silent and idle.
+    /// (None = audio only)
+    #[derivative(Default(value = "None"))]
+    pub frame_diff_threshold: Option<f32>,
+}
+
+impl SilenceConfig {
+    /// Create a new silence config (convenience method)
+    pub fn new(threshold_db: f32, min_duration: Duration) -> Self {
+        Self::default()
+            .with_threshold_db(threshold_db)
+            .with_min_duration(min_duration)
+    }
+}
+
+/// What to do with the silent sections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilenceAction {
+    /// Cut the sections out (jump cut)
+    Remove,
+    /// Play the sections faster by the given factor (> 1.0)
+    SpeedUp(f32),
+}
+
+/// Configuration for automatic silence cutting
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct AutoCutConfig {
+    /// Input video path
+    #[derivative(Default(value = "String::new()"))]
+    pub input: String,
+    /// Output video path
+    #[derivative(Default(value = "String::new()"))]
+    pub output: String,
+    /// Silence detection settings
+    pub silence: SilenceConfig,
+    /// What to do with the silent sections
+    #[derivative(Default(value = "SilenceAction::Remove"))]
+    pub action: SilenceAction,
+    /// CRF the cut video is re-encoded with
+    #[derivative(Default(value = "23"))]
+    pub crf: u8,
+}
+
+impl AutoCutConfig {
+    /// Create a new auto-cut config (convenience method)
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self::default()
+            .with_input(input.into())
+            .with_output(output.into())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.input.is_empty() {
+            return Err(Error::InvalidConfig("Input path is empty".to_string()));
+        }
+
+        if self.output.is_empty() {
+            return Err(Error::InvalidConfig("Output path is empty".to_string()));
+        }
+
+        if let SilenceAction::SpeedUp(factor) = self.action
+            && !(factor.is_finite() && factor > 1.0)
+        {
+            return Err(Error::InvalidConfig(format!(
+                "Speed-up factor must be greater than 1.0, got: {}",
+                factor
+            )));
+        }
+
+        encode::validate_crf(self.crf)?;
+
+        Ok(())
+    }
+}
+
+/// Detect the silent sections of a media file
+///
+/// # Arguments
+/// * `path` - Media file path
+/// * `config` - Silence detection settings
+///
+/// # Returns
+/// The silent sections in ascending order, already shrunk by `padding`
+///
+/// # Example
+/// ```no_run
+/// use video_utils::editor::silence::{detect_silence, SilenceConfig};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = SilenceConfig::new(-45.0, Duration::from_secs(1));
+/// for range in detect_silence("talk.mp4", &config)? {
+///     println!("{:?} - {:?}", range.start, range.end);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn detect_silence<P: AsRef<Path>>(path: P, config: &SilenceConfig) -> Result<Vec<Range<Duration>>> {
+    let path = path.as_ref();
+    log::info!("Detecting silence: {} (threshold: {} dB)", path.display(), config.threshold_db);
+
+    let mut windows = RmsWindows::default();
+    decode_audio_mono(path, |sample_rate, samples| {
+        windows.add_samples(sample_rate, samples, config.threshold_db);
+    })?;
+
+    let mut ranges = merge_spans(&windows.finish());
+
+    if let Some(threshold) = config.frame_diff_threshold {
+        let idle = merge_spans(&idle_spans(path, threshold)?);
+        ranges = intersect_ranges(&ranges, &idle);
+    }
+
+    let ranges = finalize_ranges(ranges, config.min_duration, config.padding);
+    log::info!("Found {} silent sections", ranges.len());
+
+    Ok(ranges)
+}
+
+/// Remove or speed up the silent sections of a video (jump cut)
+///
+/// The video and audio are re-encoded to H.264/AAC.
+///
+/// # Arguments
+/// * `config` - Auto-cut configuration
+///
+/// # Returns
+/// The silent sections of the input that were cut or sped up
+///
+/// # Example
+/// ```no_run
+/// use video_utils::editor::silence::{auto_cut_silence, AutoCutConfig, SilenceAction};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = AutoCutConfig::new("talk.mp4", "talk_cut.mp4")
+///     .with_action(SilenceAction::SpeedUp(4.0));
+///
+/// let sections = auto_cut_silence(&config)?;
+/// println!("Shortened {} sections", sections.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn auto_cut_silence(config: &AutoCutConfig) -> Result<Vec<Range<Duration>>> {
+    config.validate()?;
+
+    let sections = detect_silence(&config.input, &config.silence)?;
+    let silences = sections
+        .iter()
+        .map(|range| range.start.as_secs_f64()..range.end.as_secs_f64())
+        .collect::<Vec<_>>();
+
+    let speed = match config.action {
+        SilenceAction::Remove => None,
+        SilenceAction::SpeedUp(factor) => Some(factor as f64),
+    };
+
+    log::info!(
+        "Auto-cutting {} silent sections: {} -> {}",
+        silences.len(),
+        config.input,
+        config.output
+    );
+
+    render_cut(config, &silences, speed)?;
+
+    Ok(sections)
+}
+
+/// Accumulates mono samples into fixed-size RMS windows
+#[derive(Default)]
+struct RmsWindows {
+    window_samples: usize,
+    sample_rate: u32,
+    position: u64,
+    sum_squares: f64,
+    count: usize,
+    threshold_db: f32,
+    spans: Vec<(f64, f64, bool)>,
+}
+
+impl RmsWindows {
+    fn add_samples(&mut self, sample_rate: u32, samples: &[f32], threshold_db: f32) {
+        if self.window_samples == 0 {
+            self.sample_rate = sample_rate;
+            self.window_samples = ((sample_rate as f64 * RMS_WINDOW.as_secs_f64()) as usize).max(1);
+            self.threshold_db = threshold_db;
+        }
+
+        for sample in samples {
+            self.sum_squares += (*sample as f64) * (*sample as f64);
+            self.count += 1;
+
+            if self.count == self.window_samples {
+                self.finish_window();
+            }
+        }
+    }
+
+    fn finish_window(&mut self) {
+        let rms = (self.sum_squares / self.count as f64).sqrt();
+        let db = 20.0 * rms.max(1e-10).log10();
+
+        let start = self.position as f64 / self.sample_rate as f64;
+        self.position += self.count as u64;
+        let end = self.position as f64 / self.sample_rate as f64;
+
+        self.spans.push((start, end, db < self.threshold_db as f64));
+        self.sum_squares = 0.0;
+        self.count = 0;
+    }
+
+    fn finish(mut self) -> Vec<(f64, f64, bool)> {
+        if self.count > 0 {
+            self.finish_window();
+        }
+
+        self.spans
+    }
+}
+
+/// Spans between consecutive video frames, flagged when the frames barely differ
+fn idle_spans(path: &Path, threshold: f32) -> Result<Vec<(f64, f64, bool)>> {
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&path)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let (stream_index, time_base, parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg("No video stream found".to_string()))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+        .decoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+    let mut scaler = ffmpeg::software::scaling::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::GRAY8,
+        DIFF_WIDTH,
+        DIFF_HEIGHT,
+        ffmpeg::software::scaling::Flags::AREA,
+    )
+    .map_err(|e| Error::FFmpeg(format!("Failed to create scaler: {}", e)))?;
+
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut gray = ffmpeg::frame::Video::empty();
+    let mut tracker = FrameDiffTracker::new(threshold);
+
+    let mut on_frame = |decoded: &ffmpeg::frame::Video, gray: &mut ffmpeg::frame::Video| -> Result<()> {
+        let Some(ts) = decoded.timestamp().or(decoded.pts()) else {
+            return Ok(());
+        };
+
+        scaler
+            .run(decoded, gray)
+            .map_err(|e| Error::FFmpeg(format!("Scaler failed: {}", e)))?;
+
+        let stride = gray.stride(0);
+        let luma = gray
+            .data(0)
+            .chunks(stride)
+            .take(DIFF_HEIGHT as usize)
+            .flat_map(|row| &row[..DIFF_WIDTH as usize])
+            .copied()
+            .collect::<Vec<u8>>();
+
+        tracker.add_frame(ts as f64 * f64::from(time_base), luma);
+        Ok(())
+    };
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            on_frame(&decoded, &mut gray)?;
+        }
+    }
+
+    decoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        on_frame(&decoded, &mut gray)?;
+    }
+
+    Ok(tracker.spans)
+}
+
+/// Compares each frame to the previous one
+struct FrameDiffTracker {
+    threshold: f32,
+    first_time: Option<f64>,
+    prev: Option<(f64, Vec<u8>)>,
+    spans: Vec<(f64, f64, bool)>,
+}
+
+impl FrameDiffTracker {
+    fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            first_time: None,
+            prev: None,
+            spans: vec![],
+        }
+    }
+
+    fn add_frame(&mut self, time: f64, luma: Vec<u8>) {
+        // Frame times relative to the first frame, like the audio sample positions
+        let time = time - *self.first_time.get_or_insert(time);
+
+        if let Some((prev_time, prev_luma)) = &self.prev
+            && time > *prev_time
+        {
+            let diff = mean_abs_diff(prev_luma, &luma);
+            self.spans.push((*prev_time, time, diff < self.threshold));
+        }
+
+        self.prev = Some((time, luma));
+    }
+}
+
+fn mean_abs_diff(a: &[u8], b: &[u8]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return f32::MAX;
+    }
+
+    let sum = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u64).sum::<u64>();
+    sum as f32 / a.len() as f32
+}
+
+/// Merge consecutive flagged spans into ranges
+fn merge_spans(spans: &[(f64, f64, bool)]) -> Vec<Range<f64>> {
+    let mut ranges: Vec<Range<f64>> = vec![];
+
+    for &(start, end, flagged) in spans {
+        if !flagged {
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some(last) if start - last.end < 1e-6 => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+
+    ranges
+}
+
+/// Intersection of two sorted, non-overlapping range lists
+fn intersect_ranges(a: &[Range<f64>], b: &[Range<f64>]) -> Vec<Range<f64>> {
+    let (mut i, mut j) = (0, 0);
+    let mut result = vec![];
+
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+
+        if start < end {
+            result.push(start..end);
+        }
+
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    result
+}
+
+/// Drop ranges shorter than `min_duration`, then shrink the rest by `padding` at both ends
+fn finalize_ranges(ranges: Vec<Range<f64>>, min_duration: Duration, padding: Duration) -> Vec<Range<Duration>> {
+    let min_duration = min_duration.as_secs_f64();
+    let padding = padding.as_secs_f64();
+
+    ranges
+        .into_iter()
+        .filter(|range| range.end - range.start >= min_duration)
+        .map(|range| (range.start + padding)..(range.end - padding))
+        .filter(|range| range.end > range.start)
+        .map(|range| Duration::from_secs_f64(range.start)..Duration::from_secs_f64(range.end))
+        .collect()
+}
+
+/// Maps input times to output times for monotonically increasing input times
+struct TimeMap<'a> {
+    silences: &'a [Range<f64>],
+    /// Playback speed inside the silences (None = removed)
+    speed: Option<f64>,
+    cursor: usize,
+    /// Output time saved by the silences before `cursor`
+    saved: f64,
+}
+
+impl<'a> TimeMap<'a> {
+    fn new(silences: &'a [Range<f64>], speed: Option<f64>) -> Self {
+        Self {
+            silences,
+            speed,
+            cursor: 0,
+            saved: 0.0,
+        }
+    }
+
+    fn saved_in(&self, length: f64) -> f64 {
+        match self.speed {
+            Some(speed) => length * (1.0 - 1.0 / speed),
+            None => length,
+        }
+    }
+
+    /// Output time of `time` and whether it lies in a silence, None if it is removed
+    fn map(&mut self, time: f64) -> Option<(f64, bool)> {
+        while let Some(silence) = self.silences.get(self.cursor)
+            && time >= silence.end
+        {
+            self.saved += self.saved_in(silence.end - silence.start);
+            self.cursor += 1;
+        }
+
+        match self.silences.get(self.cursor) {
+            Some(silence) if time >= silence.start => {
+                let speed = self.speed?;
+                let elapsed = time - silence.start;
+                Some((silence.start - self.saved + elapsed / speed, true))
+            }
+            _ => Some((time - self.saved, false)),
+        }
+    }
+}
+
+/// Re-encode the input with the silent sections removed or sped up
+fn render_cut(config: &AutoCutConfig, silences: &[Range<f64>], speed: Option<f64>) -> Result<()> {
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&config.input)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let (video_index, video_time_base, frame_rate, video_parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg("No video stream found".to_string()))?;
+        (stream.index(), stream.time_base(), stream.avg_frame_rate(), stream.parameters())
+    };
+
+    let audio = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .map(|stream| (stream.index(), stream.time_base(), stream.parameters()));
+
+    let mut video_decoder = ffmpeg::codec::context::Context::from_parameters(video_parameters)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+        .decoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+    let width = video_decoder.width();
+    let height = video_decoder.height();
+
+    let mut output_ctx = ffmpeg::format::output(&config.output)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+    let global_header = output_ctx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+    // Video encoder
+    let h264_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
+        .ok_or_else(|| Error::FFmpeg("H.264 encoder not found".to_string()))?;
+
+    let mut video_encoder = ffmpeg::codec::context::Context::new_with_codec(h264_codec)
+        .encoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create video encoder: {}", e)))?;
+
+    video_encoder.set_width(width);
+    video_encoder.set_height(height);
+    video_encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+    video_encoder.set_time_base(video_time_base);
+    video_encoder.set_frame_rate(Some(frame_rate));
+    if global_header {
+        video_encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder_opts = ffmpeg::Dictionary::new();
+    encoder_opts.set("crf", &config.crf.to_string());
+    encoder_opts.set("preset", "medium");
+
+    let mut video_encoder = video_encoder
+        .open_with(encoder_opts)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open video encoder: {}", e)))?;
+
+    {
+        let mut stream = output_ctx
+            .add_stream(h264_codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add video stream: {}", e)))?;
+        stream.set_parameters(&video_encoder);
+        stream.set_time_base(video_time_base);
+    }
+
+    // Audio decoder, resampling graph and encoder
+    let mut audio_state = match audio {
+        Some((audio_index, audio_time_base, audio_parameters)) => Some(AudioCutState::new(
+            &mut output_ctx,
+            audio_index,
+            audio_time_base,
+            audio_parameters,
+            global_header,
+        )?),
+        None => {
+            log::info!("No audio stream in input, output has no audio");
+            None
+        }
+    };
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let video_target = StreamTarget {
+        stream_index: 0,
+        encoder_time_base: video_time_base,
+        output_time_base: output_ctx.stream(0).unwrap().time_base(),
+    };
+    if let Some(state) = audio_state.as_mut() {
+        state.target.output_time_base = output_ctx.stream(state.target.stream_index).unwrap().time_base();
+    }
+
+    let mut scaler = ffmpeg::software::scaling::Context::get(
+        video_decoder.format(),
+        width,
+        height,
+        ffmpeg::format::Pixel::YUV420P,
+        width,
+        height,
+        ffmpeg::software::scaling::Flags::BILINEAR,
+    )
+    .map_err(|e| Error::FFmpeg(format!("Failed to create scaler: {}", e)))?;
+
+    let mut video_map = TimeMap::new(silences, speed);
+    let min_frame_interval = if frame_rate.numerator() > 0 {
+        0.999 / f64::from(frame_rate)
+    } else {
+        0.0
+    };
+
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut yuv = ffmpeg::frame::Video::empty();
+    let mut first_ts = None;
+    let mut last_output_time: Option<f64> = None;
+    let mut frame_count = 0u64;
+
+    let mut on_video_frame = |decoded: &ffmpeg::frame::Video,
+                              output_ctx: &mut ffmpeg::format::context::Output,
+                              video_encoder: &mut ffmpeg::encoder::Video|
+     -> Result<()> {
+        let Some(ts) = decoded.timestamp().or(decoded.pts()) else {
+            return Ok(());
+        };
+
+        let time = (ts - *first_ts.get_or_insert(ts)) as f64 * f64::from(video_time_base);
+        let Some((output_time, _)) = video_map.map(time) else {
+            return Ok(());
+        };
+
+        // Sped-up sections would otherwise exceed the frame rate
+        if last_output_time.is_some_and(|last| output_time - last < min_frame_interval) {
+            return Ok(());
+        }
+        last_output_time = Some(output_time);
+
+        scaler
+            .run(decoded, &mut yuv)
+            .map_err(|e| Error::FFmpeg(format!("Scaler failed: {}", e)))?;
+        yuv.set_pts(Some((output_time / f64::from(video_time_base)).round() as i64));
+
+        video_encoder
+            .send_frame(&yuv)
+            .map_err(|e| Error::FFmpeg(format!("Video encoder send failed: {}", e)))?;
+        encode::write_packets(video_encoder, output_ctx, &video_target)?;
+
+        frame_count += 1;
+        Ok(())
+    };
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() == video_index {
+            video_decoder
+                .send_packet(&packet)
+                .map_err(|e| Error::FFmpeg(format!("Video decoder send failed: {}", e)))?;
+
+            while video_decoder.receive_frame(&mut decoded).is_ok() {
+                on_video_frame(&decoded, &mut output_ctx, &mut video_encoder)?;
+            }
+        } else if let Some(state) = audio_state.as_mut()
+            && stream.index() == state.input_index
+        {
+            state.send_packet(&packet, &mut output_ctx, silences, speed)?;
+        }
+    }
+
+    video_decoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush video decoder: {}", e)))?;
+
+    while video_decoder.receive_frame(&mut decoded).is_ok() {
+        on_video_frame(&decoded, &mut output_ctx, &mut video_encoder)?;
+    }
+
+    encode::finish_encoder(&mut video_encoder, &mut output_ctx, &video_target)?;
+
+    if let Some(state) = audio_state.as_mut() {
+        state.finish(&mut output_ctx, silences, speed)?;
+    }
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    log::info!("Auto-cut complete: {} frames -> {}", frame_count, config.output);
+
+    Ok(())
+}
+
+/// Audio path of the auto-cut: decode, resample to stereo 48kHz, cut and encode
+struct AudioCutState {
+    input_index: usize,
+    target: StreamTarget,
+    decoder: ffmpeg::decoder::Audio,
+    encoder: ffmpeg::encoder::Audio,
+    filter_graph: ffmpeg::filter::Graph,
+    /// Input samples seen so far, at the output sample rate
+    input_samples: u64,
+    /// Output samples encoded so far
+    output_samples: u64,
+    /// Fractional sample accumulator for the sped-up sections
+    speed_accumulator: f64,
+    pending: [Vec<f32>; 2],
+}
+
+impl AudioCutState {
+    fn new(
+        output_ctx: &mut ffmpeg::format::context::Output,
+        input_index: usize,
+        input_time_base: ffmpeg::Rational,
+        parameters: ffmpeg::codec::Parameters,
+        global_header: bool,
+    ) -> Result<Self> {
+        let decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+            .decoder()
+            .audio()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create audio decoder: {}", e)))?;
+
+        let channel_layout = if decoder.channel_layout().is_empty() {
+            ffmpeg::ChannelLayout::default(decoder.channels() as i32)
+        } else {
+            decoder.channel_layout()
+        };
+
+        let mut filter_graph = ffmpeg::filter::Graph::new();
+
+        let buffer_args = format!(
+            "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+            input_time_base,
+            decoder.rate(),
+            decoder.format().name(),
+            channel_layout.bits()
+        );
+
+        filter_graph
+            .add(&ffmpeg::filter::find("abuffer").unwrap(), "in", &buffer_args)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add abuffer filter: {}", e)))?;
+
+        filter_graph
+            .add(&ffmpeg::filter::find("abuffersink").unwrap(), "out", "")
+            .map_err(|e| Error::FFmpeg(format!("Failed to add abuffersink: {}", e)))?;
+
+        filter_graph
+            .output("in", 0)
+            .and_then(|p| p.input("out", 0))
+            .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?
+            .parse(&format!(
+                "aresample={},aformat=sample_fmts=fltp:channel_layouts=stereo",
+                OUTPUT_SAMPLE_RATE
+            ))
+            .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+        filter_graph
+            .validate()
+            .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+        let aac_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+            .ok_or_else(|| Error::FFmpeg("AAC encoder not found".to_string()))?;
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(aac_codec)
+            .encoder()
+            .audio()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create audio encoder: {}", e)))?;
+
+        encoder.set_rate(OUTPUT_SAMPLE_RATE as i32);
+        encoder.set_format(ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar));
+        encoder.set_channel_layout(ffmpeg::ChannelLayout::STEREO);
+        encoder.set_bit_rate(192_000);
+        encoder.set_time_base(ffmpeg::Rational::new(1, OUTPUT_SAMPLE_RATE as i32));
+        if global_header {
+            encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+
+        let encoder = encoder
+            .open_as(aac_codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open audio encoder: {}", e)))?;
+
+        let output_index = {
+            let mut stream = output_ctx
+                .add_stream(aac_codec)
+                .map_err(|e| Error::FFmpeg(format!("Failed to add audio stream: {}", e)))?;
+            stream.set_parameters(&encoder);
+            stream.index()
+        };
+
+        Ok(Self {
+            input_index,
+            target: StreamTarget {
+                stream_index: output_index,
+                encoder_time_base: ffmpeg::Rational::new(1, OUTPUT_SAMPLE_RATE as i32),
+                output_time_base: ffmpeg::Rational::new(1, OUTPUT_SAMPLE_RATE as i32),
+            },
+            decoder,
+            encoder,
+            filter_graph,
+            input_samples: 0,
+            output_samples: 0,
+            speed_accumulator: 0.0,
+            pending: [vec![], vec![]],
+        })
+    }
+
+    fn send_packet(
+        &mut self,
+        packet: &ffmpeg::Packet,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        silences: &[Range<f64>],
+        speed: Option<f64>,
+    ) -> Result<()> {
+        if let Err(e) = self.decoder.send_packet(packet) {
+            log::warn!("Skip undecodable audio packet: {}", e);
+            return Ok(());
+        }
+
+        self.receive_frames(output_ctx, silences, speed)
+    }
+
+    fn finish(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        silences: &[Range<f64>],
+        speed: Option<f64>,
+    ) -> Result<()> {
+        self.decoder
+            .send_eof()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush audio decoder: {}", e)))?;
+        self.receive_frames(output_ctx, silences, speed)?;
+
+        self.filter_graph
+            .get("in")
+            .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?
+            .source()
+            .flush()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+        self.cut_filtered(output_ctx, silences, speed)?;
+
+        // The AAC encoder accepts a shorter last frame
+        if !self.pending[0].is_empty() {
+            let samples = self.pending[0].len();
+            self.encode_samples(output_ctx, samples)?;
+        }
+
+        encode::finish_encoder(&mut self.encoder, output_ctx, &self.target)
+    }
+
+    fn receive_frames(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        silences: &[Range<f64>],
+        speed: Option<f64>,
+    ) -> Result<()> {
+        let mut decoded = ffmpeg::frame::Audio::empty();
+
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            self.filter_graph
+                .get("in")
+                .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?
+                .source()
+                .add(&decoded)
+                .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+
+            self.cut_filtered(output_ctx, silences, speed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keep the filtered samples outside of the silences and encode them in AAC frames
+    fn cut_filtered(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        silences: &[Range<f64>],
+        speed: Option<f64>,
+    ) -> Result<()> {
+        let mut filtered = ffmpeg::frame::Audio::empty();
+
+        loop {
+            let received = self
+                .filter_graph
+                .get("out")
+                .ok_or_else(|| Error::FFmpeg("Failed to get out filter".to_string()))?
+                .sink()
+                .frame(&mut filtered)
+                .is_ok();
+
+            if !received {
+                break;
+            }
+
+            // The time map restarts for each frame, the cursor catches up quickly
+            let mut map = TimeMap::new(silences, speed);
+            let (left, right) = (filtered.plane::<f32>(0), filtered.plane::<f32>(1));
+
+            for (l, r) in left.iter().zip(right) {
+                let time = self.input_samples as f64 / OUTPUT_SAMPLE_RATE as f64;
+                self.input_samples += 1;
+
+                let keep = match (map.map(time), speed) {
+                    (None, _) => false,
+                    (Some((_, true)), Some(speed)) => {
+                        self.speed_accumulator += 1.0 / speed;
+                        if self.speed_accumulator >= 1.0 {
+                            self.speed_accumulator -= 1.0;
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    (Some(_), _) => true,
+                };
+
+                if keep {
+                    self.pending[0].push(*l);
+                    self.pending[1].push(*r);
+                }
+            }
+
+            while self.pending[0].len() >= AAC_FRAME_SIZE {
+                self.encode_samples(output_ctx, AAC_FRAME_SIZE)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_samples(&mut self, output_ctx: &mut ffmpeg::format::context::Output, samples: usize) -> Result<()> {
+        let mut frame = ffmpeg::frame::Audio::new(
+            ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar),
+            samples,
+            ffmpeg::ChannelLayout::STEREO,
+        );
+        frame.set_rate(OUTPUT_SAMPLE_RATE);
+        frame.set_pts(Some(self.output_samples as i64));
+
+        for channel in 0..2 {
+            let data = self.pending[channel].drain(..samples).collect::<Vec<_>>();
+            frame.plane_mut::<f32>(channel).copy_from_slice(&data);
+        }
+
+        self.output_samples += samples as u64;
+
+        self.encoder
+            .send_frame(&frame)
+            .map_err(|e| Error::FFmpeg(format!("Audio encoder send failed: {}", e)))?;
+        encode::write_packets(&mut self.encoder, output_ctx, &self.target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence_config_default() {
+        let config = SilenceConfig::default();
+        assert_eq!(config.threshold_db, -40.0);
+        assert_eq!(config.min_duration, Duration::from_millis(500));
+        assert!(config.frame_diff_threshold.is_none());
+    }
+
+    #[test]
+    fn test_auto_cut_config_validation() {
+        assert!(AutoCutConfig::new("in.mp4", "out.mp4").validate().is_ok());
+        assert!(AutoCutConfig::new("", "out.mp4").validate().is_err());
+        assert!(AutoCutConfig::new("in.mp4", "out.mp4")
+            .with_action(SilenceAction::SpeedUp(0.5))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_rms_windows() {
+        let mut windows = RmsWindows::default();
+        // 100 samples/s -> 2 samples per 20ms window
+        windows.add_samples(100, &[0.5, 0.5, 0.0, 0.001, 0.5], -40.0);
+        let spans = windows.finish();
+
+        assert_eq!(spans.len(), 3);
+        assert!(!spans[0].2);
+        assert!(spans[1].2);
+        assert!(!spans[2].2);
+        assert_eq!(spans[2].0, 0.04);
+    }
+
+    #[test]
+    fn test_merge_spans() {
+        let spans = [
+            (0.0, 1.0, true),
+            (1.0, 2.0, true),
+            (2.0, 3.0, false),
+            (3.0, 4.0, true),
+        ];
+        assert_eq!(merge_spans(&spans), vec![0.0..2.0, 3.0..4.0]);
+    }
+
+    #[test]
+    fn test_intersect_ranges() {
+        let a = [0.0..5.0, 10.0..20.0];
+        let b = [3.0..12.0, 15.0..16.0, 19.0..25.0];
+        assert_eq!(
+            intersect_ranges(&a, &b),
+            vec![3.0..5.0, 10.0..12.0, 15.0..16.0, 19.0..20.0]
+        );
+    }
+
+    #[test]
+    fn test_finalize_ranges() {
+        let ranges = finalize_ranges(
+            vec![0.0..0.3, 1.0..2.0],
+            Duration::from_millis(500),
+            Duration::from_millis(100),
+        );
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, Duration::from_secs_f64(1.1));
+        assert_eq!(ranges[0].end, Duration::from_secs_f64(1.9));
+    }
+
+    #[test]
+    fn test_time_map_remove() {
+        let silences = [2.0..4.0, 6.0..7.0];
+        let mut map = TimeMap::new(&silences, None);
+
+        assert_eq!(map.map(1.0), Some((1.0, false)));
+        assert_eq!(map.map(3.0), None);
+        assert_eq!(map.map(5.0), Some((3.0, false)));
+        assert_eq!(map.map(8.0), Some((5.0, false)));
+    }
+
+    #[test]
+    fn test_time_map_speed_up() {
+        let silences = [2.0..6.0];
+        let mut map = TimeMap::new(&silences, Some(4.0));
+
+        assert_eq!(map.map(1.0), Some((1.0, false)));
+        assert_eq!(map.map(4.0), Some((2.5, true)));
+        assert_eq!(map.map(7.0), Some((4.0, false)));
+    }
+}
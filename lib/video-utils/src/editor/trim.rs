@@ -1,8 +1,10 @@
 //! Video trimming/cutting functionality
 //!
-//! Allows extracting specific time ranges from videos or removing segments.
+//! Allows extracting specific time ranges from videos or removing segments,
+//! either by re-encoding or by copying packets (lossless and smart cut).
 
 use crate::{Result, Error};
+use crate::encode;
 use std::path::Path;
 use std::time::Duration;
 use ffmpeg_next as ffmpeg;
@@ -201,6 +203,517 @@ pub fn extract_segment(input: impl AsRef<str>, output: impl AsRef<str>, start_se
     trim_video(config)
 }
 
+/// How `lossless_trim` handles a start point between two keyframes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutMode {
+    /// Move the start back to the previous keyframe, nothing is re-encoded
+    Keyframe,
+    /// Re-encode only the frames from the start to the next keyframe and copy
+    /// the rest (H.264 only, other codecs fall back to `Keyframe`)
+    Smart,
+}
+
+/// Trim a video without re-encoding it
+///
+/// Packets are copied from the input, so trimming a long recording takes
+/// about as long as copying the file. In `CutMode::Keyframe` the start snaps
+/// back to the previous keyframe; in `CutMode::Smart` the start is frame
+/// accurate and only the GOP containing it is re-encoded. The end is cut at
+/// the first video packet past it.
+///
+/// # Arguments
+/// * `config` - Trim configuration
+/// * `mode` - Handling of a start point between keyframes
+///
+/// # Returns
+/// Returns the actual start time of the output in the input video
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use video_utils::editor::trim::{lossless_trim, CutMode, TrimConfig};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Drop the first 10 seconds of a long recording
+/// let config = TrimConfig::new("recording.mp4", "trimmed.mp4", Duration::from_secs(10));
+/// let start = lossless_trim(config, CutMode::Smart)?;
+/// println!("Output starts at {:?}", start);
+/// # Ok(())
+/// # }
+/// ```
+pub fn lossless_trim(config: TrimConfig, mode: CutMode) -> Result<Duration> {
+    if config.input.is_empty() || config.output.is_empty() {
+        return Err(Error::InvalidConfig("Input and output paths must be set".to_string()));
+    }
+
+    log::info!(
+        "Lossless trim ({:?}): {} [{:?}, {:?}] -> {}",
+        mode,
+        config.input,
+        config.start,
+        config.duration,
+        config.output
+    );
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&Path::new(&config.input))
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let (video_index, video_time_base, video_parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg("No video stream found".to_string()))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+
+    let start_secs = config.start.as_secs_f64();
+    let end_secs = config
+        .duration
+        .map(|duration| start_secs + duration.as_secs_f64())
+        .unwrap_or(f64::INFINITY);
+
+    let mut output_ctx = ffmpeg::format::output(&Path::new(&config.output))
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    // Copy the video and audio streams
+    let mut stream_mapping = vec![None; input_ctx.nb_streams() as usize];
+    let mut audio_index = None;
+
+    for stream in input_ctx.streams() {
+        let medium = stream.parameters().medium();
+        if stream.index() != video_index && medium != ffmpeg::media::Type::Audio {
+            continue;
+        }
+
+        let output_index = encode::add_copy_stream(&mut output_ctx, stream.parameters())?;
+
+        if medium == ffmpeg::media::Type::Audio && audio_index.is_none() {
+            audio_index = Some(stream.index());
+        }
+
+        stream_mapping[stream.index()] = Some((output_index, stream.time_base()));
+    }
+
+    let mut smart_cut = match mode {
+        CutMode::Smart if video_parameters.id() == ffmpeg::codec::Id::H264 => {
+            Some(SmartCut::new(&video_parameters, video_time_base, input_ctx.stream(video_index).unwrap().avg_frame_rate())?)
+        }
+        CutMode::Smart => {
+            log::warn!("Smart cut only supports H.264, cutting on keyframes instead");
+            None
+        }
+        CutMode::Keyframe => None,
+    };
+
+    // Seek to the keyframe at or before the start
+    if start_secs > 0.0 {
+        let timestamp = (start_secs * 1_000_000.0) as i64; // AV_TIME_BASE
+        input_ctx
+            .seek(timestamp, ..timestamp)
+            .map_err(|e| Error::FFmpeg(format!("Failed to seek: {}", e)))?;
+    }
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let targets = stream_mapping
+        .iter()
+        .map(|mapping| {
+            mapping.map(|(stream_index, time_base)| CopyTarget {
+                stream_index,
+                time_base,
+                output_time_base: output_ctx.stream(stream_index).unwrap().time_base(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let secs = |ts: Option<i64>, time_base: ffmpeg::Rational| ts.map(|ts| ts as f64 * f64::from(time_base));
+
+    // Output start in the input, known at the first video keyframe
+    let mut cut_start: Option<f64> = None;
+    let mut reencoding = false;
+    let mut pending_audio = vec![];
+    let (mut video_done, mut audio_done) = (false, audio_index.is_none());
+
+    for (stream, mut packet) in input_ctx.packets() {
+        let Some(target) = targets[stream.index()] else {
+            continue;
+        };
+
+        if stream.index() != video_index {
+            if Some(stream.index()) == audio_index
+                && secs(packet.pts(), target.time_base).is_some_and(|pts| pts >= end_secs)
+            {
+                audio_done = true;
+            }
+
+            match cut_start {
+                Some(cut_start) => write_copied(&mut packet, &mut output_ctx, &target, cut_start, end_secs)?,
+                None => pending_audio.push((packet, target)),
+            }
+        } else if !video_done {
+            let pts_secs = secs(packet.pts(), target.time_base).unwrap_or(0.0);
+
+            if cut_start.is_none() {
+                if !packet.is_key() {
+                    continue;
+                }
+
+                // Re-encode unless the start is on this keyframe
+                let frame_secs = smart_cut.as_ref().map(|smart| smart.frame_secs).unwrap_or(0.0);
+                reencoding = smart_cut.is_some() && pts_secs < start_secs - frame_secs / 2.0;
+                let start = if reencoding { start_secs } else { pts_secs };
+                cut_start = Some(start);
+
+                for (mut packet, target) in pending_audio.drain(..) {
+                    write_copied(&mut packet, &mut output_ctx, &target, start, end_secs)?;
+                }
+
+                log::debug!("Cut starts at {:.3}s (keyframe at {:.3}s)", start, pts_secs);
+            }
+
+            let cut_start = cut_start.unwrap();
+
+            if reencoding {
+                let smart = smart_cut.as_mut().unwrap();
+
+                if !packet.is_key() || pts_secs <= cut_start {
+                    smart.decode(&packet, start_secs, end_secs)?;
+                    continue;
+                }
+
+                // Next keyframe: write the re-encoded GOP and copy from here on
+                let delay = packet.pts().zip(packet.dts()).map(|(pts, dts)| pts - dts).unwrap_or(0);
+                smart.finish(&mut output_ctx, &target, cut_start, end_secs, delay)?;
+                smart.prepend_parameter_sets(&mut packet);
+                reencoding = false;
+            }
+
+            if secs(packet.dts().or(packet.pts()), target.time_base).is_some_and(|dts| dts >= end_secs) {
+                video_done = true;
+            } else {
+                write_copied(&mut packet, &mut output_ctx, &target, cut_start, f64::INFINITY)?;
+            }
+        }
+
+        if video_done && audio_done {
+            break;
+        }
+    }
+
+    if reencoding
+        && let (Some(smart), Some(cut_start), Some(target)) = (smart_cut.as_mut(), cut_start, targets[video_index])
+    {
+        smart.finish(&mut output_ctx, &target, cut_start, end_secs, 0)?;
+    }
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    let cut_start = cut_start.ok_or_else(|| Error::FFmpeg("No video keyframe found after the start".to_string()))?;
+    log::info!("Lossless trim complete: {} (starts at {:.3}s)", config.output, cut_start);
+
+    Ok(Duration::from_secs_f64(cut_start))
+}
+
+/// Output stream of a copied input stream
+#[derive(Debug, Clone, Copy)]
+struct CopyTarget {
+    stream_index: usize,
+    /// Time base of the input stream
+    time_base: ffmpeg::Rational,
+    output_time_base: ffmpeg::Rational,
+}
+
+/// Copy a packet between `cut_start` and `end_secs`, timestamps start at `cut_start`
+fn write_copied(
+    packet: &mut ffmpeg::Packet,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    target: &CopyTarget,
+    cut_start: f64,
+    end_secs: f64,
+) -> Result<()> {
+    let pts_secs = packet.pts().map(|pts| pts as f64 * f64::from(target.time_base));
+    if pts_secs.is_some_and(|pts| pts < cut_start || pts >= end_secs) {
+        return Ok(());
+    }
+
+    let offset = (cut_start / f64::from(target.time_base)).round() as i64;
+    packet.set_pts(packet.pts().map(|pts| pts - offset));
+    packet.set_dts(packet.dts().map(|dts| dts - offset));
+    packet.set_stream(target.stream_index);
+    packet.set_position(-1);
+    packet.rescale_ts(target.time_base, target.output_time_base);
+    packet
+        .write_interleaved(output_ctx)
+        .map_err(|e| Error::FFmpeg(format!("Failed to write packet: {}", e)))
+}
+
+/// Re-encoding state of the GOP containing the cut point
+struct SmartCut {
+    decoder: ffmpeg::decoder::Video,
+    encoder: ffmpeg::encoder::Video,
+    /// NAL length size of the input stream (None = Annex B)
+    nal_length_size: Option<usize>,
+    /// SPS and PPS of the input stream in its own format, written before the
+    /// first copied keyframe since the re-encoded GOP carries its own
+    parameter_sets: Vec<u8>,
+    /// Duration of one frame in seconds
+    frame_secs: f64,
+    packets: Vec<ffmpeg::Packet>,
+}
+
+impl SmartCut {
+    fn new(parameters: &ffmpeg::codec::Parameters, time_base: ffmpeg::Rational, frame_rate: ffmpeg::Rational) -> Result<Self> {
+        let decoder = ffmpeg::codec::context::Context::from_parameters(parameters.clone())
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+            .decoder()
+            .video()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+        let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
+            .ok_or_else(|| Error::FFmpeg("H.264 encoder not found".to_string()))?;
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create encoder: {}", e)))?;
+
+        encoder.set_width(decoder.width());
+        encoder.set_height(decoder.height());
+        encoder.set_format(decoder.format());
+        encoder.set_aspect_ratio(decoder.aspect_ratio());
+        encoder.set_time_base(time_base);
+        encoder.set_frame_rate(Some(frame_rate));
+        // No B-frames so that the re-encoded packets are in presentation order,
+        // and no global header so that SPS/PPS are written in-band
+        encoder.set_max_b_frames(0);
+
+        let mut encoder_opts = ffmpeg::Dictionary::new();
+        encoder_opts.set("crf", "18");
+        encoder_opts.set("preset", "medium");
+
+        let encoder = encoder
+            .open_with(encoder_opts)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open encoder: {}", e)))?;
+
+        let extradata = codec_extradata(parameters);
+        let (nal_length_size, parameter_sets) = match parse_avcc(&extradata) {
+            Some((nal_length_size, nal_units)) => (
+                Some(nal_length_size),
+                to_length_prefixed(nal_units.iter().map(Vec::as_slice), nal_length_size),
+            ),
+            None => (None, extradata),
+        };
+
+        let frame_secs = if frame_rate.numerator() > 0 {
+            1.0 / f64::from(frame_rate)
+        } else {
+            0.0
+        };
+
+        Ok(Self {
+            decoder,
+            encoder,
+            nal_length_size,
+            parameter_sets,
+            frame_secs,
+            packets: vec![],
+        })
+    }
+
+    /// Decode a packet and encode its frames between `start_secs` and `end_secs`
+    fn decode(&mut self, packet: &ffmpeg::Packet, start_secs: f64, end_secs: f64) -> Result<()> {
+        self.decoder
+            .send_packet(packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+        self.encode_decoded(start_secs, end_secs)
+    }
+
+    fn encode_decoded(&mut self, start_secs: f64, end_secs: f64) -> Result<()> {
+        let time_base = self.encoder.time_base();
+        let mut frame = ffmpeg::frame::Video::empty();
+
+        while self.decoder.receive_frame(&mut frame).is_ok() {
+            let Some(ts) = frame.timestamp().or(frame.pts()) else {
+                continue;
+            };
+
+            let secs = ts as f64 * f64::from(time_base);
+            if secs < start_secs - self.frame_secs / 2.0 || secs >= end_secs {
+                continue;
+            }
+
+            frame.set_pts(Some(ts));
+            frame.set_kind(ffmpeg::picture::Type::None);
+
+            self.encoder
+                .send_frame(&frame)
+                .map_err(|e| Error::FFmpeg(format!("Encoder send failed: {}", e)))?;
+            self.receive_packets()?;
+        }
+
+        Ok(())
+    }
+
+    fn receive_packets(&mut self) -> Result<()> {
+        let mut packet = ffmpeg::Packet::empty();
+
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            self.packets.push(packet.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Flush the codecs and write the re-encoded packets, `delay` is the
+    /// pts - dts offset of the copied packets that follow
+    fn finish(
+        &mut self,
+        output_ctx: &mut ffmpeg::format::context::Output,
+        target: &CopyTarget,
+        cut_start: f64,
+        end_secs: f64,
+        delay: i64,
+    ) -> Result<()> {
+        self.decoder
+            .send_eof()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+        self.encode_decoded(cut_start, end_secs)?;
+
+        self.encoder
+            .send_eof()
+            .map_err(|e| Error::FFmpeg(format!("Failed to flush encoder: {}", e)))?;
+        self.receive_packets()?;
+
+        log::debug!("Smart cut re-encoded {} frames", self.packets.len());
+
+        let offset = (cut_start / f64::from(target.time_base)).round() as i64;
+
+        for encoded in self.packets.drain(..) {
+            let data = encoded.data().unwrap_or_default();
+            let mut packet = match self.nal_length_size {
+                Some(nal_length_size) => ffmpeg::Packet::copy(&to_length_prefixed(annexb_nal_units(data), nal_length_size)),
+                None => ffmpeg::Packet::copy(data),
+            };
+
+            // Shift the dts like the copied packets so that dts stays monotonic
+            let pts = encoded.pts().map(|pts| pts - offset);
+            packet.set_pts(pts);
+            packet.set_dts(pts.map(|pts| pts - delay));
+            packet.set_duration(encoded.duration());
+            packet.set_flags(encoded.flags());
+            packet.set_stream(target.stream_index);
+            packet.rescale_ts(target.time_base, target.output_time_base);
+            packet
+                .write_interleaved(output_ctx)
+                .map_err(|e| Error::FFmpeg(format!("Failed to write packet: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Put the SPS/PPS of the input in front of the first copied keyframe
+    fn prepend_parameter_sets(&self, packet: &mut ffmpeg::Packet) {
+        if self.parameter_sets.is_empty() {
+            return;
+        }
+
+        let mut data = self.parameter_sets.clone();
+        data.extend_from_slice(packet.data().unwrap_or_default());
+
+        let mut new_packet = ffmpeg::Packet::copy(&data);
+        new_packet.set_pts(packet.pts());
+        new_packet.set_dts(packet.dts());
+        new_packet.set_duration(packet.duration());
+        new_packet.set_flags(packet.flags());
+        *packet = new_packet;
+    }
+}
+
+fn codec_extradata(parameters: &ffmpeg::codec::Parameters) -> Vec<u8> {
+    unsafe {
+        let parameters = parameters.as_ptr();
+        if (*parameters).extradata.is_null() || (*parameters).extradata_size <= 0 {
+            return vec![];
+        }
+
+        std::slice::from_raw_parts((*parameters).extradata, (*parameters).extradata_size as usize).to_vec()
+    }
+}
+
+/// NAL length size and SPS/PPS NAL units of an `avcC` record, None for Annex B extradata
+fn parse_avcc(extradata: &[u8]) -> Option<(usize, Vec<Vec<u8>>)> {
+    if extradata.len() < 7 || extradata[0] != 1 {
+        return None;
+    }
+
+    let nal_length_size = (extradata[4] & 0x03) as usize + 1;
+    let mut nal_units = vec![];
+    let mut offset = 5;
+
+    // SPS count is in the low 5 bits, PPS count is a full byte
+    for mask in [0x1f, 0xff] {
+        let count = (*extradata.get(offset)? & mask) as usize;
+        offset += 1;
+
+        for _ in 0..count {
+            let size = u16::from_be_bytes([*extradata.get(offset)?, *extradata.get(offset + 1)?]) as usize;
+            nal_units.push(extradata.get(offset + 2..offset + 2 + size)?.to_vec());
+            offset += 2 + size;
+        }
+    }
+
+    Some((nal_length_size, nal_units))
+}
+
+/// Split Annex B data at its 3 or 4 byte start codes
+fn annexb_nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let mut end = starts.get(index + 1).map(|next| next - 3).unwrap_or(data.len());
+            // Trailing zero of a 4 byte start code
+            while end > start && data[end - 1] == 0 {
+                end -= 1;
+            }
+            &data[start..end]
+        })
+        .filter(|nal_unit| !nal_unit.is_empty())
+        .collect()
+}
+
+/// Join NAL units with big-endian length prefixes
+fn to_length_prefixed<'a>(nal_units: impl IntoIterator<Item = &'a [u8]>, nal_length_size: usize) -> Vec<u8> {
+    let mut data = vec![];
+
+    for nal_unit in nal_units {
+        let length = (nal_unit.len() as u32).to_be_bytes();
+        data.extend_from_slice(&length[4 - nal_length_size.min(4)..]);
+        data.extend_from_slice(nal_unit);
+    }
+
+    data
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +743,31 @@ mod tests {
 
         assert_eq!(config.duration, Some(Duration::from_secs(30)));
     }
+
+    #[test]
+    fn test_parse_avcc() {
+        let extradata = [
+            1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 3, 0x67, 0xaa, 0xbb, 1, 0, 2, 0x68, 0xcc,
+        ];
+
+        let (nal_length_size, nal_units) = parse_avcc(&extradata).unwrap();
+        assert_eq!(nal_length_size, 4);
+        assert_eq!(nal_units, vec![vec![0x67, 0xaa, 0xbb], vec![0x68, 0xcc]]);
+
+        assert!(parse_avcc(&[0, 0, 0, 1, 0x67, 0xaa, 0xbb]).is_none());
+        assert!(parse_avcc(&[1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 9, 0x67]).is_none());
+    }
+
+    #[test]
+    fn test_annexb_to_length_prefixed() {
+        let data = [0, 0, 0, 1, 0x67, 0xaa, 0, 0, 1, 0x68, 0xbb, 0, 0, 0, 1, 0x65, 0x01, 0x02];
+        let nal_units = annexb_nal_units(&data);
+
+        assert_eq!(nal_units, vec![&[0x67, 0xaa][..], &[0x68, 0xbb][..], &[0x65, 0x01, 0x02][..]]);
+        assert_eq!(
+            to_length_prefixed(nal_units, 4),
+            vec![0, 0, 0, 2, 0x67, 0xaa, 0, 0, 0, 2, 0x68, 0xbb, 0, 0, 0, 3, 0x65, 0x01, 0x02]
+        );
+        assert_eq!(to_length_prefixed([&[0x68, 0xbb][..]], 2), vec![0, 2, 0x68, 0xbb]);
+    }
 }
@@ -0,0 +1,124 @@
+//! Encoding helpers shared by the editors, the filters and the timeline
+
+use crate::{Error, Result};
+use ffmpeg_next as ffmpeg;
+
+/// Largest H.264 constant rate factor
+pub(crate) const MAX_CRF: u8 = 51;
+
+pub(crate) fn validate_crf(crf: u8) -> Result<()> {
+    if crf > MAX_CRF {
+        return Err(Error::InvalidConfig(format!(
+            "CRF must be 0-{MAX_CRF}, got: {crf}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Output stream the packets of an encoder are written to
+pub(crate) struct StreamTarget {
+    pub(crate) stream_index: usize,
+    pub(crate) encoder_time_base: ffmpeg::Rational,
+    pub(crate) output_time_base: ffmpeg::Rational,
+}
+
+/// Encode all frames currently available from the `sink` filter of the graph,
+/// `on_frame` sees each frame before it's encoded. Returns the number of
+/// encoded frames
+pub(crate) fn drain_filter(
+    filter_graph: &mut ffmpeg::filter::Graph,
+    sink: &str,
+    encoder: &mut ffmpeg::encoder::Encoder,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    target: &StreamTarget,
+    mut on_frame: impl FnMut(&mut ffmpeg::Frame),
+) -> Result<u64> {
+    // SAFETY: the frame is allocated, the sink fills it with a video or an audio frame
+    let mut frame = unsafe { ffmpeg::Frame::empty() };
+    let mut count = 0;
+
+    while filter_graph
+        .get(sink)
+        .ok_or_else(|| Error::FFmpeg(format!("Failed to get {} filter", sink)))?
+        .sink()
+        .frame(&mut frame)
+        .is_ok()
+    {
+        on_frame(&mut frame);
+
+        encoder
+            .send_frame(&frame)
+            .map_err(|e| Error::FFmpeg(format!("Encoder send failed: {}", e)))?;
+
+        write_packets(encoder, output_ctx, target)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Write all packets currently available from the encoder
+pub(crate) fn write_packets(
+    encoder: &mut ffmpeg::encoder::Encoder,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    target: &StreamTarget,
+) -> Result<()> {
+    let mut packet = ffmpeg::Packet::empty();
+
+    while encoder.receive_packet(&mut packet).is_ok() {
+        packet.set_stream(target.stream_index);
+        packet.rescale_ts(target.encoder_time_base, target.output_time_base);
+        packet
+            .write_interleaved(output_ctx)
+            .map_err(|e| Error::FFmpeg(format!("Failed to write packet: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// Add an output stream the packets with `parameters` are copied into without
+/// re-encoding. Returns the index of the output stream
+pub(crate) fn add_copy_stream(
+    output_ctx: &mut ffmpeg::format::context::Output,
+    parameters: impl Into<ffmpeg::codec::Parameters>,
+) -> Result<usize> {
+    let mut output_stream = output_ctx
+        .add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))
+        .map_err(|e| Error::FFmpeg(format!("Failed to add stream: {}", e)))?;
+    output_stream.set_parameters(parameters);
+
+    // The codec tag of the source container may be invalid in the output container,
+    // the muxer picks one for a zero tag.
+    // SAFETY: the stream was just added, so its codec parameters are allocated
+    unsafe {
+        (*(*output_stream.as_mut_ptr()).codecpar).codec_tag = 0;
+    }
+
+    Ok(output_stream.index())
+}
+
+/// Flush the encoder and write the remaining packets
+pub(crate) fn finish_encoder(
+    encoder: &mut ffmpeg::encoder::Encoder,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    target: &StreamTarget,
+) -> Result<()> {
+    encoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to send EOF to encoder: {}", e)))?;
+
+    write_packets(encoder, output_ctx, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_crf() {
+        assert!(validate_crf(0).is_ok());
+        assert!(validate_crf(MAX_CRF).is_ok());
+        assert!(validate_crf(MAX_CRF + 1).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Video deinterlacing filter
+//!
+//! Converts interlaced footage (e.g. from camcorders or capture cards) to
+//! progressive frames with FFmpeg's `yadif` or `bwdif` filter.
+
+use crate::filters::pipeline::run_video_filter;
+use crate::encode;
+use crate::{Error, Result};
+use derivative::Derivative;
+use derive_setters::Setters;
+use std::path::Path;
+
+/// Deinterlacing algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeinterlaceMethod {
+    /// Yet Another DeInterlacing Filter
+    Yadif,
+    /// Bob Weaver, sharper than yadif with less artifacts
+    Bwdif,
+}
+
+/// Output frames of the deinterlacer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeinterlaceMode {
+    /// One frame per frame, keeps the frame rate
+    Frame,
+    /// One frame per field, doubles the frame rate for smoother motion
+    Field,
+}
+
+/// Field order of the interlaced input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldParity {
+    /// Detect from the input
+    Auto,
+    TopFieldFirst,
+    BottomFieldFirst,
+}
+
+/// Video deinterlacing configuration
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct DeinterlaceConfig {
+    /// Input video file
+    #[derivative(Default(value = "String::new()"))]
+    pub input: String,
+
+    /// Output video file
+    #[derivative(Default(value = "String::new()"))]
+    pub output: String,
+
+    /// Deinterlacing algorithm
+    #[derivative(Default(value = "DeinterlaceMethod::Bwdif"))]
+    pub method: DeinterlaceMethod,
+
+    /// Output one frame per frame or per field
+    #[derivative(Default(value = "DeinterlaceMode::Frame"))]
+    pub mode: DeinterlaceMode,
+
+    /// Field order of the input
+    #[derivative(Default(value = "FieldParity::Auto"))]
+    pub parity: FieldParity,
+
+    /// Only deinterlace frames marked as interlaced
+    #[derivative(Default(value = "false"))]
+    pub only_interlaced: bool,
+
+    /// Encoder CRF of the deinterlaced frames
+    #[derivative(Default(value = "23"))]
+    pub crf: u8,
+}
+
+impl DeinterlaceConfig {
+    /// Create a new deinterlace config (convenience method)
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self::default()
+            .with_input(input.into())
+            .with_output(output.into())
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> Result<()> {
+        if self.input.is_empty() {
+            return Err(Error::InvalidConfig("Input path is empty".to_string()));
+        }
+
+        if self.output.is_empty() {
+            return Err(Error::InvalidConfig("Output path is empty".to_string()));
+        }
+
+        encode::validate_crf(self.crf)?;
+
+        Ok(())
+    }
+
+    /// Build the FFmpeg filter
+    fn build_filter_spec(&self) -> String {
+        let filter = match self.method {
+            DeinterlaceMethod::Yadif => "yadif",
+            DeinterlaceMethod::Bwdif => "bwdif",
+        };
+
+        let mode = match self.mode {
+            DeinterlaceMode::Frame => "send_frame",
+            DeinterlaceMode::Field => "send_field",
+        };
+
+        let parity = match self.parity {
+            FieldParity::Auto => "auto",
+            FieldParity::TopFieldFirst => "tff",
+            FieldParity::BottomFieldFirst => "bff",
+        };
+
+        let deint = if self.only_interlaced { "interlaced" } else { "all" };
+
+        format!("{}=mode={}:parity={}:deint={}", filter, mode, parity, deint)
+    }
+}
+
+/// Deinterlace a video
+///
+/// Audio is copied. With [`DeinterlaceMode::Field`] the output has twice the
+/// frame rate of the input.
+///
+/// # Arguments
+/// * `config` - Deinterlacing configuration
+///
+/// # Example
+/// ```no_run
+/// use video_utils::filters::deinterlace::{deinterlace_video, DeinterlaceConfig, DeinterlaceMode};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = DeinterlaceConfig::new("camcorder.mts", "camcorder.mp4")
+///     .with_mode(DeinterlaceMode::Field);
+/// deinterlace_video(config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn deinterlace_video(config: DeinterlaceConfig) -> Result<()> {
+    config.validate()?;
+
+    log::info!(
+        "Deinterlacing video: {} -> {} ({:?}, {:?})",
+        config.input,
+        config.output,
+        config.method,
+        config.mode
+    );
+
+    run_video_filter(
+        Path::new(&config.input),
+        Some(Path::new(&config.output)),
+        &config.build_filter_spec(),
+        config.crf,
+    )?;
+
+    log::info!("Deinterlacing complete: {}", config.output);
+
+    Ok(())
+}
+
+/// Deinterlace a video with the default settings (`bwdif`, one frame per frame)
+pub fn deinterlace(input: &str, output: &str) -> Result<()> {
+    deinterlace_video(DeinterlaceConfig::new(input, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deinterlace_config() {
+        let config = DeinterlaceConfig::new("in.mts", "out.mp4");
+
+        assert_eq!(config.method, DeinterlaceMethod::Bwdif);
+        assert_eq!(config.mode, DeinterlaceMode::Frame);
+        assert!(config.validate().is_ok());
+        assert!(DeinterlaceConfig::default().validate().is_err());
+    }
+
+    #[test]
+    fn test_deinterlace_filter_spec() {
+        let config = DeinterlaceConfig::new("in.mts", "out.mp4");
+        assert_eq!(
+            config.build_filter_spec(),
+            "bwdif=mode=send_frame:parity=auto:deint=all"
+        );
+        assert_eq!(
+            config
+                .with_method(DeinterlaceMethod::Yadif)
+                .with_mode(DeinterlaceMode::Field)
+                .with_parity(FieldParity::TopFieldFirst)
+                .with_only_interlaced(true)
+                .build_filter_spec(),
+            "yadif=mode=send_field:parity=tff:deint=interlaced"
+        );
+    }
+}
@@ -9,6 +9,11 @@ pub mod crop;
 pub mod color;
 pub mod crossfade;
 pub mod text_overlay;
+pub mod stabilize;
+pub mod denoise;
+pub mod deinterlace;
+
+mod pipeline;
 
 pub use scale::{scale_video, ScaleConfig, ScaleQuality, scale_to_fit, scale_to_exact};
 pub use transform::{rotate_video, flip_video, RotateAngle, FlipDirection,
@@ -18,3 +23,7 @@ pub use crop::{crop_video, CropConfig, CropMode, crop_center, crop_to_aspect};
 pub use color::{adjust_color, ColorAdjustConfig, adjust_brightness, adjust_contrast, adjust_saturation};
 pub use crossfade::{crossfade_videos, CrossfadeConfig};
 pub use text_overlay::{text_overlay, TextOverlayConfig, TextPosition, TextAlignment, add_watermark, add_title};
+pub use stabilize::{stabilize_video, StabilizeConfig, stabilize};
+pub use denoise::{denoise_video, DenoiseConfig, DenoiseMethod, DenoiseStrength, denoise};
+pub use deinterlace::{deinterlace_video, DeinterlaceConfig, DeinterlaceMethod, DeinterlaceMode,
+    FieldParity, deinterlace};
@@ -0,0 +1,320 @@
+//! Shared single-input filter pipeline
+//!
+//! Decodes the video stream of a file, runs it through an FFmpeg filter graph
+//! and re-encodes it as H.264. The audio stream is copied without re-encoding.
+
+use crate::encode::{self, StreamTarget};
+use crate::{Error, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+
+/// Run the video of `input` through `filter_spec`
+///
+/// With `output` set to `None` the filtered frames are discarded, which is
+/// used by analysis passes such as `vidstabdetect`.
+pub(crate) fn run_video_filter(input: &Path, output: Option<&Path>, filter_spec: &str, crf: u8) -> Result<()> {
+    ffmpeg::init().map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&input)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", input.display(), e)))?;
+
+    let (video_index, time_base, parameters) = {
+        let stream = input_ctx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| Error::FFmpeg(format!("No video stream found in {}", input.display())))?;
+        (stream.index(), stream.time_base(), stream.parameters())
+    };
+
+    let audio_stream = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Audio)
+        .map(|stream| (stream.index(), stream.time_base(), stream.parameters()));
+
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+        .decoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder: {}", e)))?;
+
+    // Filter graph
+    let buffer_args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+        decoder.width(),
+        decoder.height(),
+        decoder
+            .format()
+            .descriptor()
+            .ok_or_else(|| Error::FFmpeg("Unknown pixel format".to_string()))?
+            .name(),
+        time_base,
+        decoder.aspect_ratio()
+    );
+
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffer").unwrap(), "in", &buffer_args)
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffer filter: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffersink").unwrap(), "out", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffersink: {}", e)))?;
+
+    // The encoder only takes YUV420P
+    let filter_spec = format!("{},format=yuv420p", filter_spec);
+    log::debug!("Filter spec: {}", filter_spec);
+
+    filter_graph
+        .output("in", 0)
+        .and_then(|p| p.input("out", 0))
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?
+        .parse(&filter_spec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    let mut output = match output {
+        Some(path) => Some(FilterOutput::open(path, &mut filter_graph, audio_stream, crf)?),
+        None => None,
+    };
+
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut frame_count = 0u64;
+
+    for (stream, mut packet) in input_ctx.packets() {
+        if stream.index() != video_index {
+            if let Some(output) = output.as_mut() {
+                output.copy_audio(stream.index(), &mut packet)?;
+            }
+            continue;
+        }
+
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+
+        frame_count += filter_frames(&mut decoder, &mut decoded, &mut filter_graph, output.as_mut())?;
+    }
+
+    decoder
+        .send_eof()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+    frame_count += filter_frames(&mut decoder, &mut decoded, &mut filter_graph, output.as_mut())?;
+
+    filter_graph
+        .get("in")
+        .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?
+        .source()
+        .flush()
+        .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+    drain_filter(&mut filter_graph, output.as_mut())?;
+
+    if let Some(mut output) = output {
+        output.finish()?;
+    }
+
+    log::debug!("Filtered {} frames of {}", frame_count, input.display());
+
+    Ok(())
+}
+
+/// Escape a file path used as a filter option value
+pub(crate) fn escape_filter_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace(':', "\\:")
+        .replace('\'', "\\'")
+}
+
+/// Encoder and muxer of the filtered video
+struct FilterOutput {
+    ctx: ffmpeg::format::context::Output,
+    encoder: ffmpeg::encoder::Video,
+    video: StreamTarget,
+    /// Input stream index, input time base and output stream index of the copied audio
+    audio: Option<(usize, ffmpeg::Rational, usize)>,
+}
+
+impl FilterOutput {
+    fn open(
+        path: &Path,
+        filter_graph: &mut ffmpeg::filter::Graph,
+        audio_stream: Option<(usize, ffmpeg::Rational, ffmpeg::codec::Parameters)>,
+        crf: u8,
+    ) -> Result<Self> {
+        let sink = filter_graph
+            .get("out")
+            .ok_or_else(|| Error::FFmpeg("Failed to get out filter".to_string()))?;
+
+        // Deinterlacing per field doubles the frame rate, so take the
+        // properties of the sink instead of the input
+        let (width, height, frame_rate, encoder_time_base) = unsafe {
+            let ptr = sink.as_ptr();
+            (
+                ffmpeg::ffi::av_buffersink_get_w(ptr) as u32,
+                ffmpeg::ffi::av_buffersink_get_h(ptr) as u32,
+                ffmpeg::Rational::from(ffmpeg::ffi::av_buffersink_get_frame_rate(ptr)),
+                ffmpeg::Rational::from(ffmpeg::ffi::av_buffersink_get_time_base(ptr)),
+            )
+        };
+
+        let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
+            .ok_or_else(|| Error::FFmpeg("H.264 encoder not found".to_string()))?;
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .map_err(|e| Error::FFmpeg(format!("Failed to create encoder: {}", e)))?;
+
+        encoder.set_width(width);
+        encoder.set_height(height);
+        encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+        encoder.set_time_base(encoder_time_base);
+        if frame_rate.numerator() > 0 {
+            encoder.set_frame_rate(Some(frame_rate));
+        }
+
+        let mut ctx = ffmpeg::format::output(&path)
+            .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+        if ctx
+            .format()
+            .flags()
+            .contains(ffmpeg::format::Flags::GLOBAL_HEADER)
+        {
+            encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+
+        let mut encoder_opts = ffmpeg::Dictionary::new();
+        encoder_opts.set("crf", &crf.to_string());
+        encoder_opts.set("preset", "medium");
+
+        let encoder = encoder
+            .open_with(encoder_opts)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open encoder: {}", e)))?;
+
+        {
+            let mut output_stream = ctx
+                .add_stream(codec)
+                .map_err(|e| Error::FFmpeg(format!("Failed to add video stream: {}", e)))?;
+            output_stream.set_parameters(&encoder);
+            output_stream.set_time_base(encoder_time_base);
+        }
+
+        let audio = match audio_stream {
+            Some((index, time_base, parameters)) => {
+                Some((index, time_base, encode::add_copy_stream(&mut ctx, parameters)?))
+            }
+            None => None,
+        };
+
+        ctx.write_header()
+            .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+        let video = StreamTarget {
+            stream_index: 0,
+            encoder_time_base,
+            output_time_base: ctx
+                .stream(0)
+                .ok_or_else(|| Error::FFmpeg("Failed to get output video stream".to_string()))?
+                .time_base(),
+        };
+
+        Ok(Self {
+            ctx,
+            encoder,
+            video,
+            audio,
+        })
+    }
+
+    fn copy_audio(&mut self, stream_index: usize, packet: &mut ffmpeg::Packet) -> Result<()> {
+        let Some((audio_index, time_base, output_index)) = self.audio else {
+            return Ok(());
+        };
+
+        if stream_index != audio_index {
+            return Ok(());
+        }
+
+        let output_time_base = self.ctx.stream(output_index).unwrap().time_base();
+        packet.set_stream(output_index);
+        packet.set_position(-1);
+        packet.rescale_ts(time_base, output_time_base);
+        packet
+            .write_interleaved(&mut self.ctx)
+            .map_err(|e| Error::FFmpeg(format!("Failed to write audio packet: {}", e)))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        encode::finish_encoder(&mut self.encoder, &mut self.ctx, &self.video)?;
+
+        self.ctx
+            .write_trailer()
+            .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))
+    }
+}
+
+/// Push all decoded frames into the filter graph and encode the result
+fn filter_frames(
+    decoder: &mut ffmpeg::decoder::Video,
+    decoded: &mut ffmpeg::frame::Video,
+    filter_graph: &mut ffmpeg::filter::Graph,
+    mut output: Option<&mut FilterOutput>,
+) -> Result<u64> {
+    let mut count = 0;
+
+    while decoder.receive_frame(decoded).is_ok() {
+        let timestamp = decoded.timestamp();
+        decoded.set_pts(timestamp);
+
+        filter_graph
+            .get("in")
+            .ok_or_else(|| Error::FFmpeg("Failed to get in filter".to_string()))?
+            .source()
+            .add(decoded)
+            .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+
+        drain_filter(filter_graph, output.as_deref_mut())?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Encode (or discard) all frames currently available from the filter graph
+fn drain_filter(filter_graph: &mut ffmpeg::filter::Graph, output: Option<&mut FilterOutput>) -> Result<()> {
+    if let Some(output) = output {
+        encode::drain_filter(filter_graph, "out", &mut output.encoder, &mut output.ctx, &output.video, |_| {})?;
+        return Ok(());
+    }
+
+    let mut filtered = ffmpeg::frame::Video::empty();
+
+    while filter_graph
+        .get("out")
+        .ok_or_else(|| Error::FFmpeg("Failed to get out filter".to_string()))?
+        .sink()
+        .frame(&mut filtered)
+        .is_ok()
+    {}
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_filter_path() {
+        assert_eq!(escape_filter_path(Path::new("/tmp/a.trf")), "/tmp/a.trf");
+        assert_eq!(
+            escape_filter_path(Path::new("C:\\it's.trf")),
+            "C\\:\\\\it\\'s.trf"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Video stabilization filter
+//!
+//! Two-pass stabilization with libvidstab: the first pass detects the camera
+//! motion, the second pass smooths it and transforms the frames.
+
+use crate::filters::pipeline::{escape_filter_path, run_video_filter};
+use crate::encode;
+use crate::{Error, Result};
+use derivative::Derivative;
+use derive_setters::Setters;
+use ffmpeg_next as ffmpeg;
+use std::path::{Path, PathBuf};
+
+/// Video stabilization configuration
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct StabilizeConfig {
+    /// Input video file
+    #[derivative(Default(value = "String::new()"))]
+    pub input: String,
+
+    /// Output video file
+    #[derivative(Default(value = "String::new()"))]
+    pub output: String,
+
+    /// How shaky the footage is (1-10, 1 = little shake)
+    #[derivative(Default(value = "5"))]
+    pub shakiness: u8,
+
+    /// Accuracy of the motion detection (1-15, 15 = most accurate)
+    #[derivative(Default(value = "15"))]
+    pub accuracy: u8,
+
+    /// Number of frames before and after each frame used to smooth the motion
+    #[derivative(Default(value = "10"))]
+    pub smoothing: u32,
+
+    /// Zoom in to hide the black borders of the transformed frames
+    #[derivative(Default(value = "true"))]
+    pub optimal_zoom: bool,
+
+    /// Sharpen the frames after the transformation
+    #[derivative(Default(value = "true"))]
+    pub sharpen: bool,
+
+    /// CRF the stabilized frames are encoded with
+    #[derivative(Default(value = "23"))]
+    pub crf: u8,
+}
+
+impl StabilizeConfig {
+    /// Create a new stabilization config (convenience method)
+    pub fn new(input: impl Into<String>, output: impl Into<String>) -> Self {
+        Self::default()
+            .with_input(input.into())
+            .with_output(output.into())
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> Result<()> {
+        if self.input.is_empty() {
+            return Err(Error::InvalidConfig("Input path is empty".to_string()));
+        }
+
+        if self.output.is_empty() {
+            return Err(Error::InvalidConfig("Output path is empty".to_string()));
+        }
+
+        if !(1..=10).contains(&self.shakiness) {
+            return Err(Error::InvalidConfig(format!(
+                "Shakiness must be 1-10, got: {}",
+                self.shakiness
+            )));
+        }
+
+        if !(1..=15).contains(&self.accuracy) {
+            return Err(Error::InvalidConfig(format!(
+                "Accuracy must be 1-15, got: {}",
+                self.accuracy
+            )));
+        }
+
+        encode::validate_crf(self.crf)?;
+
+        Ok(())
+    }
+
+    /// Filter of the motion detection pass
+    fn build_detect_spec(&self, transforms: &Path) -> String {
+        format!(
+            "vidstabdetect=shakiness={}:accuracy={}:result='{}'",
+            self.shakiness,
+            self.accuracy,
+            escape_filter_path(transforms)
+        )
+    }
+
+    /// Filter of the transformation pass
+    fn build_transform_spec(&self, transforms: &Path) -> String {
+        let mut spec = format!(
+            "vidstabtransform=input='{}':smoothing={}:optzoom={}",
+            escape_filter_path(transforms),
+            self.smoothing,
+            u8::from(self.optimal_zoom)
+        );
+
+        if self.sharpen {
+            spec.push_str(",unsharp=5:5:0.8:3:3:0.4");
+        }
+
+        spec
+    }
+}
+
+/// Stabilize shaky video, e.g. handheld camera footage
+///
+/// The motion is detected in a first pass and written to a temporary
+/// transforms file, which is removed afterwards. Audio is copied. Requires
+/// FFmpeg built with libvidstab.
+///
+/// # Arguments
+/// * `config` - Stabilization configuration
+///
+/// # Example
+/// ```no_run
+/// use video_utils::filters::stabilize::{stabilize_video, StabilizeConfig};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = StabilizeConfig::new("camera.mp4", "camera-stable.mp4")
+///     .with_shakiness(8)
+///     .with_smoothing(20);
+/// stabilize_video(config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn stabilize_video(config: StabilizeConfig) -> Result<()> {
+    config.validate()?;
+
+    log::info!(
+        "Stabilizing video: {} -> {} (shakiness={}, smoothing={})",
+        config.input,
+        config.output,
+        config.shakiness,
+        config.smoothing
+    );
+
+    ffmpeg::init().map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    if ffmpeg::filter::find("vidstabdetect").is_none() {
+        return Err(Error::FFmpeg(
+            "Video stabilization requires FFmpeg built with libvidstab".to_string(),
+        ));
+    }
+
+    let transforms = transforms_path();
+    let result = stabilize_with(&config, &transforms);
+    _ = std::fs::remove_file(&transforms);
+    result?;
+
+    log::info!("Stabilization complete: {}", config.output);
+
+    Ok(())
+}
+
+fn stabilize_with(config: &StabilizeConfig, transforms: &Path) -> Result<()> {
+    let input = Path::new(&config.input);
+
+    log::info!("Detecting camera motion...");
+    run_video_filter(input, None, &config.build_detect_spec(transforms), config.crf)?;
+
+    log::info!("Applying stabilization...");
+    run_video_filter(
+        input,
+        Some(Path::new(&config.output)),
+        &config.build_transform_spec(transforms),
+        config.crf,
+    )
+}
+
+/// Temporary file of the detected motion, unique per call
+fn transforms_path() -> PathBuf {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    std::env::temp_dir().join(format!(
+        "video-utils-vidstab-{}-{}.trf",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Stabilize a video with the default settings
+pub fn stabilize(input: &str, output: &str) -> Result<()> {
+    stabilize_video(StabilizeConfig::new(input, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stabilize_config() {
+        let config = StabilizeConfig::new("in.mp4", "out.mp4");
+
+        assert_eq!(config.shakiness, 5);
+        assert_eq!(config.accuracy, 15);
+        assert_eq!(config.smoothing, 10);
+        assert!(config.validate().is_ok());
+
+        assert!(StabilizeConfig::default().validate().is_err());
+        assert!(config.clone().with_shakiness(0).validate().is_err());
+        assert!(config.clone().with_accuracy(16).validate().is_err());
+        assert!(config.with_crf(52).validate().is_err());
+    }
+
+    #[test]
+    fn test_stabilize_filter_spec() {
+        let transforms = Path::new("/tmp/motion.trf");
+        let config = StabilizeConfig::new("in.mp4", "out.mp4").with_shakiness(8);
+
+        assert_eq!(
+            config.build_detect_spec(transforms),
+            "vidstabdetect=shakiness=8:accuracy=15:result='/tmp/motion.trf'"
+        );
+        assert_eq!(
+            config.build_transform_spec(transforms),
+            "vidstabtransform=input='/tmp/motion.trf':smoothing=10:optzoom=1,unsharp=5:5:0.8:3:3:0.4"
+        );
+        assert_eq!(
+            config
+                .with_optimal_zoom(false)
+                .with_sharpen(false)
+                .build_transform_spec(transforms),
+            "vidstabtransform=input='/tmp/motion.trf':smoothing=10:optzoom=0"
+        );
+    }
+
+    #[test]
+    fn test_transforms_path_unique() {
+        assert_ne!(transforms_path(), transforms_path());
+    }
+}
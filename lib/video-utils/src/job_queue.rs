@@ -0,0 +1,445 @@
+//! Batch processing queue
+//!
+//! Run a list of editor/filter operations sequentially or in parallel. Every
+//! job reports progress through a callback, can be cancelled with a
+//! [`CancellationToken`], and a failing (or panicking) job doesn't stop the
+//! others.
+
+use crate::Result;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Shared flag to cancel jobs
+///
+/// Cancelling is cooperative: queued jobs are skipped, running jobs should
+/// check [`JobContext::is_cancelled`] and return early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+type Task = Box<dyn FnOnce(&JobContext) -> Result<()> + Send>;
+
+/// One operation of a job queue
+pub struct Job {
+    name: String,
+    task: Task,
+    token: CancellationToken,
+}
+
+impl Job {
+    /// Create a job from a closure that can report progress and check for cancellation
+    ///
+    /// # Example
+    /// ```no_run
+    /// use video_utils::job_queue::Job;
+    ///
+    /// let job = Job::new("export", |ctx| {
+    ///     for step in 0..10 {
+    ///         if ctx.is_cancelled() {
+    ///             break;
+    ///         }
+    ///         ctx.set_progress(step as f32 / 10.0);
+    ///     }
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn new<F>(name: impl Into<String>, task: F) -> Self
+    where
+        F: FnOnce(&JobContext) -> Result<()> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            task: Box::new(task),
+            token: CancellationToken::new(),
+        }
+    }
+
+    /// Create a job from an operation without progress reporting, e.g. `trim_video`
+    pub fn from_fn<F>(name: impl Into<String>, operation: F) -> Self
+    where
+        F: FnOnce() -> Result<()> + Send + 'static,
+    {
+        Self::new(name, move |_| operation())
+    }
+
+    /// Cancel this job with its own token, in addition to the queue token
+    pub fn with_cancel_token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl std::fmt::Debug for Job {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Job").field("name", &self.name).finish()
+    }
+}
+
+/// Handle passed to a running job
+pub struct JobContext {
+    index: usize,
+    job_token: CancellationToken,
+    queue_token: CancellationToken,
+    events: Sender<(JobEvent, Duration)>,
+}
+
+impl JobContext {
+    /// Index of the job in the queue
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Report the progress of the job in [0.0, 1.0]
+    pub fn set_progress(&self, progress: f32) {
+        let event = JobEvent::Progress {
+            index: self.index,
+            progress: progress.clamp(0.0, 1.0),
+        };
+        _ = self.events.send((event, Duration::ZERO));
+    }
+
+    /// Whether the job or the whole queue was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.job_token.is_cancelled() || self.queue_token.is_cancelled()
+    }
+}
+
+/// Final state of a job
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    Succeeded,
+    Failed(String),
+    Cancelled,
+}
+
+/// Event of a running queue, `index` is the position of the job in the queue
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobEvent {
+    Started { index: usize, name: String },
+    Progress { index: usize, progress: f32 },
+    Finished { index: usize, status: JobStatus },
+}
+
+/// Result of one job
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobReport {
+    pub name: String,
+    pub status: JobStatus,
+    pub elapsed: Duration,
+}
+
+/// Queue of jobs run by a pool of worker threads
+///
+/// # Example
+/// ```no_run
+/// use video_utils::job_queue::{Job, JobEvent, JobQueue};
+/// # fn export_segment(index: usize) -> video_utils::Result<()> { Ok(()) }
+///
+/// let mut queue = JobQueue::new().with_parallelism(2);
+///
+/// for index in 0..3 {
+///     // e.g. `trim_video` with the range of each segment
+///     queue.push(Job::from_fn(format!("segment {index}"), move || export_segment(index)));
+/// }
+///
+/// let token = queue.cancel_token();
+/// let handle = queue.spawn(|event| {
+///     if let JobEvent::Progress { index, progress } = event {
+///         println!("job {index}: {:.0}%", progress * 100.0);
+///     }
+/// });
+///
+/// // token.cancel() stops the remaining jobs
+/// let reports = handle.join().unwrap();
+/// # let _ = (token, reports);
+/// ```
+#[derive(Debug)]
+pub struct JobQueue {
+    jobs: Vec<Job>,
+    parallelism: usize,
+    token: CancellationToken,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobQueue {
+    /// Create an empty queue that runs jobs one after another
+    pub fn new() -> Self {
+        Self {
+            jobs: vec![],
+            parallelism: 1,
+            token: CancellationToken::new(),
+        }
+    }
+
+    /// Number of jobs run at the same time (at least 1)
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    pub fn with_job(mut self, job: Job) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    pub fn push(&mut self, job: Job) {
+        self.jobs.push(job);
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Token that cancels every job of the queue
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Run all jobs and block until they are done
+    ///
+    /// `on_event` is called on the current thread. Returns one report per job
+    /// in queue order.
+    pub fn run<F: FnMut(JobEvent)>(self, mut on_event: F) -> Vec<JobReport> {
+        let (tx, rx) = channel();
+        let names = self.jobs.iter().map(|job| job.name.clone()).collect::<Vec<_>>();
+        let workers = self.parallelism.min(self.jobs.len());
+        let queue_token = self.token;
+        let pending = Arc::new(Mutex::new(self.jobs.into_iter().enumerate().collect::<VecDeque<_>>()));
+
+        log::info!("Running {} jobs with {} workers", names.len(), workers);
+
+        let handles = (0..workers)
+            .map(|_| {
+                let pending = pending.clone();
+                let queue_token = queue_token.clone();
+                let tx = tx.clone();
+                thread::spawn(move || worker(pending, queue_token, tx))
+            })
+            .collect::<Vec<_>>();
+
+        drop(tx);
+
+        let mut reports = names
+            .iter()
+            .map(|name| JobReport {
+                name: name.clone(),
+                status: JobStatus::Cancelled,
+                elapsed: Duration::ZERO,
+            })
+            .collect::<Vec<_>>();
+
+        // The channel closes once every worker is done
+        for (event, elapsed) in rx {
+            if let JobEvent::Finished { index, ref status } = event {
+                reports[index].status = status.clone();
+                reports[index].elapsed = elapsed;
+            }
+
+            on_event(event);
+        }
+
+        for handle in handles {
+            _ = handle.join();
+        }
+
+        reports
+    }
+
+    /// Run all jobs on a background thread, see [`JobQueue::run`]
+    pub fn spawn<F>(self, on_event: F) -> JoinHandle<Vec<JobReport>>
+    where
+        F: FnMut(JobEvent) + Send + 'static,
+    {
+        thread::spawn(move || self.run(on_event))
+    }
+}
+
+fn worker(
+    pending: Arc<Mutex<VecDeque<(usize, Job)>>>,
+    queue_token: CancellationToken,
+    tx: Sender<(JobEvent, Duration)>,
+) {
+    loop {
+        let Some((index, job)) = pending.lock().unwrap().pop_front() else {
+            break;
+        };
+
+        let send = |event| _ = tx.send((event, Duration::ZERO));
+
+        if job.token.is_cancelled() || queue_token.is_cancelled() {
+            send(JobEvent::Finished {
+                index,
+                status: JobStatus::Cancelled,
+            });
+            continue;
+        }
+
+        log::debug!("Job {} started: {}", index, job.name);
+        send(JobEvent::Started {
+            index,
+            name: job.name.clone(),
+        });
+
+        let ctx = JobContext {
+            index,
+            job_token: job.token.clone(),
+            queue_token: queue_token.clone(),
+            events: tx.clone(),
+        };
+
+        let started = Instant::now();
+        let task = job.task;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| task(&ctx)));
+        let cancelled = ctx.is_cancelled();
+
+        let status = match result {
+            Ok(_) if cancelled => JobStatus::Cancelled,
+            Ok(Ok(())) => JobStatus::Succeeded,
+            Ok(Err(e)) => JobStatus::Failed(e.to_string()),
+            Err(e) => JobStatus::Failed(format!("Job panicked: {}", panic_message(&e))),
+        };
+
+        match status {
+            JobStatus::Failed(ref e) => log::warn!("Job {} failed: {}: {}", index, job.name, e),
+            _ => log::debug!("Job {} finished: {} ({:?})", index, job.name, status),
+        }
+
+        _ = tx.send((JobEvent::Finished { index, status }, started.elapsed()));
+    }
+}
+
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::sync::atomic::AtomicUsize;
+
+    fn io_error(message: &str) -> Error {
+        Error::IO(std::io::Error::other(message.to_string()))
+    }
+
+    #[test]
+    fn test_failure_isolation() {
+        let queue = JobQueue::new()
+            .with_job(Job::from_fn("ok", || Ok(())))
+            .with_job(Job::from_fn("error", || Err(io_error("boom"))))
+            .with_job(Job::from_fn("panic", || panic!("oops")))
+            .with_job(Job::from_fn("ok again", || Ok(())));
+
+        let reports = queue.run(|_| {});
+
+        assert_eq!(reports.len(), 4);
+        assert_eq!(reports[0].status, JobStatus::Succeeded);
+        assert!(matches!(reports[1].status, JobStatus::Failed(ref e) if e.contains("boom")));
+        assert!(matches!(reports[2].status, JobStatus::Failed(ref e) if e.contains("oops")));
+        assert_eq!(reports[3].status, JobStatus::Succeeded);
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let queue = JobQueue::new().with_parallelism(2).with_job(Job::new("steps", |ctx| {
+            ctx.set_progress(0.5);
+            ctx.set_progress(2.0);
+            Ok(())
+        }));
+
+        let mut events = vec![];
+        queue.run(|event| events.push(event));
+
+        assert_eq!(
+            events,
+            vec![
+                JobEvent::Started { index: 0, name: "steps".to_string() },
+                JobEvent::Progress { index: 0, progress: 0.5 },
+                JobEvent::Progress { index: 0, progress: 1.0 },
+                JobEvent::Finished { index: 0, status: JobStatus::Succeeded },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parallel_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut queue = JobQueue::new().with_parallelism(4);
+
+        for index in 0..16 {
+            let counter = counter.clone();
+            queue.push(Job::from_fn(format!("job {}", index), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }));
+        }
+
+        let reports = queue.run(|_| {});
+
+        assert_eq!(counter.load(Ordering::SeqCst), 16);
+        assert!(reports.iter().all(|report| report.status == JobStatus::Succeeded));
+        assert_eq!(reports[5].name, "job 5");
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let job_token = CancellationToken::new();
+        job_token.cancel();
+
+        let queue = JobQueue::new();
+        let queue_token = queue.cancel_token();
+
+        let queue = queue
+            .with_job(Job::from_fn("skipped", || Ok(())).with_cancel_token(job_token))
+            .with_job(Job::from_fn("runs", || Ok(())))
+            .with_job(Job::new("cancel all", move |ctx| {
+                assert!(!ctx.is_cancelled());
+                queue_token.cancel();
+                assert!(ctx.is_cancelled());
+                Ok(())
+            }))
+            .with_job(Job::from_fn("never runs", || panic!("should be skipped")));
+
+        let reports = queue.run(|_| {});
+
+        assert_eq!(reports[0].status, JobStatus::Cancelled);
+        assert_eq!(reports[1].status, JobStatus::Succeeded);
+        assert_eq!(reports[2].status, JobStatus::Cancelled);
+        assert_eq!(reports[3].status, JobStatus::Cancelled);
+    }
+}
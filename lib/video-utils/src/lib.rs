@@ -1,8 +1,14 @@
 pub mod subtitle;
 
+// 批量任务队列
+pub mod job_queue;
+
 #[cfg(feature = "ffmpeg")]
 pub mod subtitle_burn;
 
+#[cfg(feature = "ffmpeg")]
+pub mod subtitle_mux;
+
 #[cfg(feature = "ffmpeg")]
 pub mod audio_process;
 
@@ -15,6 +21,13 @@ pub mod audio_extraction;
 #[cfg(feature = "ffmpeg")]
 pub mod video_frame;
 
+#[cfg(feature = "ffmpeg")]
+pub mod waveform;
+
+// GIF/WebP 动画导出
+#[cfg(feature = "ffmpeg")]
+pub mod animation;
+
 // MP4 封装器
 #[cfg(feature = "ffmpeg")]
 pub mod mp4_muxer;
@@ -23,6 +36,10 @@ pub mod mp4_muxer;
 #[cfg(feature = "ffmpeg")]
 pub mod mp4_encoder;
 
+// 编辑器、滤镜和时间线共用的编码函数
+#[cfg(feature = "ffmpeg")]
+mod encode;
+
 // 视频编辑操作
 #[cfg(feature = "ffmpeg")]
 pub mod editor;
@@ -31,27 +48,47 @@ pub mod editor;
 #[cfg(feature = "ffmpeg")]
 pub mod filters;
 
+// 时间线多片段渲染
+#[cfg(feature = "ffmpeg")]
+pub mod timeline;
+
+pub use job_queue::{CancellationToken, Job, JobContext, JobEvent, JobQueue, JobReport, JobStatus};
+
 #[cfg(feature = "ffmpeg")]
 pub use subtitle_burn::{SubtitleBurnConfig, SubtitleStyle, add_subtitles, rgb_to_ass_color};
 
 #[cfg(feature = "ffmpeg")]
-pub use audio_process::{AudioProcessConfig, LoudnormConfig, process_audio};
+pub use subtitle_mux::mux_subtitles;
 
 #[cfg(feature = "ffmpeg")]
-pub use metadata::{get_metadata, VideoMetadata};
+pub use audio_process::{
+    AudioProcessConfig, LoudnessMeasurement, LoudnessPreset, LoudnormConfig, measure_loudness,
+    normalize_loudness, process_audio,
+};
+
+#[cfg(feature = "ffmpeg")]
+pub use metadata::{analyze, get_metadata, VideoAnalysis, VideoMetadata};
 
 #[cfg(feature = "ffmpeg")]
-pub use audio_extraction::{extract_audio_interval, extract_all_audio, AudioSamples};
+pub use audio_extraction::{extract_audio_interval, extract_all_audio, decode_audio_mono, AudioSamples};
+
+#[cfg(feature = "ffmpeg")]
+pub use waveform::{extract_waveform, Waveform, WaveformConfig};
 
 #[cfg(feature = "ffmpeg")]
 pub use video_frame::{
     extract_all_frames,
+    generate_thumbnails,
     extract_frame_at_time,
     extract_frames_interval,
     save_frame_as_image,
     VideoFrame,
 };
 
+// 动画导出
+#[cfg(feature = "ffmpeg")]
+pub use animation::{export_gif, export_webp, AnimationExportConfig, DitherMode};
+
 // MP4 封装器导出
 #[cfg(feature = "ffmpeg")]
 pub use mp4_muxer::{MP4Muxer, MP4MuxerConfig, AACConfig as MuxerAACConfig, FrameData as MuxerFrameData, AudioData as MuxerAudioData};
@@ -66,10 +103,13 @@ pub use mp4_encoder::{
 // 编辑操作导出
 #[cfg(feature = "ffmpeg")]
 pub use editor::{
-    trim_video, TrimConfig, extract_segment,
+    trim_video, TrimConfig, extract_segment, lossless_trim, CutMode,
     concat_videos, ConcatConfig, concat_videos_simple,
     split_video, SplitConfig, split_equal, split_by_duration, split_at_points,
     change_speed, SpeedConfig, speed_up, slow_down, reverse_video, SpeedFactor,
+    compose_pip, PipConfig, PipPosition,
+    replace_audio, mix_audio_tracks,
+    detect_silence, auto_cut_silence, SilenceConfig, SilenceAction, AutoCutConfig,
 };
 
 // 滤镜导出
@@ -83,6 +123,16 @@ pub use filters::{
     adjust_color, ColorAdjustConfig, adjust_brightness, adjust_contrast, adjust_saturation,
     crossfade_videos, CrossfadeConfig,
     text_overlay, TextOverlayConfig, TextPosition, TextAlignment, add_watermark, add_title,
+    stabilize_video, StabilizeConfig, stabilize,
+    denoise_video, DenoiseConfig, DenoiseMethod, DenoiseStrength, denoise,
+    deinterlace_video, DeinterlaceConfig, DeinterlaceMethod, DeinterlaceMode, FieldParity, deinterlace,
+};
+
+// 时间线导出
+#[cfg(feature = "ffmpeg")]
+pub use timeline::{
+    render as render_timeline, AudioTrack as TimelineAudioTrack, Clip, Overlay, Project, Transition,
+    TransitionKind,
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -95,6 +145,9 @@ pub enum Error {
     #[error("Parse Error {0}")]
     Parse(#[from] chrono::ParseError),
 
+    #[error("Invalid timestamp: {0}")]
+    Timestamp(String),
+
     #[cfg(feature = "ffmpeg")]
     #[error("FFmpeg Error: {0}")]
     FFmpeg(String),
@@ -79,6 +79,150 @@ pub fn get_metadata<P: AsRef<Path>>(path: P) -> Result<VideoMetadata> {
     })
 }
 
+/// Detailed analysis of a video file
+#[derive(Debug, Clone)]
+pub struct VideoAnalysis {
+    /// Basic metadata
+    pub metadata: VideoMetadata,
+
+    /// Codec name of the video stream (e.g., "h264")
+    pub codec: Option<String>,
+
+    /// Codec profile (e.g., "High")
+    pub profile: Option<String>,
+
+    /// Raw codec level as stored in the file, see [`VideoAnalysis::level_name`]
+    pub level: Option<i32>,
+
+    /// Timestamps of the keyframes in seconds, sorted
+    pub keyframes: Vec<f64>,
+
+    /// Bitrate of every second of the file in bits per second (all streams)
+    pub bitrate_curve: Vec<u64>,
+}
+
+impl VideoAnalysis {
+    /// Human readable codec level (e.g., "4.1")
+    pub fn level_name(&self) -> Option<String> {
+        let level = self.level?;
+
+        Some(match self.codec.as_deref() {
+            Some("h264") => format!("{}.{}", level / 10, level % 10),
+            // HEVC stores 30 times the level
+            Some("hevc") => format!("{}.{}", level / 30, level % 30 / 3),
+            _ => level.to_string(),
+        })
+    }
+
+    /// Last keyframe at or before `secs`, the position a seek without decoding lands on
+    pub fn keyframe_before(&self, secs: f64) -> Option<f64> {
+        let index = self.keyframes.partition_point(|ts| *ts <= secs);
+        index.checked_sub(1).map(|index| self.keyframes[index])
+    }
+}
+
+/// Analyze a video file: keyframe index, bitrate over time and codec profile/level
+///
+/// Only packet headers are read, no frame is decoded.
+///
+/// # Example
+/// ```no_run
+/// use video_utils::metadata::analyze;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let analysis = analyze("recording.mp4")?;
+/// println!("{} keyframes, level {:?}", analysis.keyframes.len(), analysis.level_name());
+/// # Ok(())
+/// # }
+/// ```
+pub fn analyze<P: AsRef<Path>>(path: P) -> Result<VideoAnalysis> {
+    let metadata = get_metadata(path.as_ref())?;
+
+    let mut input_ctx = ffmpeg::format::input(&metadata.path)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input file: {}", e)))?;
+
+    let video_stream = input_ctx.streams().best(ffmpeg::media::Type::Video).map(|stream| {
+        let parameters = stream.parameters();
+        let (profile, level) = unsafe {
+            let codecpar = parameters.as_ptr();
+            ((*codecpar).profile, (*codecpar).level)
+        };
+
+        let profile = unsafe {
+            let name = ffmpeg::ffi::avcodec_profile_name(parameters.id().into(), profile);
+            (!name.is_null()).then(|| std::ffi::CStr::from_ptr(name).to_string_lossy().into_owned())
+        };
+
+        (
+            stream.index(),
+            parameters.id().name().to_string(),
+            profile,
+            (level > 0).then_some(level),
+        )
+    });
+
+    // Timestamps are relative to the start of the file
+    let start_time = unsafe { (*input_ctx.as_ptr()).start_time };
+    let start_secs = if start_time == ffmpeg::ffi::AV_NOPTS_VALUE {
+        0.0
+    } else {
+        start_time as f64 / 1_000_000.0
+    };
+
+    let mut keyframes = vec![];
+    let mut bitrate_curve = vec![];
+
+    for (stream, packet) in input_ctx.packets() {
+        let Some(ts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+
+        let secs = ts as f64 * f64::from(stream.time_base()) - start_secs;
+        add_to_bitrate_curve(&mut bitrate_curve, secs, packet.size());
+
+        if packet.is_key()
+            && video_stream
+                .as_ref()
+                .is_some_and(|(index, ..)| *index == stream.index())
+        {
+            keyframes.push(secs.max(0.0));
+        }
+    }
+
+    keyframes.sort_by(f64::total_cmp);
+    keyframes.dedup();
+
+    let (codec, profile, level) = match video_stream {
+        Some((_, codec, profile, level)) => (Some(codec), profile, level),
+        None => (None, None, None),
+    };
+
+    log::debug!(
+        "Analyzed {}: {} keyframes, {} seconds of bitrate",
+        metadata.path,
+        keyframes.len(),
+        bitrate_curve.len()
+    );
+
+    Ok(VideoAnalysis {
+        metadata,
+        codec,
+        profile,
+        level,
+        keyframes,
+        bitrate_curve,
+    })
+}
+
+/// Add the bits of a packet to the second it belongs to
+fn add_to_bitrate_curve(curve: &mut Vec<u64>, secs: f64, size: usize) {
+    let second = secs.max(0.0) as usize;
+    if curve.len() <= second {
+        curve.resize(second + 1, 0);
+    }
+    curve[second] += size as u64 * 8;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +233,49 @@ mod tests {
         // let metadata = get_metadata("test.mp4").unwrap();
         // assert!(metadata.duration > 0.0);
     }
+
+    fn analysis(codec: &str, level: i32, keyframes: Vec<f64>) -> VideoAnalysis {
+        VideoAnalysis {
+            metadata: VideoMetadata {
+                path: "test.mp4".to_string(),
+                format_name: "mp4".to_string(),
+                duration: 10.0,
+                bitrate: 0,
+                size: 0,
+                video_streams_count: 1,
+                audio_streams_count: 0,
+            },
+            codec: Some(codec.to_string()),
+            profile: None,
+            level: Some(level),
+            keyframes,
+            bitrate_curve: vec![],
+        }
+    }
+
+    #[test]
+    fn test_level_name() {
+        assert_eq!(analysis("h264", 41, vec![]).level_name().as_deref(), Some("4.1"));
+        assert_eq!(analysis("hevc", 123, vec![]).level_name().as_deref(), Some("4.1"));
+        assert_eq!(analysis("av1", 8, vec![]).level_name().as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn test_keyframe_before() {
+        let analysis = analysis("h264", 40, vec![0.0, 2.0, 4.0]);
+        assert_eq!(analysis.keyframe_before(0.0), Some(0.0));
+        assert_eq!(analysis.keyframe_before(3.9), Some(2.0));
+        assert_eq!(analysis.keyframe_before(10.0), Some(4.0));
+        assert_eq!(analysis.keyframe_before(-1.0), None);
+    }
+
+    #[test]
+    fn test_bitrate_curve() {
+        let mut curve = vec![];
+        add_to_bitrate_curve(&mut curve, 0.2, 100);
+        add_to_bitrate_curve(&mut curve, 0.9, 50);
+        add_to_bitrate_curve(&mut curve, 2.5, 10);
+        add_to_bitrate_curve(&mut curve, -0.1, 1);
+        assert_eq!(curve, vec![1208, 0, 80]);
+    }
 }
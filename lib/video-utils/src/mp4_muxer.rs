@@ -7,6 +7,8 @@ use std::time::Duration;
 use image::{ImageBuffer, Rgb};
 use video_encoder::{VideoEncoder, VideoEncoderConfig, EncodedFrame};
 
+pub use mp4m::{Mp4Metadata, Mp4MetadataBuilder};
+
 /// 视频帧数据 (RGB格式)
 #[derive(Debug, Clone)]
 pub struct FrameData {
@@ -63,6 +65,8 @@ pub struct MP4MuxerConfig {
     pub frame_rate: u32,
     /// AAC 编码配置
     pub aac: AACConfig,
+    /// 容器元数据, 与 mp4m 的录制输出相同
+    pub metadata: Mp4Metadata,
 }
 
 /// MP4 封装器
@@ -78,7 +82,7 @@ impl MP4Muxer {
     /// # Example
     ///
     /// ```no_run
-    /// use video_utils::mp4_muxer::{MP4Muxer, MP4MuxerConfig, AACConfig};
+    /// use video_utils::mp4_muxer::{MP4Muxer, MP4MuxerConfig, AACConfig, Mp4Metadata};
     /// use std::path::PathBuf;
     ///
     /// let config = MP4MuxerConfig {
@@ -89,6 +93,10 @@ impl MP4Muxer {
     ///         sample_rate: 48_000,
     ///         channels: 2,
     ///     },
+    ///     metadata: Mp4Metadata {
+    ///         title: Some("Demo".to_string()),
+    ///         ..Default::default()
+    ///     },
     /// };
     ///
     /// let (muxer, video_tx, audio_tx) = MP4Muxer::start(config).unwrap();
@@ -180,6 +188,15 @@ fn mux_mp4(
     let audio_stream_index = audio_stream.index();
     let audio_time_base = audio_stream.time_base();
 
+    // 写入元数据, mp4 muxer 会将其写入 udta/ilst
+    if !config.metadata.is_empty() {
+        let mut metadata = ffmpeg::Dictionary::new();
+        for (key, value) in config.metadata.tags() {
+            metadata.set(key, &value);
+        }
+        output.set_metadata(metadata);
+    }
+
     // 写入头部
     output.write_header()
         .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
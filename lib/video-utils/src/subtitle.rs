@@ -1,12 +1,14 @@
-use crate::Result;
+use crate::{Error, Result};
 use chinese_number::{ChineseCountMethod, ChineseToNumber};
-use chrono::{NaiveTime, Timelike};
+use cutil::time::{TimestampStyle, format_duration, parse_timestamp};
+use derivative::Derivative;
+use derive_setters::Setters;
 use std::{fs, path::Path};
 use unicode_segmentation::UnicodeSegmentation;
 
 type SubtitleSplitResult = Option<((u64, u64, String), (u64, u64, String))>;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Subtitle {
     pub index: u32,
     pub start_timestamp: u64,
@@ -16,30 +18,29 @@ pub struct Subtitle {
 
 #[inline]
 pub fn ms_to_srt_timestamp(milliseconds: u64) -> String {
-    ms_to_timestamp(milliseconds, ",")
+    format_duration(milliseconds, TimestampStyle::Srt)
 }
 
-fn ms_to_timestamp(milliseconds: u64, ms_sep: &str) -> String {
-    let total_seconds = milliseconds / 1000;
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds % 3600) / 60;
-    let seconds = total_seconds % 60;
-    let millis = milliseconds % 1000;
+/// Only accepts the `HH:MM:SS,mmm` form of SRT files
+pub fn srt_timestamp_to_ms(timestamp: &str) -> Result<u64> {
+    let is_srt = timestamp.len() == 12
+        && timestamp.char_indices().all(|(index, c)| match index {
+            2 | 5 => c == ':',
+            8 => c == ',',
+            _ => c.is_ascii_digit(),
+        });
+
+    if !is_srt {
+        return Err(Error::Timestamp(format!(
+            "`{timestamp}` isn't a `HH:MM:SS,mmm` timestamp"
+        )));
+    }
 
-    format!(
-        "{:02}:{:02}:{:02}{ms_sep}{:03}",
-        hours, minutes, seconds, millis
-    )
+    timestamp_to_ms(timestamp)
 }
 
-pub fn srt_timestamp_to_ms(timestamp: &str) -> Result<u64> {
-    let time = NaiveTime::parse_from_str(timestamp, "%H:%M:%S,%f")?;
-
-    Ok((time.hour() as u64 * 3600000)
-        + (time.minute() as u64 * 60000)
-        + (time.second() as u64 * 1000)
-        // This's not a bug，chrono would parse ',%f' into nanosecond field
-        + (time.nanosecond() as u64))
+fn timestamp_to_ms(timestamp: &str) -> Result<u64> {
+    parse_timestamp(timestamp).map_err(|e| Error::Timestamp(e.to_string()))
 }
 
 pub fn valid_srt_timestamp(timestamp: &str) -> bool {
@@ -67,6 +68,401 @@ pub fn save_as_srt(subtitle: &[Subtitle], path: impl AsRef<Path>) -> Result<()>
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    /// Detect the format from the file extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            _ => None,
+        }
+    }
+}
+
+/// Style of the `Default` style line of ASS files
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+pub struct AssStyle {
+    #[derivative(Default(value = "\"Arial\".to_string()"))]
+    pub font_name: String,
+
+    #[derivative(Default(value = "24"))]
+    pub font_size: u32,
+
+    /// Colors in &HAABBGGRR format
+    #[derivative(Default(value = "\"&H00FFFFFF\".to_string()"))]
+    pub primary_color: String,
+
+    #[derivative(Default(value = "\"&H00000000\".to_string()"))]
+    pub outline_color: String,
+
+    #[derivative(Default(value = "\"&H80000000\".to_string()"))]
+    pub background_color: String,
+
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+
+    /// 1=outline+shadow, 3=opaque box
+    #[derivative(Default(value = "1"))]
+    pub border_style: u32,
+
+    #[derivative(Default(value = "2"))]
+    pub outline_width: u32,
+
+    pub shadow: u32,
+
+    /// Numpad alignment: 1-9 (1=bottom-left, 2=bottom-center, 3=bottom-right, etc.)
+    #[derivative(Default(value = "2"))]
+    pub alignment: u32,
+
+    #[derivative(Default(value = "10"))]
+    pub margin_left: u32,
+
+    #[derivative(Default(value = "10"))]
+    pub margin_right: u32,
+
+    #[derivative(Default(value = "30"))]
+    pub margin_vertical: u32,
+}
+
+impl AssStyle {
+    fn to_style_line(&self) -> String {
+        // ASS uses -1 for true
+        let flag = |value: bool| if value { -1 } else { 0 };
+
+        format!(
+            "Style: Default,{},{},{},&H000000FF,{},{},{},{},{},0,100,100,0,0,{},{},{},{},{},{},{},1",
+            self.font_name,
+            self.font_size,
+            self.primary_color,
+            self.outline_color,
+            self.background_color,
+            flag(self.bold),
+            flag(self.italic),
+            flag(self.underline),
+            self.border_style,
+            self.outline_width,
+            self.shadow,
+            self.alignment,
+            self.margin_left,
+            self.margin_right,
+            self.margin_vertical
+        )
+    }
+}
+
+#[inline]
+pub fn ms_to_vtt_timestamp(milliseconds: u64) -> String {
+    format_duration(milliseconds, TimestampStyle::Vtt)
+}
+
+#[inline]
+pub fn ms_to_ass_timestamp(milliseconds: u64) -> String {
+    format_duration(milliseconds, TimestampStyle::Ass)
+}
+
+/// Accepts both `HH:MM:SS.mmm` and the short `MM:SS.mmm` form
+#[inline]
+pub fn vtt_timestamp_to_ms(timestamp: &str) -> Result<u64> {
+    timestamp_to_ms(timestamp)
+}
+
+/// `H:MM:SS.cc` with centiseconds
+#[inline]
+pub fn ass_timestamp_to_ms(timestamp: &str) -> Result<u64> {
+    timestamp_to_ms(timestamp)
+}
+
+pub fn subtitle_to_vtt(subtitle: &Subtitle) -> String {
+    format!(
+        "{}\n{} --> {}\n{}",
+        subtitle.index,
+        ms_to_vtt_timestamp(subtitle.start_timestamp),
+        ms_to_vtt_timestamp(subtitle.end_timestamp),
+        subtitle.text
+    )
+}
+
+pub fn subtitle_to_ass(subtitle: &Subtitle) -> String {
+    format!(
+        "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+        ms_to_ass_timestamp(subtitle.start_timestamp),
+        ms_to_ass_timestamp(subtitle.end_timestamp),
+        html_to_ass_text(&subtitle.text)
+    )
+}
+
+pub fn save_as_vtt(subtitle: &[Subtitle], path: impl AsRef<Path>) -> Result<()> {
+    let contents = subtitle
+        .iter()
+        .map(|item| format!("{}\n\n", subtitle_to_vtt(item)))
+        .collect::<String>();
+
+    fs::write(path.as_ref(), format!("WEBVTT\n\n{contents}"))?;
+
+    Ok(())
+}
+
+/// `[Script Info]`, `[V4+ Styles]` and the `[Events]` format line of an ASS file
+pub fn ass_header(style: &AssStyle) -> String {
+    let mut header = String::from(
+        "[Script Info]\nScriptType: v4.00+\nWrapStyle: 0\nScaledBorderAndShadow: yes\n\n[V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+         Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, \
+         Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+    );
+
+    header.push_str(&style.to_style_line());
+    header.push_str(
+        "\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+
+    header
+}
+
+pub fn save_as_ass(subtitle: &[Subtitle], style: &AssStyle, path: impl AsRef<Path>) -> Result<()> {
+    let mut contents = ass_header(style);
+
+    for item in subtitle {
+        contents.push_str(&subtitle_to_ass(item));
+        contents.push('\n');
+    }
+
+    fs::write(path.as_ref(), contents)?;
+
+    Ok(())
+}
+
+/// Save in the format given by the file extension, SRT if unknown
+pub fn save_subtitles(subtitle: &[Subtitle], style: &AssStyle, path: impl AsRef<Path>) -> Result<()> {
+    match SubtitleFormat::from_path(&path) {
+        Some(SubtitleFormat::Vtt) => save_as_vtt(subtitle, path),
+        Some(SubtitleFormat::Ass) => save_as_ass(subtitle, style, path),
+        _ => save_as_srt(subtitle, path),
+    }
+}
+
+/// Malformed cues are skipped
+pub fn parse_srt(contents: &str) -> Result<Vec<Subtitle>> {
+    parse_cues(contents, srt_timestamp_to_ms)
+}
+
+/// Cue settings, `NOTE`, `STYLE` and `REGION` blocks are ignored and
+/// tags other than `<i>`, `<b>` and `<u>` are stripped
+pub fn parse_vtt(contents: &str) -> Result<Vec<Subtitle>> {
+    let mut subtitles = parse_cues(contents, vtt_timestamp_to_ms)?;
+
+    for item in subtitles.iter_mut() {
+        item.text = strip_tags(&item.text, &["i", "b", "u"]);
+    }
+
+    Ok(subtitles)
+}
+
+/// Only the `Dialogue` lines of the `[Events]` section are read, override tags
+/// are converted to `<i>`, `<b>` and `<u>` or dropped
+pub fn parse_ass(contents: &str) -> Result<Vec<Subtitle>> {
+    let mut subtitles = vec![];
+    let mut in_events = false;
+    let mut fields: Vec<String> = vec![];
+
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+
+        if !in_events {
+            continue;
+        }
+
+        if let Some(format) = line.strip_prefix("Format:") {
+            fields = format.split(',').map(|field| field.trim().to_lowercase()).collect();
+            continue;
+        }
+
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        // Text is the last field and may contain commas
+        let values = dialogue.splitn(fields.len(), ',').collect::<Vec<_>>();
+        let value = |name: &str| {
+            fields
+                .iter()
+                .position(|field| field == name)
+                .and_then(|index| values.get(index))
+                .map(|value| value.trim())
+        };
+
+        let (Some(start), Some(end), Some(text)) = (value("start"), value("end"), value("text"))
+        else {
+            continue;
+        };
+
+        subtitles.push(Subtitle {
+            index: subtitles.len() as u32 + 1,
+            start_timestamp: ass_timestamp_to_ms(start)?,
+            end_timestamp: ass_timestamp_to_ms(end)?,
+            text: ass_to_html_text(text),
+        });
+    }
+
+    Ok(subtitles)
+}
+
+/// Load a subtitle file, the format is given by the file extension
+pub fn load_subtitles(path: impl AsRef<Path>) -> Result<Vec<Subtitle>> {
+    let contents = fs::read_to_string(path.as_ref())?;
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    match SubtitleFormat::from_path(&path) {
+        Some(SubtitleFormat::Vtt) => parse_vtt(contents),
+        Some(SubtitleFormat::Ass) => parse_ass(contents),
+        _ => parse_srt(contents),
+    }
+}
+
+/// Convert between SRT, VTT and ASS according to the file extensions,
+/// `style` is only used when writing ASS
+pub fn convert_subtitles(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    style: &AssStyle,
+) -> Result<()> {
+    let subtitles = load_subtitles(input)?;
+    save_subtitles(&subtitles, style, output)
+}
+
+/// Parse blank-line separated cues with an optional identifier line
+fn parse_cues(contents: &str, to_ms: fn(&str) -> Result<u64>) -> Result<Vec<Subtitle>> {
+    let contents = contents.replace("\r\n", "\n");
+    let mut subtitles = vec![];
+
+    for block in contents.split("\n\n") {
+        let lines = block.lines().map(str::trim_end).collect::<Vec<_>>();
+
+        let Some(timing_index) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+
+        let Some((start, rest)) = lines[timing_index].split_once("-->") else {
+            continue;
+        };
+
+        // Drop the VTT cue settings after the end time
+        let end = rest.split_whitespace().next().unwrap_or_default();
+
+        let index = timing_index
+            .checked_sub(1)
+            .and_then(|i| lines[i].trim().parse::<u32>().ok())
+            .unwrap_or(subtitles.len() as u32 + 1);
+
+        subtitles.push(Subtitle {
+            index,
+            start_timestamp: to_ms(start.trim())?,
+            end_timestamp: to_ms(end)?,
+            text: lines[timing_index + 1..].join("\n").trim().to_string(),
+        });
+    }
+
+    Ok(subtitles)
+}
+
+/// Remove `<...>` tags except the listed ones
+fn strip_tags(text: &str, keep: &[&str]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find('>') else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+
+        let tag = &rest[start..start + end + 1];
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .trim_end_matches('>')
+            .split(['.', ' '])
+            .next()
+            .unwrap_or_default();
+
+        if keep.contains(&name) {
+            result.push_str(tag);
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+pub(crate) fn html_to_ass_text(text: &str) -> String {
+    let text = strip_tags(text, &["i", "b", "u"]);
+
+    [
+        ("<i>", "{\\i1}"),
+        ("</i>", "{\\i0}"),
+        ("<b>", "{\\b1}"),
+        ("</b>", "{\\b0}"),
+        ("<u>", "{\\u1}"),
+        ("</u>", "{\\u0}"),
+        ("\n", "\\N"),
+    ]
+    .iter()
+    .fold(text, |text, (from, to)| text.replace(from, to))
+}
+
+fn ass_to_html_text(text: &str) -> String {
+    let text = [
+        ("{\\i1}", "<i>"),
+        ("{\\i0}", "</i>"),
+        ("{\\b1}", "<b>"),
+        ("{\\b0}", "</b>"),
+        ("{\\u1}", "<u>"),
+        ("{\\u0}", "</u>"),
+        ("\\N", "\n"),
+        ("\\n", "\n"),
+        ("\\h", " "),
+    ]
+    .iter()
+    .fold(text.to_string(), |text, (from, to)| text.replace(from, to));
+
+    // Drop the remaining override blocks, e.g. positioning and colors
+    let mut result = String::with_capacity(text.len());
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => result.push(c),
+            _ => {}
+        }
+    }
+
+    result
+}
+
 pub fn split_subtitle(
     start_timestamp: u64,
     end_timestamp: u64,
@@ -1,4 +1,4 @@
-use crate::{Error, Result};
+use crate::{Error, Result, subtitle::AssStyle};
 use derivative::Derivative;
 use derive_setters::Setters;
 use ffmpeg_next as ffmpeg;
@@ -136,6 +136,30 @@ impl SubtitleStyle {
     }
 }
 
+impl From<&SubtitleStyle> for AssStyle {
+    fn from(style: &SubtitleStyle) -> Self {
+        let default = AssStyle::default();
+
+        AssStyle {
+            font_name: style.font_name.clone().unwrap_or(default.font_name),
+            font_size: style.font_size,
+            primary_color: style.primary_color.clone().unwrap_or(default.primary_color),
+            outline_color: style.outline_color.clone().unwrap_or(default.outline_color),
+            background_color: style.background_color.clone().unwrap_or(default.background_color),
+            bold: style.bold.unwrap_or(0) != 0,
+            italic: style.italic.unwrap_or(0) != 0,
+            underline: style.underline.unwrap_or(0) != 0,
+            border_style: style.border_style.unwrap_or(default.border_style),
+            outline_width: style.outline_width.unwrap_or(default.outline_width),
+            shadow: default.shadow,
+            alignment: style.alignment.unwrap_or(default.alignment),
+            margin_left: style.margin_left.unwrap_or(default.margin_left),
+            margin_right: style.margin_right.unwrap_or(default.margin_right),
+            margin_vertical: style.margin_vertical.unwrap_or(default.margin_vertical),
+        }
+    }
+}
+
 /// Configuration for adding subtitles to video
 #[derive(Debug, Clone, Derivative, Setters)]
 #[derivative(Default)]
@@ -0,0 +1,305 @@
+//! Soft subtitle muxing
+//!
+//! Embed subtitles as a selectable text track instead of burning them into
+//! the picture, so viewers can toggle captions on and off. Video and audio
+//! streams are copied without re-encoding.
+
+use crate::encode;
+use crate::subtitle::{self, AssStyle, Subtitle, SubtitleFormat};
+use crate::{Error, Result};
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+
+/// Time base of the subtitle encoder, subtitle timestamps are in milliseconds
+const SUBTITLE_TIME_BASE: (i32, i32) = (1, 1000);
+
+/// Size of the buffer for one encoded subtitle packet
+const SUBTITLE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Mux a subtitle file into a video as a soft subtitle track
+///
+/// The subtitle codec is chosen from the output container: `mov_text` for
+/// MP4/MOV, SRT (or ASS for `.ass` input) for MKV and WebVTT for WebM. The
+/// subtitle file may be SRT, WebVTT or ASS.
+///
+/// # Arguments
+/// * `video` - Input video file
+/// * `subtitle` - Subtitle file (SRT, VTT or ASS)
+/// * `output` - Output video file, the extension selects the container
+///
+/// # Example
+/// ```no_run
+/// use video_utils::subtitle_mux::mux_subtitles;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// mux_subtitles("recording.mp4", "recording.srt", "recording-cc.mp4")?;
+/// mux_subtitles("recording.mp4", "recording.srt", "recording-cc.mkv")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn mux_subtitles<P, Q, R>(video: P, subtitle: Q, output: R) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    let (video, subtitle, output) = (video.as_ref(), subtitle.as_ref(), output.as_ref());
+
+    log::info!(
+        "Muxing subtitles: {} + {} -> {}",
+        video.display(),
+        subtitle.display(),
+        output.display()
+    );
+
+    let subtitle_format = SubtitleFormat::from_path(subtitle).unwrap_or(SubtitleFormat::Srt);
+    let codec_id = subtitle_codec(output, subtitle_format)?;
+
+    let mut cues = subtitle::load_subtitles(subtitle)?;
+    if cues.is_empty() {
+        return Err(Error::InvalidConfig(format!(
+            "No subtitles found in {}",
+            subtitle.display()
+        )));
+    }
+    cues.sort_by_key(|cue| cue.start_timestamp);
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&video)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", video.display(), e)))?;
+
+    let mut output_ctx = ffmpeg::format::output(&output)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    // Copy the video and audio streams, other streams are dropped
+    let mut stream_mapping = vec![None; input_ctx.nb_streams() as usize];
+    let mut input_time_bases = vec![ffmpeg::Rational::new(0, 1); input_ctx.nb_streams() as usize];
+
+    for stream in input_ctx.streams() {
+        let medium = stream.parameters().medium();
+        if medium != ffmpeg::media::Type::Video && medium != ffmpeg::media::Type::Audio {
+            continue;
+        }
+
+        stream_mapping[stream.index()] =
+            Some(encode::add_copy_stream(&mut output_ctx, stream.parameters())?);
+        input_time_bases[stream.index()] = stream.time_base();
+    }
+
+    if stream_mapping.iter().all(Option::is_none) {
+        return Err(Error::FFmpeg(format!(
+            "No video or audio stream found in {}",
+            video.display()
+        )));
+    }
+
+    // Subtitle encoder
+    let codec = ffmpeg::encoder::find(codec_id)
+        .ok_or_else(|| Error::FFmpeg(format!("{:?} encoder not found", codec_id)))?;
+
+    let mut context = ffmpeg::codec::context::Context::new_with_codec(codec);
+    context.set_time_base(SUBTITLE_TIME_BASE);
+    set_subtitle_header(&mut context, &subtitle::ass_header(&AssStyle::default()));
+
+    if output_ctx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER)
+    {
+        context.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder = context
+        .encoder()
+        .subtitle()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create subtitle encoder: {}", e)))?
+        .open_as(codec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open subtitle encoder: {}", e)))?;
+
+    let subtitle_index = {
+        let mut output_stream = output_ctx
+            .add_stream(codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add subtitle stream: {}", e)))?;
+        output_stream.set_parameters(&encoder);
+        output_stream.set_time_base(SUBTITLE_TIME_BASE);
+        output_stream.index()
+    };
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let subtitle_time_base = output_ctx.stream(subtitle_index).unwrap().time_base();
+    let target = SubtitleTarget {
+        stream_index: subtitle_index,
+        time_base: subtitle_time_base,
+    };
+
+    let mut pending = cues.iter().enumerate().peekable();
+
+    for (stream, mut packet) in input_ctx.packets() {
+        let Some(output_index) = stream_mapping[stream.index()] else {
+            continue;
+        };
+
+        let time_base = input_time_bases[stream.index()];
+        let packet_ms = packet
+            .dts()
+            .or(packet.pts())
+            .map(|ts| (ts as f64 * f64::from(time_base) * 1000.0).max(0.0) as u64)
+            .unwrap_or(0);
+
+        // Keep the subtitle packets interleaved with the copied streams
+        while let Some((read_order, cue)) = pending.next_if(|(_, cue)| cue.start_timestamp <= packet_ms) {
+            write_subtitle(&mut encoder, &mut output_ctx, &target, read_order, cue)?;
+        }
+
+        let output_time_base = output_ctx.stream(output_index).unwrap().time_base();
+        packet.set_stream(output_index);
+        packet.set_position(-1);
+        packet.rescale_ts(time_base, output_time_base);
+        packet
+            .write_interleaved(&mut output_ctx)
+            .map_err(|e| Error::FFmpeg(format!("Failed to write packet: {}", e)))?;
+    }
+
+    for (read_order, cue) in pending {
+        write_subtitle(&mut encoder, &mut output_ctx, &target, read_order, cue)?;
+    }
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    log::info!("Subtitles muxed: {} ({} cues)", output.display(), cues.len());
+
+    Ok(())
+}
+
+/// Subtitle codec for the output container
+fn subtitle_codec(output: &Path, subtitle_format: SubtitleFormat) -> Result<ffmpeg::codec::Id> {
+    let extension = output
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "mp4" | "m4v" | "mov" => Ok(ffmpeg::codec::Id::MOV_TEXT),
+        "mkv" if subtitle_format == SubtitleFormat::Ass => Ok(ffmpeg::codec::Id::ASS),
+        "mkv" => Ok(ffmpeg::codec::Id::SUBRIP),
+        "webm" => Ok(ffmpeg::codec::Id::WEBVTT),
+        _ => Err(Error::InvalidConfig(format!(
+            "Soft subtitles are not supported for {}, use MP4, MOV, MKV or WebM",
+            output.display()
+        ))),
+    }
+}
+
+/// Event line of an ASS subtitle rect, without the timing fields
+fn ass_event(read_order: usize, cue: &Subtitle) -> String {
+    format!(
+        "{},0,Default,,0,0,0,,{}",
+        read_order,
+        subtitle::html_to_ass_text(&cue.text)
+    )
+}
+
+/// The text subtitle encoders convert from ASS and require its header
+fn set_subtitle_header(context: &mut ffmpeg::codec::context::Context, header: &str) {
+    unsafe {
+        let data = ffmpeg::ffi::av_mallocz(header.len() + 1) as *mut u8;
+        std::ptr::copy_nonoverlapping(header.as_ptr(), data, header.len());
+
+        let ctx = context.as_mut_ptr();
+        (*ctx).subtitle_header = data;
+        (*ctx).subtitle_header_size = header.len() as i32;
+    }
+}
+
+/// Output stream of the subtitles
+struct SubtitleTarget {
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+}
+
+fn write_subtitle(
+    encoder: &mut ffmpeg::encoder::subtitle::Encoder,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    target: &SubtitleTarget,
+    read_order: usize,
+    cue: &Subtitle,
+) -> Result<()> {
+    let duration = cue.end_timestamp.saturating_sub(cue.start_timestamp);
+
+    let mut sub = ffmpeg::Subtitle::new();
+    sub.set_pts(Some(cue.start_timestamp as i64 * 1000)); // AV_TIME_BASE
+    sub.set_start(0);
+    sub.set_end(duration as u32);
+
+    if let ffmpeg::subtitle::RectMut::Ass(mut rect) = sub.add_rect(ffmpeg::subtitle::Type::Ass) {
+        rect.set(&ass_event(read_order, cue));
+    }
+
+    // `Encoder::encode` drops the encoded size, so call FFmpeg directly
+    let mut buffer = vec![0u8; SUBTITLE_BUFFER_SIZE];
+    let size = unsafe {
+        ffmpeg::ffi::avcodec_encode_subtitle(
+            encoder.as_mut_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len() as i32,
+            sub.as_ptr(),
+        )
+    };
+
+    if size < 0 {
+        return Err(Error::FFmpeg(format!(
+            "Failed to encode subtitle {}: {}",
+            cue.index,
+            ffmpeg::Error::from(size)
+        )));
+    }
+
+    let mut packet = ffmpeg::Packet::copy(&buffer[..size as usize]);
+    packet.set_stream(target.stream_index);
+    packet.set_pts(Some(cue.start_timestamp as i64));
+    packet.set_dts(Some(cue.start_timestamp as i64));
+    packet.set_duration(duration as i64);
+    packet.rescale_ts(SUBTITLE_TIME_BASE, target.time_base);
+    packet
+        .write_interleaved(output_ctx)
+        .map_err(|e| Error::FFmpeg(format!("Failed to write subtitle packet: {}", e)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtitle_codec() {
+        let codec = |output: &str, format| subtitle_codec(Path::new(output), format).unwrap();
+
+        assert_eq!(codec("out.mp4", SubtitleFormat::Srt), ffmpeg::codec::Id::MOV_TEXT);
+        assert_eq!(codec("out.MOV", SubtitleFormat::Ass), ffmpeg::codec::Id::MOV_TEXT);
+        assert_eq!(codec("out.mkv", SubtitleFormat::Vtt), ffmpeg::codec::Id::SUBRIP);
+        assert_eq!(codec("out.mkv", SubtitleFormat::Ass), ffmpeg::codec::Id::ASS);
+        assert_eq!(codec("out.webm", SubtitleFormat::Srt), ffmpeg::codec::Id::WEBVTT);
+        assert!(subtitle_codec(Path::new("out.avi"), SubtitleFormat::Srt).is_err());
+    }
+
+    #[test]
+    fn test_ass_event() {
+        let cue = Subtitle {
+            index: 3,
+            start_timestamp: 1000,
+            end_timestamp: 2000,
+            text: "<i>Hello</i>\nworld".to_string(),
+        };
+
+        assert_eq!(ass_event(2, &cue), "2,0,Default,,0,0,0,,{\\i1}Hello{\\i0}\\Nworld");
+    }
+}
@@ -0,0 +1,349 @@
+//! Compile a project into a single FFmpeg filter graph description
+
+use super::project::Project;
+use crate::{Error, Result};
+
+/// Sample rate of the rendered audio
+pub(crate) const OUTPUT_SAMPLE_RATE: u32 = 48000;
+
+/// Probed properties of a source file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SourceInfo {
+    /// Duration in seconds
+    pub duration: f64,
+    pub has_audio: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InputKind {
+    Video,
+    Audio,
+}
+
+/// Decoded stream feeding one buffer source of the filter graph
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GraphInput {
+    /// Name of the buffer source, also the label in the filter spec
+    pub name: String,
+    pub path: String,
+    pub kind: InputKind,
+    /// First used source time in seconds
+    pub in_point: f64,
+    /// Source time in seconds after which decoding can stop
+    pub out_point: f64,
+    /// Timeline time of `in_point` in seconds
+    pub timeline_start: f64,
+}
+
+/// Filter graph of a project, outputs are `[vout]` and `[aout]`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FilterPlan {
+    pub inputs: Vec<GraphInput>,
+    pub spec: String,
+    /// Length of the rendered timeline in seconds
+    pub duration: f64,
+}
+
+/// Compile `project` into a filter graph, `clips`, `overlays` and `audio_tracks`
+/// hold the probed info of the sources in project order
+pub(crate) fn compile(
+    project: &Project,
+    clips: &[SourceInfo],
+    overlays: &[SourceInfo],
+    audio_tracks: &[SourceInfo],
+) -> Result<FilterPlan> {
+    let mut inputs = vec![];
+    let mut spec = String::new();
+
+    let (mut video_label, mut audio_label) = (String::new(), String::new());
+    let mut timeline_end = 0.0f64;
+    let mut previous_duration = 0.0f64;
+
+    for (index, (clip, info)) in project.clips.iter().zip(clips).enumerate() {
+        let in_point = clip.in_point.as_secs_f64();
+        let out_point = clip
+            .out_point
+            .map(|out_point| out_point.as_secs_f64().min(info.duration))
+            .unwrap_or(info.duration);
+        let duration = out_point - in_point;
+
+        if duration <= 0.0 {
+            return Err(Error::InvalidConfig(format!(
+                "Clip {}: in point {:?} is after the end of {} ({:.3}s)",
+                index, clip.in_point, clip.path, info.duration
+            )));
+        }
+
+        // A transition can't be longer than either of the two clips
+        let transition = clip
+            .transition
+            .filter(|_| index > 0)
+            .map(|transition| (transition.kind, transition.duration.as_secs_f64().min(previous_duration).min(duration)))
+            .filter(|(_, duration)| *duration > 0.0);
+
+        let timeline_start = timeline_end - transition.map(|(_, duration)| duration).unwrap_or(0.0);
+
+        inputs.push(GraphInput {
+            name: format!("v{}", index),
+            path: clip.path.clone(),
+            kind: InputKind::Video,
+            in_point,
+            out_point,
+            timeline_start,
+        });
+
+        spec.push_str(&format!(
+            "[v{i}]trim=start={in_point:.6}:end={out_point:.6},setpts=PTS-STARTPTS,\
+             scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color={bg},\
+             setsar=1,fps={fps},format=yuv420p[cv{i}];",
+            i = index,
+            w = project.width,
+            h = project.height,
+            bg = project.background,
+            fps = project.frame_rate,
+        ));
+
+        // Clips without audio get silence so the audio stays in sync
+        if info.has_audio {
+            inputs.push(GraphInput {
+                name: format!("a{}", index),
+                path: clip.path.clone(),
+                kind: InputKind::Audio,
+                in_point,
+                out_point,
+                timeline_start,
+            });
+
+            spec.push_str(&format!(
+                "[a{i}]atrim=start={in_point:.6}:end={out_point:.6},asetpts=PTS-STARTPTS,aresample={sr},\
+                 aformat=sample_fmts=fltp:channel_layouts=stereo,volume={volume},\
+                 apad=whole_dur={duration:.6},atrim=end={duration:.6}[ca{i}];",
+                i = index,
+                sr = OUTPUT_SAMPLE_RATE,
+                volume = clip.volume,
+            ));
+        } else {
+            spec.push_str(&format!(
+                "anullsrc=r={}:cl=stereo,atrim=end={:.6}[ca{}];",
+                OUTPUT_SAMPLE_RATE, duration, index
+            ));
+        }
+
+        if index == 0 {
+            video_label = "cv0".to_string();
+            audio_label = "ca0".to_string();
+        } else {
+            match transition {
+                Some((kind, transition_duration)) => spec.push_str(&format!(
+                    "[{v}][cv{i}]xfade=transition={kind}:duration={d:.6}:offset={offset:.6}[vx{i}];\
+                     [{a}][ca{i}]acrossfade=d={d:.6}[ax{i}];",
+                    v = video_label,
+                    a = audio_label,
+                    i = index,
+                    kind = kind.as_xfade_name(),
+                    d = transition_duration,
+                    offset = timeline_start,
+                )),
+                None => spec.push_str(&format!(
+                    "[{}][{}][cv{i}][ca{i}]concat=n=2:v=1:a=1[vx{i}][ax{i}];",
+                    video_label,
+                    audio_label,
+                    i = index,
+                )),
+            }
+
+            video_label = format!("vx{}", index);
+            audio_label = format!("ax{}", index);
+        }
+
+        timeline_end = timeline_start + duration;
+        previous_duration = duration;
+    }
+
+    for (index, (overlay, info)) in project.overlays.iter().zip(overlays).enumerate() {
+        let start = overlay.start.as_secs_f64();
+        let in_point = overlay.in_point.as_secs_f64();
+        let available = info.duration - in_point;
+        let duration = overlay
+            .duration
+            .map(|duration| duration.as_secs_f64().min(available))
+            .unwrap_or(available)
+            .min(timeline_end - start);
+
+        if duration <= 0.0 {
+            log::warn!("Overlay {} is outside of the timeline, skipped", index);
+            continue;
+        }
+
+        inputs.push(GraphInput {
+            name: format!("o{}", index),
+            path: overlay.path.clone(),
+            kind: InputKind::Video,
+            in_point,
+            out_point: in_point + duration,
+            timeline_start: start,
+        });
+
+        let scale = overlay
+            .width
+            .map(|width| format!(",scale={}:-2", width & !1))
+            .unwrap_or_default();
+
+        spec.push_str(&format!(
+            "[o{i}]trim=start={in_point:.6}:end={end:.6},setpts=PTS-STARTPTS+{start:.6}/TB{scale}[ov{i}];\
+             [{v}][ov{i}]overlay={position}:eof_action=pass[vo{i}];",
+            i = index,
+            end = in_point + duration,
+            v = video_label,
+            position = overlay.position.to_overlay_option(overlay.margin),
+        ));
+
+        video_label = format!("vo{}", index);
+    }
+
+    let mut track_labels = vec![];
+
+    for (index, (track, info)) in project.audio_tracks.iter().zip(audio_tracks).enumerate() {
+        let start = track.start.as_secs_f64();
+        let in_point = track.in_point.as_secs_f64();
+        let available = info.duration - in_point;
+        let duration = track
+            .duration
+            .map(|duration| duration.as_secs_f64().min(available))
+            .unwrap_or(available)
+            .min(timeline_end - start);
+
+        if !info.has_audio || duration <= 0.0 {
+            log::warn!("Audio track {} is outside of the timeline or has no audio, skipped", index);
+            continue;
+        }
+
+        inputs.push(GraphInput {
+            name: format!("t{}", index),
+            path: track.path.clone(),
+            kind: InputKind::Audio,
+            in_point,
+            out_point: in_point + duration,
+            timeline_start: start,
+        });
+
+        spec.push_str(&format!(
+            "[t{i}]atrim=start={in_point:.6}:end={end:.6},asetpts=PTS-STARTPTS,aresample={sr},\
+             aformat=sample_fmts=fltp:channel_layouts=stereo,volume={volume}",
+            i = index,
+            end = in_point + duration,
+            sr = OUTPUT_SAMPLE_RATE,
+            volume = track.volume,
+        ));
+
+        if !track.start.is_zero() {
+            spec.push_str(&format!(",adelay=delays={}:all=1", track.start.as_millis()));
+        }
+
+        spec.push_str(&format!("[ta{}];", index));
+        track_labels.push(format!("[ta{}]", index));
+    }
+
+    if !track_labels.is_empty() {
+        spec.push_str(&format!(
+            "[{}]{}amix=inputs={}:duration=first:normalize=0[amix];",
+            audio_label,
+            track_labels.concat(),
+            track_labels.len() + 1
+        ));
+        audio_label = "amix".to_string();
+    }
+
+    spec.push_str(&format!(
+        "[{}]format=yuv420p[vout];[{}]atrim=end={:.6},aformat=sample_fmts=fltp:channel_layouts=stereo,asetnsamples=n=1024[aout]",
+        video_label, audio_label, timeline_end
+    ));
+
+    Ok(FilterPlan {
+        inputs,
+        spec,
+        duration: timeline_end,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::project::{AudioTrack, Clip, Overlay, Transition, TransitionKind};
+    use std::time::Duration;
+
+    fn info(duration: f64, has_audio: bool) -> SourceInfo {
+        SourceInfo { duration, has_audio }
+    }
+
+    #[test]
+    fn test_compile_concat() {
+        let project = Project::new(1280, 720)
+            .add_clip(Clip::new("a.mp4").with_in_point(Duration::from_secs(2)))
+            .add_clip(Clip::new("b.mp4").with_out_point(Duration::from_secs(3)));
+
+        let plan = compile(&project, &[info(10.0, true), info(8.0, false)], &[], &[]).unwrap();
+
+        assert_eq!(plan.duration, 11.0);
+        assert_eq!(plan.inputs.len(), 3);
+        assert_eq!(plan.inputs[2].name, "v1");
+        assert_eq!(plan.inputs[2].timeline_start, 8.0);
+        assert!(plan.spec.contains("[v0]trim=start=2.000000:end=10.000000,"));
+        assert!(plan.spec.contains("anullsrc=r=48000:cl=stereo,atrim=end=3.000000[ca1];"));
+        assert!(plan.spec.contains("[cv0][ca0][cv1][ca1]concat=n=2:v=1:a=1[vx1][ax1];"));
+        assert!(plan.spec.ends_with("[vx1]format=yuv420p[vout];[ax1]atrim=end=11.000000,aformat=sample_fmts=fltp:channel_layouts=stereo,asetnsamples=n=1024[aout]"));
+    }
+
+    #[test]
+    fn test_compile_transition() {
+        let project = Project::new(1280, 720)
+            .add_clip(Clip::new("a.mp4"))
+            .add_clip(
+                Clip::new("b.mp4")
+                    .with_transition(Transition::new(TransitionKind::Dissolve, Duration::from_secs(1))),
+            )
+            // Clamped to the length of the clip
+            .add_clip(
+                Clip::new("c.mp4")
+                    .with_transition(Transition::new(TransitionKind::WipeLeft, Duration::from_secs(5))),
+            );
+
+        let plan = compile(&project, &[info(4.0, true), info(6.0, true), info(2.0, true)], &[], &[]).unwrap();
+
+        assert_eq!(plan.duration, 9.0);
+        assert_eq!(plan.inputs[2].timeline_start, 3.0);
+        assert_eq!(plan.inputs[4].timeline_start, 7.0);
+        assert!(plan.spec.contains("[cv0][cv1]xfade=transition=dissolve:duration=1.000000:offset=3.000000[vx1];"));
+        assert!(plan.spec.contains("[ax1][ca2]acrossfade=d=2.000000[ax2];"));
+    }
+
+    #[test]
+    fn test_compile_overlays_and_tracks() {
+        let project = Project::new(1280, 720)
+            .add_clip(Clip::new("a.mp4"))
+            .add_overlay(Overlay::new("cam.mp4", Duration::from_secs(1)).with_width(321))
+            .add_overlay(Overlay::new("late.mp4", Duration::from_secs(20)))
+            .add_audio_track(AudioTrack::new("music.mp3", Duration::from_millis(500)).with_volume(0.2));
+
+        let plan = compile(
+            &project,
+            &[info(10.0, true)],
+            &[info(5.0, false), info(5.0, false)],
+            &[info(60.0, true)],
+        )
+        .unwrap();
+
+        assert_eq!(plan.inputs.iter().map(|input| input.name.as_str()).collect::<Vec<_>>(), ["v0", "a0", "o0", "t0"]);
+        assert_eq!(plan.inputs[3].out_point, 9.5);
+        assert!(plan.spec.contains("setpts=PTS-STARTPTS+1.000000/TB,scale=320:-2[ov0];[cv0][ov0]overlay=x=W-w-20:y=20:eof_action=pass[vo0];"));
+        assert!(plan.spec.contains("volume=0.2,adelay=delays=500:all=1[ta0];"));
+        assert!(plan.spec.contains("[ca0][ta0]amix=inputs=2:duration=first:normalize=0[amix];"));
+        assert!(plan.spec.contains("[vo0]format=yuv420p[vout];[amix]atrim=end=10.000000"));
+    }
+
+    #[test]
+    fn test_compile_invalid_in_point() {
+        let project = Project::new(1280, 720).add_clip(Clip::new("a.mp4").with_in_point(Duration::from_secs(20)));
+        assert!(compile(&project, &[info(10.0, true)], &[], &[]).is_err());
+    }
+}
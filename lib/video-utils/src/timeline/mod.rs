@@ -0,0 +1,14 @@
+//! Timeline-based multi-clip editing
+//!
+//! A [`Project`] describes a whole edit declaratively: clips on the main track
+//! with in/out points and transitions, video overlays and extra audio tracks.
+//! [`render`] compiles the project into a single FFmpeg filter graph and
+//! encodes it in one pass, which the separate trim/concat/crossfade functions
+//! can't do.
+
+pub mod project;
+mod graph;
+mod render;
+
+pub use project::{AudioTrack, Clip, Overlay, Project, Transition, TransitionKind};
+pub use render::render;
@@ -0,0 +1,337 @@
+//! Declarative project model of the timeline renderer
+
+use crate::editor::pip::PipPosition;
+use crate::encode;
+use crate::{Error, Result};
+use derivative::Derivative;
+use derive_setters::Setters;
+use std::time::Duration;
+
+/// Transition effect between two consecutive clips
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    Fade,
+    FadeBlack,
+    FadeWhite,
+    Dissolve,
+    WipeLeft,
+    WipeRight,
+    SlideLeft,
+    SlideRight,
+    CircleOpen,
+}
+
+impl TransitionKind {
+    /// Name of the transition in the FFmpeg xfade filter
+    pub(crate) fn as_xfade_name(self) -> &'static str {
+        match self {
+            TransitionKind::Fade => "fade",
+            TransitionKind::FadeBlack => "fadeblack",
+            TransitionKind::FadeWhite => "fadewhite",
+            TransitionKind::Dissolve => "dissolve",
+            TransitionKind::WipeLeft => "wipeleft",
+            TransitionKind::WipeRight => "wiperight",
+            TransitionKind::SlideLeft => "slideleft",
+            TransitionKind::SlideRight => "slideright",
+            TransitionKind::CircleOpen => "circleopen",
+        }
+    }
+}
+
+/// Transition from the previous clip into a clip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    /// Overlap of the two clips, shortens the timeline by this amount
+    pub duration: Duration,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind, duration: Duration) -> Self {
+        Self { kind, duration }
+    }
+}
+
+/// Clip on the main track, clips are played one after another
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Clip {
+    /// Source media file
+    #[derivative(Default(value = "String::new()"))]
+    pub path: String,
+
+    /// Start of the clip in the source file
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub in_point: Duration,
+
+    /// End of the clip in the source file (None = end of file)
+    #[setters(strip_option)]
+    pub out_point: Option<Duration>,
+
+    /// Linear gain of the clip audio
+    #[derivative(Default(value = "1.0"))]
+    pub volume: f32,
+
+    /// Transition from the previous clip, ignored on the first clip
+    #[setters(strip_option)]
+    pub transition: Option<Transition>,
+}
+
+impl Clip {
+    /// Create a clip of a whole file (convenience method)
+    pub fn new(path: impl Into<String>) -> Self {
+        Self::default().with_path(path.into())
+    }
+}
+
+/// Video shown on top of the main track
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Overlay {
+    /// Source video file
+    #[derivative(Default(value = "String::new()"))]
+    pub path: String,
+
+    /// Start of the overlay on the timeline
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub start: Duration,
+
+    /// Start of the overlay in the source file
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub in_point: Duration,
+
+    /// Length of the overlay (None = until the end of the source)
+    #[setters(strip_option)]
+    pub duration: Option<Duration>,
+
+    /// Placement on the main track
+    #[derivative(Default(value = "PipPosition::TopRight"))]
+    pub position: PipPosition,
+
+    /// Distance to the edges of the frame in pixels
+    #[derivative(Default(value = "20"))]
+    pub margin: u32,
+
+    /// Overlay width in pixels, the height keeps the aspect ratio (None = source size)
+    #[setters(strip_option)]
+    pub width: Option<u32>,
+}
+
+impl Overlay {
+    /// Create an overlay starting at `start` on the timeline (convenience method)
+    pub fn new(path: impl Into<String>, start: Duration) -> Self {
+        Self::default().with_path(path.into()).with_start(start)
+    }
+}
+
+/// Extra audio mixed into the audio of the main track, e.g. music or a voiceover
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct AudioTrack {
+    /// Source media file
+    #[derivative(Default(value = "String::new()"))]
+    pub path: String,
+
+    /// Start of the track on the timeline
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub start: Duration,
+
+    /// Start of the track in the source file
+    #[derivative(Default(value = "Duration::ZERO"))]
+    pub in_point: Duration,
+
+    /// Length of the track (None = until the end of the source)
+    #[setters(strip_option)]
+    pub duration: Option<Duration>,
+
+    /// Linear gain of the track
+    #[derivative(Default(value = "1.0"))]
+    pub volume: f32,
+}
+
+impl AudioTrack {
+    /// Create an audio track starting at `start` on the timeline (convenience method)
+    pub fn new(path: impl Into<String>, start: Duration) -> Self {
+        Self::default().with_path(path.into()).with_start(start)
+    }
+}
+
+/// Multi-clip editing project
+///
+/// The length of the rendered video is given by the main track, overlays and
+/// audio tracks are cut at its end.
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct Project {
+    /// Output width in pixels, clips are scaled to fit and padded
+    #[derivative(Default(value = "1920"))]
+    pub width: u32,
+
+    /// Output height in pixels
+    #[derivative(Default(value = "1080"))]
+    pub height: u32,
+
+    /// Output frame rate
+    #[derivative(Default(value = "30"))]
+    pub frame_rate: u32,
+
+    /// Color of the padding around clips with another aspect ratio
+    #[derivative(Default(value = "\"black\".to_string()"))]
+    pub background: String,
+
+    /// Main track
+    pub clips: Vec<Clip>,
+
+    /// Videos shown on top of the main track
+    pub overlays: Vec<Overlay>,
+
+    /// Extra audio mixed into the main track audio
+    pub audio_tracks: Vec<AudioTrack>,
+
+    /// CRF of the rendered video, 0-51, lower is better
+    #[derivative(Default(value = "23"))]
+    pub crf: u8,
+}
+
+impl Project {
+    /// Create an empty project with the given output size (convenience method)
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::default().with_width(width).with_height(height)
+    }
+
+    /// Append a clip to the main track
+    pub fn add_clip(mut self, clip: Clip) -> Self {
+        self.clips.push(clip);
+        self
+    }
+
+    /// Add an overlay
+    pub fn add_overlay(mut self, overlay: Overlay) -> Self {
+        self.overlays.push(overlay);
+        self
+    }
+
+    /// Add an audio track
+    pub fn add_audio_track(mut self, track: AudioTrack) -> Self {
+        self.audio_tracks.push(track);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.clips.is_empty() {
+            return Err(Error::InvalidConfig("Project has no clips".to_string()));
+        }
+
+        if self.width == 0 || self.height == 0 || !self.width.is_multiple_of(2) || !self.height.is_multiple_of(2) {
+            return Err(Error::InvalidConfig(format!(
+                "Output size must be even and non-zero, got: {}x{}",
+                self.width, self.height
+            )));
+        }
+
+        if self.frame_rate == 0 {
+            return Err(Error::InvalidConfig("Frame rate must be greater than 0".to_string()));
+        }
+
+        encode::validate_crf(self.crf)?;
+
+        for (index, clip) in self.clips.iter().enumerate() {
+            if let Some(out_point) = clip.out_point
+                && out_point <= clip.in_point
+            {
+                return Err(Error::InvalidConfig(format!(
+                    "Clip {}: out point {:?} must be after in point {:?}",
+                    index, out_point, clip.in_point
+                )));
+            }
+
+            if !clip.volume.is_finite() || clip.volume < 0.0 {
+                return Err(Error::InvalidConfig(format!(
+                    "Clip {}: volume must be non-negative, got: {}",
+                    index, clip.volume
+                )));
+            }
+        }
+
+        for (index, overlay) in self.overlays.iter().enumerate() {
+            if overlay.duration.is_some_and(|duration| duration.is_zero()) {
+                return Err(Error::InvalidConfig(format!("Overlay {}: duration is zero", index)));
+            }
+
+            if overlay.width.is_some_and(|width| width < 2) {
+                return Err(Error::InvalidConfig(format!(
+                    "Overlay {}: width must be at least 2 pixels",
+                    index
+                )));
+            }
+        }
+
+        for (index, track) in self.audio_tracks.iter().enumerate() {
+            if track.duration.is_some_and(|duration| duration.is_zero()) {
+                return Err(Error::InvalidConfig(format!("Audio track {}: duration is zero", index)));
+            }
+
+            if !track.volume.is_finite() || track.volume < 0.0 {
+                return Err(Error::InvalidConfig(format!(
+                    "Audio track {}: volume must be non-negative, got: {}",
+                    index, track.volume
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_builder() {
+        let project = Project::new(1280, 720)
+            .add_clip(Clip::new("a.mp4"))
+            .add_clip(Clip::new("b.mp4").with_transition(Transition::new(TransitionKind::Fade, Duration::from_secs(1))))
+            .add_audio_track(AudioTrack::new("music.mp3", Duration::ZERO).with_volume(0.3));
+
+        assert_eq!(project.clips.len(), 2);
+        assert_eq!(project.frame_rate, 30);
+        assert_eq!(project.clips[0].volume, 1.0);
+        assert_eq!(project.audio_tracks[0].volume, 0.3);
+        assert!(project.validate().is_ok());
+    }
+
+    #[test]
+    fn test_project_validation() {
+        let clip = || Clip::new("a.mp4");
+
+        assert!(Project::new(1280, 720).validate().is_err());
+        assert!(Project::new(1279, 720).add_clip(clip()).validate().is_err());
+        assert!(Project::new(1280, 720).add_clip(clip()).with_crf(60).validate().is_err());
+        assert!(Project::new(1280, 720)
+            .add_clip(clip().with_in_point(Duration::from_secs(5)).with_out_point(Duration::from_secs(5)))
+            .validate()
+            .is_err());
+        assert!(Project::new(1280, 720).add_clip(clip().with_volume(-1.0)).validate().is_err());
+        assert!(Project::new(1280, 720)
+            .add_clip(clip())
+            .add_overlay(Overlay::new("cam.mp4", Duration::ZERO).with_width(1))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_xfade_names() {
+        assert_eq!(TransitionKind::Fade.as_xfade_name(), "fade");
+        assert_eq!(TransitionKind::WipeLeft.as_xfade_name(), "wipeleft");
+        assert_eq!(TransitionKind::CircleOpen.as_xfade_name(), "circleopen");
+    }
+}
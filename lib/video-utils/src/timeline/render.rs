@@ -0,0 +1,503 @@
+//! Render a project through one FFmpeg filter graph
+
+use super::graph::{self, FilterPlan, GraphInput, InputKind, SourceInfo, OUTPUT_SAMPLE_RATE};
+use super::project::Project;
+use crate::encode::{self, StreamTarget};
+use crate::{Error, Result};
+use ffmpeg::Rescale;
+use ffmpeg_next as ffmpeg;
+use std::path::Path;
+
+/// Bitrate of the rendered audio track
+const OUTPUT_AUDIO_BITRATE: usize = 192_000;
+
+/// Render a project into a single video file
+///
+/// All clips, transitions, overlays and audio tracks are compiled into one
+/// filter graph, so the output is encoded in a single pass (H.264 + AAC).
+/// `progress_cb` is called with the rendered fraction of the timeline in
+/// [0.0, 1.0].
+///
+/// # Arguments
+/// * `project` - Project to render
+/// * `output` - Output video file
+/// * `progress_cb` - Progress callback
+///
+/// # Example
+/// ```no_run
+/// use video_utils::timeline::{render, AudioTrack, Clip, Overlay, Project, Transition, TransitionKind};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let project = Project::new(1920, 1080)
+///     .add_clip(Clip::new("intro.mp4").with_out_point(Duration::from_secs(5)))
+///     .add_clip(
+///         Clip::new("screen.mp4")
+///             .with_in_point(Duration::from_secs(12))
+///             .with_transition(Transition::new(TransitionKind::Fade, Duration::from_millis(800))),
+///     )
+///     .add_overlay(Overlay::new("webcam.mp4", Duration::from_secs(5)).with_width(480))
+///     .add_audio_track(AudioTrack::new("music.mp3", Duration::ZERO).with_volume(0.15));
+///
+/// render(&project, "output.mp4", |progress| println!("{:.0}%", progress * 100.0))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn render<P, F>(project: &Project, output: P, mut progress_cb: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnMut(f32),
+{
+    project.validate()?;
+
+    let output = output.as_ref();
+
+    log::info!(
+        "Rendering timeline: {} clips, {} overlays, {} audio tracks -> {}",
+        project.clips.len(),
+        project.overlays.len(),
+        project.audio_tracks.len(),
+        output.display()
+    );
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let probe_all = |paths: Vec<&str>| paths.into_iter().map(probe).collect::<Result<Vec<_>>>();
+    let clips = probe_all(project.clips.iter().map(|clip| clip.path.as_str()).collect())?;
+    let overlays = probe_all(project.overlays.iter().map(|overlay| overlay.path.as_str()).collect())?;
+    let audio_tracks = probe_all(project.audio_tracks.iter().map(|track| track.path.as_str()).collect())?;
+
+    let plan = graph::compile(project, &clips, &overlays, &audio_tracks)?;
+    log::debug!("Filter spec: {}", plan.spec);
+
+    let mut inputs = plan.inputs.iter().map(SourceInput::open).collect::<Result<Vec<_>>>()?;
+    let mut filter_graph = build_filter_graph(&plan, &inputs)?;
+
+    let video_time_base = filter_graph
+        .get("vout")
+        .ok_or_else(|| Error::FFmpeg("Failed to get vout filter".to_string()))?
+        .sink()
+        .time_base();
+
+    let audio_time_base = filter_graph
+        .get("aout")
+        .ok_or_else(|| Error::FFmpeg("Failed to get aout filter".to_string()))?
+        .sink()
+        .time_base();
+
+    let mut output_ctx = ffmpeg::format::output(&output)
+        .map_err(|e| Error::FFmpeg(format!("Failed to create output: {}", e)))?;
+
+    let global_header = output_ctx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+    // Video encoder
+    let video_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264)
+        .ok_or_else(|| Error::FFmpeg("H.264 encoder not found".to_string()))?;
+
+    let mut video_encoder = ffmpeg::codec::context::Context::new_with_codec(video_codec)
+        .encoder()
+        .video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create video encoder: {}", e)))?;
+
+    video_encoder.set_width(project.width);
+    video_encoder.set_height(project.height);
+    video_encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+    video_encoder.set_time_base(video_time_base);
+    video_encoder.set_frame_rate(Some(ffmpeg::Rational::new(project.frame_rate as i32, 1)));
+
+    if global_header {
+        video_encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut encoder_opts = ffmpeg::Dictionary::new();
+    encoder_opts.set("crf", &project.crf.to_string());
+    encoder_opts.set("preset", "medium");
+
+    let mut video_encoder = video_encoder
+        .open_with(encoder_opts)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open video encoder: {}", e)))?;
+
+    // Audio encoder
+    let audio_codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+        .ok_or_else(|| Error::FFmpeg("AAC encoder not found".to_string()))?;
+
+    let mut audio_encoder = ffmpeg::codec::context::Context::new_with_codec(audio_codec)
+        .encoder()
+        .audio()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create audio encoder: {}", e)))?;
+
+    let audio_encoder_time_base = ffmpeg::Rational::new(1, OUTPUT_SAMPLE_RATE as i32);
+    audio_encoder.set_rate(OUTPUT_SAMPLE_RATE as i32);
+    audio_encoder.set_format(ffmpeg::format::Sample::F32(ffmpeg::format::sample::Type::Planar));
+    audio_encoder.set_channel_layout(ffmpeg::ChannelLayout::STEREO);
+    audio_encoder.set_bit_rate(OUTPUT_AUDIO_BITRATE);
+    audio_encoder.set_time_base(audio_encoder_time_base);
+
+    if global_header {
+        audio_encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+    }
+
+    let mut audio_encoder = audio_encoder
+        .open_as(audio_codec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open audio encoder: {}", e)))?;
+
+    {
+        let mut output_stream = output_ctx
+            .add_stream(video_codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add video stream: {}", e)))?;
+        output_stream.set_parameters(&video_encoder);
+        output_stream.set_time_base(video_time_base);
+    }
+
+    {
+        let mut output_stream = output_ctx
+            .add_stream(audio_codec)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add audio stream: {}", e)))?;
+        output_stream.set_parameters(&audio_encoder);
+    }
+
+    output_ctx
+        .write_header()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write header: {}", e)))?;
+
+    let mut outputs = Outputs {
+        video: StreamTarget {
+            stream_index: 0,
+            encoder_time_base: video_time_base,
+            output_time_base: output_ctx.stream(0).unwrap().time_base(),
+        },
+        audio: StreamTarget {
+            stream_index: 1,
+            encoder_time_base: audio_encoder_time_base,
+            output_time_base: output_ctx.stream(1).unwrap().time_base(),
+        },
+        audio_sink_time_base: audio_time_base,
+        duration: plan.duration,
+        reported_percent: 0,
+    };
+
+    // Feed the inputs in timeline order so that the transition, overlay and
+    // mix filters never have to queue more than a few frames
+    let mut video_frame = ffmpeg::frame::Video::empty();
+    let mut audio_frame = ffmpeg::frame::Audio::empty();
+
+    while let Some(index) = inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| !input.finished)
+        .min_by(|(_, a), (_, b)| a.position.total_cmp(&b.position))
+        .map(|(index, _)| index)
+    {
+        let decoded: &mut ffmpeg::Frame = match inputs[index].kind {
+            InputKind::Video => &mut video_frame,
+            InputKind::Audio => &mut audio_frame,
+        };
+        let has_frame = inputs[index].next_frame(decoded)?;
+
+        let mut source = filter_graph
+            .get(&inputs[index].name)
+            .ok_or_else(|| Error::FFmpeg(format!("Failed to get {} filter", inputs[index].name)))?;
+
+        if has_frame {
+            source
+                .source()
+                .add(decoded)
+                .map_err(|e| Error::FFmpeg(format!("Filter add failed: {}", e)))?;
+        } else {
+            source
+                .source()
+                .flush()
+                .map_err(|e| Error::FFmpeg(format!("Failed to flush filter: {}", e)))?;
+        }
+
+        drain_filter(
+            &mut filter_graph,
+            &mut video_encoder,
+            &mut audio_encoder,
+            &mut output_ctx,
+            &mut outputs,
+            &mut progress_cb,
+        )?;
+    }
+
+    encode::finish_encoder(&mut video_encoder, &mut output_ctx, &outputs.video)?;
+    encode::finish_encoder(&mut audio_encoder, &mut output_ctx, &outputs.audio)?;
+
+    output_ctx
+        .write_trailer()
+        .map_err(|e| Error::FFmpeg(format!("Failed to write trailer: {}", e)))?;
+
+    progress_cb(1.0);
+    log::info!("Timeline rendered: {:.2}s -> {}", plan.duration, output.display());
+
+    Ok(())
+}
+
+/// Duration and audio presence of a media file
+fn probe(path: &str) -> Result<SourceInfo> {
+    let ctx = ffmpeg::format::input(&path)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", path, e)))?;
+
+    if ctx.duration() <= 0 {
+        return Err(Error::FFmpeg(format!("Unknown duration of {}", path)));
+    }
+
+    Ok(SourceInfo {
+        duration: ctx.duration() as f64 / 1_000_000.0, // microseconds to seconds
+        has_audio: ctx.streams().best(ffmpeg::media::Type::Audio).is_some(),
+    })
+}
+
+fn build_filter_graph(plan: &FilterPlan, inputs: &[SourceInput]) -> Result<ffmpeg::filter::Graph> {
+    let mut filter_graph = ffmpeg::filter::Graph::new();
+
+    for input in inputs {
+        let filter = match input.kind {
+            InputKind::Video => "buffer",
+            InputKind::Audio => "abuffer",
+        };
+
+        filter_graph
+            .add(&ffmpeg::filter::find(filter).unwrap(), &input.name, &input.buffer_args)
+            .map_err(|e| Error::FFmpeg(format!("Failed to add {} filter: {}", input.name, e)))?;
+    }
+
+    filter_graph
+        .add(&ffmpeg::filter::find("buffersink").unwrap(), "vout", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add buffersink: {}", e)))?;
+
+    filter_graph
+        .add(&ffmpeg::filter::find("abuffersink").unwrap(), "aout", "")
+        .map_err(|e| Error::FFmpeg(format!("Failed to add abuffersink: {}", e)))?;
+
+    let mut parser = filter_graph
+        .input("vout", 0)
+        .and_then(|p| p.input("aout", 0))
+        .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?;
+
+    for input in inputs {
+        parser = parser
+            .output(&input.name, 0)
+            .map_err(|e| Error::FFmpeg(format!("Failed to connect filters: {}", e)))?;
+    }
+
+    parser
+        .parse(&plan.spec)
+        .map_err(|e| Error::FFmpeg(format!("Failed to parse filter: {}", e)))?;
+
+    filter_graph
+        .validate()
+        .map_err(|e| Error::FFmpeg(format!("Failed to validate filter graph: {}", e)))?;
+
+    Ok(filter_graph)
+}
+
+/// Decoding state of one graph input
+struct SourceInput {
+    name: String,
+    kind: InputKind,
+    ctx: ffmpeg::format::context::Input,
+    decoder: ffmpeg::decoder::Opened,
+    /// Arguments of the buffer source, taken from the typed decoder
+    buffer_args: String,
+    stream_index: usize,
+    time_base: ffmpeg::Rational,
+    out_point: f64,
+    /// Timeline time minus source time in seconds
+    timeline_offset: f64,
+    /// Timeline time of the last decoded frame in seconds. Starts at 0 so that
+    /// every filter chain gets its first frame right away.
+    position: f64,
+    past_out_point: bool,
+    eof_sent: bool,
+    finished: bool,
+}
+
+impl SourceInput {
+    fn open(input: &GraphInput) -> Result<Self> {
+        let path = input.path.as_str();
+        let mut ctx = ffmpeg::format::input(&path)
+            .map_err(|e| Error::FFmpeg(format!("Failed to open {}: {}", path, e)))?;
+
+        let medium = match input.kind {
+            InputKind::Video => ffmpeg::media::Type::Video,
+            InputKind::Audio => ffmpeg::media::Type::Audio,
+        };
+
+        let (stream_index, time_base, parameters) = {
+            let stream = ctx
+                .streams()
+                .best(medium)
+                .ok_or_else(|| Error::FFmpeg(format!("No {:?} stream found in {}", medium, path)))?;
+            (stream.index(), stream.time_base(), stream.parameters())
+        };
+
+        let decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+            .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?
+            .decoder();
+
+        let (decoder, buffer_args) = match input.kind {
+            InputKind::Video => {
+                let decoder = decoder
+                    .video()
+                    .map_err(|e| Error::FFmpeg(format!("Failed to create video decoder: {}", e)))?;
+
+                let buffer_args = format!(
+                    "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+                    decoder.width(),
+                    decoder.height(),
+                    decoder
+                        .format()
+                        .descriptor()
+                        .ok_or_else(|| Error::FFmpeg("Unknown pixel format".to_string()))?
+                        .name(),
+                    time_base,
+                    decoder.aspect_ratio()
+                );
+
+                (decoder.0, buffer_args)
+            }
+            InputKind::Audio => {
+                let decoder = decoder
+                    .audio()
+                    .map_err(|e| Error::FFmpeg(format!("Failed to create audio decoder: {}", e)))?;
+
+                let channel_layout = if decoder.channel_layout().is_empty() {
+                    ffmpeg::ChannelLayout::default(decoder.channels() as i32)
+                } else {
+                    decoder.channel_layout()
+                };
+
+                let buffer_args = format!(
+                    "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+                    time_base,
+                    decoder.rate(),
+                    decoder.format().name(),
+                    channel_layout.bits()
+                );
+
+                (decoder.0, buffer_args)
+            }
+        };
+
+        // Skip to the in point, the trim filters drop the frames before it
+        if input.in_point > 0.0 {
+            let timestamp = (input.in_point * 1_000_000.0) as i64; // AV_TIME_BASE
+            ctx.seek(timestamp, ..timestamp)
+                .map_err(|e| Error::FFmpeg(format!("Failed to seek {}: {}", path, e)))?;
+        }
+
+        Ok(Self {
+            name: input.name.clone(),
+            kind: input.kind,
+            ctx,
+            decoder,
+            buffer_args,
+            stream_index,
+            time_base,
+            out_point: input.out_point,
+            timeline_offset: input.timeline_start - input.in_point,
+            position: 0.0,
+            past_out_point: false,
+            eof_sent: false,
+            finished: false,
+        })
+    }
+
+    /// Decode the next frame, returns false and marks the input as finished
+    /// once the out point is passed or all frames are decoded
+    fn next_frame(&mut self, frame: &mut ffmpeg::Frame) -> Result<bool> {
+        loop {
+            if self.past_out_point {
+                self.finished = true;
+                return Ok(false);
+            }
+
+            if self.decoder.receive_frame(frame).is_ok() {
+                if let Some(ts) = frame.timestamp().or(frame.pts()) {
+                    frame.set_pts(Some(ts));
+
+                    let secs = ts as f64 * f64::from(self.time_base);
+                    self.position = secs + self.timeline_offset;
+                    self.past_out_point = secs >= self.out_point;
+                }
+                return Ok(true);
+            }
+
+            if self.eof_sent {
+                self.finished = true;
+                return Ok(false);
+            }
+
+            let next_packet = self
+                .ctx
+                .packets()
+                .next()
+                .map(|(stream, packet)| (stream.index(), packet));
+
+            match next_packet {
+                Some((stream_index, packet)) if stream_index == self.stream_index => {
+                    self.decoder
+                        .send_packet(&packet)
+                        .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+                }
+                Some(_) => {}
+                None => {
+                    self.decoder
+                        .send_eof()
+                        .map_err(|e| Error::FFmpeg(format!("Failed to flush decoder: {}", e)))?;
+                    self.eof_sent = true;
+                }
+            }
+        }
+    }
+}
+
+/// Encoding state shared by the video and audio outputs of the filter graph
+struct Outputs {
+    video: StreamTarget,
+    audio: StreamTarget,
+    audio_sink_time_base: ffmpeg::Rational,
+    /// Length of the timeline in seconds
+    duration: f64,
+    reported_percent: u32,
+}
+
+/// Encode all frames currently available from both outputs of the filter graph
+fn drain_filter<F: FnMut(f32)>(
+    filter_graph: &mut ffmpeg::filter::Graph,
+    video_encoder: &mut ffmpeg::encoder::Video,
+    audio_encoder: &mut ffmpeg::encoder::Audio,
+    output_ctx: &mut ffmpeg::format::context::Output,
+    outputs: &mut Outputs,
+    progress_cb: &mut F,
+) -> Result<()> {
+    let video_time_base = outputs.video.encoder_time_base;
+    let duration = outputs.duration.max(f64::EPSILON);
+    let reported_percent = &mut outputs.reported_percent;
+
+    encode::drain_filter(filter_graph, "vout", video_encoder, output_ctx, &outputs.video, |frame| {
+        if let Some(pts) = frame.pts() {
+            let secs = pts as f64 * f64::from(video_time_base);
+            let percent = ((secs / duration) * 100.0).clamp(0.0, 100.0) as u32;
+
+            if percent > *reported_percent {
+                *reported_percent = percent;
+                progress_cb(percent as f32 / 100.0);
+            }
+        }
+    })?;
+
+    let (sink_time_base, audio_time_base) = (outputs.audio_sink_time_base, outputs.audio.encoder_time_base);
+
+    encode::drain_filter(filter_graph, "aout", audio_encoder, output_ctx, &outputs.audio, |frame| {
+        let pts = frame.pts().map(|pts| pts.rescale(sink_time_base, audio_time_base));
+        frame.set_pts(pts);
+    })?;
+
+    Ok(())
+}
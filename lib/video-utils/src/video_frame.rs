@@ -336,6 +336,203 @@ pub fn extract_all_frames<P: AsRef<Path>>(video_path: P) -> Result<Vec<VideoFram
     extract_frames_interval(video_path, Duration::from_secs(0), duration, interval)
 }
 
+/// Generate a strip of evenly spaced thumbnails for timeline scrubbing
+///
+/// Only the keyframe nearest to (at or before) each target timestamp is decoded,
+/// non-key frames are discarded by the decoder, so this is much cheaper than
+/// `extract_frames_interval` on long recordings.
+///
+/// # Arguments
+///
+/// * `video_path` - Path to the video file
+/// * `count` - Number of thumbnails to generate
+/// * `width` - Thumbnail width in pixels, the height keeps the aspect ratio
+///
+/// # Returns
+///
+/// Returns at most `count` RGB24 `VideoFrame` objects ordered by timestamp
+///
+/// # Example
+///
+/// ```no_run
+/// use video_utils::video_frame::generate_thumbnails;
+///
+/// let thumbnails = generate_thumbnails("video.mp4", 10, 160).unwrap();
+/// println!("Generated {} thumbnails", thumbnails.len());
+/// ```
+pub fn generate_thumbnails<P: AsRef<Path>>(
+    video_path: P,
+    count: usize,
+    width: u32,
+) -> Result<Vec<VideoFrame>> {
+    let video_path = video_path.as_ref();
+    let path_str = video_path.to_string_lossy().to_string();
+
+    if !video_path.exists() {
+        return Err(Error::IO(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("File not found: {}", path_str),
+        )));
+    }
+
+    if count == 0 || width == 0 {
+        return Err(Error::InvalidConfig(
+            "Thumbnail count and width must be greater than 0".to_string(),
+        ));
+    }
+
+    ffmpeg::init()
+        .map_err(|e| Error::FFmpeg(format!("Failed to initialize FFmpeg: {}", e)))?;
+
+    let mut input_ctx = ffmpeg::format::input(&path_str)
+        .map_err(|e| Error::FFmpeg(format!("Failed to open input: {}", e)))?;
+
+    let duration = Duration::from_micros(input_ctx.duration().max(0) as u64);
+
+    let video_stream = input_ctx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or_else(|| Error::FFmpeg("No video stream found in input file".to_string()))?;
+
+    let video_stream_index = video_stream.index();
+    let time_base = video_stream.time_base();
+
+    let decoder_context = ffmpeg::codec::context::Context::from_parameters(video_stream.parameters())
+        .map_err(|e| Error::FFmpeg(format!("Failed to create decoder context: {}", e)))?;
+
+    let mut decoder = decoder_context.decoder().video()
+        .map_err(|e| Error::FFmpeg(format!("Failed to create video decoder: {}", e)))?;
+
+    // Let the decoder drop everything that is not a keyframe
+    decoder.skip_frame(ffmpeg::Discard::NonKey);
+
+    let (thumb_width, thumb_height) = thumbnail_size(decoder.width(), decoder.height(), width);
+
+    let mut scaler = ffmpeg::software::scaling::context::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::RGB24,
+        thumb_width,
+        thumb_height,
+        ffmpeg::software::scaling::Flags::FAST_BILINEAR,
+    )
+    .map_err(|e| Error::FFmpeg(format!("Failed to create scaler: {}", e)))?;
+
+    log::info!(
+        "Generating {} thumbnails ({}x{}) from {}",
+        count,
+        thumb_width,
+        thumb_height,
+        path_str
+    );
+
+    let mut thumbnails: Vec<VideoFrame> = Vec::with_capacity(count);
+    let mut decoded_frame = ffmpeg::frame::Video::empty();
+    let mut rgb_frame = ffmpeg::frame::Video::empty();
+
+    for (index, timestamp) in thumbnail_timestamps(duration, count).into_iter().enumerate() {
+        let found = seek_keyframe(&mut input_ctx, &mut decoder, video_stream_index, timestamp, &mut decoded_frame)?;
+
+        let Some(pts) = found else {
+            log::warn!("No keyframe found near {:.2}s", timestamp.as_secs_f64());
+            continue;
+        };
+
+        let frame_time = (pts as f64 * time_base.numerator() as f64
+            / time_base.denominator() as f64)
+            .max(0.0);
+
+        // Sparse keyframes can map several targets onto the same GOP
+        if thumbnails
+            .last()
+            .is_some_and(|last| last.pts == Duration::from_secs_f64(frame_time))
+        {
+            continue;
+        }
+
+        scaler.run(&decoded_frame, &mut rgb_frame)
+            .map_err(|e| Error::FFmpeg(format!("Scaler run failed: {}", e)))?;
+
+        let stride = rgb_frame.stride(0);
+        let row_size = thumb_width as usize * 3;
+        let data = rgb_frame.data(0);
+        let mut frame_data = Vec::with_capacity(row_size * thumb_height as usize);
+        for row in 0..thumb_height as usize {
+            frame_data.extend_from_slice(&data[row * stride..row * stride + row_size]);
+        }
+
+        thumbnails.push(VideoFrame {
+            width: thumb_width,
+            height: thumb_height,
+            pixel_format: "rgb24".to_string(),
+            data: frame_data,
+            pts: Duration::from_secs_f64(frame_time),
+            frame_number: index,
+        });
+    }
+
+    log::info!("Generated {} thumbnails", thumbnails.len());
+
+    Ok(thumbnails)
+}
+
+/// Decode the keyframe at or before `timestamp` into `decoded_frame`, returns its pts
+///
+/// A target past the end of the video gives the last keyframe, a video without
+/// any frame gives `None`.
+fn seek_keyframe(
+    input_ctx: &mut ffmpeg::format::context::Input,
+    decoder: &mut ffmpeg::decoder::Video,
+    video_stream_index: usize,
+    timestamp: Duration,
+    decoded_frame: &mut ffmpeg::frame::Video,
+) -> Result<Option<i64>> {
+    // Seek backward to the keyframe at or before the target timestamp
+    let seek_timestamp = timestamp.as_micros() as i64; // AV_TIME_BASE is microseconds
+    if let Err(e) = input_ctx.seek(seek_timestamp, ..seek_timestamp) {
+        // The demuxer refuses to seek in a stream without samples
+        log::debug!("Failed to seek to {:.2}s: {}", timestamp.as_secs_f64(), e);
+        return Ok(None);
+    }
+    decoder.flush();
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != video_stream_index || !packet.is_key() {
+            continue;
+        }
+
+        decoder.send_packet(&packet)
+            .map_err(|e| Error::FFmpeg(format!("Decoder send failed: {}", e)))?;
+
+        if decoder.receive_frame(decoded_frame).is_ok() {
+            return Ok(Some(decoded_frame.pts().or(packet.pts()).unwrap_or(0)));
+        }
+    }
+
+    // Some decoders hold the keyframe back until they are drained
+    let _ = decoder.send_eof();
+    if decoder.receive_frame(decoded_frame).is_ok() {
+        return Ok(Some(decoded_frame.pts().unwrap_or(0)));
+    }
+
+    Ok(None)
+}
+
+/// Timestamps at the center of `count` equal slices of `duration`
+fn thumbnail_timestamps(duration: Duration, count: usize) -> Vec<Duration> {
+    (0..count)
+        .map(|i| duration.mul_f64((i as f64 + 0.5) / count as f64))
+        .collect()
+}
+
+/// Thumbnail size for a target width, keeping the aspect ratio with even dimensions
+fn thumbnail_size(src_width: u32, src_height: u32, width: u32) -> (u32, u32) {
+    let width = width.min(src_width).max(2) & !1;
+    let height = ((src_height as u64 * width as u64) / src_width.max(1) as u64) as u32;
+    (width, height.max(2) & !1)
+}
+
 /// Save frame as image file (PNG, JPG, etc.)
 ///
 /// # Arguments
@@ -373,4 +570,156 @@ mod tests {
         // let frame = extract_frame_at_time("test.mp4", 5.0).unwrap();
         // assert_eq!(frame.width, 1920);
     }
+
+    #[test]
+    fn test_thumbnail_timestamps() {
+        let timestamps = thumbnail_timestamps(Duration::from_secs(10), 5);
+        assert_eq!(
+            timestamps,
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(3),
+                Duration::from_secs(5),
+                Duration::from_secs(7),
+                Duration::from_secs(9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thumbnail_size() {
+        assert_eq!(thumbnail_size(1920, 1080, 160), (160, 90));
+        assert_eq!(thumbnail_size(1920, 1080, 4000), (1920, 1080));
+        assert_eq!(thumbnail_size(1280, 720, 161), (160, 90));
+    }
+
+    /// Encode `frames` frames of 64x48 H.264 at 10 fps with a keyframe every `gop` frames
+    fn encode_test_clip(path: &Path, frames: u32, gop: u32) {
+        use crate::encode::{self, StreamTarget};
+
+        ffmpeg::init().unwrap();
+
+        let time_base = ffmpeg::Rational::new(1, 10);
+        let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::H264).unwrap();
+        let mut output_ctx = ffmpeg::format::output(&path).unwrap();
+
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .unwrap();
+        encoder.set_width(64);
+        encoder.set_height(48);
+        encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+        encoder.set_time_base(time_base);
+        encoder.set_frame_rate(Some(ffmpeg::Rational::new(10, 1)));
+        encoder.set_gop(gop);
+        encoder.set_max_b_frames(0);
+        if output_ctx.format().flags().contains(ffmpeg::format::Flags::GLOBAL_HEADER) {
+            encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+        }
+
+        // No scene cut keyframes, only the ones of the GOP
+        let mut encoder_opts = ffmpeg::Dictionary::new();
+        encoder_opts.set("preset", "ultrafast");
+        encoder_opts.set("sc_threshold", "0");
+        let mut encoder = encoder.open_with(encoder_opts).unwrap();
+
+        output_ctx.add_stream(codec).unwrap().set_parameters(&encoder);
+        output_ctx.write_header().unwrap();
+
+        let target = StreamTarget {
+            stream_index: 0,
+            encoder_time_base: time_base,
+            output_time_base: output_ctx.stream(0).unwrap().time_base(),
+        };
+
+        let mut frame = ffmpeg::frame::Video::new(ffmpeg::format::Pixel::YUV420P, 64, 48);
+        for index in 0..frames {
+            frame.data_mut(0).fill((index * 8) as u8);
+            frame.data_mut(1).fill(128);
+            frame.data_mut(2).fill(128);
+            frame.set_pts(Some(index as i64));
+
+            encoder.send_frame(&frame).unwrap();
+            encode::write_packets(&mut encoder, &mut output_ctx, &target).unwrap();
+        }
+
+        encode::finish_encoder(&mut encoder, &mut output_ctx, &target).unwrap();
+        output_ctx.write_trailer().unwrap();
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("video-utils-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn millis(thumbnails: &[VideoFrame]) -> Vec<u128> {
+        thumbnails.iter().map(|thumbnail| thumbnail.pts.as_millis()).collect()
+    }
+
+    #[test]
+    fn test_generate_thumbnails() {
+        let dir = test_dir("thumbnails");
+        let path = dir.join("clip.mp4");
+
+        // 2 seconds with keyframes at 0, 0.5, 1 and 1.5 seconds
+        encode_test_clip(&path, 20, 5);
+
+        let thumbnails = generate_thumbnails(&path, 4, 32).unwrap();
+        let many = generate_thumbnails(&path, 12, 32).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(millis(&thumbnails), vec![0, 500, 1000, 1500]);
+        assert_eq!(
+            thumbnails.iter().map(|thumbnail| thumbnail.frame_number).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        for thumbnail in &thumbnails {
+            assert_eq!((thumbnail.width, thumbnail.height), (32, 24));
+            assert_eq!(thumbnail.data.len(), 32 * 24 * 3);
+        }
+
+        // More thumbnails than keyframes give one thumbnail per keyframe
+        assert_eq!(millis(&many), vec![0, 500, 1000, 1500]);
+    }
+
+    #[test]
+    fn test_generate_thumbnails_empty_video() {
+        let dir = test_dir("thumbnails-empty");
+        let path = dir.join("empty.mp4");
+        encode_test_clip(&path, 0, 5);
+
+        let thumbnails = generate_thumbnails(&path, 4, 32).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(thumbnails.is_empty());
+    }
+
+    #[test]
+    fn test_seek_keyframe_past_end() {
+        let dir = test_dir("thumbnails-past-end");
+        let path = dir.join("clip.mp4");
+        encode_test_clip(&path, 20, 5);
+
+        let mut input_ctx = ffmpeg::format::input(&path).unwrap();
+        let stream = input_ctx.streams().best(ffmpeg::media::Type::Video).unwrap();
+        let (stream_index, time_base) = (stream.index(), stream.time_base());
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())
+            .unwrap()
+            .decoder()
+            .video()
+            .unwrap();
+
+        let mut decoded = ffmpeg::frame::Video::empty();
+        let last = seek_keyframe(&mut input_ctx, &mut decoder, stream_index, Duration::from_secs(60), &mut decoded).unwrap();
+        // Seeking back after the end of the stream still works
+        let first = seek_keyframe(&mut input_ctx, &mut decoder, stream_index, Duration::ZERO, &mut decoded).unwrap();
+        drop(input_ctx);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let secs = |pts: i64| pts as f64 * f64::from(time_base);
+        assert_eq!(last.map(secs), Some(1.5));
+        assert_eq!(first.map(secs), Some(0.0));
+    }
 }
@@ -0,0 +1,349 @@
+//! Peak/RMS waveform extraction with an on-disk cache
+
+use crate::audio_extraction::decode_audio_mono;
+use crate::{Error, Result};
+use derivative::Derivative;
+use derive_setters::Setters;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CACHE_MAGIC: &[u8; 4] = b"WVFM";
+const CACHE_VERSION: u32 = 1;
+
+/// Configuration for waveform extraction
+#[derive(Debug, Clone, Derivative, Setters)]
+#[derivative(Default)]
+#[setters(prefix = "with_")]
+#[non_exhaustive]
+pub struct WaveformConfig {
+    /// Number of buckets per second of audio
+    #[derivative(Default(value = "100"))]
+    pub buckets_per_second: u32,
+
+    /// Directory for cached waveforms (None = no caching)
+    #[setters(strip_option)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl WaveformConfig {
+    /// Create a new waveform config (convenience method)
+    pub fn new(buckets_per_second: u32) -> Self {
+        Self::default().with_buckets_per_second(buckets_per_second)
+    }
+}
+
+/// Waveform of a whole file, one peak and RMS value per bucket, both in [0.0, 1.0]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    /// Number of buckets per second of audio
+    pub buckets_per_second: u32,
+    /// Maximum absolute sample value of each bucket
+    pub peaks: Vec<f32>,
+    /// Root mean square of each bucket
+    pub rms: Vec<f32>,
+}
+
+impl Waveform {
+    /// Duration covered by the waveform
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.peaks.len() as f64 / self.buckets_per_second.max(1) as f64)
+    }
+
+    /// Peak buckets between `start` and `end`
+    pub fn peaks_in(&self, start: Duration, end: Duration) -> &[f32] {
+        &self.peaks[self.bucket_range(start, end)]
+    }
+
+    /// RMS buckets between `start` and `end`
+    pub fn rms_in(&self, start: Duration, end: Duration) -> &[f32] {
+        &self.rms[self.bucket_range(start, end)]
+    }
+
+    fn bucket_range(&self, start: Duration, end: Duration) -> std::ops::Range<usize> {
+        let index = |time: Duration| {
+            ((time.as_secs_f64() * self.buckets_per_second as f64) as usize).min(self.peaks.len())
+        };
+
+        let (start, end) = (index(start), index(end));
+        start..end.max(start)
+    }
+
+    fn write_to(&self, path: &Path) -> Result<()> {
+        let mut data = Vec::with_capacity(20 + self.peaks.len() * 8);
+        data.extend_from_slice(CACHE_MAGIC);
+        data.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        data.extend_from_slice(&self.buckets_per_second.to_le_bytes());
+        data.extend_from_slice(&(self.peaks.len() as u64).to_le_bytes());
+
+        for value in self.peaks.iter().chain(&self.rms) {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // Write to a temporary file first so readers never see a partial cache
+        let tmp_path = path.with_extension("tmp");
+        fs::File::create(&tmp_path)?.write_all(&data)?;
+        fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    fn read_from(path: &Path) -> Result<Self> {
+        let mut data = vec![];
+        fs::File::open(path)?.read_to_end(&mut data)?;
+
+        let invalid = || {
+            Error::IO(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid waveform cache: {}", path.display()),
+            ))
+        };
+
+        if data.len() < 20 || &data[..4] != CACHE_MAGIC {
+            return Err(invalid());
+        }
+
+        let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        let buckets_per_second = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        let count = u64::from_le_bytes(data[12..20].try_into().unwrap()) as usize;
+
+        if version != CACHE_VERSION || data.len() != 20 + count * 8 {
+            return Err(invalid());
+        }
+
+        let mut values = data[20..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let rms = values.split_off(count);
+
+        Ok(Self {
+            buckets_per_second,
+            peaks: values,
+            rms,
+        })
+    }
+}
+
+/// Accumulates mono samples into peak/RMS buckets
+struct WaveformBuilder {
+    sample_rate: u32,
+    buckets_per_second: u32,
+    sample_index: u64,
+    bucket_index: u64,
+    peak: f32,
+    sum_squares: f64,
+    count: u64,
+    peaks: Vec<f32>,
+    rms: Vec<f32>,
+}
+
+impl WaveformBuilder {
+    fn new(sample_rate: u32, buckets_per_second: u32) -> Self {
+        Self {
+            sample_rate,
+            buckets_per_second,
+            sample_index: 0,
+            bucket_index: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            count: 0,
+            peaks: vec![],
+            rms: vec![],
+        }
+    }
+
+    fn add_samples(&mut self, samples: &[f32]) {
+        for sample in samples {
+            // Integer math so that bucket boundaries never drift on long files
+            let bucket_index =
+                self.sample_index * self.buckets_per_second as u64 / self.sample_rate as u64;
+
+            while self.bucket_index < bucket_index {
+                self.finish_bucket();
+            }
+
+            self.peak = self.peak.max(sample.abs().min(1.0));
+            self.sum_squares += (*sample as f64) * (*sample as f64);
+            self.count += 1;
+            self.sample_index += 1;
+        }
+    }
+
+    fn finish_bucket(&mut self) {
+        self.peaks.push(self.peak);
+        self.rms.push(if self.count > 0 {
+            ((self.sum_squares / self.count as f64).sqrt() as f32).min(1.0)
+        } else {
+            0.0
+        });
+
+        self.peak = 0.0;
+        self.sum_squares = 0.0;
+        self.count = 0;
+        self.bucket_index += 1;
+    }
+
+    fn finish(mut self) -> Waveform {
+        if self.count > 0 {
+            self.finish_bucket();
+        }
+
+        Waveform {
+            buckets_per_second: self.buckets_per_second,
+            peaks: self.peaks,
+            rms: self.rms,
+        }
+    }
+}
+
+/// Extract the peak/RMS waveform of the audio in a media file
+///
+/// With `cache_dir` set, the waveform is stored on disk and reused as long as the
+/// media file is unchanged, so repeated calls on long files are cheap.
+///
+/// # Arguments
+///
+/// * `media_path` - Path to the media file
+/// * `config` - Waveform configuration
+///
+/// # Returns
+///
+/// Returns the `Waveform` of the whole file
+///
+/// # Example
+///
+/// ```no_run
+/// use video_utils::waveform::{extract_waveform, WaveformConfig};
+/// use std::time::Duration;
+///
+/// let config = WaveformConfig::new(50).with_cache_dir("/tmp/waveforms".into());
+/// let waveform = extract_waveform("video.mp4", &config).unwrap();
+///
+/// let segment = waveform.peaks_in(Duration::from_secs(10), Duration::from_secs(15));
+/// println!("{} buckets, {:?}", segment.len(), waveform.duration());
+/// ```
+pub fn extract_waveform<P: AsRef<Path>>(media_path: P, config: &WaveformConfig) -> Result<Waveform> {
+    let media_path = media_path.as_ref();
+
+    if config.buckets_per_second == 0 {
+        return Err(Error::InvalidConfig("Buckets per second must be greater than 0".to_string()));
+    }
+
+    let cache_path = match config.cache_dir {
+        Some(ref cache_dir) => Some(cache_path(cache_dir, media_path, config.buckets_per_second)?),
+        None => None,
+    };
+
+    if let Some(ref cache_path) = cache_path
+        && cache_path.exists()
+    {
+        match Waveform::read_from(cache_path) {
+            Ok(waveform) => {
+                log::debug!("Load waveform from cache: {}", cache_path.display());
+                return Ok(waveform);
+            }
+            Err(e) => log::warn!("Ignore waveform cache: {}", e),
+        }
+    }
+
+    log::info!(
+        "Extracting waveform from {} ({} buckets/s)",
+        media_path.display(),
+        config.buckets_per_second
+    );
+
+    let mut builder: Option<WaveformBuilder> = None;
+    decode_audio_mono(media_path, |sample_rate, samples| {
+        builder
+            .get_or_insert_with(|| WaveformBuilder::new(sample_rate, config.buckets_per_second))
+            .add_samples(samples);
+    })?;
+
+    let waveform = builder
+        .map(WaveformBuilder::finish)
+        .unwrap_or(Waveform {
+            buckets_per_second: config.buckets_per_second,
+            peaks: vec![],
+            rms: vec![],
+        });
+
+    if let Some(ref cache_path) = cache_path {
+        if let Some(dir) = cache_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        if let Err(e) = waveform.write_to(cache_path) {
+            log::warn!("Write waveform cache {} failed: {}", cache_path.display(), e);
+        }
+    }
+
+    Ok(waveform)
+}
+
+/// Cache file path derived from the media path, size, modification time and resolution
+fn cache_path(cache_dir: &Path, media_path: &Path, buckets_per_second: u32) -> Result<PathBuf> {
+    let metadata = fs::metadata(media_path)?;
+
+    let mut hasher = DefaultHasher::new();
+    media_path.canonicalize()?.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    buckets_per_second.hash(&mut hasher);
+
+    let stem = media_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(cache_dir.join(format!("{}-{:016x}.waveform", stem, hasher.finish())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_builder() {
+        let mut builder = WaveformBuilder::new(8, 2);
+        builder.add_samples(&[0.5, -0.5, 0.5, -0.5]);
+        builder.add_samples(&[1.0, 0.0, 0.0, 0.0, 0.25]);
+        let waveform = builder.finish();
+
+        assert_eq!(waveform.peaks, vec![0.5, 1.0, 0.25]);
+        assert_eq!(waveform.rms, vec![0.5, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_waveform_ranges() {
+        let waveform = Waveform {
+            buckets_per_second: 2,
+            peaks: vec![0.1, 0.2, 0.3, 0.4],
+            rms: vec![0.0; 4],
+        };
+
+        assert_eq!(waveform.duration(), Duration::from_secs(2));
+        assert_eq!(waveform.peaks_in(Duration::from_millis(500), Duration::from_millis(1500)), &[0.2, 0.3]);
+        assert_eq!(waveform.peaks_in(Duration::from_secs(1), Duration::from_secs(10)), &[0.3, 0.4]);
+        assert!(waveform.rms_in(Duration::from_secs(3), Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_waveform_cache_roundtrip() {
+        let waveform = Waveform {
+            buckets_per_second: 10,
+            peaks: vec![0.1, 0.9, 0.5],
+            rms: vec![0.05, 0.6, 0.3],
+        };
+
+        let path = std::env::temp_dir().join(format!("video-utils-waveform-{}.waveform", std::process::id()));
+        waveform.write_to(&path).unwrap();
+        let loaded = Waveform::read_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, waveform);
+    }
+}
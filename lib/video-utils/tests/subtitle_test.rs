@@ -1,6 +1,10 @@
 // cargo test -p video-utils --test subtitle_test
 
-use video_utils::subtitle::chinese_numbers_to_primitive_numbers;
+use video_utils::subtitle::{
+    AssStyle, Subtitle, ass_timestamp_to_ms, chinese_numbers_to_primitive_numbers, convert_subtitles,
+    load_subtitles, ms_to_ass_timestamp, ms_to_vtt_timestamp, parse_ass, parse_srt, parse_vtt,
+    save_as_srt, srt_timestamp_to_ms, valid_srt_timestamp, vtt_timestamp_to_ms,
+};
 
 #[test]
 fn test_chinese_numbers_simple() {
@@ -184,3 +188,106 @@ fn test_non_standard_formats() {
         "主要针对的平台是叉86杠642614，还有power P C64。"
     );
 }
+
+#[test]
+fn test_subtitle_timestamps() {
+    assert_eq!(ms_to_vtt_timestamp(3_723_456), "01:02:03.456");
+    assert_eq!(ms_to_ass_timestamp(3_723_456), "1:02:03.45");
+    assert_eq!(vtt_timestamp_to_ms("01:02:03.456").unwrap(), 3_723_456);
+    assert_eq!(vtt_timestamp_to_ms("02:03.456").unwrap(), 123_456);
+    assert_eq!(ass_timestamp_to_ms("1:02:03.45").unwrap(), 3_723_450);
+    assert_eq!(ass_timestamp_to_ms("0:00:01.5").unwrap(), 1_500);
+}
+
+#[test]
+fn test_srt_timestamp() {
+    assert_eq!(srt_timestamp_to_ms("01:02:03,456").unwrap(), 3_723_456);
+    assert_eq!(srt_timestamp_to_ms("00:00:00,000").unwrap(), 0);
+
+    // Only the strict `HH:MM:SS,mmm` form
+    for timestamp in [
+        "01:02:03.456",
+        "1:02:03,456",
+        "02:03,456",
+        "01:02:03,45",
+        "01:02:03",
+        " 01:02:03,456",
+        "01:60:03,456",
+        "０1:02:03,456",
+    ] {
+        assert!(!valid_srt_timestamp(timestamp), "{timestamp}");
+    }
+}
+
+#[test]
+fn test_parse_srt() {
+    let contents = "1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n";
+    let subtitles = parse_srt(contents).unwrap();
+
+    assert_eq!(subtitles.len(), 2);
+    assert_eq!(subtitles[0].start_timestamp, 1_000);
+    assert_eq!(subtitles[0].end_timestamp, 2_500);
+    assert_eq!(subtitles[0].text, "Hello\nworld");
+    assert_eq!(subtitles[1].index, 2);
+}
+
+#[test]
+fn test_parse_vtt() {
+    let contents = "WEBVTT\n\nNOTE a comment\n\nintro\n00:01.000 --> 00:02.000 align:start\n<v Bob><i>Hi</i> there\n\n00:00:03.000 --> 00:00:04.250\nBye\n";
+    let subtitles = parse_vtt(contents).unwrap();
+
+    assert_eq!(subtitles.len(), 2);
+    assert_eq!(subtitles[0].start_timestamp, 1_000);
+    assert_eq!(subtitles[0].text, "<i>Hi</i> there");
+    assert_eq!(subtitles[1].end_timestamp, 4_250);
+}
+
+#[test]
+fn test_parse_ass() {
+    let contents = "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,skip\nDialogue: 0,0:00:01.50,0:00:03.00,Default,,0,0,0,,{\\pos(10,10)}Hello, {\\i1}world{\\i0}\\Nagain\n";
+    let subtitles = parse_ass(contents).unwrap();
+
+    assert_eq!(subtitles.len(), 1);
+    assert_eq!(subtitles[0].start_timestamp, 1_500);
+    assert_eq!(subtitles[0].end_timestamp, 3_000);
+    assert_eq!(subtitles[0].text, "Hello, <i>world</i>\nagain");
+}
+
+#[test]
+fn test_convert_subtitles_roundtrip() {
+    let subtitles = vec![
+        Subtitle {
+            index: 1,
+            start_timestamp: 1_230,
+            end_timestamp: 2_500,
+            text: "Hello, <b>world</b>".to_string(),
+        },
+        Subtitle {
+            index: 2,
+            start_timestamp: 3_000,
+            end_timestamp: 4_560,
+            text: "Line one\nLine two".to_string(),
+        },
+    ];
+
+    let dir = std::env::temp_dir().join(format!("video-utils-subtitle-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (srt, vtt, ass) = (dir.join("a.srt"), dir.join("a.vtt"), dir.join("a.ass"));
+
+    save_as_srt(&subtitles, &srt).unwrap();
+    convert_subtitles(&srt, &vtt, &AssStyle::default()).unwrap();
+    convert_subtitles(&vtt, &ass, &AssStyle::default().with_font_size(32)).unwrap();
+
+    let from_vtt = load_subtitles(&vtt).unwrap();
+    let from_ass = load_subtitles(&ass).unwrap();
+    assert!(std::fs::read_to_string(&ass).unwrap().contains("Style: Default,Arial,32,"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(from_vtt, subtitles);
+    for (loaded, original) in from_ass.iter().zip(&subtitles) {
+        assert_eq!(loaded.start_timestamp, original.start_timestamp);
+        assert_eq!(loaded.end_timestamp, original.end_timestamp);
+        assert_eq!(loaded.text, original.text);
+    }
+    assert_eq!(from_ass.len(), 2);
+}
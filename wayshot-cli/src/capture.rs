@@ -0,0 +1,205 @@
+use crate::profile;
+use anyhow::{Context, Result, anyhow, bail};
+use recorder::{
+    AsyncErrorChannel, AudioRecorder, ChapterMarker, ProcessMode, RecorderConfig, RecordingSession,
+    platform_screen_capture,
+};
+use screen_capture::{CaptureStreamConfig, ScreenCapture, ScreenInfo};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use tokio::runtime::Handle;
+
+pub fn find_screen(name: Option<&str>) -> Result<ScreenInfo> {
+    let screens = platform_screen_capture().available_screens()?;
+
+    match name {
+        Some(name) => {
+            let names = screens
+                .iter()
+                .map(|screen| screen.name.clone())
+                .collect::<Vec<_>>();
+
+            screens
+                .into_iter()
+                .find(|screen| screen.name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "no found screen `{name}`, available screens: {}",
+                        names.join(", ")
+                    )
+                })
+        }
+        None => screens
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("available screen no found")),
+    }
+}
+
+pub fn recorder_config(
+    capture: &profile::Capture,
+    process_mode: ProcessMode,
+    save_path: PathBuf,
+) -> Result<RecorderConfig> {
+    let screen = find_screen(capture.screen.as_deref())?;
+    log::debug!("screen_info: {screen:?}");
+
+    let audio_device_name = match capture.audio_device.as_deref() {
+        Some("default") => {
+            let device = AudioRecorder::new()
+                .get_default_input_device()?
+                .ok_or_else(|| anyhow!("Default input device no found"))?;
+            Some(device.name)
+        }
+        Some(name) => Some(name.to_string()),
+        None => None,
+    };
+
+    let resolution = capture.resolution.to_resolution(
+        screen.logical_size.width as u32,
+        screen.logical_size.height as u32,
+    );
+
+    Ok(
+        RecorderConfig::new(screen.name, screen.logical_size, save_path)
+            .with_process_mode(process_mode)
+            .with_fps(profile::fps(capture.fps)?)
+            .with_resolution(resolution)
+            .with_include_cursor(capture.include_cursor)
+            .with_audio_device_name(audio_device_name)
+            .with_enable_recording_speaker(capture.enable_speaker)
+            .with_enable_denoise(capture.enable_denoise)
+            .with_convert_to_mono(capture.convert_to_mono)
+            .with_countdown(capture.countdown),
+    )
+}
+
+/// Runs the session until Ctrl-C, SIGTERM, an error of the stream, or the
+/// duration is elapsed, returns the path of the saved video. Each line of the
+/// standard input marks a chapter while a mp4 file is saved
+pub fn run_session(
+    rt_handle: Handle,
+    config: RecorderConfig,
+    duration: Option<Duration>,
+) -> Result<PathBuf> {
+    let (async_error_sender, mut async_error_receiver) = AsyncErrorChannel(16);
+    let config = config.with_async_error_sender(Some(async_error_sender));
+    log::debug!("Recording configuration: {config:#?}");
+
+    // The duration is counted from the end of the countdown
+    let duration = duration.map(|d| d + Duration::from_secs(config.countdown as u64));
+
+    let mut session = RecordingSession::new(config);
+    let stop_sig = session.get_stop_sig();
+    stop_on_signal(stop_sig.clone(), duration)?;
+
+    let async_error = Arc::new(Mutex::new(None));
+    rt_handle.spawn({
+        let stop_sig = stop_sig.clone();
+        let async_error = async_error.clone();
+
+        async move {
+            if let Some(err) = async_error_receiver.recv().await {
+                *async_error.lock().unwrap() = Some(err);
+                stop_sig.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let save_path = session.save_path();
+    session.start(rt_handle, platform_screen_capture())?;
+    log::info!("start recording...");
+
+    if let Some(marker) = session.get_chapter_marker() {
+        mark_chapters_from_stdin(marker);
+    }
+
+    session.wait()?;
+
+    if let Some(err) = async_error.lock().unwrap().take() {
+        bail!(err);
+    }
+
+    Ok(save_path)
+}
+
+/// Captures a frame of the screen and saves it as a png file
+pub fn screenshot(screen: Option<&str>, include_cursor: bool, path: &Path) -> Result<()> {
+    let screen = find_screen(screen)?;
+    let cancel_sig = Arc::new(AtomicBool::new(false));
+    let mut frame = None;
+
+    let config = CaptureStreamConfig {
+        name: screen.name,
+        include_cursor,
+        fps: None,
+        cancel_sig: cancel_sig.clone(),
+        sync_sig: Arc::new(AtomicBool::new(false)),
+    };
+
+    platform_screen_capture().capture_output_stream(config, |data| {
+        if frame.is_none() {
+            frame = Some(data.data);
+        }
+        cancel_sig.store(true, Ordering::Relaxed);
+    })?;
+
+    let Some(frame) = frame else {
+        bail!("no frame is captured");
+    };
+
+    let image = image::RgbaImage::from_raw(frame.width, frame.height, frame.pixel_data)
+        .ok_or_else(|| anyhow!("invalid frame size {}x{}", frame.width, frame.height))?;
+
+    image
+        .save(path)
+        .with_context(|| format!("save screenshot {} failed", path.display()))?;
+
+    Ok(())
+}
+
+// The line is the title of the chapter, an empty line is titled by the number
+fn mark_chapters_from_stdin(marker: ChapterMarker) {
+    thread::spawn(move || {
+        for (index, line) in io::stdin().lines().map_while(|line| line.ok()).enumerate() {
+            let title = match line.trim() {
+                "" => format!("Chapter {}", index + 1),
+                title => title.to_string(),
+            };
+
+            log::info!("add chapter: {title}");
+            marker.add(title);
+        }
+    });
+}
+
+fn stop_on_signal(stop_sig: Arc<AtomicBool>, duration: Option<Duration>) -> Result<()> {
+    ctrlc::set_handler({
+        let stop_sig = stop_sig.clone();
+        move || {
+            log::info!("stopping recording...");
+            stop_sig.store(true, Ordering::Relaxed);
+        }
+    })?;
+
+    if let Some(duration) = duration {
+        thread::spawn(move || {
+            thread::sleep(duration);
+            log::info!(
+                "{} seconds elapsed, stopping recording...",
+                duration.as_secs()
+            );
+            stop_sig.store(true, Ordering::Relaxed);
+        });
+    }
+
+    Ok(())
+}
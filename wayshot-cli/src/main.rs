@@ -0,0 +1,344 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clipboard_utils::Content;
+use recorder::{ProcessMode, RecorderConfig};
+use std::{path::PathBuf, sync::atomic::AtomicBool, time::Duration};
+
+mod capture;
+mod profile;
+mod transcribe;
+
+use profile::{Profile, ResolutionProfile};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "wayshot-cli",
+    version,
+    about = "Record, stream, take screenshots and transcribe without the GUI.",
+    long_about = None
+)]
+struct Cli {
+    /// TOML profile, the options of the command line override it
+    #[arg(short, long, global = true)]
+    profile: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Record the screen to a mp4 file. Each line typed on the standard
+    /// input marks a chapter titled by it
+    Record {
+        #[command(flatten)]
+        capture: CaptureArgs,
+
+        /// Output file, a timestamp named file in the save directory by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Copy the video file to the clipboard. On Wayland the command stays
+        /// until the clipboard is replaced
+        #[arg(long)]
+        clipboard: bool,
+    },
+
+    /// Push the screen to a RTMP server, or share it via WebRTC
+    Stream {
+        #[command(flatten)]
+        capture: CaptureArgs,
+
+        #[arg(long, value_enum, default_value_t = Protocol::Rtmp)]
+        protocol: Protocol,
+
+        /// RTMP url: rtmp://[host]:[port]/[app]/[stream_key]?[query_params]
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Address the WebRTC server listens on
+        #[arg(long)]
+        listen_addr: Option<String>,
+
+        /// Token the WebRTC clients are authorized with
+        #[arg(long)]
+        auth_token: Option<String>,
+
+        /// Don't save the stream to a mp4 file
+        #[arg(long)]
+        no_save: bool,
+    },
+
+    /// Take a screenshot as a png file
+    Screenshot {
+        /// Name of the screen, the first screen by default
+        #[arg(short, long)]
+        screen: Option<String>,
+
+        /// Don't capture the cursor
+        #[arg(long)]
+        no_cursor: bool,
+
+        /// Seconds to wait before taking the screenshot
+        #[arg(long, default_value_t = 0)]
+        delay: u32,
+
+        /// Output file, a timestamp named file in the save directory by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Copy the image to the clipboard. On Wayland the command stays until
+        /// the clipboard is replaced
+        #[arg(long)]
+        clipboard: bool,
+    },
+
+    /// Transcribe the audio of a media file to subtitles
+    Transcribe {
+        /// Audio or video file
+        input: PathBuf,
+
+        /// Subtitle file, its format is chosen by the extension: srt, vtt or ass.
+        /// The subtitles are printed as SRT without it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[arg(long)]
+        model_path: Option<PathBuf>,
+
+        #[arg(long)]
+        tokenizer_path: Option<PathBuf>,
+
+        /// Speaker embedding model, the speakers are told apart with it
+        #[arg(long)]
+        speaker_model_path: Option<PathBuf>,
+
+        /// Words recognized preferentially, separated by commas
+        #[arg(long, value_delimiter = ',')]
+        hotwords: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct CaptureArgs {
+    /// Name of the screen, the first screen by default
+    #[arg(short, long)]
+    screen: Option<String>,
+
+    /// 24, 25, 30 or 60
+    #[arg(long)]
+    fps: Option<u32>,
+
+    #[arg(long, value_enum)]
+    resolution: Option<ResolutionProfile>,
+
+    /// Name of the input device, `default` for the default input device
+    #[arg(long)]
+    audio_device: Option<String>,
+
+    /// Record the desktop audio
+    #[arg(long)]
+    speaker: bool,
+
+    /// Reduce the noise of the input device
+    #[arg(long)]
+    denoise: bool,
+
+    /// Convert the audio to mono
+    #[arg(long)]
+    mono: bool,
+
+    /// Don't capture the cursor
+    #[arg(long)]
+    no_cursor: bool,
+
+    /// Directory the videos are saved to
+    #[arg(long)]
+    save_dir: Option<PathBuf>,
+
+    /// Seconds to wait before capturing
+    #[arg(long)]
+    countdown: Option<u32>,
+
+    /// Stop after the seconds, or on Ctrl-C
+    #[arg(short, long)]
+    duration: Option<u64>,
+}
+
+impl CaptureArgs {
+    fn apply(self, capture: &mut profile::Capture) -> Option<Duration> {
+        if let Some(screen) = self.screen {
+            capture.screen = Some(screen);
+        }
+        if let Some(fps) = self.fps {
+            capture.fps = fps;
+        }
+        if let Some(resolution) = self.resolution {
+            capture.resolution = resolution;
+        }
+        if let Some(audio_device) = self.audio_device {
+            capture.audio_device = Some(audio_device);
+        }
+        if let Some(save_dir) = self.save_dir {
+            capture.save_dir = save_dir;
+        }
+        if let Some(countdown) = self.countdown {
+            capture.countdown = countdown;
+        }
+
+        capture.enable_speaker |= self.speaker;
+        capture.enable_denoise |= self.denoise;
+        capture.convert_to_mono |= self.mono;
+        capture.include_cursor &= !self.no_cursor;
+
+        self.duration.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Protocol {
+    Rtmp,
+    Webrtc,
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    rustls::crypto::CryptoProvider::install_default(rustls::crypto::ring::default_provider())
+        .expect("failed to set crypto provider");
+
+    let cli = Cli::parse();
+    let mut profile = match cli.profile {
+        Some(path) => Profile::load(path)?,
+        None => Profile::default(),
+    };
+
+    let rt = tokio::runtime::Runtime::new()?;
+
+    match cli.command {
+        Command::Record {
+            capture,
+            output,
+            clipboard,
+        } => {
+            let duration = capture.apply(&mut profile.capture);
+            let save_path =
+                output.unwrap_or_else(|| RecorderConfig::make_filename(&profile.capture.save_dir));
+
+            let config =
+                capture::recorder_config(&profile.capture, ProcessMode::RecordScreen, save_path)?;
+            let save_path = capture::run_session(rt.handle().clone(), config, duration)?;
+            println!("{}", save_path.display());
+
+            if clipboard {
+                copy_to_clipboard(Content::Files(vec![std::path::absolute(save_path)?]))?;
+            }
+        }
+        Command::Stream {
+            capture,
+            protocol,
+            url,
+            listen_addr,
+            auth_token,
+            no_save,
+        } => {
+            let duration = capture.apply(&mut profile.capture);
+            let save_path = RecorderConfig::make_filename(&profile.capture.save_dir);
+
+            let (config, save_mp4) = match protocol {
+                Protocol::Rtmp => {
+                    if let Some(url) = url {
+                        profile.rtmp.url = Some(url);
+                    }
+                    profile.rtmp.save_mp4 &= !no_save;
+
+                    let config = capture::recorder_config(
+                        &profile.capture,
+                        ProcessMode::PushStream,
+                        save_path,
+                    )?
+                    .with_push_stream_config(profile.rtmp.to_config()?);
+
+                    (config, profile.rtmp.save_mp4)
+                }
+                Protocol::Webrtc => {
+                    if let Some(listen_addr) = listen_addr {
+                        profile.webrtc.listen_addr = listen_addr;
+                    }
+                    if let Some(auth_token) = auth_token {
+                        profile.webrtc.auth_token = Some(auth_token);
+                    }
+                    profile.webrtc.save_mp4 &= !no_save;
+
+                    let config = capture::recorder_config(
+                        &profile.capture,
+                        ProcessMode::ShareScreen,
+                        save_path,
+                    )?
+                    .with_share_screen_config(profile.webrtc.to_config());
+
+                    (config, profile.webrtc.save_mp4)
+                }
+            };
+
+            let save_path = capture::run_session(rt.handle().clone(), config, duration)?;
+            if save_mp4 {
+                println!("{}", save_path.display());
+            }
+        }
+        Command::Screenshot {
+            screen,
+            no_cursor,
+            delay,
+            output,
+            clipboard,
+        } => {
+            let output = output.unwrap_or_else(|| {
+                RecorderConfig::make_filename(&profile.capture.save_dir).with_extension("png")
+            });
+
+            let screen = screen.or(profile.capture.screen);
+            let include_cursor = profile.capture.include_cursor && !no_cursor;
+
+            if delay > 0 {
+                let stop_sig = AtomicBool::new(false);
+                recorder::countdown(delay, &stop_sig, None);
+            }
+
+            capture::screenshot(screen.as_deref(), include_cursor, &output)?;
+            println!("{}", output.display());
+
+            if clipboard {
+                copy_to_clipboard(Content::Image(std::fs::read(&output)?))?;
+            }
+        }
+        Command::Transcribe {
+            input,
+            output,
+            model_path,
+            tokenizer_path,
+            speaker_model_path,
+            hotwords,
+        } => {
+            let setting = &mut profile.transcribe;
+            setting.model_path = model_path.or(setting.model_path.take());
+            setting.tokenizer_path = tokenizer_path.or(setting.tokenizer_path.take());
+            setting.speaker_model_path = speaker_model_path.or(setting.speaker_model_path.take());
+            setting.hotwords.extend(hotwords);
+
+            transcribe::transcribe(setting, &input, output.as_deref())?;
+        }
+    }
+
+    Ok(())
+}
+
+// The content is served by this process on Wayland, so it waits for another
+// client to take the clipboard before exiting
+fn copy_to_clipboard(content: Content) -> Result<()> {
+    let owner = clipboard_utils::copy(content)?;
+    log::info!("copied to the clipboard");
+
+    owner.wait();
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use recorder::{FPS, PushStreamConfig, Resolution, ShareScreenConfig};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use wrtc::RTCIceServer;
+
+/// Settings of all the subcommands, loaded from a TOML file.
+///
+/// Every section and field is optional, and the options of the command line
+/// override them.
+///
+/// ```toml
+/// [capture]
+/// screen = "eDP-1"
+/// fps = 30
+/// resolution = "1080p"
+/// audio_device = "default"
+///
+/// [rtmp]
+/// url = "rtmp://localhost:1935/live/stream"
+///
+/// [transcribe]
+/// model_path = "Fun-ASR-Nano-2512/model.pt"
+/// tokenizer_path = "Fun-ASR-Nano-2512/Qwen3-0.6B/tokenizer.json"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub capture: Capture,
+    pub rtmp: Rtmp,
+    pub webrtc: WebRtc,
+    pub transcribe: Transcribe,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Capture {
+    /// Directory the recordings and screenshots are saved to
+    pub save_dir: PathBuf,
+
+    /// Name of the screen, the first screen if it's not set
+    pub screen: Option<String>,
+
+    pub fps: u32,
+    pub resolution: ResolutionProfile,
+    pub include_cursor: bool,
+
+    /// Name of the input device, `default` for the default input device
+    pub audio_device: Option<String>,
+
+    pub enable_speaker: bool,
+    pub enable_denoise: bool,
+    pub convert_to_mono: bool,
+
+    /// Seconds to wait before capturing
+    pub countdown: u32,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            save_dir: PathBuf::from("."),
+            screen: None,
+            fps: 25,
+            resolution: ResolutionProfile::Original,
+            include_cursor: true,
+            audio_device: None,
+            enable_speaker: false,
+            enable_denoise: false,
+            convert_to_mono: false,
+            countdown: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+pub enum ResolutionProfile {
+    #[serde(rename = "original")]
+    #[value(name = "original")]
+    Original,
+
+    #[serde(rename = "480p")]
+    #[value(name = "480p")]
+    P480,
+
+    #[serde(rename = "720p")]
+    #[value(name = "720p")]
+    P720,
+
+    #[serde(rename = "1080p")]
+    #[value(name = "1080p")]
+    P1080,
+
+    #[serde(rename = "2k")]
+    #[value(name = "2k")]
+    P2K,
+
+    #[serde(rename = "4k")]
+    #[value(name = "4k")]
+    P4K,
+}
+
+impl ResolutionProfile {
+    pub fn to_resolution(self, screen_width: u32, screen_height: u32) -> Resolution {
+        match self {
+            Self::Original => Resolution::Original((screen_width, screen_height)),
+            Self::P480 => Resolution::P480,
+            Self::P720 => Resolution::P720,
+            Self::P1080 => Resolution::P1080,
+            Self::P2K => Resolution::P2K,
+            Self::P4K => Resolution::P4K,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rtmp {
+    /// rtmp://[host]:[port]/[app]/[stream_key]?[query_params]
+    pub url: Option<String>,
+    pub save_mp4: bool,
+}
+
+impl Default for Rtmp {
+    fn default() -> Self {
+        Self {
+            url: None,
+            save_mp4: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebRtc {
+    pub listen_addr: String,
+    pub auth_token: Option<String>,
+    pub save_mp4: bool,
+
+    pub stun_server: Option<IceServer>,
+    pub turn_server: Option<IceServer>,
+    pub host_ips: Vec<String>,
+    pub disable_host_ipv6: bool,
+
+    pub enable_https: bool,
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+}
+
+impl Default for WebRtc {
+    fn default() -> Self {
+        Self {
+            listen_addr: "0.0.0.0:9090".to_string(),
+            auth_token: None,
+            save_mp4: true,
+            stun_server: None,
+            turn_server: None,
+            host_ips: vec![],
+            disable_host_ipv6: false,
+            enable_https: false,
+            cert_file: None,
+            key_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IceServer {
+    pub url: String,
+    pub username: String,
+    pub credential: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transcribe {
+    pub model_path: Option<PathBuf>,
+    pub tokenizer_path: Option<PathBuf>,
+
+    /// Speaker embedding model, the speakers are told apart if it's set
+    pub speaker_model_path: Option<PathBuf>,
+
+    pub hotwords: Vec<String>,
+    pub min_silence_duration_ms: u32,
+    pub detect_language: bool,
+}
+
+impl Default for Transcribe {
+    fn default() -> Self {
+        Self {
+            model_path: None,
+            tokenizer_path: None,
+            speaker_model_path: None,
+            hotwords: vec![],
+            min_silence_duration_ms: 300,
+            detect_language: false,
+        }
+    }
+}
+
+impl Profile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("read profile {} failed", path.display()))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("parse profile {} failed", path.display()))
+    }
+}
+
+pub fn fps(value: u32) -> Result<FPS> {
+    match value {
+        24 => Ok(FPS::Fps24),
+        25 => Ok(FPS::Fps25),
+        30 => Ok(FPS::Fps30),
+        60 => Ok(FPS::Fps60),
+        _ => bail!("unsupported fps {value}, expected 24, 25, 30 or 60"),
+    }
+}
+
+impl Rtmp {
+    pub fn to_config(&self) -> Result<PushStreamConfig> {
+        let Some(url) = self.url.as_deref() else {
+            bail!("no RTMP url, set it with `--url` or `rtmp.url` of the profile");
+        };
+
+        let config = srtmp::RtmpClientConfig::parse_url(url)
+            .with_context(|| format!("invalid RTMP url `{url}`"))?;
+
+        if config.stream_key.is_empty() {
+            bail!("no stream key in RTMP url `{url}`");
+        }
+
+        Ok(
+            PushStreamConfig::new(config.rtmp_url, config.app, config.stream_key)
+                .with_query_params(config.query_params)
+                .with_save_mp4(self.save_mp4),
+        )
+    }
+}
+
+impl WebRtc {
+    pub fn to_config(&self) -> ShareScreenConfig {
+        ShareScreenConfig::new(self.listen_addr.clone())
+            .with_save_mp4(self.save_mp4)
+            .with_auth_token(self.auth_token.clone())
+            .with_stun_server(self.stun_server.clone().map(RTCIceServer::from))
+            .with_turn_server(self.turn_server.clone().map(RTCIceServer::from))
+            .with_host_ips(self.host_ips.clone())
+            .with_disable_host_ipv6(self.disable_host_ipv6)
+            .with_enable_https(self.enable_https)
+            .with_cert_file(self.cert_file.clone())
+            .with_key_file(self.key_file.clone())
+    }
+}
+
+impl From<IceServer> for RTCIceServer {
+    fn from(server: IceServer) -> Self {
+        RTCIceServer {
+            urls: vec![server.url],
+            username: server.username,
+            credential: server.credential,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_profile() -> Result<()> {
+        let profile: Profile = toml::from_str("")?;
+
+        assert_eq!(profile.capture.save_dir, PathBuf::from("."));
+        assert_eq!(profile.capture.fps, 25);
+        assert_eq!(profile.capture.resolution, ResolutionProfile::Original);
+        assert!(profile.rtmp.url.is_none());
+        assert_eq!(profile.webrtc.listen_addr, "0.0.0.0:9090");
+        assert_eq!(profile.transcribe.min_silence_duration_ms, 300);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_profile() -> Result<()> {
+        let profile: Profile = toml::from_str(
+            r#"
+            [capture]
+            screen = "HDMI-A-1"
+            fps = 60
+            resolution = "720p"
+            audio_device = "default"
+
+            [rtmp]
+            url = "rtmp://localhost:1935/live/stream?token=abc"
+            save_mp4 = false
+
+            [webrtc]
+            listen_addr = "0.0.0.0:8080"
+            stun_server = { url = "stun:stun.l.google.com:19302" }
+
+            [transcribe]
+            hotwords = ["wayshot", "Slint"]
+            "#,
+        )?;
+
+        assert_eq!(profile.capture.screen.as_deref(), Some("HDMI-A-1"));
+        assert_eq!(fps(profile.capture.fps)?, FPS::Fps60);
+        assert_eq!(
+            profile.capture.resolution.to_resolution(1920, 1080),
+            Resolution::P720
+        );
+        assert_eq!(profile.transcribe.hotwords, vec!["wayshot", "Slint"]);
+
+        let rtmp = profile.rtmp.to_config()?;
+        assert_eq!(rtmp.server_addr, "rtmp://localhost:1935");
+        assert_eq!(rtmp.app, "live");
+        assert_eq!(rtmp.stream_key, "stream");
+        assert_eq!(rtmp.query_params, "token=abc");
+        assert!(!rtmp.save_mp4);
+
+        let webrtc = profile.webrtc.to_config();
+        assert_eq!(webrtc.listen_addr, "0.0.0.0:8080");
+        assert_eq!(
+            webrtc.stun_server.map(|server| server.urls),
+            Some(vec!["stun:stun.l.google.com:19302".to_string()])
+        );
+        assert!(webrtc.turn_server.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        assert!(toml::from_str::<Profile>("[capture]\nresolution = \"8k\"").is_err());
+        assert!(toml::from_str::<Profile>("[capture]\nunknown = true").is_err());
+        assert!(fps(50).is_err());
+
+        let rtmp = Rtmp {
+            url: Some("rtmp://localhost:1935/live".to_string()),
+            save_mp4: true,
+        };
+        assert!(rtmp.to_config().is_err());
+        assert!(Rtmp::default().to_config().is_err());
+    }
+}
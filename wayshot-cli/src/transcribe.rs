@@ -0,0 +1,95 @@
+use crate::profile;
+use anyhow::{Context, Result, anyhow};
+use fun_ast_nano::{
+    DEFAULT_HOTWORD_BOOST, DiarizationConfig, FunASRModelConfig, FunAsrNanoGenerateModel,
+    TranscriptionRequest, VadConfig, load_audio_file,
+};
+use std::path::Path;
+use video_utils::subtitle::{AssStyle, Subtitle, save_subtitles, subtitle_to_srt};
+
+const DEFAULT_PROMPT: &str = "Transcribe audio to text.";
+const TRANSCRIBE_BATCH_SIZE: usize = 8;
+
+/// Transcribes the audio of the media file to subtitles, which are saved to
+/// `output` by its extension, or printed as SRT without it
+pub fn transcribe(
+    setting: &profile::Transcribe,
+    input: &Path,
+    output: Option<&Path>,
+) -> Result<()> {
+    let model_path = setting.model_path.as_ref().ok_or_else(|| {
+        anyhow!("no model, set it with `--model-path` or `transcribe.model_path` of the profile")
+    })?;
+
+    let tokenizer_path = setting.tokenizer_path.as_ref().ok_or_else(|| {
+        anyhow!(
+            "no tokenizer, set it with `--tokenizer-path` or `transcribe.tokenizer_path` of the profile"
+        )
+    })?;
+
+    let diarization = setting.speaker_model_path.as_ref().map(|path| {
+        DiarizationConfig::default().with_embedding_model(path.to_string_lossy().to_string())
+    });
+
+    let config = FunASRModelConfig::default()
+        .with_model_weights(model_path.to_string_lossy().to_string())
+        .with_tokenizer_path(tokenizer_path.to_string_lossy().to_string())
+        .with_diarization(diarization)
+        .with_batch_size(TRANSCRIBE_BATCH_SIZE);
+
+    log::info!("Loading transcribe model: {config:?}");
+
+    let audio_config = load_audio_file(input)
+        .with_context(|| format!("load file `{}` failed", input.display()))?;
+    let mut model = FunAsrNanoGenerateModel::new(config, None, None)?;
+
+    let vad_config =
+        VadConfig::default().with_min_silence_duration_ms(setting.min_silence_duration_ms.max(50));
+
+    let request = TranscriptionRequest::default()
+        .with_audio_config(audio_config)
+        .with_prompt(Some(DEFAULT_PROMPT.to_string()))
+        .with_max_tokens(512)
+        .with_hotwords(setting.hotwords.clone(), DEFAULT_HOTWORD_BOOST)
+        .with_detect_language(setting.detect_language)
+        .with_language_prompts(setting.detect_language);
+
+    let mut subtitles = vec![];
+    model.generate(request, Some(vad_config), |chunk| {
+        if chunk.is_finished || chunk.text.trim().is_empty() {
+            return Ok(());
+        }
+
+        if let Some(seg_info) = chunk.segment_info {
+            log::info!(
+                "transcribe segment {}/{}",
+                seg_info.current_segment,
+                seg_info.total_segments
+            );
+
+            let text = match seg_info.speaker_id {
+                Some(id) => format!("Speaker {id}: {}", chunk.text.trim()),
+                None => chunk.text.trim().to_string(),
+            };
+
+            subtitles.push(Subtitle {
+                index: subtitles.len() as u32 + 1,
+                start_timestamp: seg_info.segment_start_ms as u64,
+                end_timestamp: seg_info.segment_end_ms as u64,
+                text,
+            });
+        }
+
+        Ok(())
+    })?;
+
+    match output {
+        Some(output) => save_subtitles(&subtitles, &AssStyle::default(), output)
+            .with_context(|| format!("save subtitles {} failed", output.display()))?,
+        None => subtitles
+            .iter()
+            .for_each(|subtitle| println!("{}\n", subtitle_to_srt(subtitle))),
+    }
+
+    Ok(())
+}
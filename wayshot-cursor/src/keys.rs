@@ -0,0 +1,359 @@
+//! Keyboard events for the keystroke overlay.
+
+use clap::ValueEnum;
+use cursor_client::{KeyEvent, MODIFIER_ALT, MODIFIER_CTRL, MODIFIER_SHIFT, MODIFIER_SUPER};
+use rdev::Key;
+
+/// Which keys are broadcast, the typed text may contain passwords
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyFilter {
+    /// Every key
+    All,
+
+    /// The modifiers, the keys pressed with Ctrl, Alt or Super and the keys
+    /// which don't type text, e.g. Enter or F1. AltGr types text, e.g. `@` or
+    /// `€` on the European layouts, so it's not a modifier of them
+    Shortcuts,
+
+    /// Only the modifiers
+    Modifiers,
+
+    /// No key
+    Off,
+}
+
+// A bit for each physical modifier key, so releasing one of the left and
+// the right keys keeps the modifier held by the other
+const HELD_CONTROL_LEFT: u8 = 1 << 0;
+const HELD_CONTROL_RIGHT: u8 = 1 << 1;
+const HELD_SHIFT_LEFT: u8 = 1 << 2;
+const HELD_SHIFT_RIGHT: u8 = 1 << 3;
+const HELD_ALT: u8 = 1 << 4;
+const HELD_META_LEFT: u8 = 1 << 5;
+const HELD_META_RIGHT: u8 = 1 << 6;
+
+/// Tracks the held modifiers and filters the events
+pub struct KeyTracker {
+    filter: KeyFilter,
+    held: u8,
+}
+
+impl KeyTracker {
+    pub fn new(filter: KeyFilter) -> Self {
+        Self { filter, held: 0 }
+    }
+
+    /// `None` if the key is filtered out
+    pub fn handle(&mut self, key: Key, pressed: bool) -> Option<KeyEvent> {
+        let held = held_bit(key);
+        if held != 0 {
+            if pressed {
+                self.held |= held;
+            } else {
+                self.held &= !held;
+            }
+        }
+
+        let is_modifier = held != 0;
+        let modifiers = modifiers(self.held);
+        let allowed = match self.filter {
+            KeyFilter::All => true,
+            KeyFilter::Shortcuts => {
+                is_modifier
+                    || modifiers & (MODIFIER_CTRL | MODIFIER_ALT | MODIFIER_SUPER) != 0
+                    || !types_text(key)
+            }
+            KeyFilter::Modifiers => is_modifier,
+            KeyFilter::Off => false,
+        };
+
+        allowed.then(|| KeyEvent {
+            pressed,
+            modifiers,
+            label: label(key),
+        })
+    }
+}
+
+fn held_bit(key: Key) -> u8 {
+    match key {
+        Key::ControlLeft => HELD_CONTROL_LEFT,
+        Key::ControlRight => HELD_CONTROL_RIGHT,
+        Key::ShiftLeft => HELD_SHIFT_LEFT,
+        Key::ShiftRight => HELD_SHIFT_RIGHT,
+        Key::Alt => HELD_ALT,
+        Key::MetaLeft => HELD_META_LEFT,
+        Key::MetaRight => HELD_META_RIGHT,
+        _ => 0,
+    }
+}
+
+// The left and the right keys are the same modifier
+fn modifiers(held: u8) -> u8 {
+    [
+        (HELD_CONTROL_LEFT | HELD_CONTROL_RIGHT, MODIFIER_CTRL),
+        (HELD_SHIFT_LEFT | HELD_SHIFT_RIGHT, MODIFIER_SHIFT),
+        (HELD_ALT, MODIFIER_ALT),
+        (HELD_META_LEFT | HELD_META_RIGHT, MODIFIER_SUPER),
+    ]
+    .into_iter()
+    .filter(|(keys, _)| held & keys != 0)
+    .fold(0, |modifiers, (_, modifier)| modifiers | modifier)
+}
+
+fn types_text(key: Key) -> bool {
+    !matches!(
+        key,
+        Key::Backspace
+            | Key::CapsLock
+            | Key::Delete
+            | Key::DownArrow
+            | Key::End
+            | Key::Escape
+            | Key::F1
+            | Key::F2
+            | Key::F3
+            | Key::F4
+            | Key::F5
+            | Key::F6
+            | Key::F7
+            | Key::F8
+            | Key::F9
+            | Key::F10
+            | Key::F11
+            | Key::F12
+            | Key::Home
+            | Key::LeftArrow
+            | Key::PageDown
+            | Key::PageUp
+            | Key::Return
+            | Key::RightArrow
+            | Key::Tab
+            | Key::UpArrow
+            | Key::PrintScreen
+            | Key::ScrollLock
+            | Key::Pause
+            | Key::NumLock
+            | Key::Insert
+            | Key::KpReturn
+            | Key::Function
+    )
+}
+
+fn label(key: Key) -> String {
+    let label = match key {
+        Key::ControlLeft | Key::ControlRight => "Ctrl",
+        Key::ShiftLeft | Key::ShiftRight => "Shift",
+        Key::Alt => "Alt",
+        Key::AltGr => "AltGr",
+        Key::MetaLeft | Key::MetaRight => "Super",
+        Key::Return | Key::KpReturn => "Enter",
+        Key::Escape => "Esc",
+        Key::UpArrow => "Up",
+        Key::DownArrow => "Down",
+        Key::LeftArrow => "Left",
+        Key::RightArrow => "Right",
+        Key::BackQuote => "`",
+        Key::Minus | Key::KpMinus => "-",
+        Key::Equal => "=",
+        Key::KpPlus => "+",
+        Key::KpMultiply => "*",
+        Key::LeftBracket => "[",
+        Key::RightBracket => "]",
+        Key::SemiColon => ";",
+        Key::Quote => "'",
+        Key::BackSlash | Key::IntlBackslash => "\\",
+        Key::Comma => ",",
+        Key::Dot => ".",
+        Key::Slash | Key::KpDivide => "/",
+        _ => {
+            // `KeyA`, `Num1` and `Kp1` are shown as `A` and `1`
+            let name = format!("{key:?}");
+            return ["Key", "Num", "Kp"]
+                .iter()
+                .find_map(|prefix| {
+                    name.strip_prefix(prefix)
+                        .filter(|rest| rest.len() == 1)
+                        .map(str::to_string)
+                })
+                .unwrap_or(name);
+        }
+    };
+
+    label.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (pressed, modifiers, label) of the broadcast events
+    fn type_keys(filter: KeyFilter, keys: &[(Key, bool)]) -> Vec<(bool, u8, String)> {
+        let mut tracker = KeyTracker::new(filter);
+        keys.iter()
+            .filter_map(|(key, pressed)| tracker.handle(*key, *pressed))
+            .map(|event| (event.pressed, event.modifiers, event.label))
+            .collect()
+    }
+
+    fn event(pressed: bool, modifiers: u8, label: &str) -> (bool, u8, String) {
+        (pressed, modifiers, label.to_string())
+    }
+
+    #[test]
+    fn test_plain_typing() {
+        let keys = [
+            (Key::KeyP, true),
+            (Key::KeyP, false),
+            (Key::Num1, true),
+            (Key::Num1, false),
+            (Key::Return, true),
+            (Key::Return, false),
+        ];
+
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys),
+            [event(true, 0, "Enter"), event(false, 0, "Enter")]
+        );
+        assert_eq!(type_keys(KeyFilter::All, &keys).len(), 6);
+        assert_eq!(type_keys(KeyFilter::All, &keys)[2], event(true, 0, "1"));
+        assert!(type_keys(KeyFilter::Modifiers, &keys).is_empty());
+        assert!(type_keys(KeyFilter::Off, &keys).is_empty());
+    }
+
+    #[test]
+    fn test_shift() {
+        // A capital letter is text, only the Shift is shown
+        let keys = [
+            (Key::ShiftLeft, true),
+            (Key::KeyA, true),
+            (Key::KeyA, false),
+            (Key::ShiftLeft, false),
+        ];
+
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys),
+            [
+                event(true, MODIFIER_SHIFT, "Shift"),
+                event(false, 0, "Shift")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_altgr() {
+        // `@` typed with AltGr+Q on the German layout
+        let keys = [
+            (Key::AltGr, true),
+            (Key::KeyQ, true),
+            (Key::KeyQ, false),
+            (Key::AltGr, false),
+        ];
+
+        assert!(type_keys(KeyFilter::Shortcuts, &keys).is_empty());
+        assert!(type_keys(KeyFilter::Modifiers, &keys).is_empty());
+        assert_eq!(
+            type_keys(KeyFilter::All, &keys),
+            [
+                event(true, 0, "AltGr"),
+                event(true, 0, "Q"),
+                event(false, 0, "Q"),
+                event(false, 0, "AltGr")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        for (modifier_key, modifier, label) in [
+            (Key::ControlLeft, MODIFIER_CTRL, "Ctrl"),
+            (Key::ControlRight, MODIFIER_CTRL, "Ctrl"),
+            (Key::Alt, MODIFIER_ALT, "Alt"),
+            (Key::MetaLeft, MODIFIER_SUPER, "Super"),
+        ] {
+            let keys = [
+                (modifier_key, true),
+                (Key::KeyC, true),
+                (Key::KeyC, false),
+                (modifier_key, false),
+            ];
+
+            assert_eq!(
+                type_keys(KeyFilter::Shortcuts, &keys),
+                [
+                    event(true, modifier, label),
+                    event(true, modifier, "C"),
+                    event(false, modifier, "C"),
+                    event(false, 0, label)
+                ]
+            );
+        }
+
+        let keys = [
+            (Key::ControlLeft, true),
+            (Key::ShiftLeft, true),
+            (Key::KeyT, true),
+        ];
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys)[2],
+            event(true, MODIFIER_CTRL | MODIFIER_SHIFT, "T")
+        );
+    }
+
+    #[test]
+    fn test_release_ordering() {
+        // The modifier released first, the key after it is typed text again
+        let keys = [
+            (Key::ControlLeft, true),
+            (Key::KeyV, true),
+            (Key::ControlLeft, false),
+            (Key::KeyV, false),
+            (Key::KeyX, true),
+        ];
+
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys),
+            [
+                event(true, MODIFIER_CTRL, "Ctrl"),
+                event(true, MODIFIER_CTRL, "V"),
+                event(false, 0, "Ctrl")
+            ]
+        );
+
+        // Releasing one of the held modifiers keeps the others
+        let keys = [
+            (Key::ControlLeft, true),
+            (Key::Alt, true),
+            (Key::ControlLeft, false),
+            (Key::KeyD, true),
+        ];
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys)[3],
+            event(true, MODIFIER_ALT, "D")
+        );
+    }
+
+    #[test]
+    fn test_both_sides_held() {
+        // Ctrl is still held by the right key after the left one is released
+        let keys = [
+            (Key::ControlLeft, true),
+            (Key::ControlRight, true),
+            (Key::ControlLeft, false),
+            (Key::KeyC, true),
+            (Key::ControlRight, false),
+            (Key::KeyX, true),
+        ];
+
+        assert_eq!(
+            type_keys(KeyFilter::Shortcuts, &keys),
+            [
+                event(true, MODIFIER_CTRL, "Ctrl"),
+                event(true, MODIFIER_CTRL, "Ctrl"),
+                event(false, MODIFIER_CTRL, "Ctrl"),
+                event(true, MODIFIER_CTRL, "C"),
+                event(false, 0, "Ctrl")
+            ]
+        );
+    }
+}
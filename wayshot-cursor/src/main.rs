@@ -1,21 +1,123 @@
 use anyhow::Result;
-use rdev::{Event, EventType, grab};
+use clap::{Parser, Subcommand};
+use cursor_client::{HEARTBEAT_INTERVAL, Message, MouseButton, SOCKET_PATH};
+use keys::{KeyFilter, KeyTracker};
+use nix::sys::stat::{Mode, umask};
+use rdev::{Button, Event, EventType, grab};
 use std::{
-    fs,
+    cell::RefCell,
+    env, fs,
     io::Write,
     os::unix::{
-        fs::PermissionsExt,
+        fs::chown,
         net::{UnixListener, UnixStream},
     },
-    sync::atomic::{AtomicU64, Ordering},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
     thread,
+    time::Duration,
 };
 
+mod keys;
+mod systemd;
+
 static CURSOR_POSITION: AtomicU64 = AtomicU64::new(u64::MAX);
-static LAST_SENT_POSITION: AtomicU64 = AtomicU64::new(u64::MAX);
+
+// A client which doesn't read the messages is dropped instead of blocking the others
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+const CURSOR_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "wayshot-cursor",
+    version,
+    about = "Share the cursor position, the clicks and the keys with wayshot on Wayland."
+)]
+struct Cli {
+    /// Which keys are shared for the keystroke overlay
+    #[arg(long, global = true, value_enum, default_value_t = KeyFilter::Shortcuts)]
+    keys: KeyFilter,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Install a systemd service started on the first connection, so wayshot
+    /// doesn't need the elevated privileges
+    Install {
+        /// Where the units are written
+        #[arg(long, default_value = systemd::UNIT_DIR)]
+        unit_dir: PathBuf,
+
+        /// Where the rule giving the `input` group `/dev/uinput` is written
+        #[arg(long, default_value = systemd::UDEV_RULE_DIR)]
+        udev_dir: PathBuf,
+
+        /// Where the program is copied, the service can't run it from the
+        /// home directories
+        #[arg(long, default_value = systemd::BIN_DIR)]
+        bin_dir: PathBuf,
+
+        /// The user who can connect to the socket, the one of `sudo` by default
+        #[arg(long)]
+        user: Option<String>,
+
+        /// The group which can connect to the socket, the one of `sudo` by default
+        #[arg(long)]
+        group: Option<String>,
+
+        /// Only write the units, don't enable the socket
+        #[arg(long)]
+        no_enable: bool,
+    },
+
+    /// Stop and remove the systemd service
+    Uninstall {
+        #[arg(long, default_value = systemd::UNIT_DIR)]
+        unit_dir: PathBuf,
+
+        #[arg(long, default_value = systemd::UDEV_RULE_DIR)]
+        udev_dir: PathBuf,
+
+        #[arg(long, default_value = systemd::BIN_DIR)]
+        bin_dir: PathBuf,
+    },
+}
 
 pub fn main() -> Result<()> {
     env_logger::init();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Install {
+            unit_dir,
+            udev_dir,
+            bin_dir,
+            user,
+            group,
+            no_enable,
+        }) => {
+            let owner = systemd::socket_owner(user, group)?;
+            return systemd::install(&unit_dir, &udev_dir, &bin_dir, owner, cli.keys, !no_enable);
+        }
+        Some(Commands::Uninstall {
+            unit_dir,
+            udev_dir,
+            bin_dir,
+        }) => {
+            return systemd::uninstall(&unit_dir, &udev_dir, &bin_dir);
+        }
+        None => (),
+    }
+
+    let listener = systemd::activated_listener();
     log::info!("start long run cursor grap thread...");
 
     ctrlc::set_handler(move || {
@@ -23,18 +125,49 @@ pub fn main() -> Result<()> {
         std::process::exit(0);
     })?;
 
-    thread::spawn(|| {
-        if let Err(e) = server() {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        if let Err(e) = server(listener, receiver) {
             log::warn!("start socket server failed: {e}");
             std::process::exit(-1);
         }
     });
 
+    let cursor_sender = sender.clone();
+    thread::spawn(move || cursor_poller(cursor_sender));
+
+    let heartbeat_sender = sender.clone();
+    thread::spawn(move || {
+        while heartbeat_sender.send(Message::Heartbeat).is_ok() {
+            thread::sleep(HEARTBEAT_INTERVAL);
+        }
+    });
+
+    let key_tracker = RefCell::new(KeyTracker::new(cli.keys));
+
     let callback = move |event: Event| -> Option<Event> {
-        if let EventType::MouseMove { x, y } = event.event_type {
-            log::debug!("cursor position: (x, y) = ({x}, {y})");
-            let cur_pos = (((x as u64) << 32) & 0xffff_ffff_0000_0000) | (y as u64);
-            CURSOR_POSITION.store(cur_pos, Ordering::Relaxed);
+        let message = match event.event_type {
+            EventType::MouseMove { x, y } => {
+                log::debug!("cursor position: (x, y) = ({x}, {y})");
+                let cur_pos = ((x as i32 as u32 as u64) << 32) | (y as i32 as u32 as u64);
+                CURSOR_POSITION.store(cur_pos, Ordering::Relaxed);
+                None
+            }
+            EventType::ButtonPress(button) => click_message(button, true),
+            EventType::ButtonRelease(button) => click_message(button, false),
+            EventType::KeyPress(key) => {
+                key_tracker.borrow_mut().handle(key, true).map(Message::Key)
+            }
+            EventType::KeyRelease(key) => key_tracker
+                .borrow_mut()
+                .handle(key, false)
+                .map(Message::Key),
+            _ => None,
+        };
+
+        if let Some(message) = message {
+            _ = sender.send(message);
         }
 
         Some(event)
@@ -48,37 +181,126 @@ pub fn main() -> Result<()> {
     Ok(())
 }
 
-fn server() -> Result<()> {
-    let socket_path = "/tmp/wayshot-cursor.sock";
-    _ = fs::remove_file(socket_path);
+fn decode_position(pos: u64) -> (i32, i32) {
+    ((pos >> 32) as u32 as i32, pos as u32 as i32)
+}
+
+// The click events don't carry a position, so the last one is used
+fn click_message(button: Button, pressed: bool) -> Option<Message> {
+    let pos = CURSOR_POSITION.load(Ordering::Relaxed);
+    if pos == u64::MAX {
+        return None;
+    }
+
+    let button = match button {
+        Button::Left => MouseButton::Left,
+        Button::Right => MouseButton::Right,
+        Button::Middle => MouseButton::Middle,
+        Button::Unknown(v) => MouseButton::Other(v),
+    };
+
+    let (x, y) = decode_position(pos);
+    Some(Message::Click {
+        button,
+        pressed,
+        x,
+        y,
+    })
+}
+
+// The move events come much faster than a frame, only the latest position is sent
+fn cursor_poller(sender: Sender<Message>) {
+    let mut last_sent_pos = u64::MAX;
+
+    loop {
+        let pos = CURSOR_POSITION.load(Ordering::Relaxed);
+        if pos != last_sent_pos && pos != u64::MAX {
+            let (x, y) = decode_position(pos);
+            if sender.send(Message::Cursor { x, y }).is_err() {
+                break;
+            }
+
+            last_sent_pos = pos;
+        }
+
+        thread::sleep(CURSOR_POLL_INTERVAL);
+    }
+}
+
+// The socket carries the typed keys, so it's created 0660 and handed to the
+// user who ran `sudo`, the other accounts can't connect
+fn bind_private(path: &str) -> Result<UnixListener> {
+    let old_mask = umask(Mode::from_bits_truncate(0o117));
+    let listener = UnixListener::bind(path);
+    umask(old_mask);
+    let listener = listener?;
+
+    let id = |name| env::var(name).ok().and_then(|v| v.parse::<u32>().ok());
+    let (uid, gid) = (id("SUDO_UID"), id("SUDO_GID"));
+    if uid.is_some() || gid.is_some() {
+        chown(path, uid, gid)?;
+    }
+
+    Ok(listener)
+}
+
+// Every client gets all the messages, the messages are dropped without a client
+fn server(listener: Option<UnixListener>, receiver: Receiver<Message>) -> Result<()> {
+    let listener = match listener {
+        Some(listener) => {
+            log::info!("Socket activated by systemd on {}", SOCKET_PATH);
+            listener
+        }
+        None => {
+            if let Some(dir) = Path::new(SOCKET_PATH).parent() {
+                fs::create_dir_all(dir)?;
+            }
+            _ = fs::remove_file(SOCKET_PATH);
 
-    let listener = UnixListener::bind(socket_path)?;
-    log::info!("Admin process listening on {}", socket_path);
+            let listener = bind_private(SOCKET_PATH)?;
+            log::info!("Admin process listening on {}", SOCKET_PATH);
+            listener
+        }
+    };
 
-    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o666))?;
+    let clients = Arc::new(Mutex::new(Vec::<UnixStream>::new()));
+    let broadcast_clients = clients.clone();
+
+    thread::spawn(move || {
+        while let Ok(message) = receiver.recv() {
+            let bytes = message.encode();
+            broadcast_clients.lock().unwrap().retain_mut(|stream| {
+                match stream.write_all(&bytes).and_then(|_| stream.flush()) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        log::info!("Client disconnected: {e}");
+                        false
+                    }
+                }
+            });
+        }
+    });
 
     for stream in listener.incoming() {
         match stream {
             Ok(mut stream) => {
                 log::info!("Client connected");
 
-                thread::spawn(move || {
-                    loop {
-                        let pos = CURSOR_POSITION.load(Ordering::Relaxed);
-                        let last_sent_pos = LAST_SENT_POSITION.load(Ordering::Relaxed);
-
-                        if pos != last_sent_pos && pos != u64::MAX {
-                            if let Err(e) = send_position(&mut stream, pos) {
-                                log::warn!("send position failed: {e}");
-                                break;
-                            }
-
-                            LAST_SENT_POSITION.store(pos, Ordering::Relaxed);
-                        }
+                if let Err(e) = stream.set_write_timeout(Some(WRITE_TIMEOUT)) {
+                    log::warn!("set client write timeout failed: {e}");
+                }
 
-                        std::thread::sleep(std::time::Duration::from_millis(5));
+                // A new client gets the position at once, not on the next move
+                let pos = CURSOR_POSITION.load(Ordering::Relaxed);
+                if pos != u64::MAX {
+                    let (x, y) = decode_position(pos);
+                    if let Err(e) = stream.write_all(&Message::Cursor { x, y }.encode()) {
+                        log::warn!("send position failed: {e}");
+                        continue;
                     }
-                });
+                }
+
+                clients.lock().unwrap().push(stream);
             }
             Err(err) => {
                 log::warn!("Connection error: {}", err);
@@ -88,10 +310,3 @@ fn server() -> Result<()> {
 
     Ok(())
 }
-
-fn send_position(stream: &mut UnixStream, value: u64) -> Result<()> {
-    let bytes = value.to_ne_bytes();
-    stream.write_all(&bytes)?;
-    stream.flush()?;
-    Ok(())
-}
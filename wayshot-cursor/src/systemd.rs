@@ -0,0 +1,286 @@
+//! Runs the helper as a system service, started by systemd on the first
+//! connection to the socket. The service runs as an unprivileged user in the
+//! `input` group, the GUI runs as the user and talks to it through a socket
+//! which only that user can open.
+
+use crate::keys::KeyFilter;
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use cursor_client::SOCKET_PATH;
+use std::{
+    env, fs,
+    os::unix::{fs::PermissionsExt, io::FromRawFd, net::UnixListener},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub const UNIT_DIR: &str = "/etc/systemd/system";
+pub const UDEV_RULE_DIR: &str = "/etc/udev/rules.d";
+pub const BIN_DIR: &str = "/usr/local/bin";
+
+const BIN_NAME: &str = "wayshot-cursor";
+
+const SOCKET_UNIT: &str = "wayshot-cursor.socket";
+const SERVICE_UNIT: &str = "wayshot-cursor.service";
+const UDEV_RULE: &str = "70-wayshot-cursor.rules";
+
+// The grabbed events are emitted again through `/dev/uinput`, which only
+// root can open on most distributions
+const UINPUT_RULE: &str =
+    "KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n";
+
+// `ProtectHome=yes` hides these from the service, so it can't be started from there
+const PROTECTED_DIRS: [&str; 3] = ["/home", "/root", "/run/user"];
+
+// The first passed file descriptor, see `sd_listen_fds(3)`
+const LISTEN_FDS_START: i32 = 3;
+
+/// The listener passed by systemd, `None` if the process wasn't socket
+/// activated. Call it before spawning any thread, it clears the environment.
+pub fn activated_listener() -> Option<UnixListener> {
+    let pid = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok());
+    let fds = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok());
+
+    // SAFETY: no other thread is running yet
+    unsafe {
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+    }
+
+    match (pid, fds) {
+        (Some(pid), Some(fds)) if pid == std::process::id() && fds > 0 => {
+            if fds > 1 {
+                log::warn!("got {fds} sockets from systemd, only the first one is used");
+            }
+
+            // SAFETY: systemd hands over the descriptor and nothing else owns it
+            Some(unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) })
+        }
+        _ => None,
+    }
+}
+
+/// The user and the group which can connect to the socket, the ones of
+/// `sudo` when they aren't given
+pub fn socket_owner(user: Option<String>, group: Option<String>) -> Result<(String, String)> {
+    let user = user
+        .or_else(|| env::var("SUDO_USER").ok())
+        .context("no user for the socket, run it with sudo or pass --user")?;
+    let group = group
+        .or_else(|| env::var("SUDO_GID").ok())
+        .context("no group for the socket, run it with sudo or pass --group")?;
+
+    Ok((user, group))
+}
+
+/// Copy the program to `bin_dir`, write the units and the udev rule, then
+/// start listening on the socket
+pub fn install(
+    unit_dir: &Path,
+    udev_dir: &Path,
+    bin_dir: &Path,
+    (user, group): (String, String),
+    keys: KeyFilter,
+    enable: bool,
+) -> Result<()> {
+    let keys = keys
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default();
+    let service = service_unit(bin_dir, &keys)?;
+    let exe = install_binary(bin_dir)?;
+
+    fs::create_dir_all(udev_dir)?;
+    fs::write(udev_dir.join(UDEV_RULE), UINPUT_RULE)
+        .with_context(|| format!("write {UDEV_RULE} to {}", udev_dir.display()))?;
+
+    fs::create_dir_all(unit_dir)?;
+    fs::write(unit_dir.join(SOCKET_UNIT), socket_unit(&user, &group))
+        .with_context(|| format!("write {SOCKET_UNIT} to {}", unit_dir.display()))?;
+    fs::write(unit_dir.join(SERVICE_UNIT), service)
+        .with_context(|| format!("write {SERVICE_UNIT} to {}", unit_dir.display()))?;
+
+    println!(
+        "installed {}, {SOCKET_UNIT} and {SERVICE_UNIT} in {}, {UDEV_RULE} in {}, the socket is for {user}:{group}",
+        exe.display(),
+        unit_dir.display(),
+        udev_dir.display(),
+    );
+
+    if enable {
+        reload_udev_rules()?;
+        run("systemctl", &["daemon-reload"])?;
+        run("systemctl", &["enable", "--now", SOCKET_UNIT])?;
+    }
+
+    Ok(())
+}
+
+pub fn uninstall(unit_dir: &Path, udev_dir: &Path, bin_dir: &Path) -> Result<()> {
+    _ = run(
+        "systemctl",
+        &["disable", "--now", SOCKET_UNIT, SERVICE_UNIT],
+    );
+
+    for path in [
+        unit_dir.join(SOCKET_UNIT),
+        unit_dir.join(SERVICE_UNIT),
+        udev_dir.join(UDEV_RULE),
+        bin_dir.join(BIN_NAME),
+    ] {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+        }
+    }
+
+    run("systemctl", &["daemon-reload"])?;
+    reload_udev_rules()
+}
+
+// The service can't read the home directories, where `cargo install` and the
+// build directories put the program, so it runs a copy outside of them
+fn install_binary(bin_dir: &Path) -> Result<PathBuf> {
+    let exe = env::current_exe().context("find the path of wayshot-cursor")?;
+    let target = bin_dir.join(BIN_NAME);
+
+    if fs::canonicalize(&target).is_ok_and(|path| path == exe) {
+        return Ok(target);
+    }
+
+    // Renamed into place, the running service keeps its old copy open
+    fs::create_dir_all(bin_dir)?;
+    let tmp = bin_dir.join(format!(".{BIN_NAME}.tmp"));
+    fs::copy(&exe, &tmp).with_context(|| format!("copy {} to {}", exe.display(), tmp.display()))?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&tmp, &target).with_context(|| format!("install {}", target.display()))?;
+
+    Ok(target)
+}
+
+fn is_protected(path: &Path) -> bool {
+    PROTECTED_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+// Applies the rule to the existing `/dev/uinput` too
+fn reload_udev_rules() -> Result<()> {
+    run("udevadm", &["control", "--reload"])?;
+    run("udevadm", &["trigger", "--sysname-match=uinput"])
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("run {program}"))?;
+
+    if !status.success() {
+        bail!("{program} {} failed: {status}", args.join(" "));
+    }
+
+    Ok(())
+}
+
+// The socket carries the typed keys, so only the user can connect
+fn socket_unit(user: &str, group: &str) -> String {
+    format!(
+        r#"[Unit]
+Description=Share the cursor and the keys with wayshot
+
+[Socket]
+ListenStream={SOCKET_PATH}
+SocketUser={user}
+SocketGroup={group}
+SocketMode=0660
+DirectoryMode=0755
+RemoveOnStop=yes
+
+[Install]
+WantedBy=sockets.target
+"#
+    )
+}
+
+// A throwaway user in the `input` group can grab `/dev/input` and re-emit
+// through `/dev/uinput`, the rest of the system is hidden from the service
+fn service_unit(bin_dir: &Path, keys: &str) -> Result<String> {
+    let exe = bin_dir.join(BIN_NAME);
+    if !exe.is_absolute() || is_protected(&exe) {
+        bail!(
+            "{} can't be run by the service, it hides {}, pass another --bin-dir",
+            exe.display(),
+            PROTECTED_DIRS.join(", "),
+        );
+    }
+    let exe = exe.display();
+
+    Ok(format!(
+        r#"[Unit]
+Description=Share the cursor and the keys with wayshot
+Requires={SOCKET_UNIT}
+
+[Service]
+ExecStart="{exe}" --keys {keys}
+Restart=on-failure
+DynamicUser=yes
+SupplementaryGroups=input
+NoNewPrivileges=yes
+PrivateNetwork=yes
+ProtectSystem=strict
+ProtectHome=yes
+ProtectKernelTunables=yes
+ProtectKernelModules=yes
+ProtectControlGroups=yes
+RestrictAddressFamilies=AF_UNIX
+DevicePolicy=closed
+DeviceAllow=char-input rw
+DeviceAllow=/dev/uinput rw
+"#
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec_start(unit: &str) -> &str {
+        unit.lines()
+            .find_map(|line| line.strip_prefix("ExecStart="))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_service_unit_exec_start() {
+        let unit = service_unit(Path::new(BIN_DIR), "all").unwrap();
+        assert_eq!(
+            exec_start(&unit),
+            "\"/usr/local/bin/wayshot-cursor\" --keys all"
+        );
+
+        let unit = service_unit(Path::new("/opt/wayshot/bin"), "shortcuts").unwrap();
+        assert_eq!(
+            exec_start(&unit),
+            "\"/opt/wayshot/bin/wayshot-cursor\" --keys shortcuts"
+        );
+    }
+
+    #[test]
+    fn test_service_unit_rejects_hidden_dirs() {
+        for dir in [
+            "/home/user/.cargo/bin",
+            "/home/user/wayshot/target/release",
+            "/root/.cargo/bin",
+            "/run/user/1000",
+            "bin",
+        ] {
+            assert!(service_unit(Path::new(dir), "all").is_err(), "{dir}");
+        }
+
+        // Only whole path components are matched
+        assert!(service_unit(Path::new("/homebrew/bin"), "all").is_ok());
+    }
+}
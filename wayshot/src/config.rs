@@ -1,6 +1,7 @@
 use crate::slint_generatedAppWindow::{
-    BackgroundRemoverModel as UIBackgroundRemoverModel, FileType as UIFileType, Fps as UIFps,
-    MixPositionWithPadding as UIMixPositionWithPadding,
+    AiProvider as UIAiProvider, AudioFormat as UIAudioFormat,
+    BackgroundRemoverModel as UIBackgroundRemoverModel, CaptionPosition as UICaptionPosition,
+    FileType as UIFileType, Fps as UIFps, MixPositionWithPadding as UIMixPositionWithPadding,
     MixPositionWithPaddingTag as UIMixPositionWithPaddingTag, RTCIceServer as UIRTCIceServer,
     RealtimeImageEffect as UIRealtimeImageEffect, Resolution as UIResolution,
     SettingAiModel as UISettingAiModel, SettingBackgroundRemover as UISettingBackgroundRemover,
@@ -12,11 +13,12 @@ use crate::slint_generatedAppWindow::{
 };
 use anyhow::{Context, Result, bail};
 use background_remover::Model as BackgroundRemoverModel;
+use bot::Provider as AiProvider;
 use image_effect::realtime::RealtimeImageEffect;
 use log::debug;
 use once_cell::sync::Lazy;
 use pmacro::SlintFromConvert;
-use recorder::TransitionType;
+use recorder::{AudioFormat, CaptionPosition, TransitionType};
 use serde::{Deserialize, Serialize};
 use slint::Model;
 use std::{fs, path::PathBuf, sync::Mutex};
@@ -25,6 +27,10 @@ use uuid::Uuid;
 #[cfg(feature = "desktop")]
 use platform_dirs::AppDirs;
 
+pub mod profiles;
+
+pub use profiles::Profile;
+
 const CARGO_TOML: &str = include_str!("../Cargo.toml");
 static CONFIG: Lazy<Mutex<Config>> = Lazy::new(|| Mutex::new(Config::default()));
 
@@ -93,6 +99,22 @@ pub struct Config {
 
     #[serde(default)]
     pub ai_model: AiModel,
+
+    #[serde(default)]
+    pub hotkey: Hotkey,
+
+    #[serde(default)]
+    pub upload: Upload,
+
+    #[serde(default)]
+    pub tray: Tray,
+
+    // Only the user profiles, the built-in ones aren't saved
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+
+    #[serde(default)]
+    pub current_profile: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Derivative)]
@@ -140,6 +162,51 @@ pub struct Recorder {
 
     #[derivative(Default(value = "resolution_default()"))]
     pub resolution: UIResolution,
+
+    // Seconds to wait before capturing
+    #[serde(default)]
+    pub countdown: i32,
+
+    // Multiple of the normal cursor size
+    #[serde(default = "cursor_scale_default")]
+    #[derivative(Default(value = "cursor_scale_default()"))]
+    pub cursor_scale: f32,
+
+    #[serde(default)]
+    pub cursor_highlight: bool,
+
+    // Seconds the cursor stays still before it's hidden, 0 keeps it shown
+    #[serde(default)]
+    pub cursor_hide_idle: i32,
+
+    // The live preview is downscaled to the height
+    #[serde(default = "preview_height_default")]
+    #[derivative(Default(value = "preview_height_default()"))]
+    pub preview_height: i32,
+
+    // Milliseconds the audio is shifted by, negative for the microphones with latency
+    #[serde(default)]
+    pub av_offset_ms: i32,
+
+    // The file type of the audio only recordings
+    #[serde(default)]
+    pub audio_format: UIAudioFormat,
+
+    // Transcribed captions drawn onto the shared and streamed video
+    #[serde(default)]
+    pub live_caption: bool,
+
+    // Pixels of a 1080p frame
+    #[serde(default = "live_caption_font_size_default")]
+    #[derivative(Default(value = "live_caption_font_size_default()"))]
+    pub live_caption_font_size: i32,
+
+    #[serde(default)]
+    pub live_caption_position: UICaptionPosition,
+
+    #[serde(default = "live_caption_background_default")]
+    #[derivative(Default(value = "live_caption_background_default()"))]
+    pub live_caption_background: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert)]
@@ -185,6 +252,9 @@ pub struct Control {
 pub struct CursorTracker {
     pub enable_tracking: bool,
 
+    #[serde(default)]
+    pub enable_window_following: bool,
+
     #[derivative(Default(value = "1280"))]
     pub region_width: i32,
 
@@ -276,6 +346,10 @@ pub struct PushStream {
     pub stream_key: String,
 
     pub query_params: String,
+
+    // kbit/s of the constant bitrate, 0 keeps a constant quality
+    #[serde(default)]
+    pub bitrate: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert)]
@@ -341,6 +415,7 @@ pub struct Camera {
 #[serde(default)]
 #[from("UISettingAiModel")]
 pub struct AiModel {
+    pub provider: UIAiProvider,
     pub model_name: String,
     pub api_base_url: String,
     pub api_key: String,
@@ -359,11 +434,112 @@ pub struct Transcribe {
 
     #[derivative(Default(value = "0.5"))]
     pub audio_sound: f32,
+
+    // Speaker embedding model, the subtitles are labeled with their
+    // speaker when it is set
+    pub speaker_model_path: String,
+
+    // Comma separated terms the transcription is biased to
+    pub hotwords: String,
+
+    // Detect the language of each segment and transcribe it in that
+    // language, for mixed Chinese and English recordings
+    pub detect_language: bool,
+
+    pub narration_model_dir: String,
+    pub narration_reference_audio: String,
+    pub narration_reference_text: String,
+
+    // Gain of the original audio under the narration, 0 replaces it
+    #[derivative(Default(value = "0.2"))]
+    pub narration_original_volume: f32,
+
+    // Language the subtitles are translated into, as it's said to the AI model
+    #[derivative(Default(value = "\"English\".to_string()"))]
+    pub translation_target_lang: String,
+}
+
+// Triggers follow the XDG shortcuts format, e.g. `CTRL+ALT+R`
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct Hotkey {
+    #[derivative(Default(value = "true"))]
+    pub enable: bool,
+
+    #[derivative(Default(value = "\"CTRL+ALT+R\".to_string()"))]
+    pub toggle_recording: String,
+
+    #[derivative(Default(value = "\"CTRL+ALT+S\".to_string()"))]
+    pub stop_recording: String,
+
+    #[derivative(Default(value = "\"CTRL+ALT+C\".to_string()"))]
+    pub add_chapter: String,
+
+    #[derivative(Default(value = "\"CTRL+ALT+P\".to_string()"))]
+    pub pause_recording: String,
+
+    #[derivative(Default(value = "\"CTRL+ALT+B\".to_string()"))]
+    pub save_replay: String,
+
+    // Seconds of the screen recording kept for `save_replay`, 0 keeps none
+    #[derivative(Default(value = "30"))]
+    pub replay_secs: i32,
+}
+
+// Keep running in the tray when the window is closed
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct Tray {
+    #[derivative(Default(value = "true"))]
+    pub enable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UploadBackend {
+    #[default]
+    HttpPut,
+    WebDav,
+    S3,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct Upload {
+    // Upload the recordings once they are added to the histories
+    pub auto_upload: bool,
+
+    pub backend: UploadBackend,
+
+    // The directory the recordings are put into, or the endpoint of the S3
+    // service, e.g. `https://s3.us-east-1.amazonaws.com`
+    pub url: String,
+
+    // Basic auth of WebDAV, or the access key pair of S3
+    pub username: String,
+    pub password: String,
+
+    // Bearer token of the plain HTTP PUT server
+    pub token: String,
+
+    pub bucket: String,
+
+    #[derivative(Default(value = "\"us-east-1\".to_string()"))]
+    pub region: String,
+
+    // Prefix of the share URLs, the upload URLs are shared when it's empty
+    pub public_url: String,
 }
 
 crate::impl_slint_enum_serde!(UIFileType, None, Audio, Video);
 crate::impl_slint_enum_serde!(UIBackgroundRemoverModel, Modnet, Rmbg14);
+crate::impl_slint_enum_serde!(UIAiProvider, OpenAI, Anthropic, Gemini, Ollama);
 crate::impl_slint_enum_serde!(UIFps, Fps24, Fps25, Fps30, Fps60);
+crate::impl_slint_enum_serde!(UIAudioFormat, M4a, Ogg);
+crate::impl_slint_enum_serde!(UICaptionPosition, Bottom, Top);
 crate::impl_slint_enum_serde!(UIResolution, Original, P480, P720, P1080, P2K, P4K);
 crate::impl_slint_enum_serde!(UITransitionType, Linear, EaseIn, EaseOut);
 crate::impl_slint_enum_serde!(
@@ -396,6 +572,9 @@ crate::impl_slint_enum_serde!(
 );
 
 crate::impl_c_like_enum_convert!(UITransitionType, TransitionType, Linear, EaseIn, EaseOut);
+crate::impl_c_like_enum_convert!(UIAudioFormat, AudioFormat, M4a, Ogg);
+crate::impl_c_like_enum_convert!(UICaptionPosition, CaptionPosition, Bottom, Top);
+crate::impl_c_like_enum_convert!(UIAiProvider, AiProvider, OpenAI, Anthropic, Gemini, Ollama);
 crate::impl_c_like_enum_convert!(
     UIRealtimeImageEffect,
     RealtimeImageEffect,
@@ -418,12 +597,16 @@ crate::impl_c_like_enum_convert!(
     Vignette,
     Temperature
 );
-crate::impl_c_like_enum_convert!(
-    UIBackgroundRemoverModel,
-    BackgroundRemoverModel,
-    Modnet,
-    Rmbg14
-);
+
+// The settings only offer a part of the model zoo
+impl From<UIBackgroundRemoverModel> for BackgroundRemoverModel {
+    fn from(value: UIBackgroundRemoverModel) -> Self {
+        match value {
+            UIBackgroundRemoverModel::Modnet => BackgroundRemoverModel::Modnet,
+            UIBackgroundRemoverModel::Rmbg14 => BackgroundRemoverModel::Rmbg14,
+        }
+    }
+}
 
 impl Config {
     pub fn init(&mut self) -> Result<()> {
@@ -556,6 +739,22 @@ fn resolution_default() -> UIResolution {
     UIResolution::Original
 }
 
+fn cursor_scale_default() -> f32 {
+    1.0
+}
+
+fn preview_height_default() -> i32 {
+    540
+}
+
+fn live_caption_font_size_default() -> i32 {
+    40
+}
+
+fn live_caption_background_default() -> bool {
+    true
+}
+
 fn true_func() -> bool {
     true
 }
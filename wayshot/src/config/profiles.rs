@@ -0,0 +1,369 @@
+//! Recording profiles bundle the recorder, cursor tracker and push stream
+//! settings under a name, so they can be switched in one click.
+//!
+//! The built-in profiles are never written to the config file. A user profile
+//! with the same name as a built-in one takes its place.
+
+use super::{Config, CursorTracker, PushStream, Recorder};
+use crate::slint_generatedAppWindow::{
+    AudioFormat as UIAudioFormat, CaptionPosition as UICaptionPosition, Fps as UIFps,
+    Resolution as UIResolution, TransitionType as UITransitionType,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+// The settings of a config section a profile sets. The fields which are `None`
+// keep their current values when the profile is applied
+macro_rules! section_profile {
+    ($name:ident, $section:ty, { $($field:ident: $ty:ty),* $(,)? }) => {
+        #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+        #[serde(default)]
+        pub struct $name {
+            $(
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub $field: Option<$ty>,
+            )*
+        }
+
+        impl $name {
+            fn from_section(section: &$section) -> Self {
+                Self {
+                    $($field: Some(section.$field.clone()),)*
+                }
+            }
+
+            fn apply(self, section: &mut $section) {
+                $(
+                    if let Some(value) = self.$field {
+                        section.$field = value;
+                    }
+                )*
+            }
+        }
+    };
+}
+
+section_profile!(RecorderProfile, Recorder, {
+    save_dir: String,
+    include_cursor: bool,
+    enable_denoise: bool,
+    convert_to_mono: bool,
+    fps: UIFps,
+    resolution: UIResolution,
+    countdown: i32,
+    cursor_scale: f32,
+    cursor_highlight: bool,
+    cursor_hide_idle: i32,
+    preview_height: i32,
+    av_offset_ms: i32,
+    audio_format: UIAudioFormat,
+    live_caption: bool,
+    live_caption_font_size: i32,
+    live_caption_position: UICaptionPosition,
+    live_caption_background: bool,
+});
+
+section_profile!(CursorTrackerProfile, CursorTracker, {
+    enable_tracking: bool,
+    enable_window_following: bool,
+    region_width: i32,
+    region_height: i32,
+    debounce_radius: i32,
+    stable_radius: i32,
+    fast_moving_duration: i32,
+    zoom_transition_duration: i32,
+    reposition_edge_threshold: f32,
+    reposition_transition_duration: i32,
+    max_stable_region_duration: i32,
+    zoom_in_transition_type: UITransitionType,
+    zoom_out_transition_type: UITransitionType,
+});
+
+section_profile!(PushStreamProfile, PushStream, {
+    save_mp4: bool,
+    server_addr: String,
+    app: String,
+    stream_key: String,
+    query_params: String,
+    bitrate: i32,
+});
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub recorder: RecorderProfile,
+    pub cursor_tracker: CursorTrackerProfile,
+    pub push_stream: PushStreamProfile,
+}
+
+impl Profile {
+    pub fn from_config(name: impl Into<String>, config: &Config) -> Self {
+        Self {
+            name: name.into(),
+            recorder: RecorderProfile::from_section(&config.recorder),
+            cursor_tracker: CursorTrackerProfile::from_section(&config.cursor_tracker),
+            push_stream: PushStreamProfile::from_section(&config.push_stream),
+        }
+    }
+
+    // An empty save directory keeps the current one, so profiles can be
+    // shared between machines
+    pub fn apply(mut self, config: &mut Config) {
+        self.recorder.save_dir = self.recorder.save_dir.filter(|dir| !dir.is_empty());
+
+        self.recorder.apply(&mut config.recorder);
+        self.cursor_tracker.apply(&mut config.cursor_tracker);
+        self.push_stream.apply(&mut config.push_stream);
+        config.current_profile = self.name;
+    }
+
+    // The stream key is a secret, it's left out of the shared file
+    pub fn export(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut profile = self.clone();
+        profile.push_stream.stream_key = None;
+
+        let text = toml::to_string_pretty(&profile)
+            .with_context(|| format!("convert profile `{}` to toml failed", self.name))?;
+        fs::write(path.as_ref(), text)
+            .with_context(|| format!("write {} failed", path.as_ref().display()))?;
+        Ok(())
+    }
+
+    pub fn import(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("read {} failed", path.as_ref().display()))?;
+        let profile = toml::from_str::<Profile>(&text)
+            .with_context(|| format!("parse {} failed", path.as_ref().display()))?;
+
+        if profile.name.trim().is_empty() {
+            bail!("profile name is empty");
+        }
+
+        Ok(profile)
+    }
+}
+
+pub fn builtin() -> Vec<Profile> {
+    vec![
+        Profile {
+            name: "YouTube 1080p60".to_string(),
+            recorder: RecorderProfile {
+                fps: Some(UIFps::Fps60),
+                resolution: Some(UIResolution::P1080),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Profile {
+            name: "Quick 720p".to_string(),
+            recorder: RecorderProfile {
+                fps: Some(UIFps::Fps30),
+                resolution: Some(UIResolution::P720),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Profile {
+            name: "Meeting notes".to_string(),
+            recorder: RecorderProfile {
+                enable_denoise: Some(true),
+                convert_to_mono: Some(true),
+                fps: Some(UIFps::Fps24),
+                resolution: Some(UIResolution::P720),
+                ..Default::default()
+            },
+            cursor_tracker: CursorTrackerProfile {
+                enable_tracking: Some(false),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    ]
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    builtin().iter().any(|item| item.name == name)
+}
+
+pub fn all() -> Vec<Profile> {
+    let user_profiles = super::all().profiles;
+
+    let mut profiles = builtin()
+        .into_iter()
+        .filter(|item| !user_profiles.iter().any(|p| p.name == item.name))
+        .collect::<Vec<_>>();
+    profiles.extend(user_profiles);
+    profiles
+}
+
+pub fn find(name: &str) -> Option<Profile> {
+    all().into_iter().find(|item| item.name == name)
+}
+
+pub fn apply(name: &str) -> Result<()> {
+    let Some(profile) = find(name) else {
+        bail!("no found profile `{name}`");
+    };
+
+    let mut config = super::all();
+    profile.apply(&mut config);
+    super::save(config)
+}
+
+// Snapshot the current settings, replacing the profile with the same name
+pub fn save_current(name: &str) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("profile name is empty");
+    }
+
+    let mut config = super::all();
+    let profile = Profile::from_config(name, &config);
+    insert(&mut config, profile);
+    config.current_profile = name.to_string();
+    super::save(config)
+}
+
+pub fn import(path: impl AsRef<Path>) -> Result<Profile> {
+    let profile = Profile::import(path)?;
+
+    let mut config = super::all();
+    insert(&mut config, profile.clone());
+    super::save(config)?;
+
+    Ok(profile)
+}
+
+pub fn remove(name: &str) -> Result<()> {
+    let mut config = super::all();
+    let len = config.profiles.len();
+    config.profiles.retain(|item| item.name != name);
+
+    if config.profiles.len() == len {
+        if is_builtin(name) {
+            bail!("built-in profile `{name}` can't be removed");
+        }
+        bail!("no found profile `{name}`");
+    }
+
+    if config.current_profile == name {
+        config.current_profile.clear();
+    }
+
+    super::save(config)
+}
+
+fn insert(config: &mut Config, profile: Profile) {
+    match config.profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(item) => *item = profile,
+        None => config.profiles.push(profile),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_config() -> Config {
+        let mut config = Config::default();
+        config.recorder.save_dir = "/home/user/Videos".to_string();
+        config.recorder.countdown = 3;
+        config.recorder.av_offset_ms = -120;
+        config.recorder.cursor_highlight = true;
+        config.recorder.live_caption = true;
+        config.cursor_tracker.region_width = 1920;
+        config.push_stream.stream_key = "secret-key".to_string();
+        config
+    }
+
+    #[test]
+    fn test_apply_overlays_the_set_fields() {
+        let mut config = custom_config();
+        let profile = builtin().remove(2);
+        profile.apply(&mut config);
+
+        assert_eq!(config.current_profile, "Meeting notes");
+        assert!(config.recorder.enable_denoise);
+        assert!(config.recorder.convert_to_mono);
+        assert_eq!(config.recorder.fps, UIFps::Fps24);
+        assert_eq!(config.recorder.resolution, UIResolution::P720);
+        assert!(!config.cursor_tracker.enable_tracking);
+
+        // The settings the profile doesn't set are kept
+        assert_eq!(config.recorder.save_dir, "/home/user/Videos");
+        assert_eq!(config.recorder.countdown, 3);
+        assert_eq!(config.recorder.av_offset_ms, -120);
+        assert!(config.recorder.cursor_highlight);
+        assert!(config.recorder.live_caption);
+        assert_eq!(config.cursor_tracker.region_width, 1920);
+        assert_eq!(config.push_stream.stream_key, "secret-key");
+    }
+
+    #[test]
+    fn test_apply_empty_save_dir() {
+        let mut config = custom_config();
+        let mut profile = Profile::from_config("Other machine", &Config::default());
+        profile.recorder.save_dir = Some(String::default());
+        profile.recorder.countdown = Some(5);
+        profile.apply(&mut config);
+
+        assert_eq!(config.recorder.save_dir, "/home/user/Videos");
+        assert_eq!(config.recorder.countdown, 5);
+        assert_eq!(config.recorder.av_offset_ms, 0);
+        assert_eq!(config.current_profile, "Other machine");
+    }
+
+    #[test]
+    fn test_export_import() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("profile.toml");
+
+        let profile = Profile::from_config("Streaming", &custom_config());
+        profile.export(&path)?;
+
+        // The secret isn't written, the other settings are
+        let text = fs::read_to_string(&path)?;
+        assert!(!text.contains("secret-key"));
+        assert!(text.contains("rtmp://localhost:1935"));
+
+        let imported = Profile::import(&path)?;
+        assert_eq!(imported.name, "Streaming");
+        assert_eq!(imported.recorder.countdown, Some(3));
+        assert_eq!(imported.recorder.av_offset_ms, Some(-120));
+        assert_eq!(imported.cursor_tracker.region_width, Some(1920));
+        assert_eq!(imported.push_stream.stream_key, None);
+        assert_eq!(imported.push_stream.app.as_deref(), Some("live"));
+
+        // Importing on another machine keeps its stream key
+        let mut config = Config::default();
+        config.push_stream.stream_key = "other-key".to_string();
+        imported.apply(&mut config);
+        assert_eq!(config.push_stream.stream_key, "other-key");
+        assert_eq!(config.recorder.countdown, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_partial_profile() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("profile.toml");
+        fs::write(
+            &path,
+            "name = \"Countdown\"\n\n[recorder]\ncountdown = 10\n",
+        )?;
+
+        let mut config = custom_config();
+        Profile::import(&path)?.apply(&mut config);
+        assert_eq!(config.recorder.countdown, 10);
+        assert_eq!(config.recorder.av_offset_ms, -120);
+        assert_eq!(config.cursor_tracker.region_width, 1920);
+
+        fs::write(&path, "[recorder]\ncountdown = 10\n")?;
+        assert!(Profile::import(&path).is_err());
+
+        Ok(())
+    }
+}
@@ -1,29 +1,130 @@
 use crate::slint_generatedAppWindow::{
     FileType as UIFileType, HistoryEntry as UIHistoryEntry, SettingPlayer as UISettingPlayer,
-    Subtitle as UISubtitle, Transcribe as UITranscribe,
+    Subtitle as UISubtitle, Transcribe as UITranscribe, TranscribeChapter as UITranscribeChapter,
 };
-use pmacro::SlintFromConvert;
+use anyhow::{Context, Result};
+use pmacro::{SlintFromConvert, SqlTable};
 use serde::{Deserialize, Serialize};
 use slint::Model;
+use sqldb::Repository;
+use std::path::{Path, PathBuf};
+
+// Name the database of the app is opened as
+pub const DB_NAME: &str = "wayshot";
 
 pub const HISTORY_TABLE: &str = "history";
 pub const PLAYER_SETTING_TABLE: &str = "player_setting";
 pub const TRANSCRIBE_TABLE: &str = "transcribe";
 
-pub async fn init(db_path: &str) {
-    sqldb::create_db(db_path).await.expect("create db");
+// Responses of the AI model keyed by the request hash
+pub const AI_RESPONSE_CACHE_TABLE: &str = "ai_response_cache";
+
+// The database is encrypted with the passphrase if it's set, a plaintext
+// database is encrypted on the first start
+#[cfg(feature = "database-encryption")]
+const DB_PASSPHRASE_ENV: &str = "WAYSHOT_DB_PASSPHRASE";
+
+// Daily backups kept in the backup directory, a damaged database is restored
+// from the latest one
+const KEEP_DAILY_BACKUPS: usize = 7;
+
+pub async fn init(db_path: &str) -> Result<()> {
+    create_db(db_path).await?;
+    init_tables(db_path).await
+}
+
+// Opens the encrypted database with the passphrase entered in the UI
+#[cfg(feature = "database-encryption")]
+pub async fn init_with_passphrase(db_path: &str, passphrase: &str) -> Result<()> {
+    sqldb::create_encrypted_db(DB_NAME, db_path, passphrase).await?;
+    init_tables(db_path).await
+}
+
+async fn init_tables(db_path: &str) -> Result<()> {
+    let backup_dir = backup_dir(Path::new(db_path));
+    match sqldb::check_integrity(DB_NAME).await {
+        Ok(_) => {
+            tokio::spawn(async move {
+                if let Err(e) = sqldb::backup_daily(DB_NAME, &backup_dir, KEEP_DAILY_BACKUPS).await
+                {
+                    log::warn!("backup database failed: {e}");
+                }
+            });
+        }
+        Err(e) => {
+            log::warn!("{e}");
+            restore_latest_backup(&backup_dir).await;
+        }
+    }
+
+    Repository::<HistoryEntry>::new(DB_NAME, HISTORY_TABLE)
+        .init()
+        .await
+        .context("history table failed")?;
 
-    sqldb::entry::new(HISTORY_TABLE)
+    Repository::<Transcribe>::new(DB_NAME, TRANSCRIBE_TABLE)
+        .init()
         .await
-        .expect("history table failed");
+        .context("transcribe table failed")?;
 
-    sqldb::entry::new(TRANSCRIBE_TABLE)
+    Repository::<SettingPlayer>::new(DB_NAME, PLAYER_SETTING_TABLE)
+        .init()
         .await
-        .expect("transcribe table failed");
+        .context("player setting table failed")?;
 
-    sqldb::entry::new(PLAYER_SETTING_TABLE)
+    bot::ResponseCache::new(DB_NAME, AI_RESPONSE_CACHE_TABLE)
+        .init()
         .await
-        .expect("player setting table failed");
+        .context("ai response cache table failed")?;
+
+    Ok(())
+}
+
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("backups")
+}
+
+async fn restore_latest_backup(backup_dir: &Path) {
+    let backups = match sqldb::daily_backups(DB_NAME, backup_dir) {
+        Ok(backups) => backups,
+        Err(e) => {
+            log::warn!("find database backups failed: {e}");
+            return;
+        }
+    };
+
+    // A damaged backup is skipped
+    for backup in backups {
+        match sqldb::restore(DB_NAME, &backup).await {
+            Ok(_) => {
+                log::info!("restore database from {}", backup.display());
+                return;
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+
+    log::warn!("no database backup can be restored");
+}
+
+#[cfg(not(feature = "database-encryption"))]
+async fn create_db(db_path: &str) -> Result<()> {
+    sqldb::create_db(DB_NAME, db_path).await?;
+    Ok(())
+}
+
+#[cfg(feature = "database-encryption")]
+async fn create_db(db_path: &str) -> Result<()> {
+    match std::env::var(DB_PASSPHRASE_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => {
+            sqldb::create_encrypted_db(DB_NAME, db_path, &passphrase).await?
+        }
+        _ => sqldb::create_db(DB_NAME, db_path).await?,
+    }
+    Ok(())
 }
 
 #[macro_export]
@@ -31,8 +132,10 @@ macro_rules! db_add {
     ($table:expr, $ty:ident) => {
         fn db_add(ui: slint::Weak<crate::slint_generatedAppWindow::AppWindow>, entry: $ty) {
             tokio::spawn(async move {
-                let data = serde_json::to_string(&entry).expect("Not implement `Serialize` trait");
-                if let Err(e) = sqldb::entry::insert($table, entry.id.as_str(), &data).await {
+                if let Err(e) = sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+                    .insert(&entry)
+                    .await
+                {
                     crate::logic::toast::async_toast_warn(
                         ui,
                         format!("{}. {e}", crate::logic::tr::tr("insert entry failed")),
@@ -48,8 +151,10 @@ macro_rules! db_update {
     ($table:expr, $ty:ident) => {
         fn db_update(ui: slint::Weak<crate::slint_generatedAppWindow::AppWindow>, entry: $ty) {
             tokio::spawn(async move {
-                let data = serde_json::to_string(&entry).expect("Not implement `Serialize` trait");
-                if let Err(e) = sqldb::entry::update($table, entry.id.as_str(), &data).await {
+                if let Err(e) = sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+                    .update(&entry)
+                    .await
+                {
                     crate::logic::toast::async_toast_warn(
                         ui,
                         format!("{}. {e}", crate::logic::tr::tr("update entry failed")),
@@ -62,12 +167,19 @@ macro_rules! db_update {
 
 #[macro_export]
 macro_rules! db_select_all {
-    ($table:expr, $ty:ident) => {{
-        match sqldb::entry::select_all($table).await {
-            Ok(items) => items
-                .into_iter()
-                .filter_map(|item| serde_json::from_str::<$ty>(&item.data).ok())
-                .collect(),
+    ($table:expr, $ty:ident) => {
+        $crate::db_select_where!($table, $ty, sqldb::Query::new())
+    };
+}
+
+#[macro_export]
+macro_rules! db_select_where {
+    ($table:expr, $ty:ident, $query:expr) => {{
+        match sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+            .select_where(&$query)
+            .await
+        {
+            Ok(items) => items,
             Err(e) => {
                 log::warn!("{:?}", e);
                 vec![]
@@ -89,22 +201,17 @@ macro_rules! db_select {
         {
             let id = id.to_string();
             tokio::spawn(async move {
-                match sqldb::entry::select($table, id.as_str()).await {
-                    Ok(item) => match serde_json::from_str::<$ty>(&item.data) {
-                        Ok(data) => {
-                            let _ = slint::invoke_from_event_loop(move || {
-                                if let Some(ui) = ui.upgrade() {
-                                    callback(&ui, data);
-                                }
-                            });
-                        }
-                        Err(e) => {
-                            $crate::logic::toast::async_toast_warn(
-                                ui,
-                                format!("{}. {e}", crate::logic::tr::tr("parse entry failed")),
-                            );
-                        }
-                    },
+                match sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+                    .select(id.as_str())
+                    .await
+                {
+                    Ok(data) => {
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui.upgrade() {
+                                callback(&ui, data);
+                            }
+                        });
+                    }
                     Err(e) => {
                         if show_err_toast {
                             $crate::logic::toast::async_toast_warn(
@@ -121,14 +228,17 @@ macro_rules! db_select {
 
 #[macro_export]
 macro_rules! db_remove {
-    ($table:expr) => {
+    ($table:expr, $ty:ident) => {
         fn db_remove(
             ui: slint::Weak<crate::slint_generatedAppWindow::AppWindow>,
             id: impl ToString,
         ) {
             let id = id.to_string();
             tokio::spawn(async move {
-                if let Err(e) = sqldb::entry::delete($table, id.as_str()).await {
+                if let Err(e) = sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+                    .delete(id.as_str())
+                    .await
+                {
                     crate::logic::toast::async_toast_warn(
                         ui,
                         format!("{}. {e}", crate::logic::tr::tr("remove entry failed")),
@@ -141,10 +251,13 @@ macro_rules! db_remove {
 
 #[macro_export]
 macro_rules! db_remove_all {
-    ($table:expr) => {
+    ($table:expr, $ty:ident) => {
         fn db_remove_all(ui: slint::Weak<crate::slint_generatedAppWindow::AppWindow>) {
             tokio::spawn(async move {
-                if let Err(e) = sqldb::entry::delete_all($table).await {
+                if let Err(e) = sqldb::Repository::<$ty>::new(crate::db::DB_NAME, $table)
+                    .delete_all()
+                    .await
+                {
                     crate::logic::toast::async_toast_warn(
                         ui,
                         format!("{}. {e}", crate::logic::tr::tr("remove all entry failed")),
@@ -155,21 +268,27 @@ macro_rules! db_remove_all {
     };
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert)]
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert, SqlTable)]
 #[derivative(Default)]
+#[serde(default)]
 #[from("UIHistoryEntry")]
 pub struct HistoryEntry {
+    #[table(key)]
     pub id: String,
     pub file: String,
     pub size: String,
     pub duration: String,
     pub status: String,
+
+    // Link of the uploaded recording, it's empty before uploading
+    pub share_url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert)]
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert, SqlTable)]
 #[derivative(Default)]
 #[from("UISettingPlayer")]
 pub struct SettingPlayer {
+    #[table(key)]
     pub id: String,
     pub current_time: String,
     pub end_time: String,
@@ -185,6 +304,7 @@ pub struct Subtitle {
     pub end_timestamp: String,
     pub original_text: String,
     pub correction_text: String,
+    pub translation_text: String,
     pub audio_wave_amplitude: f32,
     pub is_timestamp_overlap: bool,
 }
@@ -192,8 +312,18 @@ pub struct Subtitle {
 #[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert)]
 #[derivative(Default)]
 #[serde(default)]
+#[from("UITranscribeChapter")]
+pub struct TranscribeChapter {
+    pub timestamp: String,
+    pub title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Derivative, SlintFromConvert, SqlTable)]
+#[derivative(Default)]
+#[serde(default)]
 #[from("UITranscribe")]
 pub struct Transcribe {
+    #[table(key)]
     pub id: String,
     pub file_path: String,
     pub is_file_exist: bool,
@@ -201,4 +331,9 @@ pub struct Transcribe {
 
     #[vec(from = "subtitles")]
     pub subtitles: Vec<Subtitle>,
+
+    pub summary: String,
+
+    #[vec(from = "chapters")]
+    pub chapters: Vec<TranscribeChapter>,
 }
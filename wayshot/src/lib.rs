@@ -74,9 +74,6 @@ async fn ui_before() {
     init_logger();
     config::init();
 
-    #[cfg(feature = "database")]
-    db::init(config::all().db_path.to_str().expect("invalid db path")).await;
-
     #[cfg(target_os = "linux")]
     {
         _ = slint::set_xdg_app_id("wayshot".to_string());
@@ -92,6 +89,28 @@ fn ui_after(ui: &AppWindow) {
     logic::init(ui);
 }
 
+// The app logic is initialized after the database is opened, the error of
+// opening it is shown in the window
+#[cfg(any(feature = "desktop", feature = "mobile"))]
+async fn ui_after_db(ui: &AppWindow) {
+    #[cfg(feature = "database")]
+    {
+        let db_path = config::all()
+            .db_path
+            .to_str()
+            .expect("invalid db path")
+            .to_string();
+
+        if let Err(e) = db::init(&db_path).await {
+            log::warn!("open database failed: {e:?}");
+            logic::database_open_failed(ui, db_path, e);
+            return;
+        }
+    }
+
+    ui_after(ui);
+}
+
 #[cfg(feature = "android")]
 #[unsafe(no_mangle)]
 #[tokio::main]
@@ -103,7 +122,7 @@ async fn android_main(app: slint::android::AndroidApp) {
     ui_before().await;
     let ui = AppWindow::new().unwrap();
     global_store!(ui).set_device_type(DeviceType::Mobile);
-    ui_after(&ui);
+    ui_after_db(&ui).await;
 
     ui.run().unwrap();
 
@@ -117,11 +136,18 @@ pub async fn desktop_main() {
     ui_before().await;
     let ui = AppWindow::new().unwrap();
     global_store!(ui).set_device_type(DeviceType::Desktop);
-    ui_after(&ui);
+    ui_after_db(&ui).await;
 
     global_util!(ui).invoke_set_window_center();
 
-    ui.run().unwrap();
+    // Closing the window hides it into the tray, the app is quitted from the
+    // tray menu or the status bar
+    if config::all().tray.enable {
+        ui.show().unwrap();
+        slint::run_event_loop_until_quit().unwrap();
+    } else {
+        ui.run().unwrap();
+    }
 
     log::debug!("exit...");
 }
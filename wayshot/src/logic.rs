@@ -41,9 +41,33 @@ mod realtime_image_effect;
 #[cfg(feature = "desktop")]
 mod downloader;
 
+#[cfg(feature = "desktop")]
+mod ocr;
+
+#[cfg(feature = "desktop")]
+mod hotkey;
+
+#[cfg(feature = "desktop")]
+mod profile;
+
+#[cfg(feature = "desktop")]
+mod uploader;
+
+#[cfg(feature = "desktop")]
+mod tray;
+
+#[cfg(feature = "desktop")]
+mod screenshot_editor;
+
 #[cfg(any(feature = "desktop", feature = "mobile"))]
 mod transcribe;
 
+#[cfg(feature = "database")]
+mod database;
+
+#[cfg(feature = "database")]
+pub use database::open_failed as database_open_failed;
+
 pub fn init(ui: &AppWindow) {
     #[cfg(any(feature = "desktop", feature = "mobile"))]
     {
@@ -68,6 +92,12 @@ pub fn init(ui: &AppWindow) {
         realtime_image_effect::init(ui);
         transcribe::init(ui);
         downloader::init(ui);
+        ocr::init(ui);
+        hotkey::init(ui);
+        profile::init(ui);
+        uploader::init(ui);
+        tray::init(ui);
+        screenshot_editor::init(ui);
     }
 }
 
@@ -78,8 +78,13 @@ pub fn inner_init(ui: &AppWindow) {
         });
     }
 
-    let downloaders = BackgroundRemoverModel::all_models()
+    let ui_models = [
+        UIBackgroundRemoverModel::Modnet,
+        UIBackgroundRemoverModel::Rmbg14,
+    ];
+    let downloaders = ui_models
         .into_iter()
+        .map(BackgroundRemoverModel::from)
         .map(|m| UIDownloader {
             url: m.download_url().to_string().into(),
             filename: m.to_filename().to_string().into(),
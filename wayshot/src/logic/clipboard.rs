@@ -8,6 +8,12 @@ use crate::{global_logic, slint_generatedAppWindow::AppWindow, toast_success, to
 use anyhow::{bail, Result};
 use slint::ComponentHandle;
 
+#[cfg(feature = "desktop")]
+use clipboard_utils::{ClipboardError, Content};
+
+#[cfg(feature = "desktop")]
+use std::path::Path;
+
 /// Copies text to clipboard on desktop platforms
 /// 
 /// Supports both X11 and Wayland clipboard backends on Linux.
@@ -18,7 +24,7 @@ use slint::ComponentHandle;
 /// # Returns
 /// - `Result<()>` indicating success or failure
 #[cfg(feature = "desktop")]
-fn copy_to_clipboard(msg: &str) -> Result<()> {
+pub fn copy_to_clipboard(msg: &str) -> Result<()> {
     #[cfg(target_os = "linux")]
     {
         if super::util::is_wayland() && copy_to_wayland_clipboard(msg).is_ok() {
@@ -38,6 +44,59 @@ fn copy_to_clipboard(msg: &str) -> Result<()> {
     }
 }
 
+/// Copies a PNG image to clipboard on desktop platforms
+/// 
+/// Supports the Wayland and Windows clipboards. The `wl-copy` command is
+/// used on the compositors without the wlr-data-control protocol.
+/// 
+/// # Parameters
+/// - `png`: Encoded PNG image
+/// 
+/// # Returns
+/// - `Result<()>` indicating success or failure
+#[cfg(feature = "desktop")]
+pub fn copy_image_to_clipboard(png: &[u8]) -> Result<()> {
+    match clipboard_utils::copy(Content::Image(png.to_vec())) {
+        #[cfg(target_os = "linux")]
+        Err(ClipboardError::Unsupported(e)) if super::util::is_wayland() => {
+            log::info!("copy the image with wl-copy instead: {e}");
+            duct::cmd!("wl-copy", "--type", "image/png")
+                .stdin_bytes(png)
+                .run()?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Copies a file reference to clipboard on desktop platforms
+/// 
+/// The file managers paste the file itself. The path is copied as text
+/// where file references aren't supported.
+/// 
+/// # Parameters
+/// - `file`: Path of the file
+/// 
+/// # Returns
+/// - `Result<()>` indicating success or failure
+#[cfg(feature = "desktop")]
+pub fn copy_file_to_clipboard(file: &Path) -> Result<()> {
+    if !file.exists() {
+        bail!("{} not found", file.display());
+    }
+
+    let file = std::path::absolute(file)?;
+    match clipboard_utils::copy(Content::Files(vec![file.clone()])) {
+        Err(ClipboardError::Unsupported(e)) => {
+            log::info!("copy the file path as text instead: {e}");
+            copy_to_clipboard(&file.to_string_lossy())
+        }
+        Err(e) => Err(e.into()),
+        Ok(_) => Ok(()),
+    }
+}
+
 /// Pastes text from clipboard on desktop platforms
 /// 
 /// Supports both X11 and Wayland clipboard backends on Linux.
@@ -75,7 +134,7 @@ fn paste_from_clipboard() -> Result<String> {
 /// # Returns
 /// - `Result<()>` indicating success or failure
 #[cfg(feature = "android")]
-fn copy_to_clipboard(msg: &str) -> Result<()> {
+pub fn copy_to_clipboard(msg: &str) -> Result<()> {
     match android_clipboard::set_text(msg.to_string()) {
         Err(e) => bail!("{e:?}"),
         _ => Ok(()),
@@ -136,6 +195,21 @@ pub fn init(ui: &AppWindow) {
         }
     });
 
+    #[cfg(feature = "desktop")]
+    {
+        let ui_weak = ui.as_weak();
+        global_logic!(ui).on_copy_file_to_clipboard(move |file| {
+            let ui = ui_weak.unwrap();
+            match copy_file_to_clipboard(Path::new(file.as_str())) {
+                Err(e) => toast_warn!(
+                    ui,
+                    format!("{}. {}: {e:?}", tr("Copy failed"), tr("Reason"))
+                ),
+                _ => toast_success!(ui, tr("Copy success")),
+            }
+        });
+    }
+
     let ui_weak = ui.as_weak();
     global_logic!(ui).on_paste_from_clipboard(move || {
         let ui = ui_weak.unwrap();
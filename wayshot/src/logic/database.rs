@@ -0,0 +1,65 @@
+use crate::slint_generatedAppWindow::AppWindow;
+use slint::ComponentHandle;
+
+#[cfg(feature = "database-encryption")]
+use {
+    crate::{db, global_util, slint_generatedAppWindow::PasswordSetting},
+    slint::SharedString,
+};
+
+#[cfg(not(feature = "database-encryption"))]
+use {super::tr::tr, crate::toast_warn};
+
+#[cfg(feature = "database-encryption")]
+const PASSPHRASE_HANDLE_TYPE: &str = "database-passphrase";
+
+// The passphrase of the encrypted database is asked for, the app logic is
+// initialized once it's opened
+#[cfg(feature = "database-encryption")]
+pub fn open_failed(ui: &AppWindow, db_path: String, err: anyhow::Error) {
+    // Canceling quits the app before anything is initialized
+    global_util!(ui).on_close_window(move || {
+        std::process::exit(0);
+    });
+
+    let ui_weak = ui.as_weak();
+    global_util!(ui).on_handle_password_dialog(move |handle_type, password, _user_data| {
+        if handle_type != PASSPHRASE_HANDLE_TYPE {
+            return SharedString::default();
+        }
+
+        // The prompt waits for the key derivation, so a wrong passphrase is
+        // shown in it right away
+        let handle = tokio::runtime::Handle::current();
+        let result = tokio::task::block_in_place(|| {
+            handle.block_on(db::init_with_passphrase(&db_path, &password))
+        });
+
+        match result {
+            Ok(_) => {
+                let ui = ui_weak.unwrap();
+                ui.global::<PasswordSetting>()
+                    .invoke_set(false, "".into(), "".into());
+                super::init(&ui);
+                SharedString::default()
+            }
+            Err(e) => {
+                log::warn!("open encrypted database failed: {e:?}");
+                slint::format!("{e}")
+            }
+        }
+    });
+
+    ui.global::<PasswordSetting>().invoke_set(
+        true,
+        PASSPHRASE_HANDLE_TYPE.into(),
+        slint::format!("{err}"),
+    );
+}
+
+// The app runs without the history and the transcriptions
+#[cfg(not(feature = "database-encryption"))]
+pub fn open_failed(ui: &AppWindow, _db_path: String, err: anyhow::Error) {
+    super::init(ui);
+    toast_warn!(ui, format!("{}. {err}", tr("Open database failed")));
+}
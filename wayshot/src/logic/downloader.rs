@@ -2,7 +2,7 @@ use crate::{
     logic::{recorder::picker_directory, toast, tr::tr},
     slint_generatedAppWindow::AppWindow,
 };
-use downloader::{DownloadState, Downloader};
+use downloader::{DownloadState, Downloader, RateLimiter};
 use once_cell::sync::Lazy;
 use slint::{ComponentHandle, SharedString};
 use std::{
@@ -17,6 +17,9 @@ use std::{
 static DOWNLOADER_CACHE: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+// Shared by all the downloads, so the limit caps them together
+static DOWNLOAD_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::default);
+
 pub fn init(_ui: &AppWindow) {}
 
 pub fn downloader_start(
@@ -43,7 +46,8 @@ pub fn downloader_start(
         });
 
         let ui_weak_clone = ui_weak.clone();
-        let downloader = Downloader::new(url.to_string(), save_path.clone());
+        let downloader = Downloader::new(url.to_string(), save_path.clone())
+            .with_limiter(DOWNLOAD_LIMITER.clone());
 
         DOWNLOADER_CACHE
             .lock()
@@ -105,3 +109,8 @@ pub fn downloader_cancel(
         cb(&ui);
     }
 }
+
+// Bytes per second of all the downloads, 0 is unlimited
+pub fn downloader_set_rate_limit(bytes_per_sec: u64) {
+    DOWNLOAD_LIMITER.set_rate(bytes_per_sec);
+}
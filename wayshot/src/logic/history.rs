@@ -1,16 +1,24 @@
 use crate::{
     config,
     db::{HISTORY_TABLE as DB_TABLE, HistoryEntry},
-    db_select_all,
-    logic::tr::tr,
+    db_select_where,
+    logic::{tr::tr, uploader},
     logic_cb,
     slint_generatedAppWindow::{AppWindow, HistoryEntry as UIHistoryEntry},
     toast_success,
 };
+use cutil::fs::{DirWatcher, WatchOptions};
+use once_cell::sync::Lazy;
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
-use std::{fs, path::PathBuf};
+use sqldb::{Order, Query, ROWID_COLUMN};
+use std::{fs, path::PathBuf, sync::Mutex};
 use uuid::Uuid;
 
+// Watcher of the save directory, so the statuses of the histories follow the
+// recordings removed or restored outside of the app
+static SAVE_DIR_WATCHER: Lazy<Mutex<Option<(PathBuf, DirWatcher)>>> =
+    Lazy::new(|| Mutex::new(None));
+
 #[macro_export]
 macro_rules! store_history_entries {
     ($ui:expr) => {
@@ -23,8 +31,8 @@ macro_rules! store_history_entries {
 }
 
 crate::db_add!(DB_TABLE, HistoryEntry);
-crate::db_remove!(DB_TABLE);
-crate::db_remove_all!(DB_TABLE);
+crate::db_remove!(DB_TABLE, HistoryEntry);
+crate::db_remove_all!(DB_TABLE, HistoryEntry);
 
 pub fn init(ui: &AppWindow) {
     inner_init(ui);
@@ -41,10 +49,16 @@ pub fn init(ui: &AppWindow) {
 fn inner_init(ui: &AppWindow) {
     store_history_entries!(ui).set_vec(vec![]);
 
+    let save_dir = PathBuf::from(&config::all().recorder.save_dir);
+    watch_save_dir(ui.as_weak(), save_dir.clone());
+
     let ui = ui.as_weak();
     tokio::spawn(async move {
-        let save_dir = PathBuf::from(&config::all().recorder.save_dir);
-        let entries = db_select_all!(DB_TABLE, HistoryEntry);
+        let entries = db_select_where!(
+            DB_TABLE,
+            HistoryEntry,
+            Query::new().order_by(ROWID_COLUMN, Order::Desc)
+        );
 
         _ = ui.upgrade_in_event_loop(move |ui| {
             let entries = entries
@@ -58,7 +72,6 @@ fn inner_init(ui: &AppWindow) {
                     }
                     entry
                 })
-                .rev()
                 .collect::<Vec<UIHistoryEntry>>();
 
             store_history_entries!(ui).set_vec(entries);
@@ -66,6 +79,43 @@ fn inner_init(ui: &AppWindow) {
     });
 }
 
+fn watch_save_dir(ui: slint::Weak<AppWindow>, save_dir: PathBuf) {
+    let mut watcher = SAVE_DIR_WATCHER.lock().unwrap();
+    if watcher.as_ref().is_some_and(|(dir, _)| *dir == save_dir) {
+        return;
+    }
+
+    *watcher = None;
+    if !save_dir.is_dir() {
+        return;
+    }
+
+    let options = WatchOptions::default().with_extensions(&["mp4"]);
+    match cutil::fs::watch_dir_with(&save_dir, options, move |_| {
+        _ = ui.upgrade_in_event_loop(|ui| update_histories_status(&ui));
+    }) {
+        Ok(dir_watcher) => *watcher = Some((save_dir, dir_watcher)),
+        Err(e) => log::warn!("watch {} failed: {e}", save_dir.display()),
+    }
+}
+
+fn update_histories_status(ui: &AppWindow) {
+    let save_dir = PathBuf::from(&config::all().recorder.save_dir);
+
+    for (index, mut entry) in store_history_entries!(ui).iter().enumerate() {
+        let status: SharedString = if save_dir.join(&entry.file).exists() {
+            SharedString::default()
+        } else {
+            tr("No Found").into()
+        };
+
+        if entry.status != status {
+            entry.status = status;
+            store_history_entries!(ui).set_row_data(index, entry);
+        }
+    }
+}
+
 fn history_statistics(
     _ui: &AppWindow,
     entries: ModelRc<UIHistoryEntry>,
@@ -120,9 +170,15 @@ fn add_history(ui: &AppWindow, file_path: SharedString) {
         size: file_size,
         duration: file_duration,
         status: String::default(),
+        ..Default::default()
     };
 
     store_history_entries!(ui).insert(0, entry.clone().into());
+
+    if config::all().upload.auto_upload {
+        uploader::enqueue(entry.id.as_str(), file_path.as_str());
+    }
+
     db_add(ui.as_weak(), entry);
 }
 
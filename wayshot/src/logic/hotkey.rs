@@ -0,0 +1,160 @@
+//! Global hotkeys which control the recorder while the window isn't focused.
+//!
+//! The shortcuts are bound through the GlobalShortcuts portal on Wayland and
+//! `RegisterHotKey` on Windows. Bindings are read from the `hotkey` section of
+//! the config file.
+//!
+//! A replay saves the last `replay_secs` of the screen recording into a file
+//! of its own, the seconds are only kept while `save_replay` is bound.
+
+#[cfg(target_os = "linux")]
+mod portal;
+
+#[cfg(target_os = "windows")]
+mod win;
+
+use crate::{
+    config, global_logic, global_store,
+    slint_generatedAppWindow::{AppWindow, RecordStatus as UIRecordStatus},
+};
+use slint::ComponentHandle;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    ToggleRecording,
+    StopRecording,
+    AddChapter,
+    PauseRecording,
+    SaveReplay,
+}
+
+impl HotkeyAction {
+    pub fn id(&self) -> &'static str {
+        match self {
+            HotkeyAction::ToggleRecording => "toggle-recording",
+            HotkeyAction::StopRecording => "stop-recording",
+            HotkeyAction::AddChapter => "add-chapter",
+            HotkeyAction::PauseRecording => "pause-recording",
+            HotkeyAction::SaveReplay => "save-replay",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            HotkeyAction::ToggleRecording => "Start or stop recording",
+            HotkeyAction::StopRecording => "Stop recording",
+            HotkeyAction::AddChapter => "Add chapter",
+            HotkeyAction::PauseRecording => "Pause or resume recording",
+            HotkeyAction::SaveReplay => "Save replay",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        [
+            HotkeyAction::ToggleRecording,
+            HotkeyAction::StopRecording,
+            HotkeyAction::AddChapter,
+            HotkeyAction::PauseRecording,
+            HotkeyAction::SaveReplay,
+        ]
+        .into_iter()
+        .find(|action| action.id() == id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub action: HotkeyAction,
+    pub trigger: String,
+}
+
+pub fn init(ui: &AppWindow) {
+    let setting = config::all().hotkey;
+    if !setting.enable {
+        return;
+    }
+
+    let bindings = [
+        (HotkeyAction::ToggleRecording, setting.toggle_recording),
+        (HotkeyAction::StopRecording, setting.stop_recording),
+        (HotkeyAction::AddChapter, setting.add_chapter),
+        (HotkeyAction::PauseRecording, setting.pause_recording),
+        (HotkeyAction::SaveReplay, setting.save_replay),
+    ]
+    .into_iter()
+    .filter(|(_, trigger)| !trigger.trim().is_empty())
+    .map(|(action, trigger)| Binding { action, trigger })
+    .collect::<Vec<_>>();
+
+    if bindings.is_empty() {
+        return;
+    }
+
+    let (sender, mut receiver) = unbounded_channel();
+    listen(bindings, sender);
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        while let Some(action) = receiver.recv().await {
+            log::info!("global hotkey activated: {action:?}");
+            _ = ui_weak.upgrade_in_event_loop(move |ui| trigger(&ui, action));
+        }
+
+        log::info!("global hotkey receiver exit...");
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn listen(bindings: Vec<Binding>, sender: UnboundedSender<HotkeyAction>) {
+    tokio::spawn(async move {
+        if let Err(e) = portal::listen(bindings, sender).await {
+            log::warn!("bind global shortcuts with portal failed: {e}");
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+fn listen(bindings: Vec<Binding>, sender: UnboundedSender<HotkeyAction>) {
+    win::listen(bindings, sender);
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn listen(_bindings: Vec<Binding>, _sender: UnboundedSender<HotkeyAction>) {
+    log::warn!("global hotkeys are not supported on this platform");
+}
+
+fn trigger(ui: &AppWindow, action: HotkeyAction) {
+    // The recording is already started while counting down
+    let is_recording = global_store!(ui).get_record_status() == UIRecordStatus::Recording
+        || global_store!(ui).get_recording_countdown() > 0;
+
+    match action {
+        HotkeyAction::ToggleRecording if is_recording => {
+            global_logic!(ui).invoke_stop_recording();
+        }
+        HotkeyAction::ToggleRecording => global_logic!(ui).invoke_start_recording(),
+        HotkeyAction::StopRecording if is_recording => {
+            global_logic!(ui).invoke_stop_recording();
+        }
+        HotkeyAction::StopRecording => (),
+        HotkeyAction::AddChapter
+            if global_store!(ui).get_record_status() == UIRecordStatus::Recording =>
+        {
+            global_logic!(ui).invoke_add_chapter();
+        }
+        HotkeyAction::AddChapter => (),
+        HotkeyAction::PauseRecording
+            if global_store!(ui).get_record_status() == UIRecordStatus::Recording =>
+        {
+            global_logic!(ui).invoke_toggle_pause_recording();
+        }
+        HotkeyAction::PauseRecording => (),
+        HotkeyAction::SaveReplay
+            if global_store!(ui).get_record_status() == UIRecordStatus::Recording =>
+        {
+            global_logic!(ui).invoke_save_replay();
+        }
+        HotkeyAction::SaveReplay => (),
+    }
+}
@@ -0,0 +1,51 @@
+use super::{Binding, HotkeyAction};
+use crate::logic::tr::tr;
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use futures::StreamExt;
+use tokio::sync::mpsc::UnboundedSender;
+
+// The compositor may ignore the preferred triggers and ask the user to
+// assign them, the actual triggers are logged after binding
+pub async fn listen(
+    bindings: Vec<Binding>,
+    sender: UnboundedSender<HotkeyAction>,
+) -> ashpd::Result<()> {
+    let proxy = GlobalShortcuts::new().await?;
+    let session = proxy.create_session().await?;
+
+    let shortcuts = bindings
+        .iter()
+        .map(|binding| {
+            NewShortcut::new(binding.action.id(), tr(binding.action.description()))
+                .preferred_trigger(binding.trigger.as_str())
+        })
+        .collect::<Vec<_>>();
+
+    let response = proxy
+        .bind_shortcuts(&session, &shortcuts, None)
+        .await?
+        .response()?;
+
+    for shortcut in response.shortcuts() {
+        log::info!(
+            "bind global shortcut `{}`: {}",
+            shortcut.id(),
+            shortcut.trigger_description()
+        );
+    }
+
+    let mut activated = proxy.receive_activated().await?;
+    while let Some(event) = activated.next().await {
+        let Some(action) = HotkeyAction::from_id(event.shortcut_id()) else {
+            log::warn!("unknown global shortcut: {}", event.shortcut_id());
+            continue;
+        };
+
+        if sender.send(action).is_err() {
+            break;
+        }
+    }
+
+    session.close().await?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use super::{Binding, HotkeyAction};
+use std::{ptr, thread};
+use tokio::sync::mpsc::UnboundedSender;
+use winapi::um::winuser::{
+    GetMessageW, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, MSG, RegisterHotKey,
+    UnregisterHotKey, VK_F1, VK_SPACE, WM_HOTKEY,
+};
+
+// The hotkeys are bound to the thread, so they are registered and received
+// on the same thread
+pub fn listen(bindings: Vec<Binding>, sender: UnboundedSender<HotkeyAction>) {
+    thread::spawn(move || {
+        let mut registered = vec![];
+
+        for (index, binding) in bindings.iter().enumerate() {
+            let Some((modifiers, vk)) = parse_trigger(&binding.trigger) else {
+                log::warn!("invalid hotkey trigger: {}", binding.trigger);
+                continue;
+            };
+
+            let id = index as i32 + 1;
+            if unsafe { RegisterHotKey(ptr::null_mut(), id, modifiers | MOD_NOREPEAT as u32, vk) }
+                == 0
+            {
+                log::warn!("register hotkey `{}` failed", binding.trigger);
+                continue;
+            }
+
+            log::info!(
+                "register hotkey `{}`: {}",
+                binding.action.id(),
+                binding.trigger
+            );
+            registered.push((id, binding.action));
+        }
+
+        if registered.is_empty() {
+            return;
+        }
+
+        let mut msg: MSG = unsafe { std::mem::zeroed() };
+        while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+            if msg.message != WM_HOTKEY {
+                continue;
+            }
+
+            let Some((_, action)) = registered.iter().find(|(id, _)| *id == msg.wParam as i32)
+            else {
+                continue;
+            };
+
+            if sender.send(*action).is_err() {
+                break;
+            }
+        }
+
+        for (id, _) in registered {
+            unsafe { UnregisterHotKey(ptr::null_mut(), id) };
+        }
+    });
+}
+
+// `CTRL+ALT+R` -> (MOD_CONTROL | MOD_ALT, 'R')
+fn parse_trigger(trigger: &str) -> Option<(u32, u32)> {
+    let mut modifiers = 0;
+    let mut vk = None;
+
+    for key in trigger.split('+').map(|key| key.trim().to_uppercase()) {
+        match key.as_str() {
+            "CTRL" | "CONTROL" => modifiers |= MOD_CONTROL as u32,
+            "ALT" => modifiers |= MOD_ALT as u32,
+            "SHIFT" => modifiers |= MOD_SHIFT as u32,
+            "LOGO" | "SUPER" | "WIN" => modifiers |= MOD_WIN as u32,
+            "SPACE" => vk = Some(VK_SPACE as u32),
+            key if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                vk = Some(key.as_bytes()[0] as u32);
+            }
+            key if key.starts_with('F') => match key[1..].parse::<u32>() {
+                Ok(n @ 1..=24) => vk = Some(VK_F1 as u32 + n - 1),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+
+    vk.map(|vk| (modifiers, vk))
+}
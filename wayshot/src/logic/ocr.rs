@@ -0,0 +1,69 @@
+use crate::{
+    config,
+    logic::{
+        clipboard::copy_to_clipboard,
+        recorder::capture_current_screen,
+        toast::{async_toast_success, async_toast_warn},
+        tr::tr,
+    },
+    logic_cb,
+    slint_generatedAppWindow::AppWindow,
+    toast_info,
+};
+use anyhow::Result;
+use ocr::{Model, Ocr, OcrConfig, TextBox};
+use screen_capture::Capture;
+use slint::ComponentHandle;
+use std::{path::PathBuf, thread};
+
+pub fn init(ui: &AppWindow) {
+    logic_cb!(copy_screenshot_text, ui);
+}
+
+pub fn ocr_models_dir() -> PathBuf {
+    config::all().cache_dir.join("ocr")
+}
+
+fn copy_screenshot_text(ui: &AppWindow) {
+    toast_info!(ui, tr("Recognizing text..."));
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || {
+        let result = capture_current_screen().and_then(|capture| recognize_text(&capture));
+
+        match result {
+            Ok(text) if text.is_empty() => async_toast_warn(ui_weak, tr("No text found")),
+            Ok(text) => match copy_to_clipboard(&text) {
+                Ok(_) => async_toast_success(ui_weak, tr("Copy success")),
+                Err(e) => async_toast_warn(
+                    ui_weak,
+                    format!("{}. {}: {e:?}", tr("Copy failed"), tr("Reason")),
+                ),
+            },
+            Err(e) => async_toast_warn(
+                ui_weak,
+                format!("{}. {}: {e}", tr("Recognize text failed"), tr("Reason")),
+            ),
+        }
+    });
+}
+
+fn recognize_text(capture: &Capture) -> Result<String> {
+    let models_dir = ocr_models_dir();
+    for model in Model::all_models() {
+        let path = models_dir.join(model.to_filename());
+        if !path.exists() {
+            log::warn!(
+                "OCR model file not found: {}. Download it from {}",
+                path.display(),
+                model.download_url()
+            );
+        }
+    }
+
+    let mut ocr = Ocr::new(OcrConfig::from_dir(models_dir))?;
+    let boxes = ocr.recognize_capture(capture)?;
+    log::info!("recognized {} text boxes", boxes.len());
+
+    Ok(TextBox::to_text(&boxes))
+}
@@ -1,6 +1,6 @@
 use crate::{
     config,
-    db::{PLAYER_SETTING_TABLE as DB_TABLE, SettingPlayer},
+    db::{DB_NAME, PLAYER_SETTING_TABLE as DB_TABLE, SettingPlayer},
     global_store,
     logic::tr::tr,
     logic_cb,
@@ -36,7 +36,7 @@ macro_rules! store_playlist_entries {
     };
 }
 
-crate::db_remove_all!(DB_TABLE);
+crate::db_remove_all!(DB_TABLE, SettingPlayer);
 crate::db_add!(DB_TABLE, SettingPlayer);
 crate::db_update!(DB_TABLE, SettingPlayer);
 
@@ -71,8 +71,9 @@ fn inner_init(ui: &AppWindow) {
 
     let ui_weak = ui.as_weak();
     tokio::spawn(async move {
-        let entry = if let Ok(setting) = sqldb::entry::select(DB_TABLE, PLAYER_SETTING_ID).await
-            && let Ok(entry) = serde_json::from_str::<SettingPlayer>(&setting.data)
+        let entry = if let Ok(entry) = sqldb::Repository::<SettingPlayer>::new(DB_NAME, DB_TABLE)
+            .select(PLAYER_SETTING_ID)
+            .await
         {
             entry
         } else {
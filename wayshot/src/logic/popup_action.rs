@@ -23,6 +23,9 @@ pub fn init(ui: &AppWindow) {
                 "show-realtime-image-effect-dialog" => {
                     global_logic!(ui).invoke_show_realtime_image_effect_dialog(true);
                 }
+                "edit-screenshot" => {
+                    global_logic!(ui).invoke_edit_screenshot();
+                }
                 "transcribe-subtitles-correction" => {
                     global_logic!(ui).invoke_transcribe_subtitles_correction();
                 }
@@ -32,6 +35,12 @@ pub fn init(ui: &AppWindow) {
                 "transcribe-subtitles-remove-correction" => {
                     global_logic!(ui).invoke_transcribe_subtitles_remove_correction();
                 }
+                "transcribe-subtitles-remove-translation" => {
+                    global_logic!(ui).invoke_transcribe_subtitles_remove_translation();
+                }
+                "transcribe-export-bilingual-subtitles" => {
+                    global_logic!(ui).invoke_transcribe_export_bilingual_subtitles(user_data);
+                }
                 "transcribe-subtitles-adjust-overlap-timestamp" => {
                     global_logic!(ui).invoke_transcribe_subtitles_adjust_overlap_timestamp();
                 }
@@ -0,0 +1,134 @@
+use crate::{
+    config::{self, profiles},
+    global_store,
+    logic::{recorder::picker_directory, share_screen::picker_file, toast, tr::tr},
+    logic_cb,
+    slint_generatedAppWindow::AppWindow,
+    toast_success, toast_warn,
+};
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+
+#[macro_export]
+macro_rules! store_recording_profiles {
+    ($ui:expr) => {
+        crate::global_store!($ui)
+            .get_recording_profiles()
+            .as_any()
+            .downcast_ref::<VecModel<SharedString>>()
+            .expect("We know we set a VecModel<SharedString> earlier")
+    };
+}
+
+pub fn init(ui: &AppWindow) {
+    global_store!(ui).set_recording_profiles(ModelRc::new(VecModel::from_slice(&[])));
+    update_profiles(ui);
+
+    logic_cb!(apply_recording_profile, ui, name);
+    logic_cb!(save_recording_profile, ui, name);
+    logic_cb!(remove_recording_profile, ui, name);
+    logic_cb!(export_recording_profile, ui, name);
+    logic_cb!(import_recording_profile, ui);
+}
+
+fn update_profiles(ui: &AppWindow) {
+    let names = profiles::all()
+        .into_iter()
+        .map(|item| SharedString::from(item.name))
+        .collect::<Vec<_>>();
+
+    store_recording_profiles!(ui).set_vec(names);
+    global_store!(ui).set_current_recording_profile(config::all().current_profile.into());
+}
+
+fn apply_recording_profile(ui: &AppWindow, name: SharedString) {
+    match profiles::apply(&name) {
+        Ok(_) => {
+            global_store!(ui).set_current_recording_profile(name.clone());
+            toast_success!(ui, format!("{} `{name}`", tr("Switch to profile")));
+        }
+        Err(e) => toast_warn!(
+            ui,
+            format!("{}. {}: {e}", tr("Switch profile failed"), tr("Reason"))
+        ),
+    }
+}
+
+fn save_recording_profile(ui: &AppWindow, name: SharedString) {
+    match profiles::save_current(&name) {
+        Ok(_) => {
+            update_profiles(ui);
+            toast_success!(ui, tr("Save profile successfully"));
+        }
+        Err(e) => toast_warn!(
+            ui,
+            format!("{}. {}: {e}", tr("Save profile failed"), tr("Reason"))
+        ),
+    }
+}
+
+fn remove_recording_profile(ui: &AppWindow, name: SharedString) {
+    match profiles::remove(&name) {
+        Ok(_) => {
+            update_profiles(ui);
+            toast_success!(ui, tr("Remove profile successfully"));
+        }
+        Err(e) => toast_warn!(
+            ui,
+            format!("{}. {}: {e}", tr("Remove profile failed"), tr("Reason"))
+        ),
+    }
+}
+
+fn export_recording_profile(ui: &AppWindow, name: SharedString) {
+    let Some(profile) = profiles::find(&name) else {
+        toast_warn!(ui, format!("{} `{name}`", tr("No found profile")));
+        return;
+    };
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let filename = format!("{}.toml", profile.name);
+        let Some(dir) = picker_directory(ui_weak.clone(), &tr("Choose a directory"), &filename)
+        else {
+            return;
+        };
+
+        let path = dir.join(filename);
+        match profile.export(&path) {
+            Ok(_) => toast::async_toast_success(
+                ui_weak,
+                format!("{} {}", tr("Export profile to"), path.display()),
+            ),
+            Err(e) => toast::async_toast_warn(
+                ui_weak,
+                format!("{}. {}: {e}", tr("Export profile failed"), tr("Reason")),
+            ),
+        }
+    });
+}
+
+fn import_recording_profile(ui: &AppWindow) {
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let Some(path) = picker_file(ui_weak.clone(), &tr("Choose a profile"), "toml", &["toml"])
+        else {
+            return;
+        };
+
+        match profiles::import(&path) {
+            Ok(profile) => {
+                _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                    update_profiles(&ui);
+                    toast_success!(
+                        ui,
+                        format!("{} `{}`", tr("Import profile successfully"), profile.name)
+                    );
+                });
+            }
+            Err(e) => toast::async_toast_warn(
+                ui_weak,
+                format!("{}. {}: {e}", tr("Import profile failed"), tr("Reason")),
+            ),
+        }
+    });
+}
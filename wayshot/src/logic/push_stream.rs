@@ -4,7 +4,7 @@ use crate::{
     logic_cb,
     slint_generatedAppWindow::{AppWindow, SettingPushStream as UISettingPushStream},
 };
-use recorder::PushStreamConfig;
+use recorder::{PushStreamConfig, RateControl};
 use slint::{ComponentHandle, SharedString};
 
 pub fn init(ui: &AppWindow) {
@@ -21,8 +21,19 @@ fn push_stream_verify_setting(_ui: &AppWindow, config: UISettingPushStream) -> S
 
 impl From<config::PushStream> for PushStreamConfig {
     fn from(c: config::PushStream) -> PushStreamConfig {
-        PushStreamConfig::new(c.server_addr, c.app, c.stream_key)
+        let config = PushStreamConfig::new(c.server_addr, c.app, c.stream_key)
             .with_save_mp4(c.save_mp4)
-            .with_query_params(c.query_params)
+            .with_query_params(c.query_params);
+
+        // The HRD buffer holds one second, as the platforms recommend
+        if c.bitrate > 0 {
+            let bitrate = c.bitrate as u32 * 1000;
+            config.with_rate_control(RateControl::Cbr {
+                bitrate,
+                buffer_size: bitrate,
+            })
+        } else {
+            config
+        }
     }
 }
@@ -1,26 +1,37 @@
 use crate::{
     config, global_logic, global_store,
     logic::{
+        downloader::downloader_set_rate_limit,
         realtime_image_effect::get_realtime_image_effect,
-        toast::{self, async_toast_warn},
+        screenshot_editor::FONT,
+        toast::{self, async_toast_success, async_toast_warn},
         tr::tr,
     },
     logic_cb,
     slint_generatedAppWindow::{
         AppWindow, FeatureType, Fps as UIFps, ProcessMode as UIProcessMode,
         RecordStatus as UIRecordStatus, Resolution as UIResolution,
-        SettingControl as UISettingControl, Source as UISource, SourceType,
+        SettingControl as UISettingControl, SettingRecorder as UISettingRecorder,
+        Source as UISource, SourceType,
     },
-    toast_success, toast_warn,
+    toast_info, toast_success, toast_warn,
+};
+use anyhow::{Result, anyhow, bail};
+use fun_ast_nano::{
+    DEFAULT_HOTWORD_BOOST, FunASRModelConfig, FunAsrNanoGenerateModel, StreamingConfig,
+    StreamingTranscriber,
 };
-use anyhow::{Result, bail};
 use once_cell::sync::Lazy;
 use recorder::{
-    AsyncErrorChannel, AsyncErrorReceiver, AsyncErrorSender, AudioRecorder, FPS, ProcessMode,
-    RecorderConfig, RecordingSession, Resolution, SpeakerRecorder, SpeakerRecorderConfig, bounded,
-    platform_screen_capture, platform_speaker_recoder,
+    AsyncErrorChannel, AsyncErrorReceiver, AsyncErrorSender, AudioRecorder, AvCalibrationConfig,
+    CaptionStyleConfig, ChapterMarker, CursorStyleConfig, FPS, LiveCaption, LiveCaptionConfig,
+    PauseControl, PreviewConfig, ProcessMode, Receiver, RecorderConfig, RecorderError,
+    RecordingSession, ReplaySaver, Resolution, SpeakerRecorder, SpeakerRecorderConfig,
+    SystemCheckConfig, SystemCheckWarning, bounded, platform_screen_capture,
+    platform_speaker_recoder,
 };
-use screen_capture::{ScreenCapture, ScreenInfo};
+use rodio::Source;
+use screen_capture::{Capture, CaptureStreamConfig, Rectangle, ScreenCapture, ScreenInfo};
 use slint::{
     ComponentHandle, Model, SharedPixelBuffer, SharedString, ToSharedString, VecModel, Weak,
 };
@@ -31,12 +42,23 @@ use std::{
         atomic::{AtomicBool, AtomicI32, Ordering},
     },
     thread,
+    time::Duration,
 };
 
 #[derive(Default)]
 struct Cache {
     recorder_stop_sig: Option<Arc<AtomicBool>>,
 
+    // `None` unless the running session saves a MP4 file
+    chapter_marker: Option<ChapterMarker>,
+    chapter_count: u32,
+
+    // `None` for the live modes
+    pause_control: Option<PauseControl>,
+
+    // `None` unless the screen is recorded with `replay_secs`
+    replay_saver: Option<ReplaySaver>,
+
     audio_gain: Option<Arc<AtomicI32>>,
     audio_recorder: Option<AudioRecorder>,
 
@@ -45,17 +67,35 @@ struct Cache {
     speaker_device_info: Option<(u32, String)>,
 
     async_error_sender: Option<AsyncErrorSender>,
+
+    // The screen name and the region selected on it
+    capture_region: Option<(String, Rectangle)>,
 }
 
 static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| Mutex::new(Cache::default()));
 
+// Shared with the recorder, so the preview can be toggled while recording
+static PREVIEW_ENABLE: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(true)));
+
+// Model downloads are slowed down to it while push streaming, so they don't
+// take the bandwidth of the stream
+const PUSH_STREAM_DOWNLOAD_RATE_LIMIT: u64 = 512 * 1024;
+
+// How long the window turns white and the beep plays while calibrating
+const AV_CALIBRATION_FLASH_DURATION: Duration = Duration::from_millis(200);
+
+// Chunks of the mixed audio waiting for the caption transcriber, the newer
+// chunks are dropped while the model is loading or falls behind
+const CAPTION_AUDIO_CHANNEL_SIZE: usize = 1024;
+
 crate::impl_c_like_enum_convert!(UIFps, FPS, Fps24, Fps25, Fps30, Fps60);
 crate::impl_c_like_enum_convert!(
     UIProcessMode,
     ProcessMode,
     RecordScreen,
     ShareScreen,
-    PushStream
+    PushStream,
+    RecordAudio
 );
 
 #[macro_export]
@@ -120,10 +160,19 @@ pub fn init(ui: &AppWindow) {
 
     logic_cb!(start_recording, ui);
     logic_cb!(stop_recording, ui);
+    logic_cb!(add_chapter, ui);
+    logic_cb!(toggle_pause_recording, ui);
+    logic_cb!(save_replay, ui);
+
+    logic_cb!(select_capture_region, ui);
+    logic_cb!(clear_capture_region, ui);
 
     logic_cb!(cal_region_width, ui, height);
     logic_cb!(cal_region_height, ui, width);
 
+    logic_cb!(calibrate_av_offset, ui);
+    logic_cb!(run_system_check, ui, setting);
+
     logic_cb!(open_file, ui, file);
 }
 
@@ -370,6 +419,7 @@ fn toggle_control_enable_stats(ui: &AppWindow) {
 fn toggle_control_enable_preview(ui: &AppWindow) {
     let mut setting = global_store!(ui).get_setting_control();
     setting.enable_preview = !setting.enable_preview;
+    PREVIEW_ENABLE.store(setting.enable_preview, Ordering::Relaxed);
 
     if !setting.enable_preview {
         global_store!(ui).set_preview_image(Default::default());
@@ -595,6 +645,174 @@ fn start_recording(ui: &AppWindow) {
     });
 }
 
+fn calibrate_av_offset(ui: &AppWindow) {
+    if global_store!(ui).get_is_av_calibrating()
+        || global_store!(ui).get_record_status() != UIRecordStatus::Stopped
+    {
+        return;
+    }
+
+    let all_config = config::all();
+    if all_config.control.audio.is_empty() {
+        toast_warn!(ui, tr("Please select a microphone first"));
+        return;
+    }
+
+    global_store!(ui).set_is_av_calibrating(true);
+    toast_info!(
+        ui,
+        tr("Keep the window on the recorded screen and the speakers on")
+    );
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || {
+        let config = AvCalibrationConfig::new(all_config.control.screen, all_config.control.audio);
+
+        let flash_ui_weak = ui_weak.clone();
+        let result = recorder::calibrate_av_offset(config, platform_screen_capture(), move || {
+            _ = flash_ui_weak.upgrade_in_event_loop(|ui| {
+                global_store!(ui).set_av_calibration_flash(true);
+            });
+
+            // The stream stops playing when it's dropped
+            let stream = play_beep(AV_CALIBRATION_FLASH_DURATION);
+            if let Err(ref e) = stream {
+                log::warn!("play the calibration beep failed: {e}");
+            }
+
+            thread::sleep(AV_CALIBRATION_FLASH_DURATION);
+            _ = flash_ui_weak.upgrade_in_event_loop(|ui| {
+                global_store!(ui).set_av_calibration_flash(false);
+            });
+        });
+
+        let result = result.map(|offset| {
+            let mut all = config::all();
+            all.recorder.av_offset_ms = offset;
+            _ = config::save(all);
+            offset
+        });
+
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            global_store!(ui).set_is_av_calibrating(false);
+
+            match result {
+                Ok(offset) => toast_success!(
+                    ui,
+                    format!("{}: {offset} ms", tr("A/V offset is calibrated"))
+                ),
+                Err(e) => toast_warn!(ui, format!("{}: {e}", tr("Calibrate A/V offset failed"))),
+            }
+        });
+    });
+}
+
+// Checks the unsaved settings, so they can be tried before confirming
+fn run_system_check(ui: &AppWindow, setting: UISettingRecorder) {
+    if global_store!(ui).get_is_system_checking()
+        || global_store!(ui).get_record_status() != UIRecordStatus::Stopped
+    {
+        return;
+    }
+
+    let setting: config::Recorder = setting.into();
+    if setting.save_dir.is_empty() {
+        toast_warn!(ui, tr("Please choose a save directory first"));
+        return;
+    }
+
+    global_store!(ui).set_is_system_checking(true);
+    toast_info!(ui, tr("Checking the system, it takes a few seconds"));
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || {
+        let result = inner_run_system_check(setting);
+
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            global_store!(ui).set_is_system_checking(false);
+
+            let warnings = match result {
+                Ok(warnings) => warnings,
+                Err(e) => {
+                    toast_warn!(ui, format!("{}: {e}", tr("System check failed")));
+                    return;
+                }
+            };
+
+            if warnings.is_empty() {
+                toast_success!(ui, tr("Your settings won't drop frames"));
+                return;
+            }
+
+            let reasons = warnings
+                .iter()
+                .map(|warning| match warning {
+                    SystemCheckWarning::SlowCapture { attainable_fps } => {
+                        format!("{}: {attainable_fps:.0} fps", tr("Capturing is slow"))
+                    }
+                    SystemCheckWarning::SlowEncoder { encode_fps } => {
+                        format!("{}: {encode_fps:.0} fps", tr("Encoding is slow"))
+                    }
+                    SystemCheckWarning::SlowDisk {
+                        write_speed,
+                        required_speed,
+                    } => format!(
+                        "{}: {:.1}/{:.1} MB/s",
+                        tr("Disk is slow"),
+                        write_speed / 1024.0 / 1024.0,
+                        required_speed / 1024.0 / 1024.0
+                    ),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            toast_warn!(
+                ui,
+                format!(
+                    "{}. {reasons}",
+                    tr("Your settings will drop frames, try a lower fps or resolution")
+                )
+            );
+        });
+    });
+}
+
+fn inner_run_system_check(setting: config::Recorder) -> Result<Vec<SystemCheckWarning>> {
+    let screen_info = current_screen_info()?;
+    let resolution = if matches!(setting.resolution, UIResolution::Original) {
+        Resolution::Original((
+            screen_info.logical_size.width as u32,
+            screen_info.logical_size.height as u32,
+        ))
+    } else {
+        setting.resolution.into()
+    };
+
+    let config = SystemCheckConfig::new(
+        screen_info.name,
+        screen_info.logical_size,
+        PathBuf::from(setting.save_dir),
+    )
+    .with_fps(setting.fps.into())
+    .with_resolution(resolution);
+
+    let report = recorder::system_check(config, platform_screen_capture())?;
+    log::info!("system check: {report:?}");
+
+    Ok(report.warnings())
+}
+
+// A 1 kHz beep, which stands out from the background noise
+fn play_beep(duration: Duration) -> Result<rodio::OutputStream> {
+    let stream = rodio::OutputStreamBuilder::open_default_stream()?;
+    stream.mixer().add(
+        rodio::source::SineWave::new(1000.0)
+            .take_duration(duration)
+            .amplify(0.5),
+    );
+    Ok(stream)
+}
+
 fn warmup_video_encoder() -> Result<()> {
     let screen_info = current_screen_info()?;
     log::debug!("screen_info: {screen_info:?}");
@@ -648,16 +866,42 @@ fn inner_start_recording(
         Some(all_config.control.audio.clone())
     };
 
+    let cursor_style = CursorStyleConfig::default()
+        .with_scale(all_config.recorder.cursor_scale.max(1.0))
+        .with_highlight(all_config.recorder.cursor_highlight);
+    let cursor_style = if all_config.recorder.cursor_hide_idle > 0 {
+        cursor_style.with_hide_when_idle(Duration::from_secs(
+            all_config.recorder.cursor_hide_idle as u64,
+        ))
+    } else {
+        cursor_style
+    };
+
+    PREVIEW_ENABLE.store(all_config.control.enable_preview, Ordering::Relaxed);
+
+    let audio_format = all_config.recorder.audio_format.into();
+    let save_path = if matches!(process_mode, ProcessMode::RecordAudio) {
+        RecorderConfig::make_audio_filename(&all_config.recorder.save_dir, audio_format)
+    } else {
+        RecorderConfig::make_filename(&all_config.recorder.save_dir)
+    };
+
+    let live_caption_config = live_caption_config(&ui_weak, &all_config, process_mode);
+    let enable_live_caption = live_caption_config.enable;
+
     let config = RecorderConfig::new(
         all_config.control.screen.clone(),
         screen_info.logical_size.clone(),
-        RecorderConfig::make_filename(&all_config.recorder.save_dir),
+        save_path,
     )
     .with_process_mode(process_mode)
+    .with_audio_format(audio_format)
     .with_async_error_sender(async_error_sender)
     .with_include_cursor(all_config.recorder.include_cursor)
+    .with_cursor_style(cursor_style)
     .with_enable_denoise(all_config.recorder.enable_denoise)
     .with_convert_to_mono(all_config.recorder.convert_to_mono)
+    .with_av_offset_ms(all_config.recorder.av_offset_ms)
     .with_enable_recording_speaker(all_config.control.enable_speaker)
     .with_audio_device_name(audio_name)
     .with_audio_gain(Arc::new(AtomicI32::new(
@@ -667,8 +911,10 @@ fn inner_start_recording(
         all_config.control.speaker_gain as i32,
     )))
     .with_fps(all_config.recorder.fps.clone().into())
+    .with_countdown(all_config.recorder.countdown.max(0) as u32)
     .with_resolution(resolution)
     .with_enable_cursor_tracking(all_config.cursor_tracker.enable_tracking)
+    .with_enable_window_following(all_config.cursor_tracker.enable_window_following)
     .with_region_width(all_config.cursor_tracker.region_width)
     .with_region_height(all_config.cursor_tracker.region_height)
     .with_debounce_radius(all_config.cursor_tracker.debounce_radius as u32)
@@ -685,20 +931,38 @@ fn inner_start_recording(
     .with_share_screen_config(all_config.share_screen.into())
     .with_push_stream_config(all_config.push_stream.into())
     .with_camera_mix_config(all_config.control.into())
-    .with_realtime_image_effect(get_realtime_image_effect());
+    .with_realtime_image_effect(get_realtime_image_effect())
+    .with_live_caption_config(live_caption_config)
+    .with_replay_secs(replay_secs(&all_config.hotkey))
+    .with_preview_config(
+        PreviewConfig::default()
+            .with_height(all_config.recorder.preview_height.max(0) as u32)
+            .with_enable(PREVIEW_ENABLE.clone()),
+    );
+
+    let capture_region = CACHE.lock().unwrap().capture_region.clone();
+    let config = match capture_region {
+        Some((name, region)) if name == screen_info.name => config.with_capture_region(region),
+        _ => config,
+    };
 
     log::info!("Recording configuration: {:#?}", config);
 
     let (frame_sender_user, frame_receiver_user) = bounded(16);
-    let mut session = RecordingSession::new(config).with_frame_sender_user(Some(frame_sender_user));
-    session.start(rt_handle, platform_screen_capture())?;
-
-    _ = ui_weak.upgrade_in_event_loop(move |ui| {
-        global_store!(ui).set_start_recording_timer(false);
-        global_store!(ui).set_final_video_path(SharedString::default());
-        global_store!(ui).set_record_status(UIRecordStatus::Recording);
-    });
+    let (countdown_sender, countdown_receiver) = bounded(4);
+    let mut session = RecordingSession::new(config)
+        .with_frame_sender_user(Some(frame_sender_user))
+        .with_countdown_sender(Some(countdown_sender));
+
+    let caption_audio_receiver = if enable_live_caption {
+        let (sender, receiver) = bounded(CAPTION_AUDIO_CHANNEL_SIZE);
+        session = session.with_caption_audio_sender(Some(sender));
+        Some(receiver)
+    } else {
+        None
+    };
 
+    // Set before starting, so the countdown can be cancelled
     let stop_sig = session.get_stop_sig().clone();
     {
         let mut cache = CACHE.lock().unwrap();
@@ -707,14 +971,82 @@ fn inner_start_recording(
 
     let ui_weak_clone = ui_weak.clone();
     thread::spawn(move || {
+        while let Ok(remaining) = countdown_receiver.recv() {
+            _ = ui_weak_clone.upgrade_in_event_loop(move |ui| {
+                global_store!(ui).set_recording_countdown(remaining as i32);
+            });
+        }
+    });
+
+    let result = session.start(rt_handle, platform_screen_capture());
+    if result.is_err() {
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            global_store!(ui).set_recording_countdown(0);
+        });
+    }
+
+    match result {
+        Err(RecorderError::Cancelled) => {
+            log::info!("recording is cancelled in the countdown");
+            return Ok(());
+        }
+        result => result?,
+    }
+
+    {
+        let mut cache = CACHE.lock().unwrap();
+        cache.chapter_marker = session.get_chapter_marker();
+        cache.chapter_count = 0;
+        cache.pause_control = session.get_pause_control();
+        cache.replay_saver = session.get_replay_saver();
+    }
+
+    if let (Some(caption), Some(audio_format), Some(receiver)) = (
+        session.get_live_caption(),
+        session.mix_audio_format(),
+        caption_audio_receiver,
+    ) {
+        live_caption_worker(ui_weak.clone(), caption, audio_format, receiver);
+    }
+
+    _ = ui_weak.upgrade_in_event_loop(move |ui| {
+        // No frames come to start the timer while recording only the audio
+        global_store!(ui)
+            .set_start_recording_timer(matches!(process_mode, ProcessMode::RecordAudio));
+        global_store!(ui).set_final_video_path(SharedString::default());
+        global_store!(ui).set_record_status(UIRecordStatus::Recording);
+    });
+
+    let ui_weak_clone = ui_weak.clone();
+    thread::spawn(move || {
+        // The first stats come with the configured fps
+        let (mut full_fps, mut effective_fps) = (None, None);
+
         while let Ok(frame) = frame_receiver_user.recv() {
             log::debug!(
                 "frame_receiver_user buffer len: {} bytes",
                 frame.buffer.len()
             );
 
+            let configured_fps = *full_fps.get_or_insert(frame.stats.effective_fps);
+            if let Some(last_fps) = effective_fps.replace(frame.stats.effective_fps)
+                && last_fps != frame.stats.effective_fps
+            {
+                let msg = if frame.stats.effective_fps < last_fps {
+                    tr("The encoder can't keep up, the fps is lowered")
+                } else {
+                    tr("The encoder catches up, the fps is raised")
+                };
+                async_toast_warn(
+                    ui_weak_clone.clone(),
+                    format!("{msg}: {} fps", frame.stats.effective_fps),
+                );
+            }
+
             _ = ui_weak_clone.upgrade_in_event_loop(move |ui| {
-                if global_store!(ui).get_setting_control().enable_preview {
+                if global_store!(ui).get_setting_control().enable_preview
+                    && frame.buffer.width() > 0
+                {
                     let buffer = SharedPixelBuffer::<slint::Rgb8Pixel>::clone_from_slice(
                         &frame.buffer.as_raw(),
                         frame.buffer.width(),
@@ -734,6 +1066,15 @@ fn inner_start_recording(
                 sinfo.loss =
                     frame.stats.loss_frames as f32 / frame.stats.total_frames.max(1) as f32;
                 sinfo.share_screen_connections = frame.stats.share_screen_connections as i32;
+                sinfo.dropped_process = frame.stats.dropped_frames.process as i32;
+                sinfo.dropped_collect = frame.stats.dropped_frames.collect as i32;
+                sinfo.dropped_encode = frame.stats.dropped_frames.encode as i32;
+                sinfo.dropped_output = frame.stats.dropped_frames.output as i32;
+                sinfo.lowered_fps = if frame.stats.effective_fps < configured_fps {
+                    frame.stats.effective_fps as i32
+                } else {
+                    0
+                };
                 global_store!(ui).set_stats_info(sinfo);
             });
         }
@@ -742,14 +1083,33 @@ fn inner_start_recording(
 
     let final_video_path = session.save_path();
 
-    session.wait()?;
+    if matches!(process_mode, ProcessMode::PushStream) {
+        downloader_set_rate_limit(PUSH_STREAM_DOWNLOAD_RATE_LIMIT);
+    }
+
+    let result = session.wait();
+    {
+        let mut cache = CACHE.lock().unwrap();
+        cache.chapter_marker.take();
+        cache.pause_control.take();
+        cache.replay_saver.take();
+    }
+
+    if matches!(process_mode, ProcessMode::PushStream) {
+        downloader_set_rate_limit(0);
+    }
+
+    result?;
 
     _ = ui_weak.upgrade_in_event_loop(move |ui| {
         global_store!(ui).set_start_recording_timer(false);
+        global_store!(ui).set_recording_paused(false);
         global_store!(ui).set_record_status(UIRecordStatus::Stopped);
 
-        if matches!(process_mode, ProcessMode::RecordScreen)
-            || (matches!(process_mode, ProcessMode::ShareScreen) && share_screen_save_mp4)
+        if matches!(
+            process_mode,
+            ProcessMode::RecordScreen | ProcessMode::RecordAudio
+        ) || (matches!(process_mode, ProcessMode::ShareScreen) && share_screen_save_mp4)
             || (matches!(process_mode, ProcessMode::PushStream) && push_strem_save_mp4)
         {
             global_store!(ui).set_final_video_path(final_video_path.display().to_shared_string());
@@ -762,6 +1122,111 @@ fn inner_start_recording(
     Ok(())
 }
 
+// The replay is only saved with the hotkey, so nothing is kept without it
+fn replay_secs(setting: &config::Hotkey) -> u32 {
+    if setting.enable && !setting.save_replay.trim().is_empty() {
+        setting.replay_secs.max(0) as u32
+    } else {
+        0
+    }
+}
+
+fn live_caption_config(
+    ui_weak: &Weak<AppWindow>,
+    all_config: &config::Config,
+    process_mode: ProcessMode,
+) -> LiveCaptionConfig {
+    let setting = &all_config.recorder;
+    let style = CaptionStyleConfig::default()
+        .with_font_size(setting.live_caption_font_size.max(8) as f32)
+        .with_position(setting.live_caption_position.into());
+    let style = if setting.live_caption_background {
+        style
+    } else {
+        style.with_background(None)
+    };
+
+    let config = LiveCaptionConfig::default().with_style(style);
+    if !setting.live_caption
+        || !matches!(
+            process_mode,
+            ProcessMode::ShareScreen | ProcessMode::PushStream
+        )
+    {
+        return config;
+    }
+
+    let transcribe = &all_config.transcribe;
+    if !cutil::fs::file_exist(&transcribe.model_path)
+        || !cutil::fs::file_exist(&transcribe.model_tokenizer_path)
+    {
+        async_toast_warn(
+            ui_weak.clone(),
+            tr("No transcribe model is set, the live captions are disabled"),
+        );
+        return config;
+    }
+
+    let Some(font) = FONT.clone() else {
+        async_toast_warn(
+            ui_weak.clone(),
+            tr("No font for the live captions, the live captions are disabled"),
+        );
+        return config;
+    };
+
+    config.with_enable(true).with_font(font)
+}
+
+// Transcribes the mixed audio until the recording stops, the model is loaded
+// after the recording starts, so the captions show up a little later
+fn live_caption_worker(
+    ui_weak: Weak<AppWindow>,
+    caption: LiveCaption,
+    (sample_rate, channels): (u32, u16),
+    receiver: Receiver<Vec<f32>>,
+) {
+    let setting = config::all().transcribe;
+
+    thread::spawn(move || {
+        let config = FunASRModelConfig::default()
+            .with_model_weights(setting.model_path)
+            .with_tokenizer_path(setting.model_tokenizer_path);
+
+        let model = match FunAsrNanoGenerateModel::new(config, None, None) {
+            Ok(model) => model,
+            Err(e) => {
+                async_toast_warn(ui_weak, format!("New transcribe model failed: {e}"));
+                return;
+            }
+        };
+
+        let hotwords = setting
+            .hotwords
+            .split([',', '，'])
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+
+        let config = StreamingConfig::default()
+            .with_input_sample_rate(sample_rate)
+            .with_input_channels(channels)
+            .with_hotwords(hotwords, DEFAULT_HOTWORD_BOOST);
+
+        let result = StreamingTranscriber::new(model, config).and_then(|mut transcriber| {
+            transcriber.run(receiver.iter(), |text| {
+                caption.update(text.text, text.is_final);
+                Ok(())
+            })
+        });
+
+        if let Err(e) = result {
+            async_toast_warn(ui_weak, format!("Live captions failed: {e}"));
+        }
+        log::info!("exit live caption worker");
+    });
+}
+
 pub fn get_async_error_sender() -> Option<AsyncErrorSender> {
     CACHE.lock().unwrap().async_error_sender.clone()
 }
@@ -788,10 +1253,129 @@ fn stop_recording(ui: &AppWindow) {
         log::warn!("recorder_stop_sig is None");
     }
 
+    global_store!(ui).set_recording_paused(false);
     global_store!(ui).set_record_status(UIRecordStatus::Stopped);
 }
 
-fn current_screen_info() -> Result<ScreenInfo> {
+// The chapters are numbered in the order they are added, the titles can be
+// changed later with the chapter generator
+fn add_chapter(ui: &AppWindow) {
+    let (marker, count) = {
+        let mut cache = CACHE.lock().unwrap();
+        let Some(marker) = cache.chapter_marker.clone() else {
+            toast_warn!(ui, tr("Chapters are only added while saving a MP4 file"));
+            return;
+        };
+
+        cache.chapter_count += 1;
+        (marker, cache.chapter_count)
+    };
+
+    let title = format!("{} {count}", tr("Chapter"));
+    marker.add(title.clone());
+    toast_success!(ui, format!("{}: {title}", tr("Add chapter")));
+}
+
+fn toggle_pause_recording(ui: &AppWindow) {
+    let Some(pause_control) = CACHE.lock().unwrap().pause_control.clone() else {
+        toast_warn!(
+            ui,
+            tr("Only the screen or the audio recordings can be paused")
+        );
+        return;
+    };
+
+    if pause_control.is_paused() {
+        pause_control.resume();
+        toast_info!(ui, tr("Recording is resumed"));
+    } else {
+        pause_control.pause();
+        toast_info!(ui, tr("Recording is paused"));
+    }
+
+    global_store!(ui).set_recording_paused(pause_control.is_paused());
+}
+
+// Written next to the recording, which goes on while it's saved
+fn save_replay(ui: &AppWindow) {
+    let Some(replay_saver) = CACHE.lock().unwrap().replay_saver.clone() else {
+        toast_warn!(ui, tr("Replays are only saved while recording the screen"));
+        return;
+    };
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || match replay_saver.save() {
+        Ok(path) => {
+            async_toast_success(
+                ui_weak.clone(),
+                format!("{}: {}", tr("Save replay successfully"), path.display()),
+            );
+
+            let path = path.display().to_shared_string();
+            _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                global_logic!(ui).invoke_add_history(path);
+            });
+        }
+        Err(e) => async_toast_warn(ui_weak, format!("{}: {e}", tr("Save replay failed"))),
+    });
+}
+
+fn select_capture_region(ui: &AppWindow) {
+    if config::all().cursor_tracker.enable_tracking {
+        toast_warn!(ui, tr("The region isn't used while tracking the cursor"));
+        return;
+    }
+
+    if config::all().cursor_tracker.enable_window_following {
+        toast_warn!(ui, tr("The region isn't used while following the window"));
+        return;
+    }
+
+    let screen_info = match current_screen_info() {
+        Ok(info) => info,
+        Err(e) => {
+            toast_warn!(ui, e.to_string());
+            return;
+        }
+    };
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || match inner_select_capture_region(&screen_info) {
+        Ok(Some(region)) => {
+            log::info!("select capture region: {region:?}");
+            CACHE.lock().unwrap().capture_region = Some((screen_info.name.clone(), region));
+
+            _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                global_store!(ui).set_capture_region(slint::format!(
+                    "{}x{} ({}, {})",
+                    region.width,
+                    region.height,
+                    region.x,
+                    region.y
+                ));
+            });
+        }
+        Ok(None) => log::info!("select capture region cancelled"),
+        Err(e) => toast::async_toast_warn(ui_weak, e.to_string()),
+    });
+}
+
+#[cfg(feature = "desktop-wayland-wlr")]
+fn inner_select_capture_region(screen_info: &ScreenInfo) -> Result<Option<Rectangle>> {
+    Ok(recorder::select_capture_region(screen_info)?)
+}
+
+#[cfg(not(feature = "desktop-wayland-wlr"))]
+fn inner_select_capture_region(_screen_info: &ScreenInfo) -> Result<Option<Rectangle>> {
+    bail!("selecting a region is only supported on wlroots")
+}
+
+fn clear_capture_region(ui: &AppWindow) {
+    CACHE.lock().unwrap().capture_region = None;
+    global_store!(ui).set_capture_region(SharedString::default());
+}
+
+pub fn current_screen_info() -> Result<ScreenInfo> {
     let all_config = config::all();
 
     if all_config.control.screen.is_empty() {
@@ -811,6 +1395,28 @@ fn current_screen_info() -> Result<ScreenInfo> {
     Ok(screen_info.unwrap())
 }
 
+/// The first frame of the current screen without the cursor
+pub fn capture_current_screen() -> Result<Capture> {
+    let screen_info = current_screen_info()?;
+    let cancel_sig = Arc::new(AtomicBool::new(false));
+    let config = CaptureStreamConfig {
+        name: screen_info.name,
+        include_cursor: false,
+        fps: None,
+        cancel_sig: cancel_sig.clone(),
+        sync_sig: Arc::new(AtomicBool::new(false)),
+    };
+
+    // Stop the stream after the first frame
+    let mut frame = None;
+    platform_screen_capture().capture_output_stream(config, |data| {
+        frame = Some(data.data);
+        cancel_sig.store(true, Ordering::Relaxed);
+    })?;
+
+    frame.ok_or_else(|| anyhow!("no frame captured"))
+}
+
 fn cal_region_width(_ui: &AppWindow, height: f32) -> i32 {
     match current_screen_info() {
         Ok(screen_info) => {
@@ -0,0 +1,382 @@
+//! Screenshot editor: crop, arrows, blurred regions to redact sensitive
+//! content and numbered steps on a screenshot of the current screen.
+//!
+//! The edits are kept as a list of operations over the original screenshot,
+//! so undo replays the remaining ones and redo applies the undone one again.
+
+use crate::{
+    config, global_store,
+    logic::{
+        clipboard::copy_image_to_clipboard,
+        recorder::capture_current_screen,
+        toast::{self, async_toast_warn},
+        tr::tr,
+    },
+    logic_cb,
+    slint_generatedAppWindow::{AppWindow, ScreenshotEditTool as UIScreenshotEditTool},
+    toast_success, toast_warn,
+};
+use anyhow::{Result, anyhow};
+use image::{ImageFormat, RgbaImage, imageops};
+use image_effect::{
+    Effect, EffectRegion, ImageEffect, MaskedEffect,
+    annotate::{Arrow, Font, StepBadge},
+    blur::GaussianBlurConfig,
+};
+use once_cell::sync::Lazy;
+use recorder::RecorderConfig;
+use slint::{ComponentHandle, SharedPixelBuffer};
+use std::{io::Cursor, path::PathBuf, sync::Mutex, thread, time::Duration};
+
+// Wait for the window to be minimized before capturing the screen
+const MINIMIZE_WINDOW_DELAY: Duration = Duration::from_millis(500);
+
+// Strong enough that the blurred text can't be read
+const BLUR_RADIUS: i32 = 12;
+
+// Drags shorter than it are taken as clicks
+const MIN_DRAG_DISTANCE: f32 = 4.0;
+
+// The font bundled for the UI, used by the step badges and the live captions
+pub static FONT: Lazy<Option<Font>> = Lazy::new(|| {
+    Font::from_bytes(include_bytes!("../../ui/fonts/SourceHanSansCN.otf").to_vec())
+        .map_err(|e| log::warn!("load the bundled font failed: {e}"))
+        .ok()
+});
+
+static EDITOR: Lazy<Mutex<Editor>> = Lazy::new(|| Mutex::new(Editor::default()));
+
+#[derive(Debug, Clone)]
+enum EditOperation {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Arrow {
+        start: (f32, f32),
+        end: (f32, f32),
+    },
+    Blur {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    Step {
+        center: (f32, f32),
+        number: u32,
+    },
+}
+
+impl EditOperation {
+    fn apply(&self, image: RgbaImage) -> RgbaImage {
+        match *self {
+            EditOperation::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => imageops::crop_imm(&image, x, y, width, height).to_image(),
+            EditOperation::Arrow { start, end } => {
+                let mut image = image;
+                Arrow::new(start, end).draw(&mut image);
+                image
+            }
+            EditOperation::Blur {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let effect = MaskedEffect::new(
+                    ImageEffect::GaussianBlur(GaussianBlurConfig::new().with_radius(BLUR_RADIUS)),
+                    EffectRegion::Rect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    },
+                );
+
+                // The unchanged image is kept if the effect fails
+                let fallback = image.clone();
+                effect.apply(image).unwrap_or(fallback)
+            }
+            EditOperation::Step { center, number } => {
+                let mut image = image;
+                if let Some(font) = FONT.as_ref() {
+                    StepBadge::new(center, number, font.clone()).draw(&mut image);
+                }
+                image
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Editor {
+    screenshot: Option<RgbaImage>,
+    operations: Vec<EditOperation>,
+    undone_operations: Vec<EditOperation>,
+
+    // The screenshot with all the operations applied
+    image: Option<RgbaImage>,
+}
+
+impl Editor {
+    fn open(&mut self, screenshot: RgbaImage) {
+        self.image = Some(screenshot.clone());
+        self.screenshot = Some(screenshot);
+        self.operations.clear();
+        self.undone_operations.clear();
+    }
+
+    fn close(&mut self) {
+        *self = Editor::default();
+    }
+
+    fn push(&mut self, operation: EditOperation) {
+        if let Some(image) = self.image.take() {
+            self.image = Some(operation.apply(image));
+        }
+
+        self.operations.push(operation);
+        self.undone_operations.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(operation) = self.operations.pop() {
+            self.undone_operations.push(operation);
+            self.replay();
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(operation) = self.undone_operations.pop() {
+            if let Some(image) = self.image.take() {
+                self.image = Some(operation.apply(image));
+            }
+            self.operations.push(operation);
+        }
+    }
+
+    fn replay(&mut self) {
+        self.image = self.screenshot.clone().map(|screenshot| {
+            self.operations
+                .iter()
+                .fold(screenshot, |image, operation| operation.apply(image))
+        });
+    }
+
+    fn next_step_number(&self) -> u32 {
+        self.operations
+            .iter()
+            .filter(|operation| matches!(operation, EditOperation::Step { .. }))
+            .count() as u32
+            + 1
+    }
+
+    // The operation of the tool dragged from `start` to `end`, in the pixels
+    // of the current image
+    fn operation(
+        &self,
+        tool: UIScreenshotEditTool,
+        start: (f32, f32),
+        end: (f32, f32),
+    ) -> Option<EditOperation> {
+        let image = self.image.as_ref()?;
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let clamp = |(x, y): (f32, f32)| (x.clamp(0.0, width), y.clamp(0.0, height));
+        let (start, end) = (clamp(start), clamp(end));
+
+        let is_drag = (end.0 - start.0).hypot(end.1 - start.1) >= MIN_DRAG_DISTANCE;
+        let rect = (
+            start.0.min(end.0) as u32,
+            start.1.min(end.1) as u32,
+            (end.0 - start.0).abs() as u32,
+            (end.1 - start.1).abs() as u32,
+        );
+
+        match tool {
+            UIScreenshotEditTool::Crop if is_drag && rect.2 > 0 && rect.3 > 0 => {
+                Some(EditOperation::Crop {
+                    x: rect.0,
+                    y: rect.1,
+                    width: rect.2,
+                    height: rect.3,
+                })
+            }
+            UIScreenshotEditTool::Arrow if is_drag => Some(EditOperation::Arrow { start, end }),
+            UIScreenshotEditTool::Blur if is_drag && rect.2 > 0 && rect.3 > 0 => {
+                Some(EditOperation::Blur {
+                    x: rect.0 as i32,
+                    y: rect.1 as i32,
+                    width: rect.2,
+                    height: rect.3,
+                })
+            }
+            UIScreenshotEditTool::Step => Some(EditOperation::Step {
+                center: end,
+                number: self.next_step_number(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>> {
+        let image = self
+            .image
+            .as_ref()
+            .ok_or_else(|| anyhow!("no screenshot is being edited"))?;
+
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, ImageFormat::Png)?;
+        Ok(png.into_inner())
+    }
+}
+
+pub fn init(ui: &AppWindow) {
+    logic_cb!(edit_screenshot, ui);
+    logic_cb!(
+        screenshot_editor_add,
+        ui,
+        tool,
+        start_x,
+        start_y,
+        end_x,
+        end_y
+    );
+    logic_cb!(screenshot_editor_undo, ui);
+    logic_cb!(screenshot_editor_redo, ui);
+    logic_cb!(screenshot_editor_save, ui);
+    logic_cb!(screenshot_editor_copy, ui);
+    logic_cb!(screenshot_editor_close, ui);
+}
+
+fn edit_screenshot(ui: &AppWindow) {
+    // Keep the window out of the screenshot
+    ui.window().set_minimized(true);
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || {
+        thread::sleep(MINIMIZE_WINDOW_DELAY);
+        let capture = capture_current_screen();
+
+        _ = ui_weak.clone().upgrade_in_event_loop(move |ui| {
+            ui.window().set_minimized(false);
+
+            let screenshot = capture.and_then(|capture| {
+                RgbaImage::from_raw(capture.width, capture.height, capture.pixel_data)
+                    .ok_or_else(|| anyhow!("invalid screenshot size"))
+            });
+
+            match screenshot {
+                Ok(screenshot) => {
+                    EDITOR.lock().unwrap().open(screenshot);
+                    update_editor(&ui);
+                    global_store!(ui).set_is_show_screenshot_editor(true);
+                }
+                Err(e) => toast_warn!(
+                    ui,
+                    format!("{}. {}: {e}", tr("Take screenshot failed"), tr("Reason"))
+                ),
+            }
+        });
+    });
+}
+
+fn screenshot_editor_add(
+    ui: &AppWindow,
+    tool: UIScreenshotEditTool,
+    start_x: f32,
+    start_y: f32,
+    end_x: f32,
+    end_y: f32,
+) {
+    {
+        let mut editor = EDITOR.lock().unwrap();
+        let Some(operation) = editor.operation(tool, (start_x, start_y), (end_x, end_y)) else {
+            return;
+        };
+
+        log::debug!("screenshot edit operation: {operation:?}");
+        editor.push(operation);
+    }
+
+    update_editor(ui);
+}
+
+fn screenshot_editor_undo(ui: &AppWindow) {
+    EDITOR.lock().unwrap().undo();
+    update_editor(ui);
+}
+
+fn screenshot_editor_redo(ui: &AppWindow) {
+    EDITOR.lock().unwrap().redo();
+    update_editor(ui);
+}
+
+fn screenshot_editor_save(ui: &AppWindow) {
+    let png = match EDITOR.lock().unwrap().encode_png() {
+        Ok(png) => png,
+        Err(e) => {
+            toast_warn!(ui, format!("{}. {}: {e}", tr("Save failed"), tr("Reason")));
+            return;
+        }
+    };
+
+    let ui_weak = ui.as_weak();
+    thread::spawn(move || {
+        let path = screenshot_path();
+        match std::fs::write(&path, png) {
+            Ok(_) => toast::async_toast_success(
+                ui_weak,
+                format!("{}: {}", tr("Save successfully"), path.display()),
+            ),
+            Err(e) => async_toast_warn(
+                ui_weak,
+                format!("{}. {}: {e}", tr("Save failed"), tr("Reason")),
+            ),
+        }
+    });
+}
+
+fn screenshot_editor_copy(ui: &AppWindow) {
+    match EDITOR
+        .lock()
+        .unwrap()
+        .encode_png()
+        .and_then(|png| copy_image_to_clipboard(&png))
+    {
+        Ok(_) => toast_success!(ui, tr("Copy success")),
+        Err(e) => toast_warn!(ui, format!("{}. {}: {e}", tr("Copy failed"), tr("Reason"))),
+    }
+}
+
+fn screenshot_editor_close(ui: &AppWindow) {
+    EDITOR.lock().unwrap().close();
+    global_store!(ui).set_is_show_screenshot_editor(false);
+    global_store!(ui).set_screenshot_editor_image(Default::default());
+}
+
+fn update_editor(ui: &AppWindow) {
+    let editor = EDITOR.lock().unwrap();
+
+    if let Some(image) = editor.image.as_ref() {
+        let buffer = SharedPixelBuffer::<slint::Rgba8Pixel>::clone_from_slice(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+        );
+        global_store!(ui).set_screenshot_editor_image(slint::Image::from_rgba8(buffer));
+    }
+
+    global_store!(ui).set_screenshot_editor_can_undo(!editor.operations.is_empty());
+    global_store!(ui).set_screenshot_editor_can_redo(!editor.undone_operations.is_empty());
+}
+
+fn screenshot_path() -> PathBuf {
+    RecorderConfig::make_filename(&config::all().recorder.save_dir).with_extension("png")
+}
@@ -150,6 +150,17 @@ pub fn init(ui: &AppWindow) {
                 toast_success!(ui, tr("Remove caches successfully"));
             }
         }
+
+        #[cfg(feature = "desktop")]
+        tokio::spawn(async {
+            if let Err(e) =
+                bot::ResponseCache::new(crate::db::DB_NAME, crate::db::AI_RESPONSE_CACHE_TABLE)
+                    .clear()
+                    .await
+            {
+                log::warn!("remove ai response cache failed: {e}");
+            }
+        });
     });
 
     global_logic!(ui).on_caches_size(|| {
@@ -225,6 +236,18 @@ fn backup(ui: slint::Weak<AppWindow>, setting: crate::slint_generatedAppWindow::
         );
         let output = output_dir.join(filename);
 
+        // The database in use is archived as a snapshot
+        let snapshot_dir = match tempfile::tempdir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                toast::async_toast_warn(
+                    ui,
+                    format!("{}. {}: {}", tr("Can't create tempdir"), tr("Reason"), e),
+                );
+                return;
+            }
+        };
+
         match (all.config_path.parent(), all.db_path.parent()) {
             (Some(config_dir), Some(data_dir)) => {
                 let mut sources = vec![];
@@ -236,6 +259,30 @@ fn backup(ui: slint::Weak<AppWindow>, setting: crate::slint_generatedAppWindow::
 
                 if setting.data {
                     sources.push(data_dir.to_path_buf());
+                    excludes.push(all.db_path.clone());
+                    excludes.push(crate::db::backup_dir(&all.db_path));
+
+                    if let (Some(data_dir_name), Some(db_file_name)) =
+                        (data_dir.file_name(), all.db_path.file_name())
+                    {
+                        let snapshot_data_dir = snapshot_dir.path().join(data_dir_name);
+                        let snapshot = snapshot_data_dir.join(db_file_name);
+
+                        let result = match std::fs::create_dir_all(&snapshot_data_dir) {
+                            Ok(_) => sqldb::backup(crate::db::DB_NAME, &snapshot).await,
+                            Err(e) => Err(e.into()),
+                        };
+
+                        if let Err(e) = result {
+                            toast::async_toast_warn(
+                                ui,
+                                format!("{}. {}: {}", tr("Backup failed"), tr("Reason"), e),
+                            );
+                            return;
+                        }
+
+                        sources.push(snapshot_data_dir);
+                    }
                 }
 
                 if !setting.cache {
@@ -312,6 +359,28 @@ fn recover(ui: slint::Weak<AppWindow>) {
                         _ = std::fs::copy(&config_path, config_all.config_path);
                         _ = std::fs::remove_file(&config_path);
 
+                        // The database in use is replaced by the snapshot, not
+                        // overwritten by the copy
+                        if let Some(db_file_name) = config_all.db_path.file_name() {
+                            let snapshot = target.join(&config_all.app_name).join(db_file_name);
+                            if snapshot.exists() {
+                                if let Err(e) = sqldb::restore(crate::db::DB_NAME, &snapshot).await
+                                {
+                                    toast::async_toast_warn(
+                                        ui,
+                                        format!(
+                                            "{}. {}: {}",
+                                            tr("Restore backup file failed"),
+                                            tr("Reason"),
+                                            e
+                                        ),
+                                    );
+                                    return;
+                                }
+                                _ = std::fs::remove_file(&snapshot);
+                            }
+                        }
+
                         if let Some(data_dir) = config_all.db_path.parent() {
                             _ = cutil::fs::copy_dir_all(
                                 target.join(&config_all.app_name),
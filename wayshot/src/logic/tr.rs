@@ -88,6 +88,7 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("No Message", "无消息"),
             ("normal", "普通"),
             ("Normal", "普通"),
+            ("Open database failed", "打开数据库失败"),
             ("Open link failed", "打开链接失败"),
             ("Password", "密码"),
             ("Paste failed", "粘贴失败"),
@@ -180,7 +181,9 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("API base URL", "API基础URL"),
             ("API key", "API密钥"),
             ("Chat model", "聊天模型"),
-            ("Only support OpenAI compatible API", "仅支持OpenAI兼容API"),
+            ("AI provider", "AI服务商"),
+            ("OpenAI compatible", "OpenAI兼容"),
+            ("Optional", "可选"),
             ("Remove", "移除"),
             ("Rename", "重命名"),
             ("Replace", "替换"),
@@ -226,6 +229,17 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("start desktop speaker recorder failed", "启动桌面扬声器录制器失败"),
             ("Don't convert the microphone audio to mono", "不要将麦克风音频转换为单声道"),
             ("Convert audio to mono", "将音频转换为单声道"),
+            ("Audio recording format", "录音格式"),
+            ("The file type when recording only the microphone and the speaker", "只录制麦克风和扬声器时的文件类型"),
+            ("Live captions enabled", "已启用实时字幕"),
+            ("Live captions disabled", "已禁用实时字幕"),
+            ("Caption font size", "字幕字体大小"),
+            ("The speech is transcribed with the model of the transcribe settings and drawn onto the shared and streamed video", "使用转录设置中的模型转录语音，并绘制到共享和推流的视频上"),
+            ("Caption position", "字幕位置"),
+            ("Caption background enabled", "已启用字幕背景"),
+            ("Caption background disabled", "已禁用字幕背景"),
+            ("No transcribe model is set, the live captions are disabled", "未设置转录模型，已禁用实时字幕"),
+            ("No font for the live captions, the live captions are disabled", "没有实时字幕的字体，已禁用实时字幕"),
             ("Noise reduction disabled", "已禁用降噪功能"),
             ("Don't convert audio to mono", "不要将音频转换为单声道"),
             ("Noise reduction enabled", "已启用降噪功能"),
@@ -233,6 +247,35 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("Cursor Tracking", "光标跟踪"),
             ("Cursor tracking disabled", "已禁用光标跟踪"),
             ("Cursor tracking enabled", "已启用光标跟踪"),
+            ("Window following disabled", "已禁用窗口跟随"),
+            ("Window following enabled", "已启用窗口跟随"),
+            ("Preview height", "预览高度"),
+            ("A/V offset (milliseconds)", "音视频偏移（毫秒）"),
+            ("Negative values play the audio earlier, for the microphones with latency. Calibrating plays a beep and flashes the window.", "负值让音频提前播放，用于有延迟的麦克风。校准时会播放提示音并闪烁窗口。"),
+            ("Calibrate", "校准"),
+            ("Please select a microphone first", "请先选择麦克风"),
+            ("Keep the window on the recorded screen and the speakers on", "请将窗口保持在录制的屏幕上，并打开扬声器"),
+            ("A/V offset is calibrated", "音视频偏移已校准"),
+            ("Calibrate A/V offset failed", "校准音视频偏移失败"),
+            ("System check", "系统检测"),
+            ("Measure the capturing, the encoding and the disk with the fps and the resolution above, to know whether frames will be dropped", "按上面的帧率和分辨率测试截屏、编码和磁盘的速度，以了解是否会丢帧"),
+            ("Check", "检测"),
+            ("Please choose a save directory first", "请先选择保存目录"),
+            ("Checking the system, it takes a few seconds", "正在检测系统，需要几秒钟"),
+            ("System check failed", "系统检测失败"),
+            ("Your settings won't drop frames", "当前设置不会丢帧"),
+            ("Capturing is slow", "截屏速度慢"),
+            ("Encoding is slow", "编码速度慢"),
+            ("Disk is slow", "磁盘速度慢"),
+            ("Your settings will drop frames, try a lower fps or resolution", "当前设置会丢帧，请尝试更低的帧率或分辨率"),
+            ("dropped (process/collect/encode/output)", "丢帧（处理/收集/编码/输出）"),
+            ("The encoder can't keep up, the fps is lowered", "编码器跟不上，已降低帧率"),
+            ("The encoder catches up, the fps is raised", "编码器已跟上，已恢复帧率"),
+            ("lowered fps", "降低后的帧率"),
+            ("Cursor size", "光标大小"),
+            ("Hide the idle cursor after (seconds)", "光标静止多久后隐藏（秒）"),
+            ("Cursor highlight disabled", "已禁用光标高亮"),
+            ("Cursor highlight enabled", "已启用光标高亮"),
             ("Fast moving interval(milliseconds)", "快速移动时间间隔（毫秒）"),
             ("Zoom transition duration(milliseconds)", "缩放过渡持续时间（毫秒）"),
             ("Maximum duration of stay in the stable region(seconds)", "在稳定区域内的最大停留时间（秒）"),
@@ -271,6 +314,7 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("video file duration is 0", "视频文件时长为0"),
             ("Note", "注意"),
             ("To enable the mouse tracking feature, you need to download the wayshot-cursor program from the Github release page and run it with administrator privileges. This program is used to capture the mouse position. The command is as follows:", "启用鼠标跟随功能需要到Github发布页面下载wayshot-cursor程序。并且使用管理员权限运行。这个程序的作用是获取鼠标位置。命令如下: "),
+            ("Or install it as a system service which is started on demand:", "或者将它安装为按需启动的系统服务："),
             ("Hide Statistic", "隐藏统计信息"),
             ("Show Statistic", "显示统计信息"),
             ("Hide Preview", "隐藏预览"),
@@ -300,6 +344,7 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("Password (optional)", "密码（可选）"),
             ("Private Key", "私钥"),
             ("Private key", "私钥"),
+            ("Record Audio", "录音"),
             ("Record Screen", "录屏"),
             ("STUN server", "STUN服务器"),
             ("Save mp4", "保存MP4"),
@@ -318,6 +363,8 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("Invalid RTMP server url format. Should start with `rtmp://`", "RTMP服务器URL格式无效，应以`rtmp://`开头"),
             ("Push Stream", "推流"),
             ("Query parameter", "查询参数"),
+            ("Bitrate (kbps)", "码率（kbps）"),
+            ("Constant bitrate required by most streaming platforms. 0 keeps a constant quality", "大多数直播平台要求的恒定码率，0 表示保持恒定画质"),
             ("RTMP server address", "RTMP服务器地址"),
             ("Stream key", "流名称"),
             ("Area height", "区域高度"),
@@ -396,7 +443,6 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("fun ast model or tokenizer", "加载模型或分词器时出错"),
             ("load entry failed", "加载条目失败"),
             ("open file", "打开文件"),
-            ("parse entry failed", "解析条目失败"),
             ("recovery", "恢复"),
             ("replace", "替换"),
             ("transcribe", "转录"),
@@ -404,6 +450,113 @@ fn cn() -> &'static HashMap<&'static str, &'static str> {
             ("Cancelled","已经取消"),
             ("Failed", "失败"),
             ("Correcting subtitles", "正在校正字幕"),
+            ("Narrating", "正在配音"),
+            ("Narration", "配音"),
+            ("narrate", "配音"),
+            ("Speech model directory", "语音模型目录"),
+            ("Choose speech model directory", "选择语音模型目录"),
+            ("Reference voice", "参考语音"),
+            ("Choose reference voice", "选择参考语音"),
+            ("e.g. wav, mp3", "例如：wav, mp3"),
+            ("Text of the reference voice", "参考语音的文本"),
+            ("What is said in the reference voice", "参考语音中所说的内容"),
+            ("Original audio volume (0 ~ 1)", "原始音频音量（0 ~ 1）"),
+            ("Please setup narration and try again.", "请先设置配音，然后重试。"),
+            ("Export Video", "导出视频"),
+            ("Generating chapters", "正在生成章节"),
+            ("Chapters", "章节"),
+            ("chapters", "章节"),
+            ("Summary", "摘要"),
+            ("Generate", "生成"),
+            ("Regenerate", "重新生成"),
+            ("No chapters yet", "暂无章节"),
+            ("No subtitles to generate chapters", "没有可用于生成章节的字幕"),
+            ("Generate chapters successfully", "生成章节成功"),
+            ("Write chapters into the video failed", "写入视频章节失败"),
+            ("Translating", "正在翻译"),
+            ("Translation", "翻译"),
+            ("Translate", "翻译"),
+            ("translate", "翻译"),
+            ("Target language", "目标语言"),
+            ("Please input the target language", "请输入目标语言"),
+            ("All subtitles already have translations or are empty", "所有字幕都已翻译或为空"),
+            ("Remove Translation", "删除翻译"),
+            ("Export Bilingual SRT", "导出双语 SRT"),
+            ("Export Bilingual ASS", "导出双语 ASS"),
+            ("Speaker", "说话人"),
+            ("Hotwords (comma separated)", "热词（用逗号分隔）"),
+            ("Detect the language of each segment (mixed languages)", "检测每个片段的语言（多语言混合）"),
+            ("Speaker model (optional, labels the speakers)", "说话人模型（可选，用于标注说话人）"),
+            ("Recognizing text...", "正在识别文字..."),
+            ("No text found", "未找到文字"),
+            ("Recognize text failed", "识别文字失败"),
+            ("Start or stop recording", "开始或停止录制"),
+            ("Stop recording", "停止录制"),
+            ("Chapter", "章节"),
+            ("Add chapter", "添加章节"),
+            ("Chapters are only added while saving a MP4 file", "仅在保存MP4文件时才能添加章节"),
+            ("Pause or resume recording", "暂停或继续录制"),
+            ("Pause recording", "暂停录制"),
+            ("Resume recording", "继续录制"),
+            ("Recording is paused", "录制已暂停"),
+            ("Recording is resumed", "录制已继续"),
+            ("Only the screen or the audio recordings can be paused", "只能暂停屏幕或音频录制"),
+            ("Save replay", "保存回放"),
+            ("Save replay successfully", "保存回放成功"),
+            ("Save replay failed", "保存回放失败"),
+            ("Replays are only saved while recording the screen", "仅在录制屏幕时才能保存回放"),
+            ("Profile", "配置方案"),
+            ("Import", "导入"),
+            ("Export", "导出"),
+            ("Save the current settings as a profile", "将当前设置保存为配置方案"),
+            ("Switch to profile", "切换到配置方案"),
+            ("Switch profile failed", "切换配置方案失败"),
+            ("Save profile successfully", "保存配置方案成功"),
+            ("Save profile failed", "保存配置方案失败"),
+            ("Remove profile successfully", "删除配置方案成功"),
+            ("Remove profile failed", "删除配置方案失败"),
+            ("No found profile", "未找到配置方案"),
+            ("Export profile to", "导出配置方案到"),
+            ("Export profile failed", "导出配置方案失败"),
+            ("Choose a profile", "选择配置方案"),
+            ("Import profile successfully", "导入配置方案成功"),
+            ("Import profile failed", "导入配置方案失败"),
+            ("Countdown before recording (seconds)", "录制前倒计时（秒）"),
+            ("upload", "上传"),
+            ("copy share url", "复制分享链接"),
+            ("Upload failed", "上传失败"),
+            ("Upload successfully", "上传成功"),
+            ("Please set the upload url first", "请先设置上传地址"),
+            ("Added to the upload queue", "已加入上传队列"),
+            ("The recording is already uploading", "该录像已在上传中"),
+            ("Recording", "正在录制"),
+            ("Show window", "显示窗口"),
+            ("Open last recording", "打开最近的录像"),
+            ("Quit", "退出"),
+            ("No recording found", "未找到录像"),
+            ("Select region", "选择区域"),
+            ("Record whole screen", "录制整个屏幕"),
+            (
+                "The region isn't used while tracking the cursor",
+                "跟踪光标时不使用选择的区域",
+            ),
+            (
+                "The region isn't used while following the window",
+                "跟随窗口时不使用选择的区域",
+            ),
+            ("Edit Screenshot", "编辑截图"),
+            ("Crop", "裁剪"),
+            ("Arrow", "箭头"),
+            ("Blur", "模糊"),
+            ("Step", "步骤"),
+            ("Undo", "撤销"),
+            ("Redo", "重做"),
+            ("Save", "保存"),
+            ("Copy", "复制"),
+            ("Save successfully", "保存成功"),
+            ("Save failed", "保存失败"),
+            ("Take screenshot failed", "截图失败"),
+            ("copy file", "复制文件"),
         ])
     })
 }
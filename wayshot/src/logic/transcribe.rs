@@ -1,9 +1,15 @@
 mod audio_player;
+mod chapter;
 mod downloader;
 mod model;
+mod narration;
+mod translation;
 
 pub fn init(ui: &crate::slint_generatedAppWindow::AppWindow) {
     model::init(ui);
     downloader::init(ui);
     audio_player::init(ui);
+    narration::init(ui);
+    chapter::init(ui);
+    translation::init(ui);
 }
@@ -0,0 +1,314 @@
+use crate::{
+    db::{TRANSCRIBE_TABLE as DB_TABLE, Transcribe},
+    global_store,
+    logic::{
+        toast,
+        tr::tr,
+        transcribe::{
+            model::{
+                ai_api_config, ai_chat, get_export_subtitles, is_ai_model_ready,
+                trim_json_code_block,
+            },
+            narration::set_progress,
+        },
+    },
+    logic_cb,
+    slint_generatedAppWindow::{
+        AppWindow, TranscribeChapter as UITranscribeChapter,
+        TranscribeProgressType as UITranscribeProgressType,
+    },
+    toast_info, toast_success, toast_warn,
+};
+use anyhow::{Result, anyhow};
+use bot::{ContextWindow, TokenCounter};
+use cutil::time::{TimestampStyle, format_duration};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use slint::{ComponentHandle, ModelRc, VecModel};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use video_utils::subtitle::{Subtitle as ExportSubtitle, ms_to_srt_timestamp};
+
+// Transcript tokens sent in one request. A longer transcript is chaptered in
+// parts and the summaries of the parts are merged
+const MAX_TRANSCRIPT_TOKENS: usize = 16 * 1024;
+
+const CHAPTER_PROMPT: &str = r#"You are a video editor. Split the following transcript into chapters by topic and write a summary of it. Each line of the transcript is `[index] start time: text`. A chapter starts at a line and has a short title in the language of the transcript. Only output the JSON object, no additional text.
+
+<Output format>
+{"summary": "summary of the transcript", "chapters": [{"index": 0, "title": "title1"}, {"index": 12, "title": "title2"}, ...]}
+</Output format>
+"#;
+
+const MERGE_SUMMARY_PROMPT: &str = "The following are the summaries of the consecutive parts of a video transcript. Merge them into one summary of the whole video in the language of the summaries. Only output the summary.";
+
+// Containers the chapters are written into
+const CHAPTER_CONTAINERS: [&str; 4] = ["mp4", "m4v", "m4a", "mov"];
+
+static CHAPTER_STOP_SIG: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+
+crate::db_update!(DB_TABLE, Transcribe);
+
+#[derive(Debug, Default)]
+struct Chapters {
+    summary: String,
+
+    // Start in milliseconds and title
+    chapters: Vec<(u64, String)>,
+}
+
+#[derive(Deserialize)]
+struct ChapterOutput {
+    #[serde(default)]
+    summary: String,
+
+    #[serde(default)]
+    chapters: Vec<ChapterOutputItem>,
+}
+
+#[derive(Deserialize)]
+struct ChapterOutputItem {
+    index: usize,
+    title: String,
+}
+
+pub fn init(ui: &AppWindow) {
+    logic_cb!(transcribe_generate_chapters, ui);
+}
+
+pub fn cancel_chapters() {
+    if let Some(stop_sig) = CHAPTER_STOP_SIG.lock().unwrap().take() {
+        stop_sig.store(true, Ordering::Relaxed);
+    }
+}
+
+fn transcribe_generate_chapters(ui: &AppWindow) {
+    if !is_ai_model_ready() {
+        toast_info!(ui, "Please setup AI model and try again.".to_string());
+        return;
+    }
+
+    let Some(subtitles) = get_export_subtitles(ui) else {
+        toast_warn!(ui, "Contain invalid `srt` timestamp".to_string());
+        return;
+    };
+
+    if subtitles
+        .iter()
+        .all(|subtitle| subtitle.text.trim().is_empty())
+    {
+        toast_info!(ui, tr("No subtitles to generate chapters"));
+        return;
+    }
+
+    let entry = global_store!(ui).get_transcribe();
+    let file_path = PathBuf::from(entry.file_path.as_str());
+
+    let stop_sig = Arc::new(AtomicBool::new(false));
+    if let Some(sig) = CHAPTER_STOP_SIG.lock().unwrap().replace(stop_sig.clone()) {
+        sig.store(true, Ordering::Relaxed);
+    }
+
+    let ui_weak = ui.as_weak();
+    set_progress(
+        ui_weak.clone(),
+        UITranscribeProgressType::GenerateChapters,
+        0.0,
+    );
+
+    tokio::spawn(async move {
+        let ui_weak_clone = ui_weak.clone();
+        let result = generate_chapters(subtitles, stop_sig.clone(), move |progress| {
+            set_progress(
+                ui_weak_clone.clone(),
+                UITranscribeProgressType::GenerateChapters,
+                progress,
+            )
+        })
+        .await;
+
+        // The progress type was already set by the cancel action
+        if stop_sig.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let chapters = match result {
+            Ok(chapters) => chapters,
+            Err(e) => {
+                set_progress(ui_weak.clone(), UITranscribeProgressType::Failed, 0.0);
+                toast::async_toast_warn(ui_weak, format!("Generate chapters failed: {e}"));
+                return;
+            }
+        };
+
+        let write_result = if is_chapter_container(&file_path) && file_path.exists() {
+            let file_path = file_path.clone();
+            let mp4_chapters = chapters
+                .chapters
+                .iter()
+                .map(|(ms, title)| mp4m::Chapter::new(Duration::from_millis(*ms), title))
+                .collect::<Vec<_>>();
+
+            tokio::task::spawn_blocking(move || mp4m::write_chapters(&file_path, &mp4_chapters))
+                .await
+                .map_err(|e| anyhow!("{e}"))
+                .and_then(|result| result.map_err(|e| anyhow!("{e}")))
+        } else {
+            Ok(())
+        };
+
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            let mut entry = global_store!(ui).get_transcribe();
+            entry.summary = chapters.summary.into();
+            entry.chapters = ModelRc::new(VecModel::from(
+                chapters
+                    .chapters
+                    .into_iter()
+                    .map(|(ms, title)| UITranscribeChapter {
+                        timestamp: ms_to_srt_timestamp(ms).into(),
+                        title: title.into(),
+                    })
+                    .collect::<Vec<_>>(),
+            ));
+            entry.progress = 1.0;
+            entry.progress_type = UITranscribeProgressType::Finished;
+            global_store!(ui).set_transcribe(entry.clone());
+            db_update(ui.as_weak(), entry.into());
+
+            match write_result {
+                Ok(_) => toast_success!(ui, tr("Generate chapters successfully")),
+                Err(e) => toast_warn!(
+                    ui,
+                    format!("{}: {e}", tr("Write chapters into the video failed"))
+                ),
+            }
+        });
+    });
+}
+
+fn is_chapter_container(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            CHAPTER_CONTAINERS
+                .iter()
+                .any(|container| ext.eq_ignore_ascii_case(container))
+        })
+}
+
+/// Chapter the transcript part by part, then merge the summaries of the
+/// parts into one
+async fn generate_chapters(
+    subtitles: Vec<ExportSubtitle>,
+    stop_sig: Arc<AtomicBool>,
+    on_progress: impl Fn(f32),
+) -> Result<Chapters> {
+    let config = ai_api_config();
+    let window = ContextWindow::from_config(&config);
+    let counter = &window.counter;
+    let budget = window
+        .budget()
+        .saturating_sub(counter.count_message(CHAPTER_PROMPT) + counter.count_message(""));
+    let parts = split_transcript(&subtitles, budget.min(MAX_TRANSCRIPT_TOKENS), counter);
+
+    // The last step is merging the summaries
+    let steps = parts.len() + usize::from(parts.len() > 1);
+
+    let mut chapters = Chapters::default();
+    let mut summaries = vec![];
+    for (part_index, part) in parts.iter().enumerate() {
+        let question = part
+            .iter()
+            .map(|(index, subtitle)| transcript_line(*index, subtitle))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let answer = ai_chat(config.clone(), CHAPTER_PROMPT, question, &stop_sig).await?;
+        let answer = trim_json_code_block(&answer);
+        log::debug!("{answer}");
+
+        let output: ChapterOutput = serde_json::from_str(answer)
+            .map_err(|e| anyhow!("Failed to parse AI response as JSON: {e}. Response: {answer}"))?;
+
+        // Only the lines of this part can start its chapters
+        for item in output.chapters {
+            match part.iter().find(|(index, _)| *index == item.index) {
+                Some((_, subtitle)) if !item.title.trim().is_empty() => chapters
+                    .chapters
+                    .push((subtitle.start_timestamp, item.title.trim().to_string())),
+                _ => log::warn!("skip chapter `{}` at line {}", item.title, item.index),
+            }
+        }
+
+        if !output.summary.trim().is_empty() {
+            summaries.push(output.summary.trim().to_string());
+        }
+
+        on_progress((part_index + 1) as f32 / steps as f32);
+    }
+
+    chapters.summary = if summaries.len() > 1 {
+        let question = summaries.join("\n\n");
+        ai_chat(config, MERGE_SUMMARY_PROMPT, question, &stop_sig)
+            .await?
+            .trim()
+            .to_string()
+    } else {
+        summaries.pop().unwrap_or_default()
+    };
+
+    chapters.chapters.sort_by_key(|(ms, _)| *ms);
+    chapters.chapters.dedup_by_key(|(ms, _)| *ms);
+
+    // The first chapter covers what's before it
+    if let Some((ms, _)) = chapters.chapters.first_mut() {
+        *ms = 0;
+    }
+
+    Ok(chapters)
+}
+
+// Split the non-empty lines into parts of at most `max_tokens` tokens, a part
+// has a line at least
+fn split_transcript<'a>(
+    subtitles: &'a [ExportSubtitle],
+    max_tokens: usize,
+    counter: &TokenCounter,
+) -> Vec<Vec<(usize, &'a ExportSubtitle)>> {
+    let mut parts = vec![];
+    let mut part = vec![];
+    let mut tokens = 0;
+
+    for (index, subtitle) in subtitles.iter().enumerate() {
+        if subtitle.text.trim().is_empty() {
+            continue;
+        }
+
+        // Count the line break too
+        let line_tokens = counter.count(&transcript_line(index, subtitle)) + 1;
+        if tokens + line_tokens > max_tokens && !part.is_empty() {
+            parts.push(std::mem::take(&mut part));
+            tokens = 0;
+        }
+
+        part.push((index, subtitle));
+        tokens += line_tokens;
+    }
+
+    if !part.is_empty() {
+        parts.push(part);
+    }
+
+    parts
+}
+
+fn transcript_line(index: usize, subtitle: &ExportSubtitle) -> String {
+    let timestamp = format_duration(subtitle.start_timestamp, TimestampStyle::Media);
+    format!("[{index}] {timestamp}: {}", subtitle.text.trim())
+}
@@ -55,7 +55,7 @@ fn transcribe_choose_model_path(ui: &AppWindow, index: i32) {
             ui_weak.clone(),
             &tr("Choose model or tokenizer"),
             &tr("fun ast model or tokenizer"),
-            &["pt", "json"],
+            &["pt", "json", "onnx"],
         ) else {
             return;
         };
@@ -66,6 +66,7 @@ fn transcribe_choose_model_path(ui: &AppWindow, index: i32) {
             match index {
                 0 => setting.model_path = filepath,
                 1 => setting.model_tokenizer_path = filepath,
+                2 => setting.speaker_model_path = filepath,
                 _ => log::warn!("Unexcepted trancribe model index = {index}"),
             }
 
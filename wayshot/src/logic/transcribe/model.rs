@@ -1,14 +1,17 @@
 use crate::{
     config,
-    db::{TRANSCRIBE_TABLE as DB_TABLE, Transcribe},
+    db::{AI_RESPONSE_CACHE_TABLE, DB_NAME, TRANSCRIBE_TABLE as DB_TABLE, Transcribe},
     global_logic, global_store,
     logic::{
         recorder::picker_directory,
         share_screen::picker_file,
         toast,
         tr::tr,
-        transcribe::audio_player::{
-            self, MAX_WAVE_FORM_SAMPLE_COUNTS, extract_audio_samples, get_current_audio_config,
+        transcribe::{
+            audio_player::{
+                self, MAX_WAVE_FORM_SAMPLE_COUNTS, extract_audio_samples, get_current_audio_config,
+            },
+            chapter, narration, translation,
         },
     },
     logic_cb,
@@ -25,8 +28,11 @@ use audio_utils::{
     loader::{AudioConfig, AudioSegment, gen_audio_segments},
     vad::VadConfig,
 };
-use bot::{APIConfig, Chat, ChatConfig, StreamTextItem};
-use fun_ast_nano::{FunASRModelConfig, FunAsrError, FunAsrNanoGenerateModel, load_audio_file};
+use bot::{APIConfig, Chat, ChatConfig, ResponseCache, StreamTextItem};
+use fun_ast_nano::{
+    DEFAULT_HOTWORD_BOOST, DiarizationConfig, FunASRModelConfig, FunAsrError,
+    FunAsrNanoGenerateModel, load_audio_file,
+};
 use once_cell::sync::Lazy;
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel, Weak};
 use std::{
@@ -46,10 +52,14 @@ use video_utils::subtitle::{
 
 const TRANSCRIBE_ID: &str = "transcribe_id";
 const DEFAULT_PROMPT: &str = "Transcribe audio to text.";
+
+// Segments decoded together, the model runs on the GPU when the app is
+// built with its feature
+const TRANSCRIBE_BATCH_SIZE: usize = 8;
 static TRANSCRIBE_CACHE: Lazy<Mutex<TranscribeCache>> =
     Lazy::new(|| Mutex::new(TranscribeCache::default()));
 
-crate::db_remove_all!(DB_TABLE);
+crate::db_remove_all!(DB_TABLE, Transcribe);
 crate::db_add!(DB_TABLE, Transcribe);
 crate::db_update!(DB_TABLE, Transcribe);
 crate::db_select!(DB_TABLE, Transcribe);
@@ -118,7 +128,8 @@ pub fn transcribe_init(ui: &AppWindow) {
 
     let ui_weak = ui.as_weak();
     tokio::spawn(async move {
-        if sqldb::entry::is_exist(DB_TABLE, TRANSCRIBE_ID)
+        if sqldb::Repository::<Transcribe>::new(DB_NAME, DB_TABLE)
+            .is_exist(TRANSCRIBE_ID)
             .await
             .is_ok()
         {
@@ -232,9 +243,16 @@ fn inner_transcribe_start(ui: &AppWindow, filepath: PathBuf) -> Result<()> {
 
     thread::spawn(move || {
         let ui_weak_clone = ui_weak.clone();
+        let diarization = (!setting.speaker_model_path.is_empty()).then(|| {
+            DiarizationConfig::default()
+                .with_embedding_model(setting.speaker_model_path.to_string())
+        });
+
         let config = FunASRModelConfig::default()
             .with_model_weights(setting.model_path.to_string())
-            .with_tokenizer_path(setting.model_tokenizer_path.to_string());
+            .with_tokenizer_path(setting.model_tokenizer_path.to_string())
+            .with_diarization(diarization)
+            .with_batch_size(TRANSCRIBE_BATCH_SIZE);
 
         log::info!("Loading transcribe model: {config:?}");
 
@@ -265,10 +283,20 @@ fn inner_transcribe_start(ui: &AppWindow, filepath: PathBuf) -> Result<()> {
             }
         };
 
+        let hotwords = setting
+            .hotwords
+            .split([',', '，'])
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>();
+
         let request = fun_ast_nano::TranscriptionRequest::default()
             .with_audio_config(audio_config.clone())
             .with_prompt(Some(DEFAULT_PROMPT.to_string()))
-            .with_max_tokens(512);
+            .with_max_tokens(512)
+            .with_hotwords(hotwords, DEFAULT_HOTWORD_BOOST)
+            .with_detect_language(setting.detect_language)
+            .with_language_prompts(setting.detect_language);
 
         let result = model.generate(request, Some(vad_config), move |chunk| {
             if let Some(ref stop_sig) = stop_sig
@@ -295,12 +323,18 @@ fn inner_transcribe_start(ui: &AppWindow, filepath: PathBuf) -> Result<()> {
                     let samples = downsample_audio(&samples, MAX_WAVE_FORM_SAMPLE_COUNTS as usize);
                     let amplitude = max_sound_wave_amplitude(&samples);
 
+                    let text = match seg_info.speaker_id {
+                        Some(id) => format!("{} {id}: {}", tr("Speaker"), chunk.text),
+                        None => chunk.text,
+                    };
+
                     _ = ui_weak.clone().upgrade_in_event_loop(move |ui| {
                         let subtitle = UISubtitle {
                             start_timestamp,
                             end_timestamp,
-                            original_text: chunk.text.into(),
+                            original_text: text.into(),
                             correction_text: Default::default(),
+                            translation_text: Default::default(),
                             audio_wave_amplitude: amplitude,
                             audio_samples: ModelRc::new(VecModel::from_slice(&samples)),
                             is_timestamp_overlap: false,
@@ -455,7 +489,7 @@ fn transcribe_export_subtitles(ui: &AppWindow) {
     });
 }
 
-fn get_export_subtitles(ui: &AppWindow) -> Option<Vec<ExportSubtitle>> {
+pub fn get_export_subtitles(ui: &AppWindow) -> Option<Vec<ExportSubtitle>> {
     let mut items = vec![];
     let entry = global_store!(ui).get_transcribe();
 
@@ -507,6 +541,9 @@ fn transcribe_cancel_progress(ui: &AppWindow, ty: UITranscribeProgressType) {
                 stop_sig.store(true, Ordering::Relaxed);
             }
         }
+        UITranscribeProgressType::Narrate => narration::cancel_narration(),
+        UITranscribeProgressType::GenerateChapters => chapter::cancel_chapters(),
+        UITranscribeProgressType::Translate => translation::cancel_translation(),
         _ => {
             todo!()
         }
@@ -568,12 +605,75 @@ fn transcribe_subtitles_remove_all(ui: &AppWindow) {
     db_remove_all(ui.as_weak());
 }
 
-fn transcribe_subtitles_correction(ui: &AppWindow) {
+pub fn is_ai_model_ready() -> bool {
     let setting = config::all().ai_model;
-    if setting.api_base_url.is_empty()
+    let provider = bot::Provider::from(setting.provider);
+    !((setting.api_base_url.is_empty() && matches!(provider, bot::Provider::OpenAI))
         || setting.model_name.is_empty()
-        || setting.api_key.is_empty()
-    {
+        || (setting.api_key.is_empty() && provider.requires_api_key()))
+}
+
+pub fn ai_api_config() -> APIConfig {
+    let model_config = config::all().ai_model;
+    APIConfig {
+        provider: model_config.provider.into(),
+        api_base_url: model_config.api_base_url,
+        api_model: model_config.model_name,
+        api_key: model_config.api_key,
+        temperature: None,
+        max_tokens: None,
+        context_tokens: None,
+        max_attempts: None,
+    }
+}
+
+// The JSON of an answer, models often wrap it in a markdown code block
+pub fn trim_json_code_block(text: &str) -> &str {
+    text.trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim()
+}
+
+pub async fn ai_chat(
+    config: APIConfig,
+    prompt: &str,
+    question: String,
+    stop_sig: &AtomicBool,
+) -> Result<String> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<StreamTextItem>(100);
+
+    // A question asked before is answered from the cache
+    let chat = Chat::new(prompt, question, ChatConfig { tx }, config, vec![])
+        .with_cache(ResponseCache::new(DB_NAME, AI_RESPONSE_CACHE_TABLE));
+
+    // Dropping the receiver stops the chat
+    let collect = async move {
+        let mut answer = String::new();
+        while let Some(item) = rx.recv().await {
+            if stop_sig.load(Ordering::Relaxed) {
+                return Err(anyhow!("Cancelled"));
+            }
+
+            if let Some(etext) = item.etext {
+                return Err(anyhow!(etext));
+            }
+
+            if let Some(text) = item.text {
+                answer.push_str(&text);
+            }
+        }
+        Ok(answer)
+    };
+
+    let (result, answer) = tokio::join!(chat.start(), collect);
+    result?;
+    answer
+}
+
+fn transcribe_subtitles_correction(ui: &AppWindow) {
+    if !is_ai_model_ready() {
         toast_info!(ui, "Please setup AI model and try again.".to_string());
         return;
     }
@@ -700,17 +800,13 @@ async fn ai_correct_subtitles(
 
     let (tx, mut rx) = tokio::sync::mpsc::channel::<StreamTextItem>(100);
     let question = serde_json::to_string(&input)?;
-    let model_config = config::all().ai_model.clone();
-    let request_config = APIConfig {
-        api_base_url: model_config.api_base_url,
-        api_model: model_config.model_name,
-        api_key: model_config.api_key,
-        temperature: None,
-    };
+    let request_config = ai_api_config();
 
     tokio::spawn(async move {
         let chat_config = ChatConfig { tx };
-        let chat = Chat::new(prompt, question, chat_config, request_config, vec![]);
+        // Unchanged segments are answered from the cache without tokens
+        let chat = Chat::new(prompt, question, chat_config, request_config, vec![])
+            .with_cache(ResponseCache::new(DB_NAME, AI_RESPONSE_CACHE_TABLE));
         if let Err(e) = chat.start().await {
             toast::async_toast_warn(ui_weak, format!("Start AI correction failed: {e}"));
         }
@@ -727,17 +823,11 @@ async fn ai_correct_subtitles(
         }
     }
 
-    let resp = resp
-        .trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```")
-        .trim()
-        .to_string();
+    let resp = trim_json_code_block(&resp);
 
     log::debug!("{resp}");
 
-    let output_subtitles: Vec<OutputSubtitle> = serde_json::from_str(&resp)
+    let output_subtitles: Vec<OutputSubtitle> = serde_json::from_str(resp)
         .map_err(|e| anyhow!("Failed to parse AI response as JSON: {e}. Response: {resp}"))?;
 
     let corrections = output_subtitles
@@ -1040,6 +1130,15 @@ fn transcribe_subtitle_merge_above(ui: &AppWindow, index: i32) {
     prev.end_timestamp = current.end_timestamp;
     prev.original_text = format!("{}{}", prev.original_text, current.original_text).into();
     prev.correction_text = format!("{}{}", prev.correction_text, current.correction_text).into();
+    prev.translation_text = [
+        prev.translation_text.trim(),
+        current.translation_text.trim(),
+    ]
+    .into_iter()
+    .filter(|text| !text.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+    .into();
     prev.audio_wave_amplitude = prev.audio_wave_amplitude.max(current.audio_wave_amplitude);
 
     let mut samples = prev.audio_samples.iter().collect::<Vec<_>>();
@@ -1063,20 +1162,17 @@ fn transcribe_subtitle_insert_above(ui: &AppWindow, index: i32) {
     let new_subtitle = if index == 0 {
         let first = subtitles.row_data(0).unwrap();
 
-        let end_timestamp = if first.start_timestamp == "00:00:00,000" {
-            match srt_timestamp_to_ms(&first.start_timestamp) {
-                Ok(ms) => ms_to_srt_timestamp(ms + 1000).into(),
-                _ => first.start_timestamp.clone(),
-            }
-        } else {
-            first.start_timestamp.clone()
+        let end_timestamp = match srt_timestamp_to_ms(&first.start_timestamp) {
+            Ok(0) => ms_to_srt_timestamp(1000).into(),
+            _ => first.start_timestamp.clone(),
         };
 
         UISubtitle {
-            start_timestamp: "00:00:00,000".into(),
+            start_timestamp: ms_to_srt_timestamp(0).into(),
             end_timestamp,
             original_text: "Click to edit".to_string().into(),
             correction_text: Default::default(),
+            translation_text: Default::default(),
             audio_samples: ModelRc::new(VecModel::from_slice(&[])),
             audio_wave_amplitude: 1.0,
             is_timestamp_overlap: false,
@@ -1089,6 +1185,7 @@ fn transcribe_subtitle_insert_above(ui: &AppWindow, index: i32) {
             end_timestamp: current.start_timestamp.clone(),
             original_text: "Click to edit".to_string().into(),
             correction_text: Default::default(),
+            translation_text: Default::default(),
             audio_samples: ModelRc::new(VecModel::from_slice(&[])),
             audio_wave_amplitude: 1.0,
             is_timestamp_overlap: false,
@@ -1124,6 +1221,7 @@ fn transcribe_subtitle_insert_below(ui: &AppWindow, index: i32) {
             end_timestamp,
             original_text: "Click to edit".into(),
             correction_text: Default::default(),
+            translation_text: Default::default(),
             audio_samples: ModelRc::new(VecModel::from_slice(&[])),
             audio_wave_amplitude: 1.0,
             is_timestamp_overlap: false,
@@ -1136,6 +1234,7 @@ fn transcribe_subtitle_insert_below(ui: &AppWindow, index: i32) {
             end_timestamp: next.start_timestamp.clone(),
             original_text: "Click to edit".into(),
             correction_text: Default::default(),
+            translation_text: Default::default(),
             audio_samples: ModelRc::new(VecModel::from_slice(&[])),
             audio_wave_amplitude: 1.0,
             is_timestamp_overlap: false,
@@ -0,0 +1,285 @@
+use crate::{
+    config, global_store,
+    logic::{
+        recorder::picker_directory, share_screen::picker_file, toast, tr::tr,
+        transcribe::model::get_export_subtitles,
+    },
+    logic_cb,
+    slint_generatedAppWindow::{
+        AppWindow, SettingTranscribe as UISettingTranscribe,
+        TranscribeProgressType as UITranscribeProgressType,
+    },
+    toast_warn,
+};
+use anyhow::Result;
+use gpt_sovits::{
+    AudioSink, AudioSinkConfig, ExecutionProvider, GSVError, GptSoVitsModel, GptSoVitsModelConfig,
+    LangId, OUTPUT_AUDIO_SAMPLE_RATE, Prosody, ReferenceData, SamplingParams, SpeakerBank,
+    SynthesizeOptions,
+};
+use once_cell::sync::Lazy;
+use slint::{ComponentHandle, Weak};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use video_utils::{mix_audio_tracks, subtitle::Subtitle as ExportSubtitle};
+
+// Lines longer than their time slot are sped up to at most this rate, the
+// rest delays the next line
+const MAX_NARRATION_RATE: f32 = 1.3;
+
+static NARRATION_STOP_SIG: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn init(ui: &AppWindow) {
+    logic_cb!(transcribe_narrate, ui);
+    logic_cb!(transcribe_choose_narration_model_dir, ui);
+    logic_cb!(transcribe_choose_narration_reference_audio, ui);
+}
+
+pub fn cancel_narration() {
+    if let Some(stop_sig) = NARRATION_STOP_SIG.lock().unwrap().take() {
+        stop_sig.store(true, Ordering::Relaxed);
+    }
+}
+
+fn transcribe_choose_narration_model_dir(ui: &AppWindow) {
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let Some(dir) = picker_directory(ui_weak.clone(), &tr("Choose speech model directory"), "")
+        else {
+            return;
+        };
+
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            let mut setting = global_store!(ui).get_transcribe_setting_cache();
+            setting.narration_model_dir = dir.to_string_lossy().to_string().into();
+            global_store!(ui).set_transcribe_setting_cache(setting);
+        });
+    });
+}
+
+fn transcribe_choose_narration_reference_audio(ui: &AppWindow) {
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let Some(filepath) = picker_file(
+            ui_weak.clone(),
+            &tr("Choose reference voice"),
+            &tr("e.g. wav, mp3"),
+            &["wav", "mp3"],
+        ) else {
+            return;
+        };
+
+        _ = ui_weak.upgrade_in_event_loop(move |ui| {
+            let mut setting = global_store!(ui).get_transcribe_setting_cache();
+            setting.narration_reference_audio = filepath.to_string_lossy().to_string().into();
+            global_store!(ui).set_transcribe_setting_cache(setting);
+        });
+    });
+}
+
+fn transcribe_narrate(ui: &AppWindow) {
+    let setting = global_store!(ui).get_transcribe_setting();
+    if !Path::new(setting.narration_model_dir.as_str()).is_dir()
+        || !cutil::fs::file_exist(&setting.narration_reference_audio)
+        || setting.narration_reference_text.trim().is_empty()
+    {
+        toast_warn!(ui, tr("Please setup narration and try again."));
+        return;
+    }
+
+    let entry = global_store!(ui).get_transcribe();
+    let video = PathBuf::from(&entry.file_path);
+    if !video.exists() {
+        toast_warn!(ui, format!("No found {}", video.display()));
+        return;
+    }
+
+    let Some(subtitles) = get_export_subtitles(ui) else {
+        toast_warn!(ui, "Contain invalid `srt` timestamp".to_string());
+        return;
+    };
+
+    let filename = format!(
+        "{}-narration.{}",
+        cutil::fs::file_name_without_ext(&entry.file_path),
+        video.extension().unwrap_or_default().to_string_lossy()
+    );
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let Some(dir) = picker_directory(ui_weak.clone(), &tr("Export Video"), &filename) else {
+            return;
+        };
+
+        let stop_sig = Arc::new(AtomicBool::new(false));
+        if let Some(sig) = NARRATION_STOP_SIG.lock().unwrap().replace(stop_sig.clone()) {
+            sig.store(true, Ordering::Relaxed);
+        }
+
+        set_progress(ui_weak.clone(), UITranscribeProgressType::Narrate, 0.0);
+
+        let ui_weak_clone = ui_weak.clone();
+        let result = narrate(
+            setting,
+            subtitles,
+            video,
+            dir.join(filename),
+            stop_sig.clone(),
+            move |progress| {
+                set_progress(
+                    ui_weak_clone.clone(),
+                    UITranscribeProgressType::Narrate,
+                    progress,
+                )
+            },
+        )
+        .await;
+
+        // The progress type was already set by the cancel action
+        if stop_sig.load(Ordering::Relaxed) {
+            return;
+        }
+
+        match result {
+            Ok(_) => {
+                set_progress(ui_weak.clone(), UITranscribeProgressType::Finished, 1.0);
+                toast::async_toast_success(ui_weak, "Export video successfully".to_string());
+            }
+            Err(e) => {
+                set_progress(ui_weak.clone(), UITranscribeProgressType::Failed, 0.0);
+                toast::async_toast_warn(ui_weak, format!("Narrate failed: {e}"));
+            }
+        }
+    });
+}
+
+pub fn set_progress(ui_weak: Weak<AppWindow>, ty: UITranscribeProgressType, progress: f32) {
+    _ = ui_weak.upgrade_in_event_loop(move |ui| {
+        let mut entry = global_store!(ui).get_transcribe();
+        entry.progress_type = ty;
+        entry.progress = progress;
+        global_store!(ui).set_transcribe(entry);
+    });
+}
+
+/// Synthesize every subtitle at its start time and mix the narration track
+/// into the video
+async fn narrate(
+    setting: UISettingTranscribe,
+    subtitles: Vec<ExportSubtitle>,
+    video: PathBuf,
+    output: PathBuf,
+    stop_sig: Arc<AtomicBool>,
+    on_progress: impl Fn(f32),
+) -> Result<()> {
+    let model_dir = PathBuf::from(setting.narration_model_dir.as_str());
+    let config = GptSoVitsModelConfig::default()
+        .with_sovits_path(model_dir.join("custom_vits.onnx"))
+        .with_ssl_path(model_dir.join("ssl.onnx"))
+        .with_t2s_encoder_path(model_dir.join("custom_t2s_encoder.onnx"))
+        .with_t2s_fs_decoder_path(model_dir.join("custom_t2s_fs_decoder.onnx"))
+        .with_t2s_s_decoder_path(model_dir.join("custom_t2s_s_decoder.onnx"))
+        .with_bert_path(model_dir.join("bert.onnx"))
+        .with_g2pw_path(model_dir.join("g2pW.onnx"))
+        .with_g2p_en_encoder_path(model_dir.join("g2p_en").join("encoder_model.onnx"))
+        .with_g2p_en_decoder_path(model_dir.join("g2p_en").join("decoder_model.onnx"))
+        .with_execution_providers(ExecutionProvider::available_providers())
+        .with_stream_chunk_tokens(None);
+
+    log::info!("Loading narration model: {}", model_dir.display());
+    let mut model = GptSoVitsModel::new(config)?;
+    let reference_data = get_reference_data(
+        &mut model,
+        setting.narration_reference_audio.as_str(),
+        setting.narration_reference_text.as_str(),
+    )
+    .await?;
+
+    let options = SynthesizeOptions::default().with_cancel_sig(stop_sig);
+    let mut track = vec![];
+
+    for (index, subtitle) in subtitles.iter().enumerate() {
+        let audio = match model
+            .synthesize_all(
+                &subtitle.text,
+                &reference_data,
+                SamplingParams::default(),
+                LangId::Auto,
+                options.clone(),
+            )
+            .await
+        {
+            Ok(audio) => audio,
+            Err(GSVError::InputEmpty) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let slot_end = subtitles
+            .get(index + 1)
+            .map(|next| next.start_timestamp)
+            .unwrap_or(subtitle.end_timestamp)
+            .max(subtitle.end_timestamp);
+
+        place_narration(&mut track, audio, subtitle.start_timestamp, slot_end);
+
+        // The last step is the mixing
+        on_progress((index + 1) as f32 / (subtitles.len() + 1) as f32);
+    }
+
+    let narration_path = config::all().cache_dir.join("narration.wav");
+    let mut sink = AudioSink::new(AudioSinkConfig::default());
+    sink.push(&track);
+    sink.save(&narration_path)?;
+
+    let original_volume = setting.narration_original_volume.clamp(0.0, 1.0);
+    tokio::task::spawn_blocking(move || {
+        mix_audio_tracks(&video, &[&narration_path], &[original_volume, 1.0], &output)
+    })
+    .await??;
+
+    Ok(())
+}
+
+/// The features of the reference voice are computed once and kept in a
+/// speaker bank in the cache directory
+async fn get_reference_data(
+    model: &mut GptSoVitsModel,
+    audio: &str,
+    text: &str,
+) -> Result<ReferenceData> {
+    let bank_path = config::all().cache_dir.join("narration-speakers.bin");
+    let mut bank = SpeakerBank::load(&bank_path).await.unwrap_or_default();
+
+    let name = format!("{audio}\n{text}");
+    if !bank.contains(&name) {
+        model
+            .add_speaker(&mut bank, &name, Path::new(audio), text, LangId::Auto)
+            .await?;
+        bank.save(&bank_path).await?;
+    }
+
+    Ok(bank.get(&name)?.clone())
+}
+
+/// Put the audio of a line at its start time, sped up to end before
+/// `slot_end_ms` if possible. A line never overlaps the previous one.
+fn place_narration(track: &mut Vec<f32>, audio: Vec<f32>, start_ms: u64, slot_end_ms: u64) {
+    let to_samples = |ms: u64| (ms * OUTPUT_AUDIO_SAMPLE_RATE as u64 / 1000) as usize;
+    let start = to_samples(start_ms).max(track.len());
+    let slot = to_samples(slot_end_ms).saturating_sub(start).max(1);
+
+    let rate = (audio.len() as f32 / slot as f32).clamp(1.0, MAX_NARRATION_RATE);
+    let audio = Prosody {
+        rate,
+        ..Default::default()
+    }
+    .apply(audio);
+
+    track.resize(start, 0.0);
+    track.extend(audio);
+}
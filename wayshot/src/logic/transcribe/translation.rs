@@ -0,0 +1,293 @@
+use crate::{
+    config,
+    db::{TRANSCRIBE_TABLE as DB_TABLE, Transcribe},
+    global_store,
+    logic::{
+        recorder::picker_directory,
+        toast,
+        tr::tr,
+        transcribe::{
+            model::{
+                ai_api_config, ai_chat, get_export_subtitles, is_ai_model_ready,
+                trim_json_code_block,
+            },
+            narration::set_progress,
+        },
+    },
+    logic_cb,
+    slint_generatedAppWindow::{
+        AppWindow, Subtitle as UISubtitle, TranscribeProgressType as UITranscribeProgressType,
+    },
+    store_transcribe_subtitles, toast_info, toast_warn,
+};
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use slint::{ComponentHandle, Model, SharedString, VecModel};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+use tokio::sync::Semaphore;
+use video_utils::subtitle::{AssStyle, save_subtitles};
+
+// Subtitles translated in one request, the lines around a subtitle give the
+// context of its translation
+const TRANSLATE_BATCH_SIZE: usize = 20;
+
+// Requests running at the same time, so a long talk doesn't hit the rate
+// limit of the provider at once
+const MAX_PARALLEL_REQUESTS: usize = 4;
+
+static TRANSLATION_STOP_SIG: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+
+crate::db_update!(DB_TABLE, Transcribe);
+
+pub fn init(ui: &AppWindow) {
+    logic_cb!(transcribe_subtitles_translate, ui, target_lang);
+    logic_cb!(transcribe_subtitles_remove_translation, ui);
+    logic_cb!(transcribe_export_bilingual_subtitles, ui, format);
+}
+
+pub fn cancel_translation() {
+    if let Some(stop_sig) = TRANSLATION_STOP_SIG.lock().unwrap().take() {
+        stop_sig.store(true, Ordering::Relaxed);
+    }
+}
+
+fn transcribe_subtitles_translate(ui: &AppWindow, target_lang: SharedString) {
+    if !is_ai_model_ready() {
+        toast_info!(ui, "Please setup AI model and try again.".to_string());
+        return;
+    }
+
+    let target_lang = target_lang.trim().to_string();
+    if target_lang.is_empty() {
+        toast_info!(ui, tr("Please input the target language"));
+        return;
+    }
+
+    let entry = global_store!(ui).get_transcribe();
+    let subtitles_to_translate = store_transcribe_subtitles!(entry)
+        .iter()
+        .enumerate()
+        .filter_map(|(index, sub)| {
+            if sub.translation_text.is_empty() && !sub.original_text.trim().is_empty() {
+                Some((index, sub.original_text.to_string()))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if subtitles_to_translate.is_empty() {
+        toast_info!(
+            ui,
+            tr("All subtitles already have translations or are empty")
+        );
+        return;
+    }
+
+    let stop_sig = Arc::new(AtomicBool::new(false));
+    if let Some(sig) = TRANSLATION_STOP_SIG
+        .lock()
+        .unwrap()
+        .replace(stop_sig.clone())
+    {
+        sig.store(true, Ordering::Relaxed);
+    }
+
+    set_progress(ui.as_weak(), UITranscribeProgressType::Translate, 0.0);
+
+    let total_subtitles_count = subtitles_to_translate.len();
+    let finished_subtitles_count = Arc::new(AtomicUsize::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+    let semaphore = Arc::new(Semaphore::new(MAX_PARALLEL_REQUESTS));
+
+    for (chunk_index, chunk) in subtitles_to_translate
+        .chunks(TRANSLATE_BATCH_SIZE)
+        .enumerate()
+    {
+        let ui_weak = ui.as_weak();
+        let chunk = chunk.to_vec();
+        let target_lang = target_lang.clone();
+        let stop_sig = stop_sig.clone();
+        let finished_subtitles_count = finished_subtitles_count.clone();
+        let failed = failed.clone();
+        let semaphore = semaphore.clone();
+
+        tokio::spawn(async move {
+            let Ok(_permit) = semaphore.acquire().await else {
+                return;
+            };
+
+            if stop_sig.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let chunk_len = chunk.len();
+            let translations = match ai_translate_subtitles(chunk, &target_lang, &stop_sig).await {
+                Ok(translations) => translations,
+                Err(e) => {
+                    if !stop_sig.load(Ordering::Relaxed) {
+                        failed.store(true, Ordering::Relaxed);
+                        toast::async_toast_warn(
+                            ui_weak.clone(),
+                            format!("Chunk[{chunk_index}] AI translation failed: {e}"),
+                        );
+                    }
+                    HashMap::new()
+                }
+            };
+
+            // The progress type was already set by the cancel action
+            if stop_sig.load(Ordering::Relaxed) {
+                return;
+            }
+
+            _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                let mut entry = global_store!(ui).get_transcribe();
+                let subtitles = store_transcribe_subtitles!(entry);
+
+                for (index, translation) in translations {
+                    if let Some(mut subtitle) = subtitles.row_data(index) {
+                        subtitle.translation_text = translation.into();
+                        subtitles.set_row_data(index, subtitle);
+                    }
+                }
+
+                let counts =
+                    finished_subtitles_count.fetch_add(chunk_len, Ordering::Relaxed) + chunk_len;
+                entry.progress = counts as f32 / total_subtitles_count as f32;
+
+                if counts >= total_subtitles_count {
+                    entry.progress_type = if failed.load(Ordering::Relaxed) {
+                        UITranscribeProgressType::Failed
+                    } else {
+                        UITranscribeProgressType::Finished
+                    };
+                }
+
+                global_store!(ui).set_transcribe(entry.clone());
+                db_update(ui.as_weak(), entry.into());
+            });
+        });
+    }
+}
+
+async fn ai_translate_subtitles(
+    subtitles: Vec<(usize, String)>,
+    target_lang: &str,
+    stop_sig: &AtomicBool,
+) -> Result<HashMap<usize, String>> {
+    #[derive(serde::Serialize)]
+    struct InputSubtitle {
+        index: usize,
+        text: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct OutputSubtitle {
+        index: usize,
+        translation: String,
+    }
+
+    let prompt = format!(
+        r#"You are a subtitle translator. Translate the text of each subtitle into {target_lang}. The subtitles are consecutive lines of a video, use them as the context of each other, but translate each subtitle on its own and keep its index. Don't merge or split subtitles. Only output the JSON array, no additional text.
+
+<Input format>
+[{{"index": 1, "text": "text1"}}, {{"index": 3, "text": "text3"}}, ...]
+</Input format>
+
+<Output format>
+[{{"index": 1, "translation": "translation1"}}, {{"index": 3, "translation": "translation3"}}, ...]
+</Output format>
+"#
+    );
+
+    let indexes = subtitles
+        .iter()
+        .map(|(index, _)| *index)
+        .collect::<Vec<_>>();
+    let input = subtitles
+        .into_iter()
+        .map(|(index, text)| InputSubtitle { index, text })
+        .collect::<Vec<_>>();
+    let question = serde_json::to_string(&input)?;
+
+    let answer = ai_chat(ai_api_config(), &prompt, question, stop_sig).await?;
+    let answer = trim_json_code_block(&answer);
+    log::debug!("{answer}");
+
+    let output_subtitles: Vec<OutputSubtitle> = serde_json::from_str(answer)
+        .map_err(|e| anyhow!("Failed to parse AI response as JSON: {e}. Response: {answer}"))?;
+
+    // Only the subtitles of the request are taken
+    let translations = output_subtitles
+        .into_iter()
+        .filter(|item| indexes.contains(&item.index) && !item.translation.trim().is_empty())
+        .map(|item| (item.index, item.translation.trim().to_string()))
+        .collect();
+
+    Ok(translations)
+}
+
+fn transcribe_subtitles_remove_translation(ui: &AppWindow) {
+    let entry = global_store!(ui).get_transcribe();
+    let subtitles = store_transcribe_subtitles!(entry);
+
+    let updated_subtitles = subtitles
+        .iter()
+        .map(|mut subtitle| {
+            subtitle.translation_text = SharedString::default();
+            subtitle
+        })
+        .collect::<Vec<_>>();
+
+    store_transcribe_subtitles!(entry).set_vec(updated_subtitles);
+    db_update(ui.as_weak(), entry.into());
+}
+
+// The translation is stacked under the original text, the subtitles without
+// translation keep the original text only. `format` is `srt` or `ass`
+fn transcribe_export_bilingual_subtitles(ui: &AppWindow, format: SharedString) {
+    let entry = global_store!(ui).get_transcribe();
+    let filename = format!(
+        "{}.bilingual.{format}",
+        cutil::fs::file_name_without_ext(&entry.file_path)
+    );
+
+    let Some(mut items) = get_export_subtitles(ui) else {
+        toast_warn!(ui, "Contain invalid `srt` timestamp".to_string());
+        return;
+    };
+
+    for (item, subtitle) in items
+        .iter_mut()
+        .zip(store_transcribe_subtitles!(entry).iter())
+    {
+        let translation = subtitle.translation_text.trim();
+        if !translation.is_empty() {
+            item.text = format!("{}\n{translation}", item.text);
+        }
+    }
+
+    // The font of the app has the CJK glyphs
+    let style = AssStyle::default().with_font_name(config::all().preference.font_family);
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        let Some(path) = picker_directory(ui_weak.clone(), &tr("Export Subtitle"), &filename)
+        else {
+            return;
+        };
+
+        let path = path.join(filename);
+        match save_subtitles(&items, &style, path) {
+            Err(e) => toast::async_toast_warn(ui_weak, format!("Export subtitle failed: {e}")),
+            _ => toast::async_toast_success(ui_weak, "Export subtitle successfully".to_string()),
+        }
+    });
+}
@@ -0,0 +1,172 @@
+//! System tray icon, so the recording continues with the main window closed.
+//!
+//! The icon is a StatusNotifierItem on Linux and a notification area icon on
+//! Windows. It turns into the recording indicator while recording.
+
+#[cfg(target_os = "linux")]
+mod sni;
+
+#[cfg(target_os = "windows")]
+mod win;
+
+use crate::{
+    config, global_logic, global_store, global_util,
+    logic::tr::tr,
+    slint_generatedAppWindow::{
+        AppWindow, HistoryEntry as UIHistoryEntry, RecordStatus as UIRecordStatus,
+    },
+    store_history_entries, toast_warn,
+};
+use slint::{ComponentHandle, Model, Timer, TimerMode, VecModel};
+use std::{fs, path::PathBuf, time::Duration};
+use tokio::sync::{
+    mpsc::{UnboundedSender, unbounded_channel},
+    watch,
+};
+
+// How often the record status is checked to update the icon
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+thread_local! {
+    static STATUS_TIMER: Timer = Timer::default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+    ShowWindow,
+    StopRecording,
+    OpenLastRecording,
+    Quit,
+}
+
+impl TrayAction {
+    // In the order of the menu
+    pub const ALL: [TrayAction; 4] = [
+        TrayAction::ShowWindow,
+        TrayAction::StopRecording,
+        TrayAction::OpenLastRecording,
+        TrayAction::Quit,
+    ];
+
+    // Ids of the menu items, 0 is the root of the menu
+    pub fn id(&self) -> i32 {
+        match self {
+            TrayAction::ShowWindow => 1,
+            TrayAction::StopRecording => 2,
+            TrayAction::OpenLastRecording => 3,
+            TrayAction::Quit => 4,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrayAction::ShowWindow => "Show window",
+            TrayAction::StopRecording => "Stop recording",
+            TrayAction::OpenLastRecording => "Open last recording",
+            TrayAction::Quit => "Quit",
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        TrayAction::ALL.into_iter().find(|action| action.id() == id)
+    }
+}
+
+pub fn init(ui: &AppWindow) {
+    if !config::all().tray.enable {
+        return;
+    }
+
+    let (sender, mut receiver) = unbounded_channel();
+    let (status_sender, status_receiver) = watch::channel(false);
+    listen(sender, status_receiver);
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        while let Some(action) = receiver.recv().await {
+            log::info!("tray action: {action:?}");
+            _ = ui_weak.upgrade_in_event_loop(move |ui| trigger(&ui, action));
+        }
+
+        log::info!("tray receiver exit...");
+    });
+
+    let ui_weak = ui.as_weak();
+    STATUS_TIMER.with(|timer| {
+        timer.start(TimerMode::Repeated, STATUS_CHECK_INTERVAL, move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+
+            let recording = is_recording(&ui);
+            status_sender.send_if_modified(|status| {
+                let modified = *status != recording;
+                *status = recording;
+                modified
+            });
+        });
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn listen(sender: UnboundedSender<TrayAction>, status_receiver: watch::Receiver<bool>) {
+    tokio::spawn(async move {
+        if let Err(e) = sni::run(sender, status_receiver).await {
+            log::warn!("show tray icon failed: {e}");
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+fn listen(sender: UnboundedSender<TrayAction>, status_receiver: watch::Receiver<bool>) {
+    win::listen(sender, status_receiver);
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn listen(_sender: UnboundedSender<TrayAction>, _status_receiver: watch::Receiver<bool>) {
+    log::warn!("tray icon is not supported on this platform");
+}
+
+// The recording is already started while counting down
+fn is_recording(ui: &AppWindow) -> bool {
+    global_store!(ui).get_record_status() == UIRecordStatus::Recording
+        || global_store!(ui).get_recording_countdown() > 0
+}
+
+fn trigger(ui: &AppWindow, action: TrayAction) {
+    match action {
+        TrayAction::ShowWindow => {
+            _ = ui.show();
+            ui.window().set_minimized(false);
+        }
+        TrayAction::StopRecording => {
+            if is_recording(ui) {
+                global_logic!(ui).invoke_stop_recording();
+            }
+        }
+        TrayAction::OpenLastRecording => match last_recording(ui) {
+            Some(file) => global_logic!(ui).invoke_open_file(file.display().to_string().into()),
+            None => {
+                _ = ui.show();
+                toast_warn!(ui, tr("No recording found"));
+            }
+        },
+        TrayAction::Quit => global_util!(ui).invoke_close_window(),
+    }
+}
+
+// The histories may be sorted in either order, so the latest one is found by
+// the modified time
+fn last_recording(ui: &AppWindow) -> Option<PathBuf> {
+    let final_video_path = global_store!(ui).get_final_video_path();
+    if !final_video_path.is_empty() {
+        return Some(PathBuf::from(final_video_path.as_str()));
+    }
+
+    let save_dir = PathBuf::from(&config::all().recorder.save_dir);
+    store_history_entries!(ui)
+        .iter()
+        .filter(|entry| entry.status.is_empty())
+        .map(|entry| save_dir.join(entry.file.as_str()))
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
@@ -0,0 +1,298 @@
+//! StatusNotifierItem and its dbusmenu, which the panels of KDE, GNOME (with
+//! the AppIndicator extension) and the wlroots bars show in the tray.
+
+use super::TrayAction;
+use crate::logic::tr::tr;
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use zbus::{
+    connection, interface,
+    object_server::SignalEmitter,
+    zvariant::{OwnedObjectPath, OwnedValue, Type, Value},
+};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+const IDLE_ICON: &str = "wayshot";
+const RECORDING_ICON: &str = "media-record";
+
+struct StatusNotifierItem {
+    is_recording: bool,
+    sender: UnboundedSender<TrayAction>,
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    fn activate(&self, _x: i32, _y: i32) {
+        _ = self.sender.send(TrayAction::ShowWindow);
+    }
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {
+        _ = self.sender.send(TrayAction::ShowWindow);
+    }
+
+    #[zbus(property)]
+    fn category(&self) -> String {
+        "ApplicationStatus".to_string()
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        "wayshot".to_string()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        "Wayshot".to_string()
+    }
+
+    // The hosts show the attention icon when it's `NeedsAttention`
+    #[zbus(property)]
+    fn status(&self) -> String {
+        if self.is_recording {
+            "NeedsAttention".to_string()
+        } else {
+            "Active".to_string()
+        }
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        if self.is_recording {
+            RECORDING_ICON.to_string()
+        } else {
+            IDLE_ICON.to_string()
+        }
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> String {
+        RECORDING_ICON.to_string()
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> (String, Vec<(i32, i32, Vec<u8>)>, String, String) {
+        let text = if self.is_recording {
+            tr("Recording")
+        } else {
+            "Wayshot".to_string()
+        };
+
+        (String::default(), vec![], text, String::default())
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).expect("valid object path")
+    }
+
+    #[zbus(signal)]
+    async fn new_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_attention_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+// Layout of a menu item in `(ia{sv}av)`
+#[derive(Debug, Serialize, Type, Value)]
+struct MenuLayout {
+    id: i32,
+    properties: HashMap<String, OwnedValue>,
+    children: Vec<OwnedValue>,
+}
+
+struct DBusMenu {
+    revision: u32,
+    is_recording: bool,
+    sender: UnboundedSender<TrayAction>,
+}
+
+impl DBusMenu {
+    fn item_properties(&self, action: TrayAction) -> HashMap<String, OwnedValue> {
+        let enabled = match action {
+            TrayAction::StopRecording => self.is_recording,
+            _ => true,
+        };
+
+        HashMap::from([
+            ("label".to_string(), owned_value(tr(action.label()))),
+            ("enabled".to_string(), owned_value(enabled)),
+        ])
+    }
+
+    fn layout(&self) -> MenuLayout {
+        let children = TrayAction::ALL
+            .into_iter()
+            .map(|action| MenuLayout {
+                id: action.id(),
+                properties: self.item_properties(action),
+                children: vec![],
+            })
+            .map(owned_value)
+            .collect();
+
+        MenuLayout {
+            id: 0,
+            properties: HashMap::from([("children-display".to_string(), owned_value("submenu"))]),
+            children,
+        }
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DBusMenu {
+    // The menu is flat, so the whole layout is returned for any parent
+    fn get_layout(
+        &self,
+        _parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> (u32, MenuLayout) {
+        (self.revision, self.layout())
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        ids.into_iter()
+            .filter_map(TrayAction::from_id)
+            .map(|action| (action.id(), self.item_properties(action)))
+            .collect()
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: Value<'_>, _timestamp: u32) {
+        if event_id != "clicked" {
+            return;
+        }
+
+        if let Some(action) = TrayAction::from_id(id) {
+            _ = self.sender.send(action);
+        }
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        "ltr".to_string()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        "normal".to_string()
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(
+        emitter: &SignalEmitter<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+pub async fn run(
+    sender: UnboundedSender<TrayAction>,
+    mut status_receiver: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+
+    let connection = connection::Builder::session()?
+        .name(name.as_str())?
+        .serve_at(
+            ITEM_PATH,
+            StatusNotifierItem {
+                is_recording: false,
+                sender: sender.clone(),
+            },
+        )?
+        .serve_at(
+            MENU_PATH,
+            DBusMenu {
+                revision: 0,
+                is_recording: false,
+                sender,
+            },
+        )?
+        .build()
+        .await?;
+
+    connection
+        .call_method(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            Some("org.kde.StatusNotifierWatcher"),
+            "RegisterStatusNotifierItem",
+            &(name.as_str(),),
+        )
+        .await?;
+
+    log::info!("register tray icon: {name}");
+
+    while status_receiver.changed().await.is_ok() {
+        let is_recording = *status_receiver.borrow_and_update();
+
+        let item_ref = connection
+            .object_server()
+            .interface::<_, StatusNotifierItem>(ITEM_PATH)
+            .await?;
+        let status = {
+            let mut item = item_ref.get_mut().await;
+            item.is_recording = is_recording;
+            item.status()
+        };
+
+        let emitter = item_ref.signal_emitter();
+        StatusNotifierItem::new_status(emitter, &status).await?;
+        StatusNotifierItem::new_icon(emitter).await?;
+        StatusNotifierItem::new_tool_tip(emitter).await?;
+
+        let menu_ref = connection
+            .object_server()
+            .interface::<_, DBusMenu>(MENU_PATH)
+            .await?;
+        let revision = {
+            let mut menu = menu_ref.get_mut().await;
+            menu.is_recording = is_recording;
+            menu.revision += 1;
+            menu.revision
+        };
+
+        DBusMenu::layout_updated(menu_ref.signal_emitter(), revision, 0).await?;
+    }
+
+    Ok(())
+}
+
+fn owned_value<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+    value
+        .into()
+        .try_to_owned()
+        .expect("no file descriptors in the menu")
+}
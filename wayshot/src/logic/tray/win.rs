@@ -0,0 +1,303 @@
+use super::TrayAction;
+use crate::logic::tr::tr;
+use std::{cell::RefCell, mem, ptr, thread};
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use winapi::{
+    shared::{
+        minwindef::{LPARAM, LRESULT, UINT, WPARAM},
+        windef::{HICON, HWND, POINT},
+    },
+    um::{
+        libloaderapi::GetModuleHandleW,
+        shellapi::{
+            NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+            Shell_NotifyIconW,
+        },
+        winuser::{
+            AppendMenuW, CreateIcon, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu,
+            DispatchMessageW, GetCursorPos, GetMessageW, HWND_MESSAGE, IDI_APPLICATION, LoadIconW,
+            MF_GRAYED, MF_STRING, MSG, PostMessageW, RegisterClassW, SetForegroundWindow,
+            TPM_NONOTIFY, TPM_RETURNCMD, TrackPopupMenu, TranslateMessage, WM_APP, WM_CONTEXTMENU,
+            WM_LBUTTONUP, WM_RBUTTONUP, WNDCLASSW,
+        },
+    },
+};
+
+const WINDOW_CLASS: &str = "WayshotTray";
+const APP_ICON: &str = "IDI_ICON1";
+
+// Sent by the notification area on the mouse events of the icon
+const WM_TRAY_ICON: UINT = WM_APP + 1;
+
+// Posted by the status task with `wparam` 1 for recording
+const WM_TRAY_STATUS: UINT = WM_APP + 2;
+
+const RECORDING_ICON_SIZE: i32 = 16;
+
+struct State {
+    sender: UnboundedSender<TrayAction>,
+    is_recording: bool,
+    idle_icon: HICON,
+    recording_icon: HICON,
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+// The window receiving the icon events is bound to the thread, so the icon
+// is added and its messages are dispatched on the same thread
+pub fn listen(sender: UnboundedSender<TrayAction>, mut status_receiver: watch::Receiver<bool>) {
+    let rt_handle = tokio::runtime::Handle::current();
+
+    thread::spawn(move || {
+        let hwnd = unsafe { create_window() };
+        if hwnd.is_null() {
+            log::warn!("create the window of the tray icon failed");
+            return;
+        }
+
+        let idle_icon = unsafe { load_app_icon() };
+        STATE.with_borrow_mut(|state| {
+            *state = Some(State {
+                sender,
+                is_recording: false,
+                idle_icon,
+                recording_icon: unsafe { create_recording_icon() },
+            })
+        });
+
+        let mut data = notify_icon_data(hwnd, idle_icon, "Wayshot");
+        if unsafe { Shell_NotifyIconW(NIM_ADD, &mut data) } == 0 {
+            log::warn!("add the tray icon failed");
+            return;
+        }
+
+        // HWND isn't `Send`, it's passed as an integer
+        let hwnd_value = hwnd as usize;
+        rt_handle.spawn(async move {
+            while status_receiver.changed().await.is_ok() {
+                let is_recording = *status_receiver.borrow_and_update();
+                let posted = unsafe {
+                    PostMessageW(
+                        hwnd_value as HWND,
+                        WM_TRAY_STATUS,
+                        is_recording as WPARAM,
+                        0,
+                    )
+                };
+
+                if posted == 0 {
+                    break;
+                }
+            }
+        });
+
+        let mut msg: MSG = unsafe { mem::zeroed() };
+        while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+            unsafe {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &mut data) };
+    });
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_TRAY_ICON => {
+            match lparam as UINT {
+                WM_LBUTTONUP => send(TrayAction::ShowWindow),
+                WM_RBUTTONUP | WM_CONTEXTMENU => unsafe { show_menu(hwnd) },
+                _ => (),
+            }
+            0
+        }
+        WM_TRAY_STATUS => {
+            update_status(hwnd, wparam != 0);
+            0
+        }
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}
+
+fn send(action: TrayAction) {
+    STATE.with_borrow(|state| {
+        if let Some(state) = state {
+            _ = state.sender.send(action);
+        }
+    });
+}
+
+fn update_status(hwnd: HWND, is_recording: bool) {
+    let icon = STATE.with_borrow_mut(|state| {
+        state.as_mut().map(|state| {
+            state.is_recording = is_recording;
+            if is_recording {
+                state.recording_icon
+            } else {
+                state.idle_icon
+            }
+        })
+    });
+
+    let Some(icon) = icon else {
+        return;
+    };
+
+    let tip = if is_recording {
+        tr("Recording")
+    } else {
+        "Wayshot".to_string()
+    };
+
+    let mut data = notify_icon_data(hwnd, icon, &tip);
+    unsafe { Shell_NotifyIconW(NIM_MODIFY, &mut data) };
+}
+
+unsafe fn show_menu(hwnd: HWND) {
+    let is_recording = STATE.with_borrow(|state| state.as_ref().is_some_and(|s| s.is_recording));
+
+    unsafe {
+        let menu = CreatePopupMenu();
+        if menu.is_null() {
+            return;
+        }
+
+        for action in TrayAction::ALL {
+            let mut flags = MF_STRING;
+            if action == TrayAction::StopRecording && !is_recording {
+                flags |= MF_GRAYED;
+            }
+
+            let label = wide(&tr(action.label()));
+            AppendMenuW(menu, flags, action.id() as usize, label.as_ptr());
+        }
+
+        let mut pos = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut pos);
+
+        // The menu isn't closed by clicking outside without it
+        SetForegroundWindow(hwnd);
+
+        let id = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_NONOTIFY,
+            pos.x,
+            pos.y,
+            0,
+            hwnd,
+            ptr::null(),
+        );
+        DestroyMenu(menu);
+
+        if let Some(action) = TrayAction::from_id(id) {
+            send(action);
+        }
+    }
+}
+
+unsafe fn create_window() -> HWND {
+    let class_name = wide(WINDOW_CLASS);
+
+    unsafe {
+        let hinstance = GetModuleHandleW(ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: hinstance,
+            lpszClassName: class_name.as_ptr(),
+            ..mem::zeroed()
+        };
+        RegisterClassW(&class);
+
+        CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            ptr::null_mut(),
+            hinstance,
+            ptr::null_mut(),
+        )
+    }
+}
+
+// The icon embedded by `windows/icon.rc`
+unsafe fn load_app_icon() -> HICON {
+    let name = wide(APP_ICON);
+
+    unsafe {
+        let icon = LoadIconW(GetModuleHandleW(ptr::null()), name.as_ptr());
+        if icon.is_null() {
+            LoadIconW(ptr::null_mut(), IDI_APPLICATION)
+        } else {
+            icon
+        }
+    }
+}
+
+// A red dot in 32-bit BGRA
+unsafe fn create_recording_icon() -> HICON {
+    let size = RECORDING_ICON_SIZE;
+    let radius = size as f32 / 2.0 - 1.0;
+    let center = size as f32 / 2.0 - 0.5;
+
+    let mut color = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (dx, dy) = (x as f32 - center, y as f32 - center);
+            if dx * dx + dy * dy <= radius * radius {
+                color.extend_from_slice(&[0x30, 0x30, 0xe0, 0xff]);
+            } else {
+                color.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+
+    // The alpha channel takes effect with the mask of all zero
+    let mask = vec![0u8; (size * size / 8) as usize];
+
+    unsafe {
+        CreateIcon(
+            GetModuleHandleW(ptr::null()),
+            size,
+            size,
+            1,
+            32,
+            mask.as_ptr(),
+            color.as_ptr(),
+        )
+    }
+}
+
+fn notify_icon_data(hwnd: HWND, icon: HICON, tip: &str) -> NOTIFYICONDATAW {
+    let mut data: NOTIFYICONDATAW = unsafe { mem::zeroed() };
+    data.cbSize = mem::size_of::<NOTIFYICONDATAW>() as u32;
+    data.hWnd = hwnd;
+    data.uID = 1;
+    data.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+    data.uCallbackMessage = WM_TRAY_ICON;
+    data.hIcon = icon;
+
+    let tip = wide(tip);
+    let len = tip.len().min(data.szTip.len() - 1);
+    data.szTip[..len].copy_from_slice(&tip[..len]);
+
+    data
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
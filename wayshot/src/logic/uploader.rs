@@ -0,0 +1,318 @@
+//! Upload the recordings to a plain HTTP PUT, WebDAV or S3-compatible server.
+//!
+//! The recordings are queued and uploaded one by one, the share URLs are saved
+//! in the histories. The plain HTTP PUT backend is resumable, so an upload
+//! broken by the network continues from the received bytes when it's retried.
+
+mod s3;
+
+use crate::{
+    config::{self, Upload, UploadBackend},
+    db::{HISTORY_TABLE as DB_TABLE, HistoryEntry},
+    logic::{toast, tr::tr},
+    logic_cb,
+    slint_generatedAppWindow::{
+        AppWindow, DownloaderState as UIDownloaderState, HistoryEntry as UIHistoryEntry,
+    },
+    store_history_entries, toast_info, toast_success, toast_warn,
+};
+use anyhow::{Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use cutil::http::{HeaderMap, HeaderValue, UploadState, Uploader, header::AUTHORIZATION};
+use once_cell::sync::Lazy;
+use slint::{ComponentHandle, Model, VecModel, Weak};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+// Log the progress every 10 percent
+const PROGRESS_LOG_STEP: u64 = 10;
+
+static QUEUE: Lazy<Mutex<Option<UnboundedSender<Task>>>> = Lazy::new(|| Mutex::new(None));
+
+// Ids of the histories waiting or being uploaded, so a recording isn't queued
+// twice
+static PENDING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Clone)]
+struct Task {
+    id: String,
+    path: PathBuf,
+}
+
+crate::db_update!(DB_TABLE, HistoryEntry);
+
+pub fn init(ui: &AppWindow) {
+    let (sender, mut receiver) = unbounded_channel::<Task>();
+    *QUEUE.lock().unwrap() = Some(sender);
+
+    let ui_weak = ui.as_weak();
+    tokio::spawn(async move {
+        while let Some(task) = receiver.recv().await {
+            let setting = config::all().upload;
+            async_update_upload_state(&ui_weak, &task.id, UIDownloaderState::Downloading, 0.0);
+
+            let (ui_weak_progress, id) = (ui_weak.clone(), task.id.clone());
+            let result = upload(&setting, &task.path, move |progress| {
+                async_update_upload_state(
+                    &ui_weak_progress,
+                    &id,
+                    UIDownloaderState::Downloading,
+                    progress,
+                );
+            })
+            .await;
+            PENDING.lock().unwrap().remove(&task.id);
+
+            match result {
+                Ok(Some(share_url)) => {
+                    log::info!("upload {} to {share_url}", task.path.display());
+                    async_update_upload_state(&ui_weak, &task.id, UIDownloaderState::Finished, 1.0);
+
+                    _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                        update_share_url(&ui, &task.id, share_url);
+                    });
+                }
+                Ok(None) => {
+                    log::info!("upload {} is cancelled", task.path.display());
+                    async_update_upload_state(
+                        &ui_weak,
+                        &task.id,
+                        UIDownloaderState::Cancelled,
+                        -1.0,
+                    );
+                }
+                Err(e) => {
+                    async_update_upload_state(&ui_weak, &task.id, UIDownloaderState::Failed, -1.0);
+                    toast::async_toast_warn(
+                        ui_weak.clone(),
+                        format!("{}. {}: {e}", tr("Upload failed"), tr("Reason")),
+                    );
+                }
+            }
+        }
+
+        log::info!("uploader exit...");
+    });
+
+    logic_cb!(upload_history, ui, index);
+}
+
+// Queue the recording of the history, it returns `false` if the recording is
+// already queued or the uploader isn't started
+pub fn enqueue(id: impl Into<String>, path: impl Into<PathBuf>) -> bool {
+    let task = Task {
+        id: id.into(),
+        path: path.into(),
+    };
+
+    if !PENDING.lock().unwrap().insert(task.id.clone()) {
+        return false;
+    }
+
+    let id = task.id.clone();
+    match QUEUE.lock().unwrap().as_ref() {
+        Some(sender) if sender.send(task).is_ok() => true,
+        _ => {
+            PENDING.lock().unwrap().remove(&id);
+            false
+        }
+    }
+}
+
+fn upload_history(ui: &AppWindow, index: i32) {
+    let Some(entry) = store_history_entries!(ui).row_data(index as usize) else {
+        return;
+    };
+
+    if config::all().upload.url.trim().is_empty() {
+        toast_warn!(ui, tr("Please set the upload url first"));
+        return;
+    }
+
+    let path = PathBuf::from(&config::all().recorder.save_dir).join(&entry.file);
+    if enqueue(entry.id.as_str(), path) {
+        toast_info!(ui, tr("Added to the upload queue"));
+    } else {
+        toast_warn!(ui, tr("The recording is already uploading"));
+    }
+}
+
+// A negative progress keeps the current one, so a broken upload shows where it
+// stopped
+fn async_update_upload_state(
+    ui_weak: &Weak<AppWindow>,
+    id: &str,
+    state: UIDownloaderState,
+    progress: f32,
+) {
+    let id = id.to_string();
+    _ = ui_weak.upgrade_in_event_loop(move |ui| {
+        let Some((index, mut entry)) = store_history_entries!(ui)
+            .iter()
+            .enumerate()
+            .find(|(_, entry)| entry.id == id)
+        else {
+            return;
+        };
+
+        entry.upload_state = state;
+        if progress >= 0.0 {
+            entry.upload_progress = progress;
+        }
+        store_history_entries!(ui).set_row_data(index, entry);
+    });
+}
+
+fn update_share_url(ui: &AppWindow, id: &str, share_url: String) {
+    let Some((index, mut entry)) = store_history_entries!(ui)
+        .iter()
+        .enumerate()
+        .find(|(_, entry)| entry.id == id)
+    else {
+        return;
+    };
+
+    entry.share_url = share_url.into();
+    store_history_entries!(ui).set_row_data(index, entry.clone());
+    db_update(ui.as_weak(), entry.into());
+
+    toast_success!(ui, tr("Upload successfully"));
+}
+
+// Returns the share URL, or `None` if the upload is cancelled. The progress is
+// reported in 0.0 ~ 1.0 every percent
+async fn upload(
+    setting: &Upload,
+    path: &Path,
+    mut progress_cb: impl FnMut(f32) + Send + 'static,
+) -> Result<Option<String>> {
+    if setting.url.trim().is_empty() {
+        bail!("upload url is empty");
+    }
+
+    let Some(file_name) = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+    else {
+        bail!("invalid file path: {}", path.display());
+    };
+
+    if !path.exists() {
+        bail!("no found {}", path.display());
+    }
+
+    let (mut progress, mut logged_progress) = (0, 0);
+    let progress_cb = move |sent: u64, total: u64| {
+        let percent = sent * 100 / total.max(1);
+        if percent > progress {
+            progress = percent;
+            progress_cb(percent as f32 / 100.0);
+        }
+
+        if percent >= logged_progress + PROGRESS_LOG_STEP
+            || (sent == total && logged_progress < 100)
+        {
+            logged_progress = percent;
+            log::info!("uploading {percent}%");
+        }
+    };
+
+    let (url, state) = match setting.backend {
+        UploadBackend::HttpPut => {
+            let url = join_url(&setting.url, &file_name);
+            let mut headers = HeaderMap::new();
+            if !setting.token.is_empty() {
+                headers.insert(
+                    AUTHORIZATION,
+                    HeaderValue::from_str(&format!("Bearer {}", setting.token))?,
+                );
+            }
+
+            let uploader = Uploader::new(&url, path).with_headers(headers);
+            (url, uploader.resumable_put(progress_cb).await?)
+        }
+        UploadBackend::WebDav => {
+            let url = join_url(&setting.url, &file_name);
+            let mut headers = HeaderMap::new();
+            if !setting.username.is_empty() {
+                let credential =
+                    BASE64.encode(format!("{}:{}", setting.username, setting.password));
+                headers.insert(
+                    AUTHORIZATION,
+                    HeaderValue::from_str(&format!("Basic {credential}"))?,
+                );
+            }
+
+            let uploader = Uploader::new(&url, path).with_headers(headers);
+            (url, uploader.put(progress_cb).await?)
+        }
+        UploadBackend::S3 => {
+            if setting.bucket.is_empty() {
+                bail!("S3 bucket is empty");
+            }
+
+            let url = s3::object_url(&setting.url, &setting.bucket, &file_name);
+            let headers = s3::sign_put(
+                &url,
+                &s3::Credential {
+                    access_key: &setting.username,
+                    secret_key: &setting.password,
+                    region: &setting.region,
+                },
+            )?;
+
+            let uploader = Uploader::new(&url, path).with_headers(headers);
+            (url, uploader.put(progress_cb).await?)
+        }
+    };
+
+    let UploadState::Finished(body) = state else {
+        return Ok(None);
+    };
+
+    // The plain HTTP PUT server may respond the share URL
+    let body = body.trim();
+    if setting.backend == UploadBackend::HttpPut && body.starts_with("http") {
+        return Ok(Some(body.to_string()));
+    }
+
+    if setting.public_url.trim().is_empty() {
+        Ok(Some(url))
+    } else {
+        Ok(Some(join_url(&setting.public_url, &file_name)))
+    }
+}
+
+// The file name is one encoded path segment
+fn join_url(base: &str, file_name: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim().trim_end_matches('/'),
+        s3::uri_encode(file_name, true)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_url() {
+        assert_eq!(
+            join_url(" https://example.com/upload/ ", "wayshot-1.mp4"),
+            "https://example.com/upload/wayshot-1.mp4"
+        );
+        assert_eq!(
+            join_url("https://example.com/dav", "my clip #1?.mp4"),
+            "https://example.com/dav/my%20clip%20%231%3F.mp4"
+        );
+        assert_eq!(
+            join_url("https://example.com/dav", "录屏.mp4"),
+            "https://example.com/dav/%E5%BD%95%E5%B1%8F.mp4"
+        );
+    }
+}